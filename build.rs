@@ -54,8 +54,9 @@ fn process_builtin_themes() {
     }
     fs::create_dir_all(&staged_dir).expect("Failed to create staged_themes");
 
-    // Discover and process all themes (BTreeMap for sorted iteration)
-    let mut themes: BTreeMap<String, ()> = BTreeMap::new();
+    // Discover and process all themes (BTreeMap for sorted iteration), noting
+    // which are Vite themes
+    let mut themes: BTreeMap<String, bool> = BTreeMap::new();
 
     for entry in fs::read_dir(themes_dir).expect("Failed to read themes directory") {
         let entry = entry.expect("Failed to read theme entry");
@@ -101,7 +102,7 @@ fn process_builtin_themes() {
         let dest_dir = staged_dir.join(&theme_name);
         stage_theme_directory(&source_dir, &dest_dir);
 
-        themes.insert(theme_name, ());
+        themes.insert(theme_name, is_vite);
     }
 
     // Generate the builtin_themes.rs code
@@ -160,7 +161,7 @@ fn stage_theme_directory(source: &Path, dest: &Path) {
 }
 
 /// Generate the builtin_themes.rs source code.
-fn generate_builtin_themes_code(themes: &BTreeMap<String, ()>, out_dir: &str) {
+fn generate_builtin_themes_code(themes: &BTreeMap<String, bool>, out_dir: &str) {
     let out_path = Path::new(out_dir).join("builtin_themes.rs");
     let mut file = File::create(&out_path).expect("Failed to create builtin_themes.rs");
 
@@ -187,6 +188,25 @@ fn generate_builtin_themes_code(themes: &BTreeMap<String, ()>, out_dir: &str) {
 
     writeln!(file).unwrap();
 
+    // Generate the list of names
+    writeln!(file, "/// Names of all built-in themes.").unwrap();
+    let names: Vec<String> = themes.keys().map(|n| format!("\"{}\"", n)).collect();
+    writeln!(file, "pub const NAMES: &[&str] = &[{}];", names.join(", ")).unwrap();
+    writeln!(file).unwrap();
+    writeln!(file, "/// Names of built-in themes built with Vite.").unwrap();
+    let vite: Vec<String> = themes
+        .iter()
+        .filter(|&(_, &is_vite)| is_vite)
+        .map(|(n, _)| format!("\"{}\"", n))
+        .collect();
+    writeln!(
+        file,
+        "pub const VITE_NAMES: &[&str] = &[{}];",
+        vite.join(", ")
+    )
+    .unwrap();
+    writeln!(file).unwrap();
+
     // Generate the lookup function
     writeln!(
         file,
@@ -224,6 +244,12 @@ fn generate_empty_builtin_themes() {
     writeln!(file).unwrap();
     writeln!(file, "use include_dir::Dir;").unwrap();
     writeln!(file).unwrap();
+    writeln!(file, "/// Names of all built-in themes.").unwrap();
+    writeln!(file, "pub const NAMES: &[&str] = &[];").unwrap();
+    writeln!(file).unwrap();
+    writeln!(file, "/// Names of built-in themes built with Vite.").unwrap();
+    writeln!(file, "pub const VITE_NAMES: &[&str] = &[];").unwrap();
+    writeln!(file).unwrap();
     writeln!(
         file,
         "/// Look up a built-in theme by name.\n///\n/// Returns the theme directory if found."
@@ -0,0 +1,273 @@
+//! Static ActivityPub actor and outbox.
+//!
+//! Fediverse software finds an account through WebFinger, fetches the actor,
+//! and reads its outbox. All three can be plain JSON files, so a static gallery
+//! can be looked up as `@photos@example.com` and its newest photos browsed from
+//! a fediverse client. Following needs an inbox that accepts deliveries, which
+//! a static host can't provide; the actor names one so a server can be put
+//! behind it later.
+
+use serde::Serialize;
+
+use crate::config::ActivityPubConfig;
+use crate::feed::{self, Item};
+use crate::util;
+
+/// Path of the WebFinger document, relative to the output directory.
+pub const WEBFINGER_PATH: &str = ".well-known/webfinger";
+
+/// Filename of the actor document.
+pub const ACTOR_FILE_NAME: &str = "actor.json";
+
+/// Filename of the outbox collection.
+pub const OUTBOX_FILE_NAME: &str = "outbox.json";
+
+const CONTEXT: &str = "https://www.w3.org/ns/activitystreams";
+const PUBLIC: &str = "https://www.w3.org/ns/activitystreams#Public";
+
+/// URLs of the published documents.
+#[derive(Debug)]
+pub struct Urls {
+    pub actor: String,
+    pub inbox: String,
+    pub outbox: String,
+    /// The gallery's home page
+    pub home: String,
+}
+
+impl Urls {
+    /// URLs for a site served from `base_url` (e.g., `https://example.com`).
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            actor: format!("{}/{}", base_url, ACTOR_FILE_NAME),
+            inbox: format!("{}/inbox", base_url),
+            outbox: format!("{}/{}", base_url, OUTBOX_FILE_NAME),
+            home: format!("{}/", base_url),
+        }
+    }
+}
+
+/// WebFinger response for `acct:{username}@{domain}`.
+#[derive(Debug, Serialize)]
+pub struct WebFinger {
+    subject: String,
+    aliases: Vec<String>,
+    links: Vec<WebFingerLink>,
+}
+
+#[derive(Debug, Serialize)]
+struct WebFingerLink {
+    rel: &'static str,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    href: String,
+}
+
+/// The gallery's actor.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Actor<'a> {
+    #[serde(rename = "@context")]
+    context: &'static str,
+    id: &'a str,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    preferred_username: &'a str,
+    name: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    summary: Option<&'a str>,
+    url: &'a str,
+    inbox: &'a str,
+    outbox: &'a str,
+    discoverable: bool,
+}
+
+/// The outbox: a `Create` activity for each of the newest photos.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Outbox<'a> {
+    #[serde(rename = "@context")]
+    context: &'static str,
+    id: &'a str,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    total_items: usize,
+    ordered_items: Vec<Activity<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+struct Activity<'a> {
+    id: String,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    actor: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    published: Option<String>,
+    to: [&'static str; 1],
+    object: Note<'a>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Note<'a> {
+    id: String,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    attributed_to: &'a str,
+    name: &'a str,
+    content: String,
+    url: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    published: Option<String>,
+    to: [&'static str; 1],
+    attachment: [Image<'a>; 1],
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Image<'a> {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    media_type: &'static str,
+    url: &'a str,
+    name: &'a str,
+}
+
+/// Build the WebFinger document pointing `acct:{username}@{domain}` at the actor.
+pub fn webfinger(config: &ActivityPubConfig, domain: &str, urls: &Urls) -> WebFinger {
+    WebFinger {
+        subject: format!("acct:{}@{}", config.username, domain),
+        aliases: vec![urls.actor.clone()],
+        links: vec![
+            WebFingerLink {
+                rel: "self",
+                kind: "application/activity+json",
+                href: urls.actor.clone(),
+            },
+            WebFingerLink {
+                rel: "http://webfinger.net/rel/profile-page",
+                kind: "text/html",
+                href: urls.home.clone(),
+            },
+        ],
+    }
+}
+
+/// Build the actor document.
+pub fn actor<'a>(config: &'a ActivityPubConfig, title: &'a str, urls: &'a Urls) -> Actor<'a> {
+    Actor {
+        context: CONTEXT,
+        id: &urls.actor,
+        kind: "Person",
+        preferred_username: &config.username,
+        name: config.name.as_deref().unwrap_or(title),
+        summary: config.summary.as_deref(),
+        url: &urls.home,
+        inbox: &urls.inbox,
+        outbox: &urls.outbox,
+        discoverable: true,
+    }
+}
+
+/// Build the outbox with one post per item, newest first.
+pub fn outbox<'a>(urls: &'a Urls, items: &'a [Item]) -> Outbox<'a> {
+    let ordered_items = items
+        .iter()
+        .map(|item| {
+            // Images are content-hashed, so their URLs identify the photo
+            let note_id = format!("{}#note", item.image);
+            let published = item.published.map(util::rfc3339);
+
+            Activity {
+                id: format!("{}#create", item.image),
+                kind: "Create",
+                actor: &urls.actor,
+                published: published.clone(),
+                to: [PUBLIC],
+                object: Note {
+                    id: note_id,
+                    kind: "Note",
+                    attributed_to: &urls.actor,
+                    name: &item.title,
+                    content: feed::item_html(item),
+                    url: &item.link,
+                    published,
+                    to: [PUBLIC],
+                    attachment: [Image {
+                        kind: "Image",
+                        media_type: "image/webp",
+                        url: &item.image,
+                        name: item.description.as_deref().unwrap_or(&item.title),
+                    }],
+                },
+            }
+        })
+        .collect::<Vec<_>>();
+
+    Outbox {
+        context: CONTEXT,
+        id: &urls.outbox,
+        kind: "OrderedCollection",
+        total_items: ordered_items.len(),
+        ordered_items,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> ActivityPubConfig {
+        ActivityPubConfig {
+            username: "photos".to_string(),
+            name: None,
+            summary: Some("Holiday snaps".to_string()),
+        }
+    }
+
+    #[test]
+    fn webfinger_points_at_actor() {
+        let urls = Urls::new("https://example.com");
+        let value = serde_json::to_value(webfinger(&config(), "example.com", &urls)).unwrap();
+
+        assert_eq!(value["subject"], "acct:photos@example.com");
+        assert_eq!(value["links"][0]["type"], "application/activity+json");
+        assert_eq!(value["links"][0]["href"], "https://example.com/actor.json");
+    }
+
+    #[test]
+    fn actor_defaults_name_to_site_title() {
+        let config = config();
+        let urls = Urls::new("https://example.com");
+        let value = serde_json::to_value(actor(&config, "My Photos", &urls)).unwrap();
+
+        assert_eq!(value["@context"], CONTEXT);
+        assert_eq!(value["preferredUsername"], "photos");
+        assert_eq!(value["name"], "My Photos");
+        assert_eq!(value["summary"], "Holiday snaps");
+        assert_eq!(value["outbox"], "https://example.com/outbox.json");
+    }
+
+    #[test]
+    fn outbox_wraps_items_in_create_activities() {
+        let urls = Urls::new("https://example.com");
+        let items = [Item {
+            title: "beach".to_string(),
+            link: "https://example.com/beach.html".to_string(),
+            image: "https://example.com/images/beach-abc12345-full.webp".to_string(),
+            description: Some("Sand".to_string()),
+            tags: Vec::new(),
+            published: Some(0),
+        }];
+
+        let value = serde_json::to_value(outbox(&urls, &items)).unwrap();
+        let activity = &value["orderedItems"][0];
+
+        assert_eq!(value["totalItems"], 1);
+        assert_eq!(activity["type"], "Create");
+        assert_eq!(activity["published"], "1970-01-01T00:00:00Z");
+        assert_eq!(activity["object"]["type"], "Note");
+        assert_eq!(activity["object"]["attachment"][0]["name"], "Sand");
+        assert_eq!(activity["object"]["attributedTo"], urls.actor);
+    }
+}
@@ -0,0 +1,290 @@
+//! Settings for a single album, from an `album.toml` in its directory.
+//!
+//! An album is published at its directory's path by default, so a directory
+//! named `2024-05 Japan (edited)` puts dates, spaces, and parentheses in its
+//! URL. `path` replaces the directory's part of the URL; nested albums follow
+//! their parent's. The directory path, and any `aliases`, redirect to the
+//! album's pages, so links to the old URLs keep working.
+
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::error::{Error, Result};
+use crate::photos::Album;
+
+/// Name of the file in an album's directory.
+pub const FILE_NAME: &str = "album.toml";
+
+/// Top-level output directories no album or collection can take.
+pub const RESERVED: [&str; 3] = ["images", "static", "archive"];
+
+/// What an `album.toml` sets.
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct AlbumConfig {
+    /// Last part of the album's URL path, in place of its directory name
+    pub path: Option<String>,
+    /// Other last parts of the URL path that redirect to the album
+    #[serde(default)]
+    pub aliases: Vec<String>,
+}
+
+impl AlbumConfig {
+    /// The `album.toml` in `dir`, if it has one.
+    pub fn load(dir: &Path) -> Result<Self> {
+        let file = dir.join(FILE_NAME);
+        let content = match fs::read_to_string(&file) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e.into()),
+        };
+        let config: Self = toml::from_str(&content)
+            .map_err(|e| Error::Other(format!("invalid {}: {}", file.display(), e)))?;
+        for name in config.path.iter().chain(&config.aliases) {
+            validate(&file, name)?;
+        }
+        Ok(config)
+    }
+}
+
+/// Check that `name` works as one part of a URL path.
+fn validate(file: &Path, name: &str) -> Result<()> {
+    let problem = if name.is_empty() {
+        Some("is empty")
+    } else if name.starts_with('.') {
+        Some("starts with '.'")
+    } else if !name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    {
+        Some("may only contain letters, digits, '-', '_', and '.'")
+    } else {
+        None
+    };
+    match problem {
+        Some(problem) => Err(Error::Other(format!(
+            "{}: album path '{}' {}",
+            file.display(),
+            name,
+            problem
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Check that no two albums directly in `parent` are published at the same
+/// path or alias, and that top-level albums stay out of reserved directories.
+pub fn check_children(parent: &Album) -> Result<()> {
+    let mut taken: BTreeMap<&Path, &Path> = BTreeMap::new();
+    for child in &parent.children {
+        for path in std::iter::once(&child.path).chain(&child.aliases) {
+            if let Some(other) = taken.insert(path, &child.dir)
+                && other != child.dir
+            {
+                return Err(Error::Other(format!(
+                    "albums '{}' and '{}' both publish at /{}/; set a different path in one's {}",
+                    other.display(),
+                    child.dir.display(),
+                    path.display(),
+                    FILE_NAME
+                )));
+            }
+        }
+        if !parent.path.as_os_str().is_empty() {
+            continue;
+        }
+        // A directory keeping its own name is left to the pages that check it
+        let renamed = Some(&child.path).filter(|path| **path != child.dir);
+        for path in renamed.into_iter().chain(&child.aliases) {
+            if let Some(reserved) = reserved(path) {
+                return Err(Error::Other(format!(
+                    "album '{}' can't publish at /{}/, which holds the site's {}",
+                    child.dir.display(),
+                    reserved,
+                    reserved
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The reserved top-level directory `path` is, if it's one.
+fn reserved(path: &Path) -> Option<&'static str> {
+    RESERVED.into_iter().find(|r| path == Path::new(r))
+}
+
+/// Drop aliases that are another album's path, since its pages are there.
+pub fn drop_shadowed_aliases(root: &mut Album) {
+    fn collect(album: &Album, paths: &mut HashSet<PathBuf>) {
+        paths.insert(album.path.clone());
+        for child in &album.children {
+            collect(child, paths);
+        }
+    }
+    fn drop(album: &mut Album, paths: &HashSet<PathBuf>) {
+        let dir = &album.dir;
+        album.aliases.retain(|alias| {
+            let shadowed = paths.contains(alias);
+            if shadowed {
+                tracing::warn!(
+                    album = %dir.display(),
+                    alias = %alias.display(),
+                    "not redirecting an alias that's another album's path"
+                );
+            }
+            !shadowed
+        });
+        for child in &mut album.children {
+            drop(child, paths);
+        }
+    }
+
+    let mut paths = HashSet::new();
+    collect(root, &mut paths);
+    drop(root, &paths);
+}
+
+/// The URL path of an album in `dir`, directly in the album at `parent`,
+/// and the paths that redirect to it.
+pub fn paths(config: &AlbumConfig, parent: &Path, dir: &Path) -> (PathBuf, Vec<PathBuf>) {
+    let name = dir.file_name().map(PathBuf::from).unwrap_or_default();
+    let path = parent.join(config.path.as_deref().map(PathBuf::from).unwrap_or(name));
+    let mut aliases: Vec<PathBuf> = config
+        .aliases
+        .iter()
+        .map(|alias| parent.join(alias))
+        .collect();
+    // A renamed directory redirects, unless the site's own files are there
+    if path != dir && (!parent.as_os_str().is_empty() || reserved(dir).is_none()) {
+        aliases.push(dir.to_path_buf());
+    }
+    aliases.retain(|alias| *alias != path);
+    aliases.sort();
+    aliases.dedup();
+    (path, aliases)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case("japan-2024", true ; "slug")]
+    #[test_case("Japan_2024.v2", true ; "mixed")]
+    #[test_case("", false ; "empty")]
+    #[test_case("..", false ; "parent")]
+    #[test_case("japan/2024", false ; "nested")]
+    #[test_case("japan 2024", false ; "space")]
+    fn names_validated(name: &str, valid: bool) {
+        assert_eq!(validate(Path::new(FILE_NAME), name).is_ok(), valid);
+    }
+
+    #[test_case(None, &[], "trips", "trips/2024 Japan", "trips/2024 Japan", &[] ; "default")]
+    #[test_case(Some("japan"), &[], "", "2024 Japan", "japan", &["2024 Japan"] ; "top level")]
+    #[test_case(Some("japan"), &["nippon"], "trips", "travel/2024 Japan", "trips/japan", &["travel/2024 Japan", "trips/nippon"] ; "under a renamed parent")]
+    #[test_case(None, &[], "trips", "travel/kyoto", "trips/kyoto", &["travel/kyoto"] ; "parent renamed")]
+    #[test_case(Some("archives"), &[], "", "archive", "archives", &[] ; "out of a reserved directory")]
+    fn paths_resolved(
+        path: Option<&str>,
+        aliases: &[&str],
+        parent: &str,
+        dir: &str,
+        expected: &str,
+        expected_aliases: &[&str],
+    ) {
+        let config = AlbumConfig {
+            path: path.map(str::to_string),
+            aliases: aliases.iter().map(|alias| alias.to_string()).collect(),
+        };
+
+        let (path, aliases) = paths(&config, Path::new(parent), Path::new(dir));
+
+        assert_eq!(path, Path::new(expected));
+        assert_eq!(
+            aliases,
+            expected_aliases
+                .iter()
+                .map(PathBuf::from)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test_case("japan", &["nippon"], true ; "renamed")]
+    #[test_case("static", &[], false ; "reserved path")]
+    #[test_case("japan", &["archive"], false ; "reserved alias")]
+    fn top_level_reserved_checked(path: &str, aliases: &[&str], ok: bool) {
+        let mut root = Album::new("Gallery".to_string(), String::new(), PathBuf::new());
+        let mut child = Album::new(
+            "2024 Japan".to_string(),
+            "2024-japan".to_string(),
+            PathBuf::from("2024 Japan"),
+        );
+        child.path = PathBuf::from(path);
+        child.aliases = aliases.iter().map(PathBuf::from).collect();
+        root.children.push(child);
+
+        assert_eq!(check_children(&root).is_ok(), ok);
+    }
+
+    #[test]
+    fn album_config_loaded() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(
+            AlbumConfig::load(dir.path()).unwrap(),
+            AlbumConfig::default()
+        );
+
+        fs::write(
+            dir.path().join(FILE_NAME),
+            "path = \"japan-2024\"\naliases = [\"japan\"]\n",
+        )
+        .unwrap();
+        let config = AlbumConfig::load(dir.path()).unwrap();
+        assert_eq!(config.path.as_deref(), Some("japan-2024"));
+        assert_eq!(config.aliases, ["japan"]);
+
+        fs::write(dir.path().join(FILE_NAME), "path = \"Japan 2024\"\n").unwrap();
+        assert!(AlbumConfig::load(dir.path()).is_err());
+    }
+
+    #[test]
+    fn renamed_album_redirects() {
+        let site_dir = tempfile::tempdir().unwrap();
+        let site = crate::preview::generate(site_dir.path(), "basic").unwrap();
+        let photos_dir = site_dir.path().join("photos");
+        // An album with photos of its own
+        let dir = fs::read_dir(&photos_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .find(|path| {
+                path.is_dir()
+                    && fs::read_dir(path)
+                        .unwrap()
+                        .any(|e| e.unwrap().path().is_file())
+            })
+            .unwrap();
+        fs::write(dir.join(FILE_NAME), "path = \"renamed\"\n").unwrap();
+        let old = dir.strip_prefix(&photos_dir).unwrap().to_path_buf();
+
+        let mut pipeline =
+            crate::pipeline::Pipeline::load(site_dir.path().to_path_buf(), site, false).unwrap();
+        pipeline.build().unwrap();
+
+        // The basic theme has photo pages but no album pages
+        let output = site_dir.path().join("dist");
+        let photo = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .find(|path| path.is_file() && path.file_name().unwrap() != FILE_NAME)
+            .unwrap();
+        let page = format!("{}.html", photo.file_stem().unwrap().to_string_lossy());
+        assert!(output.join("renamed").join(&page).is_file());
+        assert!(!output.join(&old).join("index.html").exists());
+        let redirect = fs::read_to_string(output.join(&old).join(&page)).unwrap();
+        assert!(redirect.contains("/renamed/"));
+    }
+}
@@ -0,0 +1,128 @@
+//! Analytics script tags.
+//!
+//! Plausible, Umami, and GoatCounter each need a single script tag whose
+//! attributes identify the site. The tag is built from `[analytics]` in
+//! `site.toml` and inserted into every page's `<head>`, so no theme needs its
+//! own copy of the snippet. With Do Not Track respected, the tag is replaced
+//! by a small loader that only adds it when the visitor hasn't opted out.
+
+use std::fmt::Write as _;
+
+use quick_xml::escape::escape;
+
+use crate::config::{AnalyticsConfig, AnalyticsProvider};
+
+/// Attribute on every generated tag.
+///
+/// Pages that already contain it include the script themselves and aren't injected into.
+pub const MARKER: &str = "data-galerie-analytics";
+
+/// Build the script tag for a site served at `domain`.
+pub fn script(config: &AnalyticsConfig, domain: &str) -> String {
+    let (src, attributes) = match &config.provider {
+        AnalyticsProvider::Plausible { domain: site, src } => (
+            src.clone(),
+            vec![("data-domain", site.as_deref().unwrap_or(domain).to_string())],
+        ),
+        AnalyticsProvider::Umami { website_id, src } => {
+            (src.clone(), vec![("data-website-id", website_id.clone())])
+        }
+        AnalyticsProvider::GoatCounter { code } => (
+            "https://gc.zgo.at/count.js".to_string(),
+            vec![(
+                "data-goatcounter",
+                format!("https://{}.goatcounter.com/count", code),
+            )],
+        ),
+    };
+
+    if config.dnt {
+        loader(&src, &attributes)
+    } else {
+        tag(&src, &attributes)
+    }
+}
+
+/// A plain `<script>` tag.
+fn tag(src: &str, attributes: &[(&str, String)]) -> String {
+    let mut html = format!("<script {}", MARKER);
+    for (name, value) in attributes {
+        let _ = write!(html, " {}=\"{}\"", name, escape(value));
+    }
+    let _ = write!(html, " src=\"{}\" defer></script>", escape(src));
+    html
+}
+
+/// An inline script that adds the tag unless Do Not Track is enabled.
+fn loader(src: &str, attributes: &[(&str, String)]) -> String {
+    let mut js = String::from(
+        "if(navigator.doNotTrack!==\"1\"&&window.doNotTrack!==\"1\")\
+         {var s=document.createElement(\"script\");",
+    );
+    for (name, value) in attributes {
+        let _ = write!(
+            js,
+            "s.setAttribute({},{});",
+            js_string(name),
+            js_string(value)
+        );
+    }
+    let _ = write!(
+        js,
+        "s.src={};document.head.appendChild(s)}}",
+        js_string(src)
+    );
+    format!("<script {}>{}</script>", MARKER, js)
+}
+
+/// A JavaScript string literal that is safe inside a `<script>` element.
+fn js_string(value: &str) -> String {
+    serde_json::Value::from(value)
+        .to_string()
+        .replace('<', "\\u003c")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    fn config(provider: AnalyticsProvider, dnt: bool) -> AnalyticsConfig {
+        AnalyticsConfig { provider, dnt }
+    }
+
+    #[test_case(
+        AnalyticsProvider::Plausible { domain: None, src: "https://plausible.io/js/script.js".to_string() },
+        "<script data-galerie-analytics data-domain=\"example.com\" src=\"https://plausible.io/js/script.js\" defer></script>"
+        ; "plausible"
+    )]
+    #[test_case(
+        AnalyticsProvider::Umami { website_id: "abc-123".to_string(), src: "https://stats.example.com/script.js".to_string() },
+        "<script data-galerie-analytics data-website-id=\"abc-123\" src=\"https://stats.example.com/script.js\" defer></script>"
+        ; "umami"
+    )]
+    #[test_case(
+        AnalyticsProvider::GoatCounter { code: "photos".to_string() },
+        "<script data-galerie-analytics data-goatcounter=\"https://photos.goatcounter.com/count\" src=\"https://gc.zgo.at/count.js\" defer></script>"
+        ; "goatcounter"
+    )]
+    fn test_script_tag(provider: AnalyticsProvider, expected: &str) {
+        assert_eq!(script(&config(provider, false), "example.com"), expected);
+    }
+
+    #[test]
+    fn dnt_uses_loader() {
+        let provider = AnalyticsProvider::Plausible {
+            domain: Some("</script>".to_string()),
+            src: "https://plausible.io/js/script.js".to_string(),
+        };
+
+        let html = script(&config(provider, true), "example.com");
+
+        assert!(html.starts_with("<script data-galerie-analytics>if(navigator.doNotTrack!==\"1\""));
+        assert!(html.contains("s.setAttribute(\"data-domain\",\"\\u003c/script>\");"));
+        assert!(html.ends_with(
+            "s.src=\"https://plausible.io/js/script.js\";document.head.appendChild(s)}</script>"
+        ));
+    }
+}
@@ -0,0 +1,175 @@
+//! Animated GIF and WebP sources.
+//!
+//! Animated photos keep their animation in the thumbnail and full-size
+//! image (and their data saver versions), encoded as animated WebP. The
+//! micro thumbnail, placeholder, and cover crops use the first frame, and
+//! animated photos get no AVIF or responsive width variants, which browsers
+//! would pick over the animated WebP.
+
+use std::io::Cursor;
+
+use image::codecs::gif::GifDecoder;
+use image::codecs::webp::WebPDecoder;
+use image::imageops::{self, FilterType};
+use image::{AnimationDecoder, DynamicImage, Frame, Frames};
+use webp::{AnimEncoder, AnimFrame, WebPConfig};
+
+use crate::error::{Error, Result};
+
+/// Frames shorter than this play at 100ms in browsers, as GIF tools expect.
+const MIN_FRAME_MS: u32 = 20;
+
+/// Whether the image in `data` has more than one frame.
+pub fn is_animated(data: &[u8], extension: &str) -> bool {
+    match extension {
+        "gif" => GifDecoder::new(Cursor::new(data))
+            .map(|decoder| decoder.into_frames().take(2).count() > 1)
+            .unwrap_or(false),
+        "webp" => WebPDecoder::new(Cursor::new(data))
+            .map(|decoder| decoder.has_animation())
+            .unwrap_or(false),
+        _ => false,
+    }
+}
+
+/// Decode every frame of an animated GIF or WebP, composited to full size.
+pub fn frames(data: &[u8], extension: &str) -> Result<Vec<Frame>> {
+    let frames: Frames = match extension {
+        "gif" => GifDecoder::new(Cursor::new(data))?.into_frames(),
+        "webp" => WebPDecoder::new(Cursor::new(data))?.into_frames(),
+        _ => {
+            return Err(Error::Other(format!(
+                "animated .{} images aren't supported",
+                extension
+            )));
+        }
+    };
+    Ok(frames.collect_frames()?)
+}
+
+/// Crop every frame to `(x, y, width, height)`.
+pub fn crop(frames: &[Frame], (x, y, width, height): (u32, u32, u32, u32)) -> Vec<Frame> {
+    frames
+        .iter()
+        .map(|frame| {
+            let buffer = imageops::crop_imm(frame.buffer(), x, y, width, height).to_image();
+            Frame::from_parts(buffer, 0, 0, frame.delay())
+        })
+        .collect()
+}
+
+/// Encode `frames` as an animated WebP fitting within `size` pixels.
+pub fn encode(frames: &[Frame], size: u32, quality: u8) -> Result<Vec<u8>> {
+    let resized: Vec<(DynamicImage, u32)> = frames
+        .iter()
+        .map(|frame| {
+            let img = DynamicImage::ImageRgba8(frame.buffer().clone());
+            let img = if img.width() > size || img.height() > size {
+                img.resize(size, size, FilterType::Lanczos3)
+            } else {
+                img
+            };
+            (img, duration_ms(frame))
+        })
+        .collect();
+    let Some((first, _)) = resized.first() else {
+        return Err(Error::Other("animation has no frames".to_string()));
+    };
+
+    let mut config =
+        WebPConfig::new().map_err(|_| Error::Other("failed to configure WebP".to_string()))?;
+    config.quality = f32::from(quality);
+    let mut encoder = AnimEncoder::new(first.width(), first.height(), &config);
+
+    // Each frame is added with the time it starts
+    let mut timestamp = 0;
+    for (img, duration) in &resized {
+        let rgba = img.as_rgba8().expect("frames are RGBA");
+        encoder.add_frame(AnimFrame::from_rgba(
+            rgba.as_raw(),
+            rgba.width(),
+            rgba.height(),
+            timestamp,
+        ));
+        timestamp += *duration as i32;
+    }
+
+    encoder
+        .try_encode()
+        .map(|data| data.to_vec())
+        .map_err(|e| Error::Other(format!("failed to encode animated WebP: {:?}", e)))
+}
+
+/// How long a frame shows, in milliseconds.
+fn duration_ms(frame: &Frame) -> u32 {
+    let (numer, denom) = frame.delay().numer_denom_ms();
+    let ms = numer / denom.max(1);
+    if ms < MIN_FRAME_MS { 100 } else { ms }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::codecs::gif::GifEncoder;
+    use image::{Delay, Rgba, RgbaImage};
+
+    fn gif(colors: &[[u8; 4]]) -> Vec<u8> {
+        let mut data = Vec::new();
+        {
+            let mut encoder = GifEncoder::new(&mut data);
+            for &color in colors {
+                let frame = Frame::from_parts(
+                    RgbaImage::from_pixel(40, 30, Rgba(color)),
+                    0,
+                    0,
+                    Delay::from_numer_denom_ms(200, 1),
+                );
+                encoder.encode_frame(frame).unwrap();
+            }
+        }
+        data
+    }
+
+    #[test]
+    fn animated_gif_detected() {
+        let animated = gif(&[[255, 0, 0, 255], [0, 0, 255, 255]]);
+        let still = gif(&[[255, 0, 0, 255]]);
+
+        assert!(is_animated(&animated, "gif"));
+        assert!(!is_animated(&still, "gif"));
+        assert!(!is_animated(&animated, "png"));
+    }
+
+    #[test]
+    fn animation_resized_and_encoded() {
+        let data = gif(&[[255, 0, 0, 255], [0, 0, 255, 255], [0, 255, 0, 255]]);
+        let frames = frames(&data, "gif").unwrap();
+
+        let webp = encode(&frames, 20, 80).unwrap();
+
+        assert!(is_animated(&webp, "webp"));
+        let decoded = super::frames(&webp, "webp").unwrap();
+        assert_eq!(decoded.len(), 3);
+        assert_eq!(decoded[0].buffer().dimensions(), (20, 15));
+        assert_eq!(duration_ms(&decoded[1]), 200);
+    }
+
+    #[test]
+    fn frames_cropped() {
+        let frames = frames(&gif(&[[255, 0, 0, 255], [0, 0, 255, 255]]), "gif").unwrap();
+
+        let cropped = crop(&frames, (5, 0, 30, 30));
+
+        assert_eq!(cropped.len(), 2);
+        assert_eq!(cropped[1].buffer().dimensions(), (30, 30));
+        assert_eq!(cropped[1].buffer().get_pixel(0, 0).0, [0, 0, 255, 255]);
+        assert_eq!(duration_ms(&cropped[1]), 200);
+    }
+
+    #[test]
+    fn instant_frames_slowed() {
+        let frame = Frame::from_parts(RgbaImage::new(1, 1), 0, 0, Delay::from_numer_denom_ms(0, 1));
+
+        assert_eq!(duration_ms(&frame), 100);
+    }
+}
@@ -0,0 +1,177 @@
+//! Albums grouped by capture year for the `/archive/` page.
+//!
+//! Each album is filed under its dominant year: the year most of its photos
+//! (including those in nested albums) were taken, from their EXIF dates. Ties
+//! go to the later year. Albums without any dated photos are listed last,
+//! under no year.
+
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::photos::{Album, Photo};
+use crate::util::url_encode_path;
+
+/// Albums from one year, newest years first.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct Year {
+    /// Capture year, or None for albums without dated photos
+    pub year: Option<i32>,
+    /// Photos in this year's albums
+    pub photo_count: usize,
+    pub albums: Vec<ArchivedAlbum>,
+}
+
+/// An album listed on the archive page.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ArchivedAlbum {
+    pub name: String,
+    pub slug: String,
+    /// URL-encoded album path (e.g., "trips/japan")
+    pub path: String,
+    /// Photos in the album and its nested albums
+    pub photo_count: usize,
+    /// How many of them were taken in the album's year
+    pub year_count: usize,
+}
+
+/// Group every album below `root` by its dominant year, sorted by name within a year.
+pub fn years(root: &Album) -> Vec<Year> {
+    let mut by_year: BTreeMap<Option<i32>, Vec<ArchivedAlbum>> = BTreeMap::new();
+    collect(root, &mut by_year);
+
+    let mut years: Vec<Year> = by_year
+        .into_iter()
+        .map(|(year, mut albums)| {
+            albums.sort_by(|a, b| a.name.cmp(&b.name));
+            Year {
+                year,
+                photo_count: albums.iter().map(|album| album.photo_count).sum(),
+                albums,
+            }
+        })
+        .collect();
+    // Newest first, undated last
+    years.sort_by_key(|y| (y.year.is_none(), Reverse(y.year)));
+    years
+}
+
+fn collect(album: &Album, by_year: &mut BTreeMap<Option<i32>, Vec<ArchivedAlbum>>) {
+    for child in &album.children {
+        let photos = child.all_photos();
+        let (year, year_count) = match dominant_year(&photos) {
+            Some((year, count)) => (Some(year), count),
+            None => (None, 0),
+        };
+        by_year.entry(year).or_default().push(ArchivedAlbum {
+            name: child.name.clone(),
+            slug: child.slug.clone(),
+            path: url_encode_path(&child.path.to_string_lossy()),
+            photo_count: photos.len(),
+            year_count,
+        });
+        collect(child, by_year);
+    }
+}
+
+/// The year most photos were taken, and how many were taken then.
+fn dominant_year(photos: &[&Photo]) -> Option<(i32, usize)> {
+    let mut counts: BTreeMap<i32, usize> = BTreeMap::new();
+    for photo in photos {
+        if let Some(year) = photo.metadata.date_taken.as_deref().and_then(year) {
+            *counts.entry(year).or_default() += 1;
+        }
+    }
+    // max_by_key returns the last maximum, so ties go to the later year
+    counts.into_iter().max_by_key(|&(_, count)| count)
+}
+
+/// Year of an EXIF (`2024:04:02 06:12:45`) or ISO 8601 date.
+fn year(date: &str) -> Option<i32> {
+    date.trim().get(..4)?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::photos::PhotoMetadata;
+    use std::path::PathBuf;
+    use test_case::test_case;
+
+    fn photo(stem: &str, date: Option<&str>) -> Photo {
+        Photo::processed(
+            &format!("{}.jpg", stem),
+            PhotoMetadata {
+                date_taken: date.map(str::to_string),
+                ..Default::default()
+            },
+        )
+    }
+
+    fn album(name: &str, path: &str, dates: &[Option<&str>]) -> Album {
+        Album {
+            name: name.to_string(),
+            slug: name.to_lowercase(),
+            path: PathBuf::from(path),
+            dir: PathBuf::from(path),
+            aliases: Vec::new(),
+            photos: dates
+                .iter()
+                .enumerate()
+                .map(|(i, date)| photo(&format!("{}{}", name, i), *date))
+                .collect(),
+            children: Vec::new(),
+            cover: None,
+            stats: Default::default(),
+        }
+    }
+
+    #[test_case(&[Some("2023:06:01 10:00:00"), Some("2024:01:01 10:00:00"), Some("2024:02:01 10:00:00")], Some((2024, 2)) ; "majority")]
+    #[test_case(&[Some("2023:06:01 10:00:00"), Some("2024-01-01T10:00:00")], Some((2024, 1)) ; "tie goes to later year")]
+    #[test_case(&[None, Some("2021:06:01 10:00:00")], Some((2021, 1)) ; "undated photos ignored")]
+    #[test_case(&[None], None ; "no dates")]
+    fn test_dominant_year(dates: &[Option<&str>], expected: Option<(i32, usize)>) {
+        let photos: Vec<_> = dates.iter().map(|date| photo("p", *date)).collect();
+        let photos: Vec<_> = photos.iter().collect();
+
+        assert_eq!(dominant_year(&photos), expected);
+    }
+
+    #[test]
+    fn albums_grouped_newest_first() {
+        let mut root = album("Gallery", "", &[]);
+        let mut trips = album("Trips", "trips", &[]);
+        trips.children.push(album(
+            "Japan",
+            "trips/japan",
+            &[Some("2024:04:02 06:12:45"), Some("2024:04:03 06:12:45")],
+        ));
+        root.children.push(trips);
+        root.children.push(album("Pets", "pets", &[None]));
+        root.children
+            .push(album("Iceland", "iceland", &[Some("2022:08:01 12:00:00")]));
+        root.children
+            .push(album("Alps", "alps", &[Some("2024:01:10 12:00:00")]));
+
+        let years = years(&root);
+        let summary: Vec<_> = years
+            .iter()
+            .map(|y| {
+                let names: Vec<_> = y.albums.iter().map(|a| a.name.as_str()).collect();
+                (y.year, y.photo_count, names)
+            })
+            .collect();
+
+        assert_eq!(
+            summary,
+            [
+                (Some(2024), 5, vec!["Alps", "Japan", "Trips"]),
+                (Some(2022), 1, vec!["Iceland"]),
+                (None, 1, vec!["Pets"]),
+            ]
+        );
+        assert_eq!(years[0].albums[1].path, "trips/japan");
+        assert_eq!(years[0].albums[2].year_count, 2);
+    }
+}
@@ -0,0 +1,161 @@
+//! Privacy audit of published images.
+//!
+//! GPS stripping and the other privacy options rewrite metadata they know
+//! about, but cameras and editors leave more behind: GPS copied into XMP,
+//! body and lens serial numbers, and edit histories naming the files and
+//! folders a photo came from. The audit reads every file under `images/`
+//! after a build and reports what is still there, so a privacy setting can
+//! be checked rather than trusted.
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use rayon::prelude::*;
+use walkdir::WalkDir;
+
+use crate::error::Result;
+use crate::exif;
+use crate::xmp;
+
+/// Sensitive metadata found in a published file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Finding {
+    /// GPS coordinates in EXIF or XMP, when GPS isn't published
+    Gps,
+    /// Camera body or lens serial number
+    SerialNumber,
+    /// XMP edit history (`xmpMM:History`)
+    EditHistory,
+    /// Local file paths, e.g. from an editor's record of the source file
+    FilePath,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Finding::Gps => "GPS location",
+            Finding::SerialNumber => "serial number",
+            Finding::EditHistory => "edit history",
+            Finding::FilePath => "file path",
+        })
+    }
+}
+
+/// Result of auditing a directory.
+#[derive(Debug, Default)]
+pub struct Report {
+    /// Number of files read
+    pub scanned: usize,
+    /// Files with findings, sorted by path
+    pub flagged: Vec<(PathBuf, Vec<Finding>)>,
+}
+
+/// Markers of local paths in XMP text.
+const PATH_MARKERS: &[&str] = &["stRef:filePath", "file:///", "/Users/", "/home/", ":\\"];
+
+/// Audit every file under `dir`. GPS is only a finding when `gps_allowed` is false.
+pub fn run(dir: &Path, gps_allowed: bool) -> Result<Report> {
+    let files: Vec<PathBuf> = WalkDir::new(dir)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .map(|entry| entry.into_path())
+        .collect();
+
+    let mut flagged = files
+        .par_iter()
+        .map(|path| {
+            let findings = scan(&fs::read(path)?, gps_allowed);
+            Ok((!findings.is_empty()).then(|| (path.clone(), findings)))
+        })
+        .collect::<Result<Vec<_>>>()?
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+    flagged.sort_by(|a, b| a.0.cmp(&b.0));
+
+    Ok(Report {
+        scanned: files.len(),
+        flagged,
+    })
+}
+
+/// Find sensitive metadata in a file's EXIF and embedded XMP.
+pub fn scan(data: &[u8], gps_allowed: bool) -> Vec<Finding> {
+    let residual = exif::residual(data);
+    let packet = xmp::embedded_packet(data).map(String::from_utf8_lossy);
+    let xmp = packet.as_deref().unwrap_or_default();
+
+    let mut findings = Vec::new();
+    if !gps_allowed && (residual.gps || xmp.contains("GPSLatitude")) {
+        findings.push(Finding::Gps);
+    }
+    if residual.serial_number || xmp.contains("SerialNumber") {
+        findings.push(Finding::SerialNumber);
+    }
+    if xmp.contains("xmpMM:History") {
+        findings.push(Finding::EditHistory);
+    }
+    if PATH_MARKERS.iter().any(|marker| xmp.contains(marker)) {
+        findings.push(Finding::FilePath);
+    }
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    fn with_xmp(body: &str) -> Vec<u8> {
+        format!(
+            "\u{ff}\u{d8}<?xpacket begin=\"\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\
+             <x:xmpmeta xmlns:x=\"adobe:ns:meta/\">{body}</x:xmpmeta><?xpacket end=\"w\"?>"
+        )
+        .into_bytes()
+    }
+
+    #[test_case("<exif:GPSLatitude>35,40.5N</exif:GPSLatitude>", false, &[Finding::Gps] ; "xmp gps")]
+    #[test_case("<exif:GPSLatitude>35,40.5N</exif:GPSLatitude>", true, &[] ; "xmp gps allowed")]
+    #[test_case("<aux:SerialNumber>0123</aux:SerialNumber>", true, &[Finding::SerialNumber] ; "xmp serial number")]
+    #[test_case(
+        "<xmpMM:History><stEvt:action>saved</stEvt:action></xmpMM:History>",
+        true,
+        &[Finding::EditHistory]
+        ; "edit history"
+    )]
+    #[test_case(
+        "<xmpMM:DerivedFrom stRef:filePath=\"C:\\Photos\\raw.dng\"/>",
+        true,
+        &[Finding::FilePath]
+        ; "derived from path"
+    )]
+    #[test_case("<dc:title>Beach</dc:title>", false, &[] ; "clean")]
+    fn test_scan_xmp(body: &str, gps_allowed: bool, expected: &[Finding]) {
+        assert_eq!(scan(&with_xmp(body), gps_allowed), expected);
+    }
+
+    #[test]
+    fn run_reports_flagged_files() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("clean.webp"), b"RIFF").unwrap();
+        fs::create_dir(dir.path().join("album")).unwrap();
+        fs::write(
+            dir.path().join("album/leaky.jpg"),
+            with_xmp("<aux:SerialNumber>0123</aux:SerialNumber>"),
+        )
+        .unwrap();
+
+        let report = run(dir.path(), true).unwrap();
+
+        assert_eq!(report.scanned, 2);
+        assert_eq!(
+            report.flagged,
+            [(
+                dir.path().join("album/leaky.jpg"),
+                vec![Finding::SerialNumber]
+            )]
+        );
+    }
+}
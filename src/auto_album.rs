@@ -0,0 +1,285 @@
+//! Albums made from capture dates.
+//!
+//! With `auto_album` set, photos directly in the photos directory, as copied
+//! off a camera card, are grouped into albums before they're processed: one
+//! per calendar month, or one per event, a run of photos with no more than
+//! [`EVENT_GAP`] between one and the next. Albums in subdirectories are left
+//! as they are, and photos without a capture date stay where they were.
+//!
+//! Dates come from the metadata cache while a photo is unchanged, so grouping
+//! a site that's been built before reads nothing new.
+
+use std::path::PathBuf;
+
+use rayon::prelude::*;
+
+use crate::cache::MetadataCache;
+use crate::config::AutoAlbum;
+use crate::photos::{Album, Photo};
+use crate::processing;
+use crate::util;
+
+/// Longest time between two photos of the same event, in seconds.
+pub const EVENT_GAP: i64 = 8 * 60 * 60;
+
+const MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+/// Move the dated photos directly in `root` into albums by `mode`.
+pub fn group(root: &mut Album, mode: AutoAlbum, cache: &MetadataCache) {
+    if root.photos.is_empty() {
+        return;
+    }
+    let dates: Vec<Option<String>> = root
+        .photos
+        .par_iter()
+        .map(|photo| taken(photo, cache))
+        .collect();
+
+    let mut dated = Vec::new();
+    let mut undated = Vec::new();
+    for (photo, date) in std::mem::take(&mut root.photos).into_iter().zip(dates) {
+        match date.as_deref().and_then(Taken::parse) {
+            Some(taken) => dated.push((taken, photo)),
+            None => undated.push(photo),
+        }
+    }
+    root.photos = undated;
+
+    let albums = albums(dated, mode);
+    tracing::info!(
+        albums = albums.len(),
+        undated = root.photos.len(),
+        "grouped photos into albums by date"
+    );
+    for album in albums {
+        // A directory of the same name gets the photos
+        match root
+            .children
+            .iter_mut()
+            .find(|child| child.path == album.path)
+        {
+            Some(child) => child.photos.extend(album.photos),
+            None => root.children.push(album),
+        }
+    }
+    root.children.sort_by(|a, b| a.slug.cmp(&b.slug));
+}
+
+/// Capture date of `photo` in EXIF format, if it can be read.
+fn taken(photo: &Photo, cache: &MetadataCache) -> Option<String> {
+    let source = match processing::source(photo, cache) {
+        Ok(source) => source,
+        Err(e) => {
+            tracing::debug!(photo = %photo.source.display(), error = %e, "no capture date to group by");
+            return None;
+        }
+    };
+    processing::capture_date(&source, &photo.source)
+}
+
+/// When a photo was taken.
+#[derive(Debug, Clone, Copy)]
+struct Taken {
+    /// Seconds since the epoch, in the camera's time
+    time: i64,
+    year: i64,
+    month: usize,
+    day: u32,
+}
+
+impl Taken {
+    /// Parse an EXIF date (`2024:05:03 10:00:00`).
+    fn parse(date: &str) -> Option<Self> {
+        let time = util::parse_exif_time(date)?;
+        let (day, _) = date.trim().split_once(' ')?;
+        let mut parts = day.split(':');
+        Some(Self {
+            time,
+            year: parts.next()?.parse().ok()?,
+            month: parts.next()?.parse().ok()?,
+            day: parts.next()?.parse().ok()?,
+        })
+    }
+
+    /// "May 2024"
+    fn month_name(&self) -> String {
+        format!("{} {}", MONTHS[self.month - 1], self.year)
+    }
+
+    /// "3 May 2024"
+    fn day_name(&self) -> String {
+        format!("{} {}", self.day, self.month_name())
+    }
+
+    /// "2024-05"
+    fn month_slug(&self) -> String {
+        format!("{:04}-{:02}", self.year, self.month)
+    }
+
+    /// "2024-05-03"
+    fn day_slug(&self) -> String {
+        format!("{}-{:02}", self.month_slug(), self.day)
+    }
+}
+
+/// Albums of `photos`, given with when they were taken, oldest first.
+fn albums(mut photos: Vec<(Taken, Photo)>, mode: AutoAlbum) -> Vec<Album> {
+    photos.sort_by(|(a, a_photo), (b, b_photo)| {
+        (a.time, &a_photo.stem).cmp(&(b.time, &b_photo.stem))
+    });
+
+    let mut albums: Vec<Album> = Vec::new();
+    let mut last: Option<i64> = None;
+    for (taken, photo) in photos {
+        let starts_album = match mode {
+            AutoAlbum::ByMonth => albums
+                .last()
+                .is_none_or(|album| album.slug != taken.month_slug()),
+            AutoAlbum::ByEvent => last.is_none_or(|last| taken.time - last > EVENT_GAP),
+        };
+        last = Some(taken.time);
+        if starts_album {
+            let (name, slug) = match mode {
+                AutoAlbum::ByMonth => (taken.month_name(), taken.month_slug()),
+                AutoAlbum::ByEvent => (taken.day_name(), unique(&albums, taken.day_slug())),
+            };
+            albums.push(Album::new(name, slug.clone(), PathBuf::from(slug)));
+        }
+        if let Some(album) = albums.last_mut() {
+            album.photos.push(photo);
+        }
+    }
+    albums
+}
+
+/// `slug`, numbered if `albums` already has it.
+fn unique(albums: &[Album], slug: String) -> String {
+    let used = |slug: &str| albums.iter().any(|album| album.slug == slug);
+    if !used(&slug) {
+        return slug;
+    }
+    (2..)
+        .map(|n| format!("{}-{}", slug, n))
+        .find(|numbered| !used(numbered))
+        .unwrap_or(slug)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+    use test_case::test_case;
+
+    fn photos(dates: &[&str]) -> Vec<(Taken, Photo)> {
+        dates
+            .iter()
+            .enumerate()
+            .map(|(i, date)| {
+                let path = format!("/card/IMG_{:04}.jpg", i);
+                (
+                    Taken::parse(date).unwrap(),
+                    Photo::from_path(Path::new(&path)).unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    fn summary(albums: &[Album]) -> Vec<(String, String, usize)> {
+        albums
+            .iter()
+            .map(|album| (album.slug.clone(), album.name.clone(), album.photos.len()))
+            .collect()
+    }
+
+    #[test]
+    fn grouped_by_month() {
+        let dates = [
+            "2024:06:01 09:00:00",
+            "2024:05:03 10:00:00",
+            "2024:05:31 23:59:59",
+            "2025:01:15 12:00:00",
+        ];
+
+        let albums = albums(photos(&dates), AutoAlbum::ByMonth);
+
+        assert_eq!(
+            summary(&albums),
+            [
+                ("2024-05".into(), "May 2024".into(), 2),
+                ("2024-06".into(), "June 2024".into(), 1),
+                ("2025-01".into(), "January 2025".into(), 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn grouped_by_event() {
+        let dates = [
+            "2024:05:03 18:00:00",
+            "2024:05:03 22:00:00",
+            // Past midnight, within the gap: the same event
+            "2024:05:04 01:00:00",
+            "2024:05:04 18:00:00",
+            "2024:05:04 22:00:00",
+        ];
+
+        let albums = albums(photos(&dates), AutoAlbum::ByEvent);
+
+        assert_eq!(
+            summary(&albums),
+            [
+                ("2024-05-03".into(), "3 May 2024".into(), 3),
+                ("2024-05-04".into(), "4 May 2024".into(), 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn events_on_one_day_named_apart() {
+        let dates = ["2024:05:03 00:30:00", "2024:05:03 20:00:00"];
+
+        let albums = albums(photos(&dates), AutoAlbum::ByEvent);
+
+        assert_eq!(
+            summary(&albums),
+            [
+                ("2024-05-03".into(), "3 May 2024".into(), 1),
+                ("2024-05-03-2".into(), "3 May 2024".into(), 1),
+            ]
+        );
+    }
+
+    #[test_case("2024:01:09 00:00:00", "January 2024", "2024-01-09", "9 January 2024")]
+    #[test_case(
+        "1999:12:31 23:59:59",
+        "December 1999",
+        "1999-12-31",
+        "31 December 1999"
+    )]
+    fn names(date: &str, month: &str, slug: &str, day: &str) {
+        let taken = Taken::parse(date).unwrap();
+        assert_eq!(taken.month_name(), month);
+        assert_eq!(taken.day_slug(), slug);
+        assert_eq!(taken.day_name(), day);
+    }
+
+    #[test_case("2024:13:01 00:00:00" ; "month out of range")]
+    #[test_case("0000:00:00 00:00:00" ; "unset")]
+    #[test_case("2024-05-03" ; "not exif")]
+    fn unparsable_dates(date: &str) {
+        assert!(Taken::parse(date).is_none());
+    }
+}
@@ -0,0 +1,115 @@
+//! A summary of what a build did, for CI pipelines and deploy scripts.
+//!
+//! With `--report FILE`, a build writes how many pages it rendered, what
+//! processing photos did, how many bytes it wrote to the output, how many
+//! stale files it removed, and how long each stage took, as JSON, so scripts
+//! can skip a deploy when nothing changed or flag a build that got slower.
+
+use std::fs;
+use std::path::Path;
+use std::time::SystemTime;
+
+use serde::Serialize;
+
+use crate::error::{Error, Result};
+use crate::processing::ProcessingStats;
+use crate::timings::StageReport;
+
+/// What a build did.
+#[derive(Debug, Serialize)]
+pub struct BuildReport {
+    /// Version of galerie that built the site
+    pub version: &'static str,
+    /// Time the whole build took
+    pub seconds: f64,
+    pub pages: PageCounts,
+    pub photos: ProcessingStats,
+    /// Size of the files written to the output directory
+    pub bytes_written: u64,
+    pub stale_files_removed: usize,
+    /// Time each stage took, empty unless the build recorded timings
+    pub stages: Vec<StageReport>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct PageCounts {
+    pub rendered: usize,
+    /// Pages left as an earlier build wrote them
+    pub unchanged: usize,
+}
+
+impl BuildReport {
+    /// Write the report as JSON to `path`.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| Error::Other(format!("failed to serialize build report: {}", e)))?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, json)?;
+        tracing::info!(path = %path.display(), "wrote build report");
+        Ok(())
+    }
+}
+
+/// Size of the files in `dir` written since `since`.
+pub fn bytes_written(dir: &Path, since: SystemTime) -> u64 {
+    walkdir::WalkDir::new(dir)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| entry.metadata().ok())
+        .filter(|metadata| metadata.is_file())
+        .filter(|metadata| metadata.modified().is_ok_and(|modified| modified >= since))
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn only_new_files_counted() {
+        let dir = tempfile::tempdir().unwrap();
+        let old = dir.path().join("old.html");
+        fs::write(&old, "kept").unwrap();
+        fs::File::options()
+            .write(true)
+            .open(&old)
+            .unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(60))
+            .unwrap();
+        let since = SystemTime::now() - Duration::from_secs(1);
+        fs::create_dir(dir.path().join("images")).unwrap();
+        fs::write(dir.path().join("images/new.webp"), "written").unwrap();
+
+        assert_eq!(bytes_written(dir.path(), since), 7);
+    }
+
+    #[test]
+    fn build_reported() {
+        let site_dir = tempfile::tempdir().unwrap();
+        let site = crate::preview::generate(site_dir.path(), "basic").unwrap();
+        let report = site_dir.path().join("reports/build.json");
+
+        let mut pipeline =
+            crate::pipeline::Pipeline::load(site_dir.path().to_path_buf(), site, false).unwrap();
+        pipeline.timings = crate::timings::Timings::new(true);
+        pipeline.report = Some(report.clone());
+        pipeline.build().unwrap();
+        let first: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&report).unwrap()).unwrap();
+        pipeline.build().unwrap();
+        let second: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&report).unwrap()).unwrap();
+
+        assert!(first["pages"]["rendered"].as_u64().unwrap() > 0);
+        assert!(first["photos"]["generated"].as_u64().unwrap() > 0);
+        assert!(first["bytes_written"].as_u64().unwrap() > 0);
+        assert_eq!(first["stages"][0]["name"], "photos");
+        assert_eq!(second["pages"]["rendered"], 0);
+        assert_eq!(second["photos"]["generated"], 0);
+        assert_eq!(second["photos"]["cached"], first["photos"]["total"]);
+    }
+}
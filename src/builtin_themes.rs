@@ -9,3 +9,17 @@
 //! Hidden files and .gitignore'd files are automatically excluded.
 
 include!(concat!(env!("OUT_DIR"), "/builtin_themes.rs"));
+
+use crate::theme_build::ThemeType;
+
+/// Every built-in theme with its files and whether it's a classic or Vite theme.
+pub fn list() -> impl Iterator<Item = (&'static str, &'static Dir<'static>, ThemeType)> {
+    NAMES.iter().filter_map(|&name| {
+        let kind = if VITE_NAMES.contains(&name) {
+            ThemeType::Vite
+        } else {
+            ThemeType::Classic
+        };
+        Some((name, get(name)?, kind))
+    })
+}
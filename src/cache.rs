@@ -0,0 +1,699 @@
+//! Persistent cache for metadata lookups.
+//!
+//! Reverse geocoding is repeated for every photo on every build, and
+//! albums shot in one place look up the same coordinates over and over.
+//! Results are kept in `.galerie-cache.json` in the site directory, keyed
+//! by provider and rounded coordinates. The file lives outside the build
+//! directory so it's never published alongside the site.
+//!
+//! The cache also records whether GPS was stripped from each published
+//! original, since the file name is the same either way.
+//!
+//! What was read from each original (its hash, dimensions, and embedded
+//! EXIF, XMP, and IPTC) is kept too, keyed by path and checked against the
+//! file's size and modification time, so unchanged photos whose variants
+//! exist aren't read at all. Entries for photos a build didn't see are
+//! dropped when it saves.
+//!
+//! Rendered pages are recorded by a fingerprint of their inputs, so pages
+//! whose inputs are unchanged aren't rendered and written again.
+//!
+//! Sites whose directory is read-only, such as one mounted into a container,
+//! can keep the cache elsewhere with `GALERIE_CACHE_DIR`.
+
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::UNIX_EPOCH;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+use crate::exif::ExifData;
+use crate::geocode::{Offline, Place, Provider};
+use crate::iptc::IptcData;
+use crate::xmp::XmpData;
+
+/// Cache file name, relative to the site directory.
+pub const FILE_NAME: &str = ".galerie-cache.json";
+
+/// Environment variable naming a directory to keep the cache in instead,
+/// for site directories the build can't write to.
+pub const DIR_ENV_VAR: &str = "GALERIE_CACHE_DIR";
+
+/// Bumped when the file format or cached values change meaning.
+const VERSION: u32 = 2;
+
+/// Decimal places kept in coordinate keys (about 110 m at the equator).
+const KEY_PRECISION: i32 = 3;
+
+/// Places by provider name, then by coordinate key.
+type Places = BTreeMap<String, BTreeMap<String, Place>>;
+
+/// Whether GPS was stripped, by published original (see [`original_key`]).
+type Originals = BTreeMap<String, bool>;
+
+/// What was read from each original, by source path.
+type Sources = BTreeMap<String, Source>;
+
+/// Fingerprint of each rendered page's inputs, by output path.
+type Pages = BTreeMap<String, String>;
+
+/// Size and modification time of a file, in nanoseconds since the Unix
+/// epoch. A file whose stamp is unchanged is assumed to be unchanged.
+pub type Stamp = (u64, u64);
+
+/// What was read from an original file, reused while its [`Stamp`] is the same.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Source {
+    pub stamp: Stamp,
+    /// First 8 hex characters of the content's BLAKE3 hash
+    pub hash: String,
+    /// Displayed width, after orientation
+    pub width: u32,
+    /// Displayed height, after orientation
+    pub height: u32,
+    pub animated: bool,
+    pub exif: Option<ExifData>,
+    /// Embedded XMP packet (sidecars are read every build)
+    pub xmp: Option<XmpData>,
+    pub iptc: Option<IptcData>,
+    /// Recording time reported by ffprobe, for videos
+    pub video_date: Option<String>,
+}
+
+/// On-disk representation of the cache.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CacheFile {
+    version: u32,
+    #[serde(default)]
+    places: Places,
+    #[serde(default)]
+    originals: Originals,
+    #[serde(default)]
+    sources: Sources,
+    #[serde(default)]
+    pages: Pages,
+}
+
+/// Metadata lookups shared across photos and persisted between builds.
+///
+/// Safe to use from the parallel photo processing workers.
+#[derive(Debug)]
+pub struct MetadataCache {
+    path: PathBuf,
+    places: RwLock<Places>,
+    originals: RwLock<Originals>,
+    /// Sources as loaded
+    sources: RwLock<Sources>,
+    /// Sources looked up or read during this build, which are the ones saved
+    seen_sources: RwLock<Sources>,
+    /// Pages as loaded
+    pages: RwLock<Pages>,
+    /// Pages kept or written during this build, which are the ones saved
+    /// once rendering has begun
+    seen_pages: RwLock<Pages>,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+    /// Originals recorded and sources read since loading
+    written: AtomicUsize,
+}
+
+impl MetadataCache {
+    /// Load the cache from `path`, starting empty if it's missing or stale.
+    pub fn load(path: PathBuf) -> Self {
+        let file = match fs::read(&path) {
+            Ok(data) => match serde_json::from_slice::<CacheFile>(&data) {
+                Ok(file) if file.version == VERSION => file,
+                Ok(file) => {
+                    tracing::debug!(
+                        path = %path.display(),
+                        version = file.version,
+                        "metadata cache is from another version, starting fresh"
+                    );
+                    CacheFile::default()
+                }
+                Err(e) => {
+                    tracing::warn!(path = %path.display(), error = %e, "ignoring unreadable metadata cache");
+                    CacheFile::default()
+                }
+            },
+            Err(_) => CacheFile::default(),
+        };
+
+        tracing::debug!(path = %path.display(), places = count(&file.places), "loaded metadata cache");
+
+        Self {
+            path,
+            places: RwLock::new(file.places),
+            originals: RwLock::new(file.originals),
+            sources: RwLock::new(file.sources),
+            seen_sources: RwLock::new(Sources::new()),
+            pages: RwLock::new(file.pages),
+            seen_pages: RwLock::new(Pages::new()),
+            hits: AtomicUsize::new(0),
+            misses: AtomicUsize::new(0),
+            written: AtomicUsize::new(0),
+        }
+    }
+
+    /// Reverse geocode coordinates with `provider`, using the cache.
+    ///
+    /// The provider is called with rounded coordinates, so every photo
+    /// within a key's area gets the same result regardless of which one was
+    /// looked up first. If it fails, the offline geocoder is used for this
+    /// build and the lookup is retried next time.
+    pub fn place<P>(&self, provider: &P, latitude: f64, longitude: f64) -> Place
+    where
+        P: Provider,
+    {
+        let (latitude, longitude) = (round(latitude), round(longitude));
+        let key = format!("{},{}", latitude, longitude);
+
+        let cached = self
+            .places
+            .read()
+            .unwrap()
+            .get(&provider.cache_key())
+            .and_then(|places| places.get(&key))
+            .cloned();
+        if let Some(place) = cached {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return place;
+        }
+
+        match provider.reverse(latitude, longitude) {
+            Ok(place) => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                self.places
+                    .write()
+                    .unwrap()
+                    .entry(provider.cache_key())
+                    .or_default()
+                    .insert(key, place.clone());
+                place
+            }
+            Err(e) => {
+                tracing::warn!(error = %e, latitude, longitude, "falling back to offline geocoder");
+                Offline
+                    .reverse(latitude, longitude)
+                    .expect("offline geocoder is infallible")
+            }
+        }
+    }
+
+    /// Whether GPS was stripped from the published original at `key`, if recorded.
+    pub fn original_stripped(&self, key: &str) -> Option<bool> {
+        self.originals.read().unwrap().get(key).copied()
+    }
+
+    /// Record how the published original at `key` was written.
+    pub fn set_original_stripped(&self, key: String, stripped: bool) {
+        self.originals.write().unwrap().insert(key, stripped);
+        self.written.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// What was read from the original at `path` last time, if its stamp
+    /// is still `stamp`.
+    pub fn source(&self, path: &Path, stamp: Stamp) -> Option<Source> {
+        let key = source_key(path);
+        let source = self
+            .sources
+            .read()
+            .unwrap()
+            .get(&key)
+            .filter(|source| source.stamp == stamp)
+            .cloned()?;
+        self.seen_sources
+            .write()
+            .unwrap()
+            .insert(key, source.clone());
+        Some(source)
+    }
+
+    /// What was read from the original at `path` last time, if its content
+    /// hash is still `hash`, restamped with `stamp`. Catches originals that
+    /// were only touched or copied without their modification times.
+    pub fn restamped_source(&self, path: &Path, stamp: Stamp, hash: &str) -> Option<Source> {
+        let source = self
+            .sources
+            .read()
+            .unwrap()
+            .get(&source_key(path))
+            .filter(|source| source.hash == hash)
+            .cloned()?;
+        let source = Source { stamp, ..source };
+        self.set_source(path, source.clone());
+        Some(source)
+    }
+
+    /// What was read from the original at `path` last time, whatever has
+    /// changed since. Nothing is marked as seen.
+    pub fn previous_source(&self, path: &Path) -> Option<Source> {
+        self.sources.read().unwrap().get(&source_key(path)).cloned()
+    }
+
+    /// The originals among `paths` that are new or changed since they were
+    /// last read, and those read last time that are gone. Nothing is marked
+    /// as seen.
+    pub fn changed_sources(&self, paths: &[PathBuf]) -> Vec<PathBuf> {
+        let sources = self.sources.read().unwrap();
+        let mut changed: Vec<PathBuf> = paths
+            .iter()
+            .filter(|path| {
+                let current = fs::metadata(path).ok().and_then(|m| stamp(&m));
+                sources
+                    .get(&source_key(path))
+                    .is_none_or(|source| Some(source.stamp) != current)
+            })
+            .cloned()
+            .collect();
+
+        let present: HashSet<String> = paths.iter().map(|path| source_key(path)).collect();
+        changed.extend(
+            sources
+                .keys()
+                .filter(|key| !present.contains(*key))
+                .map(PathBuf::from),
+        );
+        changed
+    }
+
+    /// Record what was read from the original at `path`.
+    pub fn set_source(&self, path: &Path, source: Source) {
+        self.seen_sources
+            .write()
+            .unwrap()
+            .insert(source_key(path), source);
+        self.written.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Keep the loaded sources of the originals this build never reached, so a
+    /// build stopped partway doesn't drop them.
+    pub fn keep_unseen_sources(&self) {
+        let loaded = self.sources.read().unwrap();
+        let mut seen = self.seen_sources.write().unwrap();
+        for (key, source) in loaded.iter() {
+            seen.entry(key.clone()).or_insert_with(|| source.clone());
+        }
+    }
+
+    /// Whether the page at `key` was last written from inputs with `fingerprint`.
+    pub fn page_unchanged(&self, key: &str, fingerprint: &str) -> bool {
+        let unchanged = self
+            .pages
+            .read()
+            .unwrap()
+            .get(key)
+            .is_some_and(|recorded| recorded == fingerprint);
+        if unchanged {
+            self.seen_pages
+                .write()
+                .unwrap()
+                .insert(key.to_string(), fingerprint.to_string());
+        }
+        unchanged
+    }
+
+    /// Record the fingerprint of the inputs the page at `key` was written from.
+    pub fn set_page(&self, key: String, fingerprint: String) {
+        self.seen_pages.write().unwrap().insert(key, fingerprint);
+        self.written.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Write the cache back to disk if anything was added or dropped.
+    pub fn save(&self) -> Result<()> {
+        let hits = self.hits.load(Ordering::Relaxed);
+        let misses = self.misses.load(Ordering::Relaxed);
+        let written = self.written.load(Ordering::Relaxed);
+        let sources = self.seen_sources.read().unwrap().clone();
+        let mut dropped = missing(&self.sources.read().unwrap(), &sources);
+        // Saved before rendering, the pages of the last build are kept
+        let mut pages = self.seen_pages.read().unwrap().clone();
+        if pages.is_empty() {
+            pages = self.pages.read().unwrap().clone();
+        } else {
+            dropped += missing(&self.pages.read().unwrap(), &pages);
+        }
+        tracing::debug!(hits, misses, written, dropped, "metadata cache");
+
+        if misses == 0 && written == 0 && dropped == 0 {
+            return Ok(());
+        }
+
+        let file = CacheFile {
+            version: VERSION,
+            places: self.places.read().unwrap().clone(),
+            originals: self.originals.read().unwrap().clone(),
+            sources,
+            pages,
+        };
+        let json = serde_json::to_vec(&file).map_err(std::io::Error::other)?;
+
+        // Write then rename so an interrupted build can't leave a truncated file
+        let tmp = temp_path(&self.path);
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&tmp, json)?;
+        fs::rename(&tmp, &self.path)?;
+
+        tracing::debug!(path = %self.path.display(), places = count(&file.places), "saved metadata cache");
+        Ok(())
+    }
+}
+
+/// Where the cache of the site in `site_dir` is kept.
+pub fn path(site_dir: &Path) -> PathBuf {
+    match std::env::var_os(DIR_ENV_VAR).filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir).join(FILE_NAME),
+        None => site_dir.join(FILE_NAME),
+    }
+}
+
+/// Cache key for a published original: its path relative to `images/`.
+pub fn original_key(album_path: &Path, file_name: &str) -> String {
+    album_path
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().into_owned())
+        .chain([file_name.to_string()])
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Size and modification time of a file, if the platform reports them.
+pub fn stamp(metadata: &fs::Metadata) -> Option<Stamp> {
+    let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    Some((metadata.len(), u64::try_from(modified.as_nanos()).ok()?))
+}
+
+/// How many keys of `loaded` aren't in `kept`.
+fn missing<V>(loaded: &BTreeMap<String, V>, kept: &BTreeMap<String, V>) -> usize {
+    loaded.keys().filter(|key| !kept.contains_key(*key)).count()
+}
+
+fn source_key(path: &Path) -> String {
+    path.to_string_lossy().into_owned()
+}
+
+fn count(places: &Places) -> usize {
+    places.values().map(BTreeMap::len).sum()
+}
+
+/// Round a coordinate for use as a cache key, normalizing `-0` to `0`.
+fn round(value: f64) -> f64 {
+    let scale = 10f64.powi(KEY_PRECISION);
+    (value * scale).round() / scale + 0.0
+}
+
+fn temp_path(path: &Path) -> PathBuf {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    PathBuf::from(tmp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+    use std::sync::atomic::AtomicUsize;
+    use test_case::test_case;
+
+    /// A provider that returns a fixed place and counts its lookups.
+    struct Fixed {
+        name: &'static str,
+        city: Option<&'static str>,
+        lookups: AtomicUsize,
+    }
+
+    impl Fixed {
+        fn new(name: &'static str, city: Option<&'static str>) -> Self {
+            Self {
+                name,
+                city,
+                lookups: AtomicUsize::new(0),
+            }
+        }
+
+        fn lookups(&self) -> usize {
+            self.lookups.load(Ordering::Relaxed)
+        }
+    }
+
+    impl Provider for Fixed {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        fn reverse(&self, _latitude: f64, _longitude: f64) -> Result<Place> {
+            self.lookups.fetch_add(1, Ordering::Relaxed);
+            let city = self.city.ok_or_else(|| Error::Geocode {
+                provider: self.name.to_string(),
+                message: "unavailable".to_string(),
+            })?;
+            Ok(Place {
+                city: city.to_string(),
+                region: None,
+                country_code: "IS".to_string(),
+            })
+        }
+    }
+
+    #[test_case(64.14634, 64.146 ; "rounds down")]
+    #[test_case(64.14650, 64.147 ; "rounds up")]
+    #[test_case(-0.0001, 0.0 ; "negative zero")]
+    #[test_case(-21.94258, -21.943 ; "negative")]
+    fn test_round(value: f64, expected: f64) {
+        assert_eq!(round(value), expected);
+        assert_eq!(round(value).to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn nearby_coordinates_share_a_lookup() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = MetadataCache::load(dir.path().join(FILE_NAME));
+        let provider = Fixed::new("test", Some("Reykjavik"));
+
+        for (lat, lon) in [(64.14631, -21.94251), (64.14638, -21.94259)] {
+            assert_eq!(cache.place(&provider, lat, lon).city, "Reykjavik");
+        }
+
+        assert_eq!(provider.lookups(), 1);
+    }
+
+    #[test]
+    fn providers_are_cached_separately() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = MetadataCache::load(dir.path().join(FILE_NAME));
+        let first = Fixed::new("first", Some("Reykjavik"));
+        let second = Fixed::new("second", Some("Seltjarnarnes"));
+
+        cache.place(&first, 64.146, -21.942);
+
+        assert_eq!(cache.place(&second, 64.146, -21.942).city, "Seltjarnarnes");
+    }
+
+    #[test]
+    fn failed_lookups_are_not_cached() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = MetadataCache::load(dir.path().join(FILE_NAME));
+        let provider = Fixed::new("test", None);
+
+        let place = cache.place(&provider, 64.146, -21.942);
+        cache.place(&provider, 64.146, -21.942);
+
+        assert_eq!(place.country_code, "IS");
+        assert_eq!(provider.lookups(), 2);
+    }
+
+    #[test]
+    fn persists_between_loads() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(FILE_NAME);
+
+        let cache = MetadataCache::load(path.clone());
+        cache.place(&Fixed::new("test", Some("Reykjavik")), 64.146, -21.942);
+        cache.save().unwrap();
+
+        let reloaded = MetadataCache::load(path);
+        let provider = Fixed::new("test", Some("Elsewhere"));
+
+        assert_eq!(reloaded.place(&provider, 64.146, -21.942).city, "Reykjavik");
+        assert_eq!(provider.lookups(), 0);
+    }
+
+    #[test]
+    fn original_state_persists_between_loads() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(FILE_NAME);
+        let key = original_key(Path::new("travel/iceland"), "beach-abc12345-original.jpg");
+
+        let cache = MetadataCache::load(path.clone());
+        assert_eq!(cache.original_stripped(&key), None);
+        cache.set_original_stripped(key.clone(), true);
+        cache.save().unwrap();
+
+        let reloaded = MetadataCache::load(path);
+        assert_eq!(key, "travel/iceland/beach-abc12345-original.jpg");
+        assert_eq!(reloaded.original_stripped(&key), Some(true));
+    }
+
+    fn source(stamp: Stamp) -> Source {
+        Source {
+            stamp,
+            hash: "abc12345".to_string(),
+            width: 6000,
+            height: 4000,
+            animated: false,
+            exif: None,
+            xmp: None,
+            iptc: None,
+            video_date: None,
+        }
+    }
+
+    #[test]
+    fn source_reused_while_unchanged() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(FILE_NAME);
+        let photo = Path::new("photos/iceland/beach.jpg");
+
+        let cache = MetadataCache::load(path.clone());
+        assert_eq!(cache.source(photo, (100, 5)), None);
+        cache.set_source(photo, source((100, 5)));
+        cache.save().unwrap();
+
+        let reloaded = MetadataCache::load(path);
+        assert_eq!(reloaded.source(photo, (100, 5)), Some(source((100, 5))));
+        assert_eq!(reloaded.source(photo, (100, 6)), None);
+        assert_eq!(reloaded.source(photo, (101, 5)), None);
+    }
+
+    #[test]
+    fn touched_source_restamped() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(FILE_NAME);
+        let photo = Path::new("photos/iceland/beach.jpg");
+        let cache = MetadataCache::load(path.clone());
+        cache.set_source(photo, source((100, 1)));
+        cache.save().unwrap();
+
+        let cache = MetadataCache::load(path.clone());
+        assert_eq!(cache.source(photo, (100, 5)), None);
+        assert_eq!(cache.restamped_source(photo, (100, 5), "changed"), None);
+        assert_eq!(
+            cache.restamped_source(photo, (100, 5), "abc12345"),
+            Some(source((100, 5)))
+        );
+        cache.save().unwrap();
+
+        let cache = MetadataCache::load(path.clone());
+        assert_eq!(cache.source(photo, (100, 5)), Some(source((100, 5))));
+    }
+
+    #[test]
+    fn changes_since_last_build() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(FILE_NAME);
+        let [kept, touched, added, removed] =
+            ["kept.jpg", "touched.jpg", "added.jpg", "removed.jpg"]
+                .map(|name| dir.path().join(name));
+        for photo in [&kept, &touched, &added] {
+            fs::write(photo, "jpeg").unwrap();
+        }
+        let current = |photo: &Path| stamp(&fs::metadata(photo).unwrap()).unwrap();
+
+        let cache = MetadataCache::load(path.clone());
+        cache.set_source(&kept, source(current(&kept)));
+        cache.set_source(&touched, source((4, 1)));
+        cache.set_source(&removed, source((4, 1)));
+        cache.save().unwrap();
+
+        let cache = MetadataCache::load(path);
+        let mut changed = cache.changed_sources(&[kept, touched.clone(), added.clone()]);
+        changed.sort();
+        assert_eq!(changed, [added, removed, touched]);
+    }
+
+    #[test]
+    fn unseen_sources_dropped() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(FILE_NAME);
+        let kept = Path::new("photos/kept.jpg");
+        let deleted = Path::new("photos/deleted.jpg");
+
+        let cache = MetadataCache::load(path.clone());
+        cache.set_source(kept, source((1, 1)));
+        cache.set_source(deleted, source((2, 2)));
+        cache.save().unwrap();
+
+        let cache = MetadataCache::load(path.clone());
+        assert!(cache.source(kept, (1, 1)).is_some());
+        cache.save().unwrap();
+
+        let reloaded = MetadataCache::load(path);
+        assert!(reloaded.source(kept, (1, 1)).is_some());
+        assert_eq!(reloaded.source(deleted, (2, 2)), None);
+    }
+
+    #[test]
+    fn unseen_sources_kept_when_asked() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(FILE_NAME);
+        let reached = Path::new("photos/reached.jpg");
+        let unreached = Path::new("photos/unreached.jpg");
+
+        let cache = MetadataCache::load(path.clone());
+        cache.set_source(reached, source((1, 1)));
+        cache.set_source(unreached, source((2, 2)));
+        cache.save().unwrap();
+
+        let cache = MetadataCache::load(path.clone());
+        cache.set_source(reached, source((3, 3)));
+        cache.keep_unseen_sources();
+        cache.save().unwrap();
+
+        let reloaded = MetadataCache::load(path);
+        assert!(reloaded.source(reached, (3, 3)).is_some());
+        assert!(reloaded.source(unreached, (2, 2)).is_some());
+    }
+
+    #[test]
+    fn pages_kept_until_rendered() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(FILE_NAME);
+
+        let cache = MetadataCache::load(path.clone());
+        assert!(!cache.page_unchanged("dist/index.html", "a"));
+        cache.set_page("dist/index.html".to_string(), "a".to_string());
+        cache.set_page("dist/gone.html".to_string(), "b".to_string());
+        cache.save().unwrap();
+
+        // Saving before any page is rendered keeps them all
+        let cache = MetadataCache::load(path.clone());
+        cache.set_source(Path::new("photos/beach.jpg"), source((1, 1)));
+        cache.save().unwrap();
+        assert!(cache.page_unchanged("dist/index.html", "a"));
+        assert!(!cache.page_unchanged("dist/index.html", "c"));
+        cache.save().unwrap();
+
+        let reloaded = MetadataCache::load(path);
+        assert!(reloaded.page_unchanged("dist/index.html", "a"));
+        assert!(!reloaded.page_unchanged("dist/gone.html", "b"));
+    }
+
+    #[test_case(r#"{"version": 0, "places": {}}"# ; "old version")]
+    #[test_case("not json" ; "corrupt")]
+    fn ignores_unusable_cache(contents: &str) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(FILE_NAME);
+        fs::write(&path, contents).unwrap();
+
+        let cache = MetadataCache::load(path);
+        let provider = Fixed::new("test", Some("New"));
+
+        assert_eq!(cache.place(&provider, 1.0, 1.0).city, "New");
+    }
+}
@@ -0,0 +1,201 @@
+//! Checking a site without building it.
+//!
+//! `galerie check` loads the config and theme, reads every photo, and renders
+//! every page against the real context, as a build would, but into a
+//! temporary directory and without writing images or saving caches. That
+//! directory takes as much space as the site without its images. What
+//! would stop a build stops the check with the same error. What a build would
+//! get past is counted as a problem instead: photos that can't be read, which
+//! a build leaves out, and `data-i18n` keys with no translation, which leave
+//! the template's text in every language.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use walkdir::WalkDir;
+
+use crate::config::Site;
+use crate::error::Result;
+use crate::i18n;
+use crate::pipeline::Pipeline;
+
+/// Attribute naming the translation of an element's text.
+const I18N_ATTRIBUTE: &str = "data-i18n=";
+
+/// Language every key is expected in; the others fall back to it.
+const DEFAULT_LANGUAGE: &str = "en";
+
+/// Checks run by this process, which each render into their own directory.
+static CHECKS: AtomicUsize = AtomicUsize::new(0);
+
+/// What checking a site found.
+#[derive(Debug, Default)]
+pub struct Report {
+    /// Photos read
+    pub photos: usize,
+    /// Pages rendered
+    pub pages: usize,
+    /// Photos that couldn't be read
+    pub unreadable: usize,
+    /// Translation keys that don't exist, with a page using each
+    pub unknown_keys: BTreeMap<String, PathBuf>,
+}
+
+impl Report {
+    pub fn problems(&self) -> usize {
+        self.unreadable + self.unknown_keys.len()
+    }
+
+    pub fn passed(&self) -> bool {
+        self.problems() == 0
+    }
+}
+
+/// Check the site in `site_dir`, leaving it as it is.
+pub fn run(site_dir: &Path, mut site: Site) -> Result<Report> {
+    let output_dir = std::env::temp_dir().join(format!(
+        "galerie-check-{}-{}",
+        std::process::id(),
+        CHECKS.fetch_add(1, Ordering::Relaxed)
+    ));
+    site.build = output_dir.clone();
+    // Signing needs the site's keys, and a manifest can be written outside the output
+    site.checksums = None;
+    site.manifest = None;
+
+    let report = check(site_dir, site, &output_dir);
+    if let Err(e) = fs::remove_dir_all(&output_dir) {
+        tracing::debug!(path = %output_dir.display(), error = %e, "failed to remove check output");
+    }
+    report
+}
+
+fn check(site_dir: &Path, site: Site, output_dir: &Path) -> Result<Report> {
+    let mut pipeline = Pipeline::load(site_dir.to_path_buf(), site, false)?;
+    pipeline.dry_run = true;
+    pipeline.build()?;
+
+    let translations = i18n::get_all_translations(&pipeline.countries);
+    let known = translations.get(DEFAULT_LANGUAGE);
+    let mut report = Report {
+        photos: pipeline.stats.total,
+        unreadable: pipeline.stats.skipped,
+        ..Report::default()
+    };
+
+    for page in pages(output_dir) {
+        let html = fs::read_to_string(output_dir.join(&page))?;
+        for key in keys(&html) {
+            if !known.is_some_and(|known| known.contains_key(key)) {
+                report
+                    .unknown_keys
+                    .entry(key.to_string())
+                    .or_insert_with(|| page.clone());
+            }
+        }
+        report.pages += 1;
+    }
+    Ok(report)
+}
+
+/// Rendered pages, relative to `output_dir`.
+fn pages(output_dir: &Path) -> Vec<PathBuf> {
+    WalkDir::new(output_dir)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "html"))
+        .filter_map(|entry| {
+            entry
+                .path()
+                .strip_prefix(output_dir)
+                .ok()
+                .map(Path::to_path_buf)
+        })
+        .collect()
+}
+
+/// Translation keys in `html`, quoted or not, since minified pages drop the
+/// quotes where they can.
+fn keys(html: &str) -> Vec<&str> {
+    html.match_indices(I18N_ATTRIBUTE)
+        .filter_map(|(start, _)| {
+            let value = &html[start + I18N_ATTRIBUTE.len()..];
+            let key = match value.chars().next()? {
+                quote @ ('"' | '\'') => value[1..].split(quote).next()?,
+                _ => value
+                    .split(|c: char| c.is_whitespace() || c == '>')
+                    .next()?,
+            };
+            Some(key).filter(|key| !key.is_empty())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(r#"<h2 data-i18n="section.albums">Albums</h2>"#, &["section.albums"] ; "double quotes")]
+    #[test_case("<span data-i18n='nav.next'>", &["nav.next"] ; "single quotes")]
+    #[test_case("<a data-i18n=nav.index href=/>", &["nav.index"] ; "unquoted")]
+    #[test_case("<span data-i18n=nav.next>Next</span><span data-i18n=nav.previous>", &["nav.next", "nav.previous"] ; "several")]
+    #[test_case(r#"<p data-i18n="">"#, &[] ; "empty")]
+    fn keys_found(html: &str, expected: &[&str]) {
+        assert_eq!(keys(html), expected);
+    }
+
+    #[test]
+    fn site_checked_without_writing() {
+        let site_dir = tempfile::tempdir().unwrap();
+        let site = crate::preview::generate(site_dir.path(), "basic").unwrap();
+        let before: Vec<_> = WalkDir::new(site_dir.path())
+            .into_iter()
+            .map(|entry| entry.unwrap().into_path())
+            .collect();
+
+        let report = run(site_dir.path(), site).unwrap();
+
+        assert!(report.passed(), "{:?}", report);
+        assert!(report.photos > 0);
+        assert!(report.pages > 0);
+        let after: Vec<_> = WalkDir::new(site_dir.path())
+            .into_iter()
+            .map(|entry| entry.unwrap().into_path())
+            .collect();
+        assert_eq!(before, after);
+    }
+
+    #[test]
+    fn problems_reported() {
+        let site_dir = tempfile::tempdir().unwrap();
+        let site = crate::preview::generate(site_dir.path(), "mine").unwrap();
+        crate::theme_scaffold::run(site_dir.path(), "mine").unwrap();
+        let base = site_dir.path().join("mine/templates/base.html");
+        let template = fs::read_to_string(&base).unwrap().replace(
+            "</footer>",
+            "<span data-i18n=\"nav.sideways\"></span></footer>",
+        );
+        fs::write(&base, template).unwrap();
+        let album = fs::read_dir(site_dir.path().join("photos"))
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap()
+            .path();
+        fs::write(album.join("broken.jpg"), "not a photo").unwrap();
+
+        let report = run(site_dir.path(), site).unwrap();
+
+        assert_eq!(report.unreadable, 1);
+        assert_eq!(
+            report.unknown_keys.keys().collect::<Vec<_>>(),
+            ["nav.sideways"]
+        );
+        assert_eq!(report.problems(), 2);
+    }
+}
@@ -0,0 +1,336 @@
+//! Checksums of the build output, optionally signed.
+//!
+//! `checksums.txt` lists a hash of every file in the output directory in the
+//! format `sha256sum -c` and `b3sum -c` read, so a mirror or deploy step can
+//! check it received exactly what was built. Signing it with minisign or an
+//! SSH key lets them also check who built it.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use rayon::prelude::*;
+use sha2::{Digest, Sha256};
+use walkdir::WalkDir;
+
+use crate::config::{ChecksumAlgorithm, ChecksumsConfig, SignConfig};
+use crate::error::{Error, Result};
+
+/// Filename of the checksums file in the output directory.
+pub const FILE_NAME: &str = "checksums.txt";
+
+/// SSH signature namespace, passed to `ssh-keygen -Y verify -n`.
+const SSH_NAMESPACE: &str = "file";
+
+/// Files `write` creates for `config`, relative to the output directory.
+pub fn outputs(config: &ChecksumsConfig) -> Vec<String> {
+    let mut files = vec![FILE_NAME.to_string()];
+    if let Some(sign) = &config.sign {
+        files.push(signature_file_name(sign));
+    }
+    files
+}
+
+/// Hash every file in `output_dir`, write `checksums.txt`, and sign it if configured.
+///
+/// Relative signing key paths are resolved against `site_dir`. Returns the
+/// number of files listed.
+pub fn write(output_dir: &Path, config: &ChecksumsConfig, site_dir: &Path) -> Result<usize> {
+    let skip = outputs(config);
+    let mut files: Vec<(String, PathBuf)> = WalkDir::new(output_dir)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(|entry| {
+            let relative = entry.path().strip_prefix(output_dir).ok()?;
+            let name = relative
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            (!skip.contains(&name)).then(|| (name, entry.into_path()))
+        })
+        .collect();
+    files.sort();
+
+    let hashes = files
+        .par_iter()
+        .map(|(_, path)| hash_file(path, config.algorithm))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut listing = String::new();
+    for ((name, _), hash) in files.iter().zip(&hashes) {
+        let _ = writeln!(listing, "{}", line(hash, name));
+    }
+
+    let dest = output_dir.join(FILE_NAME);
+    fs::write(&dest, listing)?;
+
+    if let Some(sign) = &config.sign {
+        // Don't leave the tool asking whether to overwrite the last build's signature
+        let signature = output_dir.join(signature_file_name(sign));
+        if signature.exists() {
+            fs::remove_file(&signature)?;
+        }
+        self::sign(&dest, sign, site_dir)?;
+    }
+
+    Ok(files.len())
+}
+
+/// Files listed in the `checksums.txt` of `output_dir` that are missing or
+/// no longer match, for checking the output before it's published.
+pub fn verify(output_dir: &Path, config: &ChecksumsConfig) -> Result<Vec<String>> {
+    let listing = fs::read_to_string(output_dir.join(FILE_NAME))?;
+    let entries: Vec<(&str, String)> = listing.lines().filter_map(parse_line).collect();
+    Ok(entries
+        .par_iter()
+        .filter(|(hash, name)| {
+            // A tampered listing mustn't have files outside the output hashed
+            !is_inside(name)
+                || hash_file(&output_dir.join(name), config.algorithm)
+                    .map_or(true, |actual| actual != *hash)
+        })
+        .map(|(_, name)| name.clone())
+        .collect())
+}
+
+/// A listing line for `name`. Like coreutils, names with a backslash or
+/// line break are escaped, and the line starts with a backslash to say so.
+fn line(hash: &str, name: &str) -> String {
+    if !name.contains(['\\', '\n', '\r']) {
+        return format!("{}  {}", hash, name);
+    }
+    let escaped = name
+        .replace('\\', "\\\\")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r");
+    format!("\\{}  {}", hash, escaped)
+}
+
+/// Hash and name from a listing line, undoing `line`'s escaping.
+fn parse_line(line: &str) -> Option<(&str, String)> {
+    let Some(line) = line.strip_prefix('\\') else {
+        let (hash, name) = line.split_once("  ")?;
+        return Some((hash, name.to_string()));
+    };
+    let (hash, escaped) = line.split_once("  ")?;
+    let mut name = String::with_capacity(escaped.len());
+    let mut chars = escaped.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            name.push(c);
+            continue;
+        }
+        match chars.next()? {
+            '\\' => name.push('\\'),
+            'n' => name.push('\n'),
+            'r' => name.push('\r'),
+            _ => return None,
+        }
+    }
+    Some((hash, name))
+}
+
+/// Whether `name` is a relative path that stays inside the output.
+fn is_inside(name: &str) -> bool {
+    !name.is_empty()
+        && Path::new(name)
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+}
+
+/// Hex digest of a file, read in chunks.
+fn hash_file(path: &Path, algorithm: ChecksumAlgorithm) -> Result<String> {
+    let mut file = fs::File::open(path)?;
+    let mut buffer = vec![0; 64 * 1024];
+
+    match algorithm {
+        ChecksumAlgorithm::Sha256 => {
+            let mut hasher = Sha256::new();
+            loop {
+                let read = file.read(&mut buffer)?;
+                if read == 0 {
+                    break;
+                }
+                hasher.update(&buffer[..read]);
+            }
+            Ok(hasher
+                .finalize()
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect())
+        }
+        ChecksumAlgorithm::Blake3 => {
+            let mut hasher = blake3::Hasher::new();
+            hasher.update_reader(file)?;
+            Ok(hasher.finalize().to_hex().to_string())
+        }
+    }
+}
+
+/// Name of the signature written next to `checksums.txt`.
+fn signature_file_name(sign: &SignConfig) -> String {
+    match sign {
+        SignConfig::Minisign { .. } => format!("{}.minisig", FILE_NAME),
+        SignConfig::Ssh { .. } => format!("{}.sig", FILE_NAME),
+    }
+}
+
+/// Sign `file` with the configured tool.
+fn sign(file: &Path, sign: &SignConfig, site_dir: &Path) -> Result<()> {
+    let (tool, mut command) = match sign {
+        SignConfig::Minisign { key } => {
+            let mut command = Command::new("minisign");
+            command
+                .arg("-S")
+                .arg("-s")
+                .arg(site_dir.join(key))
+                .arg("-m")
+                .arg(file);
+            ("minisign", command)
+        }
+        SignConfig::Ssh { key } => {
+            let mut command = Command::new("ssh-keygen");
+            command
+                .args(["-Y", "sign", "-n", SSH_NAMESPACE, "-f"])
+                .arg(site_dir.join(key))
+                .arg(file);
+            ("ssh-keygen", command)
+        }
+    };
+
+    if which::which(tool).is_err() {
+        return Err(Error::Sign {
+            message: format!("{} not found", tool),
+        });
+    }
+
+    // Inherit stdin so the tool can ask for the key's passphrase
+    let output = command
+        .stdin(Stdio::inherit())
+        .output()
+        .map_err(|e| Error::Sign {
+            message: format!("failed to run {}: {}", tool, e),
+        })?;
+    if !output.status.success() {
+        return Err(Error::Sign {
+            message: format!(
+                "{} failed: {}",
+                tool,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+        });
+    }
+
+    tracing::debug!(tool, file = %file.display(), "signed checksums");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    fn config(algorithm: ChecksumAlgorithm) -> ChecksumsConfig {
+        ChecksumsConfig {
+            algorithm,
+            sign: None,
+        }
+    }
+
+    #[test_case(
+        ChecksumAlgorithm::Sha256,
+        "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        ; "sha256"
+    )]
+    #[test_case(
+        ChecksumAlgorithm::Blake3,
+        "ea8f163db38682925e4491c5e58d4bb3506ef8c14eb78a86e908c5624a67200f"
+        ; "blake3"
+    )]
+    fn test_hash_file(algorithm: ChecksumAlgorithm, expected: &str) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("hello.txt");
+        fs::write(&path, "hello").unwrap();
+
+        assert_eq!(hash_file(&path, algorithm).unwrap(), expected);
+    }
+
+    #[test]
+    fn write_lists_files_sorted_and_skips_itself() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("images")).unwrap();
+        fs::write(dir.path().join("index.html"), "hello").unwrap();
+        fs::write(dir.path().join("images/a.webp"), "hello").unwrap();
+        fs::write(dir.path().join(FILE_NAME), "stale").unwrap();
+
+        let count = write(dir.path(), &config(ChecksumAlgorithm::Sha256), dir.path()).unwrap();
+        let listing = fs::read_to_string(dir.path().join(FILE_NAME)).unwrap();
+
+        let hash = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+        assert_eq!(count, 2);
+        assert_eq!(
+            listing,
+            format!("{hash}  images/a.webp\n{hash}  index.html\n")
+        );
+    }
+
+    #[test]
+    fn verify_finds_changed_and_missing_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = config(ChecksumAlgorithm::Blake3);
+        for name in ["index.html", "a.webp", "b.webp"] {
+            fs::write(dir.path().join(name), name).unwrap();
+        }
+        write(dir.path(), &config, dir.path()).unwrap();
+
+        fs::write(dir.path().join("a.webp"), "truncated").unwrap();
+        fs::remove_file(dir.path().join("b.webp")).unwrap();
+
+        let mut failed = verify(dir.path(), &config).unwrap();
+        failed.sort();
+        assert_eq!(failed, ["a.webp", "b.webp"]);
+    }
+
+    #[test_case("index.html", "abc  index.html" ; "plain")]
+    #[test_case("a\\b.webp", "\\abc  a\\\\b.webp" ; "backslash")]
+    #[test_case("a\nb.webp", "\\abc  a\\nb.webp" ; "newline")]
+    fn names_escaped(name: &str, expected: &str) {
+        assert_eq!(line("abc", name), expected);
+        assert_eq!(parse_line(expected), Some(("abc", name.to_string())));
+    }
+
+    #[test]
+    fn verify_rejects_names_outside_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("dist");
+        fs::create_dir(&output).unwrap();
+        fs::write(dir.path().join("secret"), "hello").unwrap();
+        let hash = hash_file(&dir.path().join("secret"), ChecksumAlgorithm::Blake3).unwrap();
+        let absolute = dir.path().join("secret");
+        fs::write(
+            output.join(FILE_NAME),
+            format!("{hash}  ../secret\n{hash}  {}\n", absolute.display()),
+        )
+        .unwrap();
+
+        let failed = verify(&output, &config(ChecksumAlgorithm::Blake3)).unwrap();
+
+        assert_eq!(failed.len(), 2);
+    }
+
+    #[test]
+    fn outputs_include_signature() {
+        let config = ChecksumsConfig {
+            algorithm: ChecksumAlgorithm::Blake3,
+            sign: Some(SignConfig::Ssh {
+                key: PathBuf::from("id_ed25519"),
+            }),
+        };
+
+        assert_eq!(outputs(&config), ["checksums.txt", "checksums.txt.sig"]);
+    }
+}
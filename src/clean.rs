@@ -0,0 +1,111 @@
+//! Deleting what builds wrote, for `galerie clean`.
+//!
+//! Processed images are most of a site's output and the slowest part to
+//! rebuild, so besides deleting the whole output directory, `--pages-only`
+//! keeps `images/` and deletes everything else: pages, static assets, data
+//! files, and feeds. `--cache` also deletes the metadata cache, so the next
+//! build reads every photo again.
+
+use std::fs;
+use std::path::Path;
+
+use crate::error::Result;
+
+/// Directory in the output holding processed images.
+const IMAGES_DIR: &str = "images";
+
+/// What to delete.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Options {
+    /// Keep processed images, deleting the rest of the output
+    pub pages_only: bool,
+    /// Delete the metadata cache too
+    pub cache: bool,
+}
+
+/// Delete the output in `output_dir`, and the metadata cache at `cache` if
+/// asked to.
+pub fn run(output_dir: &Path, cache: &Path, options: Options) -> Result<()> {
+    if !output_dir.exists() {
+        tracing::info!(path = %output_dir.display(), "output directory does not exist");
+    } else if options.pages_only {
+        let mut removed = 0;
+        for entry in fs::read_dir(output_dir)? {
+            let entry = entry?;
+            if entry.file_name() == IMAGES_DIR {
+                continue;
+            }
+            if entry.file_type()?.is_dir() {
+                fs::remove_dir_all(entry.path())?;
+            } else {
+                fs::remove_file(entry.path())?;
+            }
+            removed += 1;
+        }
+        tracing::info!(path = %output_dir.display(), removed, "cleaned output directory, keeping images");
+    } else {
+        fs::remove_dir_all(output_dir)?;
+        tracing::info!(path = %output_dir.display(), "cleaned output directory");
+    }
+
+    if options.cache {
+        match fs::remove_file(cache) {
+            Ok(()) => tracing::info!(path = %cache.display(), "removed metadata cache"),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                tracing::info!(path = %cache.display(), "metadata cache does not exist");
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(false, false ; "everything")]
+    #[test_case(true, false ; "pages only")]
+    #[test_case(true, true ; "pages and cache")]
+    fn output_cleaned(pages_only: bool, cache: bool) {
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("dist");
+        fs::create_dir_all(output.join("images/japan")).unwrap();
+        fs::write(output.join("images/japan/temple-abcd1234-thumb.webp"), "").unwrap();
+        fs::create_dir_all(output.join("japan")).unwrap();
+        fs::write(output.join("japan/temple.html"), "").unwrap();
+        fs::create_dir_all(output.join("static")).unwrap();
+        fs::write(output.join("index.html"), "").unwrap();
+        let cache_path = dir.path().join(crate::cache::FILE_NAME);
+        fs::write(&cache_path, "{}").unwrap();
+
+        run(&output, &cache_path, Options { pages_only, cache }).unwrap();
+
+        assert_eq!(
+            output
+                .join("images/japan/temple-abcd1234-thumb.webp")
+                .exists(),
+            pages_only
+        );
+        assert!(!output.join("japan").exists());
+        assert!(!output.join("static").exists());
+        assert!(!output.join("index.html").exists());
+        assert_eq!(cache_path.exists(), !cache);
+    }
+
+    #[test]
+    fn missing_output_and_cache_fine() {
+        let dir = tempfile::tempdir().unwrap();
+        let options = Options {
+            pages_only: true,
+            cache: true,
+        };
+        run(
+            &dir.path().join("dist"),
+            &dir.path().join("cache.json"),
+            options,
+        )
+        .unwrap();
+    }
+}
@@ -0,0 +1,258 @@
+//! Curated pages of photos from any albums.
+//!
+//! A collection in `[collections]` lists photos by album path and name, like
+//! `japan/kyoto-temple`, and is published at its own path with the theme's
+//! album template, as an album with those photos would be. Its photos aren't
+//! copied: their images and photo pages stay with the albums they're in,
+//! and the collection's page links to them.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+use crate::album_config::RESERVED;
+use crate::config::CollectionConfig;
+use crate::error::{Error, Result};
+use crate::photos::{Album, Cover, Photo, titlecase};
+use crate::stats;
+
+/// A collection with its photos found.
+#[derive(Debug)]
+pub struct Collection {
+    /// The collection as an album at its own path, holding its photos
+    pub album: Album,
+    /// The album each photo is in, where its images and page are, in order
+    pub sources: Vec<Album>,
+}
+
+/// Check collection names, which become URL paths.
+pub fn validate(collections: &BTreeMap<String, CollectionConfig>) -> Result<()> {
+    for name in collections.keys() {
+        let valid = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_');
+        if !valid {
+            return Err(Error::Other(format!(
+                "invalid collection name '{}' in [collections] (use lowercase letters, digits, '-', and '_')",
+                name
+            )));
+        }
+        if RESERVED.contains(&name.as_str()) {
+            return Err(Error::Other(format!(
+                "collection '{}' can't publish at /{}/, which holds the site's {}",
+                name, name, name
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Find the photos of each collection in `root`. Photos that aren't there,
+/// mistyped or filtered out, are left out with a warning.
+pub fn resolve(
+    root: &Album,
+    collections: &BTreeMap<String, CollectionConfig>,
+) -> Result<Vec<Collection>> {
+    let mut photos = HashMap::new();
+    index(root, &mut photos);
+
+    let mut resolved = Vec::new();
+    for (name, config) in collections {
+        let path = PathBuf::from(name);
+        if let Some(album) = root
+            .children
+            .iter()
+            .find(|album| album.path == path || album.aliases.contains(&path))
+        {
+            return Err(Error::Other(format!(
+                "collection '{}' and album '{}' both publish at /{}/; rename the collection",
+                name,
+                album.dir.display(),
+                name
+            )));
+        }
+
+        let title = config.title.clone().unwrap_or_else(|| titlecase(name));
+        let mut album = Album::new(title, name.clone(), path);
+        let mut sources = Vec::new();
+        for reference in &config.photos {
+            match find(&photos, reference) {
+                Some((source, photo)) => {
+                    album.photos.push(photo.clone());
+                    sources.push(Album::new(
+                        source.name.clone(),
+                        source.slug.clone(),
+                        source.path.clone(),
+                    ));
+                }
+                None => tracing::warn!(
+                    collection = %name,
+                    photo = %reference,
+                    "collection photo not found or filtered out"
+                ),
+            }
+        }
+        album.stats = stats::compute(&album);
+        album.cover = album
+            .photos
+            .first()
+            .zip(sources.first())
+            .map(|(photo, source)| Cover {
+                stem: photo.stem.clone(),
+                thumb_path: photo.thumb_path(&source.path),
+                crops: BTreeMap::new(),
+            });
+        resolved.push(Collection { album, sources });
+    }
+    Ok(resolved)
+}
+
+/// Photos of `album` and its descendants by reference, `album/path/stem`.
+fn index<'a>(album: &'a Album, photos: &mut HashMap<String, (&'a Album, &'a Photo)>) {
+    for photo in &album.photos {
+        let reference = album.path.join(&photo.stem);
+        photos.insert(reference.to_string_lossy().into_owned(), (album, photo));
+    }
+    for child in &album.children {
+        index(child, photos);
+    }
+}
+
+/// The photo `reference` names, with or without its extension.
+fn find<'a>(
+    photos: &HashMap<String, (&'a Album, &'a Photo)>,
+    reference: &str,
+) -> Option<(&'a Album, &'a Photo)> {
+    let reference = reference.trim_matches('/');
+    photos.get(reference).copied().or_else(|| {
+        let stem = Path::new(reference).with_extension("");
+        photos.get(stem.to_str()?).copied()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    fn album(path: &str, stems: &[&str]) -> Album {
+        let mut album = Album::new(titlecase(path), path.to_string(), PathBuf::from(path));
+        for stem in stems {
+            let source = Path::new("/photos")
+                .join(path)
+                .join(format!("{}.jpg", stem));
+            album.photos.push(Photo::from_path(&source).unwrap());
+        }
+        album
+    }
+
+    fn root() -> Album {
+        let mut root = album("", &["sunset"]);
+        let mut japan = album("japan", &["temple", "tower"]);
+        japan.children.push(album("japan/kyoto", &["garden"]));
+        root.children.push(japan);
+        root
+    }
+
+    fn config(photos: &[&str]) -> BTreeMap<String, CollectionConfig> {
+        BTreeMap::from([(
+            "best-of".to_string(),
+            CollectionConfig {
+                title: None,
+                photos: photos.iter().map(|photo| photo.to_string()).collect(),
+            },
+        )])
+    }
+
+    #[test_case("best-of", true ; "slug")]
+    #[test_case("2024_picks", true ; "digits")]
+    #[test_case("Best Of", false ; "spaces")]
+    #[test_case("", false ; "empty")]
+    #[test_case("static", false ; "reserved")]
+    fn names_validated(name: &str, valid: bool) {
+        let collections = BTreeMap::from([(
+            name.to_string(),
+            CollectionConfig {
+                title: None,
+                photos: Vec::new(),
+            },
+        )]);
+        assert_eq!(validate(&collections).is_ok(), valid);
+    }
+
+    #[test]
+    fn photos_found_in_order() {
+        let root = root();
+        let collections = config(&[
+            "japan/kyoto/garden",
+            "sunset",
+            "japan/missing",
+            "/japan/temple.jpg",
+        ]);
+
+        let resolved = resolve(&root, &collections).unwrap();
+
+        let collection = &resolved[0];
+        assert_eq!(collection.album.name, "Best Of");
+        assert_eq!(collection.album.path, Path::new("best-of"));
+        let found: Vec<_> = collection
+            .album
+            .photos
+            .iter()
+            .zip(&collection.sources)
+            .map(|(photo, source)| source.path.join(&photo.stem))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                Path::new("japan/kyoto/garden"),
+                Path::new("sunset"),
+                Path::new("japan/temple")
+            ]
+        );
+        assert_eq!(collection.album.stats.photo_count, 3);
+        assert_eq!(
+            collection.album.cover.as_ref().unwrap().thumb_path,
+            collection.album.photos[0].thumb_path(Path::new("japan/kyoto"))
+        );
+    }
+
+    #[test]
+    fn album_path_taken() {
+        let mut collections = config(&[]);
+        collections.insert(
+            "japan".to_string(),
+            CollectionConfig {
+                title: None,
+                photos: Vec::new(),
+            },
+        );
+        assert!(resolve(&root(), &collections).is_err());
+    }
+
+    #[test]
+    fn collection_page_links_photos() {
+        let site_dir = tempfile::tempdir().unwrap();
+        crate::preview::generate(site_dir.path(), "mine").unwrap();
+        crate::theme_scaffold::run(site_dir.path(), "mine").unwrap();
+        let config = site_dir.path().join("site.toml");
+        let mut toml = std::fs::read_to_string(&config).unwrap();
+        toml.push_str(
+            "\n[collections.best-of]\ntitle = \"Best\"\nphotos = [\"edge-cases/tiny\", \"landscape\"]\n",
+        );
+        std::fs::write(&config, toml).unwrap();
+        let site = crate::config::Site::load(&config).unwrap();
+
+        let mut pipeline =
+            crate::pipeline::Pipeline::load(site_dir.path().to_path_buf(), site, false).unwrap();
+        pipeline.build().unwrap();
+
+        let page =
+            std::fs::read_to_string(site_dir.path().join("dist/best-of/index.html")).unwrap();
+        assert!(page.contains("Best"));
+        assert!(page.contains("/edge-cases/tiny.html"));
+        assert!(page.contains("/landscape.html"));
+        // The photos' images stay with their albums
+        assert!(!site_dir.path().join("dist/images/best-of").exists());
+    }
+}
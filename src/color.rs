@@ -0,0 +1,134 @@
+//! Conversion to sRGB.
+//!
+//! Browsers treat images without a color profile as sRGB, and variants are
+//! encoded without one. Photos exported in a wider color space (Adobe RGB,
+//! Display P3) would look washed out if their pixels were kept as they are,
+//! so they're converted to sRGB with their embedded ICC profile right after
+//! decoding. Published originals keep their profile.
+
+use image::{DynamicImage, RgbImage, RgbaImage};
+use moxcms::{ColorProfile, DataColorSpace, Layout, TransformOptions, Xyzd};
+
+/// How far a primary may be from sRGB's for a profile to count as sRGB.
+const SRGB_TOLERANCE: f64 = 0.002;
+
+/// Convert `img` from the color space described by the ICC profile `icc` to
+/// sRGB. Images already in sRGB, and profiles that can't be used, leave the
+/// image as it is.
+pub fn to_srgb(img: DynamicImage, icc: &[u8]) -> DynamicImage {
+    let profile = match ColorProfile::new_from_slice(icc) {
+        Ok(profile) => profile,
+        Err(e) => {
+            tracing::warn!(error = ?e, "ignoring unreadable color profile");
+            return img;
+        }
+    };
+    if profile.color_space != DataColorSpace::Rgb || is_srgb(&profile) {
+        return img;
+    }
+
+    let srgb = ColorProfile::new_srgb();
+    let layout = if img.color().has_alpha() {
+        Layout::Rgba
+    } else {
+        Layout::Rgb
+    };
+    let transform =
+        match profile.create_transform_8bit(layout, &srgb, layout, TransformOptions::default()) {
+            Ok(transform) => transform,
+            Err(e) => {
+                tracing::warn!(error = ?e, "ignoring unsupported color profile");
+                return img;
+            }
+        };
+
+    let (width, height) = (img.width(), img.height());
+    let source = match layout {
+        Layout::Rgba => img.into_rgba8().into_raw(),
+        _ => img.into_rgb8().into_raw(),
+    };
+    let mut pixels = vec![0; source.len()];
+    if let Err(e) = transform.transform(&source, &mut pixels) {
+        tracing::warn!(error = ?e, "failed to convert to sRGB");
+        pixels = source;
+    }
+
+    match layout {
+        Layout::Rgba => RgbaImage::from_raw(width, height, pixels).map(DynamicImage::ImageRgba8),
+        _ => RgbImage::from_raw(width, height, pixels).map(DynamicImage::ImageRgb8),
+    }
+    .expect("pixel count unchanged")
+}
+
+/// Whether `profile` has sRGB's primaries. Cameras and editors commonly
+/// embed an sRGB profile, which needs no conversion.
+fn is_srgb(profile: &ColorProfile) -> bool {
+    let srgb = ColorProfile::new_srgb();
+    let close = |a: Xyzd, b: Xyzd| {
+        (a.x - b.x).abs() < SRGB_TOLERANCE
+            && (a.y - b.y).abs() < SRGB_TOLERANCE
+            && (a.z - b.z).abs() < SRGB_TOLERANCE
+    };
+    close(profile.red_colorant, srgb.red_colorant)
+        && close(profile.green_colorant, srgb.green_colorant)
+        && close(profile.blue_colorant, srgb.blue_colorant)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgb;
+
+    fn image(color: [u8; 3]) -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_pixel(4, 4, Rgb(color)))
+    }
+
+    fn pixel(img: &DynamicImage) -> [u8; 3] {
+        img.to_rgb8().get_pixel(0, 0).0
+    }
+
+    #[test]
+    fn display_p3_converted() {
+        let icc = ColorProfile::new_display_p3().encode().unwrap();
+
+        let converted = pixel(&to_srgb(image([180, 80, 80]), &icc));
+
+        // The same values mean a more saturated color in Display P3
+        assert!(converted[0] > 180, "{:?}", converted);
+        assert!(converted[1] < 80, "{:?}", converted);
+    }
+
+    #[test]
+    fn neutral_stays_neutral() {
+        let icc = ColorProfile::new_adobe_rgb().encode().unwrap();
+
+        let [r, g, b] = pixel(&to_srgb(image([128, 128, 128]), &icc));
+
+        assert!(r.abs_diff(g) <= 1 && g.abs_diff(b) <= 1, "{:?}", [r, g, b]);
+    }
+
+    #[test]
+    fn srgb_untouched() {
+        let icc = ColorProfile::new_srgb().encode().unwrap();
+
+        assert_eq!(pixel(&to_srgb(image([180, 80, 80]), &icc)), [180, 80, 80]);
+    }
+
+    #[test]
+    fn alpha_kept() {
+        let icc = ColorProfile::new_display_p3().encode().unwrap();
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(2, 2, image::Rgba([1, 2, 3, 77])));
+
+        let converted = to_srgb(img, &icc);
+
+        assert_eq!(converted.to_rgba8().get_pixel(1, 1).0[3], 77);
+    }
+
+    #[test]
+    fn garbage_profile_ignored() {
+        assert_eq!(
+            pixel(&to_srgb(image([9, 8, 7]), b"not a profile")),
+            [9, 8, 7]
+        );
+    }
+}
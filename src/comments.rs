@@ -0,0 +1,138 @@
+//! Comment threads from static-friendly backends.
+//!
+//! giscus and utterances store comments in GitHub Discussions and Issues,
+//! and Isso is a small self-hosted server; all three load from a script tag,
+//! so a static page only needs the right embed. Threads are keyed by page
+//! path, giving every album and photo page its own discussion.
+
+use quick_xml::escape::escape;
+use serde::Serialize;
+
+use crate::config::CommentsProvider;
+
+/// Class on the element wrapping every embed.
+///
+/// Pages that already contain it placed the thread themselves and aren't injected into.
+pub const MARKER: &str = "galerie-comments";
+
+/// A page's comment thread, exposed to templates as `comments`.
+#[derive(Debug, Serialize)]
+pub struct Comments {
+    /// Backend name (e.g., "giscus")
+    pub provider: &'static str,
+    /// Embed markup, to be output with `| safe`
+    pub html: String,
+}
+
+/// Build the comment embed for the page at `page_path` (e.g., "/travel/beach.html").
+pub fn embed(provider: &CommentsProvider, page_path: &str, lang: &str) -> Comments {
+    let (name, embed) = match provider {
+        CommentsProvider::Giscus {
+            repo,
+            repo_id,
+            category,
+            category_id,
+            theme,
+        } => (
+            "giscus",
+            format!(
+                "<script src=\"https://giscus.app/client.js\" data-repo=\"{}\" \
+                 data-repo-id=\"{}\" data-category=\"{}\" data-category-id=\"{}\" \
+                 data-mapping=\"specific\" data-term=\"{}\" data-strict=\"1\" \
+                 data-reactions-enabled=\"1\" data-input-position=\"bottom\" \
+                 data-theme=\"{}\" data-lang=\"{}\" crossorigin=\"anonymous\" async></script>",
+                escape(repo),
+                escape(repo_id),
+                escape(category),
+                escape(category_id),
+                escape(page_path),
+                escape(theme),
+                escape(giscus_lang(lang)),
+            ),
+        ),
+        CommentsProvider::Utterances { repo, label, theme } => (
+            "utterances",
+            format!(
+                "<script src=\"https://utteranc.es/client.js\" repo=\"{}\" issue-term=\"{}\"{} \
+                 theme=\"{}\" crossorigin=\"anonymous\" async></script>",
+                escape(repo),
+                escape(page_path),
+                label
+                    .as_deref()
+                    .map(|l| format!(" label=\"{}\"", escape(l)))
+                    .unwrap_or_default(),
+                escape(theme),
+            ),
+        ),
+        CommentsProvider::Isso { url } => {
+            let url = url.trim_end_matches('/');
+            (
+                "isso",
+                format!(
+                    "<script data-isso=\"{url}/\" src=\"{url}/js/embed.min.js\"></script>\
+                     <section id=\"isso-thread\" data-isso-id=\"{}\"></section>",
+                    escape(page_path),
+                    url = escape(url),
+                ),
+            )
+        }
+    };
+
+    Comments {
+        provider: name,
+        html: format!("<div class=\"{}\">{}</div>", MARKER, embed),
+    }
+}
+
+/// giscus language code for a galerie language code (`zh_CN` -> `zh-CN`).
+fn giscus_lang(lang: &str) -> String {
+    lang.replace('_', "-")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(
+        CommentsProvider::Giscus {
+            repo: "me/photos".to_string(),
+            repo_id: "R_1".to_string(),
+            category: "Photo Comments".to_string(),
+            category_id: "DIC_1".to_string(),
+            theme: "dark".to_string(),
+        },
+        "giscus",
+        &["data-repo=\"me/photos\"", "data-term=\"/travel/beach.html\"", "data-lang=\"zh-CN\""]
+        ; "giscus"
+    )]
+    #[test_case(
+        CommentsProvider::Utterances {
+            repo: "me/photos".to_string(),
+            label: Some("💬".to_string()),
+            theme: "github-light".to_string(),
+        },
+        "utterances",
+        &["issue-term=\"/travel/beach.html\"", "label=\"💬\""]
+        ; "utterances"
+    )]
+    #[test_case(
+        CommentsProvider::Isso { url: "https://comments.example.com/".to_string() },
+        "isso",
+        &["src=\"https://comments.example.com/js/embed.min.js\"", "data-isso-id=\"/travel/beach.html\""]
+        ; "isso"
+    )]
+    fn test_embed(provider: CommentsProvider, name: &str, expected: &[&str]) {
+        let comments = embed(&provider, "/travel/beach.html", "zh_CN");
+
+        assert_eq!(comments.provider, name);
+        assert!(
+            comments
+                .html
+                .starts_with("<div class=\"galerie-comments\">")
+        );
+        for fragment in expected {
+            assert!(comments.html.contains(fragment), "missing {fragment}");
+        }
+    }
+}
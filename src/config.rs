@@ -1,7 +1,9 @@
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
-use std::path::PathBuf;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
 
+use crate::error::Error;
 use crate::i18n;
 
 /// GPS privacy mode for controlling location data visibility.
@@ -19,352 +21,2152 @@ pub enum GpsMode {
 }
 
 impl GpsMode {
-    /// Returns the filename suffix for original files.
-    ///
-    /// When GPS is stripped (Off or General mode), originals get a `-nogps` suffix
-    /// so they're cached separately from unmodified originals.
-    pub fn original_suffix(self) -> &'static str {
-        match self {
-            GpsMode::On => "",
-            GpsMode::Off | GpsMode::General => "-nogps",
+    /// Whether GPS tags are stripped from published originals (Off or General mode).
+    pub fn strips_gps(self) -> bool {
+        self != GpsMode::On
+    }
+}
+
+/// What the privacy audit does with sensitive metadata left in published images.
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditMode {
+    /// Don't audit (default).
+    #[default]
+    Off,
+    /// Log each file with findings.
+    Warn,
+    /// Log findings and fail the build.
+    Fail,
+}
+
+/// What a build does when a photo's content changes but its file name doesn't.
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Reexports {
+    /// Publish the new version and log a warning (default).
+    #[default]
+    Warn,
+    /// Keep publishing the previous version until a build with `--accept-changes`.
+    Hold,
+    /// Publish the new version without a warning.
+    Allow,
+}
+
+/// How photos directly in the photos directory are grouped into albums.
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum AutoAlbum {
+    /// An album for each calendar month.
+    ByMonth,
+    /// An album for each run of photos taken without a long break.
+    ByEvent,
+}
+
+/// Lossless PNG optimization of static assets and published originals.
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PngOptimization {
+    /// Copy PNGs unchanged (default).
+    #[default]
+    Off,
+    /// Recompress with oxipng's default deflater.
+    Oxipng,
+    /// Recompress with Zopfli: smaller files, much slower.
+    Zopfli,
+}
+
+/// A photo metadata field that can be published to templates and gallery JSON.
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MetadataField {
+    Date,
+    Copyright,
+    Camera,
+    Lens,
+    Gps,
+    Exposure,
+    Rating,
+    Title,
+    Description,
+    Keywords,
+    Creator,
+    Location,
+}
+
+impl MetadataField {
+    /// Every field, in the order they're documented.
+    pub const ALL: &[MetadataField] = &[
+        MetadataField::Date,
+        MetadataField::Copyright,
+        MetadataField::Camera,
+        MetadataField::Lens,
+        MetadataField::Gps,
+        MetadataField::Exposure,
+        MetadataField::Rating,
+        MetadataField::Title,
+        MetadataField::Description,
+        MetadataField::Keywords,
+        MetadataField::Creator,
+        MetadataField::Location,
+    ];
+}
+
+/// Controls which extracted metadata is published.
+///
+/// ```toml
+/// [metadata]
+/// publish = ["camera", "exposure", "date"]
+/// ```
+///
+/// This only affects templates and gallery JSON; what's stripped from
+/// downloadable originals is controlled by `gps`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct MetadataConfig {
+    /// Fields to publish (defaults to all)
+    #[serde(default = "default_publish")]
+    pub publish: Vec<MetadataField>,
+}
+
+impl Default for MetadataConfig {
+    fn default() -> Self {
+        Self {
+            publish: default_publish(),
         }
     }
 }
 
-/// Theme configuration supporting both simple and extended formats.
+impl MetadataConfig {
+    /// Whether `field` should be published.
+    pub fn publishes(&self, field: MetadataField) -> bool {
+        self.publish.contains(&field)
+    }
+}
+
+/// Order of photos within each album.
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PhotoSort {
+    /// By file name (default)
+    #[default]
+    Name,
+    /// Oldest first by capture date, undated photos last
+    Date,
+    /// Highest rated first, unrated photos last
+    Rating,
+}
+
+/// Publish only a curated subset of photos, chosen by their XMP ratings,
+/// picks, and color labels.
 ///
-/// Simple format (backwards compatible):
 /// ```toml
-/// theme = "fancy"
+/// [filter]
+/// min_rating = 3
+/// picks = true
+/// labels = ["Green"]
 /// ```
 ///
-/// Extended format with settings:
+/// Photos must pass every configured condition. Rejected photos are always
+/// left out once a filter is set.
+#[derive(Debug, Clone, Default, Deserialize, PartialEq)]
+pub struct FilterConfig {
+    /// Leave out photos rated below this (unrated photos count as 0)
+    #[serde(default)]
+    pub min_rating: Option<u8>,
+    /// Only publish photos flagged as picks (defaults to false)
+    #[serde(default)]
+    pub picks: bool,
+    /// Only publish photos with one of these color labels, ignoring case (defaults to any)
+    #[serde(default)]
+    pub labels: Vec<String>,
+}
+
+fn default_publish() -> Vec<MetadataField> {
+    MetadataField::ALL.to_vec()
+}
+
+/// Aspect ratio of a crop, written `"width:height"` (or `"square"`) and kept
+/// in lowest terms.
+///
 /// ```toml
-/// [theme]
-/// name = "fancy"
-/// slideshow_delay = 8000
-/// default_sort = "date"
+/// [crops]
+/// banner = "3:1"
+/// square = "1:1"
 /// ```
-#[derive(Debug, Clone, Deserialize)]
-#[serde(untagged)]
-pub enum ThemeConfig {
-    /// Simple string format: `theme = "fancy"`
-    Name(String),
-    /// Table format with name and optional settings
-    Table(ThemeTableConfig),
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(try_from = "String")]
+pub struct AspectRatio {
+    pub width: u32,
+    pub height: u32,
 }
 
-impl Default for ThemeConfig {
-    fn default() -> Self {
-        ThemeConfig::Name("fancy".to_string())
+impl TryFrom<String> for AspectRatio {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        if value.trim().eq_ignore_ascii_case("square") {
+            return Ok(Self {
+                width: 1,
+                height: 1,
+            });
+        }
+        let invalid = || format!("invalid aspect ratio {value:?}, expected e.g. \"3:1\"");
+        let (width, height) = value.split_once(':').ok_or_else(invalid)?;
+        let width: u32 = width.trim().parse().map_err(|_| invalid())?;
+        let height: u32 = height.trim().parse().map_err(|_| invalid())?;
+        if width == 0 || height == 0 {
+            return Err(invalid());
+        }
+        let divisor = gcd(width, height);
+        Ok(Self {
+            width: width / divisor,
+            height: height / divisor,
+        })
     }
 }
 
-impl std::fmt::Display for ThemeConfig {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.name())
+fn gcd(a: u32, b: u32) -> u32 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+/// Reverse geocoding provider.
+///
+/// ```toml
+/// [geocoding]
+/// provider = "nominatim"
+/// url = "http://localhost:8080"
+/// requests_per_second = 1.0
+/// ```
+#[derive(Debug, Clone, Default, Deserialize, PartialEq)]
+#[serde(tag = "provider", rename_all = "lowercase")]
+pub enum GeocodingConfig {
+    /// Bundled offline geocoder (default)
+    #[default]
+    Offline,
+    /// Nominatim server (`/reverse?format=jsonv2`)
+    Nominatim {
+        url: String,
+        #[serde(
+            default = "default_requests_per_second",
+            deserialize_with = "deserialize_requests_per_second"
+        )]
+        requests_per_second: f64,
+    },
+    /// Photon server (`/reverse`)
+    Photon {
+        url: String,
+        #[serde(
+            default = "default_requests_per_second",
+            deserialize_with = "deserialize_requests_per_second"
+        )]
+        requests_per_second: f64,
+    },
+}
+
+fn default_requests_per_second() -> f64 {
+    1.0
+}
+
+/// Slowest rate limit accepted, about one request every 17 minutes.
+const MIN_REQUESTS_PER_SECOND: f64 = 0.001;
+
+/// A rate limit of zero (unlimited), or at least [`MIN_REQUESTS_PER_SECOND`].
+fn deserialize_requests_per_second<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<f64, D::Error> {
+    let value = f64::deserialize(deserializer)?;
+    if value == 0.0 || (value.is_finite() && value >= MIN_REQUESTS_PER_SECOND) {
+        Ok(value)
+    } else {
+        Err(serde::de::Error::custom(format!(
+            "invalid requests_per_second {value}, expected 0 for no limit or at least {MIN_REQUESTS_PER_SECOND}"
+        )))
     }
 }
 
-impl ThemeConfig {
-    /// Returns the theme name.
-    pub fn name(&self) -> &str {
-        match self {
-            ThemeConfig::Name(name) => name,
-            ThemeConfig::Table(table) => &table.name,
+/// A country's display name, alternative names, and region name fixes.
+///
+/// Used for the built-in country data and for a site's changes to it:
+///
+/// ```toml
+/// [countries.IS]
+/// name = "Ísland"
+///
+/// [countries.IS.regions]
+/// "Capital Region" = "Höfuðborgarsvæðið"
+/// ```
+#[derive(Debug, Clone, Default, Deserialize, PartialEq)]
+pub struct CountryConfig {
+    /// Display name
+    pub name: Option<String>,
+    /// Other names that identify the country where one is given by name
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    /// Region names as reported by geocoders, mapped to the name to display
+    #[serde(default)]
+    pub regions: BTreeMap<String, String>,
+}
+
+/// Geotagging from GPX tracks.
+///
+/// ```toml
+/// [gpx]
+/// tracks = "tracks"
+/// time_offset = "+02:00"
+/// max_gap = 300
+/// ```
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct GpxConfig {
+    /// Directory containing `.gpx` files (relative to site root, defaults to the site root)
+    #[serde(default = "default_tracks")]
+    pub tracks: PathBuf,
+    /// Camera clock offset from UTC as `+hh:mm[:ss]`, in seconds once parsed
+    #[serde(default, deserialize_with = "deserialize_offset")]
+    pub time_offset: i64,
+    /// Largest gap in seconds between a photo and the track (defaults to 300)
+    #[serde(default = "default_max_gap")]
+    pub max_gap: u64,
+}
+
+impl Default for GpxConfig {
+    fn default() -> Self {
+        Self {
+            tracks: default_tracks(),
+            time_offset: 0,
+            max_gap: default_max_gap(),
         }
     }
+}
 
-    /// Returns user-provided settings (empty for simple string format).
-    pub fn settings(&self) -> &BTreeMap<String, toml::Value> {
+fn default_tracks() -> PathBuf {
+    PathBuf::from(".")
+}
+
+fn default_max_gap() -> u64 {
+    300
+}
+
+fn deserialize_offset<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<i64, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    crate::util::parse_offset(&value).ok_or_else(|| {
+        serde::de::Error::custom(format!(
+            "invalid time_offset {value:?}, expected e.g. \"+02:00\" or \"-05:30\""
+        ))
+    })
+}
+
+/// A curated page of photos from any albums, published like an album.
+///
+/// ```toml
+/// [collections.best-of]
+/// title = "Best of 2024"
+/// photos = ["japan/kyoto-temple", "iceland/aurora"]
+/// ```
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct CollectionConfig {
+    /// Page title (defaults to the collection's name, titlecased)
+    #[serde(default)]
+    pub title: Option<String>,
+    /// Photos as album path and file name, without the extension, in order
+    pub photos: Vec<String>,
+}
+
+/// RSS feeds of the most recent photos.
+///
+/// ```toml
+/// [feed]
+/// albums = ["365-project"]
+/// limit = 30
+/// ```
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct FeedConfig {
+    /// Generate `/feed.xml` for the whole gallery (defaults to true)
+    #[serde(default = "default_site_feed")]
+    pub site: bool,
+    /// Albums that get their own `feed.xml` (defaults to none)
+    #[serde(default)]
+    pub albums: AlbumFeeds,
+    /// Generate `/feed.json` in JSON Feed format (defaults to true)
+    #[serde(default = "default_json_feed")]
+    pub json: bool,
+    /// Most recent photos in each feed (defaults to 50)
+    #[serde(default = "default_feed_limit")]
+    pub limit: usize,
+}
+
+impl Default for FeedConfig {
+    fn default() -> Self {
+        Self {
+            site: default_site_feed(),
+            albums: AlbumFeeds::default(),
+            json: default_json_feed(),
+            limit: default_feed_limit(),
+        }
+    }
+}
+
+/// Which albums get their own feed: `true` for all, or a list of album paths.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(untagged)]
+pub enum AlbumFeeds {
+    /// `albums = true` or `albums = false`
+    All(bool),
+    /// `albums = ["travel/iceland", "365-project"]`
+    Only(Vec<PathBuf>),
+}
+
+impl Default for AlbumFeeds {
+    fn default() -> Self {
+        AlbumFeeds::All(false)
+    }
+}
+
+impl AlbumFeeds {
+    /// Whether the album at `path` (relative to the photos directory) gets a feed.
+    pub fn includes(&self, path: &Path) -> bool {
         match self {
-            ThemeConfig::Name(_) => {
-                // Return empty BTreeMap for simple format
-                static EMPTY: std::sync::LazyLock<BTreeMap<String, toml::Value>> =
-                    std::sync::LazyLock::new(BTreeMap::new);
-                &EMPTY
-            }
-            ThemeConfig::Table(table) => &table.settings,
+            AlbumFeeds::All(all) => *all,
+            AlbumFeeds::Only(paths) => paths.iter().any(|p| p == path),
         }
     }
 }
 
-/// Table-based theme configuration with name and arbitrary settings.
-#[derive(Debug, Clone, Deserialize)]
-pub struct ThemeTableConfig {
-    /// Theme name (required)
-    pub name: String,
-    /// Arbitrary theme-specific settings
-    #[serde(flatten)]
-    pub settings: BTreeMap<String, toml::Value>,
+fn default_site_feed() -> bool {
+    true
 }
 
-/// Language configuration for i18n support.
-#[derive(Debug, Clone, Deserialize, Serialize)]
-pub struct LangConfig {
-    /// Language code (e.g., "en", "zh_CN")
-    pub code: String,
-    /// Display name for language switcher (e.g., "English", "简体中文")
-    /// If not specified, looked up from built-in language list.
+fn default_json_feed() -> bool {
+    true
+}
+
+fn default_feed_limit() -> usize {
+    50
+}
+
+/// Static ActivityPub actor so the gallery can be found from the fediverse.
+///
+/// ```toml
+/// [activitypub]
+/// username = "photos"
+/// summary = "Photos from my travels"
+/// ```
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct ActivityPubConfig {
+    /// Account name, looked up as `@{username}@{domain}`
+    pub username: String,
+    /// Display name (defaults to the site title)
     #[serde(default)]
     pub name: Option<String>,
+    /// Profile description
+    #[serde(default)]
+    pub summary: Option<String>,
 }
 
-/// Resolved language config with guaranteed name.
-#[derive(Debug, Clone, Serialize)]
-pub struct ResolvedLangConfig {
-    pub code: String,
-    pub name: String,
+/// Sizes and WebP qualities of the generated images. Unset values keep their defaults.
+///
+/// ```toml
+/// [processing]
+/// thumb = { size = 800, quality = 85 }
+/// full = { size = 3200 }
+/// widths = [480, 960, 1600]
+/// lossless_albums = ["screenshots"]
+/// thumb_crop = "square"
+/// thumb_2x = true
+/// sharpen = 40
+/// threads = 2
+/// ```
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct ProcessingConfig {
+    /// Micro thumbnail for filmstrips (defaults to 120px, quality 70)
+    #[serde(default)]
+    pub micro: VariantConfig,
+    /// Grid thumbnail, also the AVIF thumbnail's size (defaults to 600px, quality 80)
+    #[serde(default)]
+    pub thumb: VariantConfig,
+    /// Full-size image, also the AVIF full-size image's size (defaults to 2400px, quality 90)
+    #[serde(default)]
+    pub full: VariantConfig,
+    /// Data saver thumbnail (defaults to 600px, quality 50)
+    #[serde(default)]
+    pub thumb_saver: VariantConfig,
+    /// Data saver full-size image (defaults to 1200px, quality 55)
+    #[serde(default)]
+    pub full_saver: VariantConfig,
+    /// Widths of responsive images for `srcset`, at the full-size quality (defaults to none)
+    #[serde(default)]
+    pub widths: Vec<u32>,
+    /// Encode PNG sources as lossless WebP (defaults to true)
+    #[serde(default = "default_lossless_png")]
+    pub lossless_png: bool,
+    /// Albums whose photos, including nested albums', are encoded as lossless WebP
+    #[serde(default)]
+    pub lossless_albums: Vec<PathBuf>,
+    /// Crop thumbnails to this aspect ratio instead of fitting the whole photo
+    #[serde(default)]
+    pub thumb_crop: Option<AspectRatio>,
+    /// Also generate thumbnails at twice the size for high-DPI displays (defaults to false)
+    #[serde(default)]
+    pub thumb_2x: bool,
+    /// Unsharp mask amount in percent applied after scaling down (defaults to 0, off)
+    #[serde(default)]
+    pub sharpen: u16,
+    /// Threads to build with (defaults to one per CPU core)
+    #[serde(default)]
+    pub threads: Option<NonZeroUsize>,
 }
 
-fn default_languages() -> Vec<LangConfig> {
-    vec![LangConfig {
-        code: "en".to_string(),
-        name: None,
-    }]
+fn default_lossless_png() -> bool {
+    true
 }
 
-/// Site configuration loaded from site.toml
-#[derive(Debug, Deserialize)]
-pub struct Site {
-    /// The domain where this site will be hosted
-    pub domain: String,
+impl Default for ProcessingConfig {
+    fn default() -> Self {
+        Self {
+            micro: VariantConfig::default(),
+            thumb: VariantConfig::default(),
+            full: VariantConfig::default(),
+            thumb_saver: VariantConfig::default(),
+            full_saver: VariantConfig::default(),
+            widths: Vec::new(),
+            lossless_png: default_lossless_png(),
+            lossless_albums: Vec::new(),
+            thumb_crop: None,
+            thumb_2x: false,
+            sharpen: 0,
+            threads: None,
+        }
+    }
+}
+
+/// Size and quality of one generated image.
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq)]
+pub struct VariantConfig {
+    /// Longest side in pixels; smaller images aren't enlarged
+    pub size: Option<u32>,
+    /// WebP quality, 0-100; with `target_kb`, the highest quality tried
+    pub quality: Option<u8>,
+    /// Lower the quality until images fit in this many kilobytes, when set
+    pub target_kb: Option<u32>,
+}
+
+/// AVIF encodes of the thumbnail and full-size image, in addition to WebP.
+///
+/// ```toml
+/// [avif]
+/// quality = 60
+/// speed = 6
+/// ```
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq)]
+pub struct AvifConfig {
+    /// Encoder quality from 1 to 100 (defaults to 60)
+    #[serde(default = "default_avif_quality")]
+    pub quality: u8,
+    /// Encoder speed from 1 (slowest, smallest) to 10 (fastest) (defaults to 6)
+    #[serde(default = "default_avif_speed")]
+    pub speed: u8,
+}
+
+impl Default for AvifConfig {
+    fn default() -> Self {
+        Self {
+            quality: default_avif_quality(),
+            speed: default_avif_speed(),
+        }
+    }
+}
+
+fn default_avif_quality() -> u8 {
+    60
+}
+
+fn default_avif_speed() -> u8 {
+    6
+}
+
+/// A mark composited onto published images. Downloadable originals are left
+/// untouched. Set either `text` or `image`.
+///
+/// ```toml
+/// [watermark]
+/// text = "© Jane Doe"
+/// position = "bottom-right"
+/// opacity = 0.5
+/// scale = 0.2
+/// ```
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct WatermarkConfig {
+    /// Text drawn as the mark, in a built-in pixel font (ASCII and ©)
+    pub text: Option<String>,
+    /// Image used as the mark, relative to the site directory (PNG with transparency works best)
+    pub image: Option<PathBuf>,
+    /// Corner or center the mark is placed in (defaults to "bottom-right")
+    #[serde(default)]
+    pub position: WatermarkPosition,
+    /// Opacity from 0 to 1 (defaults to 0.5)
+    #[serde(default = "default_watermark_opacity")]
+    pub opacity: f32,
+    /// Width of the mark as a fraction of the image's width (defaults to 0.2)
+    #[serde(default = "default_watermark_scale")]
+    pub scale: f32,
+}
+
+fn default_watermark_opacity() -> f32 {
+    0.5
+}
+
+fn default_watermark_scale() -> f32 {
+    0.2
+}
+
+/// Where a watermark is placed.
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "kebab-case")]
+pub enum WatermarkPosition {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    #[default]
+    BottomRight,
+    Center,
+}
+
+/// Page titles and meta descriptions, filled from site, album, and photo metadata.
+///
+/// ```toml
+/// [seo]
+/// title_template = "{photo} — {album} — {site}"
+/// description_template = "{description}"
+/// structured_data = true
+/// ```
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct SeoConfig {
+    /// Template for `<title>` (defaults to `{photo} — {album} — {site}`)
+    #[serde(default = "default_title_template")]
+    pub title_template: String,
+    /// Template for `<meta name="description">` (defaults to `{description}`)
+    #[serde(default = "default_description_template")]
+    pub description_template: String,
+    /// Add schema.org JSON-LD to pages (defaults to true)
+    #[serde(default = "default_structured_data")]
+    pub structured_data: bool,
+}
+
+impl Default for SeoConfig {
+    fn default() -> Self {
+        Self {
+            title_template: default_title_template(),
+            description_template: default_description_template(),
+            structured_data: true,
+        }
+    }
+}
+
+fn default_title_template() -> String {
+    "{photo} — {album} — {site}".to_string()
+}
+
+fn default_description_template() -> String {
+    "{description}".to_string()
+}
+
+fn default_structured_data() -> bool {
+    true
+}
+
+/// Export of the asset and data manifests for deploy scripts and other tooling.
+///
+/// ```toml
+/// [manifest]
+/// path = "manifest.json"
+/// gallery_deltas = 10
+/// ```
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct ManifestConfig {
+    /// Where to write the manifest, relative to the output directory
+    #[serde(default = "default_manifest_path")]
+    pub path: PathBuf,
+    /// Earlier versions of the gallery JSON to publish changes since, so
+    /// clients holding one can catch up without the whole file (0 for none)
+    #[serde(default)]
+    pub gallery_deltas: usize,
+}
+
+fn default_manifest_path() -> PathBuf {
+    PathBuf::from("manifest.json")
+}
+
+/// Originals hosted elsewhere, linked instead of published with the site.
+///
+/// ```toml
+/// [originals]
+/// url = "https://nas.example.com/photos/{path}"
+/// ```
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct OriginalsConfig {
+    /// URL of each original, with `{path}` for its path in the photos
+    /// directory and `{hash}` for its content hash
+    pub url: String,
+    /// Where to write the list of originals, relative to the output directory
+    #[serde(default = "default_originals_manifest")]
+    pub manifest: PathBuf,
+}
+
+fn default_originals_manifest() -> PathBuf {
+    PathBuf::from("originals.json")
+}
+
+/// Checksums of every output file, for verifying a published copy.
+///
+/// ```toml
+/// [checksums]
+/// algorithm = "sha256"
+/// sign = { tool = "minisign", key = "keys/galerie.key" }
+/// ```
+#[derive(Debug, Clone, Default, Deserialize, PartialEq)]
+pub struct ChecksumsConfig {
+    /// Hash algorithm (defaults to SHA-256)
+    #[serde(default)]
+    pub algorithm: ChecksumAlgorithm,
+    /// Sign `checksums.txt` (not signed unless set)
+    #[serde(default)]
+    pub sign: Option<SignConfig>,
+}
+
+/// Where `galerie publish` uploads the output. Set one of `command`, `git`,
+/// or `s3`; only the last two can be rolled back.
+///
+/// ```toml
+/// [deploy]
+/// command = "rsync -a --delete dist/ gallery@example.com:/srv/gallery/"
+/// # or
+/// git = { repo = "git@github.com:me/photos.git", branch = "gh-pages" }
+/// # or
+/// s3 = { bucket = "photos.example.com" }
+/// ```
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct DeployConfig {
+    /// Shell command run from the site directory, with the output directory
+    /// in `GALERIE_OUTPUT`
+    #[serde(default)]
+    pub command: Option<String>,
+    /// Push the output to a branch of a git repository
+    #[serde(default)]
+    pub git: Option<GitDeploy>,
+    /// Sync the output to an S3 bucket with the `aws` CLI
+    #[serde(default)]
+    pub s3: Option<S3Deploy>,
+    /// Deploys remembered for `galerie deploy --rollback` (default: 5)
+    #[serde(default = "default_deploy_keep")]
+    pub keep: usize,
+}
+
+fn default_deploy_keep() -> usize {
+    5
+}
+
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct GitDeploy {
+    /// Repository URL, as given to `git push`
+    pub repo: String,
+    /// Branch the output is committed to (default: `gh-pages`)
+    #[serde(default = "default_deploy_branch")]
+    pub branch: String,
+}
+
+fn default_deploy_branch() -> String {
+    "gh-pages".to_string()
+}
+
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct S3Deploy {
+    pub bucket: String,
+    /// Key prefix the site is uploaded under (default: the bucket root)
+    #[serde(default)]
+    pub prefix: String,
+}
+
+/// Hash algorithm for `checksums.txt`.
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChecksumAlgorithm {
+    /// SHA-256, checked with `sha256sum -c`
+    #[default]
+    Sha256,
+    /// BLAKE3, checked with `b3sum -c`
+    Blake3,
+}
+
+/// Tool and key used to sign `checksums.txt`.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(tag = "tool", rename_all = "lowercase")]
+pub enum SignConfig {
+    /// `minisign -S`, writing `checksums.txt.minisig`
+    Minisign {
+        /// Secret key, relative to the site directory
+        key: PathBuf,
+    },
+    /// `ssh-keygen -Y sign`, writing `checksums.txt.sig`
+    Ssh {
+        /// Private key, relative to the site directory
+        key: PathBuf,
+    },
+}
+
+/// A kind of rendered page.
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PageKind {
+    /// `album.html`
+    Album,
+    /// `photo.html`
+    Photo,
+}
+
+/// Comment threads on album and photo pages.
+///
+/// ```toml
+/// [comments]
+/// provider = "giscus"
+/// repo = "me/photos"
+/// repo_id = "R_kgDO..."
+/// category = "Comments"
+/// category_id = "DIC_kwDO..."
+/// ```
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct CommentsConfig {
+    /// Comment backend and its settings
+    #[serde(flatten)]
+    pub provider: CommentsProvider,
+    /// Pages that get a comment thread (defaults to album and photo pages)
+    #[serde(default = "default_comment_pages")]
+    pub pages: Vec<PageKind>,
+    /// Insert the thread into pages automatically (defaults to true)
+    ///
+    /// Themes that place `{{ comments.html }}` themselves are left alone either way.
+    #[serde(default = "default_inject")]
+    pub inject: bool,
+}
+
+/// Comment backend that works with a static site.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(tag = "provider", rename_all = "lowercase")]
+pub enum CommentsProvider {
+    /// GitHub Discussions via giscus.app
+    Giscus {
+        repo: String,
+        repo_id: String,
+        category: String,
+        category_id: String,
+        #[serde(default = "default_giscus_theme")]
+        theme: String,
+    },
+    /// GitHub Issues via utteranc.es
+    Utterances {
+        repo: String,
+        #[serde(default)]
+        label: Option<String>,
+        #[serde(default = "default_utterances_theme")]
+        theme: String,
+    },
+    /// Self-hosted Isso server
+    Isso { url: String },
+}
+
+fn default_comment_pages() -> Vec<PageKind> {
+    vec![PageKind::Album, PageKind::Photo]
+}
+
+fn default_inject() -> bool {
+    true
+}
+
+fn default_giscus_theme() -> String {
+    "preferred_color_scheme".to_string()
+}
+
+fn default_utterances_theme() -> String {
+    "preferred-color-scheme".to_string()
+}
+
+/// Privacy-friendly analytics script added to every page.
+///
+/// ```toml
+/// [analytics]
+/// provider = "plausible"
+/// ```
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct AnalyticsConfig {
+    /// Analytics service and its settings
+    #[serde(flatten)]
+    pub provider: AnalyticsProvider,
+    /// Don't load the script for visitors with Do Not Track enabled (defaults to true)
+    #[serde(default = "default_dnt")]
+    pub dnt: bool,
+}
+
+/// Analytics service.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(tag = "provider", rename_all = "lowercase")]
+pub enum AnalyticsProvider {
+    /// Plausible (plausible.io or self-hosted)
+    Plausible {
+        /// Site domain registered with Plausible (defaults to the site's `domain`)
+        #[serde(default)]
+        domain: Option<String>,
+        /// Script URL, for self-hosted instances
+        #[serde(default = "default_plausible_src")]
+        src: String,
+    },
+    /// Umami (Umami Cloud or self-hosted)
+    Umami {
+        website_id: String,
+        /// Script URL, for self-hosted instances
+        #[serde(default = "default_umami_src")]
+        src: String,
+    },
+    /// GoatCounter (goatcounter.com)
+    GoatCounter {
+        /// Site code, the `code` in `code.goatcounter.com`
+        code: String,
+    },
+}
+
+fn default_dnt() -> bool {
+    true
+}
+
+fn default_plausible_src() -> String {
+    "https://plausible.io/js/script.js".to_string()
+}
+
+fn default_umami_src() -> String {
+    "https://cloud.umami.is/script.js".to_string()
+}
+
+/// Theme configuration supporting both simple and extended formats.
+///
+/// Simple format (backwards compatible):
+/// ```toml
+/// theme = "fancy"
+/// ```
+///
+/// Extended format with settings:
+/// ```toml
+/// [theme]
+/// name = "fancy"
+/// slideshow_delay = 8000
+/// default_sort = "date"
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum ThemeConfig {
+    /// Simple string format: `theme = "fancy"`
+    Name(String),
+    /// Table format with name and optional settings
+    Table(ThemeTableConfig),
+}
+
+impl Default for ThemeConfig {
+    fn default() -> Self {
+        ThemeConfig::Name("fancy".to_string())
+    }
+}
+
+impl std::fmt::Display for ThemeConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl ThemeConfig {
+    /// Returns the theme name.
+    pub fn name(&self) -> &str {
+        match self {
+            ThemeConfig::Name(name) => name,
+            ThemeConfig::Table(table) => &table.name,
+        }
+    }
+
+    /// Returns user-provided settings (empty for simple string format).
+    pub fn settings(&self) -> &BTreeMap<String, toml::Value> {
+        match self {
+            ThemeConfig::Name(_) => {
+                // Return empty BTreeMap for simple format
+                static EMPTY: std::sync::LazyLock<BTreeMap<String, toml::Value>> =
+                    std::sync::LazyLock::new(BTreeMap::new);
+                &EMPTY
+            }
+            ThemeConfig::Table(table) => &table.settings,
+        }
+    }
+}
+
+/// Table-based theme configuration with name and arbitrary settings.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ThemeTableConfig {
+    /// Theme name (required)
+    pub name: String,
+    /// Arbitrary theme-specific settings
+    #[serde(flatten)]
+    pub settings: BTreeMap<String, toml::Value>,
+}
+
+/// A template function defined in site.toml.
+///
+/// ```toml
+/// [functions.social]
+/// kind = "map"
+/// entries = { instagram = "https://instagram.com/me" }
+/// ```
+///
+/// Templates call it as `{{ social(key="instagram") }}`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum TemplateFunction {
+    /// Returns a fixed value: `{{ name() }}`
+    Value { value: toml::Value },
+    /// Looks up a key in a string map: `{{ name(key="...") }}`
+    Map { entries: BTreeMap<String, String> },
+    /// Reads an environment variable at build time: `{{ name() }}`
+    Env {
+        var: String,
+        #[serde(default)]
+        default: Option<String>,
+    },
+    /// Joins a path onto a base URL: `{{ name(path="...") }}`
+    Link { base: String },
+}
+
+/// Language configuration for i18n support.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct LangConfig {
+    /// Language code (e.g., "en", "zh_CN")
+    pub code: String,
+    /// Display name for language switcher (e.g., "English", "简体中文")
+    /// If not specified, looked up from built-in language list.
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+/// Resolved language config with guaranteed name.
+#[derive(Debug, Clone, Serialize)]
+pub struct ResolvedLangConfig {
+    pub code: String,
+    pub name: String,
+}
+
+fn default_languages() -> Vec<LangConfig> {
+    vec![LangConfig {
+        code: "en".to_string(),
+        name: None,
+    }]
+}
+
+/// Site configuration loaded from site.toml
+#[derive(Debug, Deserialize)]
+pub struct Site {
+    /// The domain where this site will be hosted
+    pub domain: String,
+
+    /// Site title (defaults to domain if not specified)
+    pub title: Option<String>,
+
+    /// Theme configuration (defaults to "fancy").
+    ///
+    /// Supports both simple format (`theme = "fancy"`) and extended format
+    /// with settings (`[theme]` table).
+    ///
+    /// Resolution order (handled by Pipeline::load):
+    /// 1. Local directory with this name → use local
+    /// 2. Built-in theme with this name → use embedded
+    /// 3. Error
+    #[serde(default)]
+    pub theme: ThemeConfig,
+
+    /// Directory containing source photos (relative to site root)
+    #[serde(default = "default_photos")]
+    pub photos: PathBuf,
+
+    /// Directory for build output (relative to site root)
+    #[serde(default = "default_build")]
+    pub build: PathBuf,
+
+    /// Whether to minify HTML, CSS, and JS output (defaults to true)
+    #[serde(default = "default_minify")]
+    pub minify: bool,
+
+    /// HTML-escape template output unless marked `| safe` (defaults to true)
+    #[serde(default = "default_autoescape")]
+    pub autoescape: bool,
+
+    /// GPS privacy mode (defaults to "on")
+    #[serde(default)]
+    pub gps: GpsMode,
+
+    /// Check published images for leftover sensitive metadata (defaults to "off")
+    #[serde(default)]
+    pub audit: AuditMode,
+
+    /// What to do when a photo is re-exported under the same name (defaults to "warn")
+    #[serde(default)]
+    pub reexports: Reexports,
+
+    /// Don't publish originals, and ask themes to deter saving images (defaults to false)
+    #[serde(default)]
+    pub protect_images: bool,
+
+    /// Link originals hosted elsewhere instead of publishing copies (disabled unless configured)
+    #[serde(default)]
+    pub originals: Option<OriginalsConfig>,
+
+    /// Skip full-size images, showing thumbnails with a link to the original (defaults to false)
+    #[serde(default)]
+    pub lite: bool,
+
+    /// Also generate smaller, more compressed images for visitors saving data (defaults to false)
+    #[serde(default)]
+    pub data_saver: bool,
+
+    /// Embed a tiny preview of each photo for themes to show while it loads (defaults to false)
+    #[serde(default)]
+    pub placeholders: bool,
+
+    /// Render an `/archive/` page of albums grouped by year, if the theme has one (defaults to false)
+    #[serde(default)]
+    pub archive: bool,
+
+    /// Sizes and qualities of generated images (defaults to built-in sizes)
+    #[serde(default)]
+    pub processing: ProcessingConfig,
+
+    /// Also generate AVIF thumbnails and full-size images (disabled unless configured)
+    #[serde(default)]
+    pub avif: Option<AvifConfig>,
+
+    /// Named aspect ratios each album's cover is cropped to (defaults to none)
+    #[serde(default)]
+    pub crops: BTreeMap<String, AspectRatio>,
+
+    /// Curated pages of photos from any albums, keyed by URL path (defaults to none)
+    #[serde(default)]
+    pub collections: BTreeMap<String, CollectionConfig>,
+
+    /// Mark composited onto published images (disabled unless configured)
+    #[serde(default)]
+    pub watermark: Option<WatermarkConfig>,
+
+    /// Losslessly recompress and strip metadata from static PNGs and PNG originals (defaults to "off")
+    #[serde(default)]
+    pub optimize_png: PngOptimization,
+
+    /// Which extracted metadata fields are published (defaults to all)
+    #[serde(default)]
+    pub metadata: MetadataConfig,
+
+    /// Order of photos within albums (defaults to by name)
+    #[serde(default)]
+    pub sort: PhotoSort,
+
+    /// Only publish photos matching XMP ratings, picks, or labels (disabled unless configured)
+    #[serde(default)]
+    pub filter: Option<FilterConfig>,
+
+    /// Reverse geocoding provider (defaults to offline)
+    #[serde(default)]
+    pub geocoding: GeocodingConfig,
+
+    /// GPX tracks used to geotag photos without GPS
+    #[serde(default)]
+    pub gpx: GpxConfig,
+
+    /// Changes to built-in country names, keyed by ISO 3166-1 alpha-2 code
+    #[serde(default)]
+    pub countries: BTreeMap<String, CountryConfig>,
+
+    /// Languages to generate (defaults to English only)
+    /// Ignored if `all_languages` is true.
+    #[serde(default = "default_languages")]
+    pub languages: Vec<LangConfig>,
+
+    /// Enable all supported languages (overrides `languages` list)
+    #[serde(default)]
+    pub all_languages: bool,
+
+    /// Default language code (defaults to first in languages list)
+    pub default_language: Option<String>,
+
+    /// RSS and JSON feeds for the gallery and individual albums
+    #[serde(default)]
+    pub feed: FeedConfig,
+
+    /// Page title and description templates
+    #[serde(default)]
+    pub seo: SeoConfig,
+
+    /// Static ActivityPub actor and outbox (disabled unless configured)
+    #[serde(default)]
+    pub activitypub: Option<ActivityPubConfig>,
+
+    /// Write the asset and data manifests as JSON (disabled unless configured)
+    #[serde(default)]
+    pub manifest: Option<ManifestConfig>,
+
+    /// Write checksums of the output (disabled unless configured)
+    #[serde(default)]
+    pub checksums: Option<ChecksumsConfig>,
+
+    /// How `galerie publish` uploads the output (publishing only builds and verifies unless configured)
+    #[serde(default)]
+    pub deploy: Option<DeployConfig>,
+
+    /// Comment threads on album and photo pages (disabled unless configured)
+    #[serde(default)]
+    pub comments: Option<CommentsConfig>,
+
+    /// Analytics script on every page (disabled unless configured)
+    #[serde(default)]
+    pub analytics: Option<AnalyticsConfig>,
+
+    /// Flatten album hierarchy - treat all photos as one gallery
+    #[serde(default)]
+    pub flatten: bool,
+
+    /// Group photos directly in the photos directory into albums by capture date
+    #[serde(default)]
+    pub auto_album: Option<AutoAlbum>,
+
+    /// Custom template functions, keyed by function name
+    #[serde(default)]
+    pub functions: BTreeMap<String, TemplateFunction>,
+
+    /// Look for newer galerie releases once a day when building (defaults to false)
+    #[serde(default)]
+    pub update_check: bool,
+
+    /// Token authorizing `POST /_galerie/rebuild` when serving and watching
+    /// (disabled unless set; `GALERIE_REBUILD_TOKEN` takes precedence)
+    #[serde(default)]
+    pub rebuild_token: Option<String>,
+}
+
+impl Site {
+    /// Load and parse a site config file.
+    pub fn load(path: &Path) -> crate::error::Result<Self> {
+        let content = std::fs::read_to_string(path).map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => Error::ConfigNotFound {
+                path: path.to_path_buf(),
+            },
+            _ => Error::Io(e),
+        })?;
+        Ok(toml::from_str(&content)?)
+    }
+
+    /// Returns the default language code.
+    pub fn default_lang(&self) -> String {
+        self.default_language.clone().unwrap_or_else(|| {
+            self.languages
+                .first()
+                .map(|l| l.code.clone())
+                .unwrap_or_else(|| "en".to_string())
+        })
+    }
+
+    /// Returns the languages to use, respecting `all_languages` flag.
+    /// Names are resolved from built-in list if not specified.
+    pub fn languages(&self) -> Vec<ResolvedLangConfig> {
+        let builtin: std::collections::HashMap<&str, &str> = i18n::all_supported_languages()
+            .into_iter()
+            .map(|l| (l.code, l.name))
+            .collect();
+
+        if self.all_languages {
+            i18n::all_supported_languages()
+                .into_iter()
+                .map(|l| ResolvedLangConfig {
+                    code: l.code.to_string(),
+                    name: l.name.to_string(),
+                })
+                .collect()
+        } else {
+            self.languages
+                .iter()
+                .map(|l| {
+                    let name = l.name.clone().unwrap_or_else(|| {
+                        builtin
+                            .get(l.code.as_str())
+                            .map(|s| s.to_string())
+                            .unwrap_or_else(|| l.code.clone())
+                    });
+                    ResolvedLangConfig {
+                        code: l.code.clone(),
+                        name,
+                    }
+                })
+                .collect()
+        }
+    }
+}
+
+/// Loads the site config again for each rebuild, keeping the last version
+/// that parsed so a typo saved to it doesn't stop rebuilds.
+#[derive(Debug)]
+pub struct Reloader {
+    path: PathBuf,
+    last_good: Option<String>,
+}
+
+impl Reloader {
+    /// A reloader for the config at `path`, which starts out with its
+    /// current contents as the last good version if they parse.
+    pub fn new(path: PathBuf) -> Self {
+        let last_good = std::fs::read_to_string(&path)
+            .ok()
+            .filter(|content| toml::from_str::<Site>(content).is_ok());
+        Self { path, last_good }
+    }
+
+    /// Path of the config file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Load the config, or the last good version if it's now missing or invalid.
+    pub fn load(&mut self) -> crate::error::Result<Site> {
+        let result = std::fs::read_to_string(&self.path)
+            .map_err(|e| match e.kind() {
+                std::io::ErrorKind::NotFound => Error::ConfigNotFound {
+                    path: self.path.clone(),
+                },
+                _ => Error::Io(e),
+            })
+            .and_then(|content| Ok((toml::from_str::<Site>(&content)?, content)));
+
+        match (result, &self.last_good) {
+            (Ok((site, content)), _) => {
+                self.last_good = Some(content);
+                Ok(site)
+            }
+            (Err(e), Some(last_good)) => {
+                tracing::error!(
+                    path = %self.path.display(),
+                    error = %e,
+                    "config is invalid, building with the last good version"
+                );
+                Ok(toml::from_str(last_good)?)
+            }
+            (Err(e), None) => Err(e),
+        }
+    }
+}
+
+/// Settings given on the command line, which win over the site config's.
+#[derive(Debug, Clone, Default)]
+pub struct Overrides {
+    /// Theme to use instead of the configured one
+    pub theme: Option<String>,
+    /// Threads to build with instead of `[processing] threads`
+    pub jobs: Option<NonZeroUsize>,
+    /// Publish re-exported photos held by `reexports = "hold"`
+    pub accept_changes: bool,
+}
+
+impl Overrides {
+    /// Apply these overrides to a loaded site config.
+    pub fn apply(&self, site: &mut Site) {
+        if let Some(theme) = &self.theme {
+            site.theme = ThemeConfig::Name(theme.clone());
+        }
+        if let Some(jobs) = self.jobs {
+            site.processing.threads = Some(jobs);
+        }
+        if self.accept_changes && site.reexports == Reexports::Hold {
+            site.reexports = Reexports::Allow;
+        }
+    }
+}
+
+fn default_photos() -> PathBuf {
+    PathBuf::from("photos")
+}
+
+fn default_build() -> PathBuf {
+    PathBuf::from("dist")
+}
+
+fn default_minify() -> bool {
+    true
+}
+
+fn default_autoescape() -> bool {
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test]
+    fn minimal_config() {
+        let toml = r#"
+            domain = "photos.example.com"
+            theme = "themes/minimal"
+        "#;
+        let site: Site = toml::from_str(toml).unwrap();
+
+        assert_eq!(site.domain, "photos.example.com");
+        assert_eq!(site.theme.name(), "themes/minimal");
+        assert!(site.theme.settings().is_empty());
+        assert_eq!(site.photos, PathBuf::from("photos"));
+        assert_eq!(site.build, PathBuf::from("dist"));
+        assert!(site.autoescape);
+    }
+
+    #[test]
+    fn full_config() {
+        let toml = r#"
+            domain = "photos.example.com"
+            theme = "my-theme"
+            photos = "albums/vacation"
+            build = "output"
+        "#;
+        let site: Site = toml::from_str(toml).unwrap();
+
+        assert_eq!(site.domain, "photos.example.com");
+        assert_eq!(site.theme.name(), "my-theme");
+        assert_eq!(site.photos, PathBuf::from("albums/vacation"));
+        assert_eq!(site.build, PathBuf::from("output"));
+    }
+
+    #[test]
+    fn missing_domain_fails() {
+        let toml = r#"theme = "default""#;
+        let result: Result<Site, _> = toml::from_str(toml);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn default_theme() {
+        let toml = r#"domain = "example.com""#;
+        let site: Site = toml::from_str(toml).unwrap();
+
+        assert_eq!(site.theme.name(), "fancy");
+    }
+
+    #[test]
+    fn theme_table_format() {
+        let toml = r#"
+            domain = "example.com"
+
+            [theme]
+            name = "fancy"
+            slideshow_delay = 8000
+            default_sort = "date"
+        "#;
+        let site: Site = toml::from_str(toml).unwrap();
+
+        assert_eq!(site.theme.name(), "fancy");
+
+        let settings = site.theme.settings();
+        assert_eq!(
+            settings.get("slideshow_delay"),
+            Some(&toml::Value::Integer(8000))
+        );
+        assert_eq!(
+            settings.get("default_sort"),
+            Some(&toml::Value::String("date".to_string()))
+        );
+    }
+
+    #[test]
+    fn theme_table_minimal() {
+        let toml = r#"
+            domain = "example.com"
+
+            [theme]
+            name = "basic"
+        "#;
+        let site: Site = toml::from_str(toml).unwrap();
+
+        assert_eq!(site.theme.name(), "basic");
+        assert!(site.theme.settings().is_empty());
+    }
+
+    #[test]
+    fn template_functions() {
+        let toml = r#"
+            domain = "example.com"
+
+            [functions.social]
+            kind = "map"
+            entries = { instagram = "https://instagram.com/me" }
+
+            [functions.analytics_id]
+            kind = "env"
+            var = "ANALYTICS_ID"
+            default = "none"
+
+            [functions.gh]
+            kind = "link"
+            base = "https://github.com/"
+
+            [functions.year]
+            kind = "value"
+            value = 2025
+        "#;
+        let site: Site = toml::from_str(toml).unwrap();
+
+        assert_eq!(site.functions.len(), 4);
+        assert!(matches!(
+            site.functions.get("social"),
+            Some(TemplateFunction::Map { entries }) if entries.len() == 1
+        ));
+        assert!(matches!(
+            site.functions.get("analytics_id"),
+            Some(TemplateFunction::Env { var, default: Some(_) }) if var == "ANALYTICS_ID"
+        ));
+        assert!(matches!(
+            site.functions.get("gh"),
+            Some(TemplateFunction::Link { .. })
+        ));
+        assert!(matches!(
+            site.functions.get("year"),
+            Some(TemplateFunction::Value {
+                value: toml::Value::Integer(2025)
+            })
+        ));
+    }
+
+    #[test]
+    fn template_function_unknown_kind_fails() {
+        let toml = r#"
+            domain = "example.com"
+
+            [functions.oops]
+            kind = "shell"
+        "#;
+        let result: Result<Site, _> = toml::from_str(toml);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn gps_mode_default() {
+        let toml = r#"domain = "example.com""#;
+        let site: Site = toml::from_str(toml).unwrap();
+
+        assert_eq!(site.gps, GpsMode::On);
+    }
+
+    #[test]
+    fn gps_mode_off() {
+        let toml = r#"
+            domain = "example.com"
+            gps = "off"
+        "#;
+        let site: Site = toml::from_str(toml).unwrap();
+
+        assert_eq!(site.gps, GpsMode::Off);
+    }
+
+    #[test]
+    fn gps_mode_general() {
+        let toml = r#"
+            domain = "example.com"
+            gps = "general"
+        "#;
+        let site: Site = toml::from_str(toml).unwrap();
+
+        assert_eq!(site.gps, GpsMode::General);
+    }
+
+    #[test]
+    fn gps_mode_on() {
+        let toml = r#"
+            domain = "example.com"
+            gps = "on"
+        "#;
+        let site: Site = toml::from_str(toml).unwrap();
+
+        assert_eq!(site.gps, GpsMode::On);
+    }
+
+    #[test]
+    fn metadata_publishes_all_by_default() {
+        let toml = r#"domain = "example.com""#;
+        let site: Site = toml::from_str(toml).unwrap();
+
+        assert_eq!(site.metadata.publish, MetadataField::ALL);
+    }
+
+    #[test]
+    fn metadata_publish_list() {
+        let toml = r#"
+            domain = "example.com"
+
+            [metadata]
+            publish = ["camera", "exposure", "date"]
+        "#;
+        let site: Site = toml::from_str(toml).unwrap();
+
+        assert!(site.metadata.publishes(MetadataField::Camera));
+        assert!(site.metadata.publishes(MetadataField::Date));
+        assert!(!site.metadata.publishes(MetadataField::Lens));
+        assert!(!site.metadata.publishes(MetadataField::Gps));
+    }
+
+    #[test]
+    fn metadata_unknown_field_fails() {
+        let toml = r#"
+            domain = "example.com"
+
+            [metadata]
+            publish = ["serial_number"]
+        "#;
+        let result: Result<Site, _> = toml::from_str(toml);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn gpx_defaults() {
+        let toml = r#"domain = "example.com""#;
+        let site: Site = toml::from_str(toml).unwrap();
+
+        assert_eq!(site.gpx, GpxConfig::default());
+        assert_eq!(site.gpx.tracks, PathBuf::from("."));
+    }
+
+    #[test]
+    fn gpx_config() {
+        let toml = r#"
+            domain = "example.com"
+
+            [gpx]
+            tracks = "tracks"
+            time_offset = "-05:30"
+            max_gap = 60
+        "#;
+        let site: Site = toml::from_str(toml).unwrap();
+
+        assert_eq!(site.gpx.tracks, PathBuf::from("tracks"));
+        assert_eq!(site.gpx.time_offset, -(5 * 3600 + 30 * 60));
+        assert_eq!(site.gpx.max_gap, 60);
+    }
+
+    #[test]
+    fn gpx_invalid_offset_fails() {
+        let toml = r#"
+            domain = "example.com"
+
+            [gpx]
+            time_offset = "2 hours"
+        "#;
+        let result: Result<Site, _> = toml::from_str(toml);
+
+        assert!(result.is_err());
+    }
+
+    #[test_case("0", true ; "unlimited")]
+    #[test_case("0.5", true ; "slow")]
+    #[test_case("1e-300", false ; "too slow")]
+    #[test_case("-1", false ; "negative")]
+    #[test_case("inf", false ; "infinite")]
+    #[test_case("nan", false ; "not a number")]
+    fn geocoding_rate_limit(rate: &str, valid: bool) {
+        let toml = format!(
+            "domain = \"example.com\"\n[geocoding]\nprovider = \"photon\"\nurl = \"http://localhost:2322\"\nrequests_per_second = {}",
+            rate
+        );
+        let result: Result<Site, _> = toml::from_str(&toml);
+
+        assert_eq!(result.is_ok(), valid);
+    }
+
+    #[test]
+    fn countries_config() {
+        let toml = r#"
+            domain = "example.com"
+
+            [countries.TW]
+            name = "Taiwan, Province of Formosa"
+
+            [countries.IS.regions]
+            "Capital Region" = "Höfuðborgarsvæðið"
+        "#;
+        let site: Site = toml::from_str(toml).unwrap();
+
+        assert_eq!(
+            site.countries["TW"].name.as_deref(),
+            Some("Taiwan, Province of Formosa")
+        );
+        assert_eq!(site.countries["IS"].name, None);
+        assert_eq!(
+            site.countries["IS"].regions["Capital Region"],
+            "Höfuðborgarsvæðið"
+        );
+    }
+
+    #[test]
+    fn feed_defaults() {
+        let toml = r#"domain = "example.com""#;
+        let site: Site = toml::from_str(toml).unwrap();
+
+        assert!(site.feed.site);
+        assert!(site.feed.json);
+        assert_eq!(site.feed.limit, 50);
+        assert_eq!(site.activitypub, None);
+        assert!(!site.feed.albums.includes(Path::new("travel")));
+    }
+
+    #[test]
+    fn feed_all_albums() {
+        let toml = r#"
+            domain = "example.com"
+
+            [feed]
+            site = false
+            albums = true
+        "#;
+        let site: Site = toml::from_str(toml).unwrap();
+
+        assert!(!site.feed.site);
+        assert!(site.feed.albums.includes(Path::new("travel")));
+    }
+
+    #[test]
+    fn feed_listed_albums() {
+        let toml = r#"
+            domain = "example.com"
+
+            [feed]
+            albums = ["travel/iceland", "365-project"]
+            limit = 10
+        "#;
+        let site: Site = toml::from_str(toml).unwrap();
+
+        assert_eq!(site.feed.limit, 10);
+        assert!(site.feed.albums.includes(Path::new("365-project")));
+        assert!(site.feed.albums.includes(Path::new("travel/iceland")));
+        assert!(!site.feed.albums.includes(Path::new("travel")));
+    }
+
+    #[test]
+    fn collections_config() {
+        let toml = r#"
+            domain = "example.com"
+
+            [collections.best-of]
+            photos = ["japan/kyoto", "sunset"]
+
+            [collections.portraits]
+            title = "People"
+            photos = []
+        "#;
+        let site: Site = toml::from_str(toml).unwrap();
+
+        assert_eq!(site.collections.len(), 2);
+        assert_eq!(site.collections["best-of"].title, None);
+        assert_eq!(
+            site.collections["best-of"].photos,
+            ["japan/kyoto", "sunset"]
+        );
+        assert_eq!(
+            site.collections["portraits"].title.as_deref(),
+            Some("People")
+        );
+    }
 
-    /// Site title (defaults to domain if not specified)
-    pub title: Option<String>,
+    #[test]
+    fn activitypub_config() {
+        let toml = r#"
+            domain = "example.com"
 
-    /// Theme configuration (defaults to "fancy").
-    ///
-    /// Supports both simple format (`theme = "fancy"`) and extended format
-    /// with settings (`[theme]` table).
-    ///
-    /// Resolution order (handled by Pipeline::load):
-    /// 1. Local directory with this name → use local
-    /// 2. Built-in theme with this name → use embedded
-    /// 3. Error
-    #[serde(default)]
-    pub theme: ThemeConfig,
+            [activitypub]
+            username = "photos"
+        "#;
+        let site: Site = toml::from_str(toml).unwrap();
 
-    /// Directory containing source photos (relative to site root)
-    #[serde(default = "default_photos")]
-    pub photos: PathBuf,
+        let activitypub = site.activitypub.unwrap();
+        assert_eq!(activitypub.username, "photos");
+        assert_eq!(activitypub.name, None);
+    }
 
-    /// Directory for build output (relative to site root)
-    #[serde(default = "default_build")]
-    pub build: PathBuf,
+    #[test]
+    fn activitypub_requires_username() {
+        let toml = r#"
+            domain = "example.com"
 
-    /// Whether to minify HTML, CSS, and JS output (defaults to true)
-    #[serde(default = "default_minify")]
-    pub minify: bool,
+            [activitypub]
+            name = "My Photos"
+        "#;
+        let result: Result<Site, _> = toml::from_str(toml);
 
-    /// GPS privacy mode (defaults to "on")
-    #[serde(default)]
-    pub gps: GpsMode,
+        assert!(result.is_err());
+    }
 
-    /// Languages to generate (defaults to English only)
-    /// Ignored if `all_languages` is true.
-    #[serde(default = "default_languages")]
-    pub languages: Vec<LangConfig>,
+    #[test]
+    fn comments_giscus() {
+        let toml = r#"
+            domain = "example.com"
 
-    /// Enable all supported languages (overrides `languages` list)
-    #[serde(default)]
-    pub all_languages: bool,
+            [comments]
+            provider = "giscus"
+            repo = "me/photos"
+            repo_id = "R_1"
+            category = "Comments"
+            category_id = "DIC_1"
+            pages = ["photo"]
+        "#;
+        let site: Site = toml::from_str(toml).unwrap();
 
-    /// Default language code (defaults to first in languages list)
-    pub default_language: Option<String>,
+        let comments = site.comments.unwrap();
+        assert!(matches!(
+            comments.provider,
+            CommentsProvider::Giscus { ref repo, ref theme, .. }
+                if repo == "me/photos" && theme == "preferred_color_scheme"
+        ));
+        assert_eq!(comments.pages, [PageKind::Photo]);
+        assert!(comments.inject);
+    }
 
-    /// Flatten album hierarchy - treat all photos as one gallery
-    #[serde(default)]
-    pub flatten: bool,
-}
+    #[test]
+    fn comments_isso_defaults() {
+        let toml = r#"
+            domain = "example.com"
 
-impl Site {
-    /// Returns the default language code.
-    pub fn default_lang(&self) -> String {
-        self.default_language.clone().unwrap_or_else(|| {
-            self.languages
-                .first()
-                .map(|l| l.code.clone())
-                .unwrap_or_else(|| "en".to_string())
-        })
+            [comments]
+            provider = "isso"
+            url = "https://comments.example.com"
+            inject = false
+        "#;
+        let site: Site = toml::from_str(toml).unwrap();
+
+        let comments = site.comments.unwrap();
+        assert_eq!(
+            comments.provider,
+            CommentsProvider::Isso {
+                url: "https://comments.example.com".to_string()
+            }
+        );
+        assert_eq!(comments.pages, [PageKind::Album, PageKind::Photo]);
+        assert!(!comments.inject);
     }
 
-    /// Returns the languages to use, respecting `all_languages` flag.
-    /// Names are resolved from built-in list if not specified.
-    pub fn languages(&self) -> Vec<ResolvedLangConfig> {
-        let builtin: std::collections::HashMap<&str, &str> = i18n::all_supported_languages()
-            .into_iter()
-            .map(|l| (l.code, l.name))
-            .collect();
+    #[test]
+    fn comments_unknown_provider_fails() {
+        let toml = r#"
+            domain = "example.com"
 
-        if self.all_languages {
-            i18n::all_supported_languages()
-                .into_iter()
-                .map(|l| ResolvedLangConfig {
-                    code: l.code.to_string(),
-                    name: l.name.to_string(),
-                })
-                .collect()
-        } else {
-            self.languages
-                .iter()
-                .map(|l| {
-                    let name = l.name.clone().unwrap_or_else(|| {
-                        builtin
-                            .get(l.code.as_str())
-                            .map(|s| s.to_string())
-                            .unwrap_or_else(|| l.code.clone())
-                    });
-                    ResolvedLangConfig {
-                        code: l.code.clone(),
-                        name,
-                    }
-                })
-                .collect()
-        }
-    }
-}
+            [comments]
+            provider = "disqus"
+        "#;
+        let result: Result<Site, _> = toml::from_str(toml);
 
-fn default_photos() -> PathBuf {
-    PathBuf::from("photos")
-}
+        assert!(result.is_err());
+    }
 
-fn default_build() -> PathBuf {
-    PathBuf::from("dist")
-}
+    #[test]
+    fn analytics_plausible_defaults() {
+        let toml = r#"
+            domain = "example.com"
 
-fn default_minify() -> bool {
-    true
-}
+            [analytics]
+            provider = "plausible"
+        "#;
+        let site: Site = toml::from_str(toml).unwrap();
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        let analytics = site.analytics.unwrap();
+        assert_eq!(
+            analytics.provider,
+            AnalyticsProvider::Plausible {
+                domain: None,
+                src: "https://plausible.io/js/script.js".to_string(),
+            }
+        );
+        assert!(analytics.dnt);
+    }
 
     #[test]
-    fn minimal_config() {
+    fn analytics_goatcounter() {
         let toml = r#"
-            domain = "photos.example.com"
-            theme = "themes/minimal"
+            domain = "example.com"
+
+            [analytics]
+            provider = "goatcounter"
+            code = "photos"
+            dnt = false
         "#;
         let site: Site = toml::from_str(toml).unwrap();
 
-        assert_eq!(site.domain, "photos.example.com");
-        assert_eq!(site.theme.name(), "themes/minimal");
-        assert!(site.theme.settings().is_empty());
-        assert_eq!(site.photos, PathBuf::from("photos"));
-        assert_eq!(site.build, PathBuf::from("dist"));
+        let analytics = site.analytics.unwrap();
+        assert_eq!(
+            analytics.provider,
+            AnalyticsProvider::GoatCounter {
+                code: "photos".to_string()
+            }
+        );
+        assert!(!analytics.dnt);
     }
 
     #[test]
-    fn full_config() {
+    fn checksums_with_ssh_signature() {
         let toml = r#"
-            domain = "photos.example.com"
-            theme = "my-theme"
-            photos = "albums/vacation"
-            build = "output"
+            domain = "example.com"
+
+            [checksums]
+            algorithm = "blake3"
+            sign = { tool = "ssh", key = "deploy_ed25519" }
         "#;
         let site: Site = toml::from_str(toml).unwrap();
 
-        assert_eq!(site.domain, "photos.example.com");
-        assert_eq!(site.theme.name(), "my-theme");
-        assert_eq!(site.photos, PathBuf::from("albums/vacation"));
-        assert_eq!(site.build, PathBuf::from("output"));
+        assert_eq!(
+            site.checksums,
+            Some(ChecksumsConfig {
+                algorithm: ChecksumAlgorithm::Blake3,
+                sign: Some(SignConfig::Ssh {
+                    key: PathBuf::from("deploy_ed25519")
+                }),
+            })
+        );
     }
 
     #[test]
-    fn missing_domain_fails() {
-        let toml = r#"theme = "default""#;
-        let result: Result<Site, _> = toml::from_str(toml);
+    fn originals_config() {
+        let toml = r#"
+            domain = "example.com"
 
-        assert!(result.is_err());
+            [originals]
+            url = "https://nas.example.com/photos/{path}"
+        "#;
+        let site: Site = toml::from_str(toml).unwrap();
+
+        assert_eq!(
+            site.originals,
+            Some(OriginalsConfig {
+                url: "https://nas.example.com/photos/{path}".to_string(),
+                manifest: PathBuf::from("originals.json"),
+            })
+        );
     }
 
     #[test]
-    fn default_theme() {
-        let toml = r#"domain = "example.com""#;
+    fn manifest_default_path() {
+        let toml = r#"
+            domain = "example.com"
+
+            [manifest]
+        "#;
         let site: Site = toml::from_str(toml).unwrap();
 
-        assert_eq!(site.theme.name(), "fancy");
+        assert_eq!(
+            site.manifest,
+            Some(ManifestConfig {
+                path: PathBuf::from("manifest.json"),
+                gallery_deltas: 0,
+            })
+        );
     }
 
     #[test]
-    fn theme_table_format() {
+    fn seo_keeps_default_description() {
         let toml = r#"
             domain = "example.com"
 
-            [theme]
-            name = "fancy"
-            slideshow_delay = 8000
-            default_sort = "date"
+            [seo]
+            title_template = "{site}: {photo}"
         "#;
         let site: Site = toml::from_str(toml).unwrap();
 
-        assert_eq!(site.theme.name(), "fancy");
+        assert_eq!(site.seo.title_template, "{site}: {photo}");
+        assert_eq!(site.seo.description_template, "{description}");
+        assert!(site.seo.structured_data);
+    }
+
+    #[test]
+    fn processing_partial_variant() {
+        let toml = r#"
+            domain = "example.com"
+
+            [processing]
+            thumb = { quality = 85 }
+        "#;
+        let site: Site = toml::from_str(toml).unwrap();
 
-        let settings = site.theme.settings();
         assert_eq!(
-            settings.get("slideshow_delay"),
-            Some(&toml::Value::Integer(8000))
+            site.processing.thumb,
+            VariantConfig {
+                size: None,
+                quality: Some(85),
+                target_kb: None,
+            }
         );
+        assert_eq!(site.processing.full, VariantConfig::default());
+    }
+
+    #[test]
+    fn processing_lossless() {
+        let toml = r#"
+            domain = "example.com"
+
+            [processing]
+            lossless_png = false
+            lossless_albums = ["screenshots", "scans/maps"]
+        "#;
+        let site: Site = toml::from_str(toml).unwrap();
+
+        assert!(!site.processing.lossless_png);
         assert_eq!(
-            settings.get("default_sort"),
-            Some(&toml::Value::String("date".to_string()))
+            site.processing.lossless_albums,
+            [PathBuf::from("screenshots"), PathBuf::from("scans/maps")]
         );
+        assert!(ProcessingConfig::default().lossless_png);
     }
 
     #[test]
-    fn theme_table_minimal() {
+    fn processing_target_size() {
         let toml = r#"
             domain = "example.com"
 
-            [theme]
-            name = "basic"
+            [processing]
+            full = { target_kb = 300 }
         "#;
         let site: Site = toml::from_str(toml).unwrap();
 
+        assert_eq!(site.processing.full.target_kb, Some(300));
+        assert_eq!(site.processing.full.quality, None);
+    }
+
+    #[test]
+    fn processing_threads() {
+        let toml = r#"
+            domain = "example.com"
+
+            [processing]
+            threads = 2
+        "#;
+        let mut site: Site = toml::from_str(toml).unwrap();
+        assert_eq!(site.processing.threads, NonZeroUsize::new(2));
+
+        Overrides::default().apply(&mut site);
+        assert_eq!(site.processing.threads, NonZeroUsize::new(2));
+
+        let overrides = Overrides {
+            theme: Some("basic".to_string()),
+            jobs: NonZeroUsize::new(1),
+            accept_changes: true,
+        };
+        overrides.apply(&mut site);
+        assert_eq!(site.processing.threads, NonZeroUsize::new(1));
         assert_eq!(site.theme.name(), "basic");
-        assert!(site.theme.settings().is_empty());
+        // Only held photos are affected
+        assert_eq!(site.reexports, Reexports::Warn);
+        site.reexports = Reexports::Hold;
+        overrides.apply(&mut site);
+        assert_eq!(site.reexports, Reexports::Allow);
+
+        let zero = "domain = \"example.com\"\n[processing]\nthreads = 0";
+        assert!(toml::from_str::<Site>(zero).is_err());
     }
 
     #[test]
-    fn gps_mode_default() {
-        let toml = r#"domain = "example.com""#;
-        let site: Site = toml::from_str(toml).unwrap();
+    fn reload_keeps_last_good_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("site.toml");
+        std::fs::write(&path, "domain = \"example.com\"\ntitle = \"Trips\"").unwrap();
 
-        assert_eq!(site.gps, GpsMode::On);
+        let mut reloader = Reloader::new(path.clone());
+        std::fs::write(&path, "domain = \"example.com\"\ntitle = ").unwrap();
+        assert_eq!(reloader.load().unwrap().title.as_deref(), Some("Trips"));
+
+        std::fs::write(&path, "domain = \"example.com\"\ntitle = \"Travel\"").unwrap();
+        assert_eq!(reloader.load().unwrap().title.as_deref(), Some("Travel"));
+
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(reloader.load().unwrap().title.as_deref(), Some("Travel"));
     }
 
     #[test]
-    fn gps_mode_off() {
+    fn reload_fails_without_good_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("site.toml");
+        std::fs::write(&path, "domain = ").unwrap();
+
+        let mut reloader = Reloader::new(path);
+        assert!(matches!(reloader.load(), Err(Error::Config(_))));
+    }
+
+    #[test]
+    fn sort_and_filter() {
         let toml = r#"
             domain = "example.com"
-            gps = "off"
+            sort = "rating"
+
+            [filter]
+            picks = true
+            labels = ["Green"]
         "#;
         let site: Site = toml::from_str(toml).unwrap();
 
-        assert_eq!(site.gps, GpsMode::Off);
+        assert_eq!(site.sort, PhotoSort::Rating);
+        let filter = site.filter.unwrap();
+        assert!(filter.picks);
+        assert_eq!(filter.min_rating, None);
+        assert_eq!(filter.labels, vec!["Green"]);
+    }
+
+    #[test_case("3:1", Some((3, 1)) ; "simple")]
+    #[test_case("16 : 9", Some((16, 9)) ; "spaces")]
+    #[test_case("6:2", Some((3, 1)) ; "reduced")]
+    #[test_case("square", Some((1, 1)) ; "square")]
+    #[test_case("3x1", None ; "wrong separator")]
+    #[test_case("0:1", None ; "zero")]
+    fn test_aspect_ratio(value: &str, expected: Option<(u32, u32)>) {
+        let ratio = AspectRatio::try_from(value.to_string()).ok();
+
+        assert_eq!(ratio.map(|r| (r.width, r.height)), expected);
     }
 
     #[test]
-    fn gps_mode_general() {
+    fn crops() {
         let toml = r#"
             domain = "example.com"
-            gps = "general"
+
+            [crops]
+            banner = "3:1"
         "#;
         let site: Site = toml::from_str(toml).unwrap();
 
-        assert_eq!(site.gps, GpsMode::General);
+        assert_eq!(
+            site.crops["banner"],
+            AspectRatio {
+                width: 3,
+                height: 1
+            }
+        );
+        assert!(
+            toml::from_str::<Site>("domain = \"example.com\"\ncrops = { banner = \"wide\" }")
+                .is_err()
+        );
     }
 
     #[test]
-    fn gps_mode_on() {
+    fn watermark() {
         let toml = r#"
             domain = "example.com"
-            gps = "on"
+
+            [watermark]
+            text = "© Jane Doe"
+            position = "top-left"
         "#;
         let site: Site = toml::from_str(toml).unwrap();
 
-        assert_eq!(site.gps, GpsMode::On);
+        let watermark = site.watermark.unwrap();
+        assert_eq!(watermark.text.as_deref(), Some("© Jane Doe"));
+        assert_eq!(watermark.image, None);
+        assert_eq!(watermark.position, WatermarkPosition::TopLeft);
+        assert_eq!(watermark.opacity, 0.5);
+        assert_eq!(watermark.scale, 0.2);
     }
 }
@@ -0,0 +1,226 @@
+//! Country names, flags, and region names.
+//!
+//! The built-in data in `data/countries.toml` covers every ISO 3166-1
+//! country and dependent territory. Geocoders report regions in different
+//! spellings (the offline one uses ASCII names like "Baden-Wuerttemberg"),
+//! so each country can map reported region names to the name to display.
+//! Sites can rename countries, add aliases, and fix regions in `site.toml`.
+
+use std::collections::BTreeMap;
+use std::sync::LazyLock;
+
+use crate::config::CountryConfig;
+
+/// Built-in country data, keyed by ISO 3166-1 alpha-2 code.
+const BUILTIN: &str = include_str!("data/countries.toml");
+
+/// A country or territory.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Country {
+    /// ISO 3166-1 alpha-2 code (e.g., "IS")
+    pub code: String,
+    /// Display name (e.g., "Iceland")
+    pub name: String,
+    /// Flag emoji (e.g., "🇮🇸")
+    pub flag: String,
+    /// Reported region names (lowercase) to display names
+    regions: BTreeMap<String, String>,
+}
+
+impl Country {
+    /// Display name for a region as reported by a geocoder.
+    ///
+    /// Unknown regions are returned unchanged.
+    pub fn region(&self, name: &str) -> String {
+        self.regions
+            .get(&name.to_lowercase())
+            .cloned()
+            .unwrap_or_else(|| name.to_string())
+    }
+}
+
+/// Looks up countries by code or name.
+#[derive(Debug, Clone)]
+pub struct Registry {
+    countries: BTreeMap<String, Country>,
+    /// Lowercase names, aliases, and codes to codes
+    names: BTreeMap<String, String>,
+}
+
+impl Registry {
+    /// The built-in country data.
+    pub fn builtin() -> &'static Registry {
+        static REGISTRY: LazyLock<Registry> = LazyLock::new(|| {
+            let entries: BTreeMap<String, CountryConfig> =
+                toml::from_str(BUILTIN).expect("built-in country data is valid");
+            let mut registry = Registry {
+                countries: BTreeMap::new(),
+                names: BTreeMap::new(),
+            };
+            for (code, entry) in &entries {
+                registry.apply(code, entry);
+            }
+            registry
+        });
+
+        &REGISTRY
+    }
+
+    /// The built-in data with a site's changes applied.
+    ///
+    /// A changed `name` replaces the built-in one, while aliases and regions
+    /// are added to it. Countries that aren't built in need a name.
+    pub fn new(overrides: &BTreeMap<String, CountryConfig>) -> Self {
+        let mut registry = Self::builtin().clone();
+        for (code, entry) in overrides {
+            if !is_code(code) {
+                tracing::warn!(code = %code, "ignoring country with invalid ISO 3166-1 alpha-2 code");
+            } else if entry.name.is_none() && registry.get(code).is_none() {
+                tracing::warn!(code = %code, "ignoring unknown country without a name");
+            } else {
+                registry.apply(code, entry);
+            }
+        }
+        registry
+    }
+
+    fn apply(&mut self, code: &str, entry: &CountryConfig) {
+        let code = code.to_ascii_uppercase();
+        let country = self
+            .countries
+            .entry(code.clone())
+            .or_insert_with(|| Country {
+                code: code.clone(),
+                name: code.clone(),
+                flag: flag(&code),
+                regions: BTreeMap::new(),
+            });
+
+        if let Some(name) = &entry.name {
+            country.name = name.clone();
+        }
+        for (reported, display) in &entry.regions {
+            country
+                .regions
+                .insert(reported.to_lowercase(), display.clone());
+        }
+
+        let names = [&code, &country.name].into_iter().chain(&entry.aliases);
+        for name in names {
+            self.names.insert(name.to_lowercase(), code.clone());
+        }
+    }
+
+    /// Find a country by ISO 3166-1 alpha-2 code (case-insensitive).
+    pub fn get(&self, code: &str) -> Option<&Country> {
+        self.countries.get(&code.to_ascii_uppercase())
+    }
+
+    /// Find a country by code, name, or alias (case-insensitive).
+    pub fn find(&self, name: &str) -> Option<&Country> {
+        let code = self.names.get(&name.trim().to_lowercase())?;
+        self.countries.get(code)
+    }
+
+    /// All known countries, ordered by code.
+    pub fn iter(&self) -> impl Iterator<Item = &Country> {
+        self.countries.values()
+    }
+}
+
+/// Whether `code` looks like an ISO 3166-1 alpha-2 code.
+fn is_code(code: &str) -> bool {
+    code.len() == 2 && code.chars().all(|c| c.is_ascii_alphabetic())
+}
+
+/// Convert ISO 3166-1 alpha-2 country code to flag emoji.
+/// Each letter is converted to a regional indicator symbol.
+pub fn flag(code: &str) -> String {
+    code.chars()
+        .filter_map(|c| {
+            let c = c.to_ascii_uppercase();
+            if c.is_ascii_uppercase() {
+                // Regional indicator symbols start at U+1F1E6 for 'A'
+                let offset = c as u32 - 'A' as u32;
+                char::from_u32(0x1F1E6 + offset)
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case("IS", Some("Iceland") ; "country")]
+    #[test_case("re", Some("Réunion") ; "dependent territory lowercase")]
+    #[test_case("JE", Some("Jersey") ; "crown dependency")]
+    #[test_case("ZZ", None ; "unknown")]
+    fn test_get(code: &str, expected: Option<&str>) {
+        let country = Registry::builtin().get(code);
+
+        assert_eq!(country.map(|c| c.name.as_str()), expected);
+    }
+
+    #[test_case("is", Some("IS") ; "code")]
+    #[test_case("iceland", Some("IS") ; "name")]
+    #[test_case(" United States of America ", Some("US") ; "alias")]
+    #[test_case("Atlantis", None ; "unknown")]
+    fn test_find(name: &str, expected: Option<&str>) {
+        let country = Registry::builtin().find(name);
+
+        assert_eq!(country.map(|c| c.code.as_str()), expected);
+    }
+
+    #[test_case("DE", "Baden-Wuerttemberg", "Baden-Württemberg" ; "restores diacritics")]
+    #[test_case("CN", "zhejiang sheng", "Zhejiang" ; "drops suffix case-insensitively")]
+    #[test_case("IS", "Capital Region", "Capital Region" ; "unchanged")]
+    fn test_region(code: &str, region: &str, expected: &str) {
+        let country = Registry::builtin().get(code).unwrap();
+
+        assert_eq!(country.region(region), expected);
+    }
+
+    #[test]
+    fn every_country_has_a_flag() {
+        for country in Registry::builtin().iter() {
+            assert_eq!(country.flag.chars().count(), 2, "{}", country.code);
+        }
+    }
+
+    #[test]
+    fn overrides_extend_builtin_data() {
+        let overrides = toml::from_str(
+            r#"
+                [IS]
+                name = "Ísland"
+                aliases = ["Lýðveldið Ísland"]
+                regions = { "Capital Region" = "Höfuðborgarsvæðið" }
+
+                [XX]
+                name = "Freedonia"
+
+                [YY]
+                aliases = ["Nowhere"]
+            "#,
+        )
+        .unwrap();
+
+        let registry = Registry::new(&overrides);
+        let iceland = registry.find("lýðveldið ísland").unwrap();
+
+        assert_eq!(iceland.name, "Ísland");
+        assert_eq!(iceland.region("Capital Region"), "Höfuðborgarsvæðið");
+        assert_eq!(registry.find("Iceland"), Some(iceland));
+        assert_eq!(registry.get("XX").unwrap().name, "Freedonia");
+        assert_eq!(registry.get("YY"), None);
+    }
+
+    #[test]
+    fn flag_from_code() {
+        assert_eq!(flag("jp"), "🇯🇵");
+    }
+}
@@ -0,0 +1,184 @@
+//! Named aspect ratio crops of album covers, and cropped thumbnails.
+//!
+//! `[crops]` names aspect ratios like `banner = "3:1"`, and each album's
+//! cover is cropped to every one of them for themes to use as hero images.
+//! `thumb_crop` in `[processing]` crops every thumbnail the same way.
+//! A crop keeps the whole width or height of the photo and slides along the
+//! other side to wherever there's the most detail, measured as edge strength
+//! on a small grayscale copy. Featureless sky or wall loses out to the
+//! subject, and when nothing stands out the crop stays centered.
+
+use std::collections::BTreeMap;
+
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImageView, GrayImage};
+
+use crate::config::AspectRatio;
+use crate::error::{Error, Result};
+
+/// Longest side of the copy used to find detail, plenty to locate a subject.
+const ANALYSIS_SIZE: u32 = 128;
+
+/// Check that crop names work as template keys (`album.cover.{name}_path`).
+pub fn validate(crops: &BTreeMap<String, AspectRatio>) -> Result<()> {
+    for name in crops.keys() {
+        let valid = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+        if !valid {
+            return Err(Error::Other(format!(
+                "invalid crop name '{}' in [crops] (use lowercase letters, digits, and underscores)",
+                name
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// The region of `img` to keep for `ratio`, as `(x, y, width, height)`.
+pub fn region(img: &DynamicImage, ratio: AspectRatio) -> (u32, u32, u32, u32) {
+    let (width, height) = img.dimensions();
+    let (crop_width, crop_height) = crop_size(width, height, ratio);
+    if (crop_width, crop_height) == (width, height) {
+        return (0, 0, width, height);
+    }
+
+    let small = img
+        .resize(ANALYSIS_SIZE, ANALYSIS_SIZE, FilterType::Triangle)
+        .to_luma8();
+    let (columns, rows) = edge_energy(&small);
+    let (energy, length, crop_length) = if crop_width < width {
+        (columns, width, crop_width)
+    } else {
+        (rows, height, crop_height)
+    };
+
+    // Find the best window on the small copy, then scale its start back up
+    let window = (u64::from(crop_length) * energy.len() as u64 / u64::from(length)) as usize;
+    let start = best_window(&energy, window.clamp(1, energy.len()));
+    let offset = (start as u64 * u64::from(length) / energy.len() as u64) as u32;
+    let offset = offset.min(length - crop_length);
+
+    if crop_width < width {
+        (offset, 0, crop_width, crop_height)
+    } else {
+        (0, offset, crop_width, crop_height)
+    }
+}
+
+/// Largest `ratio` crop of a `width` by `height` image.
+fn crop_size(width: u32, height: u32, ratio: AspectRatio) -> (u32, u32) {
+    let (w, h) = (u64::from(width), u64::from(height));
+    let (rw, rh) = (u64::from(ratio.width), u64::from(ratio.height));
+    if w * rh > h * rw {
+        (((h * rw + rh / 2) / rh).clamp(1, w) as u32, height)
+    } else {
+        (width, ((w * rh + rw / 2) / rw).clamp(1, h) as u32)
+    }
+}
+
+/// Sum of brightness differences to neighboring pixels, per column and per row.
+fn edge_energy(img: &GrayImage) -> (Vec<u64>, Vec<u64>) {
+    let (width, height) = img.dimensions();
+    let mut columns = vec![0; width as usize];
+    let mut rows = vec![0; height as usize];
+    for y in 0..height {
+        for x in 0..width {
+            let value = i32::from(img.get_pixel(x, y).0[0]);
+            let mut energy = 0;
+            if x + 1 < width {
+                energy += (value - i32::from(img.get_pixel(x + 1, y).0[0])).unsigned_abs();
+            }
+            if y + 1 < height {
+                energy += (value - i32::from(img.get_pixel(x, y + 1).0[0])).unsigned_abs();
+            }
+            columns[x as usize] += u64::from(energy);
+            rows[y as usize] += u64::from(energy);
+        }
+    }
+    (columns, rows)
+}
+
+/// Start of the `window` entries of `energy` with the highest sum, preferring
+/// the most central on ties.
+fn best_window(energy: &[u64], window: usize) -> usize {
+    let last = energy.len() - window;
+    let distance = |start: usize| (start * 2).abs_diff(last);
+
+    let mut sum: u64 = energy[..window].iter().sum();
+    let mut best = (sum, 0);
+    for start in 1..=last {
+        sum = sum - energy[start - 1] + energy[start + window - 1];
+        if sum > best.0 || (sum == best.0 && distance(start) < distance(best.1)) {
+            best = (sum, start);
+        }
+    }
+    best.1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+    use test_case::test_case;
+
+    fn ratio(width: u32, height: u32) -> AspectRatio {
+        AspectRatio { width, height }
+    }
+
+    #[test_case(6000, 4000, 3, 1, (6000, 2000) ; "wider ratio")]
+    #[test_case(6000, 4000, 1, 1, (4000, 4000) ; "square")]
+    #[test_case(4000, 6000, 16, 9, (4000, 2250) ; "portrait to wide")]
+    #[test_case(3000, 1000, 3, 1, (3000, 1000) ; "same ratio")]
+    fn test_crop_size(width: u32, height: u32, rw: u32, rh: u32, expected: (u32, u32)) {
+        assert_eq!(crop_size(width, height, ratio(rw, rh)), expected);
+    }
+
+    #[test_case(&[0, 0, 0, 0, 0], 3, 1 ; "flat stays centered")]
+    #[test_case(&[9, 9, 0, 0, 0], 2, 0 ; "detail at start")]
+    #[test_case(&[0, 0, 1, 5, 5], 2, 3 ; "detail at end")]
+    #[test_case(&[1, 2, 3], 3, 0 ; "whole")]
+    fn test_best_window(energy: &[u64], window: usize, expected: usize) {
+        assert_eq!(best_window(energy, window), expected);
+    }
+
+    #[test]
+    fn crop_follows_detail() {
+        // Plain image with a checkerboard near the right edge
+        let mut img = RgbImage::from_pixel(600, 200, Rgb([90, 140, 200]));
+        for y in 60..140 {
+            for x in 460..540 {
+                if (x / 10 + y / 10) % 2 == 0 {
+                    img.put_pixel(x, y, Rgb([0, 0, 0]));
+                }
+            }
+        }
+        let img = DynamicImage::ImageRgb8(img);
+
+        let (x, y, width, height) = region(&img, ratio(1, 1));
+
+        assert_eq!((y, width, height), (0, 200, 200));
+        assert!(
+            x <= 460 && x + width >= 540,
+            "crop at {x} misses the detail"
+        );
+    }
+
+    #[test]
+    fn plain_image_center_cropped() {
+        let img = DynamicImage::new_rgb8(400, 400);
+
+        assert_eq!(region(&img, ratio(2, 1)), (0, 100, 400, 200));
+    }
+
+    #[test_case("banner", true ; "simple")]
+    #[test_case("hero_2", true ; "digits and underscores")]
+    #[test_case("Banner", false ; "uppercase")]
+    #[test_case("hero-wide", false ; "dash")]
+    fn test_validate(name: &str, valid: bool) {
+        let crops = BTreeMap::from([(name.to_string(), ratio(3, 1))]);
+
+        assert_eq!(validate(&crops).is_ok(), valid);
+    }
+}
@@ -0,0 +1,330 @@
+//! Long-running build server for containers.
+//!
+//! `galerie serve --daemon` builds, serves, and rebuilds on changes like
+//! `serve`, but is meant to run unattended, for example as a container on a
+//! NAS. A failed build doesn't stop it: the last good output stays up and
+//! the next change is built again. `/healthz` answers while the process
+//! runs and `/readyz` once a build has succeeded, both with the build status
+//! as JSON. With a rebuild token, `POST /_galerie/rebuild` starts a rebuild
+//! at once (see `serve::Trigger`). SIGTERM and SIGINT let a running build finish before exiting
+//! (a second signal exits at once). Startup logs the paths and tools in use
+//! and fails early when the output directory isn't writable. Under systemd,
+//! readiness is reported after the initial build, and watchdog pings come
+//! from the server loop, so they continue during long builds.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+use signal_hook::consts::{SIGINT, SIGTERM};
+use tiny_http::{Header, Request, Response};
+
+use crate::cache;
+use crate::config::{Overrides, Reloader, Site};
+use crate::error::{Error, Result};
+use crate::serve;
+use crate::systemd;
+use crate::watch;
+
+/// How often the server checks for a stop signal between requests.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Liveness endpoint, answered while the process runs.
+const HEALTH_PATH: &str = "/healthz";
+
+/// Readiness endpoint, answered with 200 once a build has succeeded.
+const READY_PATH: &str = "/readyz";
+
+/// Outcome of the builds so far, as reported by the health endpoints.
+#[derive(Debug, Default, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Status {
+    /// A build has succeeded since startup
+    ready: bool,
+    /// A build is running
+    building: bool,
+    /// Unix time the last successful build finished
+    last_success: Option<u64>,
+    /// Error of the last build, if it failed
+    last_error: Option<String>,
+    /// Generation of the build being served, counting the initial build as 1
+    generation: Option<u64>,
+}
+
+/// Build, serve, and rebuild the site until stopped by a signal.
+pub fn run(
+    site_dir: PathBuf,
+    config_path: PathBuf,
+    overrides: Overrides,
+    source_maps: bool,
+    port: u16,
+    debounce: u64,
+) -> Result<()> {
+    let stop = Arc::new(AtomicBool::new(false));
+    for signal in [SIGTERM, SIGINT] {
+        // The first signal asks to stop; a second one exits immediately
+        signal_hook::flag::register_conditional_shutdown(signal, 1, Arc::clone(&stop))?;
+        signal_hook::flag::register(signal, Arc::clone(&stop))?;
+    }
+
+    let mut site = Site::load(&config_path)?;
+    overrides.apply(&mut site);
+    let output_dir = site_dir.join(&site.build);
+    diagnostics(&site_dir, &config_path, &site, port, debounce)?;
+    let tools = serve::Tools {
+        rebuild: serve::Trigger::new(site.rebuild_token.as_deref()),
+        ..Default::default()
+    };
+    let rebuild = tools
+        .rebuild
+        .as_ref()
+        .map(|trigger| Arc::clone(&trigger.requested))
+        .unwrap_or_default();
+    if tools.rebuild.is_some() {
+        tracing::info!("rebuilds can be requested with POST /_galerie/rebuild");
+    }
+
+    let status = Arc::new(Mutex::new(Status::default()));
+    let server = serve::bind(port)?;
+    tracing::info!(url = %format!("http://localhost:{}", port), "serving site");
+
+    // Builds run on their own thread so the health endpoints answer meanwhile
+    let builder = {
+        let stop = Arc::clone(&stop);
+        let status = Arc::clone(&status);
+        std::thread::spawn(move || {
+            let mut config = Reloader::new(config_path.clone());
+            let mut build = |generation, cancel: &Arc<AtomicBool>| {
+                status.lock().unwrap().building = true;
+                let result = watch::do_build_with_options(
+                    &site_dir,
+                    &mut config,
+                    &overrides,
+                    source_maps,
+                    cancel,
+                );
+                record(&mut status.lock().unwrap(), &result, generation, now());
+                result
+            };
+
+            tracing::info!("performing initial build");
+            let result = build(1, &Arc::default());
+            if let Err(e) = &result {
+                tracing::error!(error = %e, hint = e.hint(), "initial build failed");
+            }
+            let live = result.is_ok().then_some(1);
+            systemd::ready(&watch::status_line(&result, live));
+            watch::watch_until(
+                &site_dir,
+                &config_path,
+                Duration::from_secs(debounce),
+                live,
+                &stop,
+                &rebuild,
+                watch::Events::default(),
+                build,
+            )
+        })
+    };
+
+    while !stop.load(Ordering::Relaxed) && !builder.is_finished() {
+        systemd::watchdog();
+        match server.recv_timeout(POLL_INTERVAL) {
+            Ok(Some(request)) => {
+                let status = status.lock().unwrap().clone();
+                if let Err(e) = respond(request, &output_dir, &status, &tools) {
+                    tracing::debug!(error = %e, "failed to answer request");
+                }
+            }
+            Ok(None) => {}
+            Err(e) => tracing::warn!(error = %e, "failed to receive request"),
+        }
+    }
+
+    if status.lock().unwrap().building {
+        tracing::info!("stopping after the running build");
+    } else {
+        tracing::info!("stopping");
+    }
+    systemd::stopping();
+    stop.store(true, Ordering::Relaxed);
+    builder
+        .join()
+        .map_err(|_| Error::Other("build thread panicked".to_string()))?
+}
+
+/// Update `status` with the result of build `generation`, finished at `finished_at`.
+fn record<T>(status: &mut Status, result: &Result<T>, generation: u64, finished_at: u64) {
+    status.building = false;
+    match result {
+        Ok(_) => {
+            status.ready = true;
+            status.last_success = Some(finished_at);
+            status.last_error = None;
+            status.generation = Some(generation);
+        }
+        // A superseded build is followed by another
+        Err(Error::Cancelled) => {}
+        Err(e) => status.last_error = Some(e.to_string()),
+    }
+}
+
+/// Answer a health check, or serve a file from `output_dir` or one of `tools`.
+fn respond(
+    request: Request,
+    output_dir: &Path,
+    status: &Status,
+    tools: &serve::Tools,
+) -> Result<()> {
+    let path = request.url().split('?').next().unwrap_or_default();
+    let code = match path {
+        HEALTH_PATH => 200,
+        READY_PATH if status.ready => 200,
+        READY_PATH => 503,
+        _ => return serve::respond(request, output_dir, tools),
+    };
+
+    let body = serde_json::to_string(status).map_err(std::io::Error::other)?;
+    let response = Response::from_string(body)
+        .with_status_code(code)
+        .with_header(Header::from_bytes("Content-Type", "application/json").unwrap())
+        .with_header(Header::from_bytes("Cache-Control", "no-store").unwrap());
+    request.respond(response)?;
+    Ok(())
+}
+
+/// Log what the daemon will use, and fail if the output can't be written.
+fn diagnostics(
+    site_dir: &Path,
+    config_path: &Path,
+    site: &Site,
+    port: u16,
+    debounce: u64,
+) -> Result<()> {
+    let photos_dir = site_dir.join(&site.photos);
+    let output_dir = site_dir.join(&site.build);
+    let cache_path = cache::path(site_dir);
+    let video = which::which("ffmpeg").is_ok() && which::which("ffprobe").is_ok();
+
+    tracing::info!(
+        version = env!("GIT_VERSION"),
+        site = %site_dir.display(),
+        config = %config_path.display(),
+        photos = %photos_dir.display(),
+        output = %output_dir.display(),
+        cache = %cache_path.display(),
+        theme = %site.theme,
+        port,
+        debounce,
+        video,
+        heif = cfg!(feature = "heif"),
+        "starting daemon"
+    );
+
+    if !photos_dir.is_dir() {
+        tracing::warn!(path = %photos_dir.display(), "photos directory doesn't exist yet");
+    }
+    if let Err(e) = check_writable(&output_dir) {
+        return Err(Error::Other(format!(
+            "output directory {} isn't writable: {}",
+            output_dir.display(),
+            e
+        )));
+    }
+    if let Some(dir) = cache_path.parent()
+        && let Err(e) = check_writable(dir)
+    {
+        tracing::warn!(
+            path = %dir.display(),
+            error = %e,
+            "metadata cache can't be saved; set {} to a writable directory",
+            cache::DIR_ENV_VAR
+        );
+    }
+    Ok(())
+}
+
+/// Create `dir` if needed and check a file can be written in it.
+fn check_writable(dir: &Path) -> std::io::Result<()> {
+    fs::create_dir_all(dir)?;
+    let probe = dir.join(".galerie-write-check");
+    fs::write(&probe, b"")?;
+    fs::remove_file(&probe)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failed_build_keeps_last_success() {
+        let mut status = Status {
+            building: true,
+            ..Default::default()
+        };
+
+        record(&mut status, &Ok(()), 1, 100);
+        status.building = true;
+        record(
+            &mut status,
+            &Err::<(), _>(Error::Other("no photos".to_string())),
+            2,
+            200,
+        );
+
+        assert!(status.ready);
+        assert!(!status.building);
+        assert_eq!(status.last_success, Some(100));
+        assert_eq!(status.last_error.as_deref(), Some("no photos"));
+        assert_eq!(status.generation, Some(1));
+    }
+
+    #[test]
+    fn superseded_build_not_an_error() {
+        let mut status = Status::default();
+
+        record(&mut status, &Ok(()), 1, 100);
+        status.building = true;
+        record(&mut status, &Err::<(), _>(Error::Cancelled), 2, 200);
+
+        assert!(!status.building);
+        assert_eq!(status.last_error, None);
+        assert_eq!(status.generation, Some(1));
+    }
+
+    #[test]
+    fn status_json() {
+        let status = Status {
+            ready: true,
+            last_success: Some(100),
+            generation: Some(3),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            serde_json::to_string(&status).unwrap(),
+            r#"{"ready":true,"building":false,"lastSuccess":100,"lastError":null,"generation":3}"#
+        );
+    }
+
+    #[test]
+    fn writable_directory_created() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("dist");
+
+        check_writable(&output).unwrap();
+
+        assert!(output.is_dir());
+        assert_eq!(fs::read_dir(&output).unwrap().count(), 0);
+    }
+}
@@ -0,0 +1,522 @@
+//! Deploy targets: where `galerie publish` uploads the output, and how a
+//! deploy is undone.
+//!
+//! A shell command can upload anywhere, but galerie can't know how to put
+//! back what it replaced. A git branch, as used for GitHub Pages, is rolled
+//! back with a new commit restoring an earlier deploy's tree; a versioned S3
+//! bucket by copying each object's earlier version back over it and deleting
+//! objects added since. Both go through the `git` and `aws` command line
+//! tools, so their usual credentials apply. What a rollback needs to get
+//! back to a deploy is recorded with it as a [`State`].
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process::{Command, ExitStatus};
+
+use serde::{Deserialize, Serialize};
+use walkdir::WalkDir;
+
+use crate::config::{DeployConfig, GitDeploy, S3Deploy};
+use crate::error::{Error, Result};
+use crate::util;
+
+/// Environment variable holding the output directory for the deploy command.
+pub const OUTPUT_ENV_VAR: &str = "GALERIE_OUTPUT";
+
+/// Clone of the deploy branch, relative to the publish directory.
+const GIT_DIR: &str = "git";
+
+/// Identity for deploy commits when git has none configured.
+const GIT_IDENTITY: [&str; 4] = [
+    "-c",
+    "user.name=galerie",
+    "-c",
+    "user.email=galerie@localhost",
+];
+
+/// A step's result.
+type Outcome<T> = std::result::Result<T, DeployError>;
+
+/// Why uploading to a target or restoring it failed.
+#[derive(Debug, thiserror::Error)]
+pub enum DeployError {
+    #[error("set exactly one of command, git, or s3 in [deploy]")]
+    Targets,
+
+    #[error("can't run {tool}: {source}")]
+    Spawn {
+        tool: &'static str,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("{command} exited with {status}")]
+    Exited {
+        command: &'static str,
+        status: ExitStatus,
+    },
+
+    #[error("{command} failed: {stderr}")]
+    Failed { command: String, stderr: String },
+
+    #[error("can't copy the output: {0}")]
+    Copy(#[source] io::Error),
+
+    #[error("can't read the object versions: {0}")]
+    Versions(#[from] serde_json::Error),
+
+    #[error("a deploy command can't be rolled back; publish an earlier build instead")]
+    CommandRollback,
+
+    #[error("the deploy to roll back to went to a different target")]
+    OtherTarget,
+}
+
+/// What the target held after a deploy, enough to go back to it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "target", rename_all = "lowercase")]
+pub enum State {
+    Git {
+        commit: String,
+    },
+    S3 {
+        /// Version ID of each object, by key
+        versions: BTreeMap<String, String>,
+    },
+}
+
+/// The one target `[deploy]` sets.
+enum Target<'a> {
+    Command(&'a str),
+    Git(&'a GitDeploy),
+    S3(&'a S3Deploy),
+}
+
+fn target(config: &DeployConfig) -> Result<Target<'_>> {
+    match (&config.command, &config.git, &config.s3) {
+        (Some(command), None, None) => Ok(Target::Command(command)),
+        (None, Some(git), None) => Ok(Target::Git(git)),
+        (None, None, Some(s3)) => Ok(Target::S3(s3)),
+        _ => Err(Error::Deploy {
+            stage: "deploy",
+            source: DeployError::Targets,
+        }),
+    }
+}
+
+/// Where `config` deploys to, for logs and the journal.
+pub fn describe(config: &DeployConfig) -> String {
+    match target(config) {
+        Ok(Target::Command(command)) => command.to_string(),
+        Ok(Target::Git(git)) => format!("{} {}", git.repo, git.branch),
+        Ok(Target::S3(s3)) => s3_url(s3),
+        Err(_) => "invalid [deploy]".to_string(),
+    }
+}
+
+/// Upload `output_dir`, keeping working files in `publish_dir`. Returns the
+/// state to roll back to, if the target supports it.
+pub fn upload(
+    config: &DeployConfig,
+    site_dir: &Path,
+    output_dir: &Path,
+    publish_dir: &Path,
+) -> Result<Option<State>> {
+    let result = match target(config)? {
+        Target::Command(command) => run_command(command, site_dir, output_dir).map(|()| None),
+        Target::Git(git) => git_upload(git, output_dir, &publish_dir.join(GIT_DIR))
+            .map(|commit| Some(State::Git { commit })),
+        Target::S3(s3) => s3_upload(s3, output_dir),
+    };
+    result.map_err(|source| Error::Deploy {
+        stage: "deploy",
+        source,
+    })
+}
+
+/// Put the target back to `state`.
+pub fn restore(config: &DeployConfig, state: &State, publish_dir: &Path) -> Result<()> {
+    let result = match (target(config)?, state) {
+        (Target::Git(git), State::Git { commit }) => {
+            git_restore(git, commit, &publish_dir.join(GIT_DIR))
+        }
+        (Target::S3(s3), State::S3 { versions }) => s3_restore(s3, versions),
+        (Target::Command(_), _) => Err(DeployError::CommandRollback),
+        _ => Err(DeployError::OtherTarget),
+    };
+    result.map_err(|source| Error::Deploy {
+        stage: "roll back",
+        source,
+    })
+}
+
+/// Run the deploy command from `site_dir`, showing its output.
+fn run_command(command: &str, site_dir: &Path, output_dir: &Path) -> Outcome<()> {
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .current_dir(site_dir)
+        .env(OUTPUT_ENV_VAR, output_dir)
+        .status()
+        .map_err(|source| DeployError::Spawn {
+            tool: "the command",
+            source,
+        })?;
+    if !status.success() {
+        return Err(DeployError::Exited {
+            command: "the command",
+            status,
+        });
+    }
+    Ok(())
+}
+
+/// Commit the output to the deploy branch and push it.
+fn git_upload(target: &GitDeploy, output_dir: &Path, repo: &Path) -> Outcome<String> {
+    checkout(target, repo)?;
+    mirror(output_dir, repo).map_err(DeployError::Copy)?;
+    git(repo, &["add", "-A"])?;
+    let message = format!("Publish with galerie {}", env!("CARGO_PKG_VERSION"));
+    commit_and_push(target, repo, &message)
+}
+
+/// Commit the tree of `commit` on top of the deploy branch and push it.
+fn git_restore(target: &GitDeploy, commit: &str, repo: &Path) -> Outcome<()> {
+    checkout(target, repo)?;
+    git(repo, &["read-tree", "-u", "--reset", commit])?;
+    let short = &commit[..commit.len().min(12)];
+    commit_and_push(target, repo, &format!("Roll back to {}", short))?;
+    Ok(())
+}
+
+/// Bring the clone in `repo` to the tip of the deploy branch, creating
+/// either if needed.
+fn checkout(target: &GitDeploy, repo: &Path) -> Outcome<()> {
+    if repo.join(".git").is_dir() {
+        git(repo, &["remote", "set-url", "origin", &target.repo])?;
+    } else {
+        fs::create_dir_all(repo).map_err(DeployError::Copy)?;
+        git(repo, &["init", "-q"])?;
+        git(repo, &["remote", "add", "origin", &target.repo])?;
+    }
+    // The branch doesn't exist until the first deploy pushes it
+    if git(repo, &["fetch", "-q", "origin", &target.branch]).is_ok() {
+        git(
+            repo,
+            &["checkout", "-q", "-f", "-B", &target.branch, "FETCH_HEAD"],
+        )?;
+    } else {
+        tracing::info!(branch = %target.branch, "creating deploy branch");
+        let head = format!("refs/heads/{}", target.branch);
+        git(repo, &["symbolic-ref", "HEAD", &head])?;
+    }
+    Ok(())
+}
+
+fn commit_and_push(target: &GitDeploy, repo: &Path, message: &str) -> Outcome<String> {
+    git(repo, &["commit", "-q", "--allow-empty", "-m", message])?;
+    let refspec = format!("HEAD:refs/heads/{}", target.branch);
+    git(repo, &["push", "-q", "origin", &refspec])?;
+    Ok(git(repo, &["rev-parse", "HEAD"])?.trim().to_string())
+}
+
+/// Replace everything in `repo` but `.git` with the contents of `output_dir`.
+fn mirror(output_dir: &Path, repo: &Path) -> io::Result<()> {
+    for entry in fs::read_dir(repo)? {
+        let entry = entry?;
+        if entry.file_name() == ".git" {
+            continue;
+        }
+        if entry.file_type()?.is_dir() {
+            fs::remove_dir_all(entry.path())?;
+        } else {
+            fs::remove_file(entry.path())?;
+        }
+    }
+    for entry in WalkDir::new(output_dir).min_depth(1) {
+        let entry = entry?;
+        let Ok(relative) = entry.path().strip_prefix(output_dir) else {
+            continue;
+        };
+        let destination = repo.join(relative);
+        if entry.file_type().is_dir() {
+            fs::create_dir_all(&destination)?;
+        } else {
+            fs::copy(entry.path(), &destination)?;
+        }
+    }
+    Ok(())
+}
+
+/// Run git in `repo`, returning what it printed.
+fn git(repo: &Path, args: &[&str]) -> Outcome<String> {
+    let mut command = Command::new("git");
+    command.arg("-C").arg(repo);
+    if !has_identity(repo) {
+        command.args(GIT_IDENTITY);
+    }
+    let output = command
+        .args(args)
+        .output()
+        .map_err(|source| DeployError::Spawn {
+            tool: "git",
+            source,
+        })?;
+    if !output.status.success() {
+        return Err(DeployError::Failed {
+            command: format!("git {}", args[0]),
+            stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        });
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn has_identity(repo: &Path) -> bool {
+    Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(["config", "user.email"])
+        .output()
+        .is_ok_and(|output| output.status.success())
+}
+
+fn s3_url(target: &S3Deploy) -> String {
+    let prefix = target.prefix.trim_matches('/');
+    if prefix.is_empty() {
+        format!("s3://{}", target.bucket)
+    } else {
+        format!("s3://{}/{}", target.bucket, prefix)
+    }
+}
+
+/// Sync the output to the bucket, then note each object's version.
+fn s3_upload(target: &S3Deploy, output_dir: &Path) -> Outcome<Option<State>> {
+    let output = output_dir.to_string_lossy();
+    let url = s3_url(target);
+    let status = Command::new("aws")
+        .args(["s3", "sync", &output, &url, "--delete", "--no-progress"])
+        .status()
+        .map_err(|source| DeployError::Spawn {
+            tool: "aws",
+            source,
+        })?;
+    if !status.success() {
+        return Err(DeployError::Exited {
+            command: "aws s3 sync",
+            status,
+        });
+    }
+
+    let versions = latest_versions(target)?;
+    // Unversioned buckets report every version as "null"
+    if versions.values().all(|version| version == "null") {
+        tracing::warn!(bucket = %target.bucket, "bucket isn't versioned, so this deploy can't be rolled back");
+        return Ok(None);
+    }
+    Ok(Some(State::S3 { versions }))
+}
+
+/// Put back the objects as they were in `versions`.
+fn s3_restore(target: &S3Deploy, versions: &BTreeMap<String, String>) -> Outcome<()> {
+    let current = latest_versions(target)?;
+    let (copies, deletes) = plan(&current, versions);
+    tracing::info!(
+        restored = copies.len(),
+        deleted = deletes.len(),
+        "restoring objects"
+    );
+    for (key, version) in copies {
+        let source = format!(
+            "{}/{}?versionId={}",
+            target.bucket,
+            util::url_encode_path(key),
+            version
+        );
+        aws(&[
+            "s3api",
+            "copy-object",
+            "--bucket",
+            &target.bucket,
+            "--key",
+            key,
+            "--copy-source",
+            &source,
+        ])?;
+    }
+    for key in deletes {
+        aws(&[
+            "s3api",
+            "delete-object",
+            "--bucket",
+            &target.bucket,
+            "--key",
+            key,
+        ])?;
+    }
+    Ok(())
+}
+
+/// Objects to copy back from an earlier version, and objects to delete,
+/// to go from `current` to `target`.
+fn plan<'a>(
+    current: &'a BTreeMap<String, String>,
+    target: &'a BTreeMap<String, String>,
+) -> (Vec<(&'a str, &'a str)>, Vec<&'a str>) {
+    let copies = target
+        .iter()
+        .filter(|(key, version)| current.get(*key) != Some(*version))
+        .map(|(key, version)| (key.as_str(), version.as_str()))
+        .collect();
+    let deletes = current
+        .keys()
+        .filter(|key| !target.contains_key(*key))
+        .map(String::as_str)
+        .collect();
+    (copies, deletes)
+}
+
+/// The current version of each object under the prefix.
+fn latest_versions(target: &S3Deploy) -> Outcome<BTreeMap<String, String>> {
+    // A bare "gallery" prefix would also match "gallery2/..."
+    let prefix = match target.prefix.trim_matches('/') {
+        "" => String::new(),
+        prefix => format!("{}/", prefix),
+    };
+    let listing = aws(&[
+        "s3api",
+        "list-object-versions",
+        "--bucket",
+        &target.bucket,
+        "--prefix",
+        &prefix,
+        "--output",
+        "json",
+    ])?;
+    parse_versions(&listing, &prefix)
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Listing {
+    #[serde(default)]
+    versions: Vec<Version>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Version {
+    key: String,
+    version_id: String,
+    is_latest: bool,
+}
+
+/// Latest versions under `prefix` from `aws s3api list-object-versions`
+/// output. Deleted objects have a delete marker as their latest version, so
+/// aren't listed.
+fn parse_versions(listing: &str, prefix: &str) -> Outcome<BTreeMap<String, String>> {
+    // Nothing is printed for an empty bucket
+    if listing.trim().is_empty() {
+        return Ok(BTreeMap::new());
+    }
+    let listing: Listing = serde_json::from_str(listing)?;
+    Ok(listing
+        .versions
+        .into_iter()
+        .filter(|version| version.is_latest && version.key.starts_with(prefix))
+        .map(|version| (version.key, version.version_id))
+        .collect())
+}
+
+/// Run the aws CLI, returning what it printed.
+fn aws(args: &[&str]) -> Outcome<String> {
+    let output = Command::new("aws")
+        .args(args)
+        .output()
+        .map_err(|source| DeployError::Spawn {
+            tool: "aws",
+            source,
+        })?;
+    if !output.status.success() {
+        return Err(DeployError::Failed {
+            command: format!("aws {}", args[..2.min(args.len())].join(" ")),
+            stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        });
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn versions(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(key, version)| (key.to_string(), version.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn rollback_plan() {
+        let current = versions(&[("index.html", "v2"), ("a.webp", "v1"), ("new.webp", "v1")]);
+        let target = versions(&[("index.html", "v1"), ("a.webp", "v1"), ("old.webp", "v3")]);
+
+        let (copies, deletes) = plan(&current, &target);
+
+        assert_eq!(copies, [("index.html", "v1"), ("old.webp", "v3")]);
+        assert_eq!(deletes, ["new.webp"]);
+    }
+
+    #[test]
+    fn latest_versions_parsed() {
+        let listing = r#"{
+            "Versions": [
+                {"Key": "index.html", "VersionId": "b", "IsLatest": true, "Size": 5},
+                {"Key": "index.html", "VersionId": "a", "IsLatest": false, "Size": 4},
+                {"Key": "gone.webp", "VersionId": "c", "IsLatest": false, "Size": 9}
+            ],
+            "DeleteMarkers": [{"Key": "gone.webp", "VersionId": "d", "IsLatest": true}]
+        }"#;
+
+        assert_eq!(
+            parse_versions(listing, "").unwrap(),
+            versions(&[("index.html", "b")])
+        );
+        assert!(parse_versions("", "").unwrap().is_empty());
+    }
+
+    #[test]
+    fn sibling_prefix_ignored() {
+        let listing = r#"{
+            "Versions": [
+                {"Key": "gallery/index.html", "VersionId": "a", "IsLatest": true},
+                {"Key": "gallery2/index.html", "VersionId": "b", "IsLatest": true},
+                {"Key": "gallery-old/a.webp", "VersionId": "c", "IsLatest": true}
+            ]
+        }"#;
+        let current = parse_versions(listing, "gallery/").unwrap();
+        let target = versions(&[("gallery/index.html", "z")]);
+
+        let (copies, deletes) = plan(&current, &target);
+
+        assert_eq!(current, versions(&[("gallery/index.html", "a")]));
+        assert_eq!(copies, [("gallery/index.html", "z")]);
+        assert!(deletes.is_empty());
+    }
+
+    #[test]
+    fn one_target_required() {
+        let config: DeployConfig =
+            toml::from_str("command = \"true\"\ns3 = { bucket = \"photos\" }").unwrap();
+
+        assert!(matches!(
+            target(&config),
+            Err(Error::Deploy {
+                source: DeployError::Targets,
+                ..
+            })
+        ));
+    }
+}
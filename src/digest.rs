@@ -0,0 +1,240 @@
+//! HTML digests of recently added photos.
+//!
+//! `galerie digest --since 2024-04-01` writes a standalone HTML fragment
+//! listing the photos added since that date, ready to paste into an email
+//! or hand to a mail script, e.g. to tell family about new pictures. Styles
+//! are inline and the layout is a single table column, since that's what
+//! mail clients render reliably. Thumbnails link to the published site or
+//! are embedded as data URIs.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+use quick_xml::escape::escape;
+
+use crate::error::Result;
+use crate::photos::{Album, Photo, Variant};
+use crate::pipeline::Pipeline;
+use crate::util::{self, base64};
+
+/// Which date counts as when a photo was added.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddedBy {
+    /// Source file modification time
+    Modified,
+    /// Capture date from the photo's metadata
+    Taken,
+}
+
+/// A photo in a digest.
+#[derive(Debug)]
+pub struct Entry {
+    pub title: String,
+    pub album: Option<String>,
+    /// Absolute URL of the photo's page
+    pub link: String,
+    /// Thumbnail URL or data URI
+    pub image: String,
+    pub caption: Option<String>,
+    /// When the photo was added, in seconds since the epoch
+    pub added: i64,
+}
+
+/// Parse `--since` as a date (`2024-04-01`) or RFC 3339 time, in seconds since the epoch.
+pub fn parse_since(value: &str) -> std::result::Result<i64, String> {
+    let parsed = if value.contains('T') {
+        util::parse_iso_time(value)
+    } else {
+        util::parse_iso_time(&format!("{}T00:00:00Z", value.trim()))
+    };
+    parsed.ok_or_else(|| format!("invalid date {value:?}, expected e.g. 2024-04-01"))
+}
+
+/// Collect the photos added at or after `since`, newest first.
+///
+/// With `inline`, thumbnails are read from the build output and embedded.
+pub fn entries(
+    pipeline: &Pipeline,
+    since: i64,
+    added_by: AddedBy,
+    inline: bool,
+) -> Result<Vec<Entry>> {
+    let images_dir = pipeline
+        .site_dir
+        .join(&pipeline.config.build)
+        .join("images");
+    let mut entries = Vec::new();
+    collect(
+        pipeline,
+        &pipeline.root,
+        &images_dir,
+        since,
+        added_by,
+        inline,
+        &mut entries,
+    )?;
+    entries.sort_by_key(|entry| std::cmp::Reverse(entry.added));
+    Ok(entries)
+}
+
+fn collect(
+    pipeline: &Pipeline,
+    album: &Album,
+    images_dir: &Path,
+    since: i64,
+    added_by: AddedBy,
+    inline: bool,
+    entries: &mut Vec<Entry>,
+) -> Result<()> {
+    let base_url = pipeline.base_url();
+
+    for photo in &album.photos {
+        let Some(added) = added_at(photo, added_by) else {
+            continue;
+        };
+        if added < since {
+            continue;
+        }
+
+        let image = if inline {
+            let thumb = images_dir
+                .join(&album.path)
+                .join(photo.variant_file_name(Variant::Thumb));
+            format!("data:image/webp;base64,{}", base64(&fs::read(thumb)?))
+        } else {
+            format!("{}/{}", base_url, photo.thumb_path(&album.path))
+        };
+
+        entries.push(Entry {
+            title: photo
+                .metadata
+                .title
+                .clone()
+                .unwrap_or_else(|| photo.stem.clone()),
+            album: (!album.path.as_os_str().is_empty()).then(|| album.name.clone()),
+            link: format!("{}/{}", base_url, pipeline.page_path(album, photo)),
+            image,
+            caption: photo.metadata.description.clone(),
+            added,
+        });
+    }
+
+    for child in &album.children {
+        collect(
+            pipeline, child, images_dir, since, added_by, inline, entries,
+        )?;
+    }
+
+    Ok(())
+}
+
+/// When `photo` was added, if known.
+fn added_at(photo: &Photo, added_by: AddedBy) -> Option<i64> {
+    match added_by {
+        AddedBy::Modified => {
+            let modified = fs::metadata(&photo.source).ok()?.modified().ok()?;
+            let seconds = modified.duration_since(UNIX_EPOCH).ok()?.as_secs();
+            i64::try_from(seconds).ok()
+        }
+        AddedBy::Taken => photo
+            .metadata
+            .date_taken
+            .as_deref()
+            .and_then(util::parse_exif_time),
+    }
+}
+
+/// Render a digest as an HTML fragment.
+pub fn render(title: &str, site_url: &str, entries: &[Entry]) -> String {
+    let mut html = String::new();
+    html.push_str(
+        "<div style=\"font-family:-apple-system,'Segoe UI',Helvetica,Arial,sans-serif;\
+         max-width:600px;margin:0 auto;color:#222\">\n",
+    );
+    let _ = writeln!(
+        html,
+        "<h1 style=\"font-size:22px;font-weight:600;margin:0 0 16px\">{}</h1>",
+        escape(title)
+    );
+    html.push_str(
+        "<table role=\"presentation\" width=\"100%\" cellpadding=\"0\" cellspacing=\"0\" \
+         border=\"0\">\n",
+    );
+
+    for entry in entries {
+        html.push_str("<tr><td style=\"padding:0 0 24px\">\n");
+        let _ = writeln!(
+            html,
+            "<a href=\"{link}\"><img src=\"{image}\" alt=\"{title}\" width=\"600\" \
+             style=\"display:block;width:100%;max-width:600px;height:auto;border:0\"></a>",
+            link = escape(&entry.link),
+            image = escape(&entry.image),
+            title = escape(&entry.title),
+        );
+
+        let mut details = vec![escape(&entry.title).into_owned()];
+        if let Some(album) = &entry.album {
+            details.push(escape(album).into_owned());
+        }
+        details.push(util::rfc3339(entry.added)[..10].to_string());
+        let _ = writeln!(
+            html,
+            "<p style=\"font-size:14px;margin:8px 0 0;color:#555\">{}</p>",
+            details.join(" &middot; ")
+        );
+
+        if let Some(caption) = &entry.caption {
+            let _ = writeln!(
+                html,
+                "<p style=\"font-size:14px;margin:4px 0 0\">{}</p>",
+                escape(caption)
+            );
+        }
+        html.push_str("</td></tr>\n");
+    }
+
+    html.push_str("</table>\n");
+    let _ = writeln!(
+        html,
+        "<p style=\"font-size:14px\"><a href=\"{}/\">{}</a></p>",
+        escape(site_url),
+        escape(site_url.trim_start_matches("https://"))
+    );
+    html.push_str("</div>\n");
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case("2024-04-02", Some(1712016000) ; "date")]
+    #[test_case("2024-04-02T06:12:45Z", Some(1712038365) ; "utc time")]
+    #[test_case("2024-04-02T08:12:45+02:00", Some(1712038365) ; "offset time")]
+    #[test_case("last week", None ; "invalid")]
+    fn test_parse_since(value: &str, expected: Option<i64>) {
+        assert_eq!(parse_since(value).ok(), expected);
+    }
+
+    #[test]
+    fn render_escapes_and_lists_entries() {
+        let entries = [Entry {
+            title: "Fish & chips".to_string(),
+            album: Some("Travel".to_string()),
+            link: "https://example.com/travel/fish.html".to_string(),
+            image: "https://example.com/images/travel/fish-abc12345-thumb.webp".to_string(),
+            caption: Some("<b>lunch</b>".to_string()),
+            added: 1712038365,
+        }];
+
+        let html = render("New photos", "https://example.com", &entries);
+
+        assert!(html.contains("alt=\"Fish &amp; chips\""));
+        assert!(html.contains("Fish &amp; chips &middot; Travel &middot; 2024-04-02"));
+        assert!(html.contains("&lt;b&gt;lunch&lt;/b&gt;"));
+        assert!(html.contains("<a href=\"https://example.com/\">example.com</a>"));
+    }
+}
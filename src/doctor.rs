@@ -0,0 +1,522 @@
+//! Checking the environment a site is built in.
+//!
+//! `galerie doctor` looks for what stops a build before the build gets to it,
+//! or makes it go quietly wrong: a missing package manager for a Vite theme,
+//! an output directory that can't be written, a missing photos directory,
+//! photos that are empty or not the image their extension says, file names
+//! that aren't UTF-8, and a clock or locale that's off. Each failure comes
+//! with a hint. Nothing is built or written, apart from a probe file in the
+//! output directory that's removed straight away.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rayon::prelude::*;
+use walkdir::WalkDir;
+
+use crate::builtin_themes;
+use crate::config::Site;
+use crate::error::Error;
+use crate::photos::Photo;
+use crate::theme_build::{self, ThemeType};
+use crate::util;
+
+/// A clock earlier than this (2024-01-01) is certainly wrong.
+const EARLIEST_CLOCK: i64 = 1_704_067_200;
+
+/// How far in the future a file may be modified before the clock looks behind.
+const CLOCK_SLACK: i64 = 24 * 60 * 60;
+
+/// Environment variables naming the locale, most specific first.
+const LOCALE_VARIABLES: [&str; 3] = ["LC_ALL", "LC_CTYPE", "LANG"];
+
+/// Problem files listed by name before the rest are counted.
+const LISTED: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Ok,
+    /// Worth fixing, but builds work
+    Warn,
+    /// Stops builds, or leaves photos out of them
+    Fail,
+}
+
+/// The outcome of one check.
+#[derive(Debug)]
+pub struct Finding {
+    /// What was checked (e.g., "output directory")
+    pub check: &'static str,
+    pub status: Status,
+    pub message: String,
+    /// What to do about it, for warnings and failures
+    pub hint: Option<String>,
+}
+
+impl Finding {
+    fn ok(check: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            check,
+            status: Status::Ok,
+            message: message.into(),
+            hint: None,
+        }
+    }
+
+    fn warn(check: &'static str, message: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self {
+            check,
+            status: Status::Warn,
+            message: message.into(),
+            hint: Some(hint.into()),
+        }
+    }
+
+    fn fail(check: &'static str, message: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self {
+            check,
+            status: Status::Fail,
+            message: message.into(),
+            hint: Some(hint.into()),
+        }
+    }
+}
+
+/// Check the environment of the site in `site_dir`, whose config at
+/// `config_path` loaded as `site`. Without a config, only the machine's
+/// clock and locale are checked.
+pub fn run(site_dir: &Path, config_path: &Path, site: crate::error::Result<Site>) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    match site {
+        Ok(site) => {
+            findings.push(Finding::ok(
+                "site.toml",
+                format!("{} loaded", config_path.display()),
+            ));
+            findings.push(package_manager(site_dir, &site));
+            findings.push(output_dir(&site_dir.join(&site.build)));
+            let photos_dir = site_dir.join(&site.photos);
+            findings.push(photos_directory(&photos_dir));
+            if photos_dir.is_dir() {
+                findings.push(photo_files(&photos_dir));
+                findings.push(file_names(&photos_dir));
+            }
+            findings.push(clock(now(), Some(&photos_dir)));
+        }
+        Err(e) => {
+            let hint = e
+                .hint()
+                .unwrap_or_else(|| "check site.toml against wiki/site-config.md".to_string());
+            findings.push(Finding::fail("site.toml", e.to_string(), hint));
+            findings.push(clock(now(), None));
+        }
+    }
+    findings.push(locale(|name| std::env::var(name).ok()));
+    findings
+}
+
+/// The error for findings that fail, if any do.
+pub fn error(findings: &[Finding]) -> Option<Error> {
+    let problems = findings
+        .iter()
+        .filter(|finding| finding.status == Status::Fail)
+        .count();
+    (problems > 0).then_some(Error::Doctor { problems })
+}
+
+/// The findings as a list, with hints under the ones that have them.
+pub fn render(findings: &[Finding]) -> String {
+    let width = findings
+        .iter()
+        .map(|finding| finding.check.len())
+        .max()
+        .unwrap_or(0);
+    let mut out = String::new();
+    for finding in findings {
+        let status = match finding.status {
+            Status::Ok => "ok",
+            Status::Warn => "warn",
+            Status::Fail => "FAIL",
+        };
+        let _ = writeln!(
+            out,
+            "{:<4}  {:<width$}  {}",
+            status,
+            finding.check,
+            finding.message,
+            width = width
+        );
+        if let Some(hint) = &finding.hint {
+            let _ = writeln!(
+                out,
+                "{:<4}  {:<width$}  hint: {}",
+                "",
+                "",
+                hint,
+                width = width
+            );
+        }
+    }
+    out
+}
+
+/// A Vite theme in the site directory is built with a package manager;
+/// built-in themes come built.
+fn package_manager(site_dir: &Path, site: &Site) -> Finding {
+    const CHECK: &str = "package manager";
+    let name = site.theme.name();
+    let theme_dir = site_dir.join(name);
+    if !theme_dir.is_dir() {
+        return if builtin_themes::get(name).is_some() {
+            Finding::ok(CHECK, format!("not needed, '{}' is built in", name))
+        } else {
+            Finding::fail(
+                CHECK,
+                format!("theme '{}' not found", name),
+                "set theme in site.toml to a built-in theme or a theme directory; galerie themes lists them",
+            )
+        };
+    }
+    if theme_build::detect_theme_type(&theme_dir) == ThemeType::Classic {
+        return Finding::ok(CHECK, format!("not needed, '{}' is a classic theme", name));
+    }
+    match theme_build::find_package_manager(&theme_dir) {
+        Ok((manager, path)) => Finding::ok(
+            CHECK,
+            format!("{} at {} builds '{}'", manager, path.display(), name),
+        ),
+        Err(e) => Finding::fail(
+            CHECK,
+            format!("no package manager to build Vite theme '{}'", name),
+            e.hint().unwrap_or_default(),
+        ),
+    }
+}
+
+/// The output directory, or the directory it'll be created in, takes files.
+fn output_dir(output_dir: &Path) -> Finding {
+    const CHECK: &str = "output directory";
+    let hint =
+        "fix the directory's permissions, or set build in site.toml to a directory you can write";
+    if output_dir.exists() && !output_dir.is_dir() {
+        return Finding::fail(
+            CHECK,
+            format!("{} is a file", output_dir.display()),
+            "remove it, or set build in site.toml to another directory",
+        );
+    }
+    // A missing directory is created by the build, in its nearest existing parent
+    let Some(existing) = output_dir.ancestors().find(|dir| dir.is_dir()) else {
+        return Finding::fail(
+            CHECK,
+            format!("no directory to create {} in", output_dir.display()),
+            hint,
+        );
+    };
+    let probe = existing.join(format!(".galerie-doctor-{}", std::process::id()));
+    match fs::write(&probe, b"") {
+        Ok(()) => {
+            let _ = fs::remove_file(&probe);
+            let message = if existing == output_dir {
+                format!("{} is writable", output_dir.display())
+            } else {
+                format!("{} can be created", output_dir.display())
+            };
+            Finding::ok(CHECK, message)
+        }
+        Err(e) => Finding::fail(
+            CHECK,
+            format!("can't write to {}: {}", existing.display(), e),
+            hint,
+        ),
+    }
+}
+
+fn photos_directory(photos_dir: &Path) -> Finding {
+    const CHECK: &str = "photos directory";
+    if !photos_dir.is_dir() {
+        return Finding::fail(
+            CHECK,
+            format!("{} doesn't exist", photos_dir.display()),
+            "create it and add photos, or set photos in site.toml to where they are",
+        );
+    }
+    let count = photo_paths(photos_dir).len();
+    if count == 0 {
+        return Finding::fail(
+            CHECK,
+            format!("no photos in {}", photos_dir.display()),
+            "add JPEG, PNG, WebP, GIF, TIFF, HEIC, RAW, or video files, in subdirectories for albums",
+        );
+    }
+    Finding::ok(
+        CHECK,
+        format!("{} photo(s) in {}", count, photos_dir.display()),
+    )
+}
+
+/// Photos in `photos_dir` and its subdirectories, as a build finds them.
+fn photo_paths(photos_dir: &Path) -> Vec<PathBuf> {
+    WalkDir::new(photos_dir)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .filter(|entry| !entry.file_name().to_string_lossy().starts_with('.'))
+        .map(|entry| entry.into_path())
+        .filter(|path| Photo::from_path(path).is_some())
+        .collect()
+}
+
+/// Photos that are empty, or whose header isn't an image a build can read.
+/// Only headers are read, so damage further into a file isn't found.
+fn photo_files(photos_dir: &Path) -> Finding {
+    const CHECK: &str = "photo files";
+    let paths = photo_paths(photos_dir);
+    let mut broken: Vec<String> = paths
+        .par_iter()
+        .filter_map(|path| {
+            let problem = corrupt(path)?;
+            let name = path.strip_prefix(photos_dir).unwrap_or(path);
+            Some(format!("{} ({})", name.display(), problem))
+        })
+        .collect();
+    broken.sort();
+    if broken.is_empty() {
+        return Finding::ok(CHECK, format!("{} photo(s) readable", paths.len()));
+    }
+    Finding::fail(
+        CHECK,
+        format!(
+            "{} photo(s) can't be read: {}",
+            broken.len(),
+            listed(&broken)
+        ),
+        "export or copy them again; builds leave them out",
+    )
+}
+
+/// Why the photo at `path` can't be read, if it obviously can't.
+fn corrupt(path: &Path) -> Option<String> {
+    let photo = Photo::from_path(path)?;
+    let size = match fs::metadata(path) {
+        Ok(metadata) => metadata.len(),
+        Err(e) => return Some(e.to_string()),
+    };
+    if size == 0 {
+        return Some("empty".to_string());
+    }
+    // RAW, HEIC, and video files are read by other tools
+    if photo.is_raw() || photo.is_heif() || photo.is_video() {
+        return None;
+    }
+    let dimensions = image::ImageReader::open(path)
+        .and_then(|reader| reader.with_guessed_format())
+        .map_err(image::ImageError::from)
+        .and_then(|reader| reader.into_dimensions());
+    match dimensions {
+        Ok(_) => None,
+        Err(e) => Some(e.to_string()),
+    }
+}
+
+/// File and directory names in `photos_dir` that aren't UTF-8, which builds
+/// can't put in URLs.
+fn file_names(photos_dir: &Path) -> Finding {
+    const CHECK: &str = "file names";
+    let invalid: Vec<String> = WalkDir::new(photos_dir)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_name().to_str().is_none())
+        .map(|entry| {
+            let path = entry.path();
+            path.strip_prefix(photos_dir)
+                .unwrap_or(path)
+                .display()
+                .to_string()
+        })
+        .collect();
+    if invalid.is_empty() {
+        return Finding::ok(CHECK, "all UTF-8");
+    }
+    Finding::fail(
+        CHECK,
+        format!(
+            "{} name(s) aren't UTF-8: {}",
+            invalid.len(),
+            listed(&invalid)
+        ),
+        "rename them, or convert them with convmv --notest -f <charset> -t utf8",
+    )
+}
+
+/// The first few of `items`, and how many more there are.
+fn listed(items: &[String]) -> String {
+    let mut out = items
+        .iter()
+        .take(LISTED)
+        .cloned()
+        .collect::<Vec<_>>()
+        .join(", ");
+    if items.len() > LISTED {
+        let _ = write!(out, ", and {} more", items.len() - LISTED);
+    }
+    out
+}
+
+/// Seconds since the epoch.
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+/// The clock at `now` isn't obviously wrong, and no photo in `photos_dir`
+/// was modified after it, which happens when it's behind the machine the
+/// photos came from.
+fn clock(now: i64, photos_dir: Option<&Path>) -> Finding {
+    const CHECK: &str = "clock";
+    let hint = "set the system clock, or turn on time sync (timedatectl set-ntp true)";
+    if now < EARLIEST_CLOCK {
+        return Finding::fail(
+            CHECK,
+            format!("system time is {}", util::rfc3339(now)),
+            hint,
+        );
+    }
+    let future = photos_dir
+        .map(|dir| {
+            photo_paths(dir)
+                .iter()
+                .filter_map(|path| fs::metadata(path).ok()?.modified().ok())
+                .filter_map(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                .filter(|modified| modified.as_secs() as i64 > now + CLOCK_SLACK)
+                .count()
+        })
+        .unwrap_or(0);
+    if future > 0 {
+        return Finding::warn(
+            CHECK,
+            format!(
+                "{} photo(s) modified after the system time, {}",
+                future,
+                util::rfc3339(now)
+            ),
+            format!(
+                "{}; the feed and digest date photos by when they were modified",
+                hint
+            ),
+        );
+    }
+    Finding::ok(CHECK, util::rfc3339(now))
+}
+
+/// The locale, read through `var`, uses UTF-8.
+fn locale(var: impl Fn(&str) -> Option<String>) -> Finding {
+    const CHECK: &str = "locale";
+    let hint = "set LANG to a UTF-8 locale, such as C.UTF-8 or en_US.UTF-8, so names with accents or other scripts survive copying and deploy commands";
+    let Some((name, value)) = LOCALE_VARIABLES
+        .iter()
+        .find_map(|name| Some((*name, var(name).filter(|value| !value.is_empty())?)))
+    else {
+        return Finding::warn(CHECK, "none set, so tools fall back to ASCII", hint);
+    };
+    let normalized = value.to_ascii_lowercase().replace('-', "");
+    if normalized.contains("utf8") {
+        Finding::ok(CHECK, format!("{}={}", name, value))
+    } else {
+        Finding::warn(CHECK, format!("{}={} isn't UTF-8", name, value), hint)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(&[("LANG", "en_US.UTF-8")], Status::Ok ; "utf-8")]
+    #[test_case(&[("LANG", "C.utf8")], Status::Ok ; "lowercase")]
+    #[test_case(&[("LC_ALL", "C"), ("LANG", "en_US.UTF-8")], Status::Warn ; "overridden")]
+    #[test_case(&[("LANG", "de_DE.ISO-8859-1")], Status::Warn ; "latin-1")]
+    #[test_case(&[], Status::Warn ; "unset")]
+    fn locale_checked(variables: &[(&str, &str)], expected: Status) {
+        let finding = locale(|name| {
+            variables
+                .iter()
+                .find(|(variable, _)| *variable == name)
+                .map(|(_, value)| value.to_string())
+        });
+        assert_eq!(finding.status, expected);
+    }
+
+    #[test]
+    fn clock_checked() {
+        assert_eq!(clock(0, None).status, Status::Fail);
+        assert_eq!(clock(EARLIEST_CLOCK + 1, None).status, Status::Ok);
+
+        let photos = tempfile::tempdir().unwrap();
+        fs::write(photos.path().join("new.jpg"), "").unwrap();
+        // The photo was written after this clock's time
+        assert_eq!(
+            clock(EARLIEST_CLOCK + 1, Some(photos.path())).status,
+            Status::Warn
+        );
+        assert_eq!(clock(now(), Some(photos.path())).status, Status::Ok);
+    }
+
+    #[test]
+    fn output_dir_checked() {
+        let site = tempfile::tempdir().unwrap();
+        assert_eq!(output_dir(site.path()).status, Status::Ok);
+        assert_eq!(output_dir(&site.path().join("dist/new")).status, Status::Ok);
+        fs::write(site.path().join("file"), "").unwrap();
+        assert_eq!(output_dir(&site.path().join("file")).status, Status::Fail);
+        // The probe is removed
+        assert_eq!(fs::read_dir(site.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn site_diagnosed() {
+        let site_dir = tempfile::tempdir().unwrap();
+        let site = crate::preview::generate(site_dir.path(), "basic").unwrap();
+        let config = site_dir.path().join("site.toml");
+
+        let findings = run(site_dir.path(), &config, Ok(site));
+        assert!(error(&findings).is_none(), "{}", render(&findings));
+
+        let album = site_dir.path().join("photos/broken");
+        fs::create_dir_all(&album).unwrap();
+        fs::write(album.join("empty.jpg"), "").unwrap();
+        fs::write(album.join("text.png"), "not a photo").unwrap();
+        let site = Site::load(&config).unwrap();
+        let findings = run(site_dir.path(), &config, Ok(site));
+
+        let failed: Vec<_> = findings
+            .iter()
+            .filter(|finding| finding.status == Status::Fail)
+            .collect();
+        assert_eq!(failed.len(), 1, "{}", render(&findings));
+        assert_eq!(failed[0].check, "photo files");
+        assert!(failed[0].message.starts_with("2 photo(s)"));
+        assert!(render(&findings).contains("hint: export or copy them again"));
+    }
+
+    #[test]
+    fn missing_config_diagnosed() {
+        let site_dir = tempfile::tempdir().unwrap();
+        let config = site_dir.path().join("site.toml");
+
+        let findings = run(site_dir.path(), &config, Site::load(&config));
+
+        assert_eq!(findings[0].status, Status::Fail);
+        assert!(findings[0].hint.is_some());
+        assert!(matches!(
+            error(&findings),
+            Some(Error::Doctor { problems: 1 })
+        ));
+    }
+}
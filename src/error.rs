@@ -1,26 +1,69 @@
 use std::path::PathBuf;
 use thiserror::Error;
 
+use crate::deploy::DeployError;
+use crate::template_error::RenderError;
+
+/// Process exit codes, grouped by what went wrong.
+///
+/// Scripts wrapping galerie can rely on these to tell a broken `site.toml`
+/// from a broken theme or a full disk. Code 2 is left to clap for invalid
+/// command-line usage.
+pub mod exit_code {
+    /// Unclassified failure
+    pub const FAILURE: u8 = 1;
+    /// `site.toml` is missing or invalid
+    pub const CONFIG: u8 = 3;
+    /// Theme could not be found, built, or rendered
+    pub const THEME: u8 = 4;
+    /// Filesystem read or write failed
+    pub const IO: u8 = 5;
+    /// Photos are missing or could not be processed
+    pub const PHOTOS: u8 = 6;
+    /// File watcher or development server failed
+    pub const SERVER: u8 = 7;
+    /// Theme snapshot test found differences
+    pub const SNAPSHOT: u8 = 8;
+    /// Built output failed verification, or deploying it failed
+    pub const PUBLISH: u8 = 9;
+    /// `galerie check` found problems
+    pub const CHECK: u8 = 10;
+    /// `galerie doctor` found problems
+    pub const DOCTOR: u8 = 11;
+}
+
 #[derive(Debug, Error)]
 pub enum Error {
     #[error("config error: {0}")]
     Config(#[from] toml::de::Error),
 
+    #[error("config file not found: {}", path.display())]
+    ConfigNotFound { path: PathBuf },
+
     #[error("io error: {0}")]
     Io(#[from] std::io::Error),
 
-    #[error("template error: {0}")]
+    #[error("template error: {}", crate::template_error::message(.0))]
     Template(#[from] tera::Error),
 
+    #[error("{0}")]
+    Render(Box<RenderError>),
+
     #[error("image processing error: {0}")]
     Image(#[from] image::ImageError),
 
     #[error("watch error: {0}")]
     Watch(#[from] notify::Error),
 
+    #[error("failed to start server on port {port}: {message}")]
+    Serve { port: u16, message: String },
+
     #[error("theme missing required template: index.html")]
     MissingIndexTemplate,
 
+    #[error("theme references missing static assets: {}", .missing.join(", "))]
+    MissingStaticAssets { missing: Vec<String> },
+
     #[error("theme not found: {name} (not a local directory or built-in theme)")]
     ThemeNotFound { name: String },
 
@@ -33,8 +76,172 @@ pub enum Error {
     #[error("theme build failed: {message}")]
     ThemeBuild { message: String },
 
-    #[error("{tool} not found. {hint}")]
+    #[error("{tool} not found")]
     ToolNotFound { tool: String, hint: String },
+
+    #[error("{failed} template snapshot(s) failed")]
+    SnapshotMismatch { failed: usize },
+
+    #[error("{provider} geocoding failed: {message}")]
+    Geocode { provider: String, message: String },
+
+    #[error("privacy audit found sensitive metadata in {flagged} file(s)")]
+    Audit { flagged: usize },
+
+    #[error("signing checksums failed: {message}")]
+    Sign { message: String },
+
+    #[error("publishing failed to {stage}: {message}")]
+    Publish {
+        stage: &'static str,
+        message: String,
+    },
+
+    #[error("publishing failed to {stage}: {source}")]
+    Deploy {
+        stage: &'static str,
+        #[source]
+        source: DeployError,
+    },
+
+    #[error("check found {problems} problem(s)")]
+    Check { problems: usize },
+
+    #[error("doctor found {problems} problem(s)")]
+    Doctor { problems: usize },
+
+    #[error("build cancelled")]
+    Cancelled,
+}
+
+impl Error {
+    /// Exit code for this error (see [`exit_code`]).
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Error::Config(_) | Error::ConfigNotFound { .. } => exit_code::CONFIG,
+            Error::Template(_)
+            | Error::Render(_)
+            | Error::MissingIndexTemplate
+            | Error::MissingStaticAssets { .. }
+            | Error::ThemeNotFound { .. }
+            | Error::ThemeBuild { .. }
+            | Error::ToolNotFound { .. } => exit_code::THEME,
+            Error::Io(_) => exit_code::IO,
+            Error::Image(_)
+            | Error::NoPhotos { .. }
+            | Error::Geocode { .. }
+            | Error::Audit { .. } => exit_code::PHOTOS,
+            Error::Watch(_) | Error::Serve { .. } => exit_code::SERVER,
+            Error::SnapshotMismatch { .. } => exit_code::SNAPSHOT,
+            Error::Publish { .. } | Error::Deploy { .. } => exit_code::PUBLISH,
+            Error::Check { .. } => exit_code::CHECK,
+            Error::Doctor { .. } => exit_code::DOCTOR,
+            Error::Other(_) | Error::Sign { .. } | Error::Cancelled => exit_code::FAILURE,
+        }
+    }
+
+    /// A suggestion for fixing this error, if there is one.
+    pub fn hint(&self) -> Option<String> {
+        let hint = match self {
+            Error::Config(_) => "check site.toml against wiki/site-config.md".to_string(),
+            Error::ConfigNotFound { .. } => {
+                "run galerie from a site directory, or pass -C <dir> or -c <file>".to_string()
+            }
+            Error::Template(_) | Error::Render(_) => {
+                "see wiki/template-context.md for the variables available to templates".to_string()
+            }
+            Error::Serve { .. } => {
+                "another process may be using the port; choose one with --port".to_string()
+            }
+            Error::MissingIndexTemplate => "add templates/index.html to the theme".to_string(),
+            Error::MissingStaticAssets { .. } => {
+                "add the files to the theme's static/ directory, or fix the paths passed to static()"
+                    .to_string()
+            }
+            Error::ThemeNotFound { .. } => format!(
+                "use a theme directory relative to the site, or a built-in theme: {}",
+                crate::builtin_themes::NAMES.join(", ")
+            ),
+            Error::NoPhotos { .. } => {
+                "add images to the photos directory, or set `photos` in site.toml".to_string()
+            }
+            Error::ToolNotFound { hint, .. } => hint.clone(),
+            Error::SnapshotMismatch { .. } => {
+                "review the differences, then rerun with --update to accept them".to_string()
+            }
+            Error::Geocode { .. } => {
+                "check the [geocoding] url in site.toml, or set provider = \"offline\"".to_string()
+            }
+            Error::Audit { .. } => {
+                "remove the metadata from the source photos, or set audit = \"warn\" to only report it"
+                    .to_string()
+            }
+            Error::Sign { .. } => {
+                "check that the signing tool is installed and the key in [checksums] sign exists"
+                    .to_string()
+            }
+            Error::Publish { stage: "verify", .. } => {
+                "rebuild the site, or skip the check with --skip-verify".to_string()
+            }
+            Error::Publish {
+                stage: "roll back",
+                ..
+            }
+            | Error::Deploy {
+                stage: "roll back",
+                ..
+            } => "rolling back needs an earlier deploy to a git target or a versioned S3 bucket"
+                .to_string(),
+            Error::Publish { .. } | Error::Deploy { .. } => {
+                "check [deploy] in site.toml; the journal records this attempt".to_string()
+            }
+            Error::Check { .. } => {
+                "fix the problems logged above, then run galerie check again".to_string()
+            }
+            Error::Doctor { .. } => {
+                "follow the hints above, then run galerie doctor again".to_string()
+            }
+            Error::Io(_)
+            | Error::Image(_)
+            | Error::Watch(_)
+            | Error::Other(_)
+            | Error::ThemeBuild { .. }
+            | Error::Cancelled => return None,
+        };
+        Some(hint)
+    }
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(Error::ConfigNotFound { path: "site.toml".into() }, exit_code::CONFIG ; "config")]
+    #[test_case(Error::MissingIndexTemplate, exit_code::THEME ; "theme")]
+    #[test_case(Error::Io(std::io::Error::other("disk full")), exit_code::IO ; "io")]
+    #[test_case(Error::NoPhotos { path: "photos".into() }, exit_code::PHOTOS ; "photos")]
+    #[test_case(Error::Serve { port: 3000, message: "in use".into() }, exit_code::SERVER ; "serve")]
+    #[test_case(Error::SnapshotMismatch { failed: 1 }, exit_code::SNAPSHOT ; "snapshot")]
+    #[test_case(Error::Publish { stage: "deploy", message: "exit 1".into() }, exit_code::PUBLISH ; "publish")]
+    #[test_case(Error::Deploy { stage: "deploy", source: DeployError::Targets }, exit_code::PUBLISH ; "deploy")]
+    #[test_case(Error::Check { problems: 2 }, exit_code::CHECK ; "check")]
+    #[test_case(Error::Doctor { problems: 1 }, exit_code::DOCTOR ; "doctor")]
+    #[test_case(Error::Other("?".into()), exit_code::FAILURE ; "other")]
+    fn test_exit_code(err: Error, expected: u8) {
+        assert_eq!(err.exit_code(), expected);
+    }
+
+    #[test]
+    fn tool_not_found_hint_is_separate() {
+        let err = Error::ToolNotFound {
+            tool: "npm".to_string(),
+            hint: "Install Node.js".to_string(),
+        };
+
+        assert_eq!(err.to_string(), "npm not found");
+        assert_eq!(err.hint().as_deref(), Some("Install Node.js"));
+    }
+}
@@ -0,0 +1,917 @@
+//! Fallible EXIF reading and GPS stripping.
+//!
+//! little_exif is tried first since it also handles writing. It can panic
+//! on malformed files, so calls into it are confined to this module behind
+//! a panic boundary. When it fails or finds nothing, kamadak-exif is used
+//! as a fallback parser. Callers get plain `Option`/`Result` values and
+//! never see a panic.
+//!
+//! GPS stripping is done in place instead: rewriting metadata with
+//! little_exif loses or moves other EXIF and can leave a WebP undecodable.
+
+use std::io::Cursor;
+use std::ops::Range;
+use std::panic::{self, AssertUnwindSafe};
+
+use kamadak_exif::{Context, In, Reader, Tag, Value};
+use little_exif::exif_tag::ExifTag;
+use little_exif::filetype::FileExtension;
+use little_exif::metadata::Metadata;
+use little_exif::rational::uR64;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::xmp;
+
+/// An unsigned EXIF rational as (numerator, denominator).
+pub type Rational = (u32, u32);
+
+/// Which parser produced a photo's EXIF data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExifParser {
+    LittleExif,
+    KamadakExif,
+}
+
+/// EXIF fields galerie uses, independent of the parser that read them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExifData {
+    /// Parser that produced this data
+    pub parser: ExifParser,
+    pub date_taken: Option<String>,
+    pub copyright: Option<String>,
+    pub make: Option<String>,
+    pub model: Option<String>,
+    pub lens: Option<String>,
+    /// How the stored pixels are rotated or flipped (1-8, 1 is upright)
+    pub orientation: Option<u32>,
+    /// (latitude, longitude) in signed decimal degrees
+    pub gps: Option<(f64, f64)>,
+    /// Meters above sea level (negative below)
+    pub altitude: Option<f64>,
+    /// Direction the camera was facing, in degrees from north
+    pub direction: Option<f64>,
+    pub f_number: Option<Rational>,
+    pub exposure_time: Option<Rational>,
+    pub iso: Option<u32>,
+    pub focal_length: Option<Rational>,
+    pub exposure_program: Option<u32>,
+}
+
+impl ExifData {
+    fn empty(parser: ExifParser) -> Self {
+        Self {
+            parser,
+            date_taken: None,
+            copyright: None,
+            make: None,
+            model: None,
+            lens: None,
+            orientation: None,
+            gps: None,
+            altitude: None,
+            direction: None,
+            f_number: None,
+            exposure_time: None,
+            iso: None,
+            focal_length: None,
+            exposure_program: None,
+        }
+    }
+
+    /// Whether no fields were found.
+    fn is_empty(&self) -> bool {
+        *self == Self::empty(self.parser)
+    }
+}
+
+/// Read EXIF data, falling back to kamadak-exif if little_exif fails.
+///
+/// Returns `None` if neither parser finds any usable fields.
+pub fn read(data: &[u8], extension: &str) -> Option<ExifData> {
+    match guard("read", || read_little_exif(data, extension)).flatten() {
+        Some(exif) if !exif.is_empty() => return Some(exif),
+        Some(_) => tracing::trace!("little_exif found no fields, trying kamadak-exif"),
+        None => tracing::debug!("little_exif failed, trying kamadak-exif"),
+    }
+
+    read_kamadak_exif(data).filter(|exif| !exif.is_empty())
+}
+
+/// Remove all GPS tags from image data, preserving other EXIF metadata.
+///
+/// The GPS IFD of each EXIF block in JPEG, PNG, WebP, and TIFF-based files
+/// is cleared in place, along with GPS properties in embedded XMP. PNGs
+/// without an `eXIf` chunk may keep EXIF in a legacy text chunk, which is
+/// left to little_exif.
+pub fn strip_gps(data: &[u8], extension: &str) -> Vec<u8> {
+    let mut data = data.to_vec();
+
+    if exif_blocks(&data).is_empty() && data.starts_with(PNG_SIGNATURE) {
+        match guard("strip_gps", || strip_gps_little_exif(&data, extension)) {
+            Some(Ok(stripped)) => data = stripped,
+            Some(Err(e)) => tracing::debug!(error = %e, "little_exif could not strip GPS"),
+            None => {}
+        }
+    }
+
+    if strip_gps_in_place(&mut data) {
+        tracing::trace!("cleared GPS metadata");
+    }
+    data
+}
+
+/// Sensitive EXIF tags present in a file.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Residual {
+    /// Any GPS tag besides the GPS IFD's version
+    pub gps: bool,
+    /// Camera body or lens serial number
+    pub serial_number: bool,
+}
+
+/// Look for sensitive EXIF tags in image data.
+///
+/// Uses kamadak-exif, which reads every IFD without panicking. Files without
+/// EXIF have nothing to report.
+pub fn residual(data: &[u8]) -> Residual {
+    let Ok(exif) = Reader::new().read_from_container(&mut Cursor::new(data)) else {
+        return Residual::default();
+    };
+
+    let mut residual = Residual::default();
+    for field in exif.fields() {
+        if field.tag.context() == Context::Gps && field.tag != Tag::GPSVersionID {
+            residual.gps = true;
+        }
+        if field.tag == Tag::BodySerialNumber || field.tag == Tag::LensSerialNumber {
+            residual.serial_number = true;
+        }
+    }
+    residual
+}
+
+/// Run `f`, converting a panic into `None`.
+fn guard<T>(operation: &str, f: impl FnOnce() -> T) -> Option<T> {
+    panic::catch_unwind(AssertUnwindSafe(f))
+        .map_err(|_| tracing::debug!(operation, "little_exif panicked"))
+        .ok()
+}
+
+/// Get file extension as little_exif FileExtension.
+fn file_extension(extension: &str) -> Option<FileExtension> {
+    match extension.to_lowercase().as_str() {
+        "jpg" | "jpeg" => Some(FileExtension::JPEG),
+        "png" => Some(FileExtension::PNG {
+            as_zTXt_chunk: true,
+        }),
+        "webp" => Some(FileExtension::WEBP),
+        _ => None,
+    }
+}
+
+fn read_little_exif(data: &[u8], extension: &str) -> Option<ExifData> {
+    let file_type = file_extension(extension)?;
+    let metadata = Metadata::new_from_vec(&data.to_vec(), file_type).ok()?;
+
+    macro_rules! tag {
+        ($variant:ident, $empty:expr) => {
+            metadata
+                .get_tag(&ExifTag::$variant($empty))
+                .next()
+                .and_then(|t| match t {
+                    ExifTag::$variant(v) => Some(v.clone()),
+                    _ => None,
+                })
+        };
+    }
+
+    let rationals = |vals: Vec<uR64>| -> Vec<Rational> {
+        vals.iter().map(|r| (r.nominator, r.denominator)).collect()
+    };
+    let rational = |vals: Vec<uR64>| rationals(vals).first().copied();
+
+    let gps = (|| {
+        let lat = tag!(GPSLatitude, Vec::new()).map(rationals)?;
+        let lon = tag!(GPSLongitude, Vec::new()).map(rationals)?;
+        Some((
+            gps_to_decimal(&lat, &tag!(GPSLatitudeRef, String::new())?)?,
+            gps_to_decimal(&lon, &tag!(GPSLongitudeRef, String::new())?)?,
+        ))
+    })();
+
+    let altitude = tag!(GPSAltitude, Vec::new())
+        .and_then(rational)
+        .and_then(|r| {
+            let below = tag!(GPSAltitudeRef, Vec::new()).and_then(|v| v.first().copied());
+            gps_altitude(r, below.unwrap_or(0).into())
+        });
+
+    Some(ExifData {
+        parser: ExifParser::LittleExif,
+        date_taken: tag!(DateTimeOriginal, String::new()),
+        copyright: tag!(Copyright, String::new()),
+        make: tag!(Make, String::new()),
+        model: tag!(Model, String::new()),
+        lens: tag!(LensModel, String::new()),
+        orientation: tag!(Orientation, Vec::new()).and_then(|v| v.first().map(|&n| n as u32)),
+        gps,
+        altitude,
+        direction: tag!(GPSImgDirection, Vec::new())
+            .and_then(rational)
+            .and_then(ratio),
+        f_number: tag!(FNumber, Vec::new()).and_then(rational),
+        exposure_time: tag!(ExposureTime, Vec::new()).and_then(rational),
+        iso: tag!(ISO, Vec::new()).and_then(|v| v.first().map(|&n| n as u32)),
+        focal_length: tag!(FocalLength, Vec::new()).and_then(rational),
+        exposure_program: tag!(ExposureProgram, Vec::new())
+            .and_then(|v| v.first().map(|&n| n as u32)),
+    })
+}
+
+fn read_kamadak_exif(data: &[u8]) -> Option<ExifData> {
+    let exif = Reader::new()
+        .read_from_container(&mut Cursor::new(data))
+        .map_err(|e| tracing::debug!(error = %e, "kamadak-exif failed"))
+        .ok()?;
+
+    let field = |tag: Tag| exif.get_field(tag, In::PRIMARY).map(|f| &f.value);
+    let ascii = |tag: Tag| match field(tag)? {
+        Value::Ascii(parts) => parts
+            .first()
+            .map(|s| String::from_utf8_lossy(s).trim().to_string())
+            .filter(|s| !s.is_empty()),
+        _ => None,
+    };
+    let rationals = |tag: Tag| match field(tag)? {
+        Value::Rational(vals) => Some(vals.iter().map(|r| (r.num, r.denom)).collect::<Vec<_>>()),
+        _ => None,
+    };
+    let rational = |tag: Tag| rationals(tag)?.first().copied();
+    let uint = |tag: Tag| field(tag)?.get_uint(0);
+
+    let gps = (|| {
+        Some((
+            gps_to_decimal(&rationals(Tag::GPSLatitude)?, &ascii(Tag::GPSLatitudeRef)?)?,
+            gps_to_decimal(
+                &rationals(Tag::GPSLongitude)?,
+                &ascii(Tag::GPSLongitudeRef)?,
+            )?,
+        ))
+    })();
+
+    Some(ExifData {
+        parser: ExifParser::KamadakExif,
+        date_taken: ascii(Tag::DateTimeOriginal),
+        copyright: ascii(Tag::Copyright),
+        make: ascii(Tag::Make),
+        model: ascii(Tag::Model),
+        lens: ascii(Tag::LensModel),
+        orientation: uint(Tag::Orientation),
+        gps,
+        altitude: rational(Tag::GPSAltitude)
+            .and_then(|r| gps_altitude(r, uint(Tag::GPSAltitudeRef).unwrap_or(0))),
+        direction: rational(Tag::GPSImgDirection).and_then(ratio),
+        f_number: rational(Tag::FNumber),
+        exposure_time: rational(Tag::ExposureTime),
+        iso: uint(Tag::PhotographicSensitivity),
+        focal_length: rational(Tag::FocalLength),
+        exposure_program: uint(Tag::ExposureProgram),
+    })
+}
+
+/// Convert GPS degree/minute/second rationals to signed decimal degrees.
+fn gps_to_decimal(vals: &[Rational], direction: &str) -> Option<f64> {
+    if vals.len() < 3 {
+        return None;
+    }
+
+    let degrees = ratio(vals[0])?;
+    let minutes = ratio(vals[1]).unwrap_or(0.0);
+    let seconds = ratio(vals[2]).unwrap_or(0.0);
+
+    let mut coord = degrees + minutes / 60.0 + seconds / 3600.0;
+
+    // Apply direction
+    if direction == "S" || direction == "W" {
+        coord = -coord;
+    }
+
+    Some(coord)
+}
+
+/// Convert GPSAltitude and GPSAltitudeRef (1 = below sea level) to signed meters.
+fn gps_altitude(altitude: Rational, reference: u32) -> Option<f64> {
+    let meters = ratio(altitude)?;
+    Some(if reference == 1 { -meters } else { meters })
+}
+
+/// Value of a rational, or None if the denominator is zero.
+fn ratio((num, denom): Rational) -> Option<f64> {
+    (denom != 0).then(|| num as f64 / denom as f64)
+}
+
+fn strip_gps_little_exif(data: &[u8], extension: &str) -> Result<Vec<u8>> {
+    let mut data = data.to_vec();
+
+    let Some(file_type) = file_extension(extension) else {
+        // Unknown format, return unchanged
+        return Ok(data);
+    };
+
+    let mut metadata = match Metadata::new_from_vec(&data, file_type) {
+        Ok(m) => m,
+        Err(_) => {
+            // No EXIF or parse error, return unchanged
+            return Ok(data);
+        }
+    };
+
+    // Remove all GPS-related tags (complete list from EXIF GPS IFD)
+    metadata.remove_tag(ExifTag::GPSVersionID(Vec::new()));
+    metadata.remove_tag(ExifTag::GPSLatitudeRef(String::new()));
+    metadata.remove_tag(ExifTag::GPSLatitude(Vec::new()));
+    metadata.remove_tag(ExifTag::GPSLongitudeRef(String::new()));
+    metadata.remove_tag(ExifTag::GPSLongitude(Vec::new()));
+    metadata.remove_tag(ExifTag::GPSAltitudeRef(Vec::new()));
+    metadata.remove_tag(ExifTag::GPSAltitude(Vec::new()));
+    metadata.remove_tag(ExifTag::GPSTimeStamp(Vec::new()));
+    metadata.remove_tag(ExifTag::GPSSatellites(String::new()));
+    metadata.remove_tag(ExifTag::GPSStatus(String::new()));
+    metadata.remove_tag(ExifTag::GPSMeasureMode(String::new()));
+    metadata.remove_tag(ExifTag::GPSDOP(Vec::new()));
+    metadata.remove_tag(ExifTag::GPSSpeedRef(String::new()));
+    metadata.remove_tag(ExifTag::GPSSpeed(Vec::new()));
+    metadata.remove_tag(ExifTag::GPSTrackRef(String::new()));
+    metadata.remove_tag(ExifTag::GPSTrack(Vec::new()));
+    metadata.remove_tag(ExifTag::GPSImgDirectionRef(String::new()));
+    metadata.remove_tag(ExifTag::GPSImgDirection(Vec::new()));
+    metadata.remove_tag(ExifTag::GPSMapDatum(String::new()));
+    metadata.remove_tag(ExifTag::GPSDestLatitudeRef(String::new()));
+    metadata.remove_tag(ExifTag::GPSDestLatitude(Vec::new()));
+    metadata.remove_tag(ExifTag::GPSDestLongitudeRef(String::new()));
+    metadata.remove_tag(ExifTag::GPSDestLongitude(Vec::new()));
+    metadata.remove_tag(ExifTag::GPSDestBearingRef(String::new()));
+    metadata.remove_tag(ExifTag::GPSDestBearing(Vec::new()));
+    metadata.remove_tag(ExifTag::GPSDestDistanceRef(String::new()));
+    metadata.remove_tag(ExifTag::GPSDestDistance(Vec::new()));
+    metadata.remove_tag(ExifTag::GPSProcessingMethod(Vec::new()));
+    metadata.remove_tag(ExifTag::GPSAreaInformation(Vec::new()));
+    metadata.remove_tag(ExifTag::GPSDateStamp(String::new()));
+    metadata.remove_tag(ExifTag::GPSDifferential(Vec::new()));
+    metadata.remove_tag(ExifTag::GPSHPositioningError(Vec::new()));
+
+    // Write back to image (modifies data in-place)
+    metadata
+        .write_to_vec(&mut data, file_type)
+        .map_err(|e| Error::Other(format!("EXIF write error: {}", e)))?;
+
+    Ok(data)
+}
+
+/// TIFF tag holding the offset of the GPS IFD.
+const GPS_IFD_TAG: u16 = 0x8825;
+
+/// IFDs followed in a TIFF chain before giving up on a malformed file.
+const MAX_IFDS: usize = 16;
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// Prefix of EXIF data in JPEG APP1 segments (and some WebP EXIF chunks).
+const EXIF_HEADER: &[u8] = b"Exif\0\0";
+
+/// Clear GPS from every EXIF block and the XMP packet without moving any
+/// other bytes, so offsets and chunk sizes stay valid. Returns whether
+/// anything was cleared.
+fn strip_gps_in_place(data: &mut [u8]) -> bool {
+    let mut stripped = false;
+    for block in exif_blocks(data) {
+        stripped |= strip_tiff_gps(&mut data[block]);
+    }
+    if let Some(packet) = xmp::embedded_packet_range(data) {
+        stripped |= xmp::strip_gps(&mut data[packet]);
+    }
+
+    if stripped && data.starts_with(PNG_SIGNATURE) {
+        // Every PNG chunk carries a CRC of its contents
+        for (start, data_range) in png_chunks(data) {
+            let crc = crc32fast::hash(&data[start + 4..data_range.end]);
+            if let Some(slot) = data.get_mut(data_range.end..data_range.end + 4) {
+                slot.copy_from_slice(&crc.to_be_bytes());
+            }
+        }
+    }
+    stripped
+}
+
+/// Byte ranges of the TIFF structures holding EXIF in a file.
+fn exif_blocks(data: &[u8]) -> Vec<Range<usize>> {
+    if data.starts_with(b"II*\0") || data.starts_with(b"MM\0*") {
+        // TIFF, DNG, and most other RAW formats are TIFF throughout
+        return std::iter::once(0..data.len()).collect();
+    }
+    if data.starts_with(&[0xFF, 0xD8]) {
+        return jpeg_exif_blocks(data);
+    }
+    if data.starts_with(PNG_SIGNATURE) {
+        return png_chunks(data)
+            .into_iter()
+            .filter(|(start, _)| &data[start + 4..start + 8] == b"eXIf")
+            .map(|(_, range)| range)
+            .collect();
+    }
+    if data.len() >= 12 && &data[0..4] == b"RIFF" && &data[8..12] == b"WEBP" {
+        return webp_exif_blocks(data);
+    }
+    Vec::new()
+}
+
+/// APP1 segments holding EXIF, up to the start of the image data.
+fn jpeg_exif_blocks(data: &[u8]) -> Vec<Range<usize>> {
+    let mut blocks = Vec::new();
+    let mut pos = 2;
+    while pos + 4 <= data.len() && data[pos] == 0xFF {
+        let marker = data[pos + 1];
+        match marker {
+            // Fill byte before a marker
+            0xFF => {
+                pos += 1;
+                continue;
+            }
+            // Start of scan or end of image: no metadata follows
+            0xDA | 0xD9 => break,
+            _ => {}
+        }
+
+        let length = u16::from_be_bytes([data[pos + 2], data[pos + 3]]) as usize;
+        let end = (pos + 2 + length).min(data.len());
+        let segment = pos + 4..end;
+        if marker == 0xE1
+            && data
+                .get(segment.clone())
+                .is_some_and(|s| s.starts_with(EXIF_HEADER))
+        {
+            blocks.push(segment.start + EXIF_HEADER.len()..segment.end);
+        }
+        pos = end.max(pos + 4);
+    }
+    blocks
+}
+
+/// PNG chunks as (chunk start, data range). The chunk type follows the
+/// 4-byte length at the start; the CRC follows the data.
+fn png_chunks(data: &[u8]) -> Vec<(usize, Range<usize>)> {
+    let mut chunks = Vec::new();
+    let mut pos = PNG_SIGNATURE.len();
+    while pos + 12 <= data.len() {
+        let length = u32::from_be_bytes([data[pos], data[pos + 1], data[pos + 2], data[pos + 3]]);
+        let start = pos + 8;
+        let Some(end) = start
+            .checked_add(length as usize)
+            .filter(|end| end + 4 <= data.len())
+        else {
+            break;
+        };
+        chunks.push((pos, start..end));
+        if &data[pos + 4..pos + 8] == b"IEND" {
+            break;
+        }
+        pos = end + 4;
+    }
+    chunks
+}
+
+/// RIFF `EXIF` chunks of a WebP file.
+fn webp_exif_blocks(data: &[u8]) -> Vec<Range<usize>> {
+    let mut blocks = Vec::new();
+    let mut pos = 12;
+    while pos + 8 <= data.len() {
+        let size = u32::from_le_bytes([data[pos + 4], data[pos + 5], data[pos + 6], data[pos + 7]]);
+        let start = pos + 8;
+        let end = start.saturating_add(size as usize).min(data.len());
+        if &data[pos..pos + 4] == b"EXIF" {
+            let offset = if data[start..end].starts_with(EXIF_HEADER) {
+                EXIF_HEADER.len()
+            } else {
+                0
+            };
+            blocks.push(start + offset..end);
+        }
+        // Chunks are padded to an even size
+        pos = end + (size as usize & 1);
+    }
+    blocks
+}
+
+/// Clear the GPS IFD of a TIFF structure and unlink it from its parent IFD.
+///
+/// Everything else stays where it is: the GPS IFD and its values are zeroed,
+/// and the entry pointing to it is removed by shifting the parent's later
+/// entries down. Returns whether a GPS IFD was found.
+fn strip_tiff_gps(tiff: &mut [u8]) -> bool {
+    let little_endian = match tiff.get(0..2) {
+        Some(b"II") => true,
+        Some(b"MM") => false,
+        _ => return false,
+    };
+    let tiff_u16 = |tiff: &[u8], at: usize| {
+        let bytes = [*tiff.get(at)?, *tiff.get(at + 1)?];
+        Some(if little_endian {
+            u16::from_le_bytes(bytes)
+        } else {
+            u16::from_be_bytes(bytes)
+        })
+    };
+    let tiff_u32 = |tiff: &[u8], at: usize| {
+        let bytes: [u8; 4] = tiff.get(at..at + 4)?.try_into().ok()?;
+        Some(if little_endian {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        } as usize)
+    };
+
+    let mut stripped = false;
+    let mut visited = Vec::new();
+    let mut ifd = tiff_u32(tiff, 4).unwrap_or(0);
+    while ifd != 0 && !visited.contains(&ifd) && visited.len() < MAX_IFDS {
+        visited.push(ifd);
+        let Some(count) = tiff_u16(tiff, ifd).map(usize::from) else {
+            break;
+        };
+        let entries = ifd + 2;
+        let next_at = entries + count * 12;
+        let Some(next) = tiff_u32(tiff, next_at) else {
+            break;
+        };
+
+        let gps_entry = (0..count)
+            .map(|i| entries + i * 12)
+            .find(|&entry| tiff_u16(tiff, entry) == Some(GPS_IFD_TAG));
+        if let Some(entry) = gps_entry {
+            if let Some(gps_ifd) = tiff_u32(tiff, entry + 8) {
+                let gps_count = tiff_u16(tiff, gps_ifd).map_or(0, usize::from);
+                for i in 0..gps_count {
+                    let gps_entry = gps_ifd + 2 + i * 12;
+                    let size = tiff_u16(tiff, gps_entry + 2).map_or(0, tiff_type_size)
+                        * tiff_u32(tiff, gps_entry + 4).unwrap_or(0);
+                    // Values of four bytes or fewer sit in the entry itself
+                    if size > 4
+                        && let Some(value) = tiff_u32(tiff, gps_entry + 8)
+                        && let Some(bytes) = tiff.get_mut(value..value.saturating_add(size))
+                    {
+                        bytes.fill(0);
+                    }
+                }
+                let end = (gps_ifd + 6 + gps_count * 12).min(tiff.len());
+                if let Some(bytes) = tiff.get_mut(gps_ifd..end) {
+                    bytes.fill(0);
+                }
+            }
+
+            // Shift the later entries and the next IFD offset over the GPS
+            // entry, then zero the slot that frees up at the end
+            let remaining = count as u16 - 1;
+            let remaining = if little_endian {
+                remaining.to_le_bytes()
+            } else {
+                remaining.to_be_bytes()
+            };
+            tiff.copy_within(entry + 12..next_at + 4, entry);
+            tiff[next_at - 8..next_at + 4].fill(0);
+            tiff[ifd..ifd + 2].copy_from_slice(&remaining);
+            stripped = true;
+        }
+        ifd = next;
+    }
+    stripped
+}
+
+/// Size in bytes of one value of a TIFF field type (0 for unknown types).
+fn tiff_type_size(field_type: u16) -> usize {
+    match field_type {
+        // BYTE, ASCII, SBYTE, UNDEFINED
+        1 | 2 | 6 | 7 => 1,
+        // SHORT, SSHORT
+        3 | 8 => 2,
+        // LONG, SLONG, FLOAT, IFD
+        4 | 9 | 11 | 13 => 4,
+        // RATIONAL, SRATIONAL, DOUBLE
+        5 | 10 | 12 => 8,
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    /// A JPEG with the given EXIF tags, written by little_exif.
+    fn jpeg_with_exif(tags: Vec<ExifTag>) -> Vec<u8> {
+        let img = image::RgbImage::from_pixel(8, 8, image::Rgb([200, 100, 50]));
+        let mut data = Vec::new();
+        image::DynamicImage::ImageRgb8(img)
+            .write_to(&mut Cursor::new(&mut data), image::ImageFormat::Jpeg)
+            .unwrap();
+
+        let mut metadata = Metadata::new();
+        for tag in tags {
+            metadata.set_tag(tag);
+        }
+        metadata
+            .write_to_vec(&mut data, FileExtension::JPEG)
+            .unwrap();
+        data
+    }
+
+    fn dms(degrees: u32, minutes: u32, seconds: u32) -> Vec<uR64> {
+        [degrees, minutes, seconds]
+            .into_iter()
+            .map(|n| uR64 {
+                nominator: n,
+                denominator: 1,
+            })
+            .collect()
+    }
+
+    #[test_case(&[(35, 1), (40, 1), (0, 1)], "N", Some(35.0 + 40.0 / 60.0) ; "north")]
+    #[test_case(&[(35, 1), (40, 1), (0, 1)], "S", Some(-(35.0 + 40.0 / 60.0)) ; "south")]
+    #[test_case(&[(35, 0), (40, 1), (0, 1)], "N", None ; "zero denominator degrees")]
+    #[test_case(&[(35, 1), (40, 1)], "N", None ; "too few parts")]
+    fn test_gps_to_decimal(vals: &[Rational], direction: &str, expected: Option<f64>) {
+        let result = gps_to_decimal(vals, direction);
+        match expected {
+            Some(expected) => assert!((result.unwrap() - expected).abs() < 1e-9),
+            None => assert!(result.is_none()),
+        }
+    }
+
+    #[test_case((1234, 10), 0, Some(123.4) ; "above sea level")]
+    #[test_case((1234, 10), 1, Some(-123.4) ; "below sea level")]
+    #[test_case((1234, 0), 0, None ; "zero denominator")]
+    fn test_gps_altitude(altitude: Rational, reference: u32, expected: Option<f64>) {
+        assert_eq!(gps_altitude(altitude, reference), expected);
+    }
+
+    #[test]
+    fn both_parsers_agree() {
+        let data = jpeg_with_exif(vec![
+            ExifTag::Make("Canon".to_string()),
+            ExifTag::Model("Canon EOS R5".to_string()),
+            ExifTag::FNumber(vec![uR64 {
+                nominator: 28,
+                denominator: 10,
+            }]),
+            ExifTag::ISO(vec![400]),
+            ExifTag::Orientation(vec![6]),
+            ExifTag::GPSLatitudeRef("N".to_string()),
+            ExifTag::GPSLatitude(dms(35, 40, 30)),
+            ExifTag::GPSLongitudeRef("E".to_string()),
+            ExifTag::GPSLongitude(dms(139, 39, 0)),
+            ExifTag::GPSAltitudeRef(vec![1]),
+            ExifTag::GPSAltitude(vec![uR64 {
+                nominator: 4215,
+                denominator: 10,
+            }]),
+            ExifTag::GPSImgDirectionRef("T".to_string()),
+            ExifTag::GPSImgDirection(vec![uR64 {
+                nominator: 27050,
+                denominator: 100,
+            }]),
+        ]);
+
+        let little = read_little_exif(&data, "jpg").unwrap();
+        let kamadak = read_kamadak_exif(&data).unwrap();
+
+        assert_eq!(little.parser, ExifParser::LittleExif);
+        assert_eq!(kamadak.parser, ExifParser::KamadakExif);
+        assert_eq!(
+            ExifData {
+                parser: ExifParser::LittleExif,
+                ..kamadak
+            },
+            little
+        );
+        assert_eq!(little.make.as_deref(), Some("Canon"));
+        assert_eq!(little.iso, Some(400));
+        assert_eq!(little.orientation, Some(6));
+        assert!(little.gps.is_some());
+        assert_eq!(little.altitude, Some(-421.5));
+        assert_eq!(little.direction, Some(270.5));
+    }
+
+    #[test]
+    fn falls_back_when_little_exif_cannot_read_format() {
+        let data = jpeg_with_exif(vec![ExifTag::Make("FUJIFILM".to_string())]);
+
+        // An unknown extension means little_exif is never consulted
+        let exif = read(&data, "jpe").unwrap();
+
+        assert_eq!(exif.parser, ExifParser::KamadakExif);
+        assert_eq!(exif.make.as_deref(), Some("FUJIFILM"));
+    }
+
+    #[test]
+    fn read_garbage_returns_none() {
+        assert!(read(b"definitely not an image", "jpg").is_none());
+    }
+
+    #[test]
+    fn strip_gps_keeps_other_tags() {
+        let data = jpeg_with_exif(vec![
+            ExifTag::Make("SONY".to_string()),
+            ExifTag::GPSLatitudeRef("N".to_string()),
+            ExifTag::GPSLatitude(dms(1, 2, 3)),
+            ExifTag::GPSLongitudeRef("W".to_string()),
+            ExifTag::GPSLongitude(dms(4, 5, 6)),
+        ]);
+
+        let stripped = strip_gps(&data, "jpg");
+        let exif = read(&stripped, "jpg").unwrap();
+
+        assert_eq!(exif.make.as_deref(), Some("SONY"));
+        assert!(exif.gps.is_none());
+    }
+
+    #[test]
+    fn residual_finds_gps_and_serial_numbers() {
+        let data = jpeg_with_exif(vec![
+            ExifTag::SerialNumber("123456".to_string()),
+            ExifTag::GPSLatitudeRef("N".to_string()),
+            ExifTag::GPSLatitude(dms(35, 40, 30)),
+        ]);
+
+        assert_eq!(
+            residual(&data),
+            Residual {
+                gps: true,
+                serial_number: true,
+            }
+        );
+        assert_eq!(
+            residual(&strip_gps(&data, "jpg")),
+            Residual {
+                gps: false,
+                serial_number: true,
+            }
+        );
+    }
+
+    /// Area name stored in the fixtures' GPS IFD, to check no GPS bytes survive.
+    const GPS_AREA: &[u8] = b"SECRETPLACE";
+
+    /// A TIFF structure with a Make tag and a GPS IFD holding a latitude and
+    /// an area name, laid out the way cameras write it.
+    fn tiff_with_gps(little_endian: bool) -> Vec<u8> {
+        let u16b = |v: u16| {
+            if little_endian {
+                v.to_le_bytes()
+            } else {
+                v.to_be_bytes()
+            }
+        };
+        let u32b = |v: u32| {
+            if little_endian {
+                v.to_le_bytes()
+            } else {
+                v.to_be_bytes()
+            }
+        };
+        let entry = |tag: u16, field_type: u16, count: u32, value: [u8; 4]| {
+            [&u16b(tag)[..], &u16b(field_type), &u32b(count), &value].concat()
+        };
+
+        // Header (8), IFD0 (30), Make (8), GPS IFD (42), latitude (24), area (11)
+        let (ifd0, make, gps_ifd, latitude, area) = (8, 38, 46, 88, 112);
+        let mut tiff = Vec::new();
+        tiff.extend_from_slice(if little_endian { b"II" } else { b"MM" });
+        tiff.extend_from_slice(&u16b(42));
+        tiff.extend_from_slice(&u32b(ifd0));
+
+        tiff.extend_from_slice(&u16b(2));
+        tiff.extend(entry(0x010F, 2, 8, u32b(make)));
+        tiff.extend(entry(GPS_IFD_TAG, 4, 1, u32b(gps_ifd)));
+        tiff.extend_from_slice(&u32b(0));
+        tiff.extend_from_slice(b"TESTCAM\0");
+
+        tiff.extend_from_slice(&u16b(3));
+        tiff.extend(entry(0x0001, 2, 2, *b"N\0\0\0"));
+        tiff.extend(entry(0x0002, 5, 3, u32b(latitude)));
+        tiff.extend(entry(0x001C, 7, GPS_AREA.len() as u32, u32b(area)));
+        tiff.extend_from_slice(&u32b(0));
+        for part in [35, 1, 40, 1, 3030, 100] {
+            tiff.extend_from_slice(&u32b(part));
+        }
+        tiff.extend_from_slice(GPS_AREA);
+        tiff
+    }
+
+    /// An XMP packet with GPS written both as attributes and as an element.
+    fn xmp_with_gps() -> Vec<u8> {
+        br#"<?xpacket begin="" id="W5M0MpCehiHzreSzNTczkc9d"?>
+<x:xmpmeta xmlns:x="adobe:ns:meta/"><rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+<rdf:Description rdf:about="" xmlns:exif="http://ns.adobe.com/exif/1.0/" exif:GPSLatitude="35,40.5N" exif:GPSLongitude="139,39.0E">
+<exif:GPSAreaInformation>SECRETPLACE</exif:GPSAreaInformation>
+</rdf:Description></rdf:RDF></x:xmpmeta>
+<?xpacket end="w"?>"#
+            .to_vec()
+    }
+
+    fn encoded(format: image::ImageFormat) -> Vec<u8> {
+        let img = image::RgbImage::from_pixel(8, 8, image::Rgb([200, 100, 50]));
+        let mut data = Vec::new();
+        image::DynamicImage::ImageRgb8(img)
+            .write_to(&mut Cursor::new(&mut data), format)
+            .unwrap();
+        data
+    }
+
+    /// A WebP with `EXIF` and `XMP ` chunks, as written by most editors.
+    fn webp_with_gps() -> Vec<u8> {
+        let lossless = encoded(image::ImageFormat::WebP);
+        let chunk = |fourcc: &[u8], data: &[u8]| {
+            let mut chunk = [fourcc, &(data.len() as u32).to_le_bytes(), data].concat();
+            if data.len() % 2 == 1 {
+                chunk.push(0);
+            }
+            chunk
+        };
+
+        // VP8X flags: EXIF (0x08) and XMP (0x04), then the 8x8 canvas minus one
+        let vp8x = [0x0C, 0, 0, 0, 7, 0, 0, 7, 0, 0];
+        let body = [
+            &b"WEBP"[..],
+            &chunk(b"VP8X", &vp8x),
+            &lossless[12..],
+            &chunk(b"EXIF", &tiff_with_gps(true)),
+            &chunk(b"XMP ", &xmp_with_gps()),
+        ]
+        .concat();
+        [&b"RIFF"[..], &(body.len() as u32).to_le_bytes(), &body].concat()
+    }
+
+    /// A PNG with `eXIf` and uncompressed `iTXt` XMP chunks before `IEND`.
+    fn png_with_gps() -> Vec<u8> {
+        let png = encoded(image::ImageFormat::Png);
+        let chunk = |chunk_type: &[u8], data: &[u8]| {
+            let crc = crc32fast::hash(&[chunk_type, data].concat());
+            [
+                &(data.len() as u32).to_be_bytes()[..],
+                chunk_type,
+                data,
+                &crc.to_be_bytes(),
+            ]
+            .concat()
+        };
+
+        let iend = png.len() - 12;
+        let itxt = [&b"XML:com.adobe.xmp\0\0\0\0\0"[..], &xmp_with_gps()].concat();
+        [
+            &png[..iend],
+            &chunk(b"eXIf", &tiff_with_gps(false)),
+            &chunk(b"iTXt", &itxt),
+            &png[iend..],
+        ]
+        .concat()
+    }
+
+    fn contains(data: &[u8], needle: &[u8]) -> bool {
+        data.windows(needle.len()).any(|w| w == needle)
+    }
+
+    #[test_case(tiff_with_gps(true), "tif" ; "little endian tiff")]
+    #[test_case(tiff_with_gps(false), "tiff" ; "big endian tiff")]
+    #[test_case(webp_with_gps(), "webp" ; "webp")]
+    #[test_case(png_with_gps(), "png" ; "png")]
+    fn strip_gps_leaves_no_gps_bytes(data: Vec<u8>, extension: &str) {
+        assert!(residual(&data).gps);
+
+        let stripped = strip_gps(&data, extension);
+
+        assert!(!residual(&stripped).gps);
+        assert!(!contains(&stripped, GPS_AREA));
+        assert!(!contains(&stripped, b"GPSLatitude"));
+        assert_eq!(
+            read_kamadak_exif(&stripped).unwrap().make.as_deref(),
+            Some("TESTCAM")
+        );
+        if extension != "tif" && extension != "tiff" {
+            image::load_from_memory(&stripped).unwrap();
+        }
+    }
+
+    #[test]
+    fn strip_tiff_gps_unlinks_the_gps_ifd() {
+        let mut tiff = tiff_with_gps(true);
+
+        assert!(strip_tiff_gps(&mut tiff));
+
+        // IFD0 keeps only Make, the GPS IFD and its values are zeroed
+        assert_eq!(&tiff[8..10], &[1, 0]);
+        assert_eq!(&tiff[10..12], &[0x0F, 0x01]);
+        assert!(tiff[46..].iter().all(|&b| b == 0));
+        assert!(!strip_tiff_gps(&mut tiff));
+    }
+}
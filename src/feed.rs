@@ -0,0 +1,243 @@
+//! RSS and JSON feeds of the newest photos.
+//!
+//! The site gets a `feed.xml` (and a [JSON Feed](https://jsonfeed.org) in
+//! `feed.json`) listing the newest photos in the gallery, and
+//! albums can have their own so followers can subscribe to an ongoing series
+//! (e.g. a 365 project) without the rest of the gallery. Items are ordered by
+//! capture date and the feed only depends on gallery contents, so rebuilding
+//! an unchanged gallery produces an identical file.
+
+use std::fmt::Write as _;
+
+use quick_xml::escape::escape;
+use serde::Serialize;
+
+use crate::util::{rfc822, rfc3339};
+
+/// Filename of a feed within the directory it describes.
+pub const FILE_NAME: &str = "feed.xml";
+
+/// Filename of the site's JSON Feed.
+pub const JSON_FILE_NAME: &str = "feed.json";
+
+/// What a feed describes.
+#[derive(Debug)]
+pub struct Channel<'a> {
+    pub title: &'a str,
+    pub description: &'a str,
+    /// Absolute URL of the page the feed describes
+    pub link: &'a str,
+    /// Absolute URL of the feed itself
+    pub feed_url: &'a str,
+}
+
+/// A photo in a feed.
+#[derive(Debug)]
+pub struct Item {
+    /// Photo title, or its filename if it has none
+    pub title: String,
+    /// Absolute URL of the page showing the photo
+    pub link: String,
+    /// Absolute URL of the image shown in feed readers
+    pub image: String,
+    /// Caption
+    pub description: Option<String>,
+    /// Keywords
+    pub tags: Vec<String>,
+    /// Capture time in seconds since the epoch, if known and published
+    pub published: Option<i64>,
+}
+
+/// A [JSON Feed 1.1](https://jsonfeed.org/version/1.1) document.
+#[derive(Debug, Serialize)]
+pub struct JsonFeed<'a> {
+    version: &'static str,
+    title: &'a str,
+    description: &'a str,
+    home_page_url: &'a str,
+    feed_url: &'a str,
+    items: Vec<JsonFeedItem<'a>>,
+}
+
+/// An item in a JSON Feed.
+#[derive(Debug, Serialize)]
+struct JsonFeedItem<'a> {
+    id: &'a str,
+    url: &'a str,
+    title: &'a str,
+    content_html: String,
+    image: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    summary: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    date_published: Option<String>,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    tags: &'a [String],
+}
+
+/// Keep the `limit` newest items, newest first. Undated photos come last.
+pub fn newest(mut items: Vec<Item>, limit: usize) -> Vec<Item> {
+    items.sort_by_key(|item| std::cmp::Reverse(item.published));
+    items.truncate(limit);
+    items
+}
+
+/// Render an RSS 2.0 document.
+pub fn rss(channel: &Channel<'_>, items: &[Item]) -> String {
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    xml.push_str("<rss version=\"2.0\" xmlns:atom=\"http://www.w3.org/2005/Atom\">\n");
+    xml.push_str("<channel>\n");
+    element(&mut xml, "title", channel.title);
+    element(&mut xml, "link", channel.link);
+    element(&mut xml, "description", channel.description);
+    let _ = writeln!(
+        xml,
+        "<atom:link href=\"{}\" rel=\"self\" type=\"application/rss+xml\"/>",
+        escape(channel.feed_url)
+    );
+    element(&mut xml, "generator", "galerie");
+
+    // Newest capture date rather than build time, so unchanged feeds stay identical
+    if let Some(latest) = items.iter().filter_map(|i| i.published).max() {
+        element(&mut xml, "lastBuildDate", &rfc822(latest));
+    }
+
+    for item in items {
+        xml.push_str("<item>\n");
+        element(&mut xml, "title", &item.title);
+        element(&mut xml, "link", &item.link);
+        let _ = writeln!(
+            xml,
+            "<guid isPermaLink=\"false\">{}</guid>",
+            escape(&item.image)
+        );
+        if let Some(published) = item.published {
+            element(&mut xml, "pubDate", &rfc822(published));
+        }
+        element(&mut xml, "description", &item_html(item));
+        xml.push_str("</item>\n");
+    }
+
+    xml.push_str("</channel>\n</rss>\n");
+    xml
+}
+
+/// Build a JSON Feed document.
+pub fn json<'a>(channel: &Channel<'a>, items: &'a [Item]) -> JsonFeed<'a> {
+    JsonFeed {
+        version: "https://jsonfeed.org/version/1.1",
+        title: channel.title,
+        description: channel.description,
+        home_page_url: channel.link,
+        feed_url: channel.feed_url,
+        items: items
+            .iter()
+            .map(|item| JsonFeedItem {
+                id: &item.image,
+                url: &item.link,
+                title: &item.title,
+                content_html: item_html(item),
+                image: &item.image,
+                summary: item.description.as_deref(),
+                date_published: item.published.map(rfc3339),
+                tags: &item.tags,
+            })
+            .collect(),
+    }
+}
+
+/// HTML shown by feed readers: the image linked to its page, then the caption.
+pub fn item_html(item: &Item) -> String {
+    let mut html = format!(
+        "<p><a href=\"{}\"><img src=\"{}\" alt=\"{}\"></a></p>",
+        escape(&item.link),
+        escape(&item.image),
+        escape(&item.title)
+    );
+    if let Some(description) = &item.description {
+        let _ = write!(html, "<p>{}</p>", escape(description));
+    }
+    html
+}
+
+fn element(xml: &mut String, name: &str, text: &str) {
+    let _ = writeln!(xml, "<{name}>{}</{name}>", escape(text));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(title: &str, published: Option<i64>) -> Item {
+        Item {
+            title: title.to_string(),
+            link: format!("https://example.com/{title}.html"),
+            image: format!("https://example.com/images/{title}-abc12345-full.webp"),
+            description: None,
+            tags: Vec::new(),
+            published,
+        }
+    }
+
+    #[test]
+    fn newest_sorts_by_date_and_limits() {
+        let items = vec![
+            item("undated", None),
+            item("old", Some(100)),
+            item("new", Some(300)),
+            item("middle", Some(200)),
+        ];
+
+        let titles: Vec<_> = newest(items, 3).into_iter().map(|i| i.title).collect();
+
+        assert_eq!(titles, ["new", "middle", "old"]);
+    }
+
+    #[test]
+    fn rss_escapes_text_and_markup() {
+        let channel = Channel {
+            title: "Tom & Jerry",
+            description: "Photos",
+            link: "https://example.com/",
+            feed_url: "https://example.com/feed.xml",
+        };
+        let mut photo = item("beach", Some(0));
+        photo.description = Some("Sand <3".to_string());
+
+        let xml = rss(&channel, &[photo]);
+
+        assert!(xml.contains("<title>Tom &amp; Jerry</title>"));
+        assert!(xml.contains("<pubDate>Thu, 01 Jan 1970 00:00:00 +0000</pubDate>"));
+        assert!(xml.contains(
+            "&lt;img src=&quot;https://example.com/images/beach-abc12345-full.webp&quot;"
+        ));
+        assert!(xml.contains("&lt;p&gt;Sand &amp;lt;3&lt;/p&gt;"));
+        assert!(xml.contains("<lastBuildDate>Thu, 01 Jan 1970 00:00:00 +0000</lastBuildDate>"));
+    }
+
+    #[test]
+    fn json_feed_items() {
+        let channel = Channel {
+            title: "Photos",
+            description: "Photos",
+            link: "https://example.com/",
+            feed_url: "https://example.com/feed.json",
+        };
+        let mut photo = item("beach", Some(1712038365));
+        photo.tags = vec!["sand".to_string()];
+        let items = [photo, item("undated", None)];
+
+        let value = serde_json::to_value(json(&channel, &items)).unwrap();
+
+        assert_eq!(value["version"], "https://jsonfeed.org/version/1.1");
+        assert_eq!(value["items"][0]["date_published"], "2024-04-02T06:12:45Z");
+        assert_eq!(value["items"][0]["tags"][0], "sand");
+        assert_eq!(
+            value["items"][0]["id"],
+            "https://example.com/images/beach-abc12345-full.webp"
+        );
+        assert!(value["items"][1].get("date_published").is_none());
+        assert!(value["items"][1].get("tags").is_none());
+    }
+}
@@ -0,0 +1,204 @@
+//! Template functions defined in site.toml.
+//!
+//! Lets users parameterize theme templates (social links, analytics IDs,
+//! link prefixes) without editing the theme itself.
+
+use std::collections::{BTreeMap, HashMap};
+
+use tera::{Function, Tera, Value};
+
+use crate::config::TemplateFunction;
+use crate::error::{Error, Result};
+use crate::pipeline::toml_to_json;
+
+/// Names of functions galerie registers itself.
+const RESERVED: &[&str] = &["static"];
+
+/// A config-defined function, resolved at pipeline load.
+#[derive(Debug)]
+struct ConfigFunction {
+    name: String,
+    kind: Resolved,
+}
+
+#[derive(Debug)]
+enum Resolved {
+    Value(Value),
+    Map(BTreeMap<String, String>),
+    /// Environment variable name and its value (or configured default)
+    Env(String, Option<String>),
+    Link(String),
+}
+
+/// Register all config-defined functions with the template engine.
+pub fn register(tera: &mut Tera, functions: &BTreeMap<String, TemplateFunction>) -> Result<()> {
+    for (name, def) in functions {
+        if RESERVED.contains(&name.as_str()) {
+            return Err(Error::Other(format!(
+                "template function name '{}' is reserved",
+                name
+            )));
+        }
+
+        tera.register_function(name, ConfigFunction::new(name, def));
+    }
+
+    if !functions.is_empty() {
+        tracing::debug!(
+            functions = ?functions.keys().collect::<Vec<_>>(),
+            "registered template functions"
+        );
+    }
+
+    Ok(())
+}
+
+/// What the output of config-defined functions depends on, including the
+/// environment variables they read, to tell if pages using them are current.
+pub fn fingerprint(functions: &BTreeMap<String, TemplateFunction>) -> String {
+    functions
+        .iter()
+        .map(|(name, def)| format!("{:?}", ConfigFunction::new(name, def)))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+impl ConfigFunction {
+    fn new(name: &str, def: &TemplateFunction) -> Self {
+        let kind = match def {
+            TemplateFunction::Value { value } => Resolved::Value(toml_to_json(value)),
+            TemplateFunction::Map { entries } => Resolved::Map(entries.clone()),
+            TemplateFunction::Env { var, default } => Resolved::Env(
+                var.clone(),
+                std::env::var(var).ok().or_else(|| default.clone()),
+            ),
+            TemplateFunction::Link { base } => Resolved::Link(base.clone()),
+        };
+
+        Self {
+            name: name.to_string(),
+            kind,
+        }
+    }
+
+    /// Get a required string argument.
+    fn arg<'a>(&self, args: &'a HashMap<String, Value>, key: &str) -> tera::Result<&'a str> {
+        args.get(key).and_then(Value::as_str).ok_or_else(|| {
+            tera::Error::msg(format!("{}() requires a '{}' argument", self.name, key))
+        })
+    }
+}
+
+impl Function for ConfigFunction {
+    fn call(&self, args: &HashMap<String, Value>) -> tera::Result<Value> {
+        let fallback = args.get("default").cloned();
+
+        match &self.kind {
+            Resolved::Value(value) => Ok(value.clone()),
+            Resolved::Map(entries) => {
+                let key = self.arg(args, "key")?;
+                match (entries.get(key), fallback) {
+                    (Some(value), _) => Ok(Value::String(value.clone())),
+                    (None, Some(fallback)) => Ok(fallback),
+                    (None, None) => Err(tera::Error::msg(format!(
+                        "{}(): unknown key '{}'. Available: {:?}",
+                        self.name,
+                        key,
+                        entries.keys().collect::<Vec<_>>()
+                    ))),
+                }
+            }
+            Resolved::Env(var, value) => match (value, fallback) {
+                (Some(value), _) => Ok(Value::String(value.clone())),
+                (None, Some(fallback)) => Ok(fallback),
+                (None, None) => Err(tera::Error::msg(format!(
+                    "{}(): environment variable '{}' is not set",
+                    self.name, var
+                ))),
+            },
+            Resolved::Link(base) => {
+                let path = self.arg(args, "path")?;
+                if path.is_empty() {
+                    return Ok(Value::String(base.clone()));
+                }
+                Ok(Value::String(format!(
+                    "{}/{}",
+                    base.trim_end_matches('/'),
+                    path.trim_start_matches('/')
+                )))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    fn function(toml: &str) -> ConfigFunction {
+        let def: TemplateFunction = toml::from_str(toml).unwrap();
+        ConfigFunction::new("f", &def)
+    }
+
+    fn args(pairs: &[(&str, &str)]) -> HashMap<String, Value> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), Value::String(v.to_string())))
+            .collect()
+    }
+
+    #[test_case(r#"kind = "value""#, "value = \"hi\"", &[], Some("hi") ; "value")]
+    #[test_case(r#"kind = "map""#, "entries = { a = \"1\" }", &[("key", "a")], Some("1") ; "map hit")]
+    #[test_case(r#"kind = "map""#, "entries = { a = \"1\" }", &[("key", "b")], None ; "map miss")]
+    #[test_case(r#"kind = "map""#, "entries = { a = \"1\" }", &[("key", "b"), ("default", "x")], Some("x") ; "map miss with default")]
+    #[test_case(r#"kind = "map""#, "entries = {}", &[], None ; "map without key")]
+    #[test_case(r#"kind = "env""#, "var = \"GALERIE_TEST_UNSET_VAR\"", &[], None ; "env unset")]
+    #[test_case(r#"kind = "env""#, "var = \"GALERIE_TEST_UNSET_VAR\"\ndefault = \"d\"", &[], Some("d") ; "env config default")]
+    #[test_case(r#"kind = "env""#, "var = \"GALERIE_TEST_UNSET_VAR\"", &[("default", "x")], Some("x") ; "env call default")]
+    #[test_case(r#"kind = "link""#, "base = \"https://github.com/\"", &[("path", "/me/repo")], Some("https://github.com/me/repo") ; "link")]
+    #[test_case(r#"kind = "link""#, "base = \"https://github.com\"", &[("path", "")], Some("https://github.com") ; "link empty path")]
+    fn test_call(kind: &str, body: &str, call_args: &[(&str, &str)], expected: Option<&str>) {
+        let f = function(&format!("{}\n{}", kind, body));
+
+        let result = f.call(&args(call_args));
+
+        match expected {
+            Some(expected) => assert_eq!(result.unwrap(), Value::String(expected.to_string())),
+            None => assert!(result.is_err()),
+        }
+    }
+
+    #[test]
+    fn register_rejects_reserved_names() {
+        let mut tera = Tera::default();
+        let mut functions = BTreeMap::new();
+        functions.insert(
+            "static".to_string(),
+            TemplateFunction::Link {
+                base: "/".to_string(),
+            },
+        );
+
+        assert!(register(&mut tera, &functions).is_err());
+    }
+
+    #[test]
+    fn registered_functions_render() {
+        let mut tera = Tera::default();
+        let mut functions = BTreeMap::new();
+        functions.insert(
+            "gh".to_string(),
+            TemplateFunction::Link {
+                base: "https://github.com".to_string(),
+            },
+        );
+        register(&mut tera, &functions).unwrap();
+
+        let html = tera
+            .render_str(r#"{{ gh(path="me") }}"#, &tera::Context::new())
+            .unwrap();
+
+        assert_eq!(html, "https://github.com/me");
+    }
+}
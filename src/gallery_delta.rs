@@ -0,0 +1,322 @@
+//! Changes between versions of the gallery JSON.
+//!
+//! The gallery JSON lists every photo, so a client holding last week's copy
+//! downloads the whole file again when one photo is added. With
+//! `[manifest] gallery_deltas`, each build also publishes
+//! `gallery-delta-{from}-{to}.json` with what changed since the gallery the
+//! previous build published, found through the manifest that build wrote.
+//! The manifest lists the deltas kept, oldest first, so a client applies each
+//! one after its copy's hash in turn.
+//!
+//! A delta replaces the top-level values that changed, and for each list of
+//! albums, photos, and collections gives the items that are new or changed,
+//! the keys of those removed, and, when the keys changed, their new order.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value, json};
+
+use crate::error::{Error, Result};
+
+/// Lists in the gallery JSON, with the field that tells their items apart.
+const LISTS: [(&str, &str); 3] = [
+    ("albums", "path"),
+    ("photos", "htmlPath"),
+    ("collections", "path"),
+];
+
+/// A delta published with the site.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Delta {
+    /// Hash of the gallery JSON it applies to
+    pub from: String,
+    /// Hash of the gallery JSON it produces
+    pub to: String,
+    pub url: String,
+}
+
+/// What the manifest of the previous build says about its gallery JSON.
+#[derive(Debug, Default, Deserialize)]
+struct Previous {
+    #[serde(default)]
+    gallery: String,
+    #[serde(default)]
+    gallery_deltas: Vec<Delta>,
+}
+
+#[derive(Deserialize)]
+struct PreviousManifest {
+    data: Previous,
+}
+
+/// Hash of the gallery JSON at `url`, e.g. "/static/gallery-def67890.json".
+fn hash(url: &str) -> Option<&str> {
+    url.strip_prefix("/static/gallery-")?.strip_suffix(".json")
+}
+
+/// Output path of the file at `url`.
+fn output_path(output_dir: &Path, url: &str) -> std::path::PathBuf {
+    output_dir.join(url.trim_start_matches('/'))
+}
+
+/// Write the delta from the gallery JSON the manifest at `manifest` names to
+/// `gallery`, whose hash is `to`, and return the deltas to publish, at most
+/// `keep`. Deltas that no longer lead to `gallery` are dropped.
+pub fn publish(
+    output_dir: &Path,
+    manifest: &Path,
+    gallery: &Value,
+    to: &str,
+    keep: usize,
+) -> Result<Vec<Delta>> {
+    let previous = match fs::read_to_string(manifest) {
+        Ok(content) => match serde_json::from_str::<PreviousManifest>(&content) {
+            Ok(previous) => previous.data,
+            Err(e) => {
+                tracing::warn!(path = %manifest.display(), error = %e, "can't read the previous manifest for gallery deltas");
+                Previous::default()
+            }
+        },
+        Err(_) => Previous::default(),
+    };
+    let mut deltas: Vec<Delta> = previous
+        .gallery_deltas
+        .into_iter()
+        .filter(|delta| output_path(output_dir, &delta.url).is_file())
+        .collect();
+
+    match hash(&previous.gallery) {
+        Some(from) if from != to => {
+            let old = fs::read_to_string(output_path(output_dir, &previous.gallery))
+                .ok()
+                .and_then(|content| serde_json::from_str::<Value>(&content).ok());
+            match old {
+                Some(old) => {
+                    let url = format!("/static/gallery-delta-{}-{}.json", from, to);
+                    let json =
+                        serde_json::to_string(&diff(&old, gallery, from, to)).map_err(|e| {
+                            Error::Other(format!("failed to serialize gallery delta: {}", e))
+                        })?;
+                    fs::write(output_path(output_dir, &url), json)?;
+                    deltas.push(Delta {
+                        from: from.to_string(),
+                        to: to.to_string(),
+                        url,
+                    });
+                }
+                None => {
+                    tracing::warn!(gallery = %previous.gallery, "previous gallery JSON is gone, starting gallery deltas over");
+                    deltas.clear();
+                }
+            }
+        }
+        Some(_) => {}
+        None => deltas.clear(),
+    }
+
+    let skip = deltas.len().saturating_sub(keep);
+    deltas.drain(..skip);
+    tracing::debug!(deltas = deltas.len(), "published gallery deltas");
+    Ok(deltas)
+}
+
+/// What changed from `old` to `new`, gallery JSONs hashing to `from` and `to`.
+pub fn diff(old: &Value, new: &Value, from: &str, to: &str) -> Value {
+    let empty = Map::new();
+    let old = old.as_object().unwrap_or(&empty);
+    let new = new.as_object().unwrap_or(&empty);
+
+    let mut set = Map::new();
+    let mut lists = Map::new();
+    for (name, value) in new {
+        match LISTS.iter().find(|(list, _)| list == name) {
+            Some((_, key)) => {
+                if let Some(change) = diff_list(old.get(name), value, key) {
+                    lists.insert(name.clone(), change);
+                }
+            }
+            None => {
+                if old.get(name) != Some(value) {
+                    set.insert(name.clone(), value.clone());
+                }
+            }
+        }
+    }
+    json!({ "from": from, "to": to, "set": set, "lists": lists })
+}
+
+/// Changes to a list whose items are told apart by `key`, if any.
+fn diff_list(old: Option<&Value>, new: &Value, key: &str) -> Option<Value> {
+    fn key_of<'a>(item: &'a Value, key: &str) -> &'a str {
+        item.get(key).and_then(Value::as_str).unwrap_or_default()
+    }
+    let old = old
+        .and_then(Value::as_array)
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    let new = new.as_array().map(Vec::as_slice).unwrap_or_default();
+
+    let old_items: HashMap<&str, &Value> =
+        old.iter().map(|item| (key_of(item, key), item)).collect();
+    let new_keys: Vec<&str> = new.iter().map(|item| key_of(item, key)).collect();
+    let kept: HashSet<&str> = new_keys.iter().copied().collect();
+
+    let changed: Vec<&Value> = new
+        .iter()
+        .filter(|item| old_items.get(key_of(item, key)) != Some(item))
+        .collect();
+    let removed: Vec<&str> = old
+        .iter()
+        .map(|item| key_of(item, key))
+        .filter(|key| !kept.contains(key))
+        .collect();
+    let reordered = !old
+        .iter()
+        .map(|item| key_of(item, key))
+        .eq(new_keys.iter().copied());
+    if changed.is_empty() && !reordered {
+        return None;
+    }
+
+    let mut change = json!({ "key": key, "changed": changed, "removed": removed });
+    if reordered {
+        change["order"] = json!(new_keys);
+    }
+    Some(change)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Apply `delta` to `old`, as a client would.
+    fn apply(old: &Value, delta: &Value) -> Value {
+        let mut new = old.clone();
+        for (name, value) in delta["set"].as_object().unwrap() {
+            new[name] = value.clone();
+        }
+        for (name, change) in delta["lists"].as_object().unwrap() {
+            let key = change["key"].as_str().unwrap();
+            let key_of = |item: &Value| item[key].as_str().unwrap().to_string();
+            let mut items: HashMap<String, Value> = new[name]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|item| (key_of(item), item.clone()))
+                .collect();
+            for item in change["changed"].as_array().unwrap() {
+                items.insert(key_of(item), item.clone());
+            }
+            let order: Vec<String> = match change.get("order") {
+                Some(order) => order
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|key| key.as_str().unwrap().to_string())
+                    .collect(),
+                None => new[name].as_array().unwrap().iter().map(key_of).collect(),
+            };
+            new[name] = order.iter().map(|key| items[key].clone()).collect();
+        }
+        new
+    }
+
+    fn gallery(title: &str, photos: &[(&str, u32)]) -> Value {
+        json!({
+            "site": { "title": title },
+            "albums": [{ "path": "", "photoCount": photos.len() }],
+            "photos": photos
+                .iter()
+                .map(|(path, width)| json!({ "htmlPath": path, "width": width }))
+                .collect::<Vec<_>>(),
+            "collections": [],
+        })
+    }
+
+    #[test]
+    fn added_photo_sent_alone() {
+        let old = gallery("Trips", &[("/a.html", 100), ("/b.html", 200)]);
+        let new = gallery(
+            "Trips",
+            &[("/a.html", 100), ("/b.html", 200), ("/c.html", 300)],
+        );
+
+        let delta = diff(&old, &new, "aaaa", "bbbb");
+
+        assert!(delta["set"].as_object().unwrap().is_empty());
+        let photos = &delta["lists"]["photos"];
+        assert_eq!(
+            photos["changed"],
+            json!([{ "htmlPath": "/c.html", "width": 300 }])
+        );
+        assert_eq!(photos["order"], json!(["/a.html", "/b.html", "/c.html"]));
+        assert!(delta["lists"].get("collections").is_none());
+        assert_eq!(apply(&old, &delta), new);
+    }
+
+    #[test]
+    fn changes_applied() {
+        let old = gallery(
+            "Trips",
+            &[("/a.html", 100), ("/b.html", 200), ("/c.html", 300)],
+        );
+        let new = gallery("Travels", &[("/c.html", 300), ("/a.html", 150)]);
+
+        let delta = diff(&old, &new, "aaaa", "bbbb");
+
+        assert_eq!(delta["set"]["site"]["title"], "Travels");
+        assert_eq!(delta["lists"]["photos"]["removed"], json!(["/b.html"]));
+        assert_eq!(
+            delta["lists"]["photos"]["changed"],
+            json!([{ "htmlPath": "/a.html", "width": 150 }])
+        );
+        assert_eq!(apply(&old, &delta), new);
+    }
+
+    #[test]
+    fn deltas_chain_across_builds() {
+        let site_dir = tempfile::tempdir().unwrap();
+        crate::preview::generate(site_dir.path(), "basic").unwrap();
+        let config = site_dir.path().join("site.toml");
+        let mut toml = fs::read_to_string(&config).unwrap();
+        toml.push_str("\n[manifest]\ngallery_deltas = 1\n");
+        fs::write(&config, &toml).unwrap();
+        let output = site_dir.path().join("dist");
+        let build = |title: &str| {
+            let mut site = crate::config::Site::load(&config).unwrap();
+            site.title = Some(title.to_string());
+            let mut pipeline =
+                crate::pipeline::Pipeline::load(site_dir.path().to_path_buf(), site, false)
+                    .unwrap();
+            pipeline.build().unwrap();
+            let manifest: Value =
+                serde_json::from_str(&fs::read_to_string(output.join("manifest.json")).unwrap())
+                    .unwrap();
+            manifest["data"].clone()
+        };
+        let read = |url: &Value| -> Value {
+            let path = output.join(url.as_str().unwrap().trim_start_matches('/'));
+            serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap()
+        };
+
+        let first = build("First");
+        assert_eq!(first["gallery_deltas"], json!([]));
+        let old = read(&first["gallery"]);
+
+        let second = build("Second");
+        let delta = &second["gallery_deltas"][0];
+        assert_eq!(
+            delta["from"],
+            hash(first["gallery"].as_str().unwrap()).unwrap()
+        );
+        assert_eq!(apply(&old, &read(&delta["url"])), read(&second["gallery"]));
+
+        let third = build("Third");
+        // Only the newest is kept
+        assert_eq!(third["gallery_deltas"].as_array().unwrap().len(), 1);
+        assert!(!output_path(&output, delta["url"].as_str().unwrap()).exists());
+    }
+}
@@ -0,0 +1,410 @@
+//! Reverse geocoding providers.
+//!
+//! The built-in offline geocoder needs no network access but only knows
+//! populated places, so rural photos often get a nearby town. Sites can
+//! point galerie at a Nominatim or Photon server instead. Remote lookups
+//! are rate limited and stored in the metadata cache, and any failure
+//! falls back to the offline geocoder. After the first failure the remote
+//! server isn't asked again for the rest of the build, so a server that's
+//! down doesn't cost a timeout per photo.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::config::GeocodingConfig;
+use crate::error::{Error, Result};
+
+/// Timeout for a single remote lookup.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A reverse-geocoded place, as stored in the metadata cache.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Place {
+    /// City or locality name
+    pub city: String,
+    /// State, province, or administrative region
+    pub region: Option<String>,
+    /// ISO 3166-1 alpha-2 country code
+    pub country_code: String,
+}
+
+/// A source of reverse geocoding results.
+pub trait Provider: Send + Sync {
+    /// Name shown in logs and errors.
+    fn name(&self) -> &'static str;
+
+    /// Key keeping this provider's results apart in the cache.
+    fn cache_key(&self) -> String {
+        self.name().to_string()
+    }
+
+    /// Find the place at the given coordinates.
+    fn reverse(&self, latitude: f64, longitude: f64) -> Result<Place>;
+}
+
+/// The provider configured for the site.
+#[derive(Debug)]
+pub enum Geocoder {
+    Offline(Offline),
+    Remote(Remote),
+}
+
+impl Geocoder {
+    pub fn new(config: &GeocodingConfig) -> Self {
+        match config {
+            GeocodingConfig::Offline => Self::Offline(Offline),
+            GeocodingConfig::Nominatim {
+                url,
+                requests_per_second,
+            } => Self::Remote(Remote::new(Api::Nominatim, url, *requests_per_second)),
+            GeocodingConfig::Photon {
+                url,
+                requests_per_second,
+            } => Self::Remote(Remote::new(Api::Photon, url, *requests_per_second)),
+        }
+    }
+}
+
+impl Provider for Geocoder {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Offline(offline) => offline.name(),
+            Self::Remote(remote) => remote.name(),
+        }
+    }
+
+    fn cache_key(&self) -> String {
+        match self {
+            Self::Offline(offline) => offline.cache_key(),
+            Self::Remote(remote) => remote.cache_key(),
+        }
+    }
+
+    fn reverse(&self, latitude: f64, longitude: f64) -> Result<Place> {
+        match self {
+            Self::Offline(offline) => offline.reverse(latitude, longitude),
+            Self::Remote(remote) => remote.reverse(latitude, longitude),
+        }
+    }
+}
+
+/// The bundled offline geocoder (nearest populated place).
+#[derive(Debug)]
+pub struct Offline;
+
+impl Provider for Offline {
+    fn name(&self) -> &'static str {
+        "offline"
+    }
+
+    fn reverse(&self, latitude: f64, longitude: f64) -> Result<Place> {
+        // Loading the geocoder's dataset is expensive, so share one instance
+        static GEOCODER: LazyLock<reverse_geocoder::ReverseGeocoder> =
+            LazyLock::new(reverse_geocoder::ReverseGeocoder::new);
+
+        let record = GEOCODER.search((latitude, longitude)).record;
+
+        Ok(Place {
+            city: record.name.to_string(),
+            region: Some(record.admin1.to_string()).filter(|r| !r.is_empty()),
+            country_code: record.cc.to_string(),
+        })
+    }
+}
+
+/// Remote geocoding APIs.
+#[derive(Debug, Clone, Copy)]
+enum Api {
+    Nominatim,
+    Photon,
+}
+
+/// A provider backed by an HTTP geocoding API.
+#[derive(Debug)]
+pub struct Remote {
+    api: Api,
+    url: String,
+    agent: ureq::Agent,
+    limiter: RateLimiter,
+    /// Set once a lookup fails, after which the server isn't asked again
+    failed: AtomicBool,
+}
+
+impl Remote {
+    fn new(api: Api, url: &str, requests_per_second: f64) -> Self {
+        let agent = ureq::AgentBuilder::new()
+            .timeout(TIMEOUT)
+            .user_agent(concat!("galerie/", env!("CARGO_PKG_VERSION")))
+            .build();
+
+        Self {
+            api,
+            url: url.trim_end_matches('/').to_string(),
+            agent,
+            limiter: RateLimiter::new(requests_per_second),
+            failed: AtomicBool::new(false),
+        }
+    }
+
+    fn error(&self, message: impl ToString) -> Error {
+        Error::Geocode {
+            provider: self.name().to_string(),
+            message: message.to_string(),
+        }
+    }
+
+    /// Stop asking the server for the rest of the build after `message`.
+    fn trip(&self, message: impl ToString) -> Error {
+        if !self.failed.swap(true, Ordering::Relaxed) {
+            tracing::warn!(
+                provider = self.name(),
+                "geocoding server failed, using the offline geocoder for the rest of the build"
+            );
+        }
+        self.error(message)
+    }
+}
+
+impl Provider for Remote {
+    fn name(&self) -> &'static str {
+        match self.api {
+            Api::Nominatim => "nominatim",
+            Api::Photon => "photon",
+        }
+    }
+
+    /// Servers of the same API can answer differently, so each is cached
+    /// apart.
+    fn cache_key(&self) -> String {
+        format!("{} {}", self.name(), self.url)
+    }
+
+    fn reverse(&self, latitude: f64, longitude: f64) -> Result<Place> {
+        if self.failed.load(Ordering::Relaxed) {
+            return Err(self.error("skipped after an earlier failure"));
+        }
+        let request = self
+            .agent
+            .get(&format!("{}/reverse", self.url))
+            .query("lat", &latitude.to_string())
+            .query("lon", &longitude.to_string());
+        let request = match self.api {
+            // Zoom 10 asks for city-level detail rather than street addresses
+            Api::Nominatim => request.query("format", "jsonv2").query("zoom", "10"),
+            Api::Photon => request.query("limit", "1"),
+        };
+
+        self.limiter.wait();
+        tracing::trace!(
+            provider = self.name(),
+            latitude,
+            longitude,
+            "reverse geocoding"
+        );
+
+        let body: Value = request
+            .call()
+            .map_err(|e| self.trip(e))?
+            .into_json()
+            .map_err(|e| self.trip(e))?;
+
+        let place = match self.api {
+            Api::Nominatim => parse_nominatim(&body),
+            Api::Photon => parse_photon(&body),
+        };
+        place.ok_or_else(|| self.error("no place found"))
+    }
+}
+
+/// Read a place from a Nominatim `/reverse?format=jsonv2` response.
+fn parse_nominatim(body: &Value) -> Option<Place> {
+    let address = body.get("address")?;
+    let field = |name: &str| {
+        address
+            .get(name)
+            .and_then(Value::as_str)
+            .map(str::to_string)
+    };
+
+    let city = [
+        "city",
+        "town",
+        "village",
+        "hamlet",
+        "municipality",
+        "county",
+    ]
+    .into_iter()
+    .find_map(field)?;
+
+    Some(Place {
+        city,
+        region: field("state").or_else(|| field("region")),
+        country_code: field("country_code")?.to_uppercase(),
+    })
+}
+
+/// Read a place from a Photon `/reverse` GeoJSON response.
+fn parse_photon(body: &Value) -> Option<Place> {
+    let properties = body.get("features")?.get(0)?.get("properties")?;
+    let field = |name: &str| {
+        properties
+            .get(name)
+            .and_then(Value::as_str)
+            .map(str::to_string)
+    };
+
+    // Results that are themselves a city carry it in `name`
+    let city = field("city").or_else(|| match field("type").as_deref() {
+        Some("city" | "town" | "village" | "locality") => field("name"),
+        _ => None,
+    })?;
+
+    Some(Place {
+        city,
+        region: field("state"),
+        country_code: field("countrycode")?.to_uppercase(),
+    })
+}
+
+/// Spaces out requests shared across the photo processing workers.
+#[derive(Debug)]
+struct RateLimiter {
+    interval: Duration,
+    next: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    /// Allow `requests_per_second` requests; zero disables limiting.
+    fn new(requests_per_second: f64) -> Self {
+        let interval = if requests_per_second > 0.0 {
+            Duration::from_secs_f64(1.0 / requests_per_second)
+        } else {
+            Duration::ZERO
+        };
+
+        Self {
+            interval,
+            next: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Block until the next request is allowed.
+    fn wait(&self) {
+        let at = {
+            let mut next = self.next.lock().unwrap();
+            let at = (*next).max(Instant::now());
+            *next = at + self.interval;
+            at
+        };
+        std::thread::sleep(at.saturating_duration_since(Instant::now()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use test_case::test_case;
+
+    #[test_case(json!({"address": {"city": "Reykjavík", "state": "Capital Region", "country_code": "is"}}), Some(("Reykjavík", Some("Capital Region"), "IS")) ; "city")]
+    #[test_case(json!({"address": {"village": "Vík", "country_code": "is"}}), Some(("Vík", None, "IS")) ; "village")]
+    #[test_case(json!({"error": "Unable to geocode"}), None ; "error")]
+    #[test_case(json!({"address": {"country_code": "is"}}), None ; "no locality")]
+    fn test_parse_nominatim(body: Value, expected: Option<(&str, Option<&str>, &str)>) {
+        let place = parse_nominatim(&body);
+        assert_eq!(
+            place.as_ref().map(|p| (
+                p.city.as_str(),
+                p.region.as_deref(),
+                p.country_code.as_str()
+            )),
+            expected
+        );
+    }
+
+    #[test_case(json!({"features": [{"properties": {"name": "Hallgrímskirkja", "city": "Reykjavík", "state": "Capital Region", "countrycode": "IS", "type": "house"}}]}), Some(("Reykjavík", Some("Capital Region"), "IS")) ; "city property")]
+    #[test_case(json!({"features": [{"properties": {"name": "Vík", "countrycode": "IS", "type": "village"}}]}), Some(("Vík", None, "IS")) ; "result is a locality")]
+    #[test_case(json!({"features": []}), None ; "no features")]
+    fn test_parse_photon(body: Value, expected: Option<(&str, Option<&str>, &str)>) {
+        let place = parse_photon(&body);
+        assert_eq!(
+            place.as_ref().map(|p| (
+                p.city.as_str(),
+                p.region.as_deref(),
+                p.country_code.as_str()
+            )),
+            expected
+        );
+    }
+
+    #[test]
+    fn rate_limiter_spaces_requests() {
+        let limiter = RateLimiter::new(50.0);
+        let start = Instant::now();
+
+        for _ in 0..3 {
+            limiter.wait();
+        }
+
+        // First request is immediate, the next two wait 20ms each
+        assert!(start.elapsed() >= Duration::from_millis(40));
+    }
+
+    #[test]
+    fn servers_cached_apart() {
+        let geocoder = |url: &str| {
+            Geocoder::new(&GeocodingConfig::Nominatim {
+                url: url.to_string(),
+                requests_per_second: 0.0,
+            })
+        };
+
+        assert_eq!(
+            geocoder("https://nominatim.example.com/").cache_key(),
+            "nominatim https://nominatim.example.com"
+        );
+        assert_ne!(
+            geocoder("https://nominatim.example.com").cache_key(),
+            geocoder("http://localhost:8080").cache_key()
+        );
+        assert_eq!(
+            Geocoder::new(&GeocodingConfig::Offline).cache_key(),
+            "offline"
+        );
+    }
+
+    #[test]
+    fn unreachable_server_is_an_error() {
+        let provider = Geocoder::new(&GeocodingConfig::Nominatim {
+            url: "http://127.0.0.1:9".to_string(),
+            requests_per_second: 0.0,
+        });
+
+        let err = provider.reverse(64.146, -21.942).unwrap_err();
+
+        assert!(matches!(err, Error::Geocode { ref provider, .. } if provider == "nominatim"));
+    }
+
+    #[test]
+    fn failed_server_not_asked_again() {
+        let Geocoder::Remote(remote) = Geocoder::new(&GeocodingConfig::Photon {
+            url: "http://127.0.0.1:9".to_string(),
+            requests_per_second: 0.0,
+        }) else {
+            unreachable!("photon is remote");
+        };
+
+        remote.reverse(64.146, -21.942).unwrap_err();
+        assert!(remote.failed.load(Ordering::Relaxed));
+        let err = remote.reverse(64.146, -21.942).unwrap_err();
+
+        assert!(
+            matches!(err, Error::Geocode { ref message, .. } if message == "skipped after an earlier failure")
+        );
+    }
+}
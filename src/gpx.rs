@@ -0,0 +1,250 @@
+//! Geotagging from GPX tracks.
+//!
+//! Cameras without GPS can be geotagged by recording a track with a phone
+//! or GPS logger and matching each photo's capture time against it. Track
+//! times are UTC while EXIF times are the camera's local clock, so the
+//! configured offset is applied before matching. Positions between two
+//! track points are interpolated.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use quick_xml::events::Event;
+use quick_xml::reader::Reader;
+
+use crate::config::GpxConfig;
+use crate::util;
+
+/// A point on a recorded track.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrackPoint {
+    /// Seconds since the Unix epoch (UTC)
+    pub time: i64,
+    pub latitude: f64,
+    pub longitude: f64,
+    /// Elevation in meters
+    pub elevation: Option<f64>,
+}
+
+/// All track points from a site's GPX files, sorted by time.
+#[derive(Debug, Default)]
+pub struct Tracks {
+    points: Vec<TrackPoint>,
+    /// Camera clock offset from UTC in seconds
+    offset: i64,
+    /// Largest gap in seconds to interpolate across or extrapolate to
+    max_gap: i64,
+}
+
+impl Tracks {
+    /// Load every `.gpx` file in the configured directory.
+    ///
+    /// Unreadable files are reported and skipped.
+    pub fn load(site_dir: &Path, config: &GpxConfig) -> Self {
+        let mut points = Vec::new();
+        let mut loaded = 0;
+
+        for path in files(site_dir, config) {
+            match fs::read(&path).map(|data| parse(&data)) {
+                Ok(Some(track)) => {
+                    loaded += 1;
+                    points.extend(track);
+                }
+                Ok(None) => tracing::warn!(path = %path.display(), "ignoring invalid GPX file"),
+                Err(e) => {
+                    tracing::warn!(path = %path.display(), error = %e, "failed to read GPX file")
+                }
+            }
+        }
+
+        if loaded > 0 {
+            tracing::info!(files = loaded, points = points.len(), "loaded GPX tracks");
+        }
+
+        Self::new(points, config.time_offset, config.max_gap)
+    }
+
+    fn new(mut points: Vec<TrackPoint>, offset: i64, max_gap: u64) -> Self {
+        points.sort_by_key(|p| p.time);
+        Self {
+            points,
+            offset,
+            max_gap: max_gap as i64,
+        }
+    }
+
+    /// Position at an EXIF capture time (`2024:04:02 06:12:45`, camera clock).
+    pub fn locate(&self, date_taken: &str) -> Option<TrackPoint> {
+        let time = util::parse_exif_time(date_taken)? - self.offset;
+        self.at(time)
+    }
+
+    /// Position at a UTC time, interpolated between surrounding points.
+    fn at(&self, time: i64) -> Option<TrackPoint> {
+        let index = self.points.partition_point(|p| p.time < time);
+        let after = self.points.get(index);
+        let before = index.checked_sub(1).and_then(|i| self.points.get(i));
+
+        match (before, after) {
+            (_, Some(b)) if b.time == time => Some(*b),
+            (Some(a), Some(b)) if b.time - a.time <= self.max_gap => {
+                let t = (time - a.time) as f64 / (b.time - a.time) as f64;
+                let lerp = |x: f64, y: f64| x + (y - x) * t;
+                Some(TrackPoint {
+                    time,
+                    latitude: lerp(a.latitude, b.latitude),
+                    longitude: lerp(a.longitude, b.longitude),
+                    elevation: a.elevation.zip(b.elevation).map(|(x, y)| lerp(x, y)),
+                })
+            }
+            // Too far apart to interpolate: use the nearest point if it's close enough
+            (a, b) => [a, b]
+                .into_iter()
+                .flatten()
+                .filter(|p| (p.time - time).abs() <= self.max_gap)
+                .min_by_key(|p| (p.time - time).abs())
+                .copied(),
+        }
+    }
+}
+
+/// List the `.gpx` files in the configured tracks directory.
+pub fn files(site_dir: &Path, config: &GpxConfig) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(site_dir.join(&config.tracks)) else {
+        return Vec::new();
+    };
+
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| {
+            p.is_file()
+                && p.extension()
+                    .is_some_and(|ext| ext.eq_ignore_ascii_case("gpx"))
+        })
+        .collect();
+    files.sort();
+    files
+}
+
+/// Parse the timestamped track points of a GPX document.
+pub fn parse(xml: &[u8]) -> Option<Vec<TrackPoint>> {
+    let mut reader = Reader::from_reader(xml);
+    reader.config_mut().trim_text(true);
+
+    let mut points = Vec::new();
+    // (latitude, longitude, time, elevation) of the open <trkpt>
+    let mut current: Option<(f64, f64, Option<i64>, Option<f64>)> = None;
+    let mut field: Option<Vec<u8>> = None;
+    let mut buf = Vec::new();
+
+    loop {
+        match reader.read_event_into(&mut buf).ok()? {
+            Event::Start(e) if e.local_name().as_ref() == b"trkpt" => {
+                let attr = |name: &[u8]| {
+                    e.attributes()
+                        .flatten()
+                        .find(|a| a.key.local_name().as_ref() == name)
+                        .and_then(|a| a.unescape_value().ok()?.trim().parse::<f64>().ok())
+                };
+                current = attr(b"lat")
+                    .zip(attr(b"lon"))
+                    .map(|(lat, lon)| (lat, lon, None, None));
+            }
+            Event::Start(e) if current.is_some() => {
+                field = Some(e.local_name().as_ref().to_vec());
+            }
+            Event::Text(t) => {
+                if let (Some((_, _, time, elevation)), Some(name)) = (current.as_mut(), &field) {
+                    let text = t.unescape().ok()?;
+                    match name.as_slice() {
+                        b"time" => *time = util::parse_iso_time(&text),
+                        b"ele" => *elevation = text.trim().parse().ok(),
+                        _ => {}
+                    }
+                }
+            }
+            Event::End(e) if e.local_name().as_ref() == b"trkpt" => {
+                if let Some((latitude, longitude, Some(time), elevation)) = current.take() {
+                    points.push(TrackPoint {
+                        time,
+                        latitude,
+                        longitude,
+                        elevation,
+                    });
+                }
+            }
+            Event::End(_) => field = None,
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    Some(points)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    const GPX: &str = r#"<?xml version="1.0"?>
+<gpx version="1.1" xmlns="http://www.topografix.com/GPX/1/1">
+  <trk><trkseg>
+    <trkpt lat="64.0000" lon="-21.0000"><ele>100</ele><time>2024-04-02T12:00:00Z</time></trkpt>
+    <trkpt lat="64.0100" lon="-21.0200"><ele>200</ele><time>2024-04-02T12:01:40Z</time></trkpt>
+    <trkpt lat="65.0000" lon="-18.0000"><time>2024-04-02T15:00:00Z</time></trkpt>
+    <trkpt lat="1" lon="1"></trkpt>
+  </trkseg></trk>
+</gpx>"#;
+
+    fn tracks(offset: i64) -> Tracks {
+        Tracks::new(parse(GPX.as_bytes()).unwrap(), offset, 300)
+    }
+
+    #[test]
+    fn parses_timestamped_points() {
+        let points = parse(GPX.as_bytes()).unwrap();
+
+        assert_eq!(points.len(), 3);
+        assert_eq!(points[0].elevation, Some(100.0));
+        assert_eq!(points[2].elevation, None);
+        assert_eq!(points[1].time - points[0].time, 100);
+    }
+
+    #[test]
+    fn interpolates_between_points() {
+        let point = tracks(0).locate("2024:04:02 12:00:50").unwrap();
+
+        assert!((point.latitude - 64.005).abs() < 1e-9);
+        assert!((point.longitude - -21.01).abs() < 1e-9);
+        assert_eq!(point.elevation, Some(150.0));
+    }
+
+    #[test]
+    fn applies_camera_offset() {
+        // Camera clock set to UTC+2
+        let point = tracks(2 * 3600).locate("2024:04:02 14:01:40").unwrap();
+
+        assert_eq!((point.latitude, point.longitude), (64.01, -21.02));
+    }
+
+    #[test_case("2024:04:02 12:03:00", Some(64.01) ; "near last point of a segment")]
+    #[test_case("2024:04:02 13:30:00", None ; "inside a long gap")]
+    #[test_case("2024:04:02 11:00:00", None ; "before the track")]
+    #[test_case("not a date", None ; "invalid date")]
+    fn test_locate_gaps(date: &str, latitude: Option<f64>) {
+        assert_eq!(tracks(0).locate(date).map(|p| p.latitude), latitude);
+    }
+
+    #[test]
+    fn loads_gpx_files_from_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("morning.gpx"), GPX).unwrap();
+        fs::write(dir.path().join("notes.txt"), "not a track").unwrap();
+
+        let tracks = Tracks::load(dir.path(), &GpxConfig::default());
+
+        assert_eq!(tracks.points.len(), 3);
+    }
+}
@@ -0,0 +1,88 @@
+//! HEIC/HEIF decoding.
+//!
+//! iPhones save photos as HEIC, which the image crate can't decode. Builds
+//! with the `heif` feature decode them with the system libheif; without it,
+//! HEIC photos are skipped with a warning saying how to enable them. libheif
+//! applies the file's rotation and mirroring itself, so dimensions and
+//! decoded images are already upright.
+
+use image::DynamicImage;
+
+use crate::error::Result;
+
+#[cfg(feature = "heif")]
+mod libheif {
+    use image::{DynamicImage, RgbImage};
+    use libheif_rs::{ColorSpace, HeifContext, HeifError, LibHeif, RgbChroma};
+
+    use crate::error::{Error, Result};
+
+    fn error(e: HeifError) -> Error {
+        Error::Other(format!("failed to decode HEIF: {}", e))
+    }
+
+    pub fn dimensions(data: &[u8]) -> Result<(u32, u32)> {
+        let context = HeifContext::read_from_bytes(data).map_err(error)?;
+        let handle = context.primary_image_handle().map_err(error)?;
+        Ok((handle.width(), handle.height()))
+    }
+
+    pub fn decode(data: &[u8]) -> Result<DynamicImage> {
+        let context = HeifContext::read_from_bytes(data).map_err(error)?;
+        let handle = context.primary_image_handle().map_err(error)?;
+        let image = LibHeif::new()
+            .decode(&handle, ColorSpace::Rgb(RgbChroma::Rgb), None)
+            .map_err(error)?;
+
+        let planes = image.planes();
+        let plane = planes
+            .interleaved
+            .ok_or_else(|| Error::Other("decoded HEIF has no RGB plane".to_string()))?;
+
+        // Rows may be padded; copy out the pixels
+        let row = plane.width as usize * 3;
+        let mut pixels = Vec::with_capacity(row * plane.height as usize);
+        for y in 0..plane.height as usize {
+            let start = y * plane.stride;
+            pixels.extend_from_slice(&plane.data[start..start + row]);
+        }
+        let img = RgbImage::from_raw(plane.width, plane.height, pixels)
+            .map(DynamicImage::ImageRgb8)
+            .ok_or_else(|| Error::Other("decoded HEIF has the wrong size".to_string()))?;
+        Ok(match handle.color_profile_raw() {
+            Some(profile) => crate::color::to_srgb(img, &profile.data),
+            None => img,
+        })
+    }
+}
+
+/// Upright width and height of a HEIF image, without decoding it.
+pub fn dimensions(data: &[u8]) -> Result<(u32, u32)> {
+    #[cfg(feature = "heif")]
+    return libheif::dimensions(data);
+
+    #[cfg(not(feature = "heif"))]
+    {
+        let _ = data;
+        Err(unsupported())
+    }
+}
+
+/// Decode a HEIF image, upright and in sRGB.
+pub fn decode(data: &[u8]) -> Result<DynamicImage> {
+    #[cfg(feature = "heif")]
+    return libheif::decode(data);
+
+    #[cfg(not(feature = "heif"))]
+    {
+        let _ = data;
+        Err(unsupported())
+    }
+}
+
+#[cfg(not(feature = "heif"))]
+fn unsupported() -> crate::error::Error {
+    crate::error::Error::Other(
+        "HEIC/HEIF photos need galerie built with the `heif` feature".to_string(),
+    )
+}
@@ -1,4 +1,7 @@
 use std::collections::BTreeMap;
+use std::sync::LazyLock;
+
+use crate::countries::{Country, Registry};
 
 pub type Translations = BTreeMap<String, String>;
 pub type AllTranslations = BTreeMap<String, Translations>;
@@ -97,7 +100,7 @@ pub fn all_supported_languages() -> Vec<LanguageInfo> {
 }
 
 /// Get translations for all supported languages.
-pub fn get_all_translations() -> AllTranslations {
+pub fn get_all_translations(countries: &Registry) -> AllTranslations {
     let mut all = BTreeMap::new();
     all.insert("en".to_string(), translations_en());
     all.insert("zh_CN".to_string(), translations_zh_cn());
@@ -119,9 +122,48 @@ pub fn get_all_translations() -> AllTranslations {
     all.insert("da".to_string(), translations_da());
     all.insert("hu".to_string(), translations_hu());
     all.insert("pt".to_string(), translations_pt());
+
+    for (lang, translations) in &mut all {
+        add_country_names(lang, translations, countries);
+    }
     all
 }
 
+/// Add a `country.XX` entry for every known country.
+///
+/// English uses the registry's names so it matches the gallery data,
+/// including any the site renamed. Other languages use hand-written
+/// translations above, falling back to the CLDR names in
+/// `data/country_names.toml`.
+fn add_country_names(lang: &str, translations: &mut Translations, countries: &Registry) {
+    let key = |country: &Country| format!("country.{}", country.code);
+
+    if lang == "en" {
+        for country in countries.iter() {
+            translations.insert(key(country), country.name.clone());
+        }
+        return;
+    }
+
+    let Some(names) = CLDR_COUNTRY_NAMES.get(lang) else {
+        return;
+    };
+    for country in countries.iter() {
+        if let Some(name) = names.get(&country.code) {
+            translations
+                .entry(key(country))
+                .or_insert_with(|| name.clone());
+        }
+    }
+}
+
+/// Country names by language, then ISO 3166-1 alpha-2 code.
+static CLDR_COUNTRY_NAMES: LazyLock<BTreeMap<String, BTreeMap<String, String>>> =
+    LazyLock::new(|| {
+        toml::from_str(include_str!("data/country_names.toml"))
+            .expect("built-in country names are valid")
+    });
+
 fn translations_en() -> Translations {
     [
         // Navigation
@@ -132,6 +174,8 @@ fn translations_en() -> Translations {
         ("nav.all_photos", "All Photos"),
         // Sections
         ("section.albums", "Albums"),
+        ("section.archive", "Archive"),
+        ("section.undated", "Undated"),
         ("section.photo", "Photo"),
         ("section.date", "Date"),
         ("section.camera", "Camera"),
@@ -241,6 +285,8 @@ fn translations_zh_cn() -> Translations {
         ("nav.all_photos", "所有照片"),
         // Sections
         ("section.albums", "相册"),
+        ("section.archive", "归档"),
+        ("section.undated", "无日期"),
         ("section.photo", "照片"),
         ("section.date", "日期"),
         ("section.camera", "相机"),
@@ -350,6 +396,8 @@ fn translations_es() -> Translations {
         ("nav.all_photos", "Todas las fotos"),
         // Sections
         ("section.albums", "Álbumes"),
+        ("section.archive", "Archivo"),
+        ("section.undated", "Sin fecha"),
         ("section.photo", "Foto"),
         ("section.date", "Fecha"),
         ("section.camera", "Cámara"),
@@ -459,6 +507,8 @@ fn translations_fr() -> Translations {
         ("nav.all_photos", "Toutes les photos"),
         // Sections
         ("section.albums", "Albums"),
+        ("section.archive", "Archives"),
+        ("section.undated", "Sans date"),
         ("section.photo", "Photo"),
         ("section.date", "Date"),
         ("section.camera", "Appareil"),
@@ -568,6 +618,8 @@ fn translations_nl() -> Translations {
         ("nav.all_photos", "Alle foto's"),
         // Sections
         ("section.albums", "Albums"),
+        ("section.archive", "Archief"),
+        ("section.undated", "Zonder datum"),
         ("section.photo", "Foto"),
         ("section.date", "Datum"),
         ("section.camera", "Camera"),
@@ -677,6 +729,8 @@ fn translations_de() -> Translations {
         ("nav.all_photos", "Alle Fotos"),
         // Sections
         ("section.albums", "Alben"),
+        ("section.archive", "Archiv"),
+        ("section.undated", "Ohne Datum"),
         ("section.photo", "Foto"),
         ("section.date", "Datum"),
         ("section.camera", "Kamera"),
@@ -786,6 +840,8 @@ fn translations_uk() -> Translations {
         ("nav.all_photos", "Усі фото"),
         // Sections
         ("section.albums", "Альбоми"),
+        ("section.archive", "Архів"),
+        ("section.undated", "Без дати"),
         ("section.photo", "Фото"),
         ("section.date", "Дата"),
         ("section.camera", "Камера"),
@@ -895,6 +951,8 @@ fn translations_ru() -> Translations {
         ("nav.all_photos", "Все фото"),
         // Sections
         ("section.albums", "Альбомы"),
+        ("section.archive", "Архив"),
+        ("section.undated", "Без даты"),
         ("section.photo", "Фото"),
         ("section.date", "Дата"),
         ("section.camera", "Камера"),
@@ -1004,6 +1062,8 @@ fn translations_ja() -> Translations {
         ("nav.all_photos", "すべての写真"),
         // Sections
         ("section.albums", "アルバム"),
+        ("section.archive", "アーカイブ"),
+        ("section.undated", "日付なし"),
         ("section.photo", "写真"),
         ("section.date", "日付"),
         ("section.camera", "カメラ"),
@@ -1113,6 +1173,8 @@ fn translations_ar() -> Translations {
         ("nav.all_photos", "كل الصور"),
         // Sections
         ("section.albums", "الألبومات"),
+        ("section.archive", "الأرشيف"),
+        ("section.undated", "بدون تاريخ"),
         ("section.photo", "صورة"),
         ("section.date", "التاريخ"),
         ("section.camera", "الكاميرا"),
@@ -1222,6 +1284,8 @@ fn translations_hi() -> Translations {
         ("nav.all_photos", "सभी फ़ोटो"),
         // Sections
         ("section.albums", "एल्बम"),
+        ("section.archive", "संग्रह"),
+        ("section.undated", "बिना तारीख"),
         ("section.photo", "फ़ोटो"),
         ("section.date", "तारीख"),
         ("section.camera", "कैमरा"),
@@ -1331,6 +1395,8 @@ fn translations_he() -> Translations {
         ("nav.all_photos", "כל התמונות"),
         // Sections
         ("section.albums", "אלבומים"),
+        ("section.archive", "ארכיון"),
+        ("section.undated", "ללא תאריך"),
         ("section.photo", "תמונה"),
         ("section.date", "תאריך"),
         ("section.camera", "מצלמה"),
@@ -1440,6 +1506,8 @@ fn translations_it() -> Translations {
         ("nav.all_photos", "Tutte le foto"),
         // Sections
         ("section.albums", "Album"),
+        ("section.archive", "Archivio"),
+        ("section.undated", "Senza data"),
         ("section.photo", "Foto"),
         ("section.date", "Data"),
         ("section.camera", "Fotocamera"),
@@ -1549,6 +1617,8 @@ fn translations_ko() -> Translations {
         ("nav.all_photos", "모든 사진"),
         // Sections
         ("section.albums", "앨범"),
+        ("section.archive", "보관함"),
+        ("section.undated", "날짜 없음"),
         ("section.photo", "사진"),
         ("section.date", "날짜"),
         ("section.camera", "카메라"),
@@ -1658,6 +1728,8 @@ fn translations_pl() -> Translations {
         ("nav.all_photos", "Wszystkie zdjęcia"),
         // Sections
         ("section.albums", "Albumy"),
+        ("section.archive", "Archiwum"),
+        ("section.undated", "Bez daty"),
         ("section.photo", "Zdjęcie"),
         ("section.date", "Data"),
         ("section.camera", "Aparat"),
@@ -1767,6 +1839,8 @@ fn translations_cs() -> Translations {
         ("nav.all_photos", "Všechny fotky"),
         // Sections
         ("section.albums", "Alba"),
+        ("section.archive", "Archiv"),
+        ("section.undated", "Bez data"),
         ("section.photo", "Fotografie"),
         ("section.date", "Datum"),
         ("section.camera", "Fotoaparát"),
@@ -1876,6 +1950,8 @@ fn translations_fi() -> Translations {
         ("nav.all_photos", "Kaikki kuvat"),
         // Sections
         ("section.albums", "Albumit"),
+        ("section.archive", "Arkisto"),
+        ("section.undated", "Ei päivämäärää"),
         ("section.photo", "Valokuva"),
         ("section.date", "Päivämäärä"),
         ("section.camera", "Kamera"),
@@ -1985,6 +2061,8 @@ fn translations_da() -> Translations {
         ("nav.all_photos", "Alle billeder"),
         // Sections
         ("section.albums", "Album"),
+        ("section.archive", "Arkiv"),
+        ("section.undated", "Uden dato"),
         ("section.photo", "Foto"),
         ("section.date", "Dato"),
         ("section.camera", "Kamera"),
@@ -2094,6 +2172,8 @@ fn translations_hu() -> Translations {
         ("nav.all_photos", "Összes fotó"),
         // Sections
         ("section.albums", "Albumok"),
+        ("section.archive", "Archívum"),
+        ("section.undated", "Dátum nélkül"),
         ("section.photo", "Fotó"),
         ("section.date", "Dátum"),
         ("section.camera", "Fényképezőgép"),
@@ -2203,6 +2283,8 @@ fn translations_pt() -> Translations {
         ("nav.all_photos", "Todas as fotos"),
         // Sections
         ("section.albums", "Álbuns"),
+        ("section.archive", "Arquivo"),
+        ("section.undated", "Sem data"),
         ("section.photo", "Foto"),
         ("section.date", "Data"),
         ("section.camera", "Câmera"),
@@ -2301,3 +2383,29 @@ fn translations_pt() -> Translations {
     .map(|(k, v)| (k.to_string(), v.to_string()))
     .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case("de", "country.IS", "Island" ; "cldr name")]
+    #[test_case("zh_CN", "country.JP", "日本" ; "hand-written name")]
+    #[test_case("ja", "country.NP", "ネパール" ; "uncommon country")]
+    #[test_case("en", "country.CD", "DR Congo" ; "english matches gallery data")]
+    fn test_country_names(lang: &str, key: &str, expected: &str) {
+        let all = get_all_translations(Registry::builtin());
+
+        assert_eq!(all[lang].get(key).map(String::as_str), Some(expected));
+    }
+
+    #[test]
+    fn every_language_has_every_country() {
+        let all = get_all_translations(Registry::builtin());
+        let count = |t: &Translations| t.keys().filter(|k| k.starts_with("country.")).count();
+
+        for translations in all.values() {
+            assert_eq!(count(translations), count(&all["en"]));
+        }
+    }
+}
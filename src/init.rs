@@ -0,0 +1,215 @@
+//! Scaffolding for new sites.
+//!
+//! `galerie init` writes a `site.toml` with the common options explained in
+//! comments, a `photos/` directory with an example album, and a
+//! `.gitignore` for the build output and caches, so `galerie serve` works
+//! right away. Existing files are left alone unless forced, and photos
+//! never are.
+
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+
+use image::{DynamicImage, ImageFormat, Rgb, RgbImage};
+
+use crate::config::Site;
+use crate::error::{Error, Result};
+
+/// Album created to show how directories become albums.
+const EXAMPLE_ALBUM: &str = "example";
+
+/// Example photo in that album, and its sidecar.
+const EXAMPLE_PHOTO: &str = "sunrise.jpg";
+const EXAMPLE_SIDECAR: &str = "sunrise.toml";
+
+const EXAMPLE_SIDECAR_CONTENT: &str = "\
+# A sidecar sets what a photo's file doesn't say, such as where a scan or a
+# photo from a camera without GPS was taken. Delete it with the example album.
+location = \"Reykjavík\"
+";
+
+const GITIGNORE: &str = "\
+# Build output and caches, regenerated by galerie
+/dist/
+.galerie-cache.json
+.galerie-sizes.json
+";
+
+/// What to fill `site.toml` in with.
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub domain: String,
+    pub title: Option<String>,
+    pub theme: String,
+    /// Replace an existing `site.toml`
+    pub force: bool,
+}
+
+/// Create a site in `site_dir`.
+pub fn run(site_dir: &Path, options: &Options) -> Result<()> {
+    let config_path = site_dir.join("site.toml");
+    if config_path.exists() && !options.force {
+        return Err(Error::Other(format!(
+            "{} already exists; pass --force to replace it",
+            config_path.display()
+        )));
+    }
+
+    fs::create_dir_all(site_dir)?;
+    let config = site_toml(options);
+    // Written only once it's known to load
+    toml::from_str::<Site>(&config)?;
+    fs::write(&config_path, config)?;
+    tracing::info!(path = %config_path.display(), "wrote site config");
+
+    let photos_dir = site_dir.join("photos");
+    if photos_dir.exists() {
+        tracing::info!(path = %photos_dir.display(), "keeping existing photos directory");
+    } else {
+        let album_dir = photos_dir.join(EXAMPLE_ALBUM);
+        fs::create_dir_all(&album_dir)?;
+        fs::write(album_dir.join(EXAMPLE_PHOTO), example_photo()?)?;
+        fs::write(album_dir.join(EXAMPLE_SIDECAR), EXAMPLE_SIDECAR_CONTENT)?;
+        tracing::info!(path = %album_dir.display(), "created example album");
+    }
+
+    let gitignore = site_dir.join(".gitignore");
+    if !gitignore.exists() {
+        fs::write(&gitignore, GITIGNORE)?;
+    }
+
+    println!(
+        "\n  Created a site in {}\n  Add photos to {}, then run: galerie -C {} serve\n",
+        site_dir.display(),
+        photos_dir.display(),
+        site_dir.display()
+    );
+    Ok(())
+}
+
+/// `site.toml` for `options`, with the common settings explained.
+fn site_toml(options: &Options) -> String {
+    let quoted = |value: &str| toml::Value::String(value.to_string()).to_string();
+    let title = match &options.title {
+        Some(title) => format!("title = {}", quoted(title)),
+        None => "# title = \"My Photos\"".to_string(),
+    };
+    format!(
+        r#"# galerie site configuration. Every option is described in
+# https://github.com/Twister915/galerie/blob/main/wiki/site-config.md
+
+# Where the site will be hosted, for absolute URLs in feeds and link previews
+domain = {domain}
+
+# Shown in page titles and the header (defaults to the domain)
+{title}
+
+# A built-in theme ("basic" or "fancy"), or the path to a theme directory
+theme = {theme}
+
+# Photos to publish; each subdirectory becomes an album
+photos = "photos"
+
+# Where the site is written; this directory is what gets uploaded
+build = "dist"
+
+# Location shown for photos: "on" (coordinates and map), "general" (city and
+# country only), or "off". GPS is removed from downloads unless "on".
+gps = "general"
+
+# Offer all 20 supported languages; or remove this and list some instead:
+# [[languages]]
+# code = "en"
+all_languages = true
+
+# Sizes and WebP qualities of generated images (defaults shown)
+# [processing]
+# thumb = {{ size = 600, quality = 80 }}
+# full = {{ size = 2400, quality = 90 }}
+
+# Where `galerie publish` uploads the build: a command, or a git branch or
+# S3 bucket, which `galerie deploy --rollback` can undo
+# [deploy]
+# command = "rsync -a --delete dist/ me@example.com:/srv/photos/"
+# git = {{ repo = "git@github.com:me/photos.git", branch = "gh-pages" }}
+# s3 = {{ bucket = "photos.example.com" }}
+"#,
+        domain = quoted(&options.domain),
+        title = title,
+        theme = quoted(&options.theme),
+    )
+}
+
+/// A gradient standing in for a photo.
+fn example_photo() -> Result<Vec<u8>> {
+    let (width, height) = (1200, 800);
+    let img = RgbImage::from_fn(width, height, |x, y| {
+        let sky = y * 255 / height;
+        Rgb([
+            (255 - sky / 3) as u8,
+            (120 + x * 80 / width + sky / 3) as u8,
+            (60 + sky * 2 / 3) as u8,
+        ])
+    });
+    let mut data = Vec::new();
+    DynamicImage::ImageRgb8(img).write_to(&mut Cursor::new(&mut data), ImageFormat::Jpeg)?;
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::GpsMode;
+
+    fn options() -> Options {
+        Options {
+            domain: "photos.example.com".to_string(),
+            title: Some("Trips \"abroad\"".to_string()),
+            theme: "basic".to_string(),
+            force: false,
+        }
+    }
+
+    #[test]
+    fn creates_a_site_that_loads() {
+        let dir = tempfile::tempdir().unwrap();
+
+        run(dir.path(), &options()).unwrap();
+
+        let site = Site::load(&dir.path().join("site.toml")).unwrap();
+        assert_eq!(site.domain, "photos.example.com");
+        assert_eq!(site.title.as_deref(), Some("Trips \"abroad\""));
+        assert_eq!(site.theme.name(), "basic");
+        assert_eq!(site.gps, GpsMode::General);
+        let album = dir.path().join("photos").join(EXAMPLE_ALBUM);
+        assert!(image::open(album.join(EXAMPLE_PHOTO)).is_ok());
+        let sidecar = crate::sidecar::read(&album.join(EXAMPLE_PHOTO)).unwrap();
+        assert!(sidecar.location.is_some());
+    }
+
+    #[test]
+    fn existing_config_kept_unless_forced() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("site.toml"), "domain = \"mine\"").unwrap();
+        fs::create_dir(dir.path().join("photos")).unwrap();
+
+        assert!(run(dir.path(), &options()).is_err());
+        assert_eq!(
+            fs::read_to_string(dir.path().join("site.toml")).unwrap(),
+            "domain = \"mine\""
+        );
+
+        let forced = Options {
+            force: true,
+            ..options()
+        };
+        run(dir.path(), &forced).unwrap();
+        assert!(
+            fs::read_to_string(dir.path().join("site.toml"))
+                .unwrap()
+                .contains("photos.example.com")
+        );
+        // Existing photos aren't touched
+        assert_eq!(fs::read_dir(dir.path().join("photos")).unwrap().count(), 0);
+    }
+}
@@ -0,0 +1,55 @@
+//! Insert generated markup into rendered pages.
+//!
+//! Integrations configured in `site.toml` (comment threads, for example) need
+//! markup on pages whose templates know nothing about them. Rather than
+//! requiring a theme fork, the markup is inserted next to a well-known closing
+//! tag after rendering. Themes that want control over placement output the
+//! markup themselves, and a page that already contains it is left alone.
+
+/// Where to insert markup in a page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Position {
+    /// End of `<head>`
+    Head,
+    /// End of `<main>`, or of `<body>` if there is no `<main>`
+    Content,
+}
+
+/// Insert `snippet` into `page` at `position`.
+///
+/// Pages without the tags `position` needs are returned unchanged.
+pub fn inject(page: &str, snippet: &str, position: Position) -> String {
+    let closing_tags: &[&str] = match position {
+        Position::Head => &["</head>"],
+        Position::Content => &["</main>", "</body>"],
+    };
+
+    // ASCII lowercasing keeps byte offsets, so they index into `page` directly
+    let lower = page.to_ascii_lowercase();
+    let Some(offset) = closing_tags.iter().find_map(|tag| lower.rfind(tag)) else {
+        tracing::debug!(?position, "no insertion point in page");
+        return page.to_string();
+    };
+
+    let mut result = String::with_capacity(page.len() + snippet.len() + 1);
+    result.push_str(&page[..offset]);
+    result.push_str(snippet);
+    result.push('\n');
+    result.push_str(&page[offset..]);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case("<head></head><body><main>x</main></body>", Position::Content, "<head></head><body><main>x<p>\n</main></body>" ; "main")]
+    #[test_case("<head></head><body>x</body>", Position::Content, "<head></head><body>x<p>\n</body>" ; "body without main")]
+    #[test_case("<HEAD></HEAD><BODY>x</BODY>", Position::Content, "<HEAD></HEAD><BODY>x<p>\n</BODY>" ; "uppercase body")]
+    #[test_case("<head><title>x</title></head>", Position::Head, "<head><title>x</title><p>\n</head>" ; "head")]
+    #[test_case("<p>fragment</p>", Position::Content, "<p>fragment</p>" ; "no insertion point")]
+    fn test_inject(page: &str, position: Position, expected: &str) {
+        assert_eq!(inject(page, "<p>", position), expected);
+    }
+}
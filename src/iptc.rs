@@ -0,0 +1,339 @@
+//! IPTC IIM metadata from JPEG files.
+//!
+//! Agency and older workflows record captions, bylines, keywords, and
+//! location names as IPTC IIM datasets inside a Photoshop APP13 segment
+//! rather than in XMP. Only the fields galerie uses are decoded.
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::GpsMode;
+use crate::photos::Location;
+
+/// Photoshop image resource ID holding IPTC IIM data.
+const RESOURCE_IPTC: u16 = 0x0404;
+
+/// Metadata read from IPTC IIM datasets.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct IptcData {
+    /// 2:05 Object Name
+    pub title: Option<String>,
+    /// 2:120 Caption/Abstract
+    pub caption: Option<String>,
+    /// 2:80 By-line
+    pub creator: Option<String>,
+    /// 2:25 Keywords
+    pub keywords: Vec<String>,
+    /// 2:116 Copyright Notice
+    pub copyright: Option<String>,
+    /// 2:55 Date Created and 2:60 Time Created, in EXIF format
+    pub date_created: Option<String>,
+    /// 2:92 Sub-location
+    pub sublocation: Option<String>,
+    /// 2:90 City
+    pub city: Option<String>,
+    /// 2:95 Province/State
+    pub region: Option<String>,
+    /// 2:101 Country/Primary Location Name
+    pub country: Option<String>,
+    /// 2:100 Country/Primary Location Code
+    pub country_code: Option<String>,
+}
+
+impl IptcData {
+    /// Location named in the metadata, filtered by the GPS privacy mode.
+    ///
+    /// `off` hides it entirely; `general` drops the sub-location.
+    pub fn location(&self, gps_mode: GpsMode) -> Option<Location> {
+        let location = Location {
+            sublocation: match gps_mode {
+                GpsMode::On => self.sublocation.clone(),
+                _ => None,
+            },
+            city: self.city.clone(),
+            region: self.region.clone(),
+            country: self.country.clone(),
+            country_code: self.country_code.clone(),
+        };
+
+        let empty = location.sublocation.is_none()
+            && location.city.is_none()
+            && location.region.is_none()
+            && location.country.is_none()
+            && location.country_code.is_none();
+
+        if gps_mode == GpsMode::Off || empty {
+            None
+        } else {
+            Some(location)
+        }
+    }
+}
+
+/// Read IPTC data from a JPEG.
+pub fn read(data: &[u8]) -> Option<IptcData> {
+    let resources = photoshop_segment(data)?;
+    let iim = iptc_resource(resources)?;
+    Some(parse_iim(iim))
+}
+
+/// Find the payload of the APP13 "Photoshop 3.0" segment.
+fn photoshop_segment(data: &[u8]) -> Option<&[u8]> {
+    const SIGNATURE: &[u8] = b"Photoshop 3.0\0";
+
+    if !data.starts_with(&[0xFF, 0xD8]) {
+        return None;
+    }
+
+    let mut pos = 2;
+    while pos + 4 <= data.len() {
+        if data[pos] != 0xFF {
+            return None;
+        }
+        let marker = data[pos + 1];
+        // Start of scan: no more metadata segments
+        if marker == 0xDA {
+            return None;
+        }
+        let len = u16::from_be_bytes([data[pos + 2], data[pos + 3]]) as usize;
+        let segment = data.get(pos + 4..pos + 2 + len)?;
+
+        if marker == 0xED && segment.starts_with(SIGNATURE) {
+            return Some(&segment[SIGNATURE.len()..]);
+        }
+        pos += 2 + len;
+    }
+
+    None
+}
+
+/// Find the IPTC resource among Photoshop "8BIM" image resources.
+fn iptc_resource(mut data: &[u8]) -> Option<&[u8]> {
+    while data.len() >= 12 && data.starts_with(b"8BIM") {
+        let id = u16::from_be_bytes([data[4], data[5]]);
+
+        // Pascal string name, padded so length byte + name is even
+        let name_len = data[6] as usize;
+        let name_total = (1 + name_len).next_multiple_of(2);
+        let size_at = 6 + name_total;
+        let size = u32::from_be_bytes(data.get(size_at..size_at + 4)?.try_into().ok()?) as usize;
+        let start = size_at + 4;
+        let body = data.get(start..start + size)?;
+
+        if id == RESOURCE_IPTC {
+            return Some(body);
+        }
+        data = data.get(start + size.next_multiple_of(2)..)?;
+    }
+
+    None
+}
+
+/// Decode record 2 datasets from an IIM block.
+fn parse_iim(data: &[u8]) -> IptcData {
+    let mut iptc = IptcData::default();
+    let mut utf8 = false;
+    let mut date = None;
+    let mut time = None;
+    let mut pos = 0;
+
+    while pos + 5 <= data.len() && data[pos] == 0x1C {
+        let record = data[pos + 1];
+        let dataset = data[pos + 2];
+        let len = u16::from_be_bytes([data[pos + 3], data[pos + 4]]) as usize;
+        pos += 5;
+
+        // Extended datasets store the real length in the next (len & 0x7FFF) bytes
+        let len = if len & 0x8000 != 0 {
+            let count = len & 0x7FFF;
+            if count > size_of::<usize>() {
+                break;
+            }
+            let Some(bytes) = data.get(pos..pos + count) else {
+                break;
+            };
+            pos += count;
+            bytes.iter().fold(0usize, |acc, &b| (acc << 8) | b as usize)
+        } else {
+            len
+        };
+
+        let Some(end) = pos.checked_add(len) else {
+            break;
+        };
+        let Some(value) = data.get(pos..end) else {
+            break;
+        };
+        pos = end;
+
+        match (record, dataset) {
+            // 1:90 Coded Character Set; ESC % G selects UTF-8
+            (1, 90) => utf8 = value == b"\x1b%G",
+            (2, _) => {
+                let text = decode(value, utf8);
+                if text.is_empty() {
+                    continue;
+                }
+                match dataset {
+                    5 => iptc.title = Some(text),
+                    25 => iptc.keywords.push(text),
+                    55 => date = Some(text),
+                    60 => time = Some(text),
+                    80 => iptc.creator = Some(text),
+                    90 => iptc.city = Some(text),
+                    92 => iptc.sublocation = Some(text),
+                    95 => iptc.region = Some(text),
+                    100 => iptc.country_code = Some(text),
+                    101 => iptc.country = Some(text),
+                    116 => iptc.copyright = Some(text),
+                    120 => iptc.caption = Some(text),
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    iptc.date_created = date.and_then(|d| to_exif_date(&d, time.as_deref()));
+    iptc
+}
+
+/// Decode a text value, falling back to Latin-1 when it isn't valid UTF-8.
+fn decode(value: &[u8], utf8: bool) -> String {
+    let text = match std::str::from_utf8(value) {
+        Ok(s) => s.to_string(),
+        Err(_) if utf8 => String::from_utf8_lossy(value).into_owned(),
+        Err(_) => value.iter().map(|&b| b as char).collect(),
+    };
+    text.trim_matches(|c: char| c.is_whitespace() || c == '\0')
+        .to_string()
+}
+
+/// Convert IPTC `CCYYMMDD` and `HHMMSS±HHMM` to EXIF format.
+fn to_exif_date(date: &str, time: Option<&str>) -> Option<String> {
+    if date.len() != 8 || !date.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let date = format!("{}:{}:{}", &date[..4], &date[4..6], &date[6..]);
+
+    match time.filter(|t| t.len() >= 6 && t[..6].bytes().all(|b| b.is_ascii_digit())) {
+        Some(t) => Some(format!("{} {}:{}:{}", date, &t[..2], &t[2..4], &t[4..6])),
+        None => Some(date),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    fn dataset(record: u8, number: u8, value: &[u8]) -> Vec<u8> {
+        let mut out = vec![0x1C, record, number];
+        out.extend_from_slice(&(value.len() as u16).to_be_bytes());
+        out.extend_from_slice(value);
+        out
+    }
+
+    /// Wrap IIM data in a minimal JPEG with a Photoshop APP13 segment.
+    fn jpeg_with_iim(iim: &[u8]) -> Vec<u8> {
+        let mut resource = b"8BIM".to_vec();
+        resource.extend_from_slice(&RESOURCE_IPTC.to_be_bytes());
+        resource.extend_from_slice(&[0, 0]); // empty name, padded
+        resource.extend_from_slice(&(iim.len() as u32).to_be_bytes());
+        resource.extend_from_slice(iim);
+        if iim.len() % 2 == 1 {
+            resource.push(0);
+        }
+
+        let mut payload = b"Photoshop 3.0\0".to_vec();
+        payload.extend_from_slice(&resource);
+
+        let mut jpeg = vec![0xFF, 0xD8];
+        // An unrelated APP1 segment first
+        jpeg.extend_from_slice(&[0xFF, 0xE1, 0x00, 0x04, 0xAA, 0xBB]);
+        jpeg.extend_from_slice(&[0xFF, 0xED]);
+        jpeg.extend_from_slice(&((payload.len() + 2) as u16).to_be_bytes());
+        jpeg.extend_from_slice(&payload);
+        jpeg.extend_from_slice(&[0xFF, 0xDA, 0x00, 0x02]);
+        jpeg
+    }
+
+    #[test]
+    fn reads_fields_from_jpeg() {
+        let iim = [
+            dataset(1, 90, b"\x1b%G"),
+            dataset(2, 5, b"Harbour at dusk"),
+            dataset(2, 25, b"boats"),
+            dataset(2, 25, b"sunset"),
+            dataset(2, 55, b"20230615"),
+            dataset(2, 60, b"193000+0200"),
+            dataset(2, 80, "Zoë Photographer".as_bytes()),
+            dataset(2, 90, b"Reykjavik"),
+            dataset(2, 100, b"ISL"),
+            dataset(2, 101, b"Iceland"),
+            dataset(2, 120, b"Boats in the old harbour"),
+        ]
+        .concat();
+
+        let iptc = read(&jpeg_with_iim(&iim)).unwrap();
+
+        assert_eq!(iptc.title.as_deref(), Some("Harbour at dusk"));
+        assert_eq!(iptc.keywords, vec!["boats", "sunset"]);
+        assert_eq!(iptc.creator.as_deref(), Some("Zoë Photographer"));
+        assert_eq!(iptc.city.as_deref(), Some("Reykjavik"));
+        assert_eq!(iptc.country.as_deref(), Some("Iceland"));
+        assert_eq!(iptc.caption.as_deref(), Some("Boats in the old harbour"));
+        assert_eq!(iptc.date_created.as_deref(), Some("2023:06:15 19:30:00"));
+    }
+
+    #[test]
+    fn latin1_fallback() {
+        let iptc = parse_iim(&dataset(2, 80, b"Zo\xeb"));
+        assert_eq!(iptc.creator.as_deref(), Some("Zoë"));
+    }
+
+    #[test_case(&[0x80, 0x09, 1, 0, 0, 0, 0, 0, 0, 0, 0] ; "longer than usize")]
+    #[test_case(&[0x80, 0x08, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF] ; "past usize max")]
+    fn oversized_extended_length(header: &[u8]) {
+        let mut iim = dataset(2, 5, b"Harbour at dusk");
+        iim.extend_from_slice(&[0x1C, 2, 120]);
+        iim.extend_from_slice(header);
+
+        let iptc = parse_iim(&iim);
+
+        assert_eq!(iptc.title.as_deref(), Some("Harbour at dusk"));
+        assert_eq!(iptc.caption, None);
+    }
+
+    #[test_case(b"not a jpeg" ; "not jpeg")]
+    #[test_case(&[0xFF, 0xD8, 0xFF, 0xDA, 0x00, 0x02] ; "no app13")]
+    #[test_case(&[0xFF, 0xD8, 0xFF, 0xED, 0x00, 0xFF] ; "truncated segment")]
+    fn test_read_without_iptc(data: &[u8]) {
+        assert_eq!(read(data), None);
+    }
+
+    #[test_case(GpsMode::On, true, true ; "on")]
+    #[test_case(GpsMode::General, true, false ; "general")]
+    #[test_case(GpsMode::Off, false, false ; "off")]
+    fn test_location_privacy(mode: GpsMode, has_location: bool, has_sublocation: bool) {
+        let iptc = IptcData {
+            sublocation: Some("Old Harbour".to_string()),
+            city: Some("Reykjavik".to_string()),
+            ..Default::default()
+        };
+
+        let location = iptc.location(mode);
+
+        assert_eq!(location.is_some(), has_location);
+        assert_eq!(
+            location.and_then(|l| l.sublocation).is_some(),
+            has_sublocation
+        );
+    }
+
+    #[test_case("20230615", Some("193000+0200"), Some("2023:06:15 19:30:00") ; "date and time")]
+    #[test_case("20230615", None, Some("2023:06:15") ; "date only")]
+    #[test_case("2023-06", None, None ; "malformed")]
+    fn test_to_exif_date(date: &str, time: Option<&str>, expected: Option<&str>) {
+        assert_eq!(to_exif_date(date, time).as_deref(), expected);
+    }
+}
@@ -0,0 +1,355 @@
+//! schema.org structured data for pages.
+//!
+//! Index and album pages describe themselves as an `ImageGallery` listing
+//! their photos; photo pages as a `Photograph` with its capture details.
+//! Search engines use this for image results. Coordinates are only included
+//! when the site publishes exact GPS.
+
+use serde::Serialize;
+
+use crate::config::GpsMode;
+use crate::photos::Photo;
+use crate::util::iso_datetime;
+
+const CONTEXT: &str = "https://schema.org";
+
+/// Most photos listed in a gallery's JSON-LD, keeping large indexes small.
+pub const MAX_GALLERY_MEDIA: usize = 100;
+
+/// A photo and its absolute URLs.
+#[derive(Debug)]
+pub struct Media<'a> {
+    pub photo: &'a Photo,
+    /// The most specific page showing the photo
+    pub page_url: String,
+    pub image_url: String,
+    pub thumbnail_url: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Gallery<'a> {
+    #[serde(rename = "@context")]
+    context: &'static str,
+    #[serde(rename = "@type")]
+    kind: &'static str,
+    name: &'a str,
+    url: &'a str,
+    associated_media: Vec<ImageObject<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Photograph<'a> {
+    #[serde(rename = "@context")]
+    context: &'static str,
+    #[serde(rename = "@type")]
+    kind: &'static str,
+    name: &'a str,
+    url: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    date_created: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    creator: Option<Person<'a>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    copyright_notice: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    keywords: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content_location: Option<Place>,
+    image: ImageObject<'a>,
+    is_part_of: GalleryRef<'a>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ImageObject<'a> {
+    #[serde(rename = "@type")]
+    kind: &'static str,
+    name: &'a str,
+    url: &'a str,
+    content_url: &'a str,
+    thumbnail_url: &'a str,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    exif_data: Vec<PropertyValue>,
+}
+
+#[derive(Debug, Serialize)]
+struct PropertyValue {
+    #[serde(rename = "@type")]
+    kind: &'static str,
+    name: &'static str,
+    value: String,
+}
+
+#[derive(Debug, Serialize)]
+struct Person<'a> {
+    #[serde(rename = "@type")]
+    kind: &'static str,
+    name: &'a str,
+}
+
+#[derive(Debug, Serialize)]
+struct Place {
+    #[serde(rename = "@type")]
+    kind: &'static str,
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    geo: Option<GeoCoordinates>,
+}
+
+#[derive(Debug, Serialize)]
+struct GeoCoordinates {
+    #[serde(rename = "@type")]
+    kind: &'static str,
+    latitude: f64,
+    longitude: f64,
+}
+
+#[derive(Debug, Serialize)]
+struct GalleryRef<'a> {
+    #[serde(rename = "@type")]
+    kind: &'static str,
+    name: &'a str,
+    url: &'a str,
+}
+
+/// `ImageGallery` for an index or album page.
+pub fn gallery(name: &str, url: &str, media: &[Media]) -> String {
+    to_script(&Gallery {
+        context: CONTEXT,
+        kind: "ImageGallery",
+        name,
+        url,
+        associated_media: media.iter().map(|m| image_object(m, false)).collect(),
+    })
+}
+
+/// `Photograph` for a photo page, part of the gallery at `gallery_url`.
+pub fn photograph(media: &Media, gallery_name: &str, gallery_url: &str, gps: GpsMode) -> String {
+    let metadata = &media.photo.metadata;
+    to_script(&Photograph {
+        context: CONTEXT,
+        kind: "Photograph",
+        name: title(media.photo),
+        url: &media.page_url,
+        description: metadata.description.as_deref(),
+        date_created: metadata.date_taken.as_deref().map(iso_datetime),
+        creator: metadata.creator.as_deref().map(|name| Person {
+            kind: "Person",
+            name,
+        }),
+        copyright_notice: metadata.copyright.as_deref(),
+        keywords: (!metadata.keywords.is_empty()).then(|| metadata.keywords.join(", ")),
+        content_location: place(media.photo, gps),
+        image: image_object(media, true),
+        is_part_of: GalleryRef {
+            kind: "ImageGallery",
+            name: gallery_name,
+            url: gallery_url,
+        },
+    })
+}
+
+fn title(photo: &Photo) -> &str {
+    photo.metadata.title.as_deref().unwrap_or(&photo.stem)
+}
+
+fn image_object<'a>(media: &'a Media, with_exif: bool) -> ImageObject<'a> {
+    ImageObject {
+        kind: "ImageObject",
+        name: title(media.photo),
+        url: &media.page_url,
+        content_url: &media.image_url,
+        thumbnail_url: &media.thumbnail_url,
+        exif_data: if with_exif {
+            exif_data(media.photo)
+        } else {
+            Vec::new()
+        },
+    }
+}
+
+/// Camera and exposure settings as `PropertyValue`s.
+fn exif_data(photo: &Photo) -> Vec<PropertyValue> {
+    let metadata = &photo.metadata;
+    let exposure = metadata.exposure.as_ref();
+    [
+        ("Camera", metadata.camera.clone()),
+        ("Lens", metadata.lens.clone()),
+        ("Aperture", exposure.and_then(|e| e.aperture.clone())),
+        (
+            "Shutter speed",
+            exposure.and_then(|e| e.shutter_speed.clone()),
+        ),
+        (
+            "ISO",
+            exposure.and_then(|e| e.iso).map(|iso| iso.to_string()),
+        ),
+        (
+            "Focal length",
+            exposure.and_then(|e| e.focal_length.clone()),
+        ),
+    ]
+    .into_iter()
+    .filter_map(|(name, value)| {
+        Some(PropertyValue {
+            kind: "PropertyValue",
+            name,
+            value: value?,
+        })
+    })
+    .collect()
+}
+
+/// Where the photo was taken, with coordinates only when GPS is fully published.
+fn place(photo: &Photo, gps_mode: GpsMode) -> Option<Place> {
+    let metadata = &photo.metadata;
+    let (city, country) = match (&metadata.location, &metadata.gps) {
+        (Some(location), _) => (&location.city, &location.country),
+        (None, Some(gps)) => (&gps.city, &gps.country),
+        (None, None) => return None,
+    };
+    let geo = metadata
+        .gps
+        .as_ref()
+        .filter(|_| gps_mode == GpsMode::On)
+        .and_then(|gps| {
+            Some(GeoCoordinates {
+                kind: "GeoCoordinates",
+                latitude: gps.latitude?,
+                longitude: gps.longitude?,
+            })
+        });
+    let name: Vec<&str> = [city, country]
+        .into_iter()
+        .flatten()
+        .map(String::as_str)
+        .collect();
+    if name.is_empty() && geo.is_none() {
+        return None;
+    }
+    Some(Place {
+        kind: "Place",
+        name: name.join(", "),
+        geo,
+    })
+}
+
+/// JSON safe to put inside `<script type="application/ld+json">`.
+fn to_script<T: Serialize>(value: &T) -> String {
+    serde_json::to_string(value)
+        .unwrap_or_default()
+        .replace("</", "<\\/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::photos::{ExposureInfo, GpsCoords, PhotoMetadata};
+    use serde_json::Value;
+    use test_case::test_case;
+
+    fn photo() -> Photo {
+        Photo::processed(
+            "iceland/DSC01234.jpg",
+            PhotoMetadata {
+                date_taken: Some("2024:06:15 14:30:00".to_string()),
+                camera: Some("Canon EOS R5".to_string()),
+                exposure: Some(ExposureInfo {
+                    aperture: Some("f/8".to_string()),
+                    iso: Some(100),
+                    ..Default::default()
+                }),
+                gps: Some(GpsCoords {
+                    latitude: Some(63.4),
+                    longitude: Some(-19.04),
+                    city: Some("Vik".to_string()),
+                    country: Some("Iceland".to_string()),
+                    ..Default::default()
+                }),
+                title: Some("Reynisfjara".to_string()),
+                description: Some("Black sand </script> at dawn".to_string()),
+                ..Default::default()
+            },
+        )
+    }
+
+    fn media(photo: &Photo) -> Media<'_> {
+        Media {
+            photo,
+            page_url: "https://example.com/iceland/DSC01234/".to_string(),
+            image_url: "https://example.com/images/iceland/DSC01234-abc12345-full.webp".to_string(),
+            thumbnail_url: "https://example.com/images/iceland/DSC01234-abc12345-thumb.webp"
+                .to_string(),
+        }
+    }
+
+    #[test]
+    fn gallery_lists_photos() {
+        let photo = photo();
+        let json = gallery("Iceland", "https://example.com/iceland/", &[media(&photo)]);
+        let value: Value = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(value["@context"], "https://schema.org");
+        assert_eq!(value["@type"], "ImageGallery");
+        assert_eq!(value["url"], "https://example.com/iceland/");
+        assert_eq!(value["associatedMedia"][0]["@type"], "ImageObject");
+        assert_eq!(value["associatedMedia"][0]["name"], "Reynisfjara");
+        assert!(value["associatedMedia"][0].get("exifData").is_none());
+    }
+
+    #[test]
+    fn photograph_has_exif() {
+        let photo = photo();
+        let json = photograph(
+            &media(&photo),
+            "Iceland",
+            "https://example.com/iceland/",
+            GpsMode::On,
+        );
+        let value: Value = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(value["@type"], "Photograph");
+        assert_eq!(value["dateCreated"], "2024-06-15T14:30:00");
+        assert_eq!(value["isPartOf"]["url"], "https://example.com/iceland/");
+        let exif = value["image"]["exifData"].as_array().unwrap();
+        let names: Vec<_> = exif.iter().map(|p| p["name"].as_str().unwrap()).collect();
+        assert_eq!(names, ["Camera", "Aperture", "ISO"]);
+        assert_eq!(exif[2]["value"], "100");
+    }
+
+    #[test_case(GpsMode::On, true, true ; "on")]
+    #[test_case(GpsMode::General, true, false ; "general")]
+    fn photograph_location(gps: GpsMode, named: bool, coordinates: bool) {
+        let photo = photo();
+        let json = photograph(
+            &media(&photo),
+            "Iceland",
+            "https://example.com/iceland/",
+            gps,
+        );
+        let value: Value = serde_json::from_str(&json).unwrap();
+        let location = &value["contentLocation"];
+
+        assert_eq!(location["name"] == "Vik, Iceland", named);
+        assert_eq!(location["geo"]["latitude"] == 63.4, coordinates);
+    }
+
+    #[test]
+    fn script_end_tag_escaped() {
+        let photo = photo();
+        let json = photograph(
+            &media(&photo),
+            "Iceland",
+            "https://example.com/iceland/",
+            GpsMode::On,
+        );
+
+        assert!(!json.contains("</script>"));
+        let value: Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["description"], "Black sand </script> at dawn");
+    }
+}
@@ -1,18 +1,76 @@
+mod activitypub;
+mod album_config;
+mod analytics;
+mod animation;
+mod archive;
+mod audit;
+mod auto_album;
+mod build_report;
 mod builtin_themes;
+mod cache;
+mod check;
+mod checksums;
+mod clean;
+mod collections;
+mod color;
+mod comments;
 mod config;
+mod countries;
+mod crop;
+mod daemon;
+mod deploy;
+mod digest;
+mod doctor;
 mod error;
+mod exif;
+mod feed;
+mod functions;
+mod gallery_delta;
+mod geocode;
+mod gpx;
+mod heif;
 mod i18n;
+mod init;
+mod inject;
+mod iptc;
+mod jsonld;
 mod minify;
+mod originals;
+mod overview;
 mod photos;
 mod pipeline;
+mod preview;
 mod processing;
+mod progress;
+mod publish;
+mod raw;
+mod seo;
+mod serve;
+mod sidecar;
+mod sizes;
+mod snapshot;
+mod stats;
+mod svg;
+mod systemd;
+mod template_error;
+mod template_usage;
 mod theme;
 mod theme_build;
+mod theme_list;
+mod theme_scaffold;
+mod timings;
+mod update;
 mod util;
+mod video;
 mod watch;
+mod watermark;
+mod xmp;
 
-use clap::{Parser, Subcommand};
+use clap::{CommandFactory, Parser, Subcommand, ValueHint};
+use std::io::IsTerminal;
+use std::num::NonZeroUsize;
 use std::path::PathBuf;
+use std::process::ExitCode;
 use tracing::Level;
 
 const VERSION: &str = env!("GIT_VERSION");
@@ -23,11 +81,11 @@ const VERSION: &str = env!("GIT_VERSION");
 #[command(about = env!("CARGO_PKG_DESCRIPTION"))]
 struct Args {
     /// Site directory (contains site.toml and photo directories)
-    #[arg(short = 'C', long, default_value = ".", global = true)]
+    #[arg(short = 'C', long, default_value = ".", global = true, value_hint = ValueHint::DirPath)]
     directory: PathBuf,
 
     /// Path to site configuration file (relative to site directory)
-    #[arg(short, long, default_value = "site.toml", global = true)]
+    #[arg(short, long, default_value = "site.toml", global = true, value_hint = ValueHint::FilePath)]
     config: PathBuf,
 
     /// Logging verbosity (-v: debug, -vv: trace)
@@ -46,6 +104,27 @@ struct Args {
     #[arg(long, global = true)]
     source_maps: bool,
 
+    /// Threads to build with (defaults to [processing] threads, or one per CPU core)
+    #[arg(short, long, global = true)]
+    jobs: Option<NonZeroUsize>,
+
+    /// Publish photos re-exported under the same name that reexports = "hold" kept back
+    #[arg(long, global = true)]
+    accept_changes: bool,
+
+    /// Print where the build's time went: each stage, and each photo's decode, encode, and IO
+    #[arg(long, global = true)]
+    timings: bool,
+
+    /// Write the build's timings as JSON to this file (implies --timings)
+    #[arg(long, global = true, value_name = "FILE", value_hint = ValueHint::FilePath)]
+    timings_json: Option<PathBuf>,
+
+    /// Write what the build did as JSON to this file: pages rendered, photos processed,
+    /// bytes written, stale files removed, and time per stage
+    #[arg(long, global = true, value_name = "FILE", value_hint = ValueHint::FilePath)]
+    report: Option<PathBuf>,
+
     #[command(subcommand)]
     command: Option<Command>,
 }
@@ -68,6 +147,14 @@ enum Command {
         /// Disable automatic rebuild on file changes
         #[arg(long)]
         no_watch: bool,
+
+        /// Run unattended: survive failed builds, answer /healthz and /readyz, stop cleanly on SIGTERM
+        #[arg(long, conflicts_with = "no_watch")]
+        daemon: bool,
+
+        /// List the files of directories without an index.html, to inspect the output
+        #[arg(long, conflicts_with = "daemon")]
+        listing: bool,
     },
 
     /// Watch for changes and rebuild automatically
@@ -75,10 +162,164 @@ enum Command {
         /// Debounce delay in seconds
         #[arg(long, default_value = "5")]
         debounce: u64,
+
+        /// Exit after the first rebuild triggered by changes
+        #[arg(long)]
+        once: bool,
+
+        /// Write changes and builds to stdout as JSON lines, and logs to stderr
+        #[arg(long)]
+        json_events: bool,
+    },
+
+    /// Delete the output directory, or all but its processed images
+    Clean {
+        /// Keep processed images, deleting only pages, static assets, data files, and feeds
+        #[arg(long)]
+        pages_only: bool,
+
+        /// Also delete the metadata cache, so the next build reads every photo again
+        #[arg(long)]
+        cache: bool,
+    },
+
+    /// Summarize the photos: albums, sizes, cameras and lenses, dates, and GPS
+    Stats,
+
+    /// Load the site and render every page without touching the output, failing on problems
+    ///
+    /// Pages are rendered into a temporary directory, which needs as much space as the built
+    /// site without its images, and is removed afterwards.
+    Check,
+
+    /// Check the environment: package manager, output and photos directories, photo files, clock, and locale
+    Doctor,
+
+    /// Create a site: a commented site.toml, and a photos directory with an example album
+    Init {
+        /// Domain the site will be hosted on
+        #[arg(long, default_value = "photos.example.com")]
+        domain: String,
+
+        /// Site title
+        #[arg(long)]
+        title: Option<String>,
+
+        /// Replace an existing site.toml (photos are never touched)
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Build the site and write an HTML digest of photos added since a date
+    Digest {
+        /// Include photos added on or after this date (e.g. 2024-04-01 or 2024-04-01T18:00:00Z)
+        #[arg(long, value_parser = digest::parse_since)]
+        since: i64,
+
+        /// File to write the digest to
+        #[arg(short, long, default_value = "digest.html", value_hint = ValueHint::FilePath)]
+        output: PathBuf,
+
+        /// Embed thumbnails as data URIs instead of linking to the published site
+        #[arg(long)]
+        inline: bool,
+
+        /// Use capture dates instead of file modification times
+        #[arg(long)]
+        taken: bool,
+
+        /// Heading of the digest (defaults to "New photos in <site title>")
+        #[arg(long)]
+        title: Option<String>,
+    },
+
+    /// Build, verify, and deploy the site, recording what was published
+    Publish {
+        /// Publish the output as it is, without building first
+        #[arg(long)]
+        skip_build: bool,
+
+        /// Don't check the output is complete before deploying
+        #[arg(long)]
+        skip_verify: bool,
+
+        /// Stop after verifying, showing what a deploy would change
+        #[arg(long)]
+        skip_deploy: bool,
+    },
+
+    /// Deploy the output as it is, or undo the latest deploy
+    Deploy {
+        /// Restore the deploy before the latest, on git and S3 targets
+        #[arg(long)]
+        rollback: bool,
+    },
+
+    /// List built-in and local themes, and the pages each renders
+    Themes,
+
+    /// Create a minimal theme to start a custom one from
+    NewTheme {
+        /// Directory to create, relative to the site directory
+        name: String,
+    },
+
+    /// Theme development tools
+    Theme {
+        #[command(subcommand)]
+        command: ThemeCommand,
+    },
+
+    /// Print a shell completion script
+    Completions {
+        /// Shell to complete in
+        #[arg(value_enum)]
+        shell: clap_complete::Shell,
     },
 
-    /// Delete the output directory
-    Clean,
+    /// Print the man page
+    Man {
+        /// Write a page for every subcommand into this directory instead
+        #[arg(short, long, value_hint = ValueHint::DirPath)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum ThemeCommand {
+    /// Build a theme against a generated synthetic gallery
+    Preview {
+        /// Theme to preview (local directory or built-in theme name)
+        #[arg(value_name = "THEME")]
+        name: String,
+
+        /// Directory for the generated preview site (defaults to a temp directory)
+        #[arg(short, long, value_hint = ValueHint::DirPath)]
+        output: Option<PathBuf>,
+
+        /// Port to serve on
+        #[arg(short, long, default_value = "3000")]
+        port: u16,
+
+        /// Build only, don't start the server
+        #[arg(long)]
+        no_serve: bool,
+    },
+
+    /// Render a theme against fixtures and compare with stored snapshots
+    Test {
+        /// Theme to test (local directory or built-in theme name)
+        #[arg(value_name = "THEME")]
+        name: String,
+
+        /// Snapshot directory (defaults to `snapshots/` in the theme directory)
+        #[arg(short, long, value_hint = ValueHint::DirPath)]
+        snapshots: Option<PathBuf>,
+
+        /// Overwrite differing snapshots and remove orphaned ones
+        #[arg(long)]
+        update: bool,
+    },
 }
 
 impl Args {
@@ -94,54 +335,256 @@ impl Args {
         }
     }
 
+    /// Whether to draw progress bars, which need a terminal and aren't quiet.
+    fn shows_progress(&self) -> bool {
+        !self.quiet && std::io::stderr().is_terminal()
+    }
+
+    /// Whether logs go to stderr, leaving stdout to `watch --json-events`.
+    fn logs_to_stderr(&self) -> bool {
+        matches!(
+            self.command,
+            Some(Command::Watch {
+                json_events: true,
+                ..
+            })
+        )
+    }
+
     fn config_path(&self) -> PathBuf {
         self.directory.join(&self.config)
     }
+
+    fn overrides(&self) -> config::Overrides {
+        config::Overrides {
+            theme: self.theme.clone(),
+            jobs: self.jobs,
+            accept_changes: self.accept_changes,
+        }
+    }
+
+    fn records_timings(&self) -> bool {
+        self.timings || self.timings_json.is_some()
+    }
+}
+
+/// Print the timings of a build, and write them as JSON to `json` if given.
+fn report_timings(
+    pipeline: &pipeline::Pipeline,
+    json: Option<&std::path::Path>,
+) -> error::Result<()> {
+    let report = pipeline.timings.report();
+    print!("{}", report.table());
+    if let Some(path) = json {
+        report.write_json(path)?;
+        tracing::info!(path = %path.display(), "wrote timings");
+    }
+    Ok(())
 }
 
-fn init_tracing(level: Level) {
+fn init_tracing(level: Level, stderr: bool) {
+    use tracing_subscriber::fmt::writer::BoxMakeWriter;
     use tracing_subscriber::{EnvFilter, fmt};
 
     let filter =
         EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(level.as_str()));
+    let writer = if stderr {
+        BoxMakeWriter::new(|| progress::ClearBar::new(std::io::stderr()))
+    } else {
+        BoxMakeWriter::new(|| progress::ClearBar::new(std::io::stdout()))
+    };
 
     #[cfg(distribute)]
     {
-        fmt().json().with_env_filter(filter).init();
+        fmt()
+            .json()
+            .with_env_filter(filter)
+            .with_writer(writer)
+            .init();
     }
 
     #[cfg(not(distribute))]
     {
-        fmt().pretty().with_env_filter(filter).init();
+        fmt()
+            .pretty()
+            .with_env_filter(filter)
+            .with_writer(writer)
+            .init();
     }
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
+fn main() -> ExitCode {
     let args = Args::parse();
 
-    init_tracing(args.log_level());
+    init_tracing(args.log_level(), args.logs_to_stderr());
 
     tracing::debug!(?args, "parsed arguments");
 
+    match run(args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            report(&err);
+            ExitCode::from(err.exit_code())
+        }
+    }
+}
+
+/// Print an error and its hint to stderr.
+///
+/// Colored when stderr is a terminal, plain otherwise so logs stay readable.
+fn report(err: &error::Error) {
+    tracing::debug!(?err, "command failed");
+
+    let color = std::io::stderr().is_terminal();
+    let (error_label, hint_label) = if color {
+        ("\x1b[1;31merror\x1b[0m", "\x1b[1;36mhint\x1b[0m")
+    } else {
+        ("error", "hint")
+    };
+
+    eprintln!("{}: {}", error_label, err);
+    if let Some(hint) = err.hint() {
+        eprintln!("  {}: {}", hint_label, hint);
+    }
+}
+
+fn run(args: Args) -> error::Result<()> {
+    // Completions and man pages come from the CLI definition alone
+    match &args.command {
+        Some(Command::Completions { shell }) => {
+            let mut command = Args::command();
+            let name = command.get_name().to_string();
+            clap_complete::generate(*shell, &mut command, name, &mut std::io::stdout());
+            return Ok(());
+        }
+        Some(Command::Man { output }) => return man(output.as_deref()),
+        _ => {}
+    }
+
     // Watch command handles its own config loading (for hot-reload support)
-    if let Some(Command::Watch { debounce }) = args.command {
+    if let Some(Command::Watch {
+        debounce,
+        once,
+        json_events,
+    }) = args.command
+    {
         let config_path = args.config_path();
-        watch::watch(args.directory, config_path, args.theme, debounce)?;
+        watch::watch(
+            args.directory.clone(),
+            config_path,
+            args.overrides(),
+            debounce,
+            once,
+            watch::Events::new(json_events),
+        )?;
+        return Ok(());
+    }
+
+    // Daemon mode also reloads its config on every build
+    if let Some(Command::Serve {
+        port,
+        debounce,
+        daemon: true,
+        ..
+    }) = args.command
+    {
+        return daemon::run(
+            args.directory.clone(),
+            args.config_path(),
+            args.overrides(),
+            args.source_maps,
+            port,
+            debounce,
+        );
+    }
+
+    // Init writes site.toml rather than reading it
+    if let Some(Command::Init {
+        domain,
+        title,
+        force,
+    }) = &args.command
+    {
+        let options = init::Options {
+            domain: domain.clone(),
+            title: title.clone(),
+            theme: args.theme.clone().unwrap_or_else(|| "fancy".to_string()),
+            force: *force,
+        };
+        return init::run(&args.directory, &options);
+    }
+
+    // Lists themes whether or not there's a site here, marking its theme if so
+    if let Some(Command::Themes) = &args.command {
+        let in_use = config::Site::load(&args.config_path())
+            .ok()
+            .map(|site| site.theme.name().to_string());
+        let table = theme_list::table(
+            &theme_list::builtin(),
+            &theme_list::local(&args.directory),
+            in_use.as_deref(),
+        );
+        print!("{}", table);
         return Ok(());
     }
 
+    // Diagnoses a site.toml that doesn't load, so it's loaded here
+    if let Some(Command::Doctor) = &args.command {
+        let config_path = args.config_path();
+        let mut site = config::Site::load(&config_path);
+        if let Ok(site) = &mut site {
+            args.overrides().apply(site);
+        }
+        let findings = doctor::run(&args.directory, &config_path, site);
+        print!("{}", doctor::render(&findings));
+        return match doctor::error(&findings) {
+            Some(e) => Err(e),
+            None => Ok(()),
+        };
+    }
+
+    if let Some(Command::NewTheme { name }) = &args.command {
+        return theme_scaffold::run(&args.directory, name);
+    }
+
+    // Theme previews generate their own site and don't need site.toml
+    if let Some(Command::Theme {
+        command:
+            ThemeCommand::Preview {
+                name,
+                output,
+                port,
+                no_serve,
+            },
+    }) = &args.command
+    {
+        return theme_preview(&args, name, output.as_deref(), *port, *no_serve);
+    }
+
+    if let Some(Command::Theme {
+        command:
+            ThemeCommand::Test {
+                name,
+                snapshots,
+                update,
+            },
+    }) = &args.command
+    {
+        return theme_test(&args, name, snapshots.as_deref(), *update);
+    }
+
     // Load site configuration
     let config_path = args.config_path();
     tracing::info!(path = %config_path.display(), "loading site config");
 
-    let config_content = std::fs::read_to_string(&config_path)?;
-    let mut site: config::Site = toml::from_str(&config_content)?;
+    let mut site = config::Site::load(&config_path)?;
 
-    // Override theme if specified via CLI
+    // Override theme and threads if specified via CLI
     if let Some(theme_name) = &args.theme {
         tracing::info!(theme = %theme_name, "overriding theme from CLI");
-        site.theme = config::ThemeConfig::Name(theme_name.clone());
     }
+    let overrides = args.overrides();
+    overrides.apply(&mut site);
 
     // Disable minification when source maps are enabled (easier debugging)
     if args.source_maps && site.minify {
@@ -157,159 +600,325 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         "site configured"
     );
 
+    let records_timings = args.records_timings();
+    let progress = progress::Progress::new(args.shows_progress());
+
     // Handle command
     match args.command.unwrap_or(Command::Build) {
         Command::Build => {
             let mut pipeline =
                 pipeline::Pipeline::load(args.directory.clone(), site, args.source_maps)?;
+            pipeline.timings = timings::Timings::new(records_timings || args.report.is_some());
+            pipeline.report = args.report.clone();
+            pipeline.progress = progress;
             pipeline.build()?;
             tracing::info!("build complete");
+            if records_timings {
+                report_timings(&pipeline, args.timings_json.as_deref())?;
+            }
+            update::notify(pipeline.config.update_check);
         }
         Command::Serve {
             port,
             debounce,
             no_watch,
+            listing,
+            ..
         } => {
             let mut pipeline =
                 pipeline::Pipeline::load(args.directory.clone(), site, args.source_maps)?;
+            pipeline.timings = timings::Timings::new(records_timings || args.report.is_some());
+            pipeline.report = args.report.clone();
+            pipeline.progress = progress;
             pipeline.build()?;
+            if records_timings {
+                report_timings(&pipeline, args.timings_json.as_deref())?;
+            }
 
+            // Rebuilds are only requested of a watcher
+            let trigger =
+                serve::Trigger::new(pipeline.config.rebuild_token.as_deref()).filter(|_| !no_watch);
             if !no_watch {
                 let watch_dir = args.directory.clone();
-                let watch_config = config_path.clone();
-                let watch_theme = args.theme.clone();
+                let watch_config = config::Reloader::new(config_path.clone());
+                let watch_overrides = overrides;
+                let rebuild = trigger
+                    .as_ref()
+                    .map(|trigger| std::sync::Arc::clone(&trigger.requested))
+                    .unwrap_or_default();
                 std::thread::spawn(move || {
                     let _ = watch::watch_and_rebuild(
                         watch_dir,
                         watch_config,
-                        watch_theme,
+                        watch_overrides,
                         std::time::Duration::from_secs(debounce),
+                        Some(1),
+                        watch::Events::default(),
+                        rebuild,
                     );
                 });
             }
+            if trigger.is_some() {
+                tracing::info!("rebuilds can be requested with POST /_galerie/rebuild");
+            }
+
+            let tools = serve::Tools {
+                listing,
+                resize: true,
+                rebuild: trigger,
+            };
+            serve::serve(&pipeline.site_dir.join(&pipeline.config.build), port, tools)?;
+        }
+        Command::Digest {
+            since,
+            output,
+            inline,
+            taken,
+            title,
+        } => {
+            let mut pipeline =
+                pipeline::Pipeline::load(args.directory.clone(), site, args.source_maps)?;
+            pipeline.timings = timings::Timings::new(records_timings || args.report.is_some());
+            pipeline.report = args.report.clone();
+            pipeline.progress = progress;
+            pipeline.build()?;
+            if records_timings {
+                report_timings(&pipeline, args.timings_json.as_deref())?;
+            }
 
-            serve(&pipeline.site_dir.join(&pipeline.config.build), port)?;
+            let added_by = if taken {
+                digest::AddedBy::Taken
+            } else {
+                digest::AddedBy::Modified
+            };
+            let entries = digest::entries(&pipeline, since, added_by, inline)?;
+            let title = title.unwrap_or_else(|| {
+                let site_title = pipeline
+                    .config
+                    .title
+                    .as_ref()
+                    .unwrap_or(&pipeline.config.domain);
+                format!("New photos in {}", site_title)
+            });
+
+            std::fs::write(
+                &output,
+                digest::render(&title, &pipeline.base_url(), &entries),
+            )?;
+            tracing::info!(
+                path = %output.display(),
+                photos = entries.len(),
+                "wrote digest"
+            );
         }
-        Command::Watch { .. } => unreachable!("handled above"),
-        Command::Clean => {
-            let output_dir = args.directory.join(&site.build);
-            if output_dir.exists() {
-                std::fs::remove_dir_all(&output_dir)?;
-                tracing::info!(path = %output_dir.display(), "cleaned output directory");
+        Command::Watch { .. }
+        | Command::Init { .. }
+        | Command::Themes
+        | Command::Doctor
+        | Command::NewTheme { .. }
+        | Command::Theme { .. }
+        | Command::Completions { .. }
+        | Command::Man { .. } => unreachable!("handled above"),
+        Command::Publish {
+            skip_build,
+            skip_verify,
+            skip_deploy,
+        } => {
+            let site = if skip_build {
+                tracing::info!("skipping build");
+                site
             } else {
-                tracing::info!(path = %output_dir.display(), "output directory does not exist");
+                let mut pipeline =
+                    pipeline::Pipeline::load(args.directory.clone(), site, args.source_maps)?;
+                pipeline.timings = timings::Timings::new(records_timings || args.report.is_some());
+                pipeline.report = args.report.clone();
+                pipeline.progress = progress;
+                pipeline.build()?;
+                if records_timings {
+                    report_timings(&pipeline, args.timings_json.as_deref())?;
+                }
+                pipeline.config
+            };
+            publish::publish(
+                &args.directory,
+                &site,
+                publish::Stages {
+                    verify: !skip_verify,
+                    deploy: !skip_deploy,
+                },
+            )?;
+        }
+        Command::Deploy { rollback: true } => publish::rollback(&args.directory, &site)?,
+        Command::Deploy { rollback: false } => publish::publish(
+            &args.directory,
+            &site,
+            publish::Stages {
+                verify: true,
+                deploy: true,
+            },
+        )?,
+        Command::Check => {
+            let report = check::run(&args.directory, site)?;
+            for (key, page) in &report.unknown_keys {
+                tracing::error!(key = %key, page = %page.display(), "unknown translation key");
+            }
+            if report.unreadable > 0 {
+                tracing::error!(
+                    photos = report.unreadable,
+                    "photos could not be read and would be left out"
+                );
+            }
+            tracing::info!(
+                photos = report.photos,
+                pages = report.pages,
+                problems = report.problems(),
+                "check complete"
+            );
+            if !report.passed() {
+                return Err(error::Error::Check {
+                    problems: report.problems(),
+                });
             }
         }
+        Command::Stats => print!(
+            "{}",
+            overview::render(&overview::gather(&args.directory, &site)?)
+        ),
+        Command::Clean { pages_only, cache } => clean::run(
+            &args.directory.join(&site.build),
+            &cache::path(&args.directory),
+            clean::Options { pages_only, cache },
+        )?,
     }
 
     Ok(())
 }
 
-fn serve(dir: &std::path::Path, port: u16) -> Result<(), Box<dyn std::error::Error>> {
-    use std::fs;
-    use tiny_http::{Header, Response, Server};
+/// Print the man page, or write one for every subcommand into `output`.
+fn man(output: Option<&std::path::Path>) -> error::Result<()> {
+    let command = Args::command();
+    match output {
+        Some(dir) => {
+            std::fs::create_dir_all(dir)?;
+            clap_mangen::generate_to(command, dir)?;
+            tracing::info!(path = %dir.display(), "wrote man pages");
+        }
+        None => clap_mangen::Man::new(command).render(&mut std::io::stdout())?,
+    }
+    Ok(())
+}
 
-    let addr = format!("0.0.0.0:{}", port);
-    let server = Server::http(&addr).map_err(|e| format!("failed to start server: {}", e))?;
+/// Generate a synthetic gallery for `theme`, build it, and optionally serve it.
+fn theme_preview(
+    args: &Args,
+    theme: &str,
+    output: Option<&std::path::Path>,
+    port: u16,
+    no_serve: bool,
+) -> error::Result<()> {
+    let (theme, _) = resolve_theme_arg(&args.directory, theme)?;
+
+    let preview_dir = output
+        .map(PathBuf::from)
+        .unwrap_or_else(|| std::env::temp_dir().join("galerie-preview"));
+    std::fs::create_dir_all(&preview_dir)?;
+
+    let mut site = preview::generate(&preview_dir, &theme)?;
+    if args.source_maps {
+        site.minify = false;
+    }
 
-    tracing::info!(url = %format!("http://localhost:{}", port), "serving site");
-    println!(
-        "\n  Serving at http://localhost:{}\n  Press Ctrl+C to stop\n",
-        port
-    );
+    let mut pipeline = pipeline::Pipeline::load(preview_dir, site, args.source_maps)?;
+    pipeline.build()?;
+    tracing::info!(theme = %theme, "preview build complete");
 
-    for request in server.incoming_requests() {
-        let url_path = request.url().to_string();
-        let url_path = url_path.trim_start_matches('/');
+    if !no_serve {
+        let tools = serve::Tools {
+            listing: false,
+            resize: true,
+            rebuild: None,
+        };
+        serve::serve(&pipeline.site_dir.join(&pipeline.config.build), port, tools)?;
+    }
 
-        // URL-decode the path (handles %20 for spaces, etc.)
-        let decoded_path = url_decode(url_path);
+    Ok(())
+}
 
-        // Determine file path
-        let file_path = if decoded_path.is_empty() {
-            dir.join("index.html")
-        } else {
-            let path = dir.join(&decoded_path);
-            if path.is_dir() {
-                path.join("index.html")
-            } else {
-                path
-            }
-        };
+/// Render a theme against the synthetic gallery and compare with snapshots.
+fn theme_test(
+    args: &Args,
+    theme: &str,
+    snapshots: Option<&std::path::Path>,
+    update: bool,
+) -> error::Result<()> {
+    let (theme, local_dir) = resolve_theme_arg(&args.directory, theme)?;
 
-        // Serve the file
-        if file_path.exists() && file_path.is_file() {
-            let content = fs::read(&file_path)?;
-            let content_type = guess_content_type(&file_path);
+    let snapshot_dir = match (snapshots, &local_dir) {
+        (Some(dir), _) => args.directory.join(dir),
+        (None, Some(dir)) => dir.join("snapshots"),
+        (None, None) => args.directory.join("snapshots").join(&theme),
+    };
 
-            let response = Response::from_data(content)
-                .with_header(Header::from_bytes("Content-Type", content_type).unwrap());
+    let fixture_dir = std::env::temp_dir().join("galerie-theme-test");
+    std::fs::create_dir_all(&fixture_dir)?;
 
-            request.respond(response)?;
-            tracing::debug!(path = %url_path, "200 OK");
-        } else {
-            let response = Response::from_string("404 Not Found")
-                .with_status_code(404)
-                .with_header(Header::from_bytes("Content-Type", "text/plain").unwrap());
+    // Unminified output keeps snapshot diffs readable
+    let mut site = preview::generate(&fixture_dir, &theme)?;
+    site.minify = false;
 
-            request.respond(response)?;
-            tracing::debug!(path = %url_path, "404 Not Found");
-        }
+    let report = snapshot::check(&fixture_dir, site, &snapshot_dir, update)?;
+
+    for page in &report.created {
+        tracing::info!(page = %page.display(), "created snapshot");
+    }
+    for page in &report.updated {
+        tracing::info!(page = %page.display(), "updated snapshot");
+    }
+    for mismatch in &report.mismatched {
+        tracing::error!(
+            page = %mismatch.page.display(),
+            line = mismatch.line,
+            expected = %mismatch.expected,
+            actual = %mismatch.actual,
+            "snapshot mismatch"
+        );
+    }
+    for page in &report.orphaned {
+        tracing::error!(page = %page.display(), "snapshot has no rendered page");
     }
 
-    Ok(())
-}
+    tracing::info!(
+        snapshots = %snapshot_dir.display(),
+        matched = report.matched,
+        created = report.created.len(),
+        updated = report.updated.len(),
+        failed = report.mismatched.len() + report.orphaned.len(),
+        "snapshot test complete"
+    );
 
-fn guess_content_type(path: &std::path::Path) -> &'static str {
-    match path.extension().and_then(|e| e.to_str()) {
-        Some("html") => "text/html; charset=utf-8",
-        Some("css") => "text/css; charset=utf-8",
-        Some("js") => "application/javascript; charset=utf-8",
-        Some("json") => "application/json; charset=utf-8",
-        Some("png") => "image/png",
-        Some("jpg") | Some("jpeg") => "image/jpeg",
-        Some("gif") => "image/gif",
-        Some("webp") => "image/webp",
-        Some("svg") => "image/svg+xml",
-        Some("ico") => "image/x-icon",
-        Some("woff") => "font/woff",
-        Some("woff2") => "font/woff2",
-        Some("ttf") => "font/ttf",
-        Some("map") => "application/json",
-        _ => "application/octet-stream",
+    if !report.passed() {
+        return Err(error::Error::SnapshotMismatch {
+            failed: report.mismatched.len() + report.orphaned.len(),
+        });
     }
+
+    Ok(())
 }
 
-/// Decode URL-encoded strings (e.g., %20 -> space).
-fn url_decode(s: &str) -> String {
-    let mut result = Vec::with_capacity(s.len());
-    let mut bytes = s.bytes();
-
-    while let Some(b) = bytes.next() {
-        if b == b'%' {
-            match (bytes.next(), bytes.next()) {
-                (Some(h1), Some(h2)) => {
-                    let hex = [h1, h2];
-                    match u8::from_str_radix(std::str::from_utf8(&hex).unwrap_or(""), 16) {
-                        Ok(byte) => result.push(byte),
-                        Err(_) => {
-                            result.push(b'%');
-                            result.extend_from_slice(&hex);
-                        }
-                    }
-                }
-                (Some(h1), None) => {
-                    result.push(b'%');
-                    result.push(h1);
-                }
-                _ => result.push(b'%'),
-            }
-        } else if b == b'+' {
-            result.push(b' ');
-        } else {
-            result.push(b);
-        }
+/// Resolve a theme given on the command line.
+///
+/// Local themes are resolved against the site directory and made absolute so
+/// generated sites elsewhere can refer to them. Returns the theme string to use
+/// in `site.toml` and the local theme directory, if any.
+fn resolve_theme_arg(
+    site_dir: &std::path::Path,
+    theme: &str,
+) -> error::Result<(String, Option<PathBuf>)> {
+    let local_theme = site_dir.join(theme);
+    if local_theme.is_dir() {
+        let local_theme = local_theme.canonicalize()?;
+        Ok((local_theme.display().to_string(), Some(local_theme)))
+    } else {
+        Ok((theme.to_string(), None))
     }
-
-    String::from_utf8_lossy(&result).into_owned()
 }
@@ -1,7 +1,13 @@
-//! Asset minification for HTML, CSS, and JavaScript.
+//! Asset minification for HTML, CSS, and JavaScript, and lossless PNG optimization.
 
+use std::num::NonZeroU8;
+
+use crate::config::PngOptimization;
 use crate::error::{Error, Result};
 
+/// Zopfli iterations per PNG, oxipng's own default.
+const ZOPFLI_ITERATIONS: NonZeroU8 = NonZeroU8::new(15).unwrap();
+
 /// Minify HTML content.
 pub fn html(input: &str) -> Result<String> {
     let cfg = minify_html::Cfg {
@@ -64,6 +70,31 @@ pub fn js(input: &str) -> String {
         .code
 }
 
+/// Losslessly recompress a PNG and strip chunks that don't affect display.
+///
+/// Returns the original input if optimization is off or fails.
+pub fn png(input: &[u8], optimization: PngOptimization) -> Vec<u8> {
+    let mut options = oxipng::Options::from_preset(2);
+    options.strip = oxipng::StripChunks::Safe;
+    match optimization {
+        PngOptimization::Off => return input.to_vec(),
+        PngOptimization::Oxipng => {}
+        PngOptimization::Zopfli => {
+            options.deflate = oxipng::Deflaters::Zopfli {
+                iterations: ZOPFLI_ITERATIONS,
+            };
+        }
+    }
+
+    match oxipng::optimize_from_memory(input, &options) {
+        Ok(optimized) => optimized,
+        Err(e) => {
+            tracing::warn!(error = %e, "PNG optimization failed, using original");
+            input.to_vec()
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -112,4 +143,32 @@ mod tests {
         assert!(output.len() < input.len());
         assert!(output.contains("console"));
     }
+
+    #[test]
+    fn png_optimizes_losslessly_and_strips_text() {
+        let img = image::RgbaImage::from_fn(32, 32, |x, y| {
+            image::Rgba([x as u8 * 8, y as u8 * 8, 0, 255])
+        });
+        let mut input = Vec::new();
+        image::DynamicImage::ImageRgba8(img.clone())
+            .write_to(
+                &mut std::io::Cursor::new(&mut input),
+                image::ImageFormat::Png,
+            )
+            .unwrap();
+        // Uncompressed tEXt chunk before IEND
+        let text = b"tEXtComment\0made on my laptop";
+        let mut chunk = ((text.len() - 4) as u32).to_be_bytes().to_vec();
+        chunk.extend_from_slice(text);
+        chunk.extend_from_slice(&crc32fast::hash(text).to_be_bytes());
+        let iend = input.len() - 12;
+        input.splice(iend..iend, chunk);
+
+        let output = png(&input, PngOptimization::Oxipng);
+
+        assert!(output.len() < input.len());
+        assert!(!output.windows(6).any(|w| w == b"laptop"));
+        assert_eq!(image::load_from_memory(&output).unwrap().to_rgba8(), img);
+        assert_eq!(png(&input, PngOptimization::Off), input);
+    }
 }
@@ -0,0 +1,204 @@
+//! Originals hosted outside the site.
+//!
+//! Sites whose originals are already downloadable elsewhere, from a NAS or a
+//! storage bucket, can set `[originals]` so builds don't publish a second
+//! copy of each. Photos link to the URL the pattern gives for them instead,
+//! and a list of the originals with their paths, content hashes, and URLs is
+//! written to the output, for checking that the other host has each one.
+
+use std::fs;
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::config::OriginalsConfig;
+use crate::error::{Error, Result};
+use crate::photos::Photo;
+use crate::util::url_encode_path;
+
+/// Placeholder for an original's path in the photos directory.
+const PATH: &str = "{path}";
+
+/// Placeholder for an original's content hash.
+const HASH: &str = "{hash}";
+
+/// The list of originals written to the output.
+#[derive(Debug, Serialize)]
+struct Manifest<'a> {
+    /// The configured URL pattern
+    url: &'a str,
+    originals: Vec<Original>,
+}
+
+#[derive(Debug, Serialize)]
+struct Original {
+    /// Path in the photos directory
+    path: String,
+    /// First 8 characters of the BLAKE3 hash of the file
+    hash: String,
+    /// Size in bytes
+    size: u64,
+    url: String,
+}
+
+/// Check that the URL pattern is absolute and tells originals apart.
+pub fn validate(config: &OriginalsConfig) -> Result<()> {
+    if !config.url.starts_with("https://") && !config.url.starts_with("http://") {
+        return Err(Error::Other(format!(
+            "[originals] url '{}' must start with https:// or http://",
+            config.url
+        )));
+    }
+    if !config.url.contains(PATH) && !config.url.contains(HASH) {
+        return Err(Error::Other(format!(
+            "[originals] url '{}' needs {} or {} to tell originals apart",
+            config.url, PATH, HASH
+        )));
+    }
+    Ok(())
+}
+
+/// URL of the original of `photo`, a file in `photos_dir`.
+pub fn url(config: &OriginalsConfig, photos_dir: &Path, photo: &Photo) -> String {
+    config
+        .url
+        .replace(PATH, &url_encode_path(&relative_path(photos_dir, photo)))
+        .replace(HASH, &photo.hash)
+}
+
+/// Path of `photo` in `photos_dir`, with `/` separators.
+fn relative_path(photos_dir: &Path, photo: &Photo) -> String {
+    let path = photo
+        .source
+        .strip_prefix(photos_dir)
+        .unwrap_or(&photo.source);
+    path.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Write the list of `photos`' originals to `path`.
+pub fn write_manifest(
+    path: &Path,
+    config: &OriginalsConfig,
+    photos_dir: &Path,
+    photos: &[&Photo],
+) -> Result<()> {
+    let mut originals: Vec<Original> = photos
+        .iter()
+        .map(|photo| Original {
+            path: relative_path(photos_dir, photo),
+            hash: photo.hash.clone(),
+            size: photo.original_size,
+            url: url(config, photos_dir, photo),
+        })
+        .collect();
+    originals.sort_by(|a, b| a.path.cmp(&b.path));
+
+    let manifest = Manifest {
+        url: &config.url,
+        originals,
+    };
+    let json = serde_json::to_string_pretty(&manifest)
+        .map_err(|e| Error::Other(format!("failed to serialize originals: {}", e)))?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, json)?;
+    tracing::debug!(path = %path.display(), originals = manifest.originals.len(), "wrote originals");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use test_case::test_case;
+
+    fn config(url: &str) -> OriginalsConfig {
+        OriginalsConfig {
+            url: url.to_string(),
+            manifest: PathBuf::from("originals.json"),
+        }
+    }
+
+    fn photo(path: &str) -> Photo {
+        let mut photo = Photo::from_path(&Path::new("/site/photos").join(path)).unwrap();
+        photo.hash = "abcd1234".to_string();
+        photo
+    }
+
+    #[test_case("https://nas.example.com/photos/{path}", true ; "path")]
+    #[test_case("https://cdn.example.com/{hash}.jpg", true ; "hash")]
+    #[test_case("https://nas.example.com/photos/", false ; "no placeholder")]
+    #[test_case("/originals/{path}", false ; "not absolute")]
+    fn patterns_validated(url: &str, valid: bool) {
+        assert_eq!(validate(&config(url)).is_ok(), valid);
+    }
+
+    #[test_case("https://nas.example.com/photos/{path}", "japan/IMG_0001.jpg", "https://nas.example.com/photos/japan/IMG_0001.jpg" ; "path")]
+    #[test_case("https://nas.example.com/{path}?v={hash}", "2024 Trip/sunset.jpg", "https://nas.example.com/2024%20Trip/sunset.jpg?v=abcd1234" ; "encoded with hash")]
+    #[test_case("https://nas.example.com/{path}", "top.jpg", "https://nas.example.com/top.jpg" ; "top level")]
+    fn urls_filled_in(pattern: &str, path: &str, expected: &str) {
+        assert_eq!(
+            url(&config(pattern), Path::new("/site/photos"), &photo(path)),
+            expected
+        );
+    }
+
+    #[test]
+    fn manifest_written() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("originals.json");
+        let photos = [photo("b/second.jpg"), photo("a/first.jpg")];
+        let photos: Vec<_> = photos.iter().collect();
+
+        write_manifest(
+            &path,
+            &config("https://nas.example.com/{path}"),
+            Path::new("/site/photos"),
+            &photos,
+        )
+        .unwrap();
+
+        let manifest: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(manifest["url"], "https://nas.example.com/{path}");
+        assert_eq!(manifest["originals"][0]["path"], "a/first.jpg");
+        assert_eq!(manifest["originals"][0]["hash"], "abcd1234");
+        assert_eq!(
+            manifest["originals"][1]["url"],
+            "https://nas.example.com/b/second.jpg"
+        );
+    }
+
+    #[test]
+    fn originals_linked_not_published() {
+        let site_dir = tempfile::tempdir().unwrap();
+        crate::preview::generate(site_dir.path(), "basic").unwrap();
+        let config = site_dir.path().join("site.toml");
+        let mut toml = fs::read_to_string(&config).unwrap();
+        toml.push_str("\n[originals]\nurl = \"https://nas.example.com/{path}\"\n");
+        fs::write(&config, toml).unwrap();
+        let site = crate::config::Site::load(&config).unwrap();
+
+        let mut pipeline =
+            crate::pipeline::Pipeline::load(site_dir.path().to_path_buf(), site, false).unwrap();
+        pipeline.build().unwrap();
+
+        let output = site_dir.path().join("dist");
+        let copies = walkdir::WalkDir::new(output.join("images"))
+            .into_iter()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_name().to_string_lossy().contains("-original."))
+            .count();
+        assert_eq!(copies, 0);
+        let page = fs::read_to_string(output.join("landscape.html")).unwrap();
+        assert!(page.contains("https://nas.example.com/landscape.jpg"));
+        let manifest: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(output.join("originals.json")).unwrap())
+                .unwrap();
+        assert!(!manifest["originals"].as_array().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,306 @@
+//! An overview of a site's photos, for `galerie stats`.
+//!
+//! Reads each photo's metadata the way a build would, from the metadata cache
+//! while it's unchanged, without processing or writing anything, and sums up
+//! what would be published: photos per album, the size of the originals and
+//! of their images, cameras and lenses, the dates covered, and how many
+//! photos carry GPS coordinates.
+//!
+//! The output size is what the last build wrote for each photo. Photos it
+//! didn't process yet are estimated at the same ratio of output to source.
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+use crate::auto_album;
+use crate::cache::{self, MetadataCache};
+use crate::config::Site;
+use crate::error::Result;
+use crate::photos::{self, Album};
+use crate::processing;
+use crate::sizes;
+use crate::util::iso_datetime;
+
+/// Cameras and lenses listed, most used first.
+const TOP: usize = 5;
+
+/// Name shown for the photos directly in the photos directory.
+const TOP_LEVEL: &str = "(top level)";
+
+/// What a site's photos add up to.
+#[derive(Debug, Default, PartialEq)]
+pub struct Overview {
+    /// Photos in each album, by path, the top level first
+    pub albums: Vec<(String, usize)>,
+    pub photos: usize,
+    /// Photos whose files couldn't be read
+    pub unreadable: usize,
+    /// Size of the originals
+    pub source_size: u64,
+    /// Size of the images the last build wrote
+    pub output_size: u64,
+    /// Photos the last build wrote no images for
+    pub unbuilt: usize,
+    /// Size of the originals of those photos
+    pub unbuilt_source_size: u64,
+    /// Earliest and latest capture dates (e.g., "2024-06-02")
+    pub dates: Option<(String, String)>,
+    /// Camera models with their photo counts, most used first
+    pub cameras: Vec<(String, usize)>,
+    /// Lenses with their photo counts, most used first
+    pub lenses: Vec<(String, usize)>,
+    /// Photos with GPS coordinates in the file
+    pub with_gps: usize,
+}
+
+impl Overview {
+    /// Output size once every photo is processed, estimated from the photos
+    /// already processed, or None if none are.
+    pub fn expected_output_size(&self) -> Option<u64> {
+        let built_source = self.source_size - self.unbuilt_source_size;
+        if self.unbuilt == 0 {
+            return Some(self.output_size);
+        }
+        if built_source == 0 {
+            return None;
+        }
+        let ratio = self.output_size as f64 / built_source as f64;
+        Some(self.output_size + (self.unbuilt_source_size as f64 * ratio) as u64)
+    }
+}
+
+/// Sum up the photos of the site in `site_dir`.
+pub fn gather(site_dir: &Path, site: &Site) -> Result<Overview> {
+    let mut root = photos::discover(&site_dir.join(&site.photos))?;
+    if site.flatten {
+        root = root.flattened();
+    }
+    let cache = MetadataCache::load(cache::path(site_dir));
+    if let Some(mode) = site.auto_album {
+        auto_album::group(&mut root, mode, &cache);
+    }
+
+    let images_dir = site_dir.join(&site.build).join("images");
+    let mut overview = Overview::default();
+    let mut tally = Tally::default();
+    add_album(&root, &images_dir, &cache, &mut overview, &mut tally);
+    tally.dates.sort();
+
+    overview.dates = tally
+        .dates
+        .first()
+        .cloned()
+        .zip(tally.dates.last().cloned());
+    overview.cameras = most_used(tally.cameras);
+    overview.lenses = most_used(tally.lenses);
+    Ok(overview)
+}
+
+/// Counts collected across albums.
+#[derive(Default)]
+struct Tally {
+    dates: Vec<String>,
+    cameras: HashMap<String, usize>,
+    lenses: HashMap<String, usize>,
+}
+
+fn add_album(
+    album: &Album,
+    images_dir: &Path,
+    cache: &MetadataCache,
+    overview: &mut Overview,
+    tally: &mut Tally,
+) {
+    if !album.photos.is_empty() || album.path.as_os_str().is_empty() {
+        let name = album.path.display().to_string();
+        overview.albums.push((name, album.photos.len()));
+    }
+
+    // Images are named for their photo: `{stem}-{hash}-{variant}.{format}`
+    let outputs: Vec<(String, u64)> = fs::read_dir(images_dir.join(&album.path))
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let size = entry.metadata().ok().filter(|m| m.is_file())?.len();
+            Some((entry.file_name().to_string_lossy().into_owned(), size))
+        })
+        .collect();
+
+    for photo in &album.photos {
+        overview.photos += 1;
+        let source = match processing::source(photo, cache) {
+            Ok(source) => source,
+            Err(e) => {
+                tracing::warn!(photo = %photo.source.display(), error = %e, "failed to read photo");
+                overview.unreadable += 1;
+                continue;
+            }
+        };
+        overview.source_size += source.stamp.0;
+
+        let prefix = format!("{}-{}-", photo.stem, source.hash);
+        let output: u64 = outputs
+            .iter()
+            .filter(|(name, _)| name.starts_with(&prefix))
+            .map(|(_, size)| size)
+            .sum();
+        if output == 0 {
+            overview.unbuilt += 1;
+            overview.unbuilt_source_size += source.stamp.0;
+        }
+        overview.output_size += output;
+
+        if let Some(date) = processing::capture_date(&source, &photo.source) {
+            let date = iso_datetime(&date);
+            tally
+                .dates
+                .push(date.split('T').next().unwrap_or(&date).to_string());
+        }
+        if let Some(exif) = &source.exif {
+            if let Some(camera) = processing::camera_name(exif) {
+                *tally.cameras.entry(camera).or_default() += 1;
+            }
+            if let Some(lens) = &exif.lens {
+                *tally.lenses.entry(lens.clone()).or_default() += 1;
+            }
+        }
+        let exif_gps = source.exif.as_ref().and_then(|exif| exif.gps);
+        let xmp_gps = source.xmp.as_ref().and_then(|xmp| xmp.gps);
+        if exif_gps.or(xmp_gps).is_some() {
+            overview.with_gps += 1;
+        }
+    }
+
+    for child in &album.children {
+        add_album(child, images_dir, cache, overview, tally);
+    }
+}
+
+/// `counts` with the most used first, ties by name.
+fn most_used(counts: HashMap<String, usize>) -> Vec<(String, usize)> {
+    let mut counts: Vec<_> = counts.into_iter().collect();
+    counts.sort_by(|a, b| (Reverse(a.1), &a.0).cmp(&(Reverse(b.1), &b.0)));
+    counts
+}
+
+/// The overview as text.
+pub fn render(overview: &Overview) -> String {
+    let mut out = String::from("Albums\n");
+    let width = overview
+        .albums
+        .iter()
+        .map(|(name, _)| name.len())
+        .max()
+        .unwrap_or(0)
+        .max(TOP_LEVEL.len());
+    for (name, count) in &overview.albums {
+        let name = if name.is_empty() { TOP_LEVEL } else { name };
+        let _ = writeln!(out, "  {:<width$}  {:>5}", name, count, width = width);
+    }
+
+    let mut photos = overview.photos.to_string();
+    if overview.unreadable > 0 {
+        let _ = write!(photos, " ({} unreadable)", overview.unreadable);
+    }
+    let dates = match &overview.dates {
+        Some((first, last)) if first == last => first.clone(),
+        Some((first, last)) => format!("{} to {}", first, last),
+        None => "none recorded".to_string(),
+    };
+    let output = match (overview.unbuilt, overview.expected_output_size()) {
+        (0, _) => sizes::size(overview.output_size),
+        (unbuilt, Some(expected)) => format!(
+            "about {} once {} unprocessed photo(s) are built ({} so far)",
+            sizes::size(expected),
+            unbuilt,
+            sizes::size(overview.output_size)
+        ),
+        (_, None) => "unknown until the site is built".to_string(),
+    };
+    let gps = format!(
+        "{} of {} photos carry coordinates",
+        overview.with_gps, overview.photos
+    );
+
+    let _ = writeln!(out);
+    for (label, value) in [
+        ("Photos", photos),
+        ("Dates", dates),
+        ("Source", sizes::size(overview.source_size)),
+        ("Output", output),
+        ("GPS", gps),
+        ("Cameras", counts(&overview.cameras)),
+        ("Lenses", counts(&overview.lenses)),
+    ] {
+        let _ = writeln!(out, "{:<9}{}", label, value);
+    }
+    out
+}
+
+/// The most used names with their counts, or "none".
+fn counts(counts: &[(String, usize)]) -> String {
+    if counts.is_empty() {
+        return "none recorded".to_string();
+    }
+    let mut listed: Vec<String> = counts
+        .iter()
+        .take(TOP)
+        .map(|(name, count)| format!("{} ({})", name, count))
+        .collect();
+    if counts.len() > TOP {
+        listed.push(format!("and {} more", counts.len() - TOP));
+    }
+    listed.join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    fn overview(unbuilt: usize, unbuilt_source_size: u64, output_size: u64) -> Overview {
+        Overview {
+            photos: 4,
+            source_size: 4000,
+            output_size,
+            unbuilt,
+            unbuilt_source_size,
+            ..Overview::default()
+        }
+    }
+
+    #[test_case(overview(0, 0, 1000), Some(1000) ; "all built")]
+    #[test_case(overview(1, 1000, 1500), Some(2000) ; "estimated from the rest")]
+    #[test_case(overview(4, 4000, 0), None ; "none built")]
+    fn expected_output(overview: Overview, expected: Option<u64>) {
+        assert_eq!(overview.expected_output_size(), expected);
+    }
+
+    #[test]
+    fn site_summed_up() {
+        let site_dir = tempfile::tempdir().unwrap();
+        let site = crate::preview::generate(site_dir.path(), "basic").unwrap();
+
+        let before = gather(site_dir.path(), &site).unwrap();
+        assert!(before.photos > 0);
+        assert_eq!(before.unbuilt, before.photos);
+        assert!(before.expected_output_size().is_none());
+        assert!(before.albums.iter().any(|(name, _)| name.is_empty()));
+        assert!(before.dates.is_some());
+
+        let mut pipeline =
+            crate::pipeline::Pipeline::load(site_dir.path().to_path_buf(), site, false).unwrap();
+        pipeline.build().unwrap();
+        let site = Site::load(&site_dir.path().join("site.toml")).unwrap();
+        let after = gather(site_dir.path(), &site).unwrap();
+
+        assert_eq!(after.unbuilt, 0);
+        assert!(after.output_size > 0);
+        assert_eq!(after.photos, before.photos);
+        assert!(render(&after).contains(&format!("Photos   {}", after.photos)));
+    }
+}
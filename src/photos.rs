@@ -1,12 +1,31 @@
+use std::borrow::Cow;
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 use serde::Serialize;
 
-use crate::config::GpsMode;
+use crate::album_config::{self, AlbumConfig};
+use crate::config::{AspectRatio, FilterConfig, MetadataConfig, MetadataField, PhotoSort};
+use crate::countries::{Registry, flag};
 use crate::error::{Error, Result};
-use crate::util::{url_encode, url_encode_path};
+use crate::exif::ExifParser;
+use crate::geocode::Place;
+use crate::stats::{self, AlbumStats};
+use crate::util::{iso_datetime, url_encode, url_encode_path};
+use crate::xmp::Pick;
 
-const IMAGE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "webp", "gif"];
+const IMAGE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "webp", "gif", "tif", "tiff"];
+
+/// Camera RAW formats, processed from their embedded JPEG preview.
+const RAW_EXTENSIONS: &[&str] = &["cr3", "nef", "arw", "dng"];
+
+/// HEIC/HEIF, as iPhones save photos, decoded with the `heif` feature.
+const HEIF_EXTENSIONS: &[&str] = &["heic", "heif"];
+
+/// Video clips, shown with a poster frame extracted by ffmpeg.
+const VIDEO_EXTENSIONS: &[&str] = &["mp4", "mov"];
 
 /// A single photo in the gallery.
 #[derive(Debug, Clone, Serialize)]
@@ -21,6 +40,9 @@ pub struct Photo {
     /// File extension without dot (e.g., "jpg")
     pub extension: String,
 
+    /// Whether this is a still photo or a video clip
+    pub media_type: MediaType,
+
     /// Content hash (first 8 chars of BLAKE3 hex) for cache-busting
     pub hash: String,
 
@@ -30,11 +52,123 @@ pub struct Photo {
     /// Original image height in pixels
     pub height: u32,
 
+    /// Whether this is an animated GIF or WebP, set by processing
+    pub animated: bool,
+
+    /// Whether the variants are lossless WebP, set by processing
+    pub lossless: bool,
+
     /// Original file size in bytes
     pub original_size: u64,
 
     /// EXIF metadata extracted from the image
     pub metadata: PhotoMetadata,
+
+    /// Tiny WebP preview as a data URI, if placeholders are enabled
+    pub placeholder: Option<String>,
+
+    /// Dominant color as a CSS hex color (e.g., "#3a6ea5"), set by processing
+    pub color: Option<String>,
+
+    /// Names of the generated variants, set by processing
+    #[serde(skip)]
+    pub variants: Arc<VariantNames>,
+}
+
+/// Kind of media in an album.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MediaType {
+    #[default]
+    Photo,
+    /// Video clip; its images are made from a poster frame
+    Video,
+}
+
+/// An image generated from each photo.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Variant {
+    Micro,
+    Thumb,
+    /// Thumbnail at twice the size, for high-DPI displays
+    Thumb2x,
+    Full,
+    ThumbSaver,
+    FullSaver,
+    AvifThumb,
+    AvifFull,
+    /// Responsive image resized to this width
+    Width(u32),
+    /// Album cover cropped to this aspect ratio
+    Crop(AspectRatio),
+}
+
+impl Variant {
+    /// File extension of the variant.
+    pub fn format(self) -> &'static str {
+        match self {
+            Variant::AvifThumb | Variant::AvifFull => "avif",
+            _ => "webp",
+        }
+    }
+}
+
+/// Names of generated variants in filenames (`{stem}-{hash}-{name}.{format}`).
+///
+/// A variant with non-default sizes or quality has them in its name, so
+/// changing the settings generates new files instead of reusing old ones.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VariantNames {
+    pub micro: String,
+    pub thumb: String,
+    pub thumb_2x: String,
+    pub full: String,
+    pub thumb_saver: String,
+    pub full_saver: String,
+    pub avif_thumb: String,
+    pub avif_full: String,
+    /// Appended to `w{width}` for responsive widths
+    pub width_suffix: String,
+    /// Appended to `crop{width}x{height}` for cover crops
+    pub crop_suffix: String,
+}
+
+impl VariantNames {
+    /// Name of a variant.
+    pub fn get(&self, variant: Variant) -> Cow<'_, str> {
+        match variant {
+            Variant::Micro => Cow::Borrowed(&self.micro),
+            Variant::Thumb => Cow::Borrowed(&self.thumb),
+            Variant::Thumb2x => Cow::Borrowed(&self.thumb_2x),
+            Variant::Full => Cow::Borrowed(&self.full),
+            Variant::ThumbSaver => Cow::Borrowed(&self.thumb_saver),
+            Variant::FullSaver => Cow::Borrowed(&self.full_saver),
+            Variant::AvifThumb => Cow::Borrowed(&self.avif_thumb),
+            Variant::AvifFull => Cow::Borrowed(&self.avif_full),
+            Variant::Width(width) => Cow::Owned(format!("w{}{}", width, self.width_suffix)),
+            Variant::Crop(ratio) => Cow::Owned(format!(
+                "crop{}x{}{}",
+                ratio.width, ratio.height, self.crop_suffix
+            )),
+        }
+    }
+}
+
+impl Default for VariantNames {
+    fn default() -> Self {
+        Self {
+            micro: "micro".to_string(),
+            thumb: "thumb".to_string(),
+            thumb_2x: "thumb@2x".to_string(),
+            full: "full".to_string(),
+            thumb_saver: "thumb-saver".to_string(),
+            full_saver: "full-saver".to_string(),
+            avif_thumb: "thumb".to_string(),
+            avif_full: "full".to_string(),
+            width_suffix: String::new(),
+            crop_suffix: String::new(),
+        }
+    }
 }
 
 /// EXIF metadata extracted from a photo.
@@ -59,12 +193,119 @@ pub struct PhotoMetadata {
     pub exposure: Option<ExposureInfo>,
 
     /// Star rating (0-5, from XMP metadata)
-    /// TODO: Implement XMP parsing for rating extraction
     pub rating: Option<u8>,
+
+    /// Pick flag (from XMP metadata)
+    pub pick: Option<Pick>,
+
+    /// Color label (from XMP metadata)
+    pub label: Option<String>,
+
+    /// Title (from XMP or IPTC metadata)
+    pub title: Option<String>,
+
+    /// Caption or description (from XMP or IPTC metadata)
+    pub description: Option<String>,
+
+    /// Keywords (from XMP or IPTC metadata)
+    pub keywords: Vec<String>,
+
+    /// Photographer (XMP dc:creator or IPTC By-line)
+    pub creator: Option<String>,
+
+    /// Location named in IPTC metadata, independent of GPS
+    pub location: Option<Location>,
+
+    /// Parser that read the EXIF data (None if no EXIF was found)
+    pub exif_parser: Option<ExifParser>,
+}
+
+impl PhotoMetadata {
+    /// Whether a photo with this metadata passes `filter`.
+    pub fn passes(&self, filter: &FilterConfig) -> bool {
+        if self.pick == Some(Pick::Rejected) {
+            return false;
+        }
+        if filter
+            .min_rating
+            .is_some_and(|min| self.rating.unwrap_or(0) < min)
+        {
+            return false;
+        }
+        if filter.picks && self.pick != Some(Pick::Picked) {
+            return false;
+        }
+        filter.labels.is_empty()
+            || self.label.as_ref().is_some_and(|label| {
+                filter
+                    .labels
+                    .iter()
+                    .any(|wanted| wanted.eq_ignore_ascii_case(label))
+            })
+    }
+
+    /// Clear fields that the site doesn't publish.
+    pub fn restrict(&mut self, config: &MetadataConfig) {
+        let hide = |field| !config.publishes(field);
+
+        if hide(MetadataField::Date) {
+            self.date_taken = None;
+        }
+        if hide(MetadataField::Copyright) {
+            self.copyright = None;
+        }
+        if hide(MetadataField::Camera) {
+            self.camera = None;
+        }
+        if hide(MetadataField::Lens) {
+            self.lens = None;
+        }
+        if hide(MetadataField::Gps) {
+            self.gps = None;
+        }
+        if hide(MetadataField::Exposure) {
+            self.exposure = None;
+        }
+        if hide(MetadataField::Rating) {
+            self.rating = None;
+            self.pick = None;
+            self.label = None;
+        }
+        if hide(MetadataField::Title) {
+            self.title = None;
+        }
+        if hide(MetadataField::Description) {
+            self.description = None;
+        }
+        if hide(MetadataField::Keywords) {
+            self.keywords.clear();
+        }
+        if hide(MetadataField::Creator) {
+            self.creator = None;
+        }
+        if hide(MetadataField::Location) {
+            self.location = None;
+        }
+    }
+}
+
+/// Location written into the photo's IPTC metadata by the photographer.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Location {
+    /// Sub-location such as a landmark or neighbourhood (only with gps = "on")
+    pub sublocation: Option<String>,
+    /// City name
+    pub city: Option<String>,
+    /// State, province, or region
+    pub region: Option<String>,
+    /// Country name
+    pub country: Option<String>,
+    /// Country code as written (usually ISO 3166-1 alpha-3, e.g., "ISL")
+    pub country_code: Option<String>,
 }
 
 /// GPS coordinates and reverse-geocoded location from EXIF data.
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct GpsCoords {
     /// Latitude in decimal degrees (None if coordinates are hidden for privacy)
     pub latitude: Option<f64>,
@@ -82,11 +323,15 @@ pub struct GpsCoords {
     pub country_code: Option<String>,
     /// Country flag emoji (e.g., "🇺🇸", "🇯🇵")
     pub flag: Option<String>,
+    /// Altitude in meters, negative below sea level (None if hidden or not recorded)
+    pub altitude: Option<f64>,
+    /// Direction the camera faced in degrees from north (None if hidden or not recorded)
+    pub direction: Option<f64>,
 }
 
 impl GpsCoords {
     /// Create GPS coords with full coordinate information (for gps = "on" mode).
-    pub fn new(latitude: f64, longitude: f64) -> Self {
+    pub fn new(latitude: f64, longitude: f64, place: &Place, countries: &Registry) -> Self {
         let lat_dir = if latitude >= 0.0 { 'N' } else { 'S' };
         let lon_dir = if longitude >= 0.0 { 'E' } else { 'W' };
         let display = format!(
@@ -97,314 +342,68 @@ impl GpsCoords {
             lon_dir
         );
 
-        // Reverse geocode to get location info
-        let geocoder = reverse_geocoder::ReverseGeocoder::new();
-        let result = geocoder.search((latitude, longitude));
-
-        let cc = &result.record.cc;
-        let flag_emoji = country_code_to_flag(cc);
-        let country_name = country_code_to_name(cc);
-
-        let city = Some(result.record.name.to_string());
-        let region = if result.record.admin1.is_empty() {
-            None
-        } else {
-            Some(result.record.admin1.to_string())
-        };
-        let country = country_name.map(|s| s.to_string());
-        let country_code = Some(cc.to_string());
-        let flag = Some(flag_emoji);
-
         Self {
             latitude: Some(latitude),
             longitude: Some(longitude),
             display: Some(display),
-            city,
-            region,
-            country,
-            country_code,
-            flag,
+            ..Self::new_general(place, countries)
         }
     }
 
     /// Create GPS coords with only general location info (for gps = "general" mode).
     ///
-    /// Performs reverse geocoding but omits precise coordinates.
     /// The coordinate fields are None to indicate they should not be shown.
-    pub fn new_general(latitude: f64, longitude: f64) -> Self {
-        // Reverse geocode to get location info
-        let geocoder = reverse_geocoder::ReverseGeocoder::new();
-        let result = geocoder.search((latitude, longitude));
-
-        let cc = &result.record.cc;
-        let flag_emoji = country_code_to_flag(cc);
-        let country_name = country_code_to_name(cc);
-
-        let city = Some(result.record.name.to_string());
-        let region = if result.record.admin1.is_empty() {
-            None
-        } else {
-            Some(result.record.admin1.to_string())
-        };
-        let country = country_name.map(|s| s.to_string());
-        let country_code = Some(cc.to_string());
-        let flag = Some(flag_emoji);
+    pub fn new_general(place: &Place, countries: &Registry) -> Self {
+        let cc = &place.country_code;
+        let country = countries.get(cc);
 
         Self {
             latitude: None,
             longitude: None,
             display: None,
-            city,
-            region,
-            country,
-            country_code,
-            flag,
+            city: Some(place.city.clone()),
+            region: place
+                .region
+                .as_deref()
+                .map(|r| country.map_or_else(|| r.to_string(), |c| c.region(r))),
+            country: country.map(|c| c.name.clone()),
+            country_code: Some(cc.clone()),
+            flag: Some(flag(cc)),
+            altitude: None,
+            direction: None,
         }
     }
-}
 
-/// Convert ISO 3166-1 alpha-2 country code to flag emoji.
-/// Each letter is converted to a regional indicator symbol.
-fn country_code_to_flag(cc: &str) -> String {
-    cc.chars()
-        .filter_map(|c| {
-            let c = c.to_ascii_uppercase();
-            if c.is_ascii_uppercase() {
-                // Regional indicator symbols start at U+1F1E6 for 'A'
-                let offset = c as u32 - 'A' as u32;
-                char::from_u32(0x1F1E6 + offset)
-            } else {
-                None
-            }
-        })
-        .collect()
-}
+    /// Create GPS coords from a place name without coordinates (for manual locations).
+    ///
+    /// `country` may be an ISO 3166-1 alpha-2 code, a country name, or an alias.
+    pub fn named(
+        city: &str,
+        region: Option<&str>,
+        country: Option<&str>,
+        countries: &Registry,
+    ) -> Self {
+        let known = country.and_then(|c| countries.find(c));
 
-/// Convert ISO 3166-1 alpha-2 country code to country name.
-fn country_code_to_name(cc: &str) -> Option<&'static str> {
-    match cc {
-        "AD" => Some("Andorra"),
-        "AE" => Some("United Arab Emirates"),
-        "AF" => Some("Afghanistan"),
-        "AG" => Some("Antigua and Barbuda"),
-        "AI" => Some("Anguilla"),
-        "AL" => Some("Albania"),
-        "AM" => Some("Armenia"),
-        "AO" => Some("Angola"),
-        "AQ" => Some("Antarctica"),
-        "AR" => Some("Argentina"),
-        "AS" => Some("American Samoa"),
-        "AT" => Some("Austria"),
-        "AU" => Some("Australia"),
-        "AW" => Some("Aruba"),
-        "AZ" => Some("Azerbaijan"),
-        "BA" => Some("Bosnia and Herzegovina"),
-        "BB" => Some("Barbados"),
-        "BD" => Some("Bangladesh"),
-        "BE" => Some("Belgium"),
-        "BF" => Some("Burkina Faso"),
-        "BG" => Some("Bulgaria"),
-        "BH" => Some("Bahrain"),
-        "BI" => Some("Burundi"),
-        "BJ" => Some("Benin"),
-        "BM" => Some("Bermuda"),
-        "BN" => Some("Brunei"),
-        "BO" => Some("Bolivia"),
-        "BR" => Some("Brazil"),
-        "BS" => Some("Bahamas"),
-        "BT" => Some("Bhutan"),
-        "BW" => Some("Botswana"),
-        "BY" => Some("Belarus"),
-        "BZ" => Some("Belize"),
-        "CA" => Some("Canada"),
-        "CD" => Some("DR Congo"),
-        "CF" => Some("Central African Republic"),
-        "CG" => Some("Congo"),
-        "CH" => Some("Switzerland"),
-        "CI" => Some("Ivory Coast"),
-        "CL" => Some("Chile"),
-        "CM" => Some("Cameroon"),
-        "CN" => Some("China"),
-        "CO" => Some("Colombia"),
-        "CR" => Some("Costa Rica"),
-        "CU" => Some("Cuba"),
-        "CV" => Some("Cape Verde"),
-        "CY" => Some("Cyprus"),
-        "CZ" => Some("Czechia"),
-        "DE" => Some("Germany"),
-        "DJ" => Some("Djibouti"),
-        "DK" => Some("Denmark"),
-        "DM" => Some("Dominica"),
-        "DO" => Some("Dominican Republic"),
-        "DZ" => Some("Algeria"),
-        "EC" => Some("Ecuador"),
-        "EE" => Some("Estonia"),
-        "EG" => Some("Egypt"),
-        "ER" => Some("Eritrea"),
-        "ES" => Some("Spain"),
-        "ET" => Some("Ethiopia"),
-        "FI" => Some("Finland"),
-        "FJ" => Some("Fiji"),
-        "FK" => Some("Falkland Islands"),
-        "FM" => Some("Micronesia"),
-        "FO" => Some("Faroe Islands"),
-        "FR" => Some("France"),
-        "GA" => Some("Gabon"),
-        "GB" => Some("United Kingdom"),
-        "GD" => Some("Grenada"),
-        "GE" => Some("Georgia"),
-        "GH" => Some("Ghana"),
-        "GI" => Some("Gibraltar"),
-        "GL" => Some("Greenland"),
-        "GM" => Some("Gambia"),
-        "GN" => Some("Guinea"),
-        "GQ" => Some("Equatorial Guinea"),
-        "GR" => Some("Greece"),
-        "GT" => Some("Guatemala"),
-        "GU" => Some("Guam"),
-        "GW" => Some("Guinea-Bissau"),
-        "GY" => Some("Guyana"),
-        "HK" => Some("Hong Kong"),
-        "HN" => Some("Honduras"),
-        "HR" => Some("Croatia"),
-        "HT" => Some("Haiti"),
-        "HU" => Some("Hungary"),
-        "ID" => Some("Indonesia"),
-        "IE" => Some("Ireland"),
-        "IL" => Some("Israel"),
-        "IN" => Some("India"),
-        "IQ" => Some("Iraq"),
-        "IR" => Some("Iran"),
-        "IS" => Some("Iceland"),
-        "IT" => Some("Italy"),
-        "JM" => Some("Jamaica"),
-        "JO" => Some("Jordan"),
-        "JP" => Some("Japan"),
-        "KE" => Some("Kenya"),
-        "KG" => Some("Kyrgyzstan"),
-        "KH" => Some("Cambodia"),
-        "KI" => Some("Kiribati"),
-        "KM" => Some("Comoros"),
-        "KN" => Some("Saint Kitts and Nevis"),
-        "KP" => Some("North Korea"),
-        "KR" => Some("South Korea"),
-        "KW" => Some("Kuwait"),
-        "KY" => Some("Cayman Islands"),
-        "KZ" => Some("Kazakhstan"),
-        "LA" => Some("Laos"),
-        "LB" => Some("Lebanon"),
-        "LC" => Some("Saint Lucia"),
-        "LI" => Some("Liechtenstein"),
-        "LK" => Some("Sri Lanka"),
-        "LR" => Some("Liberia"),
-        "LS" => Some("Lesotho"),
-        "LT" => Some("Lithuania"),
-        "LU" => Some("Luxembourg"),
-        "LV" => Some("Latvia"),
-        "LY" => Some("Libya"),
-        "MA" => Some("Morocco"),
-        "MC" => Some("Monaco"),
-        "MD" => Some("Moldova"),
-        "ME" => Some("Montenegro"),
-        "MG" => Some("Madagascar"),
-        "MH" => Some("Marshall Islands"),
-        "MK" => Some("North Macedonia"),
-        "ML" => Some("Mali"),
-        "MM" => Some("Myanmar"),
-        "MN" => Some("Mongolia"),
-        "MO" => Some("Macau"),
-        "MR" => Some("Mauritania"),
-        "MT" => Some("Malta"),
-        "MU" => Some("Mauritius"),
-        "MV" => Some("Maldives"),
-        "MW" => Some("Malawi"),
-        "MX" => Some("Mexico"),
-        "MY" => Some("Malaysia"),
-        "MZ" => Some("Mozambique"),
-        "NA" => Some("Namibia"),
-        "NC" => Some("New Caledonia"),
-        "NE" => Some("Niger"),
-        "NG" => Some("Nigeria"),
-        "NI" => Some("Nicaragua"),
-        "NL" => Some("Netherlands"),
-        "NO" => Some("Norway"),
-        "NP" => Some("Nepal"),
-        "NR" => Some("Nauru"),
-        "NZ" => Some("New Zealand"),
-        "OM" => Some("Oman"),
-        "PA" => Some("Panama"),
-        "PE" => Some("Peru"),
-        "PF" => Some("French Polynesia"),
-        "PG" => Some("Papua New Guinea"),
-        "PH" => Some("Philippines"),
-        "PK" => Some("Pakistan"),
-        "PL" => Some("Poland"),
-        "PR" => Some("Puerto Rico"),
-        "PS" => Some("Palestine"),
-        "PT" => Some("Portugal"),
-        "PW" => Some("Palau"),
-        "PY" => Some("Paraguay"),
-        "QA" => Some("Qatar"),
-        "RO" => Some("Romania"),
-        "RS" => Some("Serbia"),
-        "RU" => Some("Russia"),
-        "RW" => Some("Rwanda"),
-        "SA" => Some("Saudi Arabia"),
-        "SB" => Some("Solomon Islands"),
-        "SC" => Some("Seychelles"),
-        "SD" => Some("Sudan"),
-        "SE" => Some("Sweden"),
-        "SG" => Some("Singapore"),
-        "SI" => Some("Slovenia"),
-        "SK" => Some("Slovakia"),
-        "SL" => Some("Sierra Leone"),
-        "SM" => Some("San Marino"),
-        "SN" => Some("Senegal"),
-        "SO" => Some("Somalia"),
-        "SR" => Some("Suriname"),
-        "SS" => Some("South Sudan"),
-        "ST" => Some("Sao Tome and Principe"),
-        "SV" => Some("El Salvador"),
-        "SY" => Some("Syria"),
-        "SZ" => Some("Eswatini"),
-        "TC" => Some("Turks and Caicos"),
-        "TD" => Some("Chad"),
-        "TG" => Some("Togo"),
-        "TH" => Some("Thailand"),
-        "TJ" => Some("Tajikistan"),
-        "TL" => Some("Timor-Leste"),
-        "TM" => Some("Turkmenistan"),
-        "TN" => Some("Tunisia"),
-        "TO" => Some("Tonga"),
-        "TR" => Some("Turkey"),
-        "TT" => Some("Trinidad and Tobago"),
-        "TV" => Some("Tuvalu"),
-        "TW" => Some("Taiwan"),
-        "TZ" => Some("Tanzania"),
-        "UA" => Some("Ukraine"),
-        "UG" => Some("Uganda"),
-        "US" => Some("United States"),
-        "UY" => Some("Uruguay"),
-        "UZ" => Some("Uzbekistan"),
-        "VA" => Some("Vatican City"),
-        "VC" => Some("Saint Vincent and the Grenadines"),
-        "VE" => Some("Venezuela"),
-        "VG" => Some("British Virgin Islands"),
-        "VI" => Some("U.S. Virgin Islands"),
-        "VN" => Some("Vietnam"),
-        "VU" => Some("Vanuatu"),
-        "WS" => Some("Samoa"),
-        "XK" => Some("Kosovo"),
-        "YE" => Some("Yemen"),
-        "ZA" => Some("South Africa"),
-        "ZM" => Some("Zambia"),
-        "ZW" => Some("Zimbabwe"),
-        _ => None,
+        Self {
+            latitude: None,
+            longitude: None,
+            display: None,
+            city: Some(city.to_string()),
+            region: region.map(|r| known.map_or_else(|| r.to_string(), |c| c.region(r))),
+            country: known
+                .map(|c| c.name.clone())
+                .or_else(|| country.map(str::to_string)),
+            country_code: known.map(|c| c.code.clone()),
+            flag: known.map(|c| c.flag.clone()),
+            altitude: None,
+            direction: None,
+        }
     }
 }
 
 /// Camera exposure settings from EXIF data.
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct ExposureInfo {
     /// Aperture (e.g., "f/2.8")
     pub aperture: Option<String>,
@@ -423,93 +422,193 @@ pub struct ExposureInfo {
 }
 
 impl Photo {
+    /// Whether this is a camera RAW file. Their originals aren't published.
+    pub fn is_raw(&self) -> bool {
+        RAW_EXTENSIONS.contains(&self.extension.as_str())
+    }
+
+    /// Whether this is a HEIC/HEIF file. Their originals are published as JPEG.
+    pub fn is_heif(&self) -> bool {
+        HEIF_EXTENSIONS.contains(&self.extension.as_str())
+    }
+
+    /// Whether this is a video clip. Its images are made from a poster frame.
+    pub fn is_video(&self) -> bool {
+        self.media_type == MediaType::Video
+    }
+
+    /// File extension of the published original.
+    pub fn original_extension(&self) -> &str {
+        if self.is_heif() {
+            "jpg"
+        } else {
+            &self.extension
+        }
+    }
+
     pub(crate) fn from_path(path: &Path) -> Option<Self> {
         let extension = path.extension()?.to_str()?.to_lowercase();
 
-        if !IMAGE_EXTENSIONS.contains(&extension.as_str()) {
+        let media_type = if VIDEO_EXTENSIONS.contains(&extension.as_str()) {
+            MediaType::Video
+        } else if IMAGE_EXTENSIONS.contains(&extension.as_str())
+            || RAW_EXTENSIONS.contains(&extension.as_str())
+            || HEIF_EXTENSIONS.contains(&extension.as_str())
+        {
+            MediaType::Photo
+        } else {
             return None;
-        }
+        };
 
         let stem = path.file_stem()?.to_str()?.to_string();
 
         Some(Self {
             source: path.to_path_buf(),
             extension,
+            media_type,
+            animated: false,
+            lossless: false,
             stem,
             hash: String::new(),
             width: 0,
             height: 0,
             original_size: 0,
             metadata: PhotoMetadata::default(),
+            placeholder: None,
+            color: None,
+            variants: Arc::default(),
         })
     }
 
+    /// A processed 6000×4000 photo at `source` with `metadata`, for tests.
+    #[cfg(test)]
+    pub(crate) fn processed(source: &str, metadata: PhotoMetadata) -> Self {
+        Self {
+            hash: "abc12345".to_string(),
+            width: 6000,
+            height: 4000,
+            original_size: 1024,
+            metadata,
+            ..Self::from_path(Path::new(source)).expect("test photos have a photo extension")
+        }
+    }
+
     /// URL path to the full-size WebP image (e.g., "images/album/photo-abc123-full.webp")
     pub fn image_path(&self, album_path: &Path) -> String {
-        let encoded_stem = url_encode(&self.stem);
-        if album_path.as_os_str().is_empty() {
-            format!("images/{}-{}-full.webp", encoded_stem, self.hash)
-        } else {
-            let encoded_album = url_encode_path(&album_path.display().to_string());
-            format!(
-                "images/{}/{}-{}-full.webp",
-                encoded_album, encoded_stem, self.hash
-            )
-        }
+        self.variant_path(album_path, Variant::Full)
     }
 
     /// URL path to the thumbnail WebP (e.g., "images/album/photo-abc123-thumb.webp")
     pub fn thumb_path(&self, album_path: &Path) -> String {
-        let encoded_stem = url_encode(&self.stem);
-        if album_path.as_os_str().is_empty() {
-            format!("images/{}-{}-thumb.webp", encoded_stem, self.hash)
-        } else {
-            let encoded_album = url_encode_path(&album_path.display().to_string());
-            format!(
-                "images/{}/{}-{}-thumb.webp",
-                encoded_album, encoded_stem, self.hash
-            )
-        }
+        self.variant_path(album_path, Variant::Thumb)
+    }
+
+    /// URL path to the double-size thumbnail WebP (e.g., "images/album/photo-abc123-thumb@2x.webp")
+    pub fn thumb_2x_path(&self, album_path: &Path) -> String {
+        self.variant_path(album_path, Variant::Thumb2x)
     }
 
     /// URL path to the micro thumbnail WebP (e.g., "images/album/photo-abc123-micro.webp")
     ///
-    /// Micro thumbnails are very small (120px) for use in filmstrips and other UI
+    /// Micro thumbnails are very small (120px by default) for use in filmstrips and other UI
     /// elements where fast loading is more important than detail.
     pub fn micro_thumb_path(&self, album_path: &Path) -> String {
-        let encoded_stem = url_encode(&self.stem);
+        self.variant_path(album_path, Variant::Micro)
+    }
+
+    /// URL path to the data saver full-size WebP (e.g., "images/album/photo-abc123-full-saver.webp")
+    pub fn saver_image_path(&self, album_path: &Path) -> String {
+        self.variant_path(album_path, Variant::FullSaver)
+    }
+
+    /// URL path to the data saver thumbnail WebP (e.g., "images/album/photo-abc123-thumb-saver.webp")
+    pub fn saver_thumb_path(&self, album_path: &Path) -> String {
+        self.variant_path(album_path, Variant::ThumbSaver)
+    }
+
+    /// URL path to the AVIF full-size image (e.g., "images/album/photo-abc123-full.avif")
+    pub fn avif_image_path(&self, album_path: &Path) -> String {
+        self.variant_path(album_path, Variant::AvifFull)
+    }
+
+    /// URL path to the AVIF thumbnail (e.g., "images/album/photo-abc123-thumb.avif")
+    pub fn avif_thumb_path(&self, album_path: &Path) -> String {
+        self.variant_path(album_path, Variant::AvifThumb)
+    }
+
+    /// URL path to a responsive width (e.g., "images/album/photo-abc123-w960.webp")
+    pub fn width_path(&self, album_path: &Path, width: u32) -> String {
+        self.variant_path(album_path, Variant::Width(width))
+    }
+
+    /// URL path to a cover crop (e.g., "images/album/photo-abc123-crop3x1.webp")
+    pub fn crop_path(&self, album_path: &Path, ratio: AspectRatio) -> String {
+        self.variant_path(album_path, Variant::Crop(ratio))
+    }
+
+    /// Filename of a variant: `{stem}-{hash}-{name}.{format}`
+    pub fn variant_file_name(&self, variant: Variant) -> String {
+        format!(
+            "{}-{}-{}.{}",
+            self.stem,
+            self.hash,
+            self.variants.get(variant),
+            variant.format()
+        )
+    }
+
+    /// URL path to a variant: `images/{album}/{stem}-{hash}-{name}.{format}`
+    fn variant_path(&self, album_path: &Path, variant: Variant) -> String {
+        let file_name = url_encode(&self.variant_file_name(variant));
         if album_path.as_os_str().is_empty() {
-            format!("images/{}-{}-micro.webp", encoded_stem, self.hash)
+            format!("images/{}", file_name)
         } else {
             let encoded_album = url_encode_path(&album_path.display().to_string());
-            format!(
-                "images/{}/{}-{}-micro.webp",
-                encoded_album, encoded_stem, self.hash
-            )
+            format!("images/{}/{}", encoded_album, file_name)
         }
     }
 
     /// URL path to the original image (e.g., "images/album/photo-abc123-original.jpg")
     ///
-    /// When GPS mode is not `On`, the filename includes `-nogps` suffix to indicate
-    /// GPS EXIF data has been stripped.
-    pub fn original_path(&self, album_path: &Path, gps_mode: GpsMode) -> String {
+    /// The name doesn't depend on the GPS mode; whether GPS was stripped from
+    /// the published copy is tracked in the metadata cache.
+    pub fn original_path(&self, album_path: &Path) -> String {
         let encoded_stem = url_encode(&self.stem);
-        let suffix = gps_mode.original_suffix();
         if album_path.as_os_str().is_empty() {
             format!(
-                "images/{}-{}-original{}.{}",
-                encoded_stem, self.hash, suffix, self.extension
+                "images/{}-{}-original.{}",
+                encoded_stem,
+                self.hash,
+                self.original_extension()
             )
         } else {
             let encoded_album = url_encode_path(&album_path.display().to_string());
             format!(
-                "images/{}/{}-{}-original{}.{}",
-                encoded_album, encoded_stem, self.hash, suffix, self.extension
+                "images/{}/{}-{}-original.{}",
+                encoded_album,
+                encoded_stem,
+                self.hash,
+                self.original_extension()
             )
         }
     }
 
+    /// Filename of a published video clip: `{stem}-{hash}-video.{extension}`
+    pub fn video_file_name(&self) -> String {
+        format!("{}-{}-video.{}", self.stem, self.hash, self.extension)
+    }
+
+    /// URL path to a published video clip (e.g., "images/album/clip-abc123-video.mp4")
+    pub fn video_path(&self, album_path: &Path) -> String {
+        let file_name = url_encode(&self.video_file_name());
+        if album_path.as_os_str().is_empty() {
+            format!("images/{}", file_name)
+        } else {
+            let encoded_album = url_encode_path(&album_path.display().to_string());
+            format!("images/{}/{}", encoded_album, file_name)
+        }
+    }
+
     /// URL path to the photo's HTML page (e.g., "album/photo.html")
     pub fn html_path(&self, album_path: &Path) -> String {
         let encoded_stem = url_encode(&self.stem);
@@ -528,29 +627,62 @@ pub struct Album {
     /// Display name (directory name, titlecased)
     pub name: String,
 
-    /// URL-safe slug (directory name, lowercased)
+    /// URL-safe slug (directory name, or `path` from album.toml, lowercased)
     pub slug: String,
 
-    /// Path relative to photos root (empty for root album)
+    /// URL path relative to the site root (empty for root album): the
+    /// directory's path, unless album.toml sets one
     #[serde(skip)]
     pub path: PathBuf,
 
+    /// Directory relative to photos root
+    #[serde(skip)]
+    pub dir: PathBuf,
+
+    /// Earlier URL paths that redirect to the album
+    #[serde(skip)]
+    pub aliases: Vec<PathBuf>,
+
     /// Photos directly in this album
     pub photos: Vec<Photo>,
 
     /// Child albums (subdirectories)
     pub children: Vec<Album>,
+
+    /// Photo representing the album, set after processing
+    pub cover: Option<Cover>,
+
+    /// Summary of the photos in this album and descendants, set after processing
+    pub stats: AlbumStats,
+}
+
+/// An album's cover: its first photo, or its first descendant's.
+#[derive(Debug, Clone, Serialize)]
+pub struct Cover {
+    /// Filename of the cover photo without extension
+    pub stem: String,
+
+    /// URL path to the cover photo's thumbnail
+    pub thumb_path: String,
+
+    /// URL paths to the cover cropped to each `[crops]` ratio, as `{name}_path`
+    #[serde(flatten)]
+    pub crops: BTreeMap<String, String>,
 }
 
 impl Album {
     /// Create a new empty album.
-    fn new(name: String, slug: String, path: PathBuf) -> Self {
+    pub(crate) fn new(name: String, slug: String, path: PathBuf) -> Self {
         Self {
             name,
             slug,
+            dir: path.clone(),
             path,
+            aliases: Vec::new(),
             photos: Vec::new(),
             children: Vec::new(),
+            cover: None,
+            stats: AlbumStats::default(),
         }
     }
 
@@ -559,6 +691,18 @@ impl Album {
         Self::new("Gallery".to_string(), String::new(), PathBuf::new())
     }
 
+    /// This album with the photos of its descendants moved into it, for `flatten`.
+    pub fn flattened(self) -> Album {
+        let photos = self.all_photos().into_iter().cloned().collect();
+        Album {
+            photos,
+            children: Vec::new(),
+            cover: None,
+            stats: Default::default(),
+            ..self
+        }
+    }
+
     /// Collect all photos from this album and all descendants.
     pub fn all_photos(&self) -> Vec<&Photo> {
         let mut result: Vec<&Photo> = self.photos.iter().collect();
@@ -570,6 +714,25 @@ impl Album {
         result
     }
 
+    /// Like [`Album::all_photos`], with the album each photo is in.
+    pub fn all_photos_with_albums(&self) -> Vec<(&Album, &Photo)> {
+        let mut result: Vec<_> = self.photos.iter().map(|photo| (self, photo)).collect();
+
+        for child in &self.children {
+            result.extend(child.all_photos_with_albums());
+        }
+
+        result
+    }
+
+    /// The first photo in this album or its descendants, with the album it's in.
+    pub fn cover_photo(&self) -> Option<(&Album, &Photo)> {
+        match self.photos.first() {
+            Some(photo) => Some((self, photo)),
+            None => self.children.iter().find_map(Album::cover_photo),
+        }
+    }
+
     /// URL path for this album's index page.
     #[allow(dead_code)]
     pub fn html_path(&self) -> String {
@@ -580,10 +743,59 @@ impl Album {
         }
     }
 
+    /// Keep only photos for which `keep` is true, dropping albums left empty.
+    pub fn retain_photos(&mut self, keep: &impl Fn(&Photo) -> bool) {
+        self.photos.retain(|photo| keep(photo));
+        for child in &mut self.children {
+            child.retain_photos(keep);
+        }
+        self.children.retain(|child| child.photo_count() > 0);
+    }
+
+    /// Order photos in this album and descendants. Ties keep their current order.
+    pub fn sort_photos(&mut self, sort: PhotoSort) {
+        match sort {
+            PhotoSort::Name => self.photos.sort_by(|a, b| a.stem.cmp(&b.stem)),
+            PhotoSort::Date => self.photos.sort_by_cached_key(|photo| {
+                let date = photo.metadata.date_taken.as_deref().map(iso_datetime);
+                (date.is_none(), date)
+            }),
+            PhotoSort::Rating => self
+                .photos
+                .sort_by_key(|photo| Reverse(photo.metadata.rating.unwrap_or(0))),
+        }
+        for child in &mut self.children {
+            child.sort_photos(sort);
+        }
+    }
+
+    /// Clear unpublished metadata from every photo in this album and descendants.
+    pub fn restrict_metadata(&mut self, config: &MetadataConfig) {
+        for photo in &mut self.photos {
+            photo.metadata.restrict(config);
+        }
+        for child in &mut self.children {
+            child.restrict_metadata(config);
+        }
+    }
+
+    /// Compute the stats of this album and descendants from their published metadata.
+    pub fn update_stats(&mut self) {
+        for child in &mut self.children {
+            child.update_stats();
+        }
+        self.stats = stats::compute(self);
+    }
+
     /// Count total photos in this album and descendants.
     pub fn photo_count(&self) -> usize {
         self.photos.len() + self.children.iter().map(Album::photo_count).sum::<usize>()
     }
+
+    /// Count descendant albums (not including this one).
+    pub fn album_count(&self) -> usize {
+        self.children.len() + self.children.iter().map(Album::album_count).sum::<usize>()
+    }
 }
 
 /// Discover photos and build album hierarchy from directory structure.
@@ -592,6 +804,7 @@ pub fn discover(photos_dir: &Path) -> Result<Album> {
     let mut root = Album::root();
 
     discover_recursive(&photos_dir, &photos_dir, &mut root)?;
+    album_config::drop_shadowed_aliases(&mut root);
 
     // Sort children and photos for consistent ordering
     sort_album(&mut root);
@@ -624,11 +837,15 @@ fn discover_recursive(base: &Path, dir: &Path, album: &mut Album) -> Result<()>
             let dir_name = path.file_name().unwrap().to_str().unwrap();
             let relative_path = path.strip_prefix(base).unwrap();
 
+            let config = AlbumConfig::load(&path)?;
+            let (album_path, aliases) = album_config::paths(&config, &album.path, relative_path);
             let mut child = Album::new(
                 titlecase(dir_name),
-                dir_name.to_lowercase(),
-                relative_path.to_path_buf(),
+                config.path.as_deref().unwrap_or(dir_name).to_lowercase(),
+                album_path,
             );
+            child.dir = relative_path.to_path_buf();
+            child.aliases = aliases;
 
             discover_recursive(base, &path, &mut child)?;
 
@@ -641,7 +858,19 @@ fn discover_recursive(base: &Path, dir: &Path, album: &mut Album) -> Result<()>
         }
     }
 
-    Ok(())
+    // Cameras shooting RAW+JPEG write both under one name, as do iPhones
+    // exporting Live Photos with their clip; prefer the still image
+    let developed: Vec<String> = album
+        .photos
+        .iter()
+        .filter(|photo| !photo.is_raw() && !photo.is_video())
+        .map(|photo| photo.stem.clone())
+        .collect();
+    album
+        .photos
+        .retain(|photo| !(photo.is_raw() || photo.is_video()) || !developed.contains(&photo.stem));
+
+    album_config::check_children(album)
 }
 
 fn sort_album(album: &mut Album) {
@@ -654,7 +883,7 @@ fn sort_album(album: &mut Album) {
 }
 
 /// Convert a directory name to title case for display.
-fn titlecase(s: &str) -> String {
+pub(crate) fn titlecase(s: &str) -> String {
     s.split(['-', '_'])
         .map(|word| {
             let mut chars = word.chars();
@@ -670,6 +899,7 @@ fn titlecase(s: &str) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use test_case::test_case;
 
     #[test]
     fn titlecase_simple() {
@@ -686,17 +916,58 @@ mod tests {
         assert_eq!(titlecase("my_photo_album"), "My Photo Album");
     }
 
+    #[test_case(Some("is"), Some("IS"), Some("Iceland") ; "country code")]
+    #[test_case(Some("Iceland"), Some("IS"), Some("Iceland") ; "country name")]
+    #[test_case(Some("Atlantis"), None, Some("Atlantis") ; "unknown country name")]
+    #[test_case(None, None, None ; "no country")]
+    fn test_named_coords(country: Option<&str>, code: Option<&str>, name: Option<&str>) {
+        let coords = GpsCoords::named("Vík", None, country, Registry::builtin());
+
+        assert_eq!(coords.city.as_deref(), Some("Vík"));
+        assert_eq!(coords.country_code.as_deref(), code);
+        assert_eq!(coords.country.as_deref(), name);
+        assert_eq!(coords.flag.is_some(), code.is_some());
+        assert_eq!(coords.latitude, None);
+    }
+
+    #[test]
+    fn restrict_clears_unpublished_fields() {
+        let mut metadata = PhotoMetadata {
+            date_taken: Some("2024:04:02 06:12:45".to_string()),
+            camera: Some("Canon EOS R5".to_string()),
+            lens: Some("RF 24-70mm F2.8L IS USM".to_string()),
+            keywords: vec!["tokyo".to_string()],
+            ..Default::default()
+        };
+        let config = MetadataConfig {
+            publish: vec![MetadataField::Camera, MetadataField::Date],
+        };
+
+        metadata.restrict(&config);
+
+        assert_eq!(metadata.camera.as_deref(), Some("Canon EOS R5"));
+        assert!(metadata.date_taken.is_some());
+        assert_eq!(metadata.lens, None);
+        assert!(metadata.keywords.is_empty());
+    }
+
     #[test]
     fn photo_paths_root_album() {
         let photo = Photo {
             source: PathBuf::from("/photos/test.jpg"),
             stem: "test".to_string(),
             extension: "jpg".to_string(),
+            media_type: MediaType::Photo,
+            animated: false,
+            lossless: false,
             hash: "abc12345".to_string(),
             width: 1920,
             height: 1080,
             original_size: 1024000,
             metadata: PhotoMetadata::default(),
+            placeholder: None,
+            color: None,
+            variants: Arc::default(),
         };
 
         let root_path = PathBuf::new();
@@ -709,12 +980,12 @@ mod tests {
             "images/test-abc12345-thumb.webp"
         );
         assert_eq!(
-            photo.original_path(&root_path, GpsMode::On),
-            "images/test-abc12345-original.jpg"
+            photo.saver_image_path(&root_path),
+            "images/test-abc12345-full-saver.webp"
         );
         assert_eq!(
-            photo.original_path(&root_path, GpsMode::Off),
-            "images/test-abc12345-original-nogps.jpg"
+            photo.original_path(&root_path),
+            "images/test-abc12345-original.jpg"
         );
         assert_eq!(photo.html_path(&root_path), "test.html");
     }
@@ -725,11 +996,17 @@ mod tests {
             source: PathBuf::from("/photos/vacation/test.jpg"),
             stem: "test".to_string(),
             extension: "jpg".to_string(),
+            media_type: MediaType::Photo,
+            animated: false,
+            lossless: false,
             hash: "def67890".to_string(),
             width: 3000,
             height: 2000,
             original_size: 2048000,
             metadata: PhotoMetadata::default(),
+            placeholder: None,
+            color: None,
+            variants: Arc::default(),
         };
 
         let album_path = PathBuf::from("vacation");
@@ -742,7 +1019,15 @@ mod tests {
             "images/vacation/test-def67890-thumb.webp"
         );
         assert_eq!(
-            photo.original_path(&album_path, GpsMode::On),
+            photo.saver_thumb_path(&album_path),
+            "images/vacation/test-def67890-thumb-saver.webp"
+        );
+        assert_eq!(
+            photo.avif_image_path(&album_path),
+            "images/vacation/test-def67890-full.avif"
+        );
+        assert_eq!(
+            photo.original_path(&album_path),
             "images/vacation/test-def67890-original.jpg"
         );
         assert_eq!(photo.html_path(&album_path), "vacation/test.html");
@@ -754,11 +1039,17 @@ mod tests {
             source: PathBuf::from("/photos/My Vacation/Beach Day.jpg"),
             stem: "Beach Day".to_string(),
             extension: "jpg".to_string(),
+            media_type: MediaType::Photo,
+            animated: false,
+            lossless: false,
             hash: "abc12345".to_string(),
             width: 4000,
             height: 3000,
             original_size: 5120000,
             metadata: PhotoMetadata::default(),
+            placeholder: None,
+            color: None,
+            variants: Arc::default(),
         };
 
         let root_path = PathBuf::new();
@@ -771,7 +1062,7 @@ mod tests {
             "images/Beach%20Day-abc12345-thumb.webp"
         );
         assert_eq!(
-            photo.original_path(&root_path, GpsMode::On),
+            photo.original_path(&root_path),
             "images/Beach%20Day-abc12345-original.jpg"
         );
         assert_eq!(photo.html_path(&root_path), "Beach%20Day.html");
@@ -786,7 +1077,7 @@ mod tests {
             "images/My%20Vacation/Beach%20Day-abc12345-thumb.webp"
         );
         assert_eq!(
-            photo.original_path(&album_path, GpsMode::On),
+            photo.original_path(&album_path),
             "images/My%20Vacation/Beach%20Day-abc12345-original.jpg"
         );
         assert_eq!(
@@ -810,4 +1101,208 @@ mod tests {
         );
         assert_eq!(album.html_path(), "vacation/index.html");
     }
+
+    #[test]
+    fn album_count_includes_nested() {
+        let mut root = Album::root();
+        let mut trips = Album::new("Trips".to_string(), "trips".to_string(), "trips".into());
+        trips.children.push(Album::new(
+            "Japan".to_string(),
+            "japan".to_string(),
+            "trips/japan".into(),
+        ));
+        root.children.push(trips);
+        root.children.push(Album::new(
+            "Pets".to_string(),
+            "pets".to_string(),
+            "pets".into(),
+        ));
+
+        assert_eq!(root.album_count(), 3);
+    }
+
+    fn curated_photo(stem: &str, metadata: PhotoMetadata) -> Photo {
+        Photo {
+            source: PathBuf::from(format!("/photos/{}.jpg", stem)),
+            stem: stem.to_string(),
+            extension: "jpg".to_string(),
+            media_type: MediaType::Photo,
+            animated: false,
+            lossless: false,
+            hash: "abc12345".to_string(),
+            width: 1920,
+            height: 1080,
+            original_size: 1024,
+            metadata,
+            placeholder: None,
+            color: None,
+            variants: Arc::default(),
+        }
+    }
+
+    #[test_case(None, false, &[], Some(2), None, None, true ; "no conditions")]
+    #[test_case(None, false, &[], Some(5), Some(Pick::Rejected), None, false ; "rejected")]
+    #[test_case(Some(3), false, &[], Some(2), None, None, false ; "below min rating")]
+    #[test_case(Some(3), false, &[], None, None, None, false ; "unrated")]
+    #[test_case(Some(3), false, &[], Some(4), None, None, true ; "above min rating")]
+    #[test_case(None, true, &[], None, None, None, false ; "not picked")]
+    #[test_case(None, true, &[], None, Some(Pick::Picked), None, true ; "picked")]
+    #[test_case(None, false, &["Green"], None, None, Some("green"), true ; "label ignores case")]
+    #[test_case(None, false, &["Green"], None, None, Some("Red"), false ; "other label")]
+    #[test_case(None, false, &["Green"], None, None, None, false ; "no label")]
+    fn test_filter(
+        min_rating: Option<u8>,
+        picks: bool,
+        labels: &[&str],
+        rating: Option<u8>,
+        pick: Option<Pick>,
+        label: Option<&str>,
+        expected: bool,
+    ) {
+        let filter = FilterConfig {
+            min_rating,
+            picks,
+            labels: labels.iter().map(|l| l.to_string()).collect(),
+        };
+        let metadata = PhotoMetadata {
+            rating,
+            pick,
+            label: label.map(str::to_string),
+            ..Default::default()
+        };
+
+        assert_eq!(metadata.passes(&filter), expected);
+    }
+
+    #[test]
+    fn retain_photos_drops_empty_albums() {
+        let mut root = Album::root();
+        let mut trips = Album::new("Trips".to_string(), "trips".to_string(), "trips".into());
+        trips
+            .photos
+            .push(curated_photo("beach", PhotoMetadata::default()));
+        let mut japan = Album::new(
+            "Japan".to_string(),
+            "japan".to_string(),
+            "trips/japan".into(),
+        );
+        let picked = PhotoMetadata {
+            pick: Some(Pick::Picked),
+            ..Default::default()
+        };
+        japan.photos.push(curated_photo("tokyo", picked));
+        trips.children.push(japan);
+        root.children.push(trips);
+
+        root.retain_photos(&|photo: &Photo| photo.stem == "beach");
+
+        assert_eq!(root.photo_count(), 1);
+        assert_eq!(root.album_count(), 1);
+    }
+
+    #[test]
+    fn cover_photo_falls_back_to_descendants() {
+        let mut root = Album::root();
+        let mut trips = Album::new("Trips".to_string(), "trips".to_string(), "trips".into());
+        let mut japan = Album::new(
+            "Japan".to_string(),
+            "japan".to_string(),
+            "trips/japan".into(),
+        );
+        japan
+            .photos
+            .push(curated_photo("tokyo", PhotoMetadata::default()));
+        trips.children.push(japan);
+        root.children.push(trips);
+
+        let (album, photo) = root.cover_photo().unwrap();
+        assert_eq!(album.slug, "japan");
+        assert_eq!(photo.stem, "tokyo");
+
+        root.photos
+            .push(curated_photo("beach", PhotoMetadata::default()));
+        assert_eq!(root.cover_photo().unwrap().1.stem, "beach");
+        assert!(Album::root().cover_photo().is_none());
+    }
+
+    #[test_case(PhotoSort::Name, &["a", "b", "c", "d"] ; "name")]
+    #[test_case(PhotoSort::Date, &["c", "a", "d", "b"] ; "date")]
+    #[test_case(PhotoSort::Rating, &["d", "a", "b", "c"] ; "rating")]
+    fn test_sort_photos(sort: PhotoSort, expected: &[&str]) {
+        let mut album = Album::root();
+        for (stem, date, rating) in [
+            ("d", Some("2024-01-01T10:00:00"), Some(5)),
+            ("c", Some("2023:06:01 10:00:00"), None),
+            ("b", None, Some(3)),
+            ("a", Some("2023:12:01 10:00:00"), Some(3)),
+        ] {
+            let metadata = PhotoMetadata {
+                date_taken: date.map(str::to_string),
+                rating,
+                ..Default::default()
+            };
+            album.photos.push(curated_photo(stem, metadata));
+        }
+        album.sort_photos(PhotoSort::Name);
+
+        album.sort_photos(sort);
+
+        let stems: Vec<_> = album.photos.iter().map(|p| p.stem.as_str()).collect();
+        assert_eq!(stems, expected);
+    }
+
+    #[test]
+    fn raw_files_discovered_unless_paired_with_jpeg() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["IMG_0001.CR3", "IMG_0001.JPG", "IMG_0002.nef", "notes.txt"] {
+            std::fs::write(dir.path().join(name), b"").unwrap();
+        }
+
+        let root = discover(dir.path()).unwrap();
+
+        let names: Vec<_> = root
+            .photos
+            .iter()
+            .map(|p| format!("{}.{}", p.stem, p.extension))
+            .collect();
+        assert_eq!(names, ["IMG_0001.jpg", "IMG_0002.nef"]);
+        assert!(root.photos[1].is_raw());
+    }
+
+    #[test]
+    fn videos_discovered_unless_live_photo() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["IMG_0001.HEIC", "IMG_0001.MOV", "IMG_0002.mp4"] {
+            std::fs::write(dir.path().join(name), b"").unwrap();
+        }
+
+        let root = discover(dir.path()).unwrap();
+
+        let names: Vec<_> = root
+            .photos
+            .iter()
+            .map(|p| format!("{}.{}", p.stem, p.extension))
+            .collect();
+        assert_eq!(names, ["IMG_0001.heic", "IMG_0002.mp4"]);
+        assert_eq!(root.photos[1].media_type, MediaType::Video);
+        assert_eq!(
+            root.photos[1].video_path(Path::new("clips")),
+            "images/clips/IMG_0002--video.mp4"
+        );
+    }
+
+    #[test]
+    fn heif_original_published_as_jpeg() {
+        let photo = Photo {
+            hash: "abc12345".to_string(),
+            ..Photo::from_path(Path::new("/photos/IMG_4021.HEIC")).unwrap()
+        };
+
+        assert!(photo.is_heif());
+        assert_eq!(photo.extension, "heic");
+        assert_eq!(
+            photo.original_path(Path::new("")),
+            "images/IMG_4021-abc12345-original.jpg"
+        );
+    }
 }
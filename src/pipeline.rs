@@ -2,20 +2,73 @@ use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 
 use serde::Serialize;
 use tera::{Context, Function, Value};
 
+use crate::activitypub;
+use crate::analytics;
+use crate::archive;
+use crate::audit;
+use crate::auto_album;
+use crate::build_report::{self, BuildReport, PageCounts};
 use crate::builtin_themes;
-use crate::config::Site;
+use crate::cache::{self, MetadataCache};
+use crate::checksums;
+use crate::collections::{self, Collection};
+use crate::comments::{self, Comments};
+use crate::config::{AuditMode, GpsMode, PageKind, PngOptimization, Site};
+use crate::countries::Registry;
+use crate::crop;
 use crate::error::{Error, Result};
+use crate::feed;
+use crate::functions;
+use crate::gallery_delta;
+use crate::geocode::Geocoder;
+use crate::gpx::Tracks;
 use crate::i18n;
+use crate::inject::{self, Position};
+use crate::jsonld;
 use crate::minify;
-use crate::photos::{Album, Photo};
-use crate::processing;
+use crate::originals;
+use crate::photos::{Album, MediaType, Photo, Variant};
+use crate::processing::{self, Encodings, Locations, Outputs, ProcessingStats};
+use crate::progress::{Bar, Progress};
+use crate::seo::{self, Page};
+use crate::sizes;
+use crate::stats::AlbumStats;
+use crate::svg;
 use crate::theme::{StaticSource, Theme, templates};
 use crate::theme_build::{self, ThemeType};
-use crate::util::url_encode_path;
+use crate::timings::Timings;
+use crate::util::{self, canonical_json, url_encode_path};
+use crate::watermark::Watermark;
+use crate::xmp::Pick;
+
+/// Where pages are written, and what earlier builds wrote there.
+struct Pages<'a> {
+    dir: &'a Path,
+    cache: &'a MetadataCache,
+    /// Hash of what every page depends on besides its context
+    fingerprint: blake3::Hash,
+    /// Pages left as an earlier build wrote them
+    unchanged: AtomicUsize,
+    /// Pages written in this build
+    rendered: AtomicUsize,
+    progress: Bar,
+}
+
+impl Pages<'_> {
+    /// Show the pages done so far on the progress bar.
+    fn tick(&self) {
+        let unchanged = self.unchanged.load(Ordering::Relaxed);
+        let rendered = self.rendered.load(Ordering::Relaxed);
+        self.progress
+            .set(unchanged + rendered, || format!("{} unchanged", unchanged));
+    }
+}
 
 /// Mapping from original asset path to hashed output path.
 /// e.g., "style.css" -> "/static/style-abc12345.css"
@@ -24,8 +77,11 @@ pub type AssetManifest = BTreeMap<String, String>;
 /// Version injected at build time.
 const VERSION: &str = env!("GIT_VERSION");
 
+/// Output directory of the archive page.
+const ARCHIVE_DIR: &str = "archive";
+
 /// Convert a TOML value to a JSON-compatible serde_json::Value.
-fn toml_to_json(value: &toml::Value) -> serde_json::Value {
+pub(crate) fn toml_to_json(value: &toml::Value) -> serde_json::Value {
     match value {
         toml::Value::String(s) => serde_json::Value::String(s.clone()),
         toml::Value::Integer(i) => serde_json::Value::Number((*i).into()),
@@ -45,12 +101,44 @@ fn toml_to_json(value: &toml::Value) -> serde_json::Value {
     }
 }
 
+/// A page sending visitors and search engines on to `target`, a URL path.
+fn redirect_page(base_url: &str, target: &str) -> String {
+    let url = format!("{}{}", base_url, target);
+    format!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>Redirecting</title><link rel=\"canonical\" href=\"{url}\"><meta name=\"robots\" content=\"noindex\"><meta http-equiv=\"refresh\" content=\"0; url={target}\"></head><body><a href=\"{target}\">{url}</a></body></html>\n",
+        url = url,
+        target = target
+    )
+}
+
 /// Site context passed to all templates.
 #[derive(Debug, Clone, Serialize)]
 struct SiteContext {
     domain: String,
     title: String,
     version: &'static str,
+    /// Only in templates: in gallery JSON, the build time would change its hash every build
+    #[serde(flatten)]
+    build: Option<BuildStats>,
+}
+
+/// Absolute URL of a page in another language, for `<link rel="alternate" hreflang>`.
+#[derive(Debug, Clone, Serialize)]
+struct AlternateUrl {
+    /// BCP 47 language tag, or `x-default`
+    hreflang: String,
+    href: String,
+}
+
+/// When the site was built and what it contains.
+#[derive(Debug, Clone, Copy, Serialize)]
+struct BuildStats {
+    /// Seconds since the epoch
+    built_at: i64,
+    photo_count: usize,
+    album_count: usize,
+    /// Total size of the source photos in bytes
+    total_size: u64,
 }
 
 /// Manifest of generated data files with their hashed URLs.
@@ -60,6 +148,34 @@ struct DataManifest {
     i18n: BTreeMap<String, String>,
     /// URL to gallery data JSON file (e.g., "/static/gallery-def67890.json")
     gallery: String,
+    /// Changes since earlier gallery JSONs, oldest first, if `[manifest] gallery_deltas` is set
+    gallery_deltas: Vec<gallery_delta::Delta>,
+    /// URL to the site RSS feed (e.g., "/feed.xml"), if enabled
+    feed: Option<String>,
+    /// URL to the site JSON Feed (e.g., "/feed.json"), if enabled
+    json_feed: Option<String>,
+    /// URL to the ActivityPub actor (e.g., "/actor.json"), if enabled
+    actor: Option<String>,
+    /// Map of album path to its RSS feed URL (e.g., "travel" -> "/travel/feed.xml")
+    album_feeds: BTreeMap<String, String>,
+}
+
+impl DataManifest {
+    /// URL of an album's RSS feed, if it has one.
+    fn album_feed(&self, album: &Album) -> Option<&String> {
+        self.album_feeds
+            .get(&url_encode_path(&album.path.to_string_lossy()))
+    }
+}
+
+/// Asset and data manifests written for external tooling.
+#[derive(Debug, Serialize)]
+struct ManifestFile<'a> {
+    version: &'static str,
+    /// Theme static assets, e.g. "style.css" -> "/static/style-abc12345.css"
+    assets: &'a AssetManifest,
+    /// Generated data files
+    data: &'a DataManifest,
 }
 
 /// Gallery data structure for JSON serialization.
@@ -68,6 +184,7 @@ struct GalleryData {
     site: SiteContext,
     albums: Vec<AlbumData>,
     photos: Vec<PhotoData>,
+    collections: Vec<CollectionData>,
 }
 
 /// Album data for gallery JSON.
@@ -78,6 +195,62 @@ struct AlbumData {
     slug: String,
     path: String,
     photo_count: usize,
+    stats: AlbumStatsData,
+}
+
+/// Collection data for gallery JSON.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CollectionData {
+    name: String,
+    slug: String,
+    path: String,
+    photo_count: usize,
+    stats: AlbumStatsData,
+    /// `htmlPath` of each photo, in order, naming it among `photos`
+    photos: Vec<String>,
+}
+
+/// Album stats for gallery JSON.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AlbumStatsData {
+    first_date: Option<String>,
+    last_date: Option<String>,
+    cameras: Vec<String>,
+    total_size: u64,
+    countries: Vec<CountryData>,
+}
+
+/// A country in album stats for gallery JSON.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CountryData {
+    code: String,
+    name: Option<String>,
+    flag: Option<String>,
+    photo_count: usize,
+}
+
+impl From<&AlbumStats> for AlbumStatsData {
+    fn from(stats: &AlbumStats) -> Self {
+        Self {
+            first_date: stats.first_date.clone(),
+            last_date: stats.last_date.clone(),
+            cameras: stats.cameras.clone(),
+            total_size: stats.total_size,
+            countries: stats
+                .countries
+                .iter()
+                .map(|country| CountryData {
+                    code: country.code.clone(),
+                    name: country.name.clone(),
+                    flag: country.flag.clone(),
+                    photo_count: country.photo_count,
+                })
+                .collect(),
+        }
+    }
 }
 
 /// Photo data for gallery JSON.
@@ -86,13 +259,23 @@ struct AlbumData {
 struct PhotoData {
     stem: String,
     hash: String,
+    media_type: MediaType,
+    animated: bool,
     width: u32,
     height: u32,
     original_size: u64,
     image_path: String,
     thumb_path: String,
+    thumb_2x_path: Option<String>,
     micro_thumb_path: String,
-    original_path: String,
+    original_path: Option<String>,
+    video_path: Option<String>,
+    data_saver: Option<VariantPathsData>,
+    avif: Option<VariantPathsData>,
+    srcset: Option<String>,
+    widths: Vec<WidthPath>,
+    placeholder: Option<String>,
+    color: Option<String>,
     html_path: String,
     metadata: PhotoMetadataData,
 }
@@ -108,6 +291,24 @@ struct PhotoMetadataData {
     gps: Option<GpsData>,
     exposure: Option<ExposureData>,
     rating: Option<u8>,
+    pick: Option<Pick>,
+    label: Option<String>,
+    title: Option<String>,
+    description: Option<String>,
+    keywords: Vec<String>,
+    creator: Option<String>,
+    location: Option<LocationData>,
+}
+
+/// IPTC location for gallery JSON.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct LocationData {
+    sublocation: Option<String>,
+    city: Option<String>,
+    region: Option<String>,
+    country: Option<String>,
+    country_code: Option<String>,
 }
 
 /// GPS data for gallery JSON.
@@ -122,6 +323,8 @@ struct GpsData {
     country: Option<String>,
     country_code: Option<String>,
     flag: Option<String>,
+    altitude: Option<f64>,
+    direction: Option<f64>,
 }
 
 /// Exposure data for gallery JSON.
@@ -155,6 +358,48 @@ pub struct Pipeline {
 
     /// Whether to include source maps for debugging
     pub source_maps: bool,
+
+    /// Version string exposed to templates and gallery data
+    pub version: &'static str,
+
+    /// Country names with the site's changes applied
+    pub countries: Registry,
+
+    /// Build time to use instead of the clock, so output doesn't change between builds
+    pub fixed_build_time: Option<i64>,
+
+    /// Set to abandon the build before it changes any pages, for example when
+    /// its inputs changed while it ran
+    pub cancel: Arc<AtomicBool>,
+
+    /// What processing photos did in the last build
+    pub stats: ProcessingStats,
+
+    /// Where the build's time went, if recorded
+    pub timings: Timings,
+
+    /// Read photos and render pages without writing images, cropping covers,
+    /// or saving the caches, as `galerie check` does
+    pub dry_run: bool,
+
+    /// Collections from `[collections]`, found in the albums once they're processed
+    pub collections: Vec<Collection>,
+
+    /// Where to write a summary of each build as JSON, if anywhere
+    pub report: Option<PathBuf>,
+
+    /// Whether to draw progress bars while photos are processed and pages rendered
+    pub progress: Progress,
+
+    /// Whether large originals may be memory-mapped, which is only sound
+    /// while nothing truncates them during the build
+    pub map_originals: bool,
+
+    /// Time of the current build, in seconds since the epoch
+    built_at: i64,
+
+    /// Mark composited onto generated images, if configured
+    watermark: Option<Watermark>,
 }
 
 impl Pipeline {
@@ -166,7 +411,7 @@ impl Pipeline {
         let photos_path = site_dir.join(&config.photos);
 
         // Try local directory first, then built-in themes
-        let theme = if local_theme_path.is_dir() {
+        let mut theme = if local_theme_path.is_dir() {
             match theme_build::detect_theme_type(&local_theme_path) {
                 ThemeType::Classic => {
                     tracing::debug!(theme = %local_theme_path.display(), "loading local classic theme");
@@ -187,6 +432,28 @@ impl Pipeline {
             });
         };
 
+        theme.set_autoescape(config.autoescape);
+
+        // Register template functions defined in site.toml
+        functions::register(&mut theme.templates, &config.functions)?;
+        seo::validate(&config.seo)?;
+        crop::validate(&config.crops)?;
+        collections::validate(&config.collections)?;
+        if let Some(originals) = &config.originals {
+            originals::validate(originals)?;
+            if config.gps.strips_gps() {
+                tracing::warn!(
+                    gps = ?config.gps,
+                    "originals hosted elsewhere keep their GPS coordinates; gps only strips published copies"
+                );
+            }
+        }
+        let watermark = config
+            .watermark
+            .as_ref()
+            .map(|watermark| Watermark::load(watermark, &site_dir))
+            .transpose()?;
+
         // Merge theme config: start with theme defaults, apply user overrides
         let mut theme_config = theme.defaults.clone();
         for (key, value) in config.theme.settings() {
@@ -206,14 +473,7 @@ impl Pipeline {
         // Apply flatten option if enabled
         let root = if config.flatten {
             tracing::debug!("flattening album hierarchy");
-            let all_photos: Vec<_> = discovered.all_photos().into_iter().cloned().collect();
-            Album {
-                name: discovered.name,
-                slug: discovered.slug,
-                path: discovered.path,
-                photos: all_photos,
-                children: Vec::new(),
-            }
+            discovered.flattened()
         } else {
             discovered
         };
@@ -224,6 +484,8 @@ impl Pipeline {
             "pipeline loaded"
         );
 
+        let countries = Registry::new(&config.countries);
+
         Ok(Self {
             config,
             theme,
@@ -231,14 +493,42 @@ impl Pipeline {
             root,
             site_dir,
             source_maps,
+            version: VERSION,
+            countries,
+            fixed_build_time: None,
+            cancel: Arc::default(),
+            stats: ProcessingStats::default(),
+            timings: Timings::default(),
+            dry_run: false,
+            collections: Vec::new(),
+            report: None,
+            progress: Progress::default(),
+            map_originals: true,
+            built_at: 0,
+            watermark,
         })
     }
 
-    /// Build the site to the output directory.
+    /// Build the site to the output directory, with `[processing] threads`
+    /// threads if set.
     pub fn build(&mut self) -> Result<()> {
+        let Some(threads) = self.config.processing.threads else {
+            return self.build_site();
+        };
+        tracing::debug!(threads, "building with a limited thread pool");
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads.get())
+            .build()
+            .map_err(|e| Error::Other(format!("cannot start {} build threads: {}", threads, e)))?
+            .install(|| self.build_site())
+    }
+
+    fn build_site(&mut self) -> Result<()> {
         let output_dir = self.site_dir.join(&self.config.build);
+        let build_started = (Instant::now(), SystemTime::now());
 
         tracing::info!(output = %output_dir.display(), "building site");
+        self.built_at = self.fixed_build_time.unwrap_or_else(build_time);
 
         // Track all files we generate for cleanup of stale files
         let mut expected_files: HashSet<PathBuf> = HashSet::new();
@@ -253,7 +543,58 @@ impl Pipeline {
         // Process images (extract metadata, generate variants)
         // Must happen before data file generation so photo metadata is populated
         tracing::info!("processing photos");
-        let stats = processing::process_album(&mut self.root, &images_dir, self.config.gps)?;
+        let started = Instant::now();
+        let cache = MetadataCache::load(cache::path(&self.site_dir));
+        if let Some(mode) = self.config.auto_album {
+            auto_album::group(&mut self.root, mode, &cache);
+        }
+        let geocoder = Geocoder::new(&self.config.geocoding);
+        let tracks = Tracks::load(&self.site_dir, &self.config.gpx);
+        let stats = processing::process_album(
+            &mut self.root,
+            &images_dir,
+            self.config.gps,
+            Outputs {
+                full: !self.config.lite,
+                thumb_2x: self.config.processing.thumb_2x,
+                data_saver: self.config.data_saver,
+                originals: !self.config.protect_images && self.config.originals.is_none(),
+                optimize_png: self.config.optimize_png,
+                avif: self.config.avif,
+                encodings: Encodings::new(&self.config.processing),
+                placeholders: self.config.placeholders,
+                watermark: self.watermark.as_ref(),
+                lossless_png: self.config.processing.lossless_png,
+                lossless_albums: &self.config.processing.lossless_albums,
+                reexports: self.config.reexports,
+                dry_run: self.dry_run,
+                map_originals: self.map_originals,
+                filter: self.config.filter.as_ref(),
+                widths: if self.config.lite {
+                    &[]
+                } else {
+                    &self.config.processing.widths
+                },
+            },
+            &Locations {
+                cache: &cache,
+                geocoder: &geocoder,
+                tracks: &tracks,
+                countries: &self.countries,
+            },
+            &self.timings,
+            self.progress,
+            &self.cancel,
+        )?;
+        // Photos a cancelled build never reached are still on disk
+        if self.cancel.load(Ordering::Relaxed) {
+            cache.keep_unseen_sources();
+        }
+        if !self.dry_run
+            && let Err(e) = cache.save()
+        {
+            tracing::warn!(error = %e, "failed to save metadata cache");
+        }
         tracing::info!(
             total = stats.total,
             cached = stats.cached,
@@ -262,30 +603,133 @@ impl Pipeline {
             skipped = stats.skipped,
             "photos processed"
         );
+        self.stats = stats;
+        self.timings.stage("photos", started);
+        // Variants are kept for the next build, but nothing visible has changed yet
+        if self.cancel.load(Ordering::Relaxed) {
+            return Err(Error::Cancelled);
+        }
+        if let Some(filter) = &self.config.filter {
+            let before = self.root.photo_count();
+            self.root
+                .retain_photos(&|photo: &Photo| photo.metadata.passes(filter));
+            tracing::info!(
+                kept = self.root.photo_count(),
+                removed = before - self.root.photo_count(),
+                "filtered photos"
+            );
+        }
+        let started = Instant::now();
+        self.root.sort_photos(self.config.sort);
+        self.root.restrict_metadata(&self.config.metadata);
+        self.root.update_stats();
+        let covers = processing::process_covers(
+            &mut self.root,
+            &images_dir,
+            &self.config.crops,
+            Encodings::new(&self.config.processing).full,
+            self.watermark.as_ref(),
+            self.dry_run,
+        );
+        if covers > 0 {
+            tracing::info!(covers, "cropped album covers");
+        }
+        self.timings.stage("covers", started);
+        self.collections = collections::resolve(&self.root, &self.config.collections)?;
 
         // Track expected image files
         self.collect_expected_images(&images_dir, &mut expected_files);
 
         // Generate static data files (i18n and gallery JSON)
-        let data_manifest = self.generate_data_files(&mut expected_files)?;
+        let started = Instant::now();
+        let mut data_manifest = self.generate_data_files(&mut expected_files)?;
+
+        // Generate RSS feeds
+        self.generate_feeds(&output_dir, &mut data_manifest, &mut expected_files)?;
+
+        self.timings.stage("data and feeds", started);
 
         // Copy static assets and get manifest for template function
+        let started = Instant::now();
         let asset_manifest = self.copy_static(&output_dir, &mut expected_files)?;
 
+        if let Some(config) = &self.config.manifest {
+            let path = output_dir.join(&config.path);
+            self.write_manifest(&path, &asset_manifest, &data_manifest)?;
+            expected_files.insert(path);
+        }
+        if let Some(config) = &self.config.originals {
+            let path = output_dir.join(&config.manifest);
+            let photos: Vec<&Photo> = self
+                .root
+                .all_photos()
+                .into_iter()
+                .filter(|photo| self.links_original(photo))
+                .collect();
+            originals::write_manifest(&path, config, &self.photos_dir(), &photos)?;
+            expected_files.insert(path);
+        }
+        self.timings.stage("static assets", started);
+
         // Register the static() template function with the asset manifest
+        let fingerprint = self.pages_fingerprint(&asset_manifest);
         self.theme
             .templates
             .register_function("static", make_static_function(asset_manifest));
+        self.theme.reset_renders();
 
         // Render pages
-        self.render_index(&output_dir, &data_manifest, &mut expected_files)?;
+        let started = Instant::now();
+        let pages = Pages {
+            dir: &output_dir,
+            cache: &cache,
+            fingerprint,
+            unchanged: AtomicUsize::new(0),
+            rendered: AtomicUsize::new(0),
+            progress: self.progress.bar("pages", self.page_count()),
+        };
+        self.render_index(&pages, &data_manifest, &mut expected_files)?;
 
         if self.theme.has_album_template {
-            self.render_albums(&output_dir, &data_manifest, &mut expected_files)?;
+            self.render_albums(&pages, &data_manifest, &mut expected_files)?;
+            self.render_collections(&pages, &data_manifest, &mut expected_files)?;
         }
 
         if self.theme.has_photo_template {
-            self.render_photos(&output_dir, &data_manifest, &mut expected_files)?;
+            self.render_photos(&pages, &data_manifest, &mut expected_files)?;
+        }
+        self.write_redirects(&output_dir, &mut expected_files)?;
+
+        if self.config.archive {
+            if self.theme.has_archive_template {
+                self.render_archive(&pages, &data_manifest, &mut expected_files)?;
+            } else {
+                tracing::warn!("archive is enabled, but the theme has no archive.html template");
+            }
+        }
+
+        let reused = self.theme.reset_renders();
+        if reused > 0 {
+            tracing::debug!(reused, "reused identical page renders");
+        }
+        self.timings.stage("pages", started);
+        let unchanged = pages.unchanged.load(Ordering::Relaxed);
+        drop(pages.progress);
+        if unchanged > 0 {
+            tracing::debug!(unchanged, "skipped pages with unchanged inputs");
+        }
+        if !self.dry_run
+            && let Err(e) = cache.save()
+        {
+            tracing::warn!(error = %e, "failed to save metadata cache");
+        }
+
+        // Checksums are written last, but must survive cleanup
+        let started = Instant::now();
+        if let Some(config) = &self.config.checksums {
+            for name in checksums::outputs(config) {
+                expected_files.insert(output_dir.join(name));
+            }
         }
 
         // Clean up stale files from previous builds
@@ -294,11 +738,90 @@ impl Pipeline {
             tracing::info!(removed, "cleaned up stale files");
         }
 
+        if self.config.audit != AuditMode::Off {
+            self.audit_images(&images_dir)?;
+        }
+
+        if let Some(config) = &self.config.checksums {
+            let files = checksums::write(&output_dir, config, &self.site_dir)?;
+            tracing::info!(files, "wrote checksums");
+        }
+        if !self.dry_run
+            && let Err(e) = sizes::report(&self.site_dir, &output_dir)
+        {
+            tracing::warn!(error = %e, "failed to compare output sizes");
+        }
+        self.timings.stage("cleanup and checks", started);
+
+        if let Some(path) = &self.report {
+            let (started, clock) = build_started;
+            BuildReport {
+                version: self.version,
+                seconds: started.elapsed().as_secs_f64(),
+                pages: PageCounts {
+                    rendered: pages.rendered.load(Ordering::Relaxed),
+                    unchanged,
+                },
+                photos: self.stats,
+                bytes_written: build_report::bytes_written(&output_dir, clock),
+                stale_files_removed: removed,
+                stages: self.timings.report().stages,
+            }
+            .write(path)?;
+        }
+
         tracing::info!("build complete");
 
         Ok(())
     }
 
+    /// Write the asset and data manifests as JSON.
+    fn write_manifest(
+        &self,
+        path: &Path,
+        assets: &AssetManifest,
+        data: &DataManifest,
+    ) -> Result<()> {
+        let manifest = ManifestFile {
+            version: self.version,
+            assets,
+            data,
+        };
+        let json = serde_json::to_string_pretty(&manifest)
+            .map_err(|e| Error::Other(format!("failed to serialize manifest: {}", e)))?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, json)?;
+        tracing::debug!(path = %path.display(), assets = assets.len(), "wrote manifest");
+        Ok(())
+    }
+
+    /// Check published images for sensitive metadata the privacy settings should have removed.
+    fn audit_images(&self, images_dir: &Path) -> Result<()> {
+        let report = audit::run(images_dir, self.config.gps == GpsMode::On)?;
+        for (path, findings) in &report.flagged {
+            let findings = findings
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ");
+            tracing::warn!(file = %path.display(), %findings, "sensitive metadata in published image");
+        }
+        tracing::info!(
+            scanned = report.scanned,
+            flagged = report.flagged.len(),
+            "privacy audit complete"
+        );
+
+        if self.config.audit == AuditMode::Fail && !report.flagged.is_empty() {
+            return Err(Error::Audit {
+                flagged: report.flagged.len(),
+            });
+        }
+        Ok(())
+    }
+
     /// Copy static assets from theme to output, returning the asset manifest.
     fn copy_static(
         &self,
@@ -306,7 +829,10 @@ impl Pipeline {
         expected: &mut HashSet<PathBuf>,
     ) -> Result<AssetManifest> {
         let dest = output_dir.join("static");
-        let should_minify = self.config.minify;
+        let processing = AssetProcessing {
+            minify: self.config.minify,
+            optimize_png: self.config.optimize_png,
+        };
         let mut manifest = AssetManifest::new();
 
         match &self.theme.static_source {
@@ -317,14 +843,14 @@ impl Pipeline {
                     &dest,
                     "",
                     expected,
-                    should_minify,
+                    processing,
                     self.source_maps,
                     &mut manifest,
                 )?;
                 tracing::debug!(
                     from = %dir.display(),
                     to = %dest.display(),
-                    minify = should_minify,
+                    minify = processing.minify,
                     assets = manifest.len(),
                     "copied static assets"
                 );
@@ -371,9 +897,9 @@ impl Pipeline {
                             self.source_maps,
                             map_hashes.get(&map_name).map(String::as_str),
                         );
-                        process_static_file(name, transformed.as_bytes(), should_minify)?
+                        process_static_file(name, transformed.as_bytes(), processing)?
                     } else {
-                        process_static_file(name, file.contents(), should_minify)?
+                        process_static_file(name, file.contents(), processing)?
                     };
 
                     let hashed_name = hash_filename(name, &contents);
@@ -385,7 +911,7 @@ impl Pipeline {
                 }
                 tracing::debug!(
                     to = %dest.display(),
-                    minify = should_minify,
+                    minify = processing.minify,
                     assets = manifest.len(),
                     "copied embedded static assets"
                 );
@@ -399,12 +925,19 @@ impl Pipeline {
     /// Render the site index page.
     fn render_index(
         &self,
-        output_dir: &Path,
+        pages: &Pages,
         data_manifest: &DataManifest,
         expected: &mut HashSet<PathBuf>,
     ) -> Result<()> {
         let mut context = self.base_context(data_manifest);
         context.insert("root", &self.root);
+        context.insert("seo", &self.seo(None, None));
+        self.insert_page_urls(&mut context, "");
+        let site = self.site_context(None);
+        context.insert(
+            "json_ld",
+            &self.gallery_json_ld(&site.title, "", &self.root.all_photos_with_albums()),
+        );
 
         // Collect all photos with their paths pre-computed
         let all_photos: Vec<_> = self
@@ -413,110 +946,142 @@ impl Pipeline {
             .iter()
             .map(|p| {
                 let album_path = self.find_album_path_for_photo(p);
-                PhotoWithPaths {
-                    photo: (*p).clone(),
-                    image_path: p.image_path(&album_path),
-                    thumb_path: p.thumb_path(&album_path),
-                    micro_thumb_path: p.micro_thumb_path(&album_path),
-                    original_path: p.original_path(&album_path, self.config.gps),
-                    html_path: p.html_path(&album_path),
-                }
+                self.photo_with_paths(p, &album_path)
             })
             .collect();
         context.insert("photos", &all_photos);
 
-        let mut html = self.theme.templates.render(templates::INDEX, &context)?;
-        if self.config.minify {
-            html = minify::html(&html)?;
-        }
-
-        let dest = output_dir.join("index.html");
-        fs::write(&dest, html)?;
-        expected.insert(dest.clone());
-
-        tracing::debug!(path = %dest.display(), "rendered index");
+        let dest = pages.dir.join("index.html");
+        self.write_page(pages, templates::INDEX, &context, "index", None, &dest)?;
+        expected.insert(dest);
 
         Ok(())
     }
 
+    /// Pages a build renders, for the progress bar.
+    fn page_count(&self) -> usize {
+        let mut count = 1;
+        if self.theme.has_album_template {
+            count += self.root.album_count() + self.collections.len();
+        }
+        if self.theme.has_photo_template {
+            count += self.root.photo_count();
+        }
+        if self.config.archive && self.theme.has_archive_template {
+            count += 1;
+        }
+        count
+    }
+
     /// Render album pages (if album.html template exists).
     fn render_albums(
         &self,
-        output_dir: &Path,
+        pages: &Pages,
         data_manifest: &DataManifest,
         expected: &mut HashSet<PathBuf>,
     ) -> Result<()> {
-        self.render_album_recursive(&self.root, output_dir, data_manifest, true, expected)?;
+        self.render_album_recursive(&self.root, pages, data_manifest, true, expected)?;
         Ok(())
     }
 
     fn render_album_recursive(
         &self,
         album: &Album,
-        output_dir: &Path,
+        pages: &Pages,
         data_manifest: &DataManifest,
         is_root: bool,
         expected: &mut HashSet<PathBuf>,
     ) -> Result<()> {
         // Skip root album (it's handled by index.html)
         if !is_root {
-            let mut context = self.base_context(data_manifest);
-            context.insert("root", &self.root);
-            context.insert("album", album);
-
-            // Add photos with pre-computed paths
-            let photos_with_paths: Vec<_> = album
-                .photos
-                .iter()
-                .map(|p| PhotoWithPaths {
-                    photo: p.clone(),
-                    image_path: p.image_path(&album.path),
-                    thumb_path: p.thumb_path(&album.path),
-                    micro_thumb_path: p.micro_thumb_path(&album.path),
-                    original_path: p.original_path(&album.path, self.config.gps),
-                    html_path: p.html_path(&album.path),
-                })
-                .collect();
-            context.insert("photos", &photos_with_paths);
-
-            let mut html = self.theme.templates.render(templates::ALBUM, &context)?;
-            if self.config.minify {
-                html = minify::html(&html)?;
-            }
-
-            let album_dir = output_dir.join(&album.path);
-            fs::create_dir_all(&album_dir)?;
-
-            let dest = album_dir.join("index.html");
-            fs::write(&dest, html)?;
-            expected.insert(dest.clone());
-
-            tracing::debug!(album = %album.name, path = %dest.display(), "rendered album");
+            let sources = vec![album; album.photos.len()];
+            self.render_album_page(album, &sources, pages, data_manifest, expected)?;
         }
 
         // Recurse into children
         for child in &album.children {
-            self.render_album_recursive(child, output_dir, data_manifest, false, expected)?;
+            self.render_album_recursive(child, pages, data_manifest, false, expected)?;
         }
 
         Ok(())
     }
 
+    /// Render a collection's page with the album template.
+    fn render_collections(
+        &self,
+        pages: &Pages,
+        data_manifest: &DataManifest,
+        expected: &mut HashSet<PathBuf>,
+    ) -> Result<()> {
+        for collection in &self.collections {
+            let sources: Vec<_> = collection.sources.iter().collect();
+            self.render_album_page(&collection.album, &sources, pages, data_manifest, expected)?;
+        }
+        Ok(())
+    }
+
+    /// Render the page of `album`, whose photos are each in the album at the
+    /// same index of `sources`, where their images are.
+    fn render_album_page(
+        &self,
+        album: &Album,
+        sources: &[&Album],
+        pages: &Pages,
+        data_manifest: &DataManifest,
+        expected: &mut HashSet<PathBuf>,
+    ) -> Result<()> {
+        let mut context = self.base_context(data_manifest);
+        context.insert("root", &self.root);
+        context.insert("album", album);
+        context.insert("album_feed", &data_manifest.album_feed(album));
+        context.insert("seo", &self.seo(Some(album), None));
+        let album_page = format!("{}/", url_encode_path(&album.path.to_string_lossy()));
+        self.insert_page_urls(&mut context, &album_page);
+        let album_photos: Vec<_> = sources.iter().copied().zip(&album.photos).collect();
+        context.insert(
+            "json_ld",
+            &self.gallery_json_ld(&album.name, &album_page, &album_photos),
+        );
+
+        // Add photos with pre-computed paths
+        let photos_with_paths: Vec<_> = album_photos
+            .iter()
+            .map(|(source, p)| self.photo_with_paths(p, &source.path))
+            .collect();
+        context.insert("photos", &photos_with_paths);
+
+        let comments = self.comments(PageKind::Album, &format!("/{}", album_page));
+        context.insert("comments", &comments);
+
+        let page = format!("album '{}'", album.path.display());
+        let dest = pages.dir.join(&album.path).join("index.html");
+        self.write_page(
+            pages,
+            templates::ALBUM,
+            &context,
+            &page,
+            comments.as_ref(),
+            &dest,
+        )?;
+        expected.insert(dest);
+        Ok(())
+    }
+
     /// Render individual photo pages (if photo.html template exists).
     fn render_photos(
         &self,
-        output_dir: &Path,
+        pages: &Pages,
         data_manifest: &DataManifest,
         expected: &mut HashSet<PathBuf>,
     ) -> Result<()> {
-        self.render_photos_in_album(&self.root, output_dir, data_manifest, expected)?;
+        self.render_photos_in_album(&self.root, pages, data_manifest, expected)?;
         Ok(())
     }
 
     fn render_photos_in_album(
         &self,
         album: &Album,
-        output_dir: &Path,
+        pages: &Pages,
         data_manifest: &DataManifest,
         expected: &mut HashSet<PathBuf>,
     ) -> Result<()> {
@@ -529,94 +1094,411 @@ impl Pipeline {
             let mut context = self.base_context(data_manifest);
             context.insert("root", &self.root);
             context.insert("album", album);
+            context.insert("album_feed", &data_manifest.album_feed(album));
 
             // Current photo with paths
-            let photo_ctx = PhotoWithPaths {
-                photo: photo.clone(),
-                image_path: photo.image_path(&album.path),
-                thumb_path: photo.thumb_path(&album.path),
-                micro_thumb_path: photo.micro_thumb_path(&album.path),
-                original_path: photo.original_path(&album.path, self.config.gps),
-                html_path: photo.html_path(&album.path),
-            };
+            let photo_ctx = self.photo_with_paths(photo, &album.path);
             context.insert("photo", &photo_ctx);
+            context.insert("seo", &self.seo(Some(album), Some(photo)));
+            self.insert_page_urls(&mut context, &photo_ctx.html_path);
+            context.insert("json_ld", &self.photo_json_ld(album, photo));
 
             // Prev/next with paths
             if let Some(p) = prev_photo {
-                context.insert(
-                    "prev_photo",
-                    &PhotoWithPaths {
-                        photo: p.clone(),
-                        image_path: p.image_path(&album.path),
-                        thumb_path: p.thumb_path(&album.path),
-                        micro_thumb_path: p.micro_thumb_path(&album.path),
-                        original_path: p.original_path(&album.path, self.config.gps),
-                        html_path: p.html_path(&album.path),
-                    },
-                );
+                context.insert("prev_photo", &self.photo_with_paths(p, &album.path));
             }
             if let Some(p) = next_photo {
-                context.insert(
-                    "next_photo",
-                    &PhotoWithPaths {
-                        photo: p.clone(),
-                        image_path: p.image_path(&album.path),
-                        thumb_path: p.thumb_path(&album.path),
-                        micro_thumb_path: p.micro_thumb_path(&album.path),
-                        original_path: p.original_path(&album.path, self.config.gps),
-                        html_path: p.html_path(&album.path),
-                    },
-                );
+                context.insert("next_photo", &self.photo_with_paths(p, &album.path));
             }
 
-            let mut html = self.theme.templates.render(templates::PHOTO, &context)?;
-            if self.config.minify {
-                html = minify::html(&html)?;
-            }
+            let comments = self.comments(PageKind::Photo, &format!("/{}", photo_ctx.html_path));
+            context.insert("comments", &comments);
+
+            let page = format!("photo '{}'", album.path.join(&photo.stem).display());
+            let dest = pages
+                .dir
+                .join(&album.path)
+                .join(format!("{}.html", photo.stem));
+            self.write_page(
+                pages,
+                templates::PHOTO,
+                &context,
+                &page,
+                comments.as_ref(),
+                &dest,
+            )?;
+            expected.insert(dest);
+        }
 
-            // Determine output path
-            let dest = if album.path.as_os_str().is_empty() {
-                output_dir.join(format!("{}.html", photo.stem))
-            } else {
-                let album_dir = output_dir.join(&album.path);
-                fs::create_dir_all(&album_dir)?;
-                album_dir.join(format!("{}.html", photo.stem))
-            };
+        // Recurse into children
+        for child in &album.children {
+            self.render_photos_in_album(child, pages, data_manifest, expected)?;
+        }
 
-            fs::write(&dest, html)?;
-            expected.insert(dest.clone());
+        Ok(())
+    }
 
-            tracing::trace!(photo = %photo.stem, path = %dest.display(), "rendered photo");
+    /// Write a page at each album alias that redirects to the album's page,
+    /// and one for each of its photo pages.
+    fn write_redirects(&self, output_dir: &Path, expected: &mut HashSet<PathBuf>) -> Result<()> {
+        fn collect<'a>(album: &'a Album, albums: &mut Vec<&'a Album>) {
+            albums.push(album);
+            for child in &album.children {
+                collect(child, albums);
+            }
         }
+        let mut albums = Vec::new();
+        collect(&self.root, &mut albums);
+
+        let mut written = 0;
+        for album in albums {
+            for alias in &album.aliases {
+                let mut redirects = Vec::new();
+                if self.theme.has_album_template {
+                    let target = format!("/{}/", url_encode_path(&album.path.to_string_lossy()));
+                    redirects.push((alias.join("index.html"), target));
+                }
+                if self.theme.has_photo_template {
+                    for photo in &album.photos {
+                        let dest = alias.join(format!("{}.html", photo.stem));
+                        redirects.push((dest, format!("/{}", photo.html_path(&album.path))));
+                    }
+                }
+                for (dest, target) in redirects {
+                    let dest = output_dir.join(dest);
+                    if let Some(parent) = dest.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+                    fs::write(&dest, redirect_page(&self.base_url(), &target))?;
+                    expected.insert(dest);
+                    written += 1;
+                }
+            }
+        }
+        if written > 0 {
+            tracing::debug!(pages = written, "wrote redirects from album aliases");
+        }
+        Ok(())
+    }
 
-        // Recurse into children
-        for child in &album.children {
-            self.render_photos_in_album(child, output_dir, data_manifest, expected)?;
+    /// Render the archive page of albums grouped by year.
+    fn render_archive(
+        &self,
+        pages: &Pages,
+        data_manifest: &DataManifest,
+        expected: &mut HashSet<PathBuf>,
+    ) -> Result<()> {
+        if self
+            .root
+            .children
+            .iter()
+            .flat_map(|album| std::iter::once(&album.path).chain(&album.aliases))
+            .any(|path| path == Path::new(ARCHIVE_DIR))
+        {
+            return Err(Error::Other(format!(
+                "album '{}' conflicts with the archive page; rename it or set archive = false",
+                ARCHIVE_DIR
+            )));
+        }
+
+        let mut context = self.base_context(data_manifest);
+        context.insert("root", &self.root);
+        context.insert("seo", &self.seo(None, None));
+        context.insert("json_ld", &None::<String>);
+        self.insert_page_urls(&mut context, &format!("{}/", ARCHIVE_DIR));
+        context.insert("archive", &archive::years(&self.root));
+
+        let dest = pages.dir.join(ARCHIVE_DIR).join("index.html");
+        self.write_page(pages, templates::ARCHIVE, &context, "archive", None, &dest)?;
+        expected.insert(dest);
+
+        Ok(())
+    }
+
+    /// Render a page to `dest`, unless an earlier build wrote it from the
+    /// same inputs and it's still there.
+    fn write_page(
+        &self,
+        pages: &Pages,
+        template: &str,
+        context: &Context,
+        page: &str,
+        comments: Option<&Comments>,
+        dest: &Path,
+    ) -> Result<()> {
+        // Keyed by the whole path, since sites can be built to several directories
+        let key = dest.to_string_lossy().into_owned();
+        // Comments and analytics are added after rendering, whether or not
+        // the template reads them
+        let fingerprint = self.theme.render_key(template, context).map(|render| {
+            let mut hasher = blake3::Hasher::new();
+            hasher.update(pages.fingerprint.as_bytes());
+            hasher.update(render.as_bytes());
+            hasher.update(self.analytics().unwrap_or_default().as_bytes());
+            if let Some(comments) = comments {
+                hasher.update(comments.html.as_bytes());
+            }
+            hasher.finalize().to_hex().to_string()
+        });
+        if let Some(fingerprint) = &fingerprint
+            && dest.exists()
+            && pages.cache.page_unchanged(&key, fingerprint)
+        {
+            pages.unchanged.fetch_add(1, Ordering::Relaxed);
+            pages.tick();
+            tracing::trace!(page, "unchanged");
+            return Ok(());
         }
 
+        let mut html = self.theme.render(template, context, page)?;
+        html = self.inject_comments(html, comments);
+        html = self.inject_analytics(html);
+        if self.config.minify {
+            html = minify::html(&html)?;
+        }
+        if let Some(dir) = dest.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(dest, html)?;
+        pages.rendered.fetch_add(1, Ordering::Relaxed);
+        pages.tick();
+        if let Some(fingerprint) = fingerprint {
+            pages.cache.set_page(key, fingerprint);
+        }
+        tracing::trace!(page, path = %dest.display(), "rendered");
         Ok(())
     }
 
+    /// Hash of what every page depends on besides its context: the theme's
+    /// templates, the assets `static()` links to, and the settings applied
+    /// to rendered pages.
+    fn pages_fingerprint(&self, assets: &AssetManifest) -> blake3::Hash {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(env!("GIT_VERSION").as_bytes());
+        for (name, source) in &self.theme.sources {
+            hasher.update(name.as_bytes());
+            hasher.update(b"\0");
+            hasher.update(source.as_bytes());
+            hasher.update(b"\0");
+        }
+        for (name, path) in assets {
+            hasher.update(format!("{}={}\n", name, path).as_bytes());
+        }
+        hasher.update(
+            format!(
+                "minify={} autoescape={} comments={:?}\n",
+                self.config.minify, self.config.autoescape, self.config.comments
+            )
+            .as_bytes(),
+        );
+        hasher.update(functions::fingerprint(&self.config.functions).as_bytes());
+        hasher.finalize()
+    }
+
+    /// Comment thread for a page of `kind` at `page_path`, if enabled for it.
+    fn comments(&self, kind: PageKind, page_path: &str) -> Option<Comments> {
+        let config = self.config.comments.as_ref()?;
+        if !config.pages.contains(&kind) {
+            return None;
+        }
+        Some(comments::embed(
+            &config.provider,
+            page_path,
+            &self.config.default_lang(),
+        ))
+    }
+
+    /// Insert a comment thread into a rendered page, unless the theme placed it already.
+    fn inject_comments(&self, html: String, comments: Option<&Comments>) -> String {
+        let Some(comments) = comments else {
+            return html;
+        };
+        let enabled = self.config.comments.as_ref().is_some_and(|c| c.inject);
+        if !enabled || html.contains(comments::MARKER) {
+            return html;
+        }
+        inject::inject(&html, &comments.html, Position::Content)
+    }
+
+    /// URL path of a photo's full-size image, or its thumbnail in lite builds.
+    fn image_path(&self, photo: &Photo, album_path: &Path) -> String {
+        if self.config.lite {
+            photo.thumb_path(album_path)
+        } else {
+            photo.image_path(album_path)
+        }
+    }
+
+    /// URL path of a photo's double-size thumbnail, if it's generated.
+    fn thumb_2x_path(&self, photo: &Photo, album_path: &Path) -> Option<String> {
+        self.config
+            .processing
+            .thumb_2x
+            .then(|| photo.thumb_2x_path(album_path))
+    }
+
+    /// URL paths of a photo's data saver variants, if they're generated.
+    fn data_saver_paths(&self, photo: &Photo, album_path: &Path) -> Option<VariantPaths> {
+        if !self.config.data_saver {
+            return None;
+        }
+        let thumb_path = photo.saver_thumb_path(album_path);
+        let image_path = if self.config.lite {
+            thumb_path.clone()
+        } else {
+            photo.saver_image_path(album_path)
+        };
+        Some(VariantPaths {
+            image_path,
+            thumb_path,
+        })
+    }
+
+    /// URL paths of a photo's AVIF variants, if they're generated (not for
+    /// animated or lossless photos).
+    fn avif_paths(&self, photo: &Photo, album_path: &Path) -> Option<VariantPaths> {
+        if self.config.avif.is_none() || photo.animated || photo.lossless {
+            return None;
+        }
+        let thumb_path = photo.avif_thumb_path(album_path);
+        let image_path = if self.config.lite {
+            thumb_path.clone()
+        } else {
+            photo.avif_image_path(album_path)
+        };
+        Some(VariantPaths {
+            image_path,
+            thumb_path,
+        })
+    }
+
+    /// Whether a photo's original is linked: not for RAW files and videos,
+    /// or at all when originals aren't published.
+    fn links_original(&self, photo: &Photo) -> bool {
+        !self.config.protect_images && !photo.is_raw() && !photo.is_video()
+    }
+
+    /// URL path of a photo's original on this site, unless it isn't linked
+    /// or `[originals]` hosts it elsewhere.
+    fn original_path(&self, photo: &Photo, album_path: &Path) -> Option<String> {
+        (self.links_original(photo) && self.config.originals.is_none())
+            .then(|| photo.original_path(album_path))
+    }
+
+    /// Link to a photo's original, on this site or where `[originals]` hosts it.
+    fn original_url(&self, photo: &Photo, album_path: &Path) -> Option<String> {
+        if !self.links_original(photo) {
+            return None;
+        }
+        Some(match &self.config.originals {
+            Some(config) => originals::url(config, &self.photos_dir(), photo),
+            None => format!("/{}", photo.original_path(album_path)),
+        })
+    }
+
+    fn photos_dir(&self) -> PathBuf {
+        self.site_dir.join(&self.config.photos)
+    }
+
+    /// URL path of a published video clip.
+    fn video_path(&self, photo: &Photo, album_path: &Path) -> Option<String> {
+        photo.is_video().then(|| photo.video_path(album_path))
+    }
+
+    /// Responsive widths generated for a photo (none when building lite or
+    /// for animated photos).
+    fn widths(&self, photo: &Photo) -> Vec<u32> {
+        if self.config.lite || photo.animated {
+            return Vec::new();
+        }
+        processing::ladder(&self.config.processing.widths, photo.width)
+    }
+
+    /// URL paths of a photo's responsive widths, narrowest first.
+    fn width_paths(&self, photo: &Photo, album_path: &Path) -> Vec<WidthPath> {
+        self.widths(photo)
+            .into_iter()
+            .map(|width| WidthPath {
+                width,
+                path: photo.width_path(album_path, width),
+            })
+            .collect()
+    }
+
+    /// `srcset` value for a photo's responsive widths and its full-size image
+    /// if that's wider: `/images/a-w480.webp 480w, ...`
+    fn srcset(&self, photo: &Photo, album_path: &Path, widths: &[WidthPath]) -> Option<String> {
+        let widest = widths.last()?.width;
+        let mut candidates: Vec<String> = widths
+            .iter()
+            .map(|w| format!("/{} {}w", w.path, w.width))
+            .collect();
+        let full = Encodings::new(&self.config.processing).full;
+        let full_width = processing::fitted_width(photo.width, photo.height, full.size);
+        if full_width > widest {
+            candidates.push(format!(
+                "/{} {}w",
+                self.image_path(photo, album_path),
+                full_width
+            ));
+        }
+        Some(candidates.join(", "))
+    }
+
+    /// A photo with every path templates need.
+    fn photo_with_paths(&self, photo: &Photo, album_path: &Path) -> PhotoWithPaths {
+        let widths = self.width_paths(photo, album_path);
+        PhotoWithPaths {
+            photo: photo.clone(),
+            image_path: self.image_path(photo, album_path),
+            thumb_path: photo.thumb_path(album_path),
+            thumb_2x_path: self.thumb_2x_path(photo, album_path),
+            micro_thumb_path: photo.micro_thumb_path(album_path),
+            original_path: self.original_path(photo, album_path),
+            original_url: self.original_url(photo, album_path),
+            video_path: self.video_path(photo, album_path),
+            data_saver: self.data_saver_paths(photo, album_path),
+            avif: self.avif_paths(photo, album_path),
+            srcset: self.srcset(photo, album_path, &widths),
+            widths,
+            html_path: photo.html_path(album_path),
+        }
+    }
+
+    /// Analytics script tag, if configured.
+    fn analytics(&self) -> Option<String> {
+        let config = self.config.analytics.as_ref()?;
+        Some(analytics::script(config, &self.config.domain))
+    }
+
+    /// Insert the analytics script into a rendered page, unless the theme included it already.
+    fn inject_analytics(&self, html: String) -> String {
+        match self.analytics() {
+            Some(script) if !html.contains(analytics::MARKER) => {
+                inject::inject(&html, &script, Position::Head)
+            }
+            _ => html,
+        }
+    }
+
     /// Create base context with site info and data URLs.
     fn base_context(&self, data_manifest: &DataManifest) -> Context {
         let mut context = Context::new();
-        context.insert(
-            "site",
-            &SiteContext {
-                title: self
-                    .config
-                    .title
-                    .clone()
-                    .unwrap_or_else(|| self.config.domain.clone()),
-                domain: self.config.domain.clone(),
-                version: VERSION,
-            },
-        );
+        let stats = BuildStats {
+            built_at: self.built_at,
+            photo_count: self.root.photo_count(),
+            album_count: self.root.album_count(),
+            total_size: self.root.all_photos().iter().map(|p| p.original_size).sum(),
+        };
+        context.insert("site", &self.site_context(Some(stats)));
 
         // Add data file URLs for async loading
         context.insert("data_urls", data_manifest);
         context.insert("languages", &self.config.languages());
         context.insert("default_lang", &self.config.default_lang());
+        context.insert("analytics", &self.analytics());
+        context.insert("protect_images", &self.config.protect_images);
+        context.insert("lite", &self.config.lite);
 
         // Add theme configuration for frontend
         context.insert("theme_config", &self.theme_config_json());
@@ -624,6 +1506,116 @@ impl Pipeline {
         context
     }
 
+    /// Add the page's canonical URL and its language alternates to a context.
+    ///
+    /// Pages are translated in the browser, so each language's URL is the
+    /// page with `?lang=` set, which the built-in themes read.
+    fn insert_page_urls(&self, context: &mut Context, page_path: &str) {
+        let canonical = format!("{}/{}", self.base_url(), page_path);
+        let languages = self.config.languages();
+        let alternates: Vec<AlternateUrl> = if languages.len() > 1 {
+            languages
+                .iter()
+                .map(|lang| AlternateUrl {
+                    hreflang: lang.code.replace('_', "-"),
+                    href: format!("{}?lang={}", canonical, lang.code),
+                })
+                .chain(std::iter::once(AlternateUrl {
+                    hreflang: "x-default".to_string(),
+                    href: canonical.clone(),
+                }))
+                .collect()
+        } else {
+            Vec::new()
+        };
+        context.insert("canonical_url", &canonical);
+        context.insert("alternate_urls", &alternates);
+    }
+
+    /// schema.org `ImageGallery` JSON-LD for an index or album page, if enabled.
+    fn gallery_json_ld(
+        &self,
+        name: &str,
+        page_path: &str,
+        photos: &[(&Album, &Photo)],
+    ) -> Option<String> {
+        if !self.config.seo.structured_data {
+            return None;
+        }
+        let media: Vec<_> = photos
+            .iter()
+            .take(jsonld::MAX_GALLERY_MEDIA)
+            .map(|(album, photo)| self.json_ld_media(album, photo))
+            .collect();
+        let url = format!("{}/{}", self.base_url(), page_path);
+        Some(jsonld::gallery(name, &url, &media))
+    }
+
+    /// schema.org `Photograph` JSON-LD for a photo page, if enabled.
+    ///
+    /// The photo is part of its album's page, or the index for the root
+    /// album and themes without album pages.
+    fn photo_json_ld(&self, album: &Album, photo: &Photo) -> Option<String> {
+        if !self.config.seo.structured_data {
+            return None;
+        }
+        let (gallery_name, gallery_path) =
+            if self.theme.has_album_template && !album.path.as_os_str().is_empty() {
+                (
+                    album.name.clone(),
+                    format!("{}/", url_encode_path(&album.path.to_string_lossy())),
+                )
+            } else {
+                (self.site_context(None).title, String::new())
+            };
+        let gallery_url = format!("{}/{}", self.base_url(), gallery_path);
+        Some(jsonld::photograph(
+            &self.json_ld_media(album, photo),
+            &gallery_name,
+            &gallery_url,
+            self.config.gps,
+        ))
+    }
+
+    /// A photo's absolute URLs for JSON-LD.
+    fn json_ld_media<'a>(&self, album: &Album, photo: &'a Photo) -> jsonld::Media<'a> {
+        let base_url = self.base_url();
+        jsonld::Media {
+            photo,
+            page_url: format!("{}/{}", base_url, self.page_path(album, photo)),
+            image_url: format!("{}/{}", base_url, self.image_path(photo, &album.path)),
+            thumbnail_url: format!("{}/{}", base_url, photo.thumb_path(&album.path)),
+        }
+    }
+
+    /// Title and description of a page from the `[seo]` templates.
+    fn seo(&self, album: Option<&Album>, photo: Option<&Photo>) -> seo::Seo {
+        let site = self.site_context(None);
+        seo::render(
+            &self.config.seo,
+            &Page {
+                site: &site.title,
+                // The root album's name isn't shown anywhere
+                album: album.filter(|album| !album.path.as_os_str().is_empty()),
+                photo,
+            },
+        )
+    }
+
+    /// Site details for templates and gallery JSON.
+    fn site_context(&self, build: Option<BuildStats>) -> SiteContext {
+        SiteContext {
+            title: self
+                .config
+                .title
+                .clone()
+                .unwrap_or_else(|| self.config.domain.clone()),
+            domain: self.config.domain.clone(),
+            version: self.version,
+            build,
+        }
+    }
+
     /// Convert theme config to JSON-compatible format for template embedding.
     fn theme_config_json(&self) -> serde_json::Value {
         let json_map: serde_json::Map<String, serde_json::Value> = self
@@ -675,20 +1667,49 @@ impl Pipeline {
         };
 
         for photo in &album.photos {
-            // Matches processing.rs naming: {stem}-{hash}-{variant}.{ext}
-            expected
-                .insert(album_images_dir.join(format!("{}-{}-micro.webp", photo.stem, photo.hash)));
-            expected
-                .insert(album_images_dir.join(format!("{}-{}-thumb.webp", photo.stem, photo.hash)));
-            expected
-                .insert(album_images_dir.join(format!("{}-{}-full.webp", photo.stem, photo.hash)));
-            expected.insert(album_images_dir.join(format!(
-                "{}-{}-original{}.{}",
-                photo.stem,
-                photo.hash,
-                self.config.gps.original_suffix(),
-                photo.extension
-            )));
+            let mut variants = vec![Variant::Micro, Variant::Thumb];
+            if self.config.processing.thumb_2x {
+                variants.push(Variant::Thumb2x);
+            }
+            if !self.config.lite {
+                variants.push(Variant::Full);
+            }
+            if self.config.data_saver {
+                variants.push(Variant::ThumbSaver);
+                if !self.config.lite {
+                    variants.push(Variant::FullSaver);
+                }
+            }
+            if self.config.avif.is_some() && !photo.animated && !photo.lossless {
+                variants.push(Variant::AvifThumb);
+                if !self.config.lite {
+                    variants.push(Variant::AvifFull);
+                }
+            }
+            variants.extend(self.widths(photo).into_iter().map(Variant::Width));
+            for variant in variants {
+                expected.insert(album_images_dir.join(photo.variant_file_name(variant)));
+            }
+            if photo.is_video() {
+                expected.insert(album_images_dir.join(photo.video_file_name()));
+            } else if self.original_path(photo, &album.path).is_some() {
+                expected.insert(album_images_dir.join(format!(
+                    "{}-{}-original.{}",
+                    photo.stem,
+                    photo.hash,
+                    photo.original_extension()
+                )));
+            }
+        }
+
+        if let Some((cover_album, photo)) = album.cover_photo() {
+            for &ratio in self.config.crops.values() {
+                expected.insert(
+                    images_dir
+                        .join(&cover_album.path)
+                        .join(photo.variant_file_name(Variant::Crop(ratio))),
+                );
+            }
         }
 
         for child in &album.children {
@@ -707,9 +1728,9 @@ impl Pipeline {
         let i18n_dir = static_dir.join("i18n");
         fs::create_dir_all(&i18n_dir)?;
 
-        let all_translations = i18n::get_all_translations();
+        let all_translations = i18n::get_all_translations(&self.countries);
         for (lang_code, translations) in &all_translations {
-            let lang_json = serde_json::to_string(translations).map_err(|e| {
+            let lang_json = canonical_json(translations).map_err(|e| {
                 Error::Other(format!("failed to serialize i18n for {}: {}", lang_code, e))
             })?;
             let lang_hash = &blake3::hash(lang_json.as_bytes()).to_hex()[..8];
@@ -723,8 +1744,10 @@ impl Pipeline {
         }
 
         // Generate gallery JSON (photos and albums)
-        let gallery_data = self.build_gallery_data();
-        let gallery_json = serde_json::to_string(&gallery_data)
+        // Sorted keys, so the URL only changes with the content
+        let gallery = serde_json::to_value(self.build_gallery_data())
+            .map_err(|e| Error::Other(format!("failed to serialize gallery: {}", e)))?;
+        let gallery_json = canonical_json(&gallery)
             .map_err(|e| Error::Other(format!("failed to serialize gallery: {}", e)))?;
         let gallery_hash = &blake3::hash(gallery_json.as_bytes()).to_hex()[..8];
         let gallery_filename = format!("gallery-{}.json", gallery_hash);
@@ -733,6 +1756,24 @@ impl Pipeline {
         expected.insert(gallery_path);
         manifest.gallery = format!("/static/{}", gallery_filename);
 
+        // Deltas from the galleries earlier builds published, which the
+        // manifest they wrote names
+        if let Some(config) = &self.config.manifest
+            && config.gallery_deltas > 0
+        {
+            let output_dir = self.site_dir.join(&self.config.build);
+            manifest.gallery_deltas = gallery_delta::publish(
+                &output_dir,
+                &output_dir.join(&config.path),
+                &gallery,
+                gallery_hash,
+                config.gallery_deltas,
+            )?;
+            for delta in &manifest.gallery_deltas {
+                expected.insert(output_dir.join(delta.url.trim_start_matches('/')));
+            }
+        }
+
         tracing::debug!(
             i18n_languages = manifest.i18n.len(),
             gallery = %manifest.gallery,
@@ -742,17 +1783,180 @@ impl Pipeline {
         Ok(manifest)
     }
 
+    /// Write the site feeds, album feeds, and ActivityPub documents, recording
+    /// their URLs in the manifest.
+    fn generate_feeds(
+        &self,
+        output_dir: &Path,
+        manifest: &mut DataManifest,
+        expected: &mut HashSet<PathBuf>,
+    ) -> Result<()> {
+        let domain = self.config.domain.trim_end_matches('/');
+        let base_url = self.base_url();
+        let title = self
+            .config
+            .title
+            .clone()
+            .unwrap_or_else(|| self.config.domain.clone());
+        let items = self.feed_items(&self.root, &base_url);
+
+        if self.config.feed.site {
+            let url = format!("/{}", feed::FILE_NAME);
+            let channel = feed::Channel {
+                title: &title,
+                description: &title,
+                link: &format!("{}/", base_url),
+                feed_url: &format!("{}{}", base_url, url),
+            };
+            let dest = output_dir.join(feed::FILE_NAME);
+            fs::write(&dest, feed::rss(&channel, &items))?;
+            expected.insert(dest);
+            manifest.feed = Some(url);
+        }
+
+        if self.config.feed.json {
+            let url = format!("/{}", feed::JSON_FILE_NAME);
+            let channel = feed::Channel {
+                title: &title,
+                description: &title,
+                link: &format!("{}/", base_url),
+                feed_url: &format!("{}{}", base_url, url),
+            };
+            let dest = output_dir.join(feed::JSON_FILE_NAME);
+            write_json(&dest, &feed::json(&channel, &items))?;
+            expected.insert(dest);
+            manifest.json_feed = Some(url);
+        }
+
+        if let Some(config) = &self.config.activitypub {
+            let urls = activitypub::Urls::new(&base_url);
+
+            let webfinger = output_dir.join(activitypub::WEBFINGER_PATH);
+            fs::create_dir_all(webfinger.parent().unwrap_or(output_dir))?;
+            write_json(&webfinger, &activitypub::webfinger(config, domain, &urls))?;
+            expected.insert(webfinger);
+
+            let actor = output_dir.join(activitypub::ACTOR_FILE_NAME);
+            write_json(&actor, &activitypub::actor(config, &title, &urls))?;
+            expected.insert(actor);
+
+            let outbox = output_dir.join(activitypub::OUTBOX_FILE_NAME);
+            write_json(&outbox, &activitypub::outbox(&urls, &items))?;
+            expected.insert(outbox);
+
+            manifest.actor = Some(format!("/{}", activitypub::ACTOR_FILE_NAME));
+        }
+
+        self.generate_album_feeds(
+            &self.root, output_dir, &base_url, &title, manifest, expected,
+        )?;
+
+        tracing::debug!(
+            site = manifest.feed.is_some(),
+            json = manifest.json_feed.is_some(),
+            activitypub = manifest.actor.is_some(),
+            albums = manifest.album_feeds.len(),
+            items = items.len(),
+            "generated feeds"
+        );
+
+        Ok(())
+    }
+
+    fn generate_album_feeds(
+        &self,
+        album: &Album,
+        output_dir: &Path,
+        base_url: &str,
+        site_title: &str,
+        manifest: &mut DataManifest,
+        expected: &mut HashSet<PathBuf>,
+    ) -> Result<()> {
+        for child in &album.children {
+            if self.config.feed.albums.includes(&child.path) {
+                let album_url = url_encode_path(&child.path.to_string_lossy());
+                let url = format!("/{}/{}", album_url, feed::FILE_NAME);
+                let link = if self.theme.has_album_template {
+                    format!("{}/{}/", base_url, album_url)
+                } else {
+                    format!("{}/", base_url)
+                };
+                let channel = feed::Channel {
+                    title: &format!("{}: {}", site_title, child.name),
+                    description: &child.name,
+                    link: &link,
+                    feed_url: &format!("{}{}", base_url, url),
+                };
+
+                let album_dir = output_dir.join(&child.path);
+                fs::create_dir_all(&album_dir)?;
+                let dest = album_dir.join(feed::FILE_NAME);
+                let items = self.feed_items(child, base_url);
+                fs::write(&dest, feed::rss(&channel, &items))?;
+                expected.insert(dest);
+                manifest.album_feeds.insert(album_url, url);
+            }
+
+            self.generate_album_feeds(child, output_dir, base_url, site_title, manifest, expected)?;
+        }
+
+        Ok(())
+    }
+
+    /// The newest photos in `album` and its descendants, newest first.
+    fn feed_items(&self, album: &Album, base_url: &str) -> Vec<feed::Item> {
+        let mut items = Vec::new();
+        self.collect_feed_items(album, base_url, &mut items);
+        feed::newest(items, self.config.feed.limit)
+    }
+
+    fn collect_feed_items(&self, album: &Album, base_url: &str, items: &mut Vec<feed::Item>) {
+        for photo in &album.photos {
+            items.push(feed::Item {
+                title: photo
+                    .metadata
+                    .title
+                    .clone()
+                    .unwrap_or_else(|| photo.stem.clone()),
+                link: format!("{}/{}", base_url, self.page_path(album, photo)),
+                image: format!("{}/{}", base_url, self.image_path(photo, &album.path)),
+                description: photo.metadata.description.clone(),
+                tags: photo.metadata.keywords.clone(),
+                published: photo
+                    .metadata
+                    .date_taken
+                    .as_deref()
+                    .and_then(util::parse_exif_time),
+            });
+        }
+
+        for child in &album.children {
+            self.collect_feed_items(child, base_url, items);
+        }
+    }
+
+    /// Absolute URL of the site root, without a trailing slash (e.g., "https://example.com").
+    pub fn base_url(&self) -> String {
+        format!("https://{}", self.config.domain.trim_end_matches('/'))
+    }
+
+    /// URL path of the most specific page the theme renders for a photo.
+    ///
+    /// The photo's own page if the theme has one, otherwise its album page,
+    /// otherwise the index (an empty path).
+    pub fn page_path(&self, album: &Album, photo: &Photo) -> String {
+        if self.theme.has_photo_template {
+            photo.html_path(&album.path)
+        } else if self.theme.has_album_template && !album.path.as_os_str().is_empty() {
+            format!("{}/", url_encode_path(&album.path.to_string_lossy()))
+        } else {
+            String::new()
+        }
+    }
+
     /// Build gallery data structure for JSON serialization.
     fn build_gallery_data(&self) -> GalleryData {
-        let site = SiteContext {
-            title: self
-                .config
-                .title
-                .clone()
-                .unwrap_or_else(|| self.config.domain.clone()),
-            domain: self.config.domain.clone(),
-            version: VERSION,
-        };
+        let site = self.site_context(None);
 
         // Recursively collect all albums (excluding root)
         fn collect_all_albums(album: &Album) -> Vec<AlbumData> {
@@ -763,6 +1967,7 @@ impl Pipeline {
                     slug: child.slug.clone(),
                     path: url_encode_path(&child.path.to_string_lossy()),
                     photo_count: child.photo_count(),
+                    stats: (&child.stats).into(),
                 });
                 result.extend(collect_all_albums(child));
             }
@@ -777,26 +1982,61 @@ impl Pipeline {
             .iter()
             .map(|p| {
                 let album_path = self.find_album_path_for_photo(p);
+                let widths = self.width_paths(p, &album_path);
                 PhotoData {
                     stem: p.stem.clone(),
                     hash: p.hash.clone(),
+                    media_type: p.media_type,
+                    animated: p.animated,
                     width: p.width,
                     height: p.height,
                     original_size: p.original_size,
-                    image_path: p.image_path(&album_path),
+                    image_path: self.image_path(p, &album_path),
                     thumb_path: p.thumb_path(&album_path),
+                    thumb_2x_path: self.thumb_2x_path(p, &album_path),
                     micro_thumb_path: p.micro_thumb_path(&album_path),
-                    original_path: p.original_path(&album_path, self.config.gps),
+                    // Links to originals hosted elsewhere are absolute
+                    original_path: match self.config.originals {
+                        Some(_) => self.original_url(p, &album_path),
+                        None => self.original_path(p, &album_path),
+                    },
+                    video_path: self.video_path(p, &album_path),
+                    data_saver: self.data_saver_paths(p, &album_path).map(Into::into),
+                    avif: self.avif_paths(p, &album_path).map(Into::into),
+                    srcset: self.srcset(p, &album_path, &widths),
+                    widths,
+                    placeholder: p.placeholder.clone(),
+                    color: p.color.clone(),
                     html_path: p.html_path(&album_path),
                     metadata: self.convert_photo_metadata(&p.metadata),
                 }
             })
             .collect();
 
+        let collections = self
+            .collections
+            .iter()
+            .map(|collection| CollectionData {
+                name: collection.album.name.clone(),
+                slug: collection.album.slug.clone(),
+                path: url_encode_path(&collection.album.path.to_string_lossy()),
+                photo_count: collection.album.photos.len(),
+                stats: (&collection.album.stats).into(),
+                photos: collection
+                    .album
+                    .photos
+                    .iter()
+                    .zip(&collection.sources)
+                    .map(|(photo, source)| photo.html_path(&source.path))
+                    .collect(),
+            })
+            .collect();
+
         GalleryData {
             site,
             albums,
             photos,
+            collections,
         }
     }
 
@@ -816,6 +2056,8 @@ impl Pipeline {
                 country: g.country.clone(),
                 country_code: g.country_code.clone(),
                 flag: g.flag.clone(),
+                altitude: g.altitude,
+                direction: g.direction,
             }),
             exposure: metadata.exposure.as_ref().map(|e| ExposureData {
                 aperture: e.aperture.clone(),
@@ -825,6 +2067,19 @@ impl Pipeline {
                 program: e.program.clone(),
             }),
             rating: metadata.rating,
+            pick: metadata.pick,
+            label: metadata.label.clone(),
+            title: metadata.title.clone(),
+            description: metadata.description.clone(),
+            keywords: metadata.keywords.clone(),
+            creator: metadata.creator.clone(),
+            location: metadata.location.as_ref().map(|l| LocationData {
+                sublocation: l.sublocation.clone(),
+                city: l.city.clone(),
+                region: l.region.clone(),
+                country: l.country.clone(),
+                country_code: l.country_code.clone(),
+            }),
         }
     }
 
@@ -866,6 +2121,30 @@ impl Pipeline {
     }
 }
 
+/// Paths of an alternate set of a photo's variants (data saver or AVIF).
+#[derive(Debug, Serialize)]
+struct VariantPaths {
+    image_path: String,
+    thumb_path: String,
+}
+
+/// Variant paths for gallery JSON.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct VariantPathsData {
+    image_path: String,
+    thumb_path: String,
+}
+
+impl From<VariantPaths> for VariantPathsData {
+    fn from(paths: VariantPaths) -> Self {
+        Self {
+            image_path: paths.image_path,
+            thumb_path: paths.thumb_path,
+        }
+    }
+}
+
 /// Photo with pre-computed paths for templates.
 #[derive(Debug, Serialize)]
 struct PhotoWithPaths {
@@ -873,18 +2152,50 @@ struct PhotoWithPaths {
     photo: Photo,
     image_path: String,
     thumb_path: String,
+    /// URL path of the double-size thumbnail, if generated
+    thumb_2x_path: Option<String>,
     micro_thumb_path: String,
-    original_path: String,
+    original_path: Option<String>,
+    /// Link to the original, on this site or where `[originals]` hosts it
+    original_url: Option<String>,
+    /// URL path of the clip, for videos
+    video_path: Option<String>,
+    data_saver: Option<VariantPaths>,
+    avif: Option<VariantPaths>,
+    /// `srcset` attribute value for the responsive widths, if generated
+    srcset: Option<String>,
+    widths: Vec<WidthPath>,
     html_path: String,
 }
 
+/// URL path of a responsive image and its width in pixels.
+#[derive(Debug, Clone, Serialize)]
+struct WidthPath {
+    width: u32,
+    path: String,
+}
+
+/// Current time in seconds since the epoch, or `SOURCE_DATE_EPOCH` for
+/// reproducible builds.
+fn build_time() -> i64 {
+    if let Some(epoch) = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|value| value.parse().ok())
+    {
+        return epoch;
+    }
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs() as i64)
+}
+
 /// Recursively copy a directory with content-hashed filenames.
 fn copy_dir_with_hashing(
     src: &Path,
     dest: &Path,
     relative_path: &str,
     expected: &mut HashSet<PathBuf>,
-    should_minify: bool,
+    processing: AssetProcessing,
     source_maps: bool,
     manifest: &mut AssetManifest,
 ) -> Result<()> {
@@ -952,7 +2263,7 @@ fn copy_dir_with_hashing(
                 &dest_subdir,
                 &entry_relative,
                 expected,
-                should_minify,
+                processing,
                 source_maps,
                 manifest,
             )?;
@@ -968,9 +2279,9 @@ fn copy_dir_with_hashing(
                     source_maps,
                     map_hashes.get(&map_name).map(String::as_str),
                 );
-                process_static_file(name, transformed.as_bytes(), should_minify)?
+                process_static_file(name, transformed.as_bytes(), processing)?
             } else {
-                process_static_file(name, &contents, should_minify)?
+                process_static_file(name, &contents, processing)?
             };
 
             let hashed_name = hash_filename(name, &output);
@@ -992,6 +2303,17 @@ fn copy_dir_with_hashing(
     Ok(())
 }
 
+/// Serialize `value` as JSON to `dest`.
+fn write_json<T>(dest: &Path, value: &T) -> Result<()>
+where
+    T: Serialize,
+{
+    let json = serde_json::to_string(value)
+        .map_err(|e| Error::Other(format!("failed to serialize {}: {}", dest.display(), e)))?;
+    fs::write(dest, json)?;
+    Ok(())
+}
+
 /// Generate a hashed filename: stem-hash.ext
 fn hash_filename(name: &str, contents: &[u8]) -> String {
     let hash = blake3::hash(contents);
@@ -1007,25 +2329,39 @@ fn hash_filename(name: &str, contents: &[u8]) -> String {
     }
 }
 
-/// Create the Tera `static` function that resolves asset paths.
-fn make_static_function(manifest: AssetManifest) -> impl Function {
-    let manifest = Arc::new(manifest);
+/// Tera `static` function that resolves asset paths.
+///
+/// Marked safe so hashed URLs aren't HTML-escaped under autoescaping.
+#[derive(Debug)]
+struct StaticFunction {
+    manifest: AssetManifest,
+}
 
-    move |args: &HashMap<String, Value>| -> tera::Result<Value> {
+impl Function for StaticFunction {
+    fn call(&self, args: &HashMap<String, Value>) -> tera::Result<Value> {
         let path = args
             .get("path")
             .and_then(|v| v.as_str())
             .ok_or_else(|| tera::Error::msg("static() requires a 'path' argument"))?;
 
-        match manifest.get(path) {
+        match self.manifest.get(path) {
             Some(hashed_path) => Ok(Value::String(hashed_path.clone())),
             None => Err(tera::Error::msg(format!(
                 "static asset not found: '{}'. Available: {:?}",
                 path,
-                manifest.keys().collect::<Vec<_>>()
+                self.manifest.keys().collect::<Vec<_>>()
             ))),
         }
     }
+
+    fn is_safe(&self) -> bool {
+        true
+    }
+}
+
+/// Create the Tera `static` function that resolves asset paths.
+fn make_static_function(manifest: AssetManifest) -> impl Function {
+    StaticFunction { manifest }
 }
 
 /// Transform source map comment in JavaScript content.
@@ -1093,15 +2429,37 @@ fn transform_js_source_map(
     result
 }
 
-/// Process a static file, optionally minifying based on extension.
-fn process_static_file(name: &str, contents: &[u8], should_minify: bool) -> Result<Vec<u8>> {
-    if !should_minify {
-        return Ok(contents.to_vec());
-    }
+/// How static assets are processed on their way to the output.
+#[derive(Debug, Clone, Copy)]
+struct AssetProcessing {
+    /// Minify CSS and JS
+    minify: bool,
+    /// Recompress PNGs
+    optimize_png: PngOptimization,
+}
 
+/// Process a static file, minifying, sanitizing, or optimizing it based on extension.
+fn process_static_file(
+    name: &str,
+    contents: &[u8],
+    processing: AssetProcessing,
+) -> Result<Vec<u8>> {
     // Determine file type by extension
     let ext = name.rsplit('.').next().unwrap_or("");
 
+    if ext == "png" {
+        return Ok(minify::png(contents, processing.optimize_png));
+    }
+    // SVGs are sanitized even when not minifying
+    if ext == "svg" {
+        let input = std::str::from_utf8(contents)
+            .map_err(|e| Error::Other(format!("invalid UTF-8 in SVG: {}", e)))?;
+        return Ok(svg::clean(input, processing.minify)?.into_bytes());
+    }
+    if !processing.minify {
+        return Ok(contents.to_vec());
+    }
+
     match ext {
         "css" => {
             let input = std::str::from_utf8(contents)
@@ -0,0 +1,332 @@
+//! Synthetic gallery generation for theme development.
+//!
+//! `galerie theme preview` builds a theme against a generated gallery that
+//! exercises layout edge cases without needing a real photo library:
+//! extreme aspect ratios, long and non-ASCII names, nested albums, photos
+//! with and without EXIF, and every supported language.
+
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+
+use image::{DynamicImage, ImageFormat, Rgb, RgbImage};
+use little_exif::exif_tag::ExifTag;
+use little_exif::filetype::FileExtension;
+use little_exif::metadata::Metadata;
+use little_exif::rational::uR64;
+
+use crate::config::Site;
+use crate::error::{Error, Result};
+
+/// Domain used for generated preview sites.
+const PREVIEW_DOMAIN: &str = "preview.galerie.local";
+
+/// File marking a directory as a generated preview, which can be replaced.
+const MARKER: &str = ".galerie-preview";
+
+/// EXIF fields attached to a synthetic photo.
+#[derive(Debug, Clone, Copy)]
+struct SyntheticExif {
+    date_taken: &'static str,
+    make: &'static str,
+    model: &'static str,
+    /// (latitude, longitude) in decimal degrees
+    gps: Option<(f64, f64)>,
+}
+
+/// A single generated photo.
+#[derive(Debug, Clone, Copy)]
+struct SyntheticPhoto {
+    /// Album directory relative to the photos root (empty for root)
+    album: &'static str,
+    /// Filename including extension
+    name: &'static str,
+    width: u32,
+    height: u32,
+    exif: Option<SyntheticExif>,
+}
+
+const TOKYO: SyntheticExif = SyntheticExif {
+    date_taken: "2024:04:02 06:12:45",
+    make: "FUJIFILM",
+    model: "X-T5",
+    gps: Some((35.6762, 139.6503)),
+};
+
+const REYKJAVIK: SyntheticExif = SyntheticExif {
+    date_taken: "2023:12:21 15:30:00",
+    make: "Canon",
+    model: "Canon EOS R5",
+    gps: Some((64.1466, -21.9426)),
+};
+
+const NO_GPS: SyntheticExif = SyntheticExif {
+    date_taken: "2021:07:14 19:45:10",
+    make: "SONY",
+    model: "ILCE-7M4",
+    gps: None,
+};
+
+/// The generated gallery. Covers landscape, portrait, square, panorama,
+/// very tall, and tiny images; missing EXIF; and awkward names.
+const PHOTOS: &[SyntheticPhoto] = &[
+    SyntheticPhoto {
+        album: "",
+        name: "landscape.jpg",
+        width: 1600,
+        height: 1067,
+        exif: Some(TOKYO),
+    },
+    SyntheticPhoto {
+        album: "",
+        name: "portrait.jpg",
+        width: 1067,
+        height: 1600,
+        exif: Some(NO_GPS),
+    },
+    SyntheticPhoto {
+        album: "",
+        name: "square no exif.png",
+        width: 1200,
+        height: 1200,
+        exif: None,
+    },
+    SyntheticPhoto {
+        album: "edge-cases",
+        name: "panorama-ultra-wide.jpg",
+        width: 3200,
+        height: 600,
+        exif: Some(REYKJAVIK),
+    },
+    SyntheticPhoto {
+        album: "edge-cases",
+        name: "very-tall-vertical-strip.jpg",
+        width: 400,
+        height: 2000,
+        exif: None,
+    },
+    SyntheticPhoto {
+        album: "edge-cases",
+        name: "tiny.png",
+        width: 64,
+        height: 48,
+        exif: None,
+    },
+    SyntheticPhoto {
+        album: "edge-cases",
+        name: "an-extremely-long-filename-that-keeps-going-well-past-any-reasonable-layout-width-2024-final-v2.jpg",
+        width: 1500,
+        height: 1000,
+        exif: Some(TOKYO),
+    },
+    SyntheticPhoto {
+        album: "edge-cases/nested album with spaces/and-another-level",
+        name: "deeply nested.jpg",
+        width: 1000,
+        height: 1500,
+        exif: Some(NO_GPS),
+    },
+    SyntheticPhoto {
+        album: "ünïcödé_旅行_путешествие",
+        name: "東京タワー.jpg",
+        width: 1200,
+        height: 800,
+        exif: Some(TOKYO),
+    },
+    SyntheticPhoto {
+        album: "ünïcödé_旅行_путешествие",
+        name: "Ísland.jpg",
+        width: 800,
+        height: 1200,
+        exif: Some(REYKJAVIK),
+    },
+];
+
+/// Generate a synthetic gallery in `site_dir` and return its configuration.
+///
+/// Writes `site.toml` (all languages enabled, using `theme`) and a `photos/`
+/// tree. Output is deterministic, so repeated previews reuse the image cache.
+/// Since it replaces both, `site_dir` must be empty or an earlier preview,
+/// never a real site.
+pub fn generate(site_dir: &Path, theme: &str) -> Result<Site> {
+    let marker = site_dir.join(MARKER);
+    let empty = fs::read_dir(site_dir).map_or(true, |mut entries| entries.next().is_none());
+    if !empty && !marker.exists() {
+        return Err(Error::Other(format!(
+            "{} isn't empty or an earlier theme preview; preview in an empty directory so no site's photos or site.toml are replaced",
+            site_dir.display()
+        )));
+    }
+    fs::create_dir_all(site_dir)?;
+    fs::write(&marker, "")?;
+    let photos_dir = site_dir.join("photos");
+
+    // Start from a clean photo tree so removed fixtures don't linger
+    if photos_dir.exists() {
+        fs::remove_dir_all(&photos_dir)?;
+    }
+
+    for (index, photo) in PHOTOS.iter().enumerate() {
+        let album_dir = photos_dir.join(photo.album);
+        fs::create_dir_all(&album_dir)?;
+
+        let data = render_photo(photo, index)?;
+        fs::write(album_dir.join(photo.name), data)?;
+    }
+
+    let site_toml = format!(
+        "domain = \"{}\"\ntitle = \"galerie theme preview\"\ntheme = {}\nall_languages = true\n",
+        PREVIEW_DOMAIN,
+        toml::Value::String(theme.to_string()),
+    );
+    fs::write(site_dir.join("site.toml"), &site_toml)?;
+
+    tracing::info!(
+        path = %site_dir.display(),
+        photos = PHOTOS.len(),
+        "generated preview gallery"
+    );
+
+    Ok(toml::from_str(&site_toml)?)
+}
+
+/// Render a gradient test image and encode it in the format implied by its name.
+fn render_photo(photo: &SyntheticPhoto, index: usize) -> Result<Vec<u8>> {
+    // Distinct hue per photo so thumbnails are distinguishable at a glance
+    let base = (index as u32 * 47) % 256;
+    let img = RgbImage::from_fn(photo.width, photo.height, |x, y| {
+        let r = (base + x * 255 / photo.width) % 256;
+        let g = (base * 2 + y * 255 / photo.height) % 256;
+        let b = 255 - base;
+        Rgb([r as u8, g as u8, b as u8])
+    });
+
+    let (format, file_type) = if photo.name.ends_with(".png") {
+        (
+            ImageFormat::Png,
+            FileExtension::PNG {
+                as_zTXt_chunk: true,
+            },
+        )
+    } else {
+        (ImageFormat::Jpeg, FileExtension::JPEG)
+    };
+
+    let mut data = Vec::new();
+    DynamicImage::ImageRgb8(img).write_to(&mut Cursor::new(&mut data), format)?;
+
+    if let Some(exif) = photo.exif {
+        build_exif(&exif)
+            .write_to_vec(&mut data, file_type)
+            .map_err(|e| Error::Other(format!("EXIF write error: {}", e)))?;
+    }
+
+    Ok(data)
+}
+
+/// Build EXIF metadata for a synthetic photo.
+fn build_exif(exif: &SyntheticExif) -> Metadata {
+    let mut metadata = Metadata::new();
+    metadata.set_tag(ExifTag::DateTimeOriginal(exif.date_taken.to_string()));
+    metadata.set_tag(ExifTag::Make(exif.make.to_string()));
+    metadata.set_tag(ExifTag::Model(exif.model.to_string()));
+    metadata.set_tag(ExifTag::FNumber(vec![uR64 {
+        nominator: 28,
+        denominator: 10,
+    }]));
+    metadata.set_tag(ExifTag::ExposureTime(vec![uR64 {
+        nominator: 1,
+        denominator: 250,
+    }]));
+    metadata.set_tag(ExifTag::ISO(vec![200]));
+
+    if let Some((lat, lon)) = exif.gps {
+        let lat_ref = if lat >= 0.0 { "N" } else { "S" };
+        let lon_ref = if lon >= 0.0 { "E" } else { "W" };
+        metadata.set_tag(ExifTag::GPSLatitudeRef(lat_ref.to_string()));
+        metadata.set_tag(ExifTag::GPSLatitude(decimal_to_dms(lat)));
+        metadata.set_tag(ExifTag::GPSLongitudeRef(lon_ref.to_string()));
+        metadata.set_tag(ExifTag::GPSLongitude(decimal_to_dms(lon)));
+    }
+
+    metadata
+}
+
+/// Convert decimal degrees to EXIF degrees/minutes/seconds rationals.
+fn decimal_to_dms(value: f64) -> Vec<uR64> {
+    let value = value.abs();
+    let degrees = value.trunc();
+    let minutes = ((value - degrees) * 60.0).trunc();
+    let seconds = (value - degrees - minutes / 60.0) * 3600.0;
+
+    vec![
+        uR64 {
+            nominator: degrees as u32,
+            denominator: 1,
+        },
+        uR64 {
+            nominator: minutes as u32,
+            denominator: 1,
+        },
+        uR64 {
+            nominator: (seconds * 100.0).round() as u32,
+            denominator: 100,
+        },
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generate_writes_config_and_photos() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let site = generate(dir.path(), "basic").unwrap();
+
+        assert_eq!(site.domain, PREVIEW_DOMAIN);
+        assert_eq!(site.theme.name(), "basic");
+        assert!(site.all_languages);
+
+        let root = crate::photos::discover(&dir.path().join("photos")).unwrap();
+        assert_eq!(root.photo_count(), PHOTOS.len());
+    }
+
+    #[test]
+    fn real_site_left_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("site.toml"), "domain = \"example.com\"\n").unwrap();
+        fs::create_dir(dir.path().join("photos")).unwrap();
+
+        assert!(generate(dir.path(), "basic").is_err());
+        assert_eq!(
+            fs::read_to_string(dir.path().join("site.toml")).unwrap(),
+            "domain = \"example.com\"\n"
+        );
+
+        // An earlier preview is regenerated
+        let preview = tempfile::tempdir().unwrap();
+        generate(preview.path(), "basic").unwrap();
+        generate(preview.path(), "fancy").unwrap();
+    }
+
+    #[test]
+    fn generate_quotes_theme_paths() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let site = generate(dir.path(), r#"C:\themes\my "quoted" theme"#).unwrap();
+
+        assert_eq!(site.theme.name(), r#"C:\themes\my "quoted" theme"#);
+    }
+
+    #[test]
+    fn decimal_to_dms_round_trips() {
+        let dms = decimal_to_dms(35.6762);
+        let back = dms[0].nominator as f64
+            + dms[1].nominator as f64 / 60.0
+            + dms[2].nominator as f64 / dms[2].denominator as f64 / 3600.0;
+
+        assert!((back - 35.6762).abs() < 0.0001);
+    }
+}
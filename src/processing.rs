@@ -3,43 +3,393 @@
 //! Processes photos to generate:
 //! - BLAKE3 content hash for cache-busting
 //! - EXIF metadata extraction
-//! - Micro thumbnail (120px WebP, lossy) for filmstrips
-//! - Thumbnail (600px WebP, lossy) for grid display
-//! - Full-size web image (2400px max WebP, lossy)
-//! - Original copy
+//! - Micro thumbnail (120px WebP by default, lossy) for filmstrips
+//! - Thumbnail (600px WebP by default, lossy) for grid display
+//! - Full-size web image (2400px max WebP by default, lossy), unless building lite
+//! - Data saver thumbnail and full-size image (smaller, more compressed), if enabled
+//! - AVIF thumbnail and full-size image, if enabled
+//! - Responsive widths for `srcset`, if configured
+//! - Placeholder (16px WebP data URI) from the micro thumbnail, if enabled
+//! - Original copy, unless originals aren't published (never for RAW files)
+//!
+//! RAW files are processed from the JPEG preview the camera embedded in them.
+//! HEIC/HEIF files are decoded with libheif, and their original is published
+//! as a JPEG.
+//!
+//! Once photos are filtered and sorted, each album's cover is cropped to the
+//! `[crops]` aspect ratios at the full-size encoding.
 //!
 //! Files are written directly during processing to minimize memory usage
 //! and allow progress monitoring.
 
+use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::fs;
-use std::io::Cursor;
-use std::panic::{self, AssertUnwindSafe};
-use std::path::Path;
-use std::sync::atomic::{AtomicUsize, Ordering};
-
-use gufo_common::xmp::Namespace;
-use gufo_xmp::{Tag, Xmp};
-use image::DynamicImage;
-use image::imageops::FilterType;
-use little_exif::exif_tag::ExifTag;
-use little_exif::filetype::FileExtension;
-use little_exif::metadata::Metadata;
-use little_exif::rational::uR64;
+use std::io::{self, Cursor, Read, Seek};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use image::codecs::avif::AvifEncoder;
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::{self, FilterType};
+use image::metadata::Orientation;
+use image::{DynamicImage, ExtendedColorType, Frame, ImageDecoder, ImageEncoder, RgbaImage};
 use rayon::prelude::*;
+use serde::Serialize;
+
+use crate::animation;
+use crate::cache::{self, MetadataCache};
+use crate::color;
+use crate::config::{
+    AspectRatio, AvifConfig, FilterConfig, GpsMode, PngOptimization, ProcessingConfig, Reexports,
+    VariantConfig,
+};
+use crate::countries::Registry;
+use crate::crop;
+use crate::error::{Error, Result};
+use crate::exif::{self, ExifData};
+use crate::geocode::Geocoder;
+use crate::gpx::Tracks;
+use crate::heif;
+use crate::iptc;
+use crate::minify;
+use crate::photos::{
+    Album, Cover, ExposureInfo, GpsCoords, Photo, PhotoMetadata, Variant, VariantNames,
+};
+use crate::progress::Progress;
+use crate::raw;
+use crate::sidecar::{self, LocationOverride};
+use crate::timings::{Phase, PhotoTimings, Timings};
+use crate::util::base64;
+use crate::video;
+use crate::watermark::Watermark;
+use crate::xmp;
+
+/// Bits dropped from each channel when grouping pixels by color
+const COLOR_BUCKET_SHIFT: u8 = 5;
+
+/// Longest side of placeholders, scaled up and blurry by design
+const PLACEHOLDER_SIZE: u32 = 16;
+const PLACEHOLDER_QUALITY: f32 = 30.0;
+
+/// Lowest WebP quality tried when fitting a target size
+const MIN_TARGET_QUALITY: u8 = 20;
+
+/// Blur radius of the unsharp mask, small enough to only crisp up fine detail
+const SHARPEN_SIGMA: f32 = 0.6;
+
+/// Quality of JPEG originals converted from HEIF
+const JPEG_QUALITY: u8 = 92;
+
+/// Originals this large are memory-mapped rather than read into memory, so
+/// workers processing big TIFFs and PNGs at once don't each hold a copy,
+/// unless they may be edited while the build runs.
+const MMAP_SIZE: u64 = 64 * 1024 * 1024;
+
+// Defaults, overridden by `[processing]` in site.toml
+const MICRO_THUMB: Encoding = Encoding::new(120, 70);
+const THUMB: Encoding = Encoding::new(600, 80);
+const FULL: Encoding = Encoding::new(2400, 90);
+const SAVER_THUMB: Encoding = Encoding::new(600, 50);
+const SAVER_FULL: Encoding = Encoding::new(1200, 55);
+
+/// Maximum dimension and WebP quality of a generated image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Encoding {
+    pub size: u32,
+    pub quality: u8,
+    /// Size in kilobytes to fit the image in by lowering the quality
+    pub target_kb: Option<u32>,
+    /// Encode losslessly, ignoring the quality and target size
+    pub lossless: bool,
+    /// Unsharp mask amount in percent, applied after scaling down
+    pub sharpen: u16,
+}
+
+impl Encoding {
+    const fn new(size: u32, quality: u8) -> Self {
+        Self {
+            size,
+            quality,
+            target_kb: None,
+            lossless: false,
+            sharpen: 0,
+        }
+    }
+
+    /// This encoding with the configured values applied.
+    fn with(self, config: VariantConfig) -> Self {
+        Self {
+            size: config.size.unwrap_or(self.size).max(1),
+            quality: config.quality.unwrap_or(self.quality).min(100),
+            target_kb: config.target_kb.map(|kb| kb.max(1)),
+            lossless: false,
+            sharpen: 0,
+        }
+    }
+
+    /// `base`, or `base-{size}q{quality}` (and `k{target_kb}`) when this
+    /// isn't the `default` encoding. Lossless encodings are named
+    /// `base-lossless`, with the size if it isn't the default. Sharpening
+    /// is named by `Encodings::names`.
+    fn name(self, base: &str, default: Encoding) -> String {
+        if self.lossless && self.size == default.size {
+            format!("{}-lossless", base)
+        } else if (Self { sharpen: 0, ..self }) == default {
+            base.to_string()
+        } else {
+            format!("{}-{}", base, self.suffix())
+        }
+    }
+
+    /// `{size}q{quality}`, and `k{target_kb}` with a target size, or
+    /// `{size}lossless`.
+    fn suffix(self) -> String {
+        match self.target_kb {
+            _ if self.lossless => format!("{}lossless", self.size),
+            Some(kb) => format!("{}q{}k{}", self.size, self.quality, kb),
+            None => format!("{}q{}", self.size, self.quality),
+        }
+    }
+
+    /// This encoding, lossless if `lossless` is set.
+    pub fn lossless_if(self, lossless: bool) -> Self {
+        Self {
+            lossless: self.lossless || lossless,
+            ..self
+        }
+    }
+
+    /// `base` fitted within the encoding's size, sharpened if the original
+    /// was scaled down.
+    fn scale<'a>(self, base: &'a Base) -> Cow<'a, DynamicImage> {
+        base.fit(self.size, self.size, self.sharpen)
+    }
+
+    /// Encode `rgba` as WebP.
+    fn encode_webp(self, rgba: &RgbaImage) -> Vec<u8> {
+        let encoder = webp::Encoder::from_rgba(rgba.as_raw(), rgba.width(), rgba.height());
+        if self.lossless {
+            return encoder.encode_lossless().to_vec();
+        }
+        let encode = |quality: u8| encoder.encode(f32::from(quality)).to_vec();
+        match self.target_kb {
+            Some(kb) => fit_target(encode, self.quality, kb as usize * 1024),
+            None => encode(self.quality),
+        }
+    }
+}
+
+/// A decoded photo scaled down once to the largest size its variants need,
+/// which they're all made from, so the full-resolution original is only
+/// resized once.
+struct Base<'a> {
+    img: Cow<'a, DynamicImage>,
+    /// Dimensions of the original `img` was scaled down from
+    original: (u32, u32),
+}
+
+impl<'a> Base<'a> {
+    /// `img` scaled down to fit within `size`, if it's larger.
+    fn new(img: &'a DynamicImage, size: u32) -> Self {
+        let scaled = if img.width() > size || img.height() > size {
+            Cow::Owned(img.resize(size, size, FilterType::Lanczos3))
+        } else {
+            Cow::Borrowed(img)
+        };
+        Self {
+            img: scaled,
+            original: (img.width(), img.height()),
+        }
+    }
+
+    /// This image with `f` applied, which keeps its dimensions.
+    fn map(&self, f: impl FnOnce(&DynamicImage) -> DynamicImage) -> Base<'static> {
+        Base {
+            img: Cow::Owned(f(&self.img)),
+            original: self.original,
+        }
+    }
+
+    /// The region of this image at `x`, `y` of `width` by `height`.
+    fn crop(&self, (x, y, width, height): (u32, u32, u32, u32)) -> Base<'static> {
+        let original = |length: u32, original: u32, scaled: u32| {
+            (u64::from(length) * u64::from(original) / u64::from(scaled.max(1))) as u32
+        };
+        Base {
+            img: Cow::Owned(self.img.crop_imm(x, y, width, height)),
+            original: (
+                original(width, self.original.0, self.img.width()),
+                original(height, self.original.1, self.img.height()),
+            ),
+        }
+    }
+
+    /// This image fitted within `width` by `height`, sharpened by `amount`
+    /// percent if the original was scaled down.
+    fn fit(&self, width: u32, height: u32, amount: u16) -> Cow<'_, DynamicImage> {
+        if self.original.0 <= width && self.original.1 <= height {
+            // Not scaled down at all, so this is the original
+            return Cow::Borrowed(&self.img);
+        }
+        let fits = self.img.width() <= width && self.img.height() <= height;
+        match (fits, amount) {
+            (true, 0) => Cow::Borrowed(&self.img),
+            (true, _) => Cow::Owned(sharpen((*self.img).clone(), amount)),
+            (false, _) => Cow::Owned(sharpen(
+                self.img.resize(width, height, FilterType::Lanczos3),
+                amount,
+            )),
+        }
+    }
+}
+
+impl<'a> From<&'a DynamicImage> for Base<'a> {
+    fn from(img: &'a DynamicImage) -> Self {
+        Self::new(img, u32::MAX)
+    }
+}
+
+/// Encodings of every generated variant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Encodings {
+    pub micro: Encoding,
+    pub thumb: Encoding,
+    /// Twice the thumbnail's size, with four times its target size
+    pub thumb_2x: Encoding,
+    pub full: Encoding,
+    pub thumb_saver: Encoding,
+    pub full_saver: Encoding,
+    /// Aspect ratio thumbnails are cropped to, if any
+    pub thumb_crop: Option<AspectRatio>,
+}
+
+impl Encodings {
+    /// The default encodings with the site's settings applied.
+    pub fn new(config: &ProcessingConfig) -> Self {
+        let thumb = THUMB.with(config.thumb);
+        Self {
+            micro: MICRO_THUMB.with(config.micro),
+            thumb,
+            thumb_2x: Encoding {
+                size: thumb.size.saturating_mul(2),
+                target_kb: thumb.target_kb.map(|kb| kb.saturating_mul(4)),
+                ..thumb
+            },
+            full: FULL.with(config.full),
+            thumb_saver: SAVER_THUMB.with(config.thumb_saver),
+            full_saver: SAVER_FULL.with(config.full_saver),
+            thumb_crop: config.thumb_crop,
+        }
+        .sharpened(config.sharpen)
+    }
+
+    /// These encodings with every variant but the micro thumbnail sharpened
+    /// by `amount` percent.
+    fn sharpened(self, amount: u16) -> Self {
+        let sharpen = |encoding: Encoding| Encoding {
+            sharpen: amount,
+            ..encoding
+        };
+        Self {
+            thumb: sharpen(self.thumb),
+            thumb_2x: sharpen(self.thumb_2x),
+            full: sharpen(self.full),
+            thumb_saver: sharpen(self.thumb_saver),
+            full_saver: sharpen(self.full_saver),
+            ..self
+        }
+    }
 
-use crate::config::GpsMode;
-use crate::error::Result;
-use crate::photos::{Album, ExposureInfo, GpsCoords, Photo, PhotoMetadata};
+    /// These encodings with everything but the data saver variants lossless,
+    /// if `lossless` is set.
+    pub fn lossless_if(self, lossless: bool) -> Self {
+        Self {
+            micro: self.micro.lossless_if(lossless),
+            thumb: self.thumb.lossless_if(lossless),
+            thumb_2x: self.thumb_2x.lossless_if(lossless),
+            full: self.full.lossless_if(lossless),
+            ..self
+        }
+    }
 
-// Hardcoded defaults - can be made configurable later if needed
-const MICRO_THUMB_SIZE: u32 = 120;
-const MICRO_THUMB_QUALITY: f32 = 70.0;
-const THUMB_SIZE: u32 = 600;
-const THUMB_QUALITY: f32 = 80.0;
-const FULL_SIZE: u32 = 2400;
-const FULL_QUALITY: f32 = 90.0;
+    /// Filename parts of the variants these encodings (and AVIF settings) produce.
+    pub fn names(&self, avif: Option<AvifConfig>, watermark: Option<&Watermark>) -> VariantNames {
+        let avif_name = |base: &str, size: u32, default_size: u32| {
+            let avif = avif.unwrap_or_default();
+            if size == default_size && avif == AvifConfig::default() {
+                base.to_string()
+            } else {
+                format!("{}-{}q{}s{}", base, size, avif.quality, avif.speed)
+            }
+        };
+        let mut names = VariantNames {
+            micro: self.micro.name("micro", MICRO_THUMB),
+            thumb: self.thumb.name("thumb", THUMB),
+            thumb_2x: String::new(),
+            full: self.full.name("full", FULL),
+            thumb_saver: self.thumb_saver.name("thumb-saver", SAVER_THUMB),
+            full_saver: self.full_saver.name("full-saver", SAVER_FULL),
+            avif_thumb: avif_name("thumb", self.thumb.size, THUMB.size),
+            avif_full: avif_name("full", self.full.size, FULL.size),
+            width_suffix: if self.full.lossless {
+                "-lossless".to_string()
+            } else if self.full.quality == FULL.quality {
+                String::new()
+            } else {
+                format!("q{}", self.full.quality)
+            },
+            crop_suffix: self.full.name("", FULL),
+        };
+        if let Some(ratio) = self.thumb_crop {
+            let crop = format!("-{}x{}", ratio.width, ratio.height);
+            for name in [
+                &mut names.thumb,
+                &mut names.thumb_saver,
+                &mut names.avif_thumb,
+            ] {
+                name.push_str(&crop);
+            }
+        }
+        if self.full.sharpen > 0 {
+            let mark = format!("-sh{}", self.full.sharpen);
+            for name in [
+                &mut names.thumb,
+                &mut names.full,
+                &mut names.thumb_saver,
+                &mut names.full_saver,
+                &mut names.avif_thumb,
+                &mut names.avif_full,
+                &mut names.width_suffix,
+                &mut names.crop_suffix,
+            ] {
+                name.push_str(&mark);
+            }
+        }
+        if let Some(watermark) = watermark {
+            let mark = format!("-wm{}", watermark.id());
+            for name in [
+                &mut names.thumb,
+                &mut names.full,
+                &mut names.thumb_saver,
+                &mut names.full_saver,
+                &mut names.avif_thumb,
+                &mut names.avif_full,
+                &mut names.width_suffix,
+                &mut names.crop_suffix,
+            ] {
+                name.push_str(&mark);
+            }
+        }
+        // Named after the thumbnail it doubles, with all of its settings
+        names.thumb_2x = format!("{}@2x", names.thumb);
+        names
+    }
+}
 
 /// Stats from processing an album tree.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
 pub struct ProcessingStats {
     pub total: usize,
     pub cached: usize,
@@ -48,22 +398,80 @@ pub struct ProcessingStats {
     pub skipped: usize,
 }
 
+/// Optional files written for each photo.
+#[derive(Debug, Clone, Copy)]
+pub struct Outputs<'a> {
+    /// Full-size WebP
+    pub full: bool,
+    /// Double-size thumbnail
+    pub thumb_2x: bool,
+    /// Data saver variants of the thumbnail and full-size WebP
+    pub data_saver: bool,
+    /// Copy of the original
+    pub originals: bool,
+    /// Recompression of PNG originals
+    pub optimize_png: PngOptimization,
+    /// AVIF thumbnail and full-size image, with their encoder settings
+    pub avif: Option<AvifConfig>,
+    /// Sizes and qualities of the WebP variants
+    pub encodings: Encodings,
+    /// Widths of responsive images, empty if not generated
+    pub widths: &'a [u32],
+    /// Tiny previews embedded in page and gallery data
+    pub placeholders: bool,
+    /// Mark composited onto every variant but the micro thumbnail
+    pub watermark: Option<&'a Watermark>,
+    /// Encode PNG sources losslessly
+    pub lossless_png: bool,
+    /// Albums whose photos, and their nested albums' photos, are encoded losslessly
+    pub lossless_albums: &'a [PathBuf],
+    /// Handling of originals whose content changed under the same name
+    pub reexports: Reexports,
+    /// Read each photo, but write none of its images
+    pub dry_run: bool,
+    /// Memory-map large originals, which is only sound while nothing
+    /// truncates them until they're processed
+    pub map_originals: bool,
+    /// Photos to publish, by their metadata; the rest get no images
+    pub filter: Option<&'a FilterConfig>,
+}
+
 /// What was processed for a single photo.
 struct PhotoProcessingResult {
-    /// WebP thumb or full was generated.
-    generated_webp: bool,
+    /// A WebP or AVIF variant was generated.
+    generated_variants: bool,
     /// Original file was copied (with or without GPS stripping).
     copied_original: bool,
+    /// The photo doesn't pass `[filter]`, so nothing was written for it.
+    filtered_out: bool,
+}
+
+/// Lookups used to place photos, shared by every photo in a build.
+pub struct Locations<'a> {
+    /// Cached reverse geocoding results and published original state
+    pub cache: &'a MetadataCache,
+    /// Reverse geocoder for cache misses
+    pub geocoder: &'a Geocoder,
+    /// GPX tracks for photos without GPS
+    pub tracks: &'a Tracks,
+    /// Country names and region name fixes
+    pub countries: &'a Registry,
 }
 
 /// Process all photos in an album tree in parallel.
 ///
 /// Files are written directly to `images_dir` during processing.
 /// Cached images (same hash already exists) are skipped.
+#[allow(clippy::too_many_arguments)]
 pub fn process_album(
     album: &mut Album,
     images_dir: &Path,
     gps_mode: GpsMode,
+    outputs: Outputs<'_>,
+    locations: &Locations,
+    timings: &Timings,
+    progress: Progress,
+    cancel: &AtomicBool,
 ) -> Result<ProcessingStats> {
     let total = AtomicUsize::new(0);
     let cached = AtomicUsize::new(0);
@@ -71,9 +479,68 @@ pub fn process_album(
     let copied = AtomicUsize::new(0);
     let skipped = AtomicUsize::new(0);
 
-    process_album_recursive(
-        album, images_dir, gps_mode, &total, &cached, &generated, &copied, &skipped,
-    );
+    // Photos from every album go through one parallel loop, so a tree of
+    // many small albums keeps all cores busy
+    let mut jobs = Vec::new();
+    collect_photos(album, images_dir, &mut jobs);
+    let bar = progress.bar("photos", jobs.len());
+
+    // Process photos in parallel, catching errors per-photo
+    jobs.into_par_iter()
+        .for_each(|(photo, album_images_dir, album_path)| {
+            // The build is abandoned after processing, so the rest can wait
+            if cancel.load(Ordering::Relaxed) {
+                return;
+            }
+            let source = photo.source.display().to_string();
+            let mut photo_timings = PhotoTimings::default();
+            match process_photo(
+                photo,
+                &album_images_dir,
+                album_path,
+                gps_mode,
+                outputs,
+                locations,
+                &mut photo_timings,
+            ) {
+                Ok(result) => {
+                    total.fetch_add(1, Ordering::Relaxed);
+                    if !result.generated_variants
+                        && !result.copied_original
+                        && !result.filtered_out
+                    {
+                        cached.fetch_add(1, Ordering::Relaxed);
+                    }
+                    if result.generated_variants {
+                        generated.fetch_add(1, Ordering::Relaxed);
+                    }
+                    if result.copied_original {
+                        copied.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+                Err(e) => {
+                    tracing::warn!(photo = %source, error = %e, "skipping photo due to processing error");
+                    skipped.fetch_add(1, Ordering::Relaxed);
+                    // Mark photo as skipped by clearing its hash
+                    photo.hash.clear();
+                }
+            }
+            timings.photo(&photo.source, photo_timings);
+            // Each photo is hashed, decoded, and encoded in one go, so a
+            // single bar covers every phase; --timings splits them up
+            let done = total.load(Ordering::Relaxed) + skipped.load(Ordering::Relaxed);
+            bar.set(done, || {
+                format!(
+                    "{} cached, {} generated, {} skipped",
+                    cached.load(Ordering::Relaxed),
+                    generated.load(Ordering::Relaxed),
+                    skipped.load(Ordering::Relaxed)
+                )
+            });
+        });
+    drop(bar);
+
+    remove_skipped(album);
 
     Ok(ProcessingStats {
         total: total.load(Ordering::Relaxed),
@@ -84,62 +551,38 @@ pub fn process_album(
     })
 }
 
-#[allow(clippy::too_many_arguments)]
-fn process_album_recursive(
-    album: &mut Album,
+/// Each photo in `album` and its children, with the directory its outputs go
+/// in and its album's path. The directories are created along the way; the
+/// photos of an album whose directory can't be created are left out.
+fn collect_photos<'a>(
+    album: &'a mut Album,
     images_dir: &Path,
-    gps_mode: GpsMode,
-    total: &AtomicUsize,
-    cached: &AtomicUsize,
-    generated: &AtomicUsize,
-    copied: &AtomicUsize,
-    skipped: &AtomicUsize,
+    jobs: &mut Vec<(&'a mut Photo, PathBuf, &'a Path)>,
 ) {
-    let album_path = album.path.clone();
-    let album_images_dir = if album_path.as_os_str().is_empty() {
+    let album_images_dir = if album.path.as_os_str().is_empty() {
         images_dir.to_path_buf()
     } else {
-        let dir = images_dir.join(&album_path);
+        let dir = images_dir.join(&album.path);
         if let Err(e) = fs::create_dir_all(&dir) {
-            tracing::error!(album = %album_path.display(), error = %e, "failed to create album directory");
+            tracing::error!(album = %album.path.display(), error = %e, "failed to create album directory");
             return;
         }
         dir
     };
 
-    // Process photos in this album in parallel, catching errors per-photo
-    album.photos.par_iter_mut().for_each(|photo| {
-        let source = photo.source.display().to_string();
-        match process_photo(photo, &album_images_dir, gps_mode) {
-            Ok(result) => {
-                total.fetch_add(1, Ordering::Relaxed);
-                if !result.generated_webp && !result.copied_original {
-                    cached.fetch_add(1, Ordering::Relaxed);
-                }
-                if result.generated_webp {
-                    generated.fetch_add(1, Ordering::Relaxed);
-                }
-                if result.copied_original {
-                    copied.fetch_add(1, Ordering::Relaxed);
-                }
-            }
-            Err(e) => {
-                tracing::warn!(photo = %source, error = %e, "skipping photo due to processing error");
-                skipped.fetch_add(1, Ordering::Relaxed);
-                // Mark photo as skipped by clearing its hash
-                photo.hash.clear();
-            }
-        }
-    });
+    for photo in &mut album.photos {
+        jobs.push((photo, album_images_dir.clone(), &album.path));
+    }
+    for child in &mut album.children {
+        collect_photos(child, images_dir, jobs);
+    }
+}
 
-    // Remove skipped photos (those with empty hash)
+/// Remove photos that weren't processed (those with empty hash) from `album` and its children.
+fn remove_skipped(album: &mut Album) {
     album.photos.retain(|p| !p.hash.is_empty());
-
-    // Recursively process child albums
     for child in &mut album.children {
-        process_album_recursive(
-            child, images_dir, gps_mode, total, cached, generated, copied, skipped,
-        );
+        remove_skipped(child);
     }
 }
 
@@ -147,68 +590,199 @@ fn process_album_recursive(
 fn process_photo(
     photo: &mut Photo,
     images_dir: &Path,
+    album_path: &Path,
     gps_mode: GpsMode,
+    outputs: Outputs<'_>,
+    locations: &Locations,
+    timings: &mut PhotoTimings,
 ) -> Result<PhotoProcessingResult> {
     tracing::trace!(photo = %photo.source.display(), "processing photo");
 
-    // Read the original file
-    let original_data = fs::read(&photo.source)?;
-
-    // Capture original file size
-    photo.original_size = original_data.len() as u64;
-
-    // Compute BLAKE3 hash (first 8 chars of hex) based on content only
-    // This hash is used for cache-busting URLs and doesn't change with GPS mode
-    let hash = blake3::hash(&original_data);
-    photo.hash = hash.to_hex()[..8].to_string();
-
-    // Extract EXIF metadata (cheap operation, always do it)
-    // Use catch_unwind because little_exif can panic on malformed images
-    // Wrap in a span so little_exif's internal logging includes the file context
-    let source_path = photo.source.clone();
-    let source_display = source_path.display().to_string();
-    photo.metadata = {
-        let _span = tracing::info_span!("exif", file = %source_display).entered();
-        panic::catch_unwind(AssertUnwindSafe(|| {
-            extract_exif(&original_data, &photo.extension, gps_mode)
-        }))
-        .unwrap_or_else(|_| {
-            tracing::warn!("EXIF extraction panicked, skipping metadata");
-            PhotoMetadata::default()
-        })
+    // Reuse what was read from the original last time if it's unchanged, so
+    // photos with all their variants in place aren't read at all
+    let stamp = cache::stamp(&fs::metadata(&photo.source)?);
+    let cached = stamp.and_then(|stamp| locations.cache.source(&photo.source, stamp));
+    let mut held = false;
+    let (source, original_data) = match cached {
+        Some(source) => (source, None),
+        None => {
+            // Hashed before reading it in, since a touched original needs no
+            // more. Both go through one handle, so a file replaced meanwhile
+            // can't have its new content published under the old hash.
+            let file = fs::File::open(&photo.source)?;
+            let hash = timings.time(Phase::Hash, || hash_file(&file))?;
+            let restamped = stamp.and_then(|stamp| {
+                locations
+                    .cache
+                    .restamped_source(&photo.source, stamp, &hash)
+            });
+            let previous = match restamped {
+                Some(_) => None,
+                None => locations.cache.previous_source(&photo.source),
+            };
+            match (restamped, previous) {
+                (Some(source), _) => (source, None),
+                // The last version's images are still published in its place
+                (None, Some(previous)) if outputs.reexports == Reexports::Hold => {
+                    tracing::warn!(
+                        photo = %photo.source.display(),
+                        published = %previous.hash,
+                        new = %hash,
+                        "photo changed under the same name; still publishing the previous version until a build with --accept-changes"
+                    );
+                    held = true;
+                    locations.cache.set_source(&photo.source, previous.clone());
+                    (previous, None)
+                }
+                (None, previous) => {
+                    if let Some(previous) = previous
+                        && outputs.reexports == Reexports::Warn
+                    {
+                        tracing::warn!(
+                            photo = %photo.source.display(),
+                            published = %previous.hash,
+                            new = %hash,
+                            "photo changed under the same name, so its images get new URLs that caches and deploys treat as new files"
+                        );
+                    }
+                    let data = timings
+                        .time(Phase::Read, || Original::open(file, outputs.map_originals))?;
+                    let size_only = (data.len() as u64, 0);
+                    let source = timings.time(Phase::Metadata, || {
+                        read_source(photo, &data, stamp.unwrap_or(size_only), hash)
+                    })?;
+                    // Without a modification time, there's no telling next time if it changed
+                    if stamp.is_some() {
+                        locations.cache.set_source(&photo.source, source.clone());
+                    }
+                    (source, Some(data))
+                }
+            }
+        }
     };
 
-    // Extract image dimensions (reads header only, doesn't decode full image)
-    let reader = image::ImageReader::new(Cursor::new(&original_data))
-        .with_guessed_format()
-        .map_err(|e| crate::error::Error::Image(image::ImageError::IoError(e)))?;
-    let (width, height) = reader.into_dimensions()?;
-    photo.width = width;
-    photo.height = height;
-
-    // Build output paths
-    let micro_thumb_path = images_dir.join(format!("{}-{}-micro.webp", photo.stem, photo.hash));
-    let thumb_path = images_dir.join(format!("{}-{}-thumb.webp", photo.stem, photo.hash));
-    let full_path = images_dir.join(format!("{}-{}-full.webp", photo.stem, photo.hash));
-    let original_path = images_dir.join(format!(
-        "{}-{}-original{}.{}",
+    photo.original_size = source.stamp.0;
+    photo.hash = source.hash.clone();
+    photo.metadata = timings.time(Phase::Metadata, || {
+        extract_metadata(&source, &photo.source, gps_mode, locations)
+    });
+    if photo.metadata.date_taken.is_none() {
+        photo.metadata.date_taken = source.video_date.clone();
+    }
+    (photo.width, photo.height) = (source.width, source.height);
+    photo.animated = source.animated;
+    // Left out of the site, so not worth decoding or encoding
+    if outputs
+        .filter
+        .is_some_and(|filter| !photo.metadata.passes(filter))
+    {
+        tracing::debug!(photo = %photo.stem, "filtered out");
+        return Ok(PhotoProcessingResult {
+            generated_variants: false,
+            copied_original: false,
+            filtered_out: true,
+        });
+    }
+    photo.lossless = is_lossless(photo, album_path, &outputs);
+    let encodings = outputs.encodings.lossless_if(photo.lossless);
+
+    // Build output paths, named for their settings
+    photo.variants = Arc::new(encodings.names(outputs.avif, outputs.watermark));
+    if outputs.dry_run {
+        return Ok(PhotoProcessingResult {
+            generated_variants: false,
+            copied_original: false,
+            filtered_out: false,
+        });
+    }
+    let variant_path = |variant| images_dir.join(photo.variant_file_name(variant));
+    let micro_thumb_path = variant_path(Variant::Micro);
+    let thumb_path = variant_path(Variant::Thumb);
+    let thumb_2x_path = variant_path(Variant::Thumb2x);
+    let full_path = variant_path(Variant::Full);
+    let saver_thumb_path = variant_path(Variant::ThumbSaver);
+    let saver_full_path = variant_path(Variant::FullSaver);
+    let avif_thumb_path = variant_path(Variant::AvifThumb);
+    let avif_full_path = variant_path(Variant::AvifFull);
+    let original_name = format!(
+        "{}-{}-original.{}",
         photo.stem,
         photo.hash,
-        gps_mode.original_suffix(),
-        photo.extension
-    ));
+        photo.original_extension()
+    );
+    let original_path = images_dir.join(&original_name);
+    let video_name = photo.video_file_name();
+    let video_path = images_dir.join(&video_name);
+
+    // The published original's name is the same in every GPS mode, so the cache
+    // records whether it was stripped; an unrecorded copy is rewritten to be sure
+    let original_key = cache::original_key(album_path, &original_name);
+    let video_key = cache::original_key(album_path, &video_name);
+    let strip_gps = gps_mode.strips_gps();
 
     // Check what needs to be generated
     let need_micro = !micro_thumb_path.exists();
     let need_thumb = !thumb_path.exists();
-    let need_full = !full_path.exists();
-    let need_original = !original_path.exists();
+    let need_thumb_2x = outputs.thumb_2x && !thumb_2x_path.exists();
+    let need_full = outputs.full && !full_path.exists();
+    let need_saver_thumb = outputs.data_saver && !saver_thumb_path.exists();
+    let need_saver_full = outputs.data_saver && outputs.full && !saver_full_path.exists();
+    let need_saver = need_saver_thumb || need_saver_full;
+    // Browsers would pick AVIF or a responsive width over an animated WebP,
+    // and lossy AVIF over a lossless one
+    let avif = outputs.avif.filter(|_| !photo.animated && !photo.lossless);
+    let need_avif_thumb = avif.is_some() && !avif_thumb_path.exists();
+    let need_avif_full = avif.is_some() && outputs.full && !avif_full_path.exists();
+    let need_avif = need_avif_thumb || need_avif_full;
+    let widths = if photo.animated {
+        &[][..]
+    } else {
+        outputs.widths
+    };
+    let need_widths: Vec<u32> = ladder(widths, photo.width)
+        .into_iter()
+        .filter(|&width| !variant_path(Variant::Width(width)).exists())
+        .collect();
+    let need_original = outputs.originals
+        && !photo.is_raw()
+        && !photo.is_video()
+        && (!original_path.exists()
+            || locations.cache.original_stripped(&original_key) != Some(strip_gps));
+    let need_video = photo.is_video()
+        && (!video_path.exists()
+            || locations.cache.original_stripped(&video_key) != Some(strip_gps));
+
+    let need_anything = need_micro
+        || need_thumb
+        || need_thumb_2x
+        || need_full
+        || need_saver
+        || need_avif
+        || !need_widths.is_empty()
+        || need_original
+        || need_video;
+    // A held version can't be regenerated; its original is gone
+    if held && need_anything {
+        tracing::warn!(
+            photo = %photo.source.display(),
+            "the previous version's images are gone, so publishing the new one"
+        );
+        let outputs = Outputs {
+            reexports: Reexports::Allow,
+            ..outputs
+        };
+        return process_photo(
+            photo, images_dir, album_path, gps_mode, outputs, locations, timings,
+        );
+    }
 
-    if !need_micro && !need_thumb && !need_full && !need_original {
+    if !need_anything {
         tracing::debug!(photo = %photo.stem, hash = %photo.hash, "cached");
+        previews(photo, &micro_thumb_path, outputs.placeholders)?;
         return Ok(PhotoProcessingResult {
-            generated_webp: false,
+            generated_variants: false,
             copied_original: false,
+            filtered_out: false,
         });
     }
 
@@ -216,372 +790,977 @@ fn process_photo(
         photo = %photo.stem,
         need_micro,
         need_thumb,
+        need_thumb_2x,
         need_full,
+        need_saver,
+        need_avif,
+        need_widths = need_widths.len(),
         need_original,
+        need_video,
         "processing"
     );
 
-    // Only decode image if we need any webp variant
-    if need_micro || need_thumb || need_full {
-        let img = image::load_from_memory(&original_data)?;
+    // Only decode image if we need any variant, or a HEIF original converted
+    let need_variants = need_micro
+        || need_thumb
+        || need_thumb_2x
+        || need_full
+        || need_saver
+        || need_avif
+        || !need_widths.is_empty();
+    // Originals published as they are, and videos, which are decoded from a
+    // poster frame, are never read in whole
+    let rewrite_original =
+        need_original && (photo.is_heif() || strip_gps || photo.extension == "png");
+    let original_data = match original_data {
+        Some(data) => Some(data),
+        None if (need_variants && !photo.is_video()) || rewrite_original => {
+            Some(timings.time(Phase::Read, || {
+                Original::open(fs::File::open(&photo.source)?, outputs.map_originals)
+            })?)
+        }
+        None => None,
+    };
+    let image_data = original_data
+        .as_deref()
+        .map(|data| timings.time(Phase::Decode, || image_data(photo, data)))
+        .transpose()?;
+    let orientation = orientation(source.exif.as_ref().and_then(|e| e.orientation));
+    let img = if need_variants || (need_original && photo.is_heif()) {
+        let image_data = image_data.unwrap_or_default();
+        Some(timings.time(Phase::Decode, || decode(photo, image_data, orientation))?)
+    } else {
+        None
+    };
+
+    if need_variants && let Some(img) = &img {
+        let image_data = image_data.unwrap_or_default();
+        // Variants are made from one copy scaled to the largest of them.
+        // Animations are made from their frames, cropped where the still
+        // image is, so theirs stays full size
+        let largest = [
+            (need_micro, encodings.micro.size),
+            (need_thumb || need_avif_thumb, encodings.thumb.size),
+            (need_thumb_2x, encodings.thumb_2x.size),
+            (need_full || need_avif_full, encodings.full.size),
+            (need_saver_thumb, encodings.thumb_saver.size),
+            (need_saver_full, encodings.full_saver.size),
+        ]
+        .into_iter()
+        .filter_map(|(needed, size)| needed.then_some(size))
+        .chain(need_widths.iter().map(|&width| {
+            let longest = img.width().max(img.height());
+            (u64::from(width) * u64::from(longest)).div_ceil(u64::from(img.width().max(1))) as u32
+        }))
+        .max()
+        .unwrap_or(u32::MAX);
+        let base = timings.time(Phase::Encode, || {
+            if photo.animated {
+                Base::from(img)
+            } else {
+                Base::new(img, largest)
+            }
+        });
+
+        // Everything but the micro thumbnail is watermarked
+        let need_marked = need_thumb
+            || need_thumb_2x
+            || need_full
+            || need_saver
+            || need_avif
+            || !need_widths.is_empty();
+        let marked = outputs
+            .watermark
+            .filter(|_| need_marked)
+            .map(|watermark| timings.time(Phase::Encode, || base.map(|img| watermark.apply(img))));
+        let web = marked.as_ref().unwrap_or(&base);
+
+        // Cropped thumbnails are cut before marking, so the watermark stays in
+        let need_thumbs = need_thumb || need_thumb_2x || need_saver_thumb || need_avif_thumb;
+        let thumb_region = encodings
+            .thumb_crop
+            .filter(|_| need_thumbs)
+            .map(|ratio| crop::region(&base.img, ratio));
+        let thumb_img = thumb_region.map(|region| {
+            let cropped = base.crop(region);
+            match outputs.watermark {
+                Some(watermark) => cropped.map(|img| watermark.apply(img)),
+                None => cropped,
+            }
+        });
+        let thumb_web = thumb_img.as_ref().unwrap_or(web);
+
+        // Thumbnails and full-size images keep the animation of animated photos
+        let mark = |mut frames: Vec<Frame>| {
+            if let Some(watermark) = outputs.watermark {
+                for frame in &mut frames {
+                    let img = DynamicImage::ImageRgba8(frame.buffer().clone());
+                    *frame.buffer_mut() = watermark.apply(&img).to_rgba8();
+                }
+            }
+            frames
+        };
+        let frames = if photo.animated && (need_thumb || need_thumb_2x || need_full || need_saver) {
+            Some(timings.time(Phase::Decode, || {
+                animation::frames(image_data, &photo.extension)
+            })?)
+        } else {
+            None
+        };
+        let thumb_frames = frames
+            .as_deref()
+            .zip(thumb_region)
+            .map(|(frames, region)| mark(animation::crop(frames, region)));
+        let frames = frames.map(mark);
+        let generate = |encoding: Encoding, frames: Option<&[Frame]>, img: &Base| match frames {
+            Some(frames) => animation::encode(frames, encoding.size, encoding.quality),
+            None => generate_variant(img, encoding),
+        };
+        let thumb_frames = thumb_frames.as_deref().or(frames.as_deref());
+        let frames = frames.as_deref();
 
         if need_micro {
-            let micro_data = generate_variant(&img, MICRO_THUMB_SIZE, MICRO_THUMB_QUALITY)?;
-            fs::write(&micro_thumb_path, &micro_data)?;
+            save(timings, &micro_thumb_path, || {
+                generate_variant(&base, encodings.micro)
+            })?;
         }
 
         if need_thumb {
-            let thumb_data = generate_variant(&img, THUMB_SIZE, THUMB_QUALITY)?;
-            fs::write(&thumb_path, &thumb_data)?;
+            save(timings, &thumb_path, || {
+                generate(encodings.thumb, thumb_frames, thumb_web)
+            })?;
+        }
+
+        if need_thumb_2x {
+            save(timings, &thumb_2x_path, || {
+                generate(encodings.thumb_2x, thumb_frames, thumb_web)
+            })?;
         }
 
         if need_full {
-            let full_data = generate_variant(&img, FULL_SIZE, FULL_QUALITY)?;
-            fs::write(&full_path, &full_data)?;
+            save(timings, &full_path, || {
+                generate(encodings.full, frames, web)
+            })?;
+        }
+
+        if need_saver_thumb {
+            save(timings, &saver_thumb_path, || {
+                generate(encodings.thumb_saver, thumb_frames, thumb_web)
+            })?;
+        }
+
+        if need_saver_full {
+            save(timings, &saver_full_path, || {
+                generate(encodings.full_saver, frames, web)
+            })?;
+        }
+
+        for &width in &need_widths {
+            save(timings, &variant_path(Variant::Width(width)), || {
+                generate_width(web, width, encodings.full)
+            })?;
+        }
+
+        if let Some(avif) = avif {
+            if need_avif_thumb {
+                save(timings, &avif_thumb_path, || {
+                    generate_avif(thumb_web, encodings.thumb, avif)
+                })?;
+            }
+            if need_avif_full {
+                save(timings, &avif_full_path, || {
+                    generate_avif(web, encodings.full, avif)
+                })?;
+            }
         }
     }
 
+    timings.time(Phase::Encode, || {
+        previews(photo, &micro_thumb_path, outputs.placeholders)
+    })?;
+
     // Write original (with GPS stripped if needed)
     if need_original {
-        let final_original = if gps_mode != GpsMode::On {
-            // Use catch_unwind because little_exif can panic on malformed images
-            // Wrap in a span so little_exif's internal logging includes the file context
-            let ext = photo.extension.clone();
-            let _span = tracing::info_span!("strip_gps", file = %source_display).entered();
-            match panic::catch_unwind(AssertUnwindSafe(|| {
-                strip_gps_from_image(original_data, &ext)
-            })) {
-                Ok(result) => result?,
-                Err(_) => {
-                    tracing::warn!("GPS stripping panicked, copying original unchanged");
-                    // Re-read the original since we consumed it
-                    fs::read(&photo.source)?
-                }
+        match original_data.filter(|_| rewrite_original) {
+            Some(original_data) => {
+                save(timings, &original_path, || {
+                    let mut final_original =
+                        if let Some(img) = img.as_ref().filter(|_| photo.is_heif()) {
+                            // Browsers can't show HEIC, so the download is a JPEG, without metadata
+                            jpeg(img)?
+                        } else if strip_gps {
+                            // Wrap in a span so little_exif's internal logging includes the file context
+                            let _span =
+                                tracing::info_span!("strip_gps", file = %photo.source.display())
+                                    .entered();
+                            exif::strip_gps(&original_data, &photo.extension)
+                        } else {
+                            original_data.to_vec()
+                        };
+                    if photo.extension == "png" {
+                        final_original = minify::png(&final_original, outputs.optimize_png);
+                    }
+                    Ok(final_original)
+                })?;
             }
-        } else {
-            original_data
-        };
-        fs::write(&original_path, &final_original)?;
+            // Copied in chunks, without the source's permissions so it can be replaced later
+            None => {
+                timings.time(Phase::Write, || {
+                    io::copy(
+                        &mut fs::File::open(&photo.source)?,
+                        &mut fs::File::create(&original_path)?,
+                    )
+                })?;
+            }
+        }
+        locations
+            .cache
+            .set_original_stripped(original_key, strip_gps);
+    }
+
+    // Videos are published in every GPS mode, as the photo itself
+    if need_video {
+        timings.time(Phase::Write, || {
+            video::publish(&photo.source, &video_path, strip_gps)
+        })?;
+        locations.cache.set_original_stripped(video_key, strip_gps);
     }
 
     Ok(PhotoProcessingResult {
-        generated_webp: need_thumb || need_full,
-        copied_original: need_original,
+        generated_variants: need_thumb
+            || need_full
+            || need_saver
+            || need_avif
+            || !need_widths.is_empty(),
+        copied_original: need_original || need_video,
+        filtered_out: false,
     })
 }
 
-/// Generate a resized WebP variant of the image.
-fn generate_variant(img: &DynamicImage, max_size: u32, quality: f32) -> Result<Vec<u8>> {
-    // Resize if larger than max_size (preserve aspect ratio)
-    let resized = if img.width() > max_size || img.height() > max_size {
-        img.resize(max_size, max_size, FilterType::Lanczos3)
-    } else {
-        img.clone()
-    };
+/// Content hash of `file`, read in chunks rather than all at once. Based on
+/// content only, so it doesn't change with GPS mode.
+fn hash_file(file: &fs::File) -> Result<String> {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update_reader(file)?;
+    Ok(hasher.finalize().to_hex()[..8].to_string())
+}
 
-    // Encode as lossy WebP using the webp crate
-    let rgba = resized.to_rgba8();
-    let encoder = webp::Encoder::from_rgba(rgba.as_raw(), rgba.width(), rgba.height());
-    let webp_data = encoder.encode(quality);
+/// The contents of an original, read in or memory-mapped by size.
+#[derive(Debug)]
+enum Original {
+    Read(Vec<u8>),
+    Mapped(memmap2::Mmap),
+}
 
-    Ok(webp_data.to_vec())
+impl Original {
+    /// Read all of `file`, from the start whatever has been read of it
+    /// already, mapping it instead if it's large and `map` is set.
+    fn open(mut file: fs::File, map: bool) -> Result<Self> {
+        file.rewind()?;
+        if !map || file.metadata()?.len() < MMAP_SIZE {
+            let mut data = Vec::new();
+            (&file).read_to_end(&mut data)?;
+            return Ok(Self::Read(data));
+        }
+        // SAFETY: the file must not be truncated while the map is alive, as
+        // touching pages past its new end raises SIGBUS. `map` is only set
+        // for one-off builds, whose originals aren't expected to change until
+        // they finish; watched sites are read instead. Rewriting the file in
+        // place only gives wrong images for this build, and its new hash has
+        // it processed again next time.
+        let map = unsafe { memmap2::Mmap::map(&file)? };
+        Ok(Self::Mapped(map))
+    }
 }
 
-/// Get file extension as little_exif FileExtension.
-fn get_file_extension(extension: &str) -> Option<FileExtension> {
-    match extension.to_lowercase().as_str() {
-        "jpg" | "jpeg" => Some(FileExtension::JPEG),
-        "png" => Some(FileExtension::PNG {
-            as_zTXt_chunk: true,
-        }),
-        "webp" => Some(FileExtension::WEBP),
-        _ => None,
+impl std::ops::Deref for Original {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Self::Read(data) => data,
+            Self::Mapped(map) => map,
+        }
     }
 }
 
-/// Extract EXIF metadata from image data using little_exif.
-fn extract_exif(data: &Vec<u8>, extension: &str, gps_mode: GpsMode) -> PhotoMetadata {
-    let Some(file_type) = get_file_extension(extension) else {
-        return PhotoMetadata::default();
-    };
+/// Write the file `encode` makes to `path`, timing each separately.
+fn save(
+    timings: &mut PhotoTimings,
+    path: &Path,
+    encode: impl FnOnce() -> Result<Vec<u8>>,
+) -> Result<()> {
+    let data = timings.time(Phase::Encode, encode)?;
+    timings.time(Phase::Write, || fs::write(path, &data))?;
+    Ok(())
+}
 
-    let Ok(metadata) = Metadata::new_from_vec(data, file_type) else {
-        return PhotoMetadata::default();
+/// The encoded image in a photo's file: the embedded JPEG preview of RAW
+/// files, or the whole file.
+fn image_data<'a>(photo: &Photo, data: &'a [u8]) -> Result<&'a [u8]> {
+    if photo.is_raw() {
+        raw::preview(data)
+            .ok_or_else(|| Error::Other("no embedded JPEG preview in RAW file".to_string()))
+    } else {
+        Ok(data)
+    }
+}
+
+/// EXIF of a photo's file, or of the preview in RAW files the parsers can't read.
+/// Videos have none.
+fn read_exif(photo: &Photo, data: &[u8], image_data: &[u8]) -> Option<ExifData> {
+    if photo.is_video() {
+        return None;
+    }
+    exif::read(data, &photo.extension).or_else(|| {
+        photo
+            .is_raw()
+            .then(|| exif::read(image_data, "jpg"))
+            .flatten()
+    })
+}
+
+/// Hash, dimensions, and embedded metadata of a photo, from `cache` while
+/// its file is unchanged, or read from the file as processing reads it.
+pub fn source(photo: &Photo, cache: &MetadataCache) -> Result<cache::Source> {
+    let stamp = cache::stamp(&fs::metadata(&photo.source)?);
+    if let Some(source) = stamp.and_then(|stamp| cache.source(&photo.source, stamp)) {
+        return Ok(source);
+    }
+    let file = fs::File::open(&photo.source)?;
+    let hash = hash_file(&file)?;
+    // One photo at a time, so reading it whole holds a single copy
+    let data = Original::open(file, false)?;
+    let size_only = (data.len() as u64, 0);
+    read_source(photo, &data, stamp.unwrap_or(size_only), hash)
+}
+
+/// Capture date of the photo at `path` in EXIF format, from the fields its
+/// metadata takes it from.
+pub fn capture_date(source: &cache::Source, path: &Path) -> Option<String> {
+    source
+        .exif
+        .as_ref()
+        .and_then(|exif| exif.date_taken.clone())
+        .or_else(|| xmp::read_sidecar(path).and_then(|xmp| xmp.date_taken))
+        .or_else(|| source.xmp.as_ref().and_then(|xmp| xmp.date_taken.clone()))
+        .or_else(|| {
+            source
+                .iptc
+                .as_ref()
+                .and_then(|iptc| iptc.date_created.clone())
+        })
+        .or_else(|| source.video_date.clone())
+}
+
+/// Hash, dimensions, and embedded metadata of a photo's file `data`,
+/// whose size and modification time are `stamp`.
+fn read_source(
+    photo: &Photo,
+    data: &[u8],
+    stamp: cache::Stamp,
+    hash: String,
+) -> Result<cache::Source> {
+    let image_data = image_data(photo, data)?;
+
+    // Wrap in a span so little_exif's internal logging includes the file context
+    let exif = {
+        let _span = tracing::info_span!("exif", file = %photo.source.display()).entered();
+        read_exif(photo, data, image_data)
     };
 
-    // Extract date/time
-    let date_taken = metadata
-        .get_tag(&ExifTag::DateTimeOriginal(String::new()))
-        .next()
-        .and_then(|t| match t {
-            ExifTag::DateTimeOriginal(s) => Some(s.clone()),
-            _ => None,
-        });
+    // Reads the header only, without decoding the image
+    let mut video_date = None;
+    let (width, height) = if photo.is_video() {
+        let probe = video::probe(&photo.source)?;
+        video_date = probe.date_taken;
+        (probe.width, probe.height)
+    } else if photo.is_heif() {
+        heif::dimensions(image_data)?
+    } else {
+        let reader = image::ImageReader::new(Cursor::new(image_data))
+            .with_guessed_format()
+            .map_err(|e| crate::error::Error::Image(image::ImageError::IoError(e)))?;
+        let (width, height) = reader.into_dimensions()?;
+        oriented_dimensions(
+            width,
+            height,
+            orientation(exif.as_ref().and_then(|e| e.orientation)),
+        )
+    };
 
-    // Extract copyright
-    let copyright = metadata
-        .get_tag(&ExifTag::Copyright(String::new()))
-        .next()
-        .and_then(|t| match t {
-            ExifTag::Copyright(s) => Some(s.clone()),
-            _ => None,
-        });
+    Ok(cache::Source {
+        stamp,
+        hash,
+        width,
+        height,
+        animated: animation::is_animated(image_data, &photo.extension),
+        exif,
+        xmp: xmp::read_embedded(data),
+        iptc: iptc::read(data),
+        video_date,
+    })
+}
 
-    // Extract camera (Make + Model)
-    let make = metadata
-        .get_tag(&ExifTag::Make(String::new()))
-        .next()
-        .and_then(|t| match t {
-            ExifTag::Make(s) => Some(s.clone()),
-            _ => None,
-        });
+/// Set each album's cover and crop it to the `[crops]` aspect ratios.
+///
+/// Runs after photos are filtered and sorted, which decide the first photo.
+/// Crops are encoded like full-size images, at `encoding`, and watermarked
+/// like them. A dry run sets the covers without cropping them. Returns how
+/// many covers had crops generated.
+pub fn process_covers(
+    album: &mut Album,
+    images_dir: &Path,
+    crops: &BTreeMap<String, AspectRatio>,
+    encoding: Encoding,
+    watermark: Option<&Watermark>,
+    dry_run: bool,
+) -> usize {
+    let generated = AtomicUsize::new(0);
+    process_covers_recursive(
+        album, images_dir, crops, encoding, watermark, dry_run, &generated,
+    );
+    generated.load(Ordering::Relaxed)
+}
 
-    let model = metadata
-        .get_tag(&ExifTag::Model(String::new()))
-        .next()
-        .and_then(|t| match t {
-            ExifTag::Model(s) => Some(s.clone()),
-            _ => None,
-        });
+fn process_covers_recursive(
+    album: &mut Album,
+    images_dir: &Path,
+    crops: &BTreeMap<String, AspectRatio>,
+    encoding: Encoding,
+    watermark: Option<&Watermark>,
+    dry_run: bool,
+    generated: &AtomicUsize,
+) {
+    // Children first: a parent shares its cover with its first child
+    // when it has no photos of its own, and finds the crops already written
+    album.children.par_iter_mut().for_each(|child| {
+        process_covers_recursive(
+            child, images_dir, crops, encoding, watermark, dry_run, generated,
+        );
+    });
 
-    let camera = match (make, model) {
-        (Some(m), Some(mo)) => {
-            // Avoid duplication like "Canon Canon EOS R5"
-            if mo.starts_with(&m) {
-                Some(mo)
-            } else {
-                Some(format!("{} {}", m, mo))
+    album.cover = album.cover_photo().map(|(cover_album, photo)| {
+        let dir = images_dir.join(&cover_album.path);
+        // A dry run has no images to crop, but its pages refer to every crop
+        let written = if dry_run {
+            Ok(false)
+        } else {
+            write_crops(photo, &dir, crops, encoding, watermark)
+        };
+        match written {
+            Ok(true) => {
+                generated.fetch_add(1, Ordering::Relaxed);
+            }
+            Ok(false) => {}
+            Err(e) => {
+                tracing::warn!(photo = %photo.source.display(), error = %e, "failed to crop album cover");
             }
         }
-        (None, Some(mo)) => Some(mo),
-        (Some(m), None) => Some(m),
-        (None, None) => None,
-    };
+        Cover {
+            stem: photo.stem.clone(),
+            thumb_path: photo.thumb_path(&cover_album.path),
+            crops: crops
+                .iter()
+                .filter(|(_, ratio)| {
+                    dry_run || dir.join(photo.variant_file_name(Variant::Crop(**ratio))).exists()
+                })
+                .map(|(name, ratio)| {
+                    (
+                        format!("{}_path", name),
+                        photo.crop_path(&cover_album.path, *ratio),
+                    )
+                })
+                .collect(),
+        }
+    });
+}
 
-    // Extract lens
-    let lens = metadata
-        .get_tag(&ExifTag::LensModel(String::new()))
-        .next()
-        .and_then(|t| match t {
-            ExifTag::LensModel(s) => Some(s.clone()),
-            _ => None,
-        });
+/// Write any missing crops of a photo. Returns whether any were written.
+fn write_crops(
+    photo: &Photo,
+    images_dir: &Path,
+    crops: &BTreeMap<String, AspectRatio>,
+    encoding: Encoding,
+    watermark: Option<&Watermark>,
+) -> Result<bool> {
+    let mut missing: Vec<AspectRatio> = crops
+        .values()
+        .copied()
+        .filter(|&ratio| {
+            !images_dir
+                .join(photo.variant_file_name(Variant::Crop(ratio)))
+                .exists()
+        })
+        .collect();
+    missing.sort_unstable();
+    missing.dedup();
+    if missing.is_empty() {
+        return Ok(false);
+    }
 
-    // Extract GPS based on mode
-    let gps = match gps_mode {
-        GpsMode::Off => None,
-        GpsMode::General => {
-            extract_gps(&metadata).map(|(lat, lon)| GpsCoords::new_general(lat, lon))
-        }
-        GpsMode::On => extract_gps(&metadata).map(|(lat, lon)| GpsCoords::new(lat, lon)),
+    let data = fs::read(&photo.source)?;
+    let image_data = image_data(photo, &data)?;
+    let exif = {
+        let _span = tracing::info_span!("exif", file = %photo.source.display()).entered();
+        read_exif(photo, &data, image_data)
     };
+    let img = decode(
+        photo,
+        image_data,
+        orientation(exif.and_then(|e| e.orientation)),
+    )?;
+
+    for ratio in missing {
+        let (x, y, width, height) = crop::region(&img, ratio);
+        let mut cropped = img.crop_imm(x, y, width, height);
+        if let Some(watermark) = watermark {
+            cropped = watermark.apply(&cropped);
+        }
+        fs::write(
+            images_dir.join(photo.variant_file_name(Variant::Crop(ratio))),
+            generate_variant(&Base::from(&cropped), encoding.lossless_if(photo.lossless))?,
+        )?;
+    }
+    Ok(true)
+}
 
-    // Extract exposure info
-    let exposure = extract_exposure(&metadata);
+/// Decode a photo's image, convert it to sRGB, and turn it upright. Videos are represented by a
+/// poster frame, which ffmpeg rotates upright.
+fn decode(photo: &Photo, image_data: &[u8], orientation: Orientation) -> Result<DynamicImage> {
+    if photo.is_video() {
+        return Ok(image::load_from_memory(&video::poster(&photo.source)?)?);
+    }
+    if photo.is_heif() {
+        // libheif applies HEIF's own rotation, which takes precedence over EXIF
+        return heif::decode(image_data);
+    }
+    let mut decoder = image::ImageReader::new(Cursor::new(image_data))
+        .with_guessed_format()?
+        .into_decoder()?;
+    let icc = decoder.icc_profile()?;
+    let mut img = DynamicImage::from_decoder(decoder)?;
+    if let Some(icc) = icc {
+        img = color::to_srgb(img, &icc);
+    }
+    img.apply_orientation(orientation);
+    Ok(img)
+}
 
-    // Extract XMP rating
-    let rating = extract_xmp_rating(data);
+/// Encode the image as a JPEG, for originals browsers can't show.
+fn jpeg(img: &DynamicImage) -> Result<Vec<u8>> {
+    let rgb = img.to_rgb8();
+    let mut data = Vec::new();
+    JpegEncoder::new_with_quality(&mut data, JPEG_QUALITY).write_image(
+        rgb.as_raw(),
+        rgb.width(),
+        rgb.height(),
+        ExtendedColorType::Rgb8,
+    )?;
+    Ok(data)
+}
 
-    PhotoMetadata {
-        date_taken,
-        copyright,
-        camera,
-        lens,
-        gps,
-        exposure,
-        rating,
+/// Generate a resized WebP variant of the image.
+fn generate_variant(base: &Base, encoding: Encoding) -> Result<Vec<u8>> {
+    Ok(encoding.encode_webp(&rgba(&encoding.scale(base))))
+}
+
+/// The image's pixels as RGBA, converted only if they aren't already.
+fn rgba(img: &DynamicImage) -> Cow<'_, RgbaImage> {
+    match img.as_rgba8() {
+        Some(rgba) => Cow::Borrowed(rgba),
+        None => Cow::Owned(img.to_rgba8()),
+    }
+}
+
+/// Whether the photo's variants are encoded losslessly: screenshots and
+/// line art, which lossy WebP smears, usually come as PNGs or are kept
+/// together in an album.
+fn is_lossless(photo: &Photo, album_path: &Path, outputs: &Outputs) -> bool {
+    if photo.animated || photo.is_video() {
+        return false;
     }
+    (outputs.lossless_png && photo.extension.eq_ignore_ascii_case("png"))
+        || outputs
+            .lossless_albums
+            .iter()
+            .any(|album| album_path.starts_with(album))
 }
 
-/// Extract XMP data from image bytes and parse the rating.
+/// Encode at the highest quality up to `max_quality` whose output fits in
+/// `target` bytes, found by binary search. Images that don't fit even at
+/// [`MIN_TARGET_QUALITY`] are encoded at it, as small as they'll get
+/// without turning to mush.
+fn fit_target(encode: impl Fn(u8) -> Vec<u8>, max_quality: u8, target: usize) -> Vec<u8> {
+    let first = encode(max_quality);
+    if first.len() <= target {
+        return first;
+    }
+
+    // `high` is always a quality known not to fit
+    let (mut low, mut high) = (MIN_TARGET_QUALITY.min(max_quality), max_quality);
+    let mut fitting = None;
+    let mut smallest = first;
+    while low < high {
+        let quality = low + (high - low) / 2;
+        let data = encode(quality);
+        if data.len() <= target {
+            fitting = Some(data);
+            low = quality + 1;
+        } else {
+            smallest = data;
+            high = quality;
+        }
+    }
+    fitting.unwrap_or(smallest)
+}
+
+/// Set the photo's dominant color, and its placeholder if enabled. Both are
+/// made from the micro thumbnail, which is cheap to decode even for photos
+/// whose other variants are cached.
+fn previews(photo: &mut Photo, micro_thumb_path: &Path, placeholders: bool) -> Result<()> {
+    let micro = image::load_from_memory(&fs::read(micro_thumb_path)?)?;
+    photo.color = dominant_color(&micro);
+    if placeholders {
+        photo.placeholder = Some(placeholder(&micro));
+    }
+    Ok(())
+}
+
+/// Tiny WebP data URI of the image.
+fn placeholder(img: &DynamicImage) -> String {
+    let rgba = resized(img, PLACEHOLDER_SIZE).to_rgba8();
+    let encoder = webp::Encoder::from_rgba(rgba.as_raw(), rgba.width(), rgba.height());
+    format!(
+        "data:image/webp;base64,{}",
+        base64(&encoder.encode(PLACEHOLDER_QUALITY))
+    )
+}
+
+/// Most common color of the image as a CSS hex color (e.g., "#3a6ea5").
 ///
-/// XMP is embedded in JPEG/PNG files as XML. We search for the xpacket
-/// markers and parse the XMP content to get the xmp:Rating value.
-fn extract_xmp_rating(data: &[u8]) -> Option<u8> {
-    // Find XMP packet in the image data
-    // XMP packets are wrapped with <?xpacket begin="..." ?> and <?xpacket end="..." ?>
-    let xpacket_begin = b"<?xpacket begin=";
-    let xpacket_end = b"<?xpacket end=";
-
-    // Find the start of XMP data
-    let start_marker = data
-        .windows(xpacket_begin.len())
-        .position(|w| w == xpacket_begin)?;
-
-    // Find the end of XMP data
-    let end_marker = data[start_marker..]
-        .windows(xpacket_end.len())
-        .position(|w| w == xpacket_end)?;
-
-    // Extract XMP bytes (include the end marker and closing ?>)
-    let xmp_end = start_marker + end_marker + xpacket_end.len();
-    let xmp_slice = &data[start_marker..];
-
-    // Find the actual end of the xpacket end tag
-    let final_end = xmp_slice
-        .windows(2)
-        .skip(end_marker)
-        .position(|w| w == b"?>")
-        .map(|p| end_marker + p + 2)
-        .unwrap_or(xmp_end - start_marker);
-
-    let xmp_bytes = data[start_marker..start_marker + final_end].to_vec();
-
-    // Parse XMP
-    let xmp = Xmp::new(xmp_bytes).ok()?;
-
-    // Get the xmp:Rating value
-    let rating_tag = Tag::new(Namespace::Xmp, "Rating".to_string());
-    let rating_str = xmp.get(rating_tag)?;
-
-    // Parse as u8 (ratings are typically 0-5)
-    rating_str.parse::<u8>().ok()
-}
-
-/// Extract GPS coordinates from EXIF metadata.
-/// Returns (latitude, longitude) if both are present.
-fn extract_gps(metadata: &Metadata) -> Option<(f64, f64)> {
-    // Get latitude values (degrees, minutes, seconds as rationals)
-    let lat_vals = metadata
-        .get_tag(&ExifTag::GPSLatitude(Vec::new()))
-        .next()
-        .and_then(|t| match t {
-            ExifTag::GPSLatitude(vals) => Some(vals.clone()),
-            _ => None,
-        })?;
+/// Pixels are grouped into coarse buckets and the average of the fullest
+/// bucket is taken, so a blue sky over a small red boat comes out blue
+/// rather than the muddy average of both. Transparent pixels don't count.
+fn dominant_color(img: &DynamicImage) -> Option<String> {
+    let mut buckets: BTreeMap<[u8; 3], (u32, [u32; 3])> = BTreeMap::new();
+    for pixel in img.to_rgba8().pixels() {
+        let [r, g, b, a] = pixel.0;
+        if a < 128 {
+            continue;
+        }
+        let (count, sums) = buckets
+            .entry([
+                r >> COLOR_BUCKET_SHIFT,
+                g >> COLOR_BUCKET_SHIFT,
+                b >> COLOR_BUCKET_SHIFT,
+            ])
+            .or_default();
+        *count += 1;
+        for (sum, value) in sums.iter_mut().zip([r, g, b]) {
+            *sum += u32::from(value);
+        }
+    }
 
-    // Get latitude reference (N or S)
-    let lat_ref = metadata
-        .get_tag(&ExifTag::GPSLatitudeRef(String::new()))
-        .next()
-        .and_then(|t| match t {
-            ExifTag::GPSLatitudeRef(s) => Some(s.clone()),
-            _ => None,
-        })?;
+    // Ties go to the first bucket, so the result doesn't depend on pixel order
+    let (count, sums) = buckets
+        .into_values()
+        .rev()
+        .max_by_key(|&(count, _)| count)?;
+    let [r, g, b] = sums.map(|sum| (sum + count / 2) / count);
+    Some(format!("#{:02x}{:02x}{:02x}", r, g, b))
+}
 
-    // Get longitude values
-    let lon_vals = metadata
-        .get_tag(&ExifTag::GPSLongitude(Vec::new()))
-        .next()
-        .and_then(|t| match t {
-            ExifTag::GPSLongitude(vals) => Some(vals.clone()),
-            _ => None,
-        })?;
+/// Generate a WebP of the image scaled to `width`.
+fn generate_width(base: &Base, width: u32, full: Encoding) -> Result<Vec<u8>> {
+    let scaled = base.fit(width, u32::MAX, full.sharpen);
+    // Widths keep the full-size quality rather than fitting its target size
+    let encoding = Encoding {
+        target_kb: None,
+        ..full
+    };
+    Ok(encoding.encode_webp(&rgba(&scaled)))
+}
 
-    // Get longitude reference (E or W)
-    let lon_ref = metadata
-        .get_tag(&ExifTag::GPSLongitudeRef(String::new()))
-        .next()
-        .and_then(|t| match t {
-            ExifTag::GPSLongitudeRef(s) => Some(s.clone()),
-            _ => None,
-        })?;
+/// Width of a `width` by `height` image scaled down to fit within `max_size`.
+pub fn fitted_width(width: u32, height: u32, max_size: u32) -> u32 {
+    let longest = width.max(height);
+    if longest <= max_size {
+        return width;
+    }
+    (u64::from(width) * u64::from(max_size) / u64::from(longest)).max(1) as u32
+}
 
-    // Convert to decimal degrees
-    let lat = gps_rational_to_decimal(&lat_vals, &lat_ref)?;
-    let lon = gps_rational_to_decimal(&lon_vals, &lon_ref)?;
+/// Responsive widths generated for a photo `photo_width` pixels wide.
+///
+/// Configured widths at or above the photo's width are replaced by the photo's
+/// own width, since images aren't enlarged.
+pub fn ladder(widths: &[u32], photo_width: u32) -> Vec<u32> {
+    let mut ladder: Vec<u32> = widths
+        .iter()
+        .map(|&width| width.clamp(1, photo_width.max(1)))
+        .collect();
+    ladder.sort_unstable();
+    ladder.dedup();
+    ladder
+}
 
-    Some((lat, lon))
+/// Generate a resized AVIF variant of the image.
+fn generate_avif(base: &Base, encoding: Encoding, config: AvifConfig) -> Result<Vec<u8>> {
+    let scaled = encoding.scale(base);
+    let rgba = rgba(&scaled);
+    let mut data = Vec::new();
+    let encoder = AvifEncoder::new_with_speed_quality(
+        &mut data,
+        config.speed.clamp(1, 10),
+        config.quality.clamp(1, 100),
+    );
+    encoder.write_image(
+        rgba.as_raw(),
+        rgba.width(),
+        rgba.height(),
+        ExtendedColorType::Rgba8,
+    )?;
+    Ok(data)
 }
 
-/// Convert GPS rational values to decimal degrees.
-fn gps_rational_to_decimal(vals: &[uR64], direction: &str) -> Option<f64> {
-    if vals.len() < 3 {
-        return None;
+/// How to turn the stored pixels upright, from the EXIF Orientation tag.
+fn orientation(value: Option<u32>) -> Orientation {
+    value
+        .and_then(|value| u8::try_from(value).ok())
+        .and_then(Orientation::from_exif)
+        .unwrap_or(Orientation::NoTransforms)
+}
+
+/// Dimensions of a `width` by `height` image once `orientation` is applied.
+fn oriented_dimensions(width: u32, height: u32, orientation: Orientation) -> (u32, u32) {
+    match orientation {
+        Orientation::Rotate90
+        | Orientation::Rotate270
+        | Orientation::Rotate90FlipH
+        | Orientation::Rotate270FlipH => (height, width),
+        _ => (width, height),
     }
+}
 
-    let degrees = if vals[0].denominator != 0 {
-        vals[0].nominator as f64 / vals[0].denominator as f64
-    } else {
-        return None;
-    };
+/// The image scaled down to fit within `max_size` (preserving aspect ratio).
+/// Unsharp mask: add `amount` percent of each pixel's difference from a
+/// blurred copy, restoring the crispness lost when scaling down.
+fn sharpen(img: DynamicImage, amount: u16) -> DynamicImage {
+    if amount == 0 {
+        return img;
+    }
+    let rgba = img.into_rgba8();
+    let blurred = imageops::blur(&rgba, SHARPEN_SIGMA);
+    let amount = f32::from(amount) / 100.0;
+    let mut sharpened = rgba.clone();
+    for (out, (pixel, blur)) in sharpened
+        .pixels_mut()
+        .zip(rgba.pixels().zip(blurred.pixels()))
+    {
+        for c in 0..3 {
+            let (value, blur) = (f32::from(pixel[c]), f32::from(blur[c]));
+            out[c] = (value + amount * (value - blur)).round().clamp(0.0, 255.0) as u8;
+        }
+    }
+    DynamicImage::ImageRgba8(sharpened)
+}
 
-    let minutes = if vals[1].denominator != 0 {
-        vals[1].nominator as f64 / vals[1].denominator as f64
+fn resized(img: &DynamicImage, max_size: u32) -> DynamicImage {
+    if img.width() > max_size || img.height() > max_size {
+        img.resize(max_size, max_size, FilterType::Lanczos3)
     } else {
-        0.0
+        img.clone()
+    }
+}
+
+/// Extract metadata from what was read from a photo's file, and its sidecars.
+///
+/// EXIF is preferred for capture details; XMP (sidecar first, then embedded)
+/// supplies ratings, titles, and keywords, and fills in missing dates and GPS.
+/// IPTC IIM fills whatever is still missing and provides named locations.
+/// A location in the photo's galerie sidecar replaces GPS from the file, and
+/// photos without GPS are placed on the site's GPX tracks by capture time.
+fn extract_metadata(
+    file: &cache::Source,
+    source: &Path,
+    gps_mode: GpsMode,
+    locations: &Locations,
+) -> PhotoMetadata {
+    let exif = file.exif.as_ref();
+    let xmp = match (xmp::read_sidecar(source), file.xmp.clone()) {
+        (Some(sidecar), Some(embedded)) => sidecar.or(embedded),
+        (sidecar, embedded) => sidecar.or(embedded).unwrap_or_default(),
     };
 
-    let seconds = if vals[2].denominator != 0 {
-        vals[2].nominator as f64 / vals[2].denominator as f64
-    } else {
-        0.0
+    let iptc = file.iptc.clone().unwrap_or_default();
+    let location = iptc.location(gps_mode);
+    let sidecar = sidecar::read(source).unwrap_or_default();
+
+    if let Some(exif) = exif {
+        tracing::trace!(parser = ?exif.parser, "read EXIF");
+    }
+
+    let date_taken = exif
+        .and_then(|e| e.date_taken.clone())
+        .or(xmp.date_taken)
+        .or(iptc.date_created);
+
+    let gps = exif.and_then(|e| e.gps).or(xmp.gps);
+    let altitude = exif.and_then(|e| e.altitude).or(xmp.altitude);
+    let direction = exif.and_then(|e| e.direction).or(xmp.direction);
+    let track_point = match gps {
+        Some(_) => None,
+        None => date_taken
+            .as_deref()
+            .and_then(|d| locations.tracks.locate(d)),
     };
+    if let Some(point) = &track_point {
+        tracing::trace!(
+            latitude = point.latitude,
+            longitude = point.longitude,
+            "geotagged from GPX track"
+        );
+    }
 
-    let mut coord = degrees + minutes / 60.0 + seconds / 3600.0;
+    PhotoMetadata {
+        date_taken,
+        copyright: exif
+            .and_then(|e| e.copyright.clone())
+            .or(xmp.copyright)
+            .or(iptc.copyright),
+        camera: exif.and_then(camera_name),
+        lens: exif.and_then(|e| e.lens.clone()),
+        gps: match sidecar.location {
+            Some(location) => location_coords(location, gps_mode, locations),
+            None => match track_point {
+                Some(point) => gps_coords(
+                    Some((point.latitude, point.longitude)),
+                    point.elevation,
+                    None,
+                    gps_mode,
+                    locations,
+                ),
+                None => gps_coords(gps, altitude, direction, gps_mode, locations),
+            },
+        },
+        exposure: exif.and_then(extract_exposure),
+        rating: xmp.rating,
+        pick: xmp.pick,
+        label: xmp.label,
+        title: xmp.title.or(iptc.title),
+        description: xmp.description.or(iptc.caption),
+        keywords: if xmp.keywords.is_empty() {
+            iptc.keywords
+        } else {
+            xmp.keywords
+        },
+        creator: xmp.creator.or(iptc.creator),
+        location,
+        exif_parser: exif.map(|e| e.parser),
+    }
+}
 
-    // Apply direction
-    if direction == "S" || direction == "W" {
-        coord = -coord;
+/// Camera make and model, without repeating the make (e.g., "Canon Canon EOS R5").
+pub fn camera_name(exif: &ExifData) -> Option<String> {
+    match (exif.make.clone(), exif.model.clone()) {
+        (Some(make), Some(model)) if model.starts_with(&make) => Some(model),
+        (Some(make), Some(model)) => Some(format!("{} {}", make, model)),
+        (None, Some(model)) => Some(model),
+        (Some(make), None) => Some(make),
+        (None, None) => None,
     }
+}
 
-    Some(coord)
+/// Reverse geocode coordinates according to the GPS privacy mode.
+///
+/// Altitude and direction are as precise as the coordinates, so they're
+/// only kept in `on` mode.
+fn gps_coords(
+    gps: Option<(f64, f64)>,
+    altitude: Option<f64>,
+    direction: Option<f64>,
+    gps_mode: GpsMode,
+    locations: &Locations,
+) -> Option<GpsCoords> {
+    let (lat, lon) = gps?;
+    let place = || locations.cache.place(locations.geocoder, lat, lon);
+    match gps_mode {
+        GpsMode::Off => None,
+        GpsMode::General => Some(GpsCoords::new_general(&place(), locations.countries)),
+        GpsMode::On => Some(GpsCoords {
+            altitude,
+            direction,
+            ..GpsCoords::new(lat, lon, &place(), locations.countries)
+        }),
+    }
+}
+
+/// Resolve a location set in the photo's sidecar, honoring the GPS privacy mode.
+fn location_coords(
+    location: LocationOverride,
+    gps_mode: GpsMode,
+    locations: &Locations,
+) -> Option<GpsCoords> {
+    if gps_mode == GpsMode::Off {
+        return None;
+    }
+
+    match location {
+        LocationOverride::Coordinates { lat, lon } => {
+            gps_coords(Some((lat, lon)), None, None, gps_mode, locations)
+        }
+        LocationOverride::Place {
+            city,
+            region,
+            country,
+        } => Some(GpsCoords::named(
+            &city,
+            region.as_deref(),
+            country.as_deref(),
+            locations.countries,
+        )),
+        LocationOverride::Name(city) => {
+            Some(GpsCoords::named(&city, None, None, locations.countries))
+        }
+    }
 }
 
 /// Extract exposure settings from EXIF metadata.
-fn extract_exposure(metadata: &Metadata) -> Option<ExposureInfo> {
+fn extract_exposure(exif: &ExifData) -> Option<ExposureInfo> {
     // Aperture (FNumber)
-    let aperture = metadata
-        .get_tag(&ExifTag::FNumber(Vec::new()))
-        .next()
-        .and_then(|t| match t {
-            ExifTag::FNumber(vals) if !vals.is_empty() && vals[0].denominator != 0 => {
-                let f_num = vals[0].nominator as f64 / vals[0].denominator as f64;
-                Some(format!("f/{:.1}", f_num))
-            }
-            _ => None,
-        });
+    let aperture = exif
+        .f_number
+        .filter(|&(_, denom)| denom != 0)
+        .map(|(num, denom)| format!("f/{:.1}", num as f64 / denom as f64));
 
     // Shutter speed (ExposureTime)
-    let shutter_speed = metadata
-        .get_tag(&ExifTag::ExposureTime(Vec::new()))
-        .next()
-        .and_then(|t| match t {
-            ExifTag::ExposureTime(vals) if !vals.is_empty() && vals[0].denominator != 0 => {
-                let num = vals[0].nominator;
-                let denom = vals[0].denominator;
-                if num >= denom {
-                    // Whole seconds
-                    Some(format!("{}s", num / denom))
-                } else {
-                    // Fraction
-                    Some(format!("1/{}", denom / num))
-                }
+    let shutter_speed = exif
+        .exposure_time
+        .filter(|&(num, denom)| num != 0 && denom != 0)
+        .map(|(num, denom)| {
+            if num >= denom {
+                // Whole seconds
+                format!("{}s", num / denom)
+            } else {
+                // Fraction
+                format!("1/{}", denom / num)
             }
-            _ => None,
         });
 
     // ISO
-    let iso = metadata
-        .get_tag(&ExifTag::ISO(Vec::new()))
-        .next()
-        .and_then(|t| match t {
-            ExifTag::ISO(vals) if !vals.is_empty() => Some(vals[0] as u32),
-            _ => None,
-        });
+    let iso = exif.iso;
 
     // Focal length
-    let focal_length = metadata
-        .get_tag(&ExifTag::FocalLength(Vec::new()))
-        .next()
-        .and_then(|t| match t {
-            ExifTag::FocalLength(vals) if !vals.is_empty() && vals[0].denominator != 0 => {
-                let fl = vals[0].nominator / vals[0].denominator;
-                Some(format!("{}mm", fl))
-            }
-            _ => None,
-        });
+    let focal_length = exif
+        .focal_length
+        .filter(|&(_, denom)| denom != 0)
+        .map(|(num, denom)| format!("{}mm", num / denom));
 
     // Exposure program
-    let program = metadata
-        .get_tag(&ExifTag::ExposureProgram(Vec::new()))
-        .next()
-        .and_then(|t| match t {
-            ExifTag::ExposureProgram(vals) if !vals.is_empty() => {
-                exposure_program_key(vals[0] as u8)
-            }
-            _ => None,
-        });
+    let program = exif
+        .exposure_program
+        .and_then(|value| exposure_program_key(value as u8));
 
     // Only return Some if at least one field is present
     if aperture.is_some()
@@ -617,61 +1796,493 @@ fn exposure_program_key(value: u8) -> Option<String> {
     }
 }
 
-/// Strip GPS EXIF tags from image data.
-/// Preserves all other EXIF metadata (camera, lens, exposure, etc.).
-/// Takes ownership of data to avoid unnecessary copies.
-fn strip_gps_from_image(mut data: Vec<u8>, extension: &str) -> Result<Vec<u8>> {
-    let Some(file_type) = get_file_extension(extension) else {
-        // Unknown format, return unchanged
-        return Ok(data);
-    };
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::WatermarkConfig;
+    use image::{Rgba, RgbaImage};
+    use test_case::test_case;
+
+    /// Thumbnails published for `landscape.jpg` in a built preview site.
+    fn landscape_thumbs(site_dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(site_dir.join("dist/images"))
+            .unwrap()
+            .filter_map(|entry| entry.unwrap().file_name().into_string().ok())
+            .filter(|name| name.starts_with("landscape-") && name.ends_with("-thumb.webp"))
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn reexported_photo_held_until_accepted() {
+        let dir = tempfile::tempdir().unwrap();
+        crate::preview::generate(dir.path(), "basic").unwrap();
+        let build = |reexports| {
+            let mut site = crate::config::Site::load(&dir.path().join("site.toml")).unwrap();
+            site.reexports = reexports;
+            crate::pipeline::Pipeline::load(dir.path().to_path_buf(), site, false)
+                .unwrap()
+                .build()
+                .unwrap();
+            landscape_thumbs(dir.path())
+        };
+        let published = build(Reexports::Hold);
+
+        // Re-exported under the same name, later than the last build
+        let photo = dir.path().join("photos/landscape.jpg");
+        let mut data = Vec::new();
+        DynamicImage::ImageRgb8(image::RgbImage::new(800, 533))
+            .write_to(&mut Cursor::new(&mut data), image::ImageFormat::Jpeg)
+            .unwrap();
+        fs::write(&photo, data).unwrap();
+        let later = std::time::SystemTime::now() + std::time::Duration::from_secs(10);
+        fs::File::options()
+            .write(true)
+            .open(&photo)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+
+        assert_eq!(build(Reexports::Hold), published);
+        let accepted = build(Reexports::Allow);
+        assert_eq!(accepted.len(), 1);
+        assert_ne!(accepted, published);
+    }
 
-    let mut metadata = match Metadata::new_from_vec(&data, file_type) {
-        Ok(m) => m,
-        Err(_) => {
-            // No EXIF or parse error, return unchanged
-            return Ok(data);
+    #[test]
+    fn filtered_photos_not_processed() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut site = crate::preview::generate(dir.path(), "basic").unwrap();
+        // The preview's photos are unrated
+        site.filter = Some(FilterConfig {
+            min_rating: Some(1),
+            ..FilterConfig::default()
+        });
+        let mut pipeline =
+            crate::pipeline::Pipeline::load(dir.path().to_path_buf(), site, false).unwrap();
+        pipeline.build().unwrap();
+
+        assert!(pipeline.stats.total > 0);
+        assert_eq!((pipeline.stats.generated, pipeline.stats.copied), (0, 0));
+        let images = walkdir::WalkDir::new(dir.path().join("dist"))
+            .into_iter()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().starts_with(dir.path().join("dist/images")))
+            .filter(|entry| entry.file_type().is_file())
+            .count();
+        assert_eq!(images, 0);
+    }
+
+    #[test]
+    fn cancelled_build_keeps_cached_sources() {
+        let dir = tempfile::tempdir().unwrap();
+        let site = crate::preview::generate(dir.path(), "basic").unwrap();
+        crate::pipeline::Pipeline::load(dir.path().to_path_buf(), site, false)
+            .unwrap()
+            .build()
+            .unwrap();
+
+        // Cancelled before any photo is reached
+        let site = crate::config::Site::load(&dir.path().join("site.toml")).unwrap();
+        let mut pipeline =
+            crate::pipeline::Pipeline::load(dir.path().to_path_buf(), site, false).unwrap();
+        pipeline.cancel.store(true, Ordering::Relaxed);
+        assert!(matches!(pipeline.build(), Err(Error::Cancelled)));
+
+        let cache = MetadataCache::load(cache::path(dir.path()));
+        let photos: Vec<_> = walkdir::WalkDir::new(dir.path().join("photos"))
+            .into_iter()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_file())
+            .map(|entry| entry.into_path())
+            .collect();
+        assert!(!photos.is_empty());
+        for photo in photos {
+            let stamp = cache::stamp(&fs::metadata(&photo).unwrap()).unwrap();
+            assert!(cache.source(&photo, stamp).is_some(), "{}", photo.display());
         }
-    };
+    }
 
-    // Remove all GPS-related tags (complete list from EXIF GPS IFD)
-    metadata.remove_tag(ExifTag::GPSVersionID(Vec::new()));
-    metadata.remove_tag(ExifTag::GPSLatitudeRef(String::new()));
-    metadata.remove_tag(ExifTag::GPSLatitude(Vec::new()));
-    metadata.remove_tag(ExifTag::GPSLongitudeRef(String::new()));
-    metadata.remove_tag(ExifTag::GPSLongitude(Vec::new()));
-    metadata.remove_tag(ExifTag::GPSAltitudeRef(Vec::new()));
-    metadata.remove_tag(ExifTag::GPSAltitude(Vec::new()));
-    metadata.remove_tag(ExifTag::GPSTimeStamp(Vec::new()));
-    metadata.remove_tag(ExifTag::GPSSatellites(String::new()));
-    metadata.remove_tag(ExifTag::GPSStatus(String::new()));
-    metadata.remove_tag(ExifTag::GPSMeasureMode(String::new()));
-    metadata.remove_tag(ExifTag::GPSDOP(Vec::new()));
-    metadata.remove_tag(ExifTag::GPSSpeedRef(String::new()));
-    metadata.remove_tag(ExifTag::GPSSpeed(Vec::new()));
-    metadata.remove_tag(ExifTag::GPSTrackRef(String::new()));
-    metadata.remove_tag(ExifTag::GPSTrack(Vec::new()));
-    metadata.remove_tag(ExifTag::GPSImgDirectionRef(String::new()));
-    metadata.remove_tag(ExifTag::GPSImgDirection(Vec::new()));
-    metadata.remove_tag(ExifTag::GPSMapDatum(String::new()));
-    metadata.remove_tag(ExifTag::GPSDestLatitudeRef(String::new()));
-    metadata.remove_tag(ExifTag::GPSDestLatitude(Vec::new()));
-    metadata.remove_tag(ExifTag::GPSDestLongitudeRef(String::new()));
-    metadata.remove_tag(ExifTag::GPSDestLongitude(Vec::new()));
-    metadata.remove_tag(ExifTag::GPSDestBearingRef(String::new()));
-    metadata.remove_tag(ExifTag::GPSDestBearing(Vec::new()));
-    metadata.remove_tag(ExifTag::GPSDestDistanceRef(String::new()));
-    metadata.remove_tag(ExifTag::GPSDestDistance(Vec::new()));
-    metadata.remove_tag(ExifTag::GPSProcessingMethod(Vec::new()));
-    metadata.remove_tag(ExifTag::GPSAreaInformation(Vec::new()));
-    metadata.remove_tag(ExifTag::GPSDateStamp(String::new()));
-    metadata.remove_tag(ExifTag::GPSDifferential(Vec::new()));
-    metadata.remove_tag(ExifTag::GPSHPositioningError(Vec::new()));
-
-    // Write back to image (modifies data in-place)
-    metadata
-        .write_to_vec(&mut data, file_type)
-        .map_err(|e| crate::error::Error::Other(format!("EXIF write error: {}", e)))?;
+    #[test]
+    fn default_encodings_keep_plain_names() {
+        let names =
+            Encodings::new(&ProcessingConfig::default()).names(Some(AvifConfig::default()), None);
 
-    Ok(data)
+        assert_eq!(names, VariantNames::default());
+    }
+
+    #[test]
+    fn configured_encodings_are_named() {
+        let config = ProcessingConfig {
+            thumb: VariantConfig {
+                size: Some(800),
+                quality: None,
+                target_kb: None,
+            },
+            full: VariantConfig {
+                size: None,
+                quality: Some(120),
+                target_kb: None,
+            },
+            ..Default::default()
+        };
+        let avif = AvifConfig {
+            quality: 50,
+            speed: 6,
+        };
+
+        let names = Encodings::new(&config).names(Some(avif), None);
+
+        assert_eq!(names.micro, "micro");
+        assert_eq!(names.thumb, "thumb-800q80");
+        assert_eq!(names.full, "full-2400q100");
+        assert_eq!(names.avif_thumb, "thumb-800q50s6");
+        assert_eq!(names.avif_full, "full-2400q50s6");
+        assert_eq!(names.get(Variant::Width(960)), "w960q100");
+        let banner = AspectRatio {
+            width: 3,
+            height: 1,
+        };
+        assert_eq!(names.get(Variant::Crop(banner)), "crop3x1-2400q100");
+    }
+
+    #[test]
+    fn target_sizes_are_named() {
+        let config = ProcessingConfig {
+            full: VariantConfig {
+                target_kb: Some(300),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let names = Encodings::new(&config).names(None, None);
+
+        assert_eq!(names.thumb, "thumb");
+        assert_eq!(names.full, "full-2400q90k300");
+        assert_eq!(names.get(Variant::Width(960)), "w960");
+        let banner = AspectRatio {
+            width: 3,
+            height: 1,
+        };
+        assert_eq!(names.get(Variant::Crop(banner)), "crop3x1-2400q90k300");
+    }
+
+    #[test_case(90, 5050, 50 ; "fits partway")]
+    #[test_case(90, 9000, 90 ; "fits at max quality")]
+    #[test_case(90, 100, 20 ; "never fits")]
+    #[test_case(10, 100, 10 ; "max below minimum")]
+    fn test_fit_target(max_quality: u8, target: usize, expected: u8) {
+        // Output grows by 100 bytes per quality step; the quality is the first byte
+        let encode = |quality: u8| vec![quality; usize::from(quality) * 100];
+
+        assert_eq!(fit_target(encode, max_quality, target)[0], expected);
+    }
+
+    #[test]
+    fn target_size_met() {
+        let img = DynamicImage::ImageRgb8(image::RgbImage::from_fn(400, 300, |x, y| {
+            image::Rgb([((x * 7) ^ (y * 13)) as u8, (x * y) as u8, (x + y * 3) as u8])
+        }));
+        let fixed = generate_variant(&Base::from(&img), FULL).unwrap();
+        let target_kb = (fixed.len() / 2 / 1024) as u32;
+        let encoding = Encoding {
+            target_kb: Some(target_kb),
+            ..FULL
+        };
+
+        let fitted = generate_variant(&Base::from(&img), encoding).unwrap();
+
+        assert!(
+            fitted.len() <= target_kb as usize * 1024,
+            "{}",
+            fitted.len()
+        );
+        assert!(
+            fitted.len() > target_kb as usize * 1024 / 2,
+            "{}",
+            fitted.len()
+        );
+    }
+
+    #[test]
+    fn lossless_variants_are_named() {
+        let config = ProcessingConfig {
+            thumb: VariantConfig {
+                size: Some(800),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let names = Encodings::new(&config).lossless_if(true).names(None, None);
+
+        assert_eq!(names.micro, "micro-lossless");
+        assert_eq!(names.thumb, "thumb-800lossless");
+        assert_eq!(names.full, "full-lossless");
+        assert_eq!(names.thumb_saver, "thumb-saver");
+        assert_eq!(names.get(Variant::Width(960)), "w960-lossless");
+        let banner = AspectRatio {
+            width: 3,
+            height: 1,
+        };
+        assert_eq!(names.get(Variant::Crop(banner)), "crop3x1-lossless");
+    }
+
+    #[test]
+    fn cropped_thumbnails_are_named() {
+        let config = ProcessingConfig {
+            thumb_crop: Some(AspectRatio {
+                width: 1,
+                height: 1,
+            }),
+            ..Default::default()
+        };
+
+        let names = Encodings::new(&config).names(Some(AvifConfig::default()), None);
+
+        assert_eq!(names.thumb, "thumb-1x1");
+        assert_eq!(names.thumb_saver, "thumb-saver-1x1");
+        assert_eq!(names.avif_thumb, "thumb-1x1");
+        assert_eq!(names.full, "full");
+        assert_eq!(names.micro, "micro");
+    }
+
+    #[test]
+    fn double_thumbnails_are_named() {
+        let config = ProcessingConfig {
+            thumb: VariantConfig {
+                size: Some(500),
+                target_kb: Some(40),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let encodings = Encodings::new(&config);
+
+        assert_eq!(encodings.thumb_2x.size, 1000);
+        assert_eq!(encodings.thumb_2x.target_kb, Some(160));
+        assert_eq!(
+            Encodings::new(&ProcessingConfig::default())
+                .names(None, None)
+                .thumb_2x,
+            "thumb@2x"
+        );
+        assert_eq!(encodings.names(None, None).thumb_2x, "thumb-500q80k40@2x");
+    }
+
+    #[test]
+    fn sharpened_variants_are_named() {
+        let config = ProcessingConfig {
+            sharpen: 40,
+            ..Default::default()
+        };
+
+        let names = Encodings::new(&config).names(Some(AvifConfig::default()), None);
+
+        assert_eq!(names.micro, "micro");
+        assert_eq!(names.thumb, "thumb-sh40");
+        assert_eq!(names.thumb_2x, "thumb-sh40@2x");
+        assert_eq!(names.full_saver, "full-saver-sh40");
+        assert_eq!(names.avif_full, "full-sh40");
+        assert_eq!(names.get(Variant::Width(960)), "w960-sh40");
+        let banner = AspectRatio {
+            width: 3,
+            height: 1,
+        };
+        assert_eq!(names.get(Variant::Crop(banner)), "crop3x1-sh40");
+    }
+
+    #[test]
+    fn sharpening_raises_edge_contrast() {
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_fn(8, 1, |x, _| {
+            if x < 4 {
+                Rgba([100, 100, 100, 255])
+            } else {
+                Rgba([150, 150, 150, 255])
+            }
+        }));
+
+        let sharpened = sharpen(img.clone(), 100).to_rgba8();
+
+        assert!(sharpened.get_pixel(3, 0)[0] < 100);
+        assert!(sharpened.get_pixel(4, 0)[0] > 150);
+        assert_eq!(sharpened.get_pixel(0, 0)[0], 100);
+        assert_eq!(sharpened.get_pixel(4, 0)[3], 255);
+        assert_eq!(sharpen(img.clone(), 0), img);
+    }
+
+    #[test]
+    fn streamed_hash_matches_content_hash() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("large.tif");
+        // Larger than one read, so the hash spans several chunks
+        let data: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+        fs::write(&path, &data).unwrap();
+
+        let file = fs::File::open(&path).unwrap();
+
+        assert_eq!(hash_file(&file).unwrap(), blake3::hash(&data).to_hex()[..8]);
+        // Read from the start again after hashing
+        assert_eq!(&*Original::open(file, false).unwrap(), &data[..]);
+    }
+
+    #[test_case(1024, true, false ; "read")]
+    #[test_case(MMAP_SIZE, true, true ; "mapped")]
+    #[test_case(MMAP_SIZE, false, false ; "read when not mapping")]
+    fn originals_opened(size: u64, map: bool, mapped: bool) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("scan.tif");
+        fs::write(&path, b"II*\0").unwrap();
+        // Extended sparsely, so the large one takes no disk space
+        fs::OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_len(size)
+            .unwrap();
+
+        let original = Original::open(fs::File::open(&path).unwrap(), map).unwrap();
+
+        assert_eq!(matches!(original, Original::Mapped(_)), mapped);
+        assert_eq!(original.len() as u64, size);
+        assert_eq!(&original[..4], b"II*\0");
+    }
+
+    #[test]
+    fn variants_made_from_one_scaled_copy() {
+        let img = DynamicImage::ImageRgb8(image::RgbImage::from_fn(400, 300, |x, y| {
+            image::Rgb([((x * 7) ^ (y * 13)) as u8, (x * y) as u8, (x + y * 3) as u8])
+        }));
+        let base = Base::new(&img, 200);
+        assert_eq!((base.img.width(), base.img.height()), (200, 150));
+
+        // The largest variant is the copy itself, sharpened as if scaled from the original
+        assert!(matches!(base.fit(200, 200, 0), Cow::Borrowed(_)));
+        assert_ne!(*base.fit(200, 200, 50), *base.img);
+        assert_eq!(base.fit(100, 100, 0).width(), 100);
+        assert_eq!(base.fit(120, u32::MAX, 0).width(), 120);
+
+        // Regions map back to the original's size
+        let cropped = base.crop((0, 0, 150, 150));
+        assert_eq!(cropped.original, (300, 300));
+
+        // Photos already small enough are left alone, not sharpened
+        let small = Base::from(&img);
+        assert_eq!(*small.fit(600, 600, 50), img);
+    }
+
+    #[test]
+    fn lossless_keeps_pixels() {
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_fn(64, 48, |x, y| {
+            Rgba([(x * 4) as u8, (y * 5) as u8, ((x ^ y) * 3) as u8, 255])
+        }));
+
+        let data = generate_variant(&Base::from(&img), FULL.lossless_if(true)).unwrap();
+
+        let decoded = image::load_from_memory(&data).unwrap().to_rgba8();
+        assert_eq!(decoded, img.to_rgba8());
+    }
+
+    #[test]
+    fn watermarked_variants_are_named() {
+        let config = WatermarkConfig {
+            text: Some("© Jane Doe".to_string()),
+            image: None,
+            position: Default::default(),
+            opacity: 0.5,
+            scale: 0.2,
+        };
+        let watermark = Watermark::load(&config, Path::new(".")).unwrap();
+        let mark = format!("-wm{}", watermark.id());
+
+        let names = Encodings::new(&ProcessingConfig::default()).names(None, Some(&watermark));
+
+        assert_eq!(names.micro, "micro");
+        assert_eq!(names.thumb, format!("thumb{}", mark));
+        assert_eq!(names.full_saver, format!("full-saver{}", mark));
+        assert_eq!(names.get(Variant::Width(960)), format!("w960{}", mark));
+    }
+
+    #[test_case(&[480, 960, 1600], 4000, &[480, 960, 1600] ; "all narrower")]
+    #[test_case(&[1600, 480, 2400, 960], 2000, &[480, 960, 1600, 2000] ; "capped at photo width")]
+    #[test_case(&[2400, 3200], 2000, &[2000] ; "all wider")]
+    #[test_case(&[], 2000, &[] ; "none configured")]
+    fn test_ladder(widths: &[u32], photo_width: u32, expected: &[u32]) {
+        assert_eq!(ladder(widths, photo_width), expected);
+    }
+
+    #[test]
+    fn placeholder_is_tiny_webp_data_uri() {
+        let dir = tempfile::tempdir().unwrap();
+        let micro = dir.path().join("micro.webp");
+        let img = DynamicImage::new_rgb8(120, 80);
+        fs::write(
+            &micro,
+            generate_variant(&Base::from(&img), MICRO_THUMB).unwrap(),
+        )
+        .unwrap();
+
+        let uri = placeholder(&image::load_from_memory(&fs::read(&micro).unwrap()).unwrap());
+
+        assert!(uri.starts_with("data:image/webp;base64,UklGR"));
+        assert!(uri.len() < 400);
+    }
+
+    #[test]
+    fn dominant_color_of_largest_area() {
+        let mut img = RgbaImage::from_pixel(10, 10, Rgba([58, 110, 165, 255]));
+        for x in 0..3 {
+            img.put_pixel(x, 0, Rgba([200, 20, 20, 255]));
+        }
+        img.put_pixel(9, 9, Rgba([60, 108, 170, 255]));
+
+        let color = dominant_color(&DynamicImage::ImageRgba8(img));
+
+        assert_eq!(color.as_deref(), Some("#3a6ea5"));
+    }
+
+    #[test]
+    fn dominant_color_ignores_transparency() {
+        let mut img = RgbaImage::from_pixel(4, 4, Rgba([255, 255, 255, 0]));
+        img.put_pixel(0, 0, Rgba([0, 0, 0, 255]));
+
+        assert_eq!(
+            dominant_color(&DynamicImage::ImageRgba8(img)).as_deref(),
+            Some("#000000")
+        );
+        assert_eq!(dominant_color(&DynamicImage::new_rgba8(4, 4)), None);
+    }
+
+    #[test]
+    fn jpeg_original() {
+        let img = DynamicImage::new_rgb8(64, 48);
+
+        let data = jpeg(&img).unwrap();
+
+        assert!(data.starts_with(&[0xFF, 0xD8, 0xFF]));
+        let decoded = image::load_from_memory(&data).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (64, 48));
+    }
+
+    #[test_case(6000, 4000, 2400, 2400 ; "landscape")]
+    #[test_case(4000, 6000, 2400, 1600 ; "portrait")]
+    #[test_case(1000, 800, 2400, 1000 ; "smaller than max")]
+    fn test_fitted_width(width: u32, height: u32, max_size: u32, expected: u32) {
+        assert_eq!(fitted_width(width, height, max_size), expected);
+    }
+
+    #[test_case(Some(1), 6000, 4000, (6000, 4000) ; "upright")]
+    #[test_case(Some(3), 6000, 4000, (6000, 4000) ; "upside down")]
+    #[test_case(Some(6), 6000, 4000, (4000, 6000) ; "rotated clockwise")]
+    #[test_case(Some(8), 6000, 4000, (4000, 6000) ; "rotated counterclockwise")]
+    #[test_case(Some(9), 6000, 4000, (6000, 4000) ; "invalid")]
+    #[test_case(None, 6000, 4000, (6000, 4000) ; "missing")]
+    fn test_oriented_dimensions(value: Option<u32>, width: u32, height: u32, expected: (u32, u32)) {
+        assert_eq!(
+            oriented_dimensions(width, height, orientation(value)),
+            expected
+        );
+    }
 }
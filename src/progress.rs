@@ -0,0 +1,167 @@
+//! Progress bars for the long stages of a build.
+//!
+//! Processing a big library can take minutes between log lines, so while
+//! photos are processed and pages rendered a bar on stderr counts them, from
+//! the counts each stage already keeps for its summary. Photos are hashed,
+//! decoded, and encoded one after another by each worker, so one bar covers
+//! all of a photo's phases rather than one per phase. Bars are only drawn
+//! when stderr is a terminal and the build isn't `--quiet`.
+//! Log lines clear the bar before they're written, and it's drawn again below
+//! them on the next update.
+
+use std::io::{self, Write};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+/// Time between redraws, so thousands of fast updates don't flood the terminal.
+const REDRAW: Duration = Duration::from_millis(100);
+
+/// Width of the bar itself, in characters.
+const WIDTH: usize = 30;
+
+/// Whether a bar is on screen, for log lines to clear it first.
+static DRAWN: AtomicBool = AtomicBool::new(false);
+
+/// Erases the current terminal line.
+const CLEAR_LINE: &str = "\r\x1b[2K";
+
+/// Whether bars are drawn.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Progress {
+    enabled: bool,
+}
+
+impl Progress {
+    pub fn new(enabled: bool) -> Self {
+        Self { enabled }
+    }
+
+    /// A bar out of `total` steps of the stage `label`.
+    pub fn bar(self, label: &'static str, total: usize) -> Bar {
+        Bar {
+            label,
+            total,
+            last_drawn: self.enabled.then(|| Mutex::new(None)),
+        }
+    }
+}
+
+/// A stage's progress, cleared from the screen when dropped. The stage keeps
+/// the counts; the bar only draws them.
+#[derive(Debug)]
+pub struct Bar {
+    label: &'static str,
+    total: usize,
+    /// When the bar was last drawn, None if it's not drawn at all
+    last_drawn: Option<Mutex<Option<Instant>>>,
+}
+
+impl Bar {
+    /// Show `done` steps, redrawing with `detail` after the count if it's time.
+    pub fn set(&self, done: usize, detail: impl FnOnce() -> String) {
+        let Some(last_drawn) = &self.last_drawn else {
+            return;
+        };
+        // Another thread drawing is as good as drawing
+        let Ok(mut last_drawn) = last_drawn.try_lock() else {
+            return;
+        };
+        if last_drawn.is_some_and(|at| at.elapsed() < REDRAW) && done < self.total {
+            return;
+        }
+        *last_drawn = Some(Instant::now());
+        let line = render(self.label, done, self.total, &detail());
+        let mut stderr = io::stderr().lock();
+        let _ = write!(stderr, "{}{}", CLEAR_LINE, line);
+        let _ = stderr.flush();
+        DRAWN.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Drop for Bar {
+    fn drop(&mut self) {
+        if self.last_drawn.is_some() {
+            clear();
+        }
+    }
+}
+
+/// Erase the bar, if one is drawn.
+fn clear() {
+    if DRAWN.swap(false, Ordering::Relaxed) {
+        let mut stderr = io::stderr().lock();
+        let _ = write!(stderr, "{}", CLEAR_LINE);
+        let _ = stderr.flush();
+    }
+}
+
+/// One line of a bar: label, bar, count, and detail.
+fn render(label: &str, done: usize, total: usize, detail: &str) -> String {
+    let done = done.min(total);
+    let filled = (done * WIDTH).checked_div(total).unwrap_or(WIDTH);
+    let mut line = format!(
+        "{:<10} [{}{}] {}/{}",
+        label,
+        "#".repeat(filled),
+        " ".repeat(WIDTH - filled),
+        done,
+        total
+    );
+    if !detail.is_empty() {
+        line.push_str(" (");
+        line.push_str(detail);
+        line.push(')');
+    }
+    line
+}
+
+/// Writes log lines, clearing a bar first so the two don't run together.
+pub struct ClearBar<W> {
+    inner: W,
+    cleared: bool,
+}
+
+impl<W> ClearBar<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            cleared: false,
+        }
+    }
+}
+
+impl<W: Write> Write for ClearBar<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if !self.cleared {
+            self.cleared = true;
+            clear();
+        }
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(0, 10, "", "photos     [                              ] 0/10" ; "started")]
+    #[test_case(5, 10, "5 cached", "photos     [###############               ] 5/10 (5 cached)" ; "halfway")]
+    #[test_case(12, 10, "", "photos     [##############################] 10/10" ; "past the end")]
+    #[test_case(0, 0, "", "photos     [##############################] 0/0" ; "nothing to do")]
+    fn lines_rendered(done: usize, total: usize, detail: &str, expected: &str) {
+        assert_eq!(render("photos", done, total, detail), expected);
+    }
+
+    #[test]
+    fn disabled_bar_not_drawn() {
+        let bar = Progress::new(false).bar("photos", 2);
+        bar.set(1, || unreachable!("disabled bars don't ask for details"));
+        assert!(!DRAWN.load(Ordering::Relaxed));
+    }
+}
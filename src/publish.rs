@@ -0,0 +1,615 @@
+//! Build, verify, and deploy in one step, keeping a journal of what was published.
+//!
+//! `galerie publish` builds the site, checks the output is complete, and
+//! uploads it to the `[deploy]` target; each stage can be skipped. Every
+//! deploy, whether it worked or not, is appended to
+//! `.galerie-publish/journal.jsonl` in the site directory with the files
+//! added, changed, and removed since the last one that worked. Those are
+//! found by hashing the output and comparing it with the manifest of that
+//! deploy; files whose size and modification time are unchanged aren't
+//! hashed again.
+//!
+//! The last few deploys that worked are kept in `.galerie-publish/releases`,
+//! each with its manifest and what the target needs to go back to it, so
+//! `galerie deploy --rollback` can restore the one before the latest.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use walkdir::WalkDir;
+
+use crate::cache::{self, Stamp};
+use crate::checksums;
+use crate::config::Site;
+use crate::deploy::{self, State};
+use crate::error::{Error, Result};
+
+/// Directory of the journal and releases, relative to the site directory.
+pub const DIR_NAME: &str = ".galerie-publish";
+
+const JOURNAL_FILE: &str = "journal.jsonl";
+const RELEASES_DIR: &str = "releases";
+
+/// Files listed when verification fails.
+const LISTED_FAILURES: usize = 5;
+
+/// Stages after the build to run.
+#[derive(Debug, Clone, Copy)]
+pub struct Stages {
+    pub verify: bool,
+    pub deploy: bool,
+}
+
+/// Hash of each output file, by path relative to the output directory.
+pub type Manifest = BTreeMap<String, FileEntry>;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileEntry {
+    pub stamp: Stamp,
+    /// BLAKE3 of the content, in hex
+    pub hash: String,
+}
+
+/// Files that differ between two manifests.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Changes {
+    pub added: Vec<String>,
+    pub changed: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+/// A deploy that worked, kept for rolling back to.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Release {
+    /// Numbered in order of deploying
+    pub id: u64,
+    /// Unix time the deploy started
+    pub time: u64,
+    pub files: Manifest,
+    /// How to restore it, if the target can
+    pub state: Option<State>,
+}
+
+/// A deploy or rollback, as recorded in the journal.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Entry {
+    /// Unix time the deploy started
+    pub time: u64,
+    /// galerie version that published
+    pub version: String,
+    /// Where it was deployed to
+    pub target: String,
+    /// Release deployed, or rolled back to; absent if that failed
+    pub release: Option<u64>,
+    #[serde(default)]
+    pub rollback: bool,
+    pub seconds: f64,
+    /// Files in the output, and their total size
+    pub files: usize,
+    pub bytes: u64,
+    /// Why the deploy failed, if it did
+    pub error: Option<String>,
+    /// Changes since the last deploy that worked
+    #[serde(flatten)]
+    pub changes: Changes,
+}
+
+/// Verify and deploy the built output of the site in `site_dir`.
+pub fn publish(site_dir: &Path, site: &Site, stages: Stages) -> Result<()> {
+    let output_dir = site_dir.join(&site.build);
+    let dir = site_dir.join(DIR_NAME);
+    let releases = releases(&dir);
+    let latest = releases.last().and_then(|(_, path)| load_release(path));
+    let previous = latest.map(|release| release.files).unwrap_or_default();
+
+    let manifest = scan(&output_dir, &previous)?;
+    if stages.verify {
+        verify(&output_dir, site, &manifest)?;
+        tracing::info!(files = manifest.len(), "verified output");
+    } else {
+        tracing::info!("skipping verification");
+    }
+
+    let changes = diff(&previous, &manifest);
+    tracing::info!(
+        added = changes.added.len(),
+        changed = changes.changed.len(),
+        removed = changes.removed.len(),
+        "changes since the last deploy"
+    );
+
+    let deploy = match &site.deploy {
+        Some(deploy) if stages.deploy => deploy,
+        Some(_) => {
+            tracing::info!("skipping deploy");
+            return Ok(());
+        }
+        None => {
+            tracing::info!("nothing to deploy to; add [deploy] to site.toml");
+            return Ok(());
+        }
+    };
+
+    let time = now();
+    let started = Instant::now();
+    let target = deploy::describe(deploy);
+    tracing::info!(%target, "deploying");
+    let result = deploy::upload(deploy, site_dir, &output_dir, &dir);
+    let id = next_id(&dir, &releases);
+    let entry = Entry {
+        time,
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        target,
+        release: result.is_ok().then_some(id),
+        rollback: false,
+        seconds: started.elapsed().as_secs_f64(),
+        files: manifest.len(),
+        bytes: total_bytes(&manifest),
+        error: result.as_ref().err().map(ToString::to_string),
+        changes,
+    };
+    record(&dir, &entry)?;
+    let state = result?;
+    if state.is_none() {
+        tracing::info!("this target can't be rolled back");
+    }
+    let release = Release {
+        id,
+        time,
+        files: manifest,
+        state,
+    };
+    save_release(&dir, &release, deploy.keep)?;
+
+    tracing::info!(
+        seconds = entry.seconds,
+        files = entry.files,
+        release = id,
+        "deploy complete"
+    );
+    Ok(())
+}
+
+/// Restore the deploy before the latest, and forget the latest so another
+/// rollback goes further back.
+pub fn rollback(site_dir: &Path, site: &Site) -> Result<()> {
+    let failed = |message: String| Error::Publish {
+        stage: "roll back",
+        message,
+    };
+    let deploy = site
+        .deploy
+        .as_ref()
+        .ok_or_else(|| failed("there's no [deploy] in site.toml".to_string()))?;
+    let dir = site_dir.join(DIR_NAME);
+    let releases = releases(&dir);
+    let [.., (_, previous_path), (_, latest_path)] = releases.as_slice() else {
+        return Err(failed(format!(
+            "there's no earlier deploy to go back to ({} kept)",
+            releases.len()
+        )));
+    };
+    let (Some(previous), Some(latest)) = (load_release(previous_path), load_release(latest_path))
+    else {
+        return Err(failed("the kept deploys can't be read".to_string()));
+    };
+    let state = previous.state.as_ref().ok_or_else(|| {
+        failed(format!(
+            "deploy {} can't be restored; its target didn't support rolling back",
+            previous.id
+        ))
+    })?;
+
+    let time = now();
+    let started = Instant::now();
+    let target = deploy::describe(deploy);
+    tracing::info!(%target, from = latest.id, to = previous.id, "rolling back");
+    let result = deploy::restore(deploy, state, &dir);
+    let entry = Entry {
+        time,
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        target,
+        release: result.is_ok().then_some(previous.id),
+        rollback: true,
+        seconds: started.elapsed().as_secs_f64(),
+        files: previous.files.len(),
+        bytes: total_bytes(&previous.files),
+        error: result.as_ref().err().map(ToString::to_string),
+        changes: diff(&latest.files, &previous.files),
+    };
+    record(&dir, &entry)?;
+    result?;
+    fs::remove_file(latest_path)?;
+
+    tracing::info!(
+        seconds = entry.seconds,
+        release = previous.id,
+        "rollback complete"
+    );
+    Ok(())
+}
+
+/// Hash every file in `output_dir`, reusing hashes from `previous` for
+/// files with the same size and modification time.
+fn scan(output_dir: &Path, previous: &Manifest) -> Result<Manifest> {
+    if !output_dir.is_dir() {
+        return Err(Error::Publish {
+            stage: "verify",
+            message: format!("{} doesn't exist; build first", output_dir.display()),
+        });
+    }
+    let files: Vec<(String, PathBuf)> = WalkDir::new(output_dir)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(|entry| {
+            let relative = entry.path().strip_prefix(output_dir).ok()?;
+            let name = relative
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            Some((name, entry.into_path()))
+        })
+        .collect();
+
+    files
+        .into_par_iter()
+        .map(|(name, path)| {
+            let stamp = cache::stamp(&fs::metadata(&path)?).unwrap_or_default();
+            let hash = match previous.get(&name).filter(|file| file.stamp == stamp) {
+                Some(file) => file.hash.clone(),
+                None => {
+                    let mut hasher = blake3::Hasher::new();
+                    hasher.update_reader(fs::File::open(&path)?)?;
+                    hasher.finalize().to_hex().to_string()
+                }
+            };
+            Ok((name, FileEntry { stamp, hash }))
+        })
+        .collect()
+}
+
+/// Check the output has a home page and no empty images, and, with
+/// checksums, matches them.
+fn verify(output_dir: &Path, site: &Site, manifest: &Manifest) -> Result<()> {
+    let failed = |message: String| Error::Publish {
+        stage: "verify",
+        message,
+    };
+    if !manifest.contains_key("index.html") {
+        return Err(failed("no index.html in the output".to_string()));
+    }
+
+    let empty: Vec<&str> = manifest
+        .iter()
+        .filter(|(name, file)| name.starts_with("images/") && file.stamp.0 == 0)
+        .map(|(name, _)| name.as_str())
+        .collect();
+    if !empty.is_empty() {
+        return Err(failed(format!("empty images: {}", listed(&empty))));
+    }
+
+    if let Some(config) = &site.checksums {
+        let mismatched = checksums::verify(output_dir, config)?;
+        if !mismatched.is_empty() {
+            let names: Vec<&str> = mismatched.iter().map(String::as_str).collect();
+            return Err(failed(format!(
+                "files differ from {}: {}",
+                checksums::FILE_NAME,
+                listed(&names)
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// The first few of `names`, with how many more there are.
+fn listed(names: &[&str]) -> String {
+    let mut list = names[..names.len().min(LISTED_FAILURES)].join(", ");
+    if names.len() > LISTED_FAILURES {
+        list.push_str(&format!(" and {} more", names.len() - LISTED_FAILURES));
+    }
+    list
+}
+
+/// Files added, changed, and removed going from `before` to `after`.
+fn diff(before: &Manifest, after: &Manifest) -> Changes {
+    let mut changes = Changes::default();
+    for (name, file) in after {
+        match before.get(name) {
+            None => changes.added.push(name.clone()),
+            Some(old) if old.hash != file.hash => changes.changed.push(name.clone()),
+            Some(_) => {}
+        }
+    }
+    changes.removed = before
+        .keys()
+        .filter(|name| !after.contains_key(*name))
+        .cloned()
+        .collect();
+    changes
+}
+
+fn total_bytes(manifest: &Manifest) -> u64 {
+    manifest.values().map(|file| file.stamp.0).sum()
+}
+
+/// Append `entry` to the journal in `dir`.
+fn record(dir: &Path, entry: &Entry) -> Result<()> {
+    fs::create_dir_all(dir)?;
+    let line = serde_json::to_string(entry)
+        .map_err(|e| Error::Other(format!("failed to serialize publish journal: {}", e)))?;
+    let mut journal = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(dir.join(JOURNAL_FILE))?;
+    writeln!(journal, "{}", line)?;
+    Ok(())
+}
+
+/// Kept releases in `dir` by ID, oldest first.
+fn releases(dir: &Path) -> Vec<(u64, PathBuf)> {
+    let Ok(entries) = fs::read_dir(dir.join(RELEASES_DIR)) else {
+        return Vec::new();
+    };
+    let mut releases: Vec<(u64, PathBuf)> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let path = entry.path();
+            let id = path
+                .file_name()?
+                .to_str()?
+                .strip_suffix(".json")?
+                .parse()
+                .ok()?;
+            Some((id, path))
+        })
+        .collect();
+    releases.sort();
+    releases
+}
+
+/// One past the highest release ID used so far. Rolling back removes the
+/// latest release file, but its ID stays in the journal and isn't reused.
+fn next_id(dir: &Path, releases: &[(u64, PathBuf)]) -> u64 {
+    let journaled = fs::read_to_string(dir.join(JOURNAL_FILE))
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| serde_json::from_str::<Entry>(line).ok()?.release)
+        .max();
+    releases
+        .iter()
+        .map(|&(id, _)| id)
+        .chain(journaled)
+        .max()
+        .map_or(1, |id| id + 1)
+}
+
+fn load_release(path: &Path) -> Option<Release> {
+    let json = fs::read_to_string(path).ok()?;
+    serde_json::from_str(&json).ok()
+}
+
+/// Write `release` to `dir`, then drop the oldest beyond `keep`.
+fn save_release(dir: &Path, release: &Release, keep: usize) -> Result<()> {
+    let releases_dir = dir.join(RELEASES_DIR);
+    fs::create_dir_all(&releases_dir)?;
+    let json = serde_json::to_string(release)
+        .map_err(|e| Error::Other(format!("failed to serialize release: {}", e)))?;
+    let path = releases_dir.join(format!("{:06}.json", release.id));
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, json)?;
+    fs::rename(&tmp, &path)?;
+
+    // The latest is always kept, to compare the next deploy with
+    let kept = releases(dir);
+    let excess = kept.len().saturating_sub(keep.max(1));
+    for (_, path) in &kept[..excess] {
+        fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STAGES: Stages = Stages {
+        verify: true,
+        deploy: true,
+    };
+
+    fn site(command: &str) -> Site {
+        toml::from_str(&format!(
+            "domain = \"photos.example.com\"\n[deploy]\ncommand = '{}'",
+            command
+        ))
+        .unwrap()
+    }
+
+    fn journal(site_dir: &Path) -> Vec<Entry> {
+        fs::read_to_string(site_dir.join(DIR_NAME).join(JOURNAL_FILE))
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn deploys_journaled_with_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("dist");
+        fs::create_dir(&output).unwrap();
+        fs::write(output.join("index.html"), "home").unwrap();
+        fs::write(output.join("old.webp"), "old").unwrap();
+        let site = site(r#"test -f "$GALERIE_OUTPUT/index.html""#);
+
+        publish(dir.path(), &site, STAGES).unwrap();
+        fs::write(output.join("index.html"), "new home").unwrap();
+        fs::remove_file(output.join("old.webp")).unwrap();
+        fs::write(output.join("new.webp"), "new").unwrap();
+        publish(dir.path(), &site, STAGES).unwrap();
+
+        let entries = journal(dir.path());
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].changes.added, ["index.html", "old.webp"]);
+        assert_eq!(
+            entries[1].changes,
+            Changes {
+                added: vec!["new.webp".to_string()],
+                changed: vec!["index.html".to_string()],
+                removed: vec!["old.webp".to_string()],
+            }
+        );
+        assert_eq!((entries[1].files, entries[1].bytes), (2, 11));
+        assert_eq!(entries[1].error, None);
+    }
+
+    #[test]
+    fn failed_deploy_journaled_without_moving_on() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("dist");
+        fs::create_dir(&output).unwrap();
+        fs::write(output.join("index.html"), "home").unwrap();
+
+        let err = publish(dir.path(), &site("exit 3"), STAGES).unwrap_err();
+        publish(dir.path(), &site("true"), STAGES).unwrap();
+
+        assert!(matches!(
+            err,
+            Error::Deploy {
+                stage: "deploy",
+                source: deploy::DeployError::Exited { .. },
+            }
+        ));
+        let entries = journal(dir.path());
+        assert!(entries[0].error.is_some());
+        // The failed deploy's files still count as new
+        assert_eq!(entries[1].changes.added, ["index.html"]);
+    }
+
+    #[test]
+    fn incomplete_output_not_deployed() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("dist");
+        fs::create_dir(&output).unwrap();
+        fs::write(output.join("index.html"), "home").unwrap();
+        fs::create_dir(output.join("images")).unwrap();
+        fs::write(output.join("images/photo.webp"), "").unwrap();
+
+        let err = publish(dir.path(), &site("true"), STAGES).unwrap_err();
+
+        assert!(matches!(
+            err,
+            Error::Publish {
+                stage: "verify",
+                ..
+            }
+        ));
+        assert!(!dir.path().join(DIR_NAME).exists());
+    }
+
+    #[test]
+    fn git_deploy_rolled_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let remote = dir.path().join("remote.git");
+        let status = std::process::Command::new("git")
+            .args(["init", "-q", "--bare"])
+            .arg(&remote)
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let output = dir.path().join("dist");
+        fs::create_dir(&output).unwrap();
+        let site: Site = toml::from_str(&format!(
+            "domain = \"photos.example.com\"\n[deploy]\ngit = {{ repo = '{}' }}",
+            remote.display()
+        ))
+        .unwrap();
+        let deployed = || {
+            let output = std::process::Command::new("git")
+                .arg("-C")
+                .arg(&remote)
+                .args(["show", "gh-pages:index.html"])
+                .output()
+                .unwrap();
+            String::from_utf8(output.stdout).unwrap()
+        };
+
+        fs::write(output.join("index.html"), "first").unwrap();
+        publish(dir.path(), &site, STAGES).unwrap();
+        fs::write(output.join("index.html"), "second").unwrap();
+        publish(dir.path(), &site, STAGES).unwrap();
+        assert_eq!(deployed(), "second");
+
+        rollback(dir.path(), &site).unwrap();
+
+        assert_eq!(deployed(), "first");
+        let entries = journal(dir.path());
+        assert!(entries[2].rollback);
+        assert_eq!(entries[2].release, Some(1));
+        assert_eq!(entries[2].changes.changed, ["index.html"]);
+        // Only the first deploy is left, so there's nothing further back
+        assert_eq!(releases(&dir.path().join(DIR_NAME)).len(), 1);
+        assert!(rollback(dir.path(), &site).is_err());
+
+        // The rolled back deploy's ID isn't reused
+        fs::write(output.join("index.html"), "third").unwrap();
+        publish(dir.path(), &site, STAGES).unwrap();
+        assert_eq!(journal(dir.path())[3].release, Some(3));
+    }
+
+    #[test]
+    fn command_deploys_kept_but_not_rolled_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("dist");
+        fs::create_dir(&output).unwrap();
+        fs::write(output.join("index.html"), "home").unwrap();
+        let mut site = site("true");
+        site.deploy.as_mut().unwrap().keep = 2;
+
+        for _ in 0..3 {
+            publish(dir.path(), &site, STAGES).unwrap();
+        }
+        let err = rollback(dir.path(), &site).unwrap_err();
+
+        let kept: Vec<u64> = releases(&dir.path().join(DIR_NAME))
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect();
+        assert_eq!(kept, [2, 3]);
+        assert!(matches!(
+            err,
+            Error::Publish {
+                stage: "roll back",
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn unchanged_files_not_rehashed() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.webp"), "a").unwrap();
+        let first = scan(dir.path(), &Manifest::new()).unwrap();
+        let mut previous = first.clone();
+        previous.get_mut("a.webp").unwrap().hash = "remembered".to_string();
+
+        let second = scan(dir.path(), &previous).unwrap();
+
+        assert_eq!(second["a.webp"].hash, "remembered");
+    }
+}
@@ -0,0 +1,148 @@
+//! Embedded previews in camera RAW files.
+//!
+//! Decoding sensor data is a project of its own, but cameras also store a
+//! JPEG rendering of every RAW file, usually at or near full resolution.
+//! Rather than walk each container (TIFF for NEF, ARW, and DNG; ISO BMFF for
+//! CR3), the file is scanned for complete JPEG streams and the one with the
+//! most pixels is used. Lossless JPEG, which some formats use for the sensor
+//! data itself, is skipped.
+
+/// The largest JPEG embedded in `data`, if any.
+pub fn preview(data: &[u8]) -> Option<&[u8]> {
+    let mut best: Option<(u64, &[u8])> = None;
+    let mut pos = 0;
+
+    while let Some(offset) = find_jpeg_start(&data[pos..]) {
+        let start = pos + offset;
+        match parse_jpeg(&data[start..]) {
+            Some((pixels, len)) => {
+                if best.is_none_or(|(most, _)| pixels > most) {
+                    best = Some((pixels, &data[start..start + len]));
+                }
+                // Thumbnails nested inside this JPEG are never larger
+                pos = start + len;
+            }
+            None => pos = start + 2,
+        }
+    }
+
+    best.map(|(_, jpeg)| jpeg)
+}
+
+/// Offset of the next JPEG start of image marker followed by another marker.
+fn find_jpeg_start(data: &[u8]) -> Option<usize> {
+    data.windows(3).position(|w| w == [0xFF, 0xD8, 0xFF])
+}
+
+/// Pixel count and byte length of the baseline or progressive JPEG at the
+/// start of `data`, or None if it isn't one or is truncated.
+fn parse_jpeg(data: &[u8]) -> Option<(u64, usize)> {
+    let mut pixels = None;
+    let mut pos = 2;
+
+    loop {
+        if *data.get(pos)? != 0xFF {
+            return None;
+        }
+        // Markers may be preceded by any number of fill bytes
+        while *data.get(pos)? == 0xFF {
+            pos += 1;
+        }
+        let marker = data[pos];
+        pos += 1;
+
+        match marker {
+            // End of image
+            0xD9 => return pixels.map(|pixels| (pixels, pos)),
+            // Standalone markers without a length
+            0x01 | 0xD0..=0xD7 => continue,
+            0xD8 => return None,
+            _ => {}
+        }
+
+        let length = usize::from(u16::from_be_bytes([*data.get(pos)?, *data.get(pos + 1)?]));
+        let segment = data.get(pos + 2..pos + length)?;
+        match marker {
+            // Baseline, extended, and progressive Huffman frames
+            0xC0..=0xC2 => {
+                let height = u16::from_be_bytes([*segment.get(1)?, *segment.get(2)?]);
+                let width = u16::from_be_bytes([*segment.get(3)?, *segment.get(4)?]);
+                pixels = Some(u64::from(width) * u64::from(height));
+            }
+            // Lossless, hierarchical, and arithmetic-coded frames
+            0xC3 | 0xC5..=0xC7 | 0xC9..=0xCB | 0xCD..=0xCF => return None,
+            _ => {}
+        }
+        pos += length;
+
+        if marker == 0xDA {
+            pos += entropy_coded_length(data.get(pos..)?)?;
+        }
+    }
+}
+
+/// Length of the entropy-coded data after a start of scan segment, up to
+/// the next marker. Stuffed `FF 00` bytes and restart markers are part of it.
+fn entropy_coded_length(data: &[u8]) -> Option<usize> {
+    let mut pos = 0;
+    loop {
+        pos += data.get(pos..)?.iter().position(|&b| b == 0xFF)?;
+        match *data.get(pos + 1)? {
+            0x00 | 0xD0..=0xD7 | 0xFF => pos += 1,
+            _ => return Some(pos),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::codecs::jpeg::JpegEncoder;
+    use image::{DynamicImage, ImageEncoder};
+
+    fn jpeg(width: u32, height: u32) -> Vec<u8> {
+        let img = DynamicImage::new_rgb8(width, height).to_rgb8();
+        let mut data = Vec::new();
+        JpegEncoder::new(&mut data)
+            .write_image(img.as_raw(), width, height, image::ExtendedColorType::Rgb8)
+            .unwrap();
+        data
+    }
+
+    #[test]
+    fn finds_largest_preview() {
+        let small = jpeg(16, 12);
+        let large = jpeg(64, 48);
+        let mut data = b"II*\0 sensor data \xff\xd8 not a jpeg ".to_vec();
+        data.extend_from_slice(&small);
+        data.extend_from_slice(b"\x00\x01\x02 more sensor data");
+        data.extend_from_slice(&large);
+        data.extend_from_slice(b"\xff\xd8\xff\xe1 truncated");
+
+        let preview = preview(&data).unwrap();
+
+        assert_eq!(preview, large.as_slice());
+        let img = image::load_from_memory(preview).unwrap();
+        assert_eq!((img.width(), img.height()), (64, 48));
+    }
+
+    #[test]
+    fn nested_thumbnail_is_skipped() {
+        // A thumbnail inside an APP1 segment, as EXIF stores it
+        let thumbnail = jpeg(8, 6);
+        let outer = jpeg(32, 24);
+        let mut app1 = vec![0xFF, 0xE1];
+        app1.extend_from_slice(&((thumbnail.len() + 2) as u16).to_be_bytes());
+        app1.extend_from_slice(&thumbnail);
+        let mut data = outer[..2].to_vec();
+        data.extend_from_slice(&app1);
+        data.extend_from_slice(&outer[2..]);
+
+        assert_eq!(preview(&data).unwrap().len(), data.len());
+    }
+
+    #[test]
+    fn no_jpeg() {
+        assert_eq!(preview(b"II*\0 only sensor data"), None);
+    }
+}
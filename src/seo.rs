@@ -0,0 +1,257 @@
+//! Page titles and descriptions from `[seo]` templates.
+//!
+//! Templates are plain text with `{placeholder}`s for site, album, and photo
+//! metadata. A placeholder with no value on a page is dropped along with the
+//! separator after it (or before it, at the end of the template), so one
+//! template covers every page: `{photo} — {album} — {site}` gives
+//! `Beach — Iceland — My Photos` on a photo page, `Iceland — My Photos` on
+//! an album page, and `My Photos` on the index.
+
+use serde::Serialize;
+
+use crate::config::SeoConfig;
+use crate::error::{Error, Result};
+use crate::photos::{Album, Photo};
+use crate::util::iso_datetime;
+
+/// Placeholders templates may use.
+const PLACEHOLDERS: &[&str] = &[
+    "site",
+    "album",
+    "photo",
+    "description",
+    "date",
+    "camera",
+    "lens",
+    "location",
+    "creator",
+    "copyright",
+    "keywords",
+];
+
+/// Title and description of a rendered page.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct Seo {
+    pub title: String,
+    /// Empty when the template has nothing to fill it with
+    pub description: String,
+}
+
+/// What a page is about. Album is None on the index and for photos in the root album.
+#[derive(Debug, Clone, Copy)]
+pub struct Page<'a> {
+    pub site: &'a str,
+    pub album: Option<&'a Album>,
+    pub photo: Option<&'a Photo>,
+}
+
+impl Page<'_> {
+    /// Value of a placeholder on this page.
+    fn value(&self, name: &str) -> Option<String> {
+        let metadata = self.photo.map(|photo| &photo.metadata);
+        let value = match name {
+            "site" => Some(self.site.to_string()),
+            "album" => self.album.map(|album| album.name.clone()),
+            "photo" => self
+                .photo
+                .map(|photo| photo.metadata.title.clone().unwrap_or(photo.stem.clone())),
+            "description" => metadata.and_then(|m| m.description.clone()),
+            // Date part of the timestamp
+            "date" => metadata.and_then(|m| m.date_taken.as_deref()).map(|date| {
+                let date = iso_datetime(date);
+                date.split('T').next().unwrap_or(&date).to_string()
+            }),
+            "camera" => metadata.and_then(|m| m.camera.clone()),
+            "lens" => metadata.and_then(|m| m.lens.clone()),
+            "location" => metadata.and_then(|m| {
+                let (city, country) = match (&m.location, &m.gps) {
+                    (Some(location), _) => (&location.city, &location.country),
+                    (None, Some(gps)) => (&gps.city, &gps.country),
+                    (None, None) => return None,
+                };
+                let parts: Vec<&str> = [city, country]
+                    .into_iter()
+                    .flatten()
+                    .map(String::as_str)
+                    .collect();
+                Some(parts.join(", "))
+            }),
+            "creator" => metadata.and_then(|m| m.creator.clone()),
+            "copyright" => metadata.and_then(|m| m.copyright.clone()),
+            "keywords" => metadata.map(|m| m.keywords.join(", ")),
+            _ => None,
+        };
+        value.filter(|value| !value.trim().is_empty())
+    }
+}
+
+/// Check that templates only use known placeholders.
+pub fn validate(config: &SeoConfig) -> Result<()> {
+    for (key, template) in [
+        ("title_template", &config.title_template),
+        ("description_template", &config.description_template),
+    ] {
+        for part in parse(template) {
+            if let Part::Field(name) = part
+                && !PLACEHOLDERS.contains(&name)
+            {
+                return Err(Error::Other(format!(
+                    "unknown placeholder '{{{}}}' in seo.{} (expected one of: {})",
+                    name,
+                    key,
+                    PLACEHOLDERS.join(", ")
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Title and description for a page.
+pub fn render(config: &SeoConfig, page: &Page) -> Seo {
+    Seo {
+        title: fill(&config.title_template, page),
+        description: fill(&config.description_template, page),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Part<'a> {
+    Text(&'a str),
+    Field(&'a str),
+}
+
+/// Split a template into text and `{placeholder}`s. Unclosed braces are text.
+fn parse(template: &str) -> Vec<Part<'_>> {
+    let mut parts = Vec::new();
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        let Some(close) = rest[open..].find('}') else {
+            break;
+        };
+        if open > 0 {
+            parts.push(Part::Text(&rest[..open]));
+        }
+        parts.push(Part::Field(&rest[open + 1..open + close]));
+        rest = &rest[open + close + 1..];
+    }
+    if !rest.is_empty() {
+        parts.push(Part::Text(rest));
+    }
+    parts
+}
+
+/// Fill a template, dropping empty placeholders and their separators.
+fn fill(template: &str, page: &Page) -> String {
+    let parts = parse(template);
+
+    // Text before the first placeholder, then each placeholder with the text after it
+    let mut prefix = "";
+    let mut fields: Vec<(Option<String>, &str)> = Vec::new();
+    for part in parts {
+        match (part, fields.last_mut()) {
+            (Part::Text(text), Some((_, separator))) => *separator = text,
+            (Part::Text(text), None) => prefix = text,
+            (Part::Field(name), _) => fields.push((page.value(name), "")),
+        }
+    }
+    let suffix = fields.last().map_or("", |(_, text)| *text);
+
+    let kept: Vec<_> = fields
+        .iter()
+        .filter_map(|(value, separator)| value.as_deref().map(|value| (value, *separator)))
+        .collect();
+
+    let mut output = prefix.to_string();
+    for (i, (value, separator)) in kept.iter().enumerate() {
+        output.push_str(value);
+        output.push_str(if i + 1 == kept.len() {
+            suffix
+        } else {
+            separator
+        });
+    }
+    if kept.is_empty() && !fields.is_empty() {
+        output.push_str(suffix);
+        return output.trim().to_string();
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::photos::PhotoMetadata;
+    use std::path::PathBuf;
+    use test_case::test_case;
+
+    fn album() -> Album {
+        Album {
+            name: "Iceland".to_string(),
+            slug: "iceland".to_string(),
+            path: PathBuf::from("iceland"),
+            dir: PathBuf::from("iceland"),
+            aliases: Vec::new(),
+            photos: Vec::new(),
+            children: Vec::new(),
+            cover: None,
+            stats: Default::default(),
+        }
+    }
+
+    fn photo() -> Photo {
+        Photo::processed(
+            "iceland/DSC01234.jpg",
+            PhotoMetadata {
+                date_taken: Some("2024:06:15 14:30:00".to_string()),
+                camera: Some("Canon EOS R5".to_string()),
+                description: Some("Black sand at dawn".to_string()),
+                ..Default::default()
+            },
+        )
+    }
+
+    #[test_case("{photo} — {album} — {site}", true, true, "DSC01234 — Iceland — My Photos" ; "photo page")]
+    #[test_case("{photo} — {album} — {site}", true, false, "Iceland — My Photos" ; "album page")]
+    #[test_case("{photo} — {album} — {site}", false, false, "My Photos" ; "index page")]
+    #[test_case("{photo} ({camera}, {date})", true, true, "DSC01234 (Canon EOS R5, 2024-06-15)" ; "metadata fields")]
+    #[test_case("{photo} ({lens}, {date})", true, true, "DSC01234 (2024-06-15)" ; "missing middle field")]
+    #[test_case("{photo} ({date}, {lens})", true, true, "DSC01234 (2024-06-15)" ; "missing last field")]
+    #[test_case("{description}", true, false, "" ; "nothing to fill")]
+    #[test_case("Photos by Ana", false, false, "Photos by Ana" ; "no placeholders")]
+    fn test_fill(template: &str, with_album: bool, with_photo: bool, expected: &str) {
+        let album = album();
+        let photo = photo();
+        let page = Page {
+            site: "My Photos",
+            album: with_album.then_some(&album),
+            photo: with_photo.then_some(&photo),
+        };
+
+        assert_eq!(fill(template, &page), expected);
+    }
+
+    #[test]
+    fn photo_title_preferred_over_filename() {
+        let mut photo = photo();
+        photo.metadata.title = Some("Reynisfjara".to_string());
+        let page = Page {
+            site: "My Photos",
+            album: None,
+            photo: Some(&photo),
+        };
+
+        assert_eq!(fill("{photo} — {site}", &page), "Reynisfjara — My Photos");
+    }
+
+    #[test_case("{photo} — {site}", true ; "known")]
+    #[test_case("{photo} — {sitename}", false ; "unknown")]
+    fn test_validate(title_template: &str, valid: bool) {
+        let config = SeoConfig {
+            title_template: title_template.to_string(),
+            ..Default::default()
+        };
+
+        assert_eq!(validate(&config).is_ok(), valid);
+    }
+}
@@ -0,0 +1,640 @@
+//! Development server for built sites.
+//!
+//! Serves the output directory over HTTP with tiny_http, mapping directories
+//! to their `index.html`. Meant for previewing and small self-hosted setups
+//! rather than as a production web server. Like a production server, it
+//! honors `Accept-Encoding`: precompressed `.br` and `.gz` siblings of a
+//! file are served when the browser accepts them, and text is otherwise
+//! gzipped on the fly, so transfer sizes match what visitors download.
+//!
+//! Outside daemon mode it also has tools for theme development. With
+//! `--listing`, directories without an `index.html` get a generated listing
+//! of their files, to help inspect the output's structure. And
+//! `/_galerie/resize?src=…&w=…` scales any image in the output (usually a
+//! published original) to the requested width on demand, so new variant
+//! sizes can be tried before configuring them.
+//!
+//! When watching, with a rebuild token configured, `POST /_galerie/rebuild`
+//! with `Authorization: Bearer <token>` starts a rebuild at once, without
+//! waiting for changes to settle, so a sync job on another machine can ask
+//! for one when it finishes.
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageDecoder, ImageReader};
+use quick_xml::escape::escape;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::error::{Error, Result};
+use crate::util::url_encode;
+
+/// Path of the on-demand resize endpoint.
+const RESIZE_PATH: &str = "_galerie/resize";
+
+/// Path of the rebuild endpoint.
+const REBUILD_PATH: &str = "_galerie/rebuild";
+
+/// Environment variable setting the rebuild token, overriding `rebuild_token`.
+pub const REBUILD_TOKEN_ENV_VAR: &str = "GALERIE_REBUILD_TOKEN";
+
+/// Widest image the resize endpoint makes.
+const MAX_RESIZE_WIDTH: u32 = 8192;
+
+/// Tallest image WebP can hold.
+const MAX_WEBP_HEIGHT: u32 = 16383;
+
+/// WebP quality of resized images unless the request sets `q`.
+const RESIZE_QUALITY: u8 = 80;
+
+/// Development tools answered besides the site's files.
+#[derive(Debug, Clone, Default)]
+pub struct Tools {
+    /// List directories without an `index.html`
+    pub listing: bool,
+    /// Answer `/_galerie/resize` requests
+    pub resize: bool,
+    /// Answer `/_galerie/rebuild` requests
+    pub rebuild: Option<Trigger>,
+}
+
+/// Rebuilds requested over HTTP by clients holding the token.
+#[derive(Debug, Clone)]
+pub struct Trigger {
+    token: String,
+    /// Set by a request, and cleared by the watcher when it starts the rebuild
+    pub requested: Arc<AtomicBool>,
+}
+
+impl Trigger {
+    /// A trigger for the token in the environment, or else `configured`,
+    /// unless neither is set.
+    pub fn new(configured: Option<&str>) -> Option<Self> {
+        let token = std::env::var(REBUILD_TOKEN_ENV_VAR)
+            .ok()
+            .or_else(|| configured.map(str::to_string))
+            .filter(|token| !token.is_empty())?;
+        Some(Self {
+            token,
+            requested: Arc::default(),
+        })
+    }
+
+    /// Request a rebuild for a request with `method` and `authorization`
+    /// header, answering with an HTTP status and message.
+    fn request(&self, method: &Method, authorization: Option<&str>) -> (u16, &'static str) {
+        if *method != Method::Post {
+            return (405, "use POST");
+        }
+        let token = authorization.and_then(|value| value.strip_prefix("Bearer "));
+        if !token.is_some_and(|token| same(token.trim().as_bytes(), self.token.as_bytes())) {
+            return (401, "missing or wrong token");
+        }
+        self.requested.store(true, Ordering::Relaxed);
+        (202, "rebuild requested")
+    }
+}
+
+/// Whether `a` and `b` are equal, taking as long wherever they differ.
+fn same(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// Serve `dir` on `port` until the process is stopped.
+pub fn serve(dir: &Path, port: u16, tools: Tools) -> Result<()> {
+    let server = bind(port)?;
+
+    tracing::info!(url = %format!("http://localhost:{}", port), "serving site");
+    println!(
+        "\n  Serving at http://localhost:{}\n  Press Ctrl+C to stop\n",
+        port
+    );
+
+    for request in server.incoming_requests() {
+        respond(request, dir, &tools)?;
+    }
+
+    Ok(())
+}
+
+/// Listen on `port` on all interfaces.
+pub fn bind(port: u16) -> Result<Server> {
+    let addr = format!("0.0.0.0:{}", port);
+    Server::http(&addr).map_err(|e| Error::Serve {
+        port,
+        message: e.to_string(),
+    })
+}
+
+/// Answer `request` with the file it asks for from `dir`, or with one of
+/// the enabled `tools`.
+pub fn respond(request: Request, dir: &Path, tools: &Tools) -> Result<()> {
+    let url = request.url().to_string();
+    let (url_path, query) = url.split_once('?').unwrap_or((&url, ""));
+    let url_path = url_path.trim_start_matches('/');
+
+    if let Some(trigger) = &tools.rebuild
+        && url_path == REBUILD_PATH
+    {
+        let authorization = request
+            .headers()
+            .iter()
+            .find(|header| header.field.equiv("Authorization"))
+            .map(|header| header.value.as_str());
+        let (code, message) = trigger.request(request.method(), authorization);
+        let remote = request
+            .remote_addr()
+            .map(ToString::to_string)
+            .unwrap_or_default();
+        match code {
+            202 => tracing::info!(from = %remote, "rebuild requested over HTTP"),
+            _ => {
+                tracing::warn!(from = %remote, code, message, "rebuild request refused")
+            }
+        }
+        let response = Response::from_string(message)
+            .with_status_code(code)
+            .with_header(Header::from_bytes("Content-Type", "text/plain").unwrap())
+            .with_header(Header::from_bytes("Cache-Control", "no-store").unwrap());
+        request.respond(response)?;
+        return Ok(());
+    }
+
+    if tools.resize && url_path == RESIZE_PATH {
+        let response = match resize(dir, query) {
+            Ok(data) => Response::from_data(data)
+                .with_header(Header::from_bytes("Content-Type", "image/webp").unwrap())
+                .with_header(Header::from_bytes("Cache-Control", "no-store").unwrap()),
+            Err((code, message)) => {
+                tracing::debug!(query, code, message, "resize failed");
+                Response::from_string(message)
+                    .with_status_code(code)
+                    .with_header(Header::from_bytes("Content-Type", "text/plain").unwrap())
+            }
+        };
+        request.respond(response)?;
+        return Ok(());
+    }
+
+    // URL-decode the path (handles %20 for spaces, etc.)
+    let decoded_path = url_decode(url_path);
+
+    // Determine file path
+    // Only files and directories inside the served directory, whatever `..`
+    // the path holds
+    let requested = contained(dir, &dir.join(&decoded_path));
+    let file_path = requested.clone().map(|path| {
+        if path.is_dir() {
+            path.join("index.html")
+        } else {
+            path
+        }
+    });
+
+    if tools.listing
+        && let Some(requested) = requested.filter(|path| path.is_dir())
+        && !file_path.as_ref().is_some_and(|path| path.exists())
+    {
+        let response = Response::from_string(list_directory(&requested, url_path)?)
+            .with_header(Header::from_bytes("Content-Type", "text/html; charset=utf-8").unwrap());
+        request.respond(response)?;
+        tracing::debug!(path = %url_path, "200 OK (listing)");
+        return Ok(());
+    }
+
+    // Serve the file
+    if let Some(file_path) = file_path.filter(|path| path.is_file()) {
+        let content_type = guess_content_type(&file_path);
+        let accept_encoding = request
+            .headers()
+            .iter()
+            .find(|header| header.field.equiv("Accept-Encoding"))
+            .map(|header| header.value.as_str().to_string())
+            .unwrap_or_default();
+        let (content, encoding) = encode(&file_path, content_type, &accept_encoding)?;
+
+        let mut response = Response::from_data(content)
+            .with_header(Header::from_bytes("Content-Type", content_type).unwrap())
+            .with_header(Header::from_bytes("Vary", "Accept-Encoding").unwrap());
+        if let Some(encoding) = encoding {
+            response.add_header(Header::from_bytes("Content-Encoding", encoding).unwrap());
+        }
+
+        request.respond(response)?;
+        tracing::debug!(path = %url_path, encoding = encoding.unwrap_or("identity"), "200 OK");
+    } else {
+        let response = Response::from_string("404 Not Found")
+            .with_status_code(404)
+            .with_header(Header::from_bytes("Content-Type", "text/plain").unwrap());
+
+        request.respond(response)?;
+        tracing::debug!(path = %url_path, "404 Not Found");
+    }
+
+    Ok(())
+}
+
+/// WebP of the image `src` in `dir`, scaled down to `w` pixels wide, and
+/// further if it'd be taller than WebP allows, at quality `q`, for a resize
+/// request's `query`. Fails with an HTTP status and message.
+fn resize(dir: &Path, query: &str) -> std::result::Result<Vec<u8>, (u16, String)> {
+    let param = |name: &str| {
+        query
+            .split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| url_decode(value))
+    };
+    let bad_request = |message: &str| (400, message.to_string());
+
+    let src = param("src").ok_or_else(|| bad_request("missing src"))?;
+    let width = param("w")
+        .and_then(|w| w.parse::<u32>().ok())
+        .filter(|w| (1..=MAX_RESIZE_WIDTH).contains(w))
+        .ok_or_else(|| bad_request(&format!("w must be 1 to {}", MAX_RESIZE_WIDTH)))?;
+    let quality = match param("q") {
+        Some(q) => q
+            .parse::<u8>()
+            .ok()
+            .filter(|q| *q <= 100)
+            .ok_or_else(|| bad_request("q must be 0 to 100"))?,
+        None => RESIZE_QUALITY,
+    };
+
+    // Only images inside the served directory
+    let path = contained(dir, &dir.join(src.trim_start_matches('/')))
+        .filter(|path| path.is_file())
+        .ok_or_else(|| (404, format!("{} not found", src)))?;
+    let img = decode_upright(&path).map_err(|e| (422, format!("can't decode {}: {}", src, e)))?;
+
+    let img = if img.width() > width || img.height() > MAX_WEBP_HEIGHT {
+        img.resize(width, MAX_WEBP_HEIGHT, FilterType::Lanczos3)
+    } else {
+        img
+    };
+    let rgba = img.to_rgba8();
+    let encoder = webp::Encoder::from_rgba(rgba.as_raw(), rgba.width(), rgba.height());
+    Ok(encoder.encode(f32::from(quality)).to_vec())
+}
+
+/// `path` with `..` and links resolved, if it exists inside `dir`, so
+/// requests can't reach the site config or anything else on the host.
+fn contained(dir: &Path, path: &Path) -> Option<PathBuf> {
+    let root = dir.canonicalize().ok()?;
+    path.canonicalize()
+        .ok()
+        .filter(|path| path.starts_with(&root))
+}
+
+/// Decode the image at `path`, turned upright by its EXIF orientation.
+fn decode_upright(path: &Path) -> image::ImageResult<DynamicImage> {
+    let mut decoder = ImageReader::open(path)?
+        .with_guessed_format()?
+        .into_decoder()?;
+    let orientation = decoder.orientation()?;
+    let mut img = DynamicImage::from_decoder(decoder)?;
+    img.apply_orientation(orientation);
+    Ok(img)
+}
+
+/// HTML page listing the entries of `dir`, requested as `url_path`:
+/// directories first, then files with their sizes.
+fn list_directory(dir: &Path, url_path: &str) -> Result<String> {
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        entries.push((!metadata.is_dir(), name, metadata.len()));
+    }
+    entries.sort();
+
+    let base = match url_path.trim_matches('/') {
+        "" => "/".to_string(),
+        path => format!("/{}/", path),
+    };
+    let title = escape(url_decode(&base)).into_owned();
+    let mut rows = String::new();
+    if let Some((parent, _)) = base.trim_end_matches('/').rsplit_once('/') {
+        rows.push_str(&format!(
+            "<tr><td><a href=\"{}/\">../</a></td><td></td></tr>\n",
+            parent
+        ));
+    }
+    for (is_file, name, size) in &entries {
+        let (href, label, size) = if *is_file {
+            (
+                url_encode(name),
+                escape(name).into_owned(),
+                size.to_string(),
+            )
+        } else {
+            (
+                format!("{}/", url_encode(name)),
+                format!("{}/", escape(name)),
+                String::new(),
+            )
+        };
+        rows.push_str(&format!(
+            "<tr><td><a href=\"{}{}\">{}</a></td><td>{}</td></tr>\n",
+            base, href, label, size
+        ));
+    }
+
+    Ok(format!(
+        "<!doctype html>\n<html>\n<head><meta charset=\"utf-8\"><title>Index of {title}</title>\n\
+         <style>body{{font:14px monospace;margin:2em}}td{{padding:0 2em 0 0}}td+td{{text-align:right}}</style></head>\n\
+         <body>\n<h1>Index of {title}</h1>\n<table>\n{rows}</table>\n</body>\n</html>\n"
+    ))
+}
+
+/// The body to send for `path` and its content coding, given the request's
+/// `Accept-Encoding` header.
+fn encode(
+    path: &Path,
+    content_type: &str,
+    accept_encoding: &str,
+) -> Result<(Vec<u8>, Option<&'static str>)> {
+    for (coding, extension) in [("br", "br"), ("gzip", "gz")] {
+        let sibling = with_extension_suffix(path, extension);
+        if accepts(accept_encoding, coding) && sibling.is_file() {
+            return Ok((fs::read(sibling)?, Some(coding)));
+        }
+    }
+
+    let content = fs::read(path)?;
+    if accepts(accept_encoding, "gzip") && is_compressible(content_type) {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&content)?;
+        return Ok((encoder.finish()?, Some("gzip")));
+    }
+    Ok((content, None))
+}
+
+/// Whether an `Accept-Encoding` header value allows `coding`, explicitly or
+/// through `*`, with a non-zero quality.
+fn accepts(accept_encoding: &str, coding: &str) -> bool {
+    let mut wildcard = false;
+    for item in accept_encoding.split(',') {
+        let mut parts = item.split(';');
+        let name = parts.next().unwrap_or_default().trim();
+        let allowed = parts
+            .filter_map(|param| param.trim().strip_prefix("q="))
+            .all(|q| q.trim().parse::<f32>().is_ok_and(|q| q > 0.0));
+        if name.eq_ignore_ascii_case(coding) {
+            return allowed;
+        }
+        if name == "*" {
+            wildcard = allowed;
+        }
+    }
+    wildcard
+}
+
+/// Whether a response of `content_type` is text worth compressing.
+fn is_compressible(content_type: &str) -> bool {
+    content_type.starts_with("text/")
+        || content_type.starts_with("application/javascript")
+        || content_type.starts_with("application/json")
+        || content_type.starts_with("image/svg+xml")
+}
+
+/// `path` with `.{extension}` appended (`app.js` to `app.js.br`).
+fn with_extension_suffix(path: &Path, extension: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".");
+    name.push(extension);
+    PathBuf::from(name)
+}
+
+fn guess_content_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("html") => "text/html; charset=utf-8",
+        Some("css") => "text/css; charset=utf-8",
+        Some("js") => "application/javascript; charset=utf-8",
+        Some("json") => "application/json; charset=utf-8",
+        Some("png") => "image/png",
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("tif") | Some("tiff") => "image/tiff",
+        Some("svg") => "image/svg+xml",
+        Some("ico") => "image/x-icon",
+        Some("woff") => "font/woff",
+        Some("woff2") => "font/woff2",
+        Some("ttf") => "font/ttf",
+        Some("map") => "application/json",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Decode URL-encoded strings (e.g., %20 -> space).
+fn url_decode(s: &str) -> String {
+    let mut result = Vec::with_capacity(s.len());
+    let mut bytes = s.bytes();
+
+    while let Some(b) = bytes.next() {
+        if b == b'%' {
+            match (bytes.next(), bytes.next()) {
+                (Some(h1), Some(h2)) => {
+                    let hex = [h1, h2];
+                    match u8::from_str_radix(std::str::from_utf8(&hex).unwrap_or(""), 16) {
+                        Ok(byte) => result.push(byte),
+                        Err(_) => {
+                            result.push(b'%');
+                            result.extend_from_slice(&hex);
+                        }
+                    }
+                }
+                (Some(h1), None) => {
+                    result.push(b'%');
+                    result.push(h1);
+                }
+                _ => result.push(b'%'),
+            }
+        } else if b == b'+' {
+            result.push(b' ');
+        } else {
+            result.push(b);
+        }
+    }
+
+    String::from_utf8_lossy(&result).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+    use test_case::test_case;
+
+    #[test_case("gzip, deflate, br", "br", true ; "listed")]
+    #[test_case("gzip;q=1.0, br;q=0", "br", false ; "refused")]
+    #[test_case("deflate", "gzip", false ; "missing")]
+    #[test_case("*", "gzip", true ; "wildcard")]
+    #[test_case("*, gzip;q=0", "gzip", false ; "refused despite wildcard")]
+    #[test_case("GZIP", "gzip", true ; "case insensitive")]
+    #[test_case("", "gzip", false ; "empty")]
+    fn test_accepts(header: &str, coding: &str, expected: bool) {
+        assert_eq!(accepts(header, coding), expected);
+    }
+
+    fn trigger() -> Trigger {
+        Trigger {
+            token: "s3cret".to_string(),
+            requested: Arc::default(),
+        }
+    }
+
+    #[test_case(Method::Post, Some("Bearer s3cret"), 202 ; "authorized")]
+    #[test_case(Method::Post, Some("Bearer wrong!"), 401 ; "wrong token")]
+    #[test_case(Method::Post, Some("s3cret"), 401 ; "not bearer")]
+    #[test_case(Method::Post, None, 401 ; "missing")]
+    #[test_case(Method::Get, Some("Bearer s3cret"), 405 ; "not post")]
+    fn rebuild_requests(method: Method, authorization: Option<&str>, expected: u16) {
+        let trigger = trigger();
+
+        let (code, _) = trigger.request(&method, authorization);
+
+        assert_eq!(code, expected);
+        assert_eq!(trigger.requested.load(Ordering::Relaxed), expected == 202);
+    }
+
+    #[test]
+    fn resized_on_demand() {
+        let dir = tempfile::tempdir().unwrap();
+        let images = dir.path().join("images");
+        fs::create_dir(&images).unwrap();
+        image::RgbImage::from_pixel(400, 200, image::Rgb([200, 40, 40]))
+            .save(images.join("beach-abc-original.png"))
+            .unwrap();
+
+        let data = resize(
+            dir.path(),
+            "src=%2Fimages%2Fbeach-abc-original.png&w=100&q=70",
+        )
+        .unwrap();
+
+        let resized = image::load_from_memory(&data).unwrap();
+        assert_eq!((resized.width(), resized.height()), (100, 50));
+    }
+
+    #[test]
+    fn tall_images_fit_webp() {
+        let dir = tempfile::tempdir().unwrap();
+        image::GrayImage::new(20, 40_000)
+            .save(dir.path().join("panorama.png"))
+            .unwrap();
+
+        let data = resize(dir.path(), "src=panorama.png&w=100").unwrap();
+
+        let resized = image::load_from_memory(&data).unwrap();
+        assert_eq!(resized.height(), MAX_WEBP_HEIGHT);
+        assert!(resized.width() < 20);
+    }
+
+    #[test_case("w=100" ; "missing src")]
+    #[test_case("src=images/beach.png&w=0" ; "zero width")]
+    #[test_case("src=images/beach.png&w=100&q=101" ; "quality too high")]
+    fn bad_resize_requests(query: &str) {
+        let dir = tempfile::tempdir().unwrap();
+
+        assert_eq!(resize(dir.path(), query).unwrap_err().0, 400);
+    }
+
+    #[test]
+    fn resize_stays_inside_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("dist");
+        fs::create_dir(&output).unwrap();
+        image::RgbImage::new(10, 10)
+            .save(dir.path().join("secret.png"))
+            .unwrap();
+
+        assert_eq!(resize(&output, "src=../secret.png&w=5").unwrap_err().0, 404);
+    }
+
+    #[test_case("index.html", true ; "file")]
+    #[test_case("album/../index.html", true ; "parent inside")]
+    #[test_case("../site.toml", false ; "parent outside")]
+    #[test_case("../../../../etc/hostname", false ; "host file")]
+    #[test_case("missing.html", false ; "missing")]
+    fn requests_contained(path: &str, served: bool) {
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("dist");
+        fs::create_dir_all(output.join("album")).unwrap();
+        fs::write(output.join("index.html"), "").unwrap();
+        fs::write(dir.path().join("site.toml"), "rebuild_token = \"s3cret\"").unwrap();
+
+        assert_eq!(contained(&output, &output.join(path)).is_some(), served);
+    }
+
+    #[test]
+    fn directory_listed() {
+        let dir = tempfile::tempdir().unwrap();
+        let album = dir.path().join("images").join("Tokyo & Kyoto");
+        fs::create_dir_all(album.join("nested")).unwrap();
+        fs::write(album.join("photo-abc-thumb.webp"), "RIFF").unwrap();
+
+        let html = list_directory(&album, "images/Tokyo%20%26%20Kyoto/").unwrap();
+
+        assert!(html.contains("<title>Index of /images/Tokyo &amp; Kyoto/</title>"));
+        assert!(html.contains("<a href=\"/images/\">../</a>"));
+        let nested = html
+            .find("<a href=\"/images/Tokyo%20%26%20Kyoto/nested/\">nested/</a>")
+            .unwrap();
+        let file = html
+            .find("<a href=\"/images/Tokyo%20%26%20Kyoto/photo-abc-thumb.webp\">photo-abc-thumb.webp</a></td><td>4</td>")
+            .unwrap();
+        assert!(nested < file);
+    }
+
+    #[test]
+    fn precompressed_sibling_preferred() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.js");
+        fs::write(&path, "plain").unwrap();
+        fs::write(dir.path().join("app.js.br"), "brotli").unwrap();
+        fs::write(dir.path().join("app.js.gz"), "gzip").unwrap();
+        let js = guess_content_type(&path);
+
+        assert_eq!(
+            encode(&path, js, "gzip, br").unwrap(),
+            (b"brotli".to_vec(), Some("br"))
+        );
+        assert_eq!(
+            encode(&path, js, "gzip").unwrap(),
+            (b"gzip".to_vec(), Some("gzip"))
+        );
+        assert_eq!(encode(&path, js, "").unwrap(), (b"plain".to_vec(), None));
+    }
+
+    #[test]
+    fn text_gzipped_on_the_fly() {
+        let dir = tempfile::tempdir().unwrap();
+        let html = dir.path().join("index.html");
+        let image = dir.path().join("photo.webp");
+        fs::write(&html, "<p>hello</p>").unwrap();
+        fs::write(&image, "RIFF").unwrap();
+
+        let (body, encoding) = encode(&html, guess_content_type(&html), "gzip").unwrap();
+        let mut decoded = String::new();
+        GzDecoder::new(&body[..])
+            .read_to_string(&mut decoded)
+            .unwrap();
+
+        assert_eq!(encoding, Some("gzip"));
+        assert_eq!(decoded, "<p>hello</p>");
+        assert_eq!(
+            encode(&image, guess_content_type(&image), "gzip").unwrap(),
+            (b"RIFF".to_vec(), None)
+        );
+    }
+}
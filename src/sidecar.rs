@@ -0,0 +1,151 @@
+//! Per-photo settings from galerie sidecar files.
+//!
+//! A `photo.toml` (or `photo.jpg.toml`) next to an image overrides what
+//! galerie would otherwise read from the file, e.g. the location of a
+//! scanned print or a photo from a camera without GPS:
+//!
+//! ```toml
+//! location = { lat = 64.1417, lon = -21.9266 }
+//! ```
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+/// Settings read from a photo's sidecar.
+#[derive(Debug, Default, Deserialize, PartialEq)]
+pub struct Sidecar {
+    /// Replaces the location from EXIF/XMP GPS
+    pub location: Option<LocationOverride>,
+}
+
+/// A manually specified photo location.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(untagged)]
+pub enum LocationOverride {
+    /// Coordinates, reverse geocoded like GPS from the camera:
+    /// `{ lat = 64.14, lon = -21.93 }`
+    Coordinates { lat: f64, lon: f64 },
+    /// A named place without coordinates:
+    /// `{ city = "Reykjavík", region = "Capital Region", country = "IS" }`
+    Place {
+        city: String,
+        #[serde(default)]
+        region: Option<String>,
+        /// ISO 3166-1 alpha-2 code or country name
+        #[serde(default)]
+        country: Option<String>,
+    },
+    /// Just a place name: `"Reykjavík"`
+    Name(String),
+}
+
+impl LocationOverride {
+    /// Whether coordinates are within valid latitude/longitude ranges.
+    fn is_valid(&self) -> bool {
+        match self {
+            LocationOverride::Coordinates { lat, lon } => {
+                (-90.0..=90.0).contains(lat) && (-180.0..=180.0).contains(lon)
+            }
+            LocationOverride::Place { city, .. } | LocationOverride::Name(city) => {
+                !city.trim().is_empty()
+            }
+        }
+    }
+}
+
+/// Find the sidecar for an image: `photo.jpg.toml` or `photo.toml`.
+pub fn path(source: &Path) -> Option<PathBuf> {
+    let mut with_ext = source.as_os_str().to_owned();
+    with_ext.push(".toml");
+
+    [PathBuf::from(with_ext), source.with_extension("toml")]
+        .into_iter()
+        .find(|p| p.is_file())
+}
+
+/// Read the sidecar for an image, if one exists.
+///
+/// Invalid sidecars are reported and ignored so one typo doesn't fail the build.
+pub fn read(source: &Path) -> Option<Sidecar> {
+    let path = path(source)?;
+    let content = fs::read_to_string(&path)
+        .map_err(|e| tracing::warn!(path = %path.display(), error = %e, "failed to read sidecar"))
+        .ok()?;
+
+    let mut sidecar = parse(&content)
+        .map_err(|e| tracing::warn!(path = %path.display(), error = %e, "invalid sidecar"))
+        .ok()?;
+
+    if sidecar.location.as_ref().is_some_and(|l| !l.is_valid()) {
+        tracing::warn!(path = %path.display(), "ignoring invalid location in sidecar");
+        sidecar.location = None;
+    }
+
+    Some(sidecar)
+}
+
+fn parse(content: &str) -> Result<Sidecar, toml::de::Error> {
+    toml::from_str(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(
+        "location = { lat = 64.14, lon = -21.93 }",
+        LocationOverride::Coordinates { lat: 64.14, lon: -21.93 } ;
+        "coordinates"
+    )]
+    #[test_case(
+        r#"location = { city = "Vík", country = "IS" }"#,
+        LocationOverride::Place { city: "Vík".into(), region: None, country: Some("IS".into()) } ;
+        "place"
+    )]
+    #[test_case(
+        r#"location = "Reykjavík""#,
+        LocationOverride::Name("Reykjavík".into()) ;
+        "name"
+    )]
+    fn test_parse_location(content: &str, expected: LocationOverride) {
+        assert_eq!(parse(content).unwrap().location, Some(expected));
+    }
+
+    #[test_case(LocationOverride::Coordinates { lat: 64.14, lon: -21.93 }, true ; "valid coordinates")]
+    #[test_case(LocationOverride::Coordinates { lat: 95.0, lon: 0.0 }, false ; "latitude out of range")]
+    #[test_case(LocationOverride::Coordinates { lat: 0.0, lon: -181.0 }, false ; "longitude out of range")]
+    #[test_case(LocationOverride::Name("  ".into()), false ; "blank name")]
+    fn test_is_valid(location: LocationOverride, expected: bool) {
+        assert_eq!(location.is_valid(), expected);
+    }
+
+    #[test]
+    fn finds_sidecar_next_to_photo() {
+        let dir = tempfile::tempdir().unwrap();
+        let photo = dir.path().join("scan.jpg");
+        fs::write(dir.path().join("scan.toml"), "location = \"Akureyri\"").unwrap();
+
+        let sidecar = read(&photo).unwrap();
+
+        assert_eq!(
+            sidecar.location,
+            Some(LocationOverride::Name("Akureyri".into()))
+        );
+    }
+
+    #[test]
+    fn invalid_location_is_dropped() {
+        let dir = tempfile::tempdir().unwrap();
+        let photo = dir.path().join("scan.jpg");
+        fs::write(
+            dir.path().join("scan.jpg.toml"),
+            "location = { lat = 123.0, lon = 0.0 }",
+        )
+        .unwrap();
+
+        assert_eq!(read(&photo), Some(Sidecar::default()));
+    }
+}
@@ -0,0 +1,234 @@
+//! Output size changes between builds.
+//!
+//! After each build the size of every output file is kept in
+//! `.galerie-sizes.json` in the site directory, and compared with the
+//! previous build's: files added, removed, and resized, and the net change.
+//! A settings change that doubles the site, such as a higher quality or an
+//! extra image format, shows up right away instead of on the hosting bill.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use walkdir::WalkDir;
+
+use crate::error::{Error, Result};
+
+/// Sizes of the last build's output, relative to the site directory.
+pub const FILE_NAME: &str = ".galerie-sizes.json";
+
+/// Files listed with the largest changes.
+const LARGEST: usize = 5;
+
+/// Growth, as a ratio of the previous total, that's warned about.
+const GROWTH_WARNING: f64 = 1.5;
+
+/// Size of each output file, by path relative to the output directory.
+pub type Sizes = BTreeMap<String, u64>;
+
+/// How the output changed between two builds.
+#[derive(Debug, Default, PartialEq)]
+pub struct Diff {
+    pub added: Vec<(String, u64)>,
+    pub removed: Vec<(String, u64)>,
+    /// Files with their size before and after
+    pub resized: Vec<(String, u64, u64)>,
+    pub before: u64,
+    pub after: u64,
+}
+
+impl Diff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.resized.is_empty()
+    }
+
+    pub fn net(&self) -> i64 {
+        self.after as i64 - self.before as i64
+    }
+
+    /// Files whose size changed the most, either way.
+    pub fn largest(&self) -> Vec<(&str, i64)> {
+        let mut changes: Vec<(&str, i64)> = self
+            .added
+            .iter()
+            .map(|(name, size)| (name.as_str(), *size as i64))
+            .chain(
+                self.removed
+                    .iter()
+                    .map(|(name, size)| (name.as_str(), -(*size as i64))),
+            )
+            .chain(
+                self.resized
+                    .iter()
+                    .map(|(name, before, after)| (name.as_str(), *after as i64 - *before as i64)),
+            )
+            .collect();
+        changes.sort_by_key(|&(name, change)| (std::cmp::Reverse(change.unsigned_abs()), name));
+        changes.truncate(LARGEST);
+        changes
+    }
+}
+
+/// Compare this build's output with the last, log what changed, and keep
+/// the sizes for the next build.
+pub fn report(site_dir: &Path, output_dir: &Path) -> Result<()> {
+    let path = site_dir.join(FILE_NAME);
+    let previous: Option<Sizes> = fs::read_to_string(&path)
+        .ok()
+        .and_then(|json| serde_json::from_str(&json).ok());
+    let current = scan(output_dir);
+
+    match previous {
+        Some(previous) => log(&diff(&previous, &current)),
+        None => tracing::debug!(
+            files = current.len(),
+            "recorded output sizes to compare with the next build"
+        ),
+    }
+
+    let json = serde_json::to_string(&current)
+        .map_err(|e| Error::Other(format!("failed to serialize output sizes: {}", e)))?;
+    fs::write(&path, json)?;
+    Ok(())
+}
+
+fn log(diff: &Diff) {
+    if diff.is_empty() {
+        tracing::debug!("output sizes unchanged since the last build");
+        return;
+    }
+    tracing::info!(
+        added = diff.added.len(),
+        removed = diff.removed.len(),
+        resized = diff.resized.len(),
+        net = %delta(diff.net()),
+        total = %size(diff.after),
+        "output changed since the last build"
+    );
+    for (name, change) in diff.largest() {
+        tracing::info!(file = name, change = %delta(change), "size change");
+    }
+    if diff.before > 0 && diff.after as f64 >= diff.before as f64 * GROWTH_WARNING {
+        tracing::warn!(
+            before = %size(diff.before),
+            after = %size(diff.after),
+            "output grew by half or more since the last build; check for a settings change"
+        );
+    }
+}
+
+/// Size of every file under `output_dir`.
+fn scan(output_dir: &Path) -> Sizes {
+    WalkDir::new(output_dir)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(|entry| {
+            let name = entry
+                .path()
+                .strip_prefix(output_dir)
+                .ok()?
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            Some((name, entry.metadata().ok()?.len()))
+        })
+        .collect()
+}
+
+/// Files added, removed, and resized going from `before` to `after`.
+fn diff(before: &Sizes, after: &Sizes) -> Diff {
+    let mut diff = Diff {
+        before: before.values().sum(),
+        after: after.values().sum(),
+        ..Diff::default()
+    };
+    for (name, &size) in after {
+        match before.get(name) {
+            None => diff.added.push((name.clone(), size)),
+            Some(&old) if old != size => diff.resized.push((name.clone(), old, size)),
+            Some(_) => {}
+        }
+    }
+    for (name, &size) in before {
+        if !after.contains_key(name) {
+            diff.removed.push((name.clone(), size));
+        }
+    }
+    diff
+}
+
+/// Bytes in the largest unit that keeps them at least 1.
+pub fn size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1000 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1000.0;
+    let mut unit = 0;
+    while value >= 1000.0 && unit < UNITS.len() - 1 {
+        value /= 1000.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+/// A size change with its sign.
+fn delta(bytes: i64) -> String {
+    let sign = if bytes < 0 { "-" } else { "+" };
+    format!("{}{}", sign, size(bytes.unsigned_abs()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    fn sizes(files: &[(&str, u64)]) -> Sizes {
+        files
+            .iter()
+            .map(|(name, size)| (name.to_string(), *size))
+            .collect()
+    }
+
+    #[test]
+    fn added_removed_and_resized() {
+        let before = sizes(&[("index.html", 100), ("a.webp", 5000), ("old.webp", 300)]);
+        let after = sizes(&[("index.html", 120), ("a.webp", 5000), ("new.webp", 9000)]);
+
+        let diff = diff(&before, &after);
+
+        assert_eq!(diff.added, [("new.webp".to_string(), 9000)]);
+        assert_eq!(diff.removed, [("old.webp".to_string(), 300)]);
+        assert_eq!(diff.resized, [("index.html".to_string(), 100, 120)]);
+        assert_eq!(diff.net(), 8720);
+        assert_eq!(
+            diff.largest(),
+            [("new.webp", 9000), ("old.webp", -300), ("index.html", 20)]
+        );
+    }
+
+    #[test]
+    fn sizes_kept_between_builds() {
+        let site = tempfile::tempdir().unwrap();
+        let output = site.path().join("dist");
+        fs::create_dir_all(output.join("images")).unwrap();
+        fs::write(output.join("images/a.webp"), "abc").unwrap();
+
+        report(site.path(), &output).unwrap();
+
+        let kept: Sizes =
+            serde_json::from_str(&fs::read_to_string(site.path().join(FILE_NAME)).unwrap())
+                .unwrap();
+        assert_eq!(kept, sizes(&[("images/a.webp", 3)]));
+    }
+
+    #[test_case(999, "999 B")]
+    #[test_case(1_500, "1.5 KB")]
+    #[test_case(12_300_000, "12.3 MB")]
+    #[test_case(2_000_000_000, "2.0 GB")]
+    fn sizes_formatted(bytes: u64, expected: &str) {
+        assert_eq!(size(bytes), expected);
+    }
+}
@@ -0,0 +1,231 @@
+//! Golden-file snapshot testing for theme templates.
+//!
+//! Builds a site with the real pipeline and compares every rendered page
+//! against a stored snapshot. Theme or template context changes then show
+//! up as reviewable diffs instead of surprises in production builds.
+//!
+//! Snapshots mirror the output layout: `dist/album/photo.html` is compared
+//! against `{snapshot_dir}/album/photo.html`.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use walkdir::WalkDir;
+
+use crate::config::Site;
+use crate::error::Result;
+use crate::pipeline::Pipeline;
+
+/// Version string rendered into snapshots so they don't change with every release.
+pub const SNAPSHOT_VERSION: &str = "snapshot";
+
+/// Build time rendered into snapshots so they don't change with every build.
+const SNAPSHOT_BUILD_TIME: i64 = 0;
+
+/// A rendered page that doesn't match its snapshot.
+#[derive(Debug)]
+pub struct Mismatch {
+    /// Page path relative to the output directory
+    pub page: PathBuf,
+    /// 1-based line number of the first difference
+    pub line: usize,
+    /// Snapshot line (empty if the snapshot is shorter)
+    pub expected: String,
+    /// Rendered line (empty if the rendered page is shorter)
+    pub actual: String,
+}
+
+/// Outcome of comparing rendered pages against snapshots.
+#[derive(Debug, Default)]
+pub struct SnapshotReport {
+    /// Pages identical to their snapshot
+    pub matched: usize,
+    /// Snapshots written for pages that had none
+    pub created: Vec<PathBuf>,
+    /// Snapshots overwritten in update mode
+    pub updated: Vec<PathBuf>,
+    /// Pages that differ from their snapshot (empty in update mode)
+    pub mismatched: Vec<Mismatch>,
+    /// Snapshots with no corresponding rendered page
+    pub orphaned: Vec<PathBuf>,
+}
+
+impl SnapshotReport {
+    /// Whether every snapshot matched (new snapshots count as passing).
+    pub fn passed(&self) -> bool {
+        self.mismatched.is_empty() && self.orphaned.is_empty()
+    }
+}
+
+/// Build `site` and compare each rendered page against `snapshot_dir`.
+///
+/// Missing snapshots are always written. With `update`, differing snapshots
+/// are overwritten and orphaned ones are removed instead of being reported.
+pub fn check(
+    site_dir: &Path,
+    site: Site,
+    snapshot_dir: &Path,
+    update: bool,
+) -> Result<SnapshotReport> {
+    let mut pipeline = Pipeline::load(site_dir.to_path_buf(), site, false)?;
+    pipeline.version = SNAPSHOT_VERSION;
+    pipeline.fixed_build_time = Some(SNAPSHOT_BUILD_TIME);
+    pipeline.build()?;
+
+    let output_dir = pipeline.site_dir.join(&pipeline.config.build);
+    let pages = html_files(&output_dir, &["images", "static"]);
+    let mut report = SnapshotReport::default();
+
+    for page in &pages {
+        let actual = fs::read_to_string(output_dir.join(page))?;
+        let snapshot_path = snapshot_dir.join(page);
+
+        if !snapshot_path.exists() {
+            write_snapshot(&snapshot_path, &actual)?;
+            report.created.push(page.clone());
+            continue;
+        }
+
+        let expected = fs::read_to_string(&snapshot_path)?;
+        match first_difference(&expected, &actual) {
+            None => report.matched += 1,
+            Some(_) if update => {
+                write_snapshot(&snapshot_path, &actual)?;
+                report.updated.push(page.clone());
+            }
+            Some((line, expected, actual)) => report.mismatched.push(Mismatch {
+                page: page.clone(),
+                line,
+                expected: expected.to_string(),
+                actual: actual.to_string(),
+            }),
+        }
+    }
+
+    for snapshot in html_files(snapshot_dir, &[]) {
+        if pages.contains(&snapshot) {
+            continue;
+        }
+        if update {
+            fs::remove_file(snapshot_dir.join(&snapshot))?;
+            tracing::debug!(snapshot = %snapshot.display(), "removed orphaned snapshot");
+        } else {
+            report.orphaned.push(snapshot);
+        }
+    }
+
+    tracing::debug!(
+        matched = report.matched,
+        created = report.created.len(),
+        updated = report.updated.len(),
+        mismatched = report.mismatched.len(),
+        orphaned = report.orphaned.len(),
+        "compared snapshots"
+    );
+
+    Ok(report)
+}
+
+/// Collect `.html` files under `dir` as sorted relative paths.
+///
+/// Top-level directories named in `skip` are not descended into.
+fn html_files(dir: &Path, skip: &[&str]) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = WalkDir::new(dir)
+        .into_iter()
+        .filter_entry(|e| {
+            e.depth() != 1 || !e.file_name().to_str().is_some_and(|n| skip.contains(&n))
+        })
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .filter(|e| e.path().extension().is_some_and(|ext| ext == "html"))
+        .filter_map(|e| e.path().strip_prefix(dir).ok().map(Path::to_path_buf))
+        .collect();
+    files.sort();
+    files
+}
+
+fn write_snapshot(path: &Path, contents: &str) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, contents)?;
+    Ok(())
+}
+
+/// Find the first differing line, returning (1-based line, expected, actual).
+fn first_difference<'a>(expected: &'a str, actual: &'a str) -> Option<(usize, &'a str, &'a str)> {
+    if expected == actual {
+        return None;
+    }
+
+    let mut expected_lines = expected.lines();
+    let mut actual_lines = actual.lines();
+    let mut line = 1;
+
+    loop {
+        match (expected_lines.next(), actual_lines.next()) {
+            (Some(e), Some(a)) if e == a => line += 1,
+            (None, None) => {
+                // Only trailing newlines differ
+                return Some((line, "", ""));
+            }
+            (e, a) => return Some((line, e.unwrap_or(""), a.unwrap_or(""))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case("a\nb\nc", "a\nb\nc", None ; "identical")]
+    #[test_case("a\nb\nc", "a\nx\nc", Some((2, "b", "x")) ; "changed line")]
+    #[test_case("a\nb", "a\nb\nc", Some((3, "", "c")) ; "added line")]
+    #[test_case("a\nb\nc", "a\nb", Some((3, "c", "")) ; "removed line")]
+    #[test_case("a\n", "a", Some((2, "", "")) ; "trailing newline")]
+    fn test_first_difference(
+        expected: &str,
+        actual: &str,
+        difference: Option<(usize, &str, &str)>,
+    ) {
+        assert_eq!(first_difference(expected, actual), difference);
+    }
+
+    #[test]
+    fn snapshots_round_trip() {
+        let site_dir = tempfile::tempdir().unwrap();
+        let snapshot_dir = tempfile::tempdir().unwrap();
+        let load = || {
+            let mut site = crate::preview::generate(site_dir.path(), "basic").unwrap();
+            site.minify = false;
+            site
+        };
+
+        // First run records every page
+        let report = check(site_dir.path(), load(), snapshot_dir.path(), false).unwrap();
+        assert!(report.passed());
+        assert!(!report.created.is_empty());
+        assert_eq!(report.matched, 0);
+
+        // Second run matches
+        let report = check(site_dir.path(), load(), snapshot_dir.path(), false).unwrap();
+        assert!(report.passed());
+        assert!(report.created.is_empty());
+
+        // Tampered and orphaned snapshots are reported
+        fs::write(snapshot_dir.path().join("index.html"), "stale").unwrap();
+        fs::write(snapshot_dir.path().join("gone.html"), "orphan").unwrap();
+        let report = check(site_dir.path(), load(), snapshot_dir.path(), false).unwrap();
+        assert!(!report.passed());
+        assert_eq!(report.mismatched.len(), 1);
+        assert_eq!(report.mismatched[0].page, PathBuf::from("index.html"));
+        assert_eq!(report.orphaned, vec![PathBuf::from("gone.html")]);
+
+        // Update mode repairs both
+        let report = check(site_dir.path(), load(), snapshot_dir.path(), true).unwrap();
+        assert!(report.passed());
+        assert_eq!(report.updated, vec![PathBuf::from("index.html")]);
+        assert!(!snapshot_dir.path().join("gone.html").exists());
+    }
+}
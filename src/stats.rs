@@ -0,0 +1,177 @@
+//! Summaries of the photos in an album.
+//!
+//! Themes use these for lines like "June 2024 · 182 photos · 🇯🇵" without
+//! looping over every photo in a template. Stats cover nested albums and
+//! only published metadata, so a site that hides dates or GPS doesn't leak
+//! them through its album summaries.
+
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::photos::Album;
+use crate::util::iso_datetime;
+
+/// Stats of an album and its nested albums.
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct AlbumStats {
+    /// Number of photos
+    pub photo_count: usize,
+    /// Earliest capture date (e.g., "2024-06-02")
+    pub first_date: Option<String>,
+    /// Latest capture date
+    pub last_date: Option<String>,
+    /// Camera models, most used first
+    pub cameras: Vec<String>,
+    /// Total size of the source files in bytes
+    pub total_size: u64,
+    /// Countries photos were taken in according to GPS, most photos first
+    pub countries: Vec<Country>,
+}
+
+/// A country photos in an album were taken in.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct Country {
+    /// ISO 3166-1 alpha-2 code (e.g., "JP")
+    pub code: String,
+    /// Display name
+    pub name: Option<String>,
+    /// Flag emoji
+    pub flag: Option<String>,
+    /// Photos taken there
+    pub photo_count: usize,
+}
+
+/// Stats of `album`, including nested albums.
+pub fn compute(album: &Album) -> AlbumStats {
+    let photos = album.all_photos();
+
+    let dates: Vec<String> = photos
+        .iter()
+        .filter_map(|photo| photo.metadata.date_taken.as_deref())
+        .map(|date| {
+            let date = iso_datetime(date);
+            date.split('T').next().unwrap_or(&date).to_string()
+        })
+        .collect();
+
+    let mut cameras: BTreeMap<&str, usize> = BTreeMap::new();
+    let mut countries: BTreeMap<&str, Country> = BTreeMap::new();
+    for photo in &photos {
+        if let Some(camera) = &photo.metadata.camera {
+            *cameras.entry(camera).or_default() += 1;
+        }
+        if let Some(gps) = &photo.metadata.gps
+            && let Some(code) = &gps.country_code
+        {
+            countries
+                .entry(code)
+                .or_insert_with(|| Country {
+                    code: code.clone(),
+                    name: gps.country.clone(),
+                    flag: gps.flag.clone(),
+                    photo_count: 0,
+                })
+                .photo_count += 1;
+        }
+    }
+
+    // Most used first; ties stay in name or code order
+    let mut cameras: Vec<_> = cameras.into_iter().collect();
+    cameras.sort_by_key(|&(_, count)| Reverse(count));
+    let mut countries: Vec<_> = countries.into_values().collect();
+    countries.sort_by_key(|country| Reverse(country.photo_count));
+
+    AlbumStats {
+        photo_count: photos.len(),
+        first_date: dates.iter().min().cloned(),
+        last_date: dates.iter().max().cloned(),
+        cameras: cameras
+            .into_iter()
+            .map(|(camera, _)| camera.to_string())
+            .collect(),
+        total_size: photos.iter().map(|photo| photo.original_size).sum(),
+        countries,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::photos::{GpsCoords, Photo, PhotoMetadata};
+    use std::path::PathBuf;
+
+    fn photo(date: Option<&str>, camera: Option<&str>, country: Option<&str>) -> Photo {
+        Photo::processed(
+            "photo.jpg",
+            PhotoMetadata {
+                date_taken: date.map(str::to_string),
+                camera: camera.map(str::to_string),
+                gps: country.map(|code| GpsCoords {
+                    country_code: Some(code.to_string()),
+                    flag: Some(format!("flag-{}", code)),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+        )
+    }
+
+    fn album(photos: Vec<Photo>, children: Vec<Album>) -> Album {
+        Album {
+            name: "Japan".to_string(),
+            slug: "japan".to_string(),
+            path: PathBuf::from("japan"),
+            dir: PathBuf::from("japan"),
+            aliases: Vec::new(),
+            photos,
+            children,
+            cover: None,
+            stats: AlbumStats::default(),
+        }
+    }
+
+    #[test]
+    fn stats_include_nested_albums() {
+        let kyoto = album(
+            vec![
+                photo(
+                    Some("2024:06:20 09:00:00"),
+                    Some("Canon EOS R5"),
+                    Some("JP"),
+                ),
+                photo(Some("2024:06:21 18:30:00"), Some("iPhone 15"), Some("JP")),
+            ],
+            Vec::new(),
+        );
+        let japan = album(
+            vec![
+                photo(Some("2024:06:02 07:15:00"), Some("iPhone 15"), Some("KR")),
+                photo(None, Some("iPhone 15"), Some("JP")),
+                photo(None, None, None),
+            ],
+            vec![kyoto],
+        );
+
+        let stats = compute(&japan);
+
+        assert_eq!(stats.photo_count, 5);
+        assert_eq!(stats.first_date.as_deref(), Some("2024-06-02"));
+        assert_eq!(stats.last_date.as_deref(), Some("2024-06-21"));
+        assert_eq!(stats.cameras, ["iPhone 15", "Canon EOS R5"]);
+        assert_eq!(stats.total_size, 5 * 1024);
+        let codes: Vec<_> = stats.countries.iter().map(|c| c.code.as_str()).collect();
+        assert_eq!(codes, ["JP", "KR"]);
+        assert_eq!(stats.countries[0].photo_count, 3);
+        assert_eq!(stats.countries[0].flag.as_deref(), Some("flag-JP"));
+    }
+
+    #[test]
+    fn empty_album() {
+        assert_eq!(
+            compute(&album(Vec::new(), Vec::new())),
+            AlbumStats::default()
+        );
+    }
+}
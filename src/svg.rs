@@ -0,0 +1,199 @@
+//! SVG sanitization and minification for static assets.
+//!
+//! An SVG is a document, not just an image: opened directly or embedded with
+//! `<object>`, its scripts run with the site's origin. Theme SVGs, including
+//! logos dropped in by site owners, are sanitized on every build, removing
+//! `<script>` and `<foreignObject>` elements, event handler attributes, and
+//! `javascript:` URLs. With minification on, comments, editor metadata, and
+//! whitespace between tags are dropped as well.
+
+use quick_xml::Writer;
+use quick_xml::events::attributes::Attribute;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::reader::Reader;
+
+use crate::error::{Error, Result};
+
+/// Elements removed along with everything inside them.
+const UNSAFE_ELEMENTS: &[&[u8]] = &[b"script", b"foreignObject"];
+
+/// Namespace prefixes of editor-only elements and attributes.
+const EDITOR_PREFIXES: &[&[u8]] = &[b"inkscape", b"sodipodi", b"sketch", b"serif"];
+
+/// Elements whose whitespace is rendered.
+const TEXT_ELEMENTS: &[&[u8]] = &[b"text", b"tspan", b"textPath", b"style"];
+
+/// Sanitize an SVG, and minify it if `minify` is set.
+pub fn clean(input: &str, minify: bool) -> Result<String> {
+    let mut reader = Reader::from_str(input);
+    let mut writer = Writer::new(Vec::with_capacity(input.len()));
+    let parse_error = |e: quick_xml::Error| Error::Other(format!("SVG parse error: {}", e));
+
+    // Depth inside a removed element (0 when not removing)
+    let mut skipping = 0usize;
+    // Depth inside elements whose text is rendered
+    let mut in_text = 0usize;
+
+    loop {
+        let event = reader.read_event().map_err(parse_error)?;
+        if skipping > 0 {
+            match event {
+                Event::Start(_) => skipping += 1,
+                Event::End(_) => skipping -= 1,
+                Event::Eof => break,
+                _ => {}
+            }
+            continue;
+        }
+
+        let output = match event {
+            Event::Eof => break,
+            Event::Start(e) if removed(&e, minify) => {
+                skipping = 1;
+                continue;
+            }
+            Event::Empty(e) if removed(&e, minify) => continue,
+            Event::Start(e) => {
+                if TEXT_ELEMENTS.contains(&e.local_name().as_ref()) {
+                    in_text += 1;
+                }
+                Event::Start(sanitized(&e, minify))
+            }
+            Event::Empty(e) => Event::Empty(sanitized(&e, minify)),
+            Event::End(e) => {
+                if TEXT_ELEMENTS.contains(&e.local_name().as_ref()) {
+                    in_text = in_text.saturating_sub(1);
+                }
+                Event::End(e)
+            }
+            Event::Text(e) if minify && in_text == 0 && e.iter().all(u8::is_ascii_whitespace) => {
+                continue;
+            }
+            Event::Comment(_) | Event::Decl(_) | Event::PI(_) if minify => continue,
+            event => event,
+        };
+        writer
+            .write_event(output)
+            .map_err(|e| Error::Other(format!("SVG write error: {}", e)))?;
+    }
+
+    String::from_utf8(writer.into_inner()).map_err(|e| Error::Other(e.to_string()))
+}
+
+/// Whether an element is dropped: unsafe ones always, editor metadata when minifying.
+fn removed(element: &BytesStart, minify: bool) -> bool {
+    let name = element.name();
+    if UNSAFE_ELEMENTS.contains(&name.local_name().as_ref()) {
+        return true;
+    }
+    minify
+        && (name.local_name().as_ref() == b"metadata"
+            || name
+                .prefix()
+                .is_some_and(|prefix| EDITOR_PREFIXES.contains(&prefix.as_ref())))
+}
+
+/// Copy of an element without event handlers, `javascript:` URLs, or
+/// (when minifying) editor attributes.
+fn sanitized(element: &BytesStart, minify: bool) -> BytesStart<'static> {
+    let mut clean = BytesStart::new(String::from_utf8_lossy(element.name().as_ref()).into_owned());
+    let mut attributes = element.attributes();
+    attributes.with_checks(false);
+    clean.extend_attributes(
+        attributes
+            .filter_map(|attr| attr.ok())
+            .filter(|attr| !unsafe_attribute(attr))
+            .filter(|attr| !minify || !editor_attribute(attr)),
+    );
+    clean
+}
+
+/// Editor attributes like `inkscape:label` and the namespace declarations for them.
+fn editor_attribute(attr: &Attribute) -> bool {
+    attr.key.prefix().is_some_and(|prefix| {
+        EDITOR_PREFIXES.contains(&prefix.as_ref())
+            || (prefix.as_ref() == b"xmlns"
+                && EDITOR_PREFIXES.contains(&attr.key.local_name().as_ref()))
+    })
+}
+
+/// Event handlers (`onload`, `onclick`, ...) and values that run script.
+fn unsafe_attribute(attr: &Attribute) -> bool {
+    if attr
+        .key
+        .local_name()
+        .as_ref()
+        .to_ascii_lowercase()
+        .starts_with(b"on")
+    {
+        return true;
+    }
+    let Ok(value) = attr.unescape_value() else {
+        return true;
+    };
+    // Browsers ignore whitespace and control characters inside the scheme
+    let value: String = value
+        .chars()
+        .filter(|c| !c.is_ascii_whitespace() && !c.is_ascii_control())
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    value.starts_with("javascript:")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(
+        r#"<svg><script>alert(1)</script><circle r="1"/></svg>"#,
+        r#"<svg><circle r="1"/></svg>"#
+        ; "script element"
+    )]
+    #[test_case(
+        r#"<svg><foreignObject><div><b>x</b></div></foreignObject></svg>"#,
+        r#"<svg></svg>"#
+        ; "foreign object"
+    )]
+    #[test_case(
+        r#"<svg onload="alert(1)" width="10"><rect ONCLICK="x()"/></svg>"#,
+        r#"<svg width="10"><rect/></svg>"#
+        ; "event handlers"
+    )]
+    #[test_case(
+        r#"<svg><a xlink:href=" java&#9;script:alert(1)" href="JavaScript:x"><text>hi</text></a></svg>"#,
+        r#"<svg><a><text>hi</text></a></svg>"#
+        ; "javascript urls"
+    )]
+    #[test_case(
+        "<!-- kept -->\n<svg>\n  <circle r=\"1\"/>\n</svg>",
+        "<!-- kept -->\n<svg>\n  <circle r=\"1\"/>\n</svg>"
+        ; "formatting kept without minify"
+    )]
+    fn test_sanitize(input: &str, expected: &str) {
+        assert_eq!(clean(input, false).unwrap(), expected);
+    }
+
+    #[test]
+    fn minify_drops_comments_metadata_and_whitespace() {
+        let input = r##"<?xml version="1.0" encoding="UTF-8"?>
+<!-- Created with Inkscape -->
+<svg xmlns="http://www.w3.org/2000/svg" xmlns:inkscape="http://www.inkscape.org/namespaces/inkscape" inkscape:version="1.3">
+  <metadata><rdf:RDF/></metadata>
+  <sodipodi:namedview pagecolor="#fff"/>
+  <text x="0"> Hello <tspan>world</tspan></text>
+  <circle r="1" inkscape:label="dot"/>
+</svg>
+"##;
+
+        assert_eq!(
+            clean(input, true).unwrap(),
+            r#"<svg xmlns="http://www.w3.org/2000/svg"><text x="0"> Hello <tspan>world</tspan></text><circle r="1"/></svg>"#
+        );
+    }
+
+    #[test]
+    fn invalid_svg_is_an_error() {
+        assert!(clean("<svg><g></svg>", true).is_err());
+    }
+}
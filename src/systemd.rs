@@ -0,0 +1,223 @@
+//! systemd service notifications.
+//!
+//! When systemd starts `galerie watch` or `galerie serve --daemon` as a
+//! `Type=notify` service, it passes a socket in `$NOTIFY_SOCKET`. galerie
+//! reports readiness there once the initial build has finished, a status
+//! line after every build, and when it's stopping. With `WatchdogSec` set,
+//! systemd also passes `$WATCHDOG_USEC`, and galerie pings at half that
+//! interval from the loop waiting for changes, and from another thread
+//! while a rebuild runs, so a hung process gets restarted but a long build
+//! doesn't. Outside systemd nothing is sent.
+
+#[cfg(unix)]
+use std::os::unix::net::UnixDatagram;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Connection to systemd's notification socket, opened on first use.
+static NOTIFIER: OnceLock<Option<Notifier>> = OnceLock::new();
+
+struct Notifier {
+    #[cfg(unix)]
+    socket: UnixDatagram,
+    /// Time between watchdog pings, if systemd expects them
+    watchdog: Option<Duration>,
+    last_ping: Mutex<Option<Instant>>,
+}
+
+impl Notifier {
+    fn from_env() -> Option<Self> {
+        let path = std::env::var("NOTIFY_SOCKET").ok()?;
+        let watchdog = watchdog_interval(
+            std::env::var("WATCHDOG_USEC").ok().as_deref(),
+            std::env::var("WATCHDOG_PID").ok().as_deref(),
+            std::process::id(),
+        );
+        match Self::connect(&path, watchdog) {
+            Ok(notifier) => Some(notifier),
+            Err(e) => {
+                tracing::warn!(socket = %path, error = %e, "failed to connect to systemd");
+                None
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    fn connect(path: &str, watchdog: Option<Duration>) -> std::io::Result<Self> {
+        let socket = UnixDatagram::unbound()?;
+        match path.strip_prefix('@') {
+            // Abstract socket names start with '@' in the environment
+            #[cfg(target_os = "linux")]
+            Some(name) => {
+                use std::os::linux::net::SocketAddrExt;
+                let addr = std::os::unix::net::SocketAddr::from_abstract_name(name)?;
+                socket.connect_addr(&addr)?;
+            }
+            _ => socket.connect(path)?,
+        }
+        Ok(Self {
+            socket,
+            watchdog,
+            last_ping: Mutex::new(None),
+        })
+    }
+
+    #[cfg(not(unix))]
+    fn connect(_path: &str, _watchdog: Option<Duration>) -> std::io::Result<Self> {
+        Err(std::io::Error::other("notifications need Unix sockets"))
+    }
+
+    fn send(&self, message: &str) {
+        #[cfg(unix)]
+        if let Err(e) = self.socket.send(message.as_bytes()) {
+            tracing::debug!(error = %e, "failed to notify systemd");
+        }
+        #[cfg(not(unix))]
+        let _ = message;
+    }
+
+    fn ping(&self) {
+        let Some(interval) = self.watchdog else {
+            return;
+        };
+        let mut last = self.last_ping.lock().unwrap();
+        if last.is_none_or(|at| at.elapsed() >= interval) {
+            self.send("WATCHDOG=1");
+            *last = Some(Instant::now());
+        }
+    }
+
+    /// Run `f`, pinging the watchdog from another thread until it returns.
+    fn keep_alive<T>(&self, f: impl FnOnce() -> T) -> T {
+        let Some(interval) = self.watchdog else {
+            return f();
+        };
+        let (done, finished) = mpsc::channel::<()>();
+        std::thread::scope(|scope| {
+            scope.spawn(move || {
+                // Waking twice per interval keeps pings from slipping a whole one
+                while let Err(RecvTimeoutError::Timeout) = finished.recv_timeout(interval / 2) {
+                    self.ping();
+                }
+            });
+            let result = f();
+            drop(done);
+            result
+        })
+    }
+}
+
+fn notifier() -> Option<&'static Notifier> {
+    NOTIFIER.get_or_init(Notifier::from_env).as_ref()
+}
+
+/// Tell systemd startup has finished, with a status line.
+pub fn ready(status: &str) {
+    if let Some(notifier) = notifier() {
+        notifier.send(&format!("READY=1\nSTATUS={}", status));
+    }
+}
+
+/// Update the status line shown by `systemctl status`.
+pub fn status(status: &str) {
+    if let Some(notifier) = notifier() {
+        notifier.send(&format!("STATUS={}", status));
+    }
+}
+
+/// Tell systemd the service is shutting down.
+pub fn stopping() {
+    if let Some(notifier) = notifier() {
+        notifier.send("STOPPING=1");
+    }
+}
+
+/// Ping the watchdog if it's due. Cheap enough to call on every loop iteration.
+pub fn watchdog() {
+    if let Some(notifier) = notifier() {
+        notifier.ping();
+    }
+}
+
+/// Run `f`, such as a build, while keeping the watchdog pinged.
+pub fn keep_alive<T>(f: impl FnOnce() -> T) -> T {
+    match notifier() {
+        Some(notifier) => notifier.keep_alive(f),
+        None => f(),
+    }
+}
+
+/// Half the watchdog timeout in `usec`, unless it's meant for another
+/// process than `pid`.
+fn watchdog_interval(usec: Option<&str>, watchdog_pid: Option<&str>, pid: u32) -> Option<Duration> {
+    if let Some(watchdog_pid) = watchdog_pid
+        && watchdog_pid.trim().parse::<u32>().ok() != Some(pid)
+    {
+        return None;
+    }
+    let usec: u64 = usec?.trim().parse().ok().filter(|&usec| usec > 0)?;
+    Some(Duration::from_micros(usec / 2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(Some("30000000"), None, Some(15_000) ; "timeout halved")]
+    #[test_case(Some("30000000"), Some("42"), Some(15_000) ; "for this process")]
+    #[test_case(Some("30000000"), Some("7"), None ; "for another process")]
+    #[test_case(Some("0"), None, None ; "disabled")]
+    #[test_case(None, None, None ; "unset")]
+    fn test_watchdog_interval(usec: Option<&str>, pid: Option<&str>, expected_ms: Option<u64>) {
+        assert_eq!(
+            watchdog_interval(usec, pid, 42),
+            expected_ms.map(Duration::from_millis)
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn watchdog_pings_rate_limited() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notify.sock");
+        let systemd = UnixDatagram::bind(&path).unwrap();
+        systemd.set_nonblocking(true).unwrap();
+        let notifier =
+            Notifier::connect(path.to_str().unwrap(), Some(Duration::from_secs(60))).unwrap();
+
+        notifier.send("READY=1");
+        notifier.ping();
+        notifier.ping();
+
+        let mut buf = [0; 64];
+        let n = systemd.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"READY=1");
+        let n = systemd.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"WATCHDOG=1");
+        assert!(systemd.recv(&mut buf).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn watchdog_pinged_during_slow_build() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notify.sock");
+        let systemd = UnixDatagram::bind(&path).unwrap();
+        let notifier =
+            Notifier::connect(path.to_str().unwrap(), Some(Duration::from_millis(20))).unwrap();
+
+        let pinged = notifier.keep_alive(|| {
+            // The build outlasts several intervals, and watches for pings as it goes
+            systemd
+                .set_read_timeout(Some(Duration::from_secs(5)))
+                .unwrap();
+            let mut buf = [0; 64];
+            let n = systemd.recv(&mut buf).unwrap();
+            buf[..n].to_vec()
+        });
+
+        assert_eq!(pinged, b"WATCHDOG=1");
+    }
+}
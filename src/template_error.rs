@@ -0,0 +1,234 @@
+//! Readable template rendering errors.
+//!
+//! Tera reports render failures as a chain of messages without source
+//! locations. This module flattens the chain and locates the offending
+//! expression in the template sources so the error points at a line.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Lines of context shown before and after the offending line.
+const CONTEXT_LINES: usize = 2;
+
+/// A template that failed to render, with the page it was rendering.
+#[derive(Debug)]
+pub struct RenderError {
+    /// Template being rendered
+    pub template: String,
+    /// Page being rendered (e.g. `photo 'trips/beach'`)
+    pub page: String,
+    /// Flattened Tera error message
+    pub message: String,
+    /// Source location of the failing expression, if found
+    pub snippet: Option<Snippet>,
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "failed to render {} with {}: {}",
+            self.page, self.template, self.message
+        )?;
+        if let Some(snippet) = &self.snippet {
+            write!(f, "\n{}", snippet)?;
+        }
+        Ok(())
+    }
+}
+
+/// Location of a failing expression within a template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snippet {
+    /// Template containing the expression (may differ from the rendered one)
+    pub template: String,
+    /// 1-based line number of the expression
+    pub line: usize,
+    /// Numbered source lines surrounding the expression
+    pub lines: Vec<(usize, String)>,
+}
+
+impl fmt::Display for Snippet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self.lines.last().map_or(1, |(n, _)| n.to_string().len());
+        write!(f, "  --> {}:{}", self.template, self.line)?;
+        for (number, text) in &self.lines {
+            let marker = if *number == self.line { '>' } else { ' ' };
+            write!(
+                f,
+                "\n {} {:>width$} | {}",
+                marker,
+                number,
+                text,
+                width = width
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Flatten a Tera error chain into a single message.
+///
+/// The generic "Failed to render" wrapper is dropped since callers report
+/// the template name themselves.
+pub fn message(err: &tera::Error) -> String {
+    chain(err)
+        .into_iter()
+        .filter(|m| !m.starts_with("Failed to render"))
+        .collect::<Vec<_>>()
+        .join(": ")
+}
+
+/// Locate the expression that caused `err` while rendering `template`.
+///
+/// Tera doesn't track runtime source positions, so this looks for the
+/// identifier named in the error message (variable, function, or filter)
+/// in `template` first, then in the other templates it may extend or include.
+pub fn locate(
+    err: &tera::Error,
+    template: &str,
+    sources: &BTreeMap<String, String>,
+) -> Option<Snippet> {
+    let candidates: Vec<String> = chain(err)
+        .iter()
+        .flat_map(|m| quoted(m))
+        .filter(|token| !sources.contains_key(token))
+        .collect();
+
+    let search_order = sources
+        .get_key_value(template)
+        .into_iter()
+        .chain(sources.iter().filter(|(name, _)| *name != template));
+
+    for (name, source) in search_order {
+        for token in &candidates {
+            if let Some(index) = source.lines().position(|l| l.contains(token.as_str())) {
+                return Some(snippet(name, source, index));
+            }
+        }
+    }
+
+    None
+}
+
+/// Collect the messages of an error and all of its sources.
+fn chain(err: &tera::Error) -> Vec<String> {
+    let mut messages = vec![err.to_string()];
+    let mut source = std::error::Error::source(err);
+    while let Some(e) = source {
+        messages.push(e.to_string());
+        source = e.source();
+    }
+    messages
+}
+
+/// Extract identifiers quoted with backticks or single quotes.
+fn quoted(message: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    for delimiter in ['`', '\''] {
+        let mut parts = message.split(delimiter);
+        parts.next();
+        while let (Some(token), Some(_)) = (parts.next(), parts.next()) {
+            if !token.is_empty() && !token.contains(char::is_whitespace) {
+                tokens.push(token.to_string());
+            }
+        }
+    }
+    tokens
+}
+
+fn snippet(template: &str, source: &str, index: usize) -> Snippet {
+    let start = index.saturating_sub(CONTEXT_LINES);
+    let lines = source
+        .lines()
+        .enumerate()
+        .skip(start)
+        .take(index - start + CONTEXT_LINES + 1)
+        .map(|(i, text)| (i + 1, text.to_string()))
+        .collect();
+
+    Snippet {
+        template: template.to_string(),
+        line: index + 1,
+        lines,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    fn render_error(
+        templates: &[(&str, &str)],
+        name: &str,
+    ) -> (tera::Error, BTreeMap<String, String>) {
+        let mut tera = tera::Tera::default();
+        tera.add_raw_templates(templates.to_vec()).unwrap();
+        let sources = templates
+            .iter()
+            .map(|(n, s)| (n.to_string(), s.to_string()))
+            .collect();
+        let err = tera.render(name, &tera::Context::new()).unwrap_err();
+        (err, sources)
+    }
+
+    #[test_case("Variable `photo.title` not found in context while rendering 'photo.html'", &["photo.title", "photo.html"] ; "variable")]
+    #[test_case("Function call 'social' failed", &["social"] ; "function")]
+    #[test_case("no quotes here", &[] ; "none")]
+    #[test_case("`a b` and `c`", &["c"] ; "skips whitespace")]
+    fn test_quoted(message: &str, expected: &[&str]) {
+        assert_eq!(quoted(message), expected);
+    }
+
+    #[test]
+    fn locates_missing_variable() {
+        let (err, sources) = render_error(
+            &[(
+                "photo.html",
+                "<html>\n<body>\n<h1>{{ photo.title }}</h1>\n</body>\n</html>\n",
+            )],
+            "photo.html",
+        );
+
+        let snippet = locate(&err, "photo.html", &sources).unwrap();
+
+        assert_eq!(snippet.template, "photo.html");
+        assert_eq!(snippet.line, 3);
+        assert_eq!(snippet.lines.first().map(|(n, _)| *n), Some(1));
+        assert_eq!(snippet.lines.last().map(|(n, _)| *n), Some(5));
+        assert!(message(&err).contains("photo.title"));
+    }
+
+    #[test]
+    fn locates_error_in_parent_template() {
+        let (err, sources) = render_error(
+            &[
+                (
+                    "base.html",
+                    "<title>{{ site.name }}</title>\n{% block content %}{% endblock content %}",
+                ),
+                (
+                    "index.html",
+                    "{% extends \"base.html\" %}\n{% block content %}hi{% endblock content %}",
+                ),
+            ],
+            "index.html",
+        );
+
+        let snippet = locate(&err, "index.html", &sources).unwrap();
+
+        assert_eq!(snippet.template, "base.html");
+        assert_eq!(snippet.line, 1);
+    }
+
+    #[test]
+    fn display_marks_offending_line() {
+        let snippet = snippet("index.html", "a\nb\nc\nd", 1);
+
+        assert_eq!(
+            snippet.to_string(),
+            "  --> index.html:2\n   1 | a\n > 2 | b\n   3 | c\n   4 | d"
+        );
+    }
+}
@@ -0,0 +1,268 @@
+//! Which context variables each template reads.
+//!
+//! Rendered pages are reused when everything their template can read is the
+//! same, within a build and between builds. What a template can read is
+//! narrowed two ways: it only sees the templates it extends, includes, or
+//! imports, and a variable it only uses through its fields (`site.title`)
+//! depends on just those fields, so a photo page doesn't change when a
+//! photo is added elsewhere. Only template tags are scanned; words in a
+//! page's HTML and strings in tags don't count.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use tera::Context;
+
+/// Tags naming other templates, whose usage is included.
+const DEPENDENCY_TAGS: &[&str] = &["extends", "include", "import"];
+
+/// Context variables read by a template and the templates it depends on.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Usage {
+    /// Variables used as a whole: printed, tested, looped over, or passed on
+    whole: BTreeSet<String>,
+    /// Fields read from variables, for variables not used as a whole
+    fields: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl Usage {
+    /// Feed the parts of `context` this usage reads into `hasher`.
+    pub fn hash(&self, hasher: &mut blake3::Hasher, context: &Context) -> Option<()> {
+        let names: BTreeSet<&String> = self.whole.iter().chain(self.fields.keys()).collect();
+        for name in names {
+            let Some(value) = context.get(name) else {
+                continue;
+            };
+            hasher.update(b"\0");
+            hasher.update(name.as_bytes());
+            match (self.whole.contains(name), value.as_object()) {
+                (false, Some(object)) => {
+                    for field in &self.fields[name] {
+                        if let Some(value) = object.get(field) {
+                            hasher.update(b".");
+                            hasher.update(field.as_bytes());
+                            hasher.update(b"=");
+                            serde_json::to_writer(&mut *hasher, value).ok()?;
+                        }
+                    }
+                }
+                _ => {
+                    hasher.update(b"=");
+                    serde_json::to_writer(&mut *hasher, value).ok()?;
+                }
+            }
+        }
+        Some(())
+    }
+
+    fn merge(&mut self, other: &Usage) {
+        self.whole.extend(other.whole.iter().cloned());
+        for (name, fields) in &other.fields {
+            self.fields
+                .entry(name.clone())
+                .or_default()
+                .extend(fields.iter().cloned());
+        }
+    }
+}
+
+/// Usage of each template in `sources`, by template name.
+pub fn analyze(sources: &BTreeMap<String, String>) -> BTreeMap<String, Usage> {
+    let scanned: BTreeMap<&String, (Usage, Vec<String>)> = sources
+        .iter()
+        .map(|(name, source)| (name, scan(source)))
+        .collect();
+
+    scanned
+        .keys()
+        .map(|&name| {
+            let mut usage = Usage::default();
+            let mut seen = BTreeSet::new();
+            let mut pending = vec![name.clone()];
+            while let Some(name) = pending.pop() {
+                if !seen.insert(name.clone()) {
+                    continue;
+                }
+                if let Some((own, dependencies)) = scanned.get(&name) {
+                    usage.merge(own);
+                    pending.extend(dependencies.iter().cloned());
+                }
+            }
+            (name.clone(), usage)
+        })
+        .collect()
+}
+
+/// Variables used in a template's tags, and the templates it names.
+fn scan(source: &str) -> (Usage, Vec<String>) {
+    let mut usage = Usage::default();
+    let mut dependencies = Vec::new();
+
+    let mut rest = source;
+    while let Some(start) = rest.find('{') {
+        let after = &rest[start + 1..];
+        let end = match after.chars().next() {
+            Some('{') => "}}",
+            Some('%') => "%}",
+            Some('#') => "#}",
+            _ => {
+                rest = after;
+                continue;
+            }
+        };
+        let body = &after[1..];
+        let (tag, remainder) = body.split_once(end).unwrap_or((body, ""));
+        rest = remainder;
+        if end != "#}" {
+            scan_tag(tag, &mut usage, &mut dependencies);
+        }
+    }
+
+    // Fields only matter for variables not used whole
+    for name in &usage.whole {
+        usage.fields.remove(name);
+    }
+    (usage, dependencies)
+}
+
+fn scan_tag(tag: &str, usage: &mut Usage, dependencies: &mut Vec<String>) {
+    let names_templates = tag
+        .trim_start_matches('-')
+        .split_whitespace()
+        .next()
+        .is_some_and(|keyword| DEPENDENCY_TAGS.contains(&keyword));
+    let chars: Vec<char> = tag.chars().collect();
+
+    let mut i = 0;
+    let mut previous = None;
+    while i < chars.len() {
+        let c = chars[i];
+        if matches!(c, '"' | '\'' | '`') {
+            let start = i + 1;
+            i = start;
+            while i < chars.len() && chars[i] != c {
+                i += 1;
+            }
+            if names_templates {
+                dependencies.push(chars[start..i.min(chars.len())].iter().collect());
+            }
+            i += 1;
+            previous = Some(c);
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            let name: String = chars[start..i].iter().collect();
+            // A field is recorded with the variable it belongs to
+            if previous != Some('.') {
+                let field = (chars.get(i) == Some(&'.'))
+                    .then(|| identifier(&chars[i + 1..]))
+                    .flatten();
+                match field {
+                    Some(field) => {
+                        usage.fields.entry(name).or_default().insert(field);
+                    }
+                    // Function calls aren't variables
+                    None if chars.get(i) == Some(&'(') => {}
+                    None => {
+                        usage.whole.insert(name);
+                    }
+                }
+            }
+            previous = Some('a');
+        } else {
+            if !c.is_whitespace() {
+                previous = Some(c);
+            }
+            i += 1;
+        }
+    }
+}
+
+/// The identifier at the start of `chars`, if any.
+fn identifier(chars: &[char]) -> Option<String> {
+    let name: String = chars
+        .iter()
+        .take_while(|c| c.is_alphanumeric() || **c == '_')
+        .collect();
+    (!name.is_empty() && !name.starts_with(|c: char| c.is_ascii_digit())).then_some(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sources(templates: &[(&str, &str)]) -> BTreeMap<String, String> {
+        templates
+            .iter()
+            .map(|(name, source)| (name.to_string(), source.to_string()))
+            .collect()
+    }
+
+    fn hash(usage: &Usage, context: &Context) -> blake3::Hash {
+        let mut hasher = blake3::Hasher::new();
+        usage.hash(&mut hasher, context).unwrap();
+        hasher.finalize()
+    }
+
+    #[test]
+    fn fields_and_whole_variables() {
+        let (usage, dependencies) = scan(
+            r#"{% extends "base.html" %}<p class="album">{{ site.title }} {{ site.build.photo_count }}</p>
+{% for photo in album.photos %}{{ photo.stem | upper }}{% endfor %}{# {{ root }} #}{{ static(path="x.css") }}"#,
+        );
+
+        assert_eq!(dependencies, ["base.html"]);
+        assert!(usage.whole.contains("photo"));
+        assert!(!usage.whole.contains("album"));
+        assert!(!usage.whole.contains("root"));
+        assert!(!usage.whole.contains("static"));
+        assert_eq!(
+            usage.fields["site"],
+            BTreeSet::from(["title".to_string(), "build".to_string()])
+        );
+        assert_eq!(
+            usage.fields["album"],
+            BTreeSet::from(["photos".to_string()])
+        );
+    }
+
+    #[test]
+    fn whole_use_wins_over_fields() {
+        let (usage, _) = scan("{{ languages.first }}{{ languages | json_encode() }}");
+
+        assert!(usage.whole.contains("languages"));
+        assert!(!usage.fields.contains_key("languages"));
+    }
+
+    #[test]
+    fn dependencies_included() {
+        let usage = analyze(&sources(&[
+            ("base.html", "{{ site.title }}{% include 'nav.html' %}"),
+            ("nav.html", "{{ root }}"),
+            ("photo.html", "{% extends \"base.html\" %}{{ photo.stem }}"),
+            ("index.html", "{{ photos }}"),
+        ]));
+
+        let photo = &usage["photo.html"];
+        assert!(photo.whole.contains("root"));
+        assert!(!photo.whole.contains("photos"));
+        assert!(photo.fields.contains_key("site"));
+        assert!(photo.fields.contains_key("photo"));
+    }
+
+    #[test]
+    fn unread_fields_ignored() {
+        let (usage, _) = scan("{{ site.title }}");
+        let mut before = Context::new();
+        before.insert("site", &serde_json::json!({"title": "Trips", "build": 1}));
+        before.insert("root", &"unused");
+        let mut after = Context::new();
+        after.insert("site", &serde_json::json!({"title": "Trips", "build": 2}));
+
+        assert_eq!(hash(&usage, &before), hash(&usage, &after));
+
+        after.insert("site", &serde_json::json!({"title": "Travel", "build": 2}));
+        assert_ne!(hash(&usage, &before), hash(&usage, &after));
+    }
+}
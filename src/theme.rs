@@ -1,11 +1,15 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 use include_dir::Dir;
 use serde::Deserialize;
-use tera::Tera;
+use tera::{Context, Tera};
 
 use crate::error::{Error, Result};
+use crate::template_error::{self, RenderError};
+use crate::template_usage::{self, Usage};
 
 /// Well-known template names.
 pub mod templates {
@@ -15,8 +19,18 @@ pub mod templates {
     pub const ALBUM: &str = "album.html";
     /// Individual photo pages (optional)
     pub const PHOTO: &str = "photo.html";
+    /// Albums grouped by year (optional, rendered with `archive = true`)
+    pub const ARCHIVE: &str = "archive.html";
 }
 
+/// Template code whose output isn't determined by the context variables it
+/// names: the whole context, the environment, and functions that differ
+/// between calls.
+const UNMEMOIZABLE: &[&str] = &["__tera_context", "get_env(", "get_random(", "now("];
+
+/// Template suffixes escaped when autoescaping is enabled.
+const AUTOESCAPE_SUFFIXES: &[&str] = &[".html", ".htm", ".xml"];
+
 /// Source of static assets for a theme.
 #[derive(Debug)]
 pub enum StaticSource {
@@ -28,6 +42,53 @@ pub enum StaticSource {
     None,
 }
 
+impl StaticSource {
+    /// Paths of the static files as `static()` takes them, relative to the
+    /// static directory. Hidden files aren't published, so they're left out.
+    fn asset_names(&self) -> BTreeSet<String> {
+        let visible = |path: &Path| {
+            !path.components().any(|c| {
+                c.as_os_str()
+                    .to_str()
+                    .is_some_and(|name| name.starts_with('.'))
+            })
+        };
+        let name = |path: &Path| {
+            path.components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/")
+        };
+
+        match self {
+            StaticSource::Directory(dir) => walkdir::WalkDir::new(dir)
+                .into_iter()
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.file_type().is_file())
+                .filter_map(|entry| entry.path().strip_prefix(dir).ok().map(Path::to_path_buf))
+                .filter(|path| visible(path))
+                .map(|path| name(&path))
+                .collect(),
+            StaticSource::Builtin(dir) => {
+                let mut names = BTreeSet::new();
+                let mut dirs = vec![*dir];
+                while let Some(current) = dirs.pop() {
+                    dirs.extend(current.dirs());
+                    for file in current.files() {
+                        // Embedded paths are relative to the theme, not static/
+                        let path = file.path().strip_prefix(dir.path()).unwrap_or(file.path());
+                        if visible(path) {
+                            names.insert(name(path));
+                        }
+                    }
+                }
+                names
+            }
+            StaticSource::None => BTreeSet::new(),
+        }
+    }
+}
+
 /// A loaded theme with templates, static assets, and configuration defaults.
 #[derive(Debug)]
 pub struct Theme {
@@ -43,8 +104,53 @@ pub struct Theme {
     /// Whether photo.html template exists
     pub has_photo_template: bool,
 
+    /// Whether archive.html template exists
+    pub has_archive_template: bool,
+
     /// Theme default configuration from theme.toml
     pub defaults: BTreeMap<String, toml::Value>,
+
+    /// Template sources by name, for error reporting
+    pub sources: BTreeMap<String, String>,
+
+    /// Pages already rendered, reused when a page's context is the same
+    renders: RenderCache,
+}
+
+/// Rendered pages keyed by template and the context it can read.
+///
+/// Simple themes often ignore most of a page's context, such as a photo
+/// page that only shows the album. Hashing just what the template reads
+/// (see [`template_usage`]) lets those pages render once.
+#[derive(Debug)]
+struct RenderCache {
+    /// What each template reads, or None if a template's output can't be
+    /// reused (see [`UNMEMOIZABLE`])
+    usage: Option<BTreeMap<String, Usage>>,
+    pages: Mutex<HashMap<blake3::Hash, String>>,
+    reused: AtomicUsize,
+}
+
+impl RenderCache {
+    fn new(sources: &BTreeMap<String, String>) -> Self {
+        let memoizable = !sources
+            .values()
+            .any(|source| UNMEMOIZABLE.iter().any(|pattern| source.contains(pattern)));
+        Self {
+            usage: memoizable.then(|| template_usage::analyze(sources)),
+            pages: Mutex::new(HashMap::new()),
+            reused: AtomicUsize::new(0),
+        }
+    }
+
+    /// Hash of the template name and the context it may read.
+    fn key(&self, template: &str, context: &Context) -> Option<blake3::Hash> {
+        let usage = self.usage.as_ref()?.get(template)?;
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(template.as_bytes());
+        usage.hash(&mut hasher, context)?;
+        Some(hasher.finalize())
+    }
 }
 
 /// Structure for parsing theme.toml files.
@@ -74,6 +180,64 @@ fn load_theme_defaults(theme_dir: &Path) -> Result<BTreeMap<String, toml::Value>
 }
 
 impl Theme {
+    /// Enable or disable HTML autoescaping of template output.
+    ///
+    /// When enabled, `{{ value }}` is HTML-escaped in `.html`, `.htm`, and
+    /// `.xml` templates unless marked with `| safe`.
+    pub fn set_autoescape(&mut self, enabled: bool) {
+        let suffixes = if enabled {
+            AUTOESCAPE_SUFFIXES.to_vec()
+        } else {
+            Vec::new()
+        };
+        self.templates.autoescape_on(suffixes);
+    }
+
+    /// Render a template, attaching the page and source location on failure.
+    ///
+    /// `page` describes what was being rendered (e.g. `photo 'trips/beach'`).
+    /// A page whose context matches an earlier page's in everything its
+    /// template reads reuses that page's output.
+    pub fn render(&self, template: &str, context: &Context, page: &str) -> Result<String> {
+        let key = self.renders.key(template, context);
+        if let Some(key) = &key
+            && let Some(html) = self.renders.pages.lock().unwrap().get(key)
+        {
+            tracing::trace!(template, page, "reused identical render");
+            self.renders.reused.fetch_add(1, Ordering::Relaxed);
+            return Ok(html.clone());
+        }
+
+        let html = self.templates.render(template, context).map_err(|err| {
+            let snippet = template_error::locate(&err, template, &self.sources);
+            Error::Render(Box::new(RenderError {
+                template: template.to_string(),
+                page: page.to_string(),
+                message: template_error::message(&err),
+                snippet,
+            }))
+        })?;
+
+        if let Some(key) = key {
+            self.renders.pages.lock().unwrap().insert(key, html.clone());
+        }
+        Ok(html)
+    }
+
+    /// Hash of the template and the parts of `context` it reads, or None if
+    /// the template's output isn't determined by them.
+    pub fn render_key(&self, template: &str, context: &Context) -> Option<blake3::Hash> {
+        self.renders.key(template, context)
+    }
+
+    /// Forget rendered pages, which depend on more than the context: template
+    /// functions like `static()` change between builds. Returns how many
+    /// renders were reused since the last reset.
+    pub fn reset_renders(&mut self) -> usize {
+        self.renders.pages.get_mut().unwrap().clear();
+        std::mem::take(self.renders.reused.get_mut())
+    }
+
     /// Load a theme from the given directory.
     ///
     /// The directory must contain a `templates/` subdirectory with at least
@@ -86,7 +250,7 @@ impl Theme {
         let glob_pattern = format!("{}/**/*.html", templates_dir.display());
         let mut templates = Tera::new(&glob_pattern)?;
 
-        // Disable autoescaping - we control all template data
+        // Autoescaping starts disabled; the pipeline enables it per site config
         templates.autoescape_on(vec![]);
 
         // Validate required templates
@@ -104,6 +268,17 @@ impl Theme {
         let has_photo_template = templates
             .get_template_names()
             .any(|n| n == templates::PHOTO);
+        let has_archive_template = templates
+            .get_template_names()
+            .any(|n| n == templates::ARCHIVE);
+
+        let sources = templates
+            .get_template_names()
+            .filter_map(|name| {
+                let content = std::fs::read_to_string(templates_dir.join(name)).ok()?;
+                Some((name.to_string(), content))
+            })
+            .collect();
 
         // Check for static directory
         let static_source = if static_dir.is_dir() {
@@ -118,17 +293,24 @@ impl Theme {
         tracing::info!(
             has_album = has_album_template,
             has_photo = has_photo_template,
+            has_archive = has_archive_template,
             has_static = !matches!(static_source, StaticSource::None),
             defaults = defaults.len(),
             "theme loaded"
         );
 
+        check_static_references(&sources, &static_source)?;
+        let renders = RenderCache::new(&sources);
+
         Ok(Self {
             templates,
             static_source,
             has_album_template,
             has_photo_template,
+            has_archive_template,
             defaults,
+            sources,
+            renders,
         })
     }
 
@@ -138,6 +320,7 @@ impl Theme {
     /// an `index.html` template.
     pub fn from_builtin(dir: &'static Dir<'static>) -> Result<Self> {
         let mut templates = Tera::default();
+        let mut sources = BTreeMap::new();
 
         // Load all templates from templates/ subdirectory
         let templates_dir = dir
@@ -163,10 +346,17 @@ impl Theme {
                 continue;
             };
 
-            templates.add_raw_template(name, content)?;
+            sources.insert(name.to_string(), content.to_string());
         }
 
-        // Disable autoescaping - we control all template data
+        // Added together so templates can extend ones that sort after them
+        templates.add_raw_templates(
+            sources
+                .iter()
+                .map(|(name, content)| (name.as_str(), content.as_str())),
+        )?;
+
+        // Autoescaping starts disabled; the pipeline enables it per site config
         templates.autoescape_on(vec![]);
 
         // Validate required templates
@@ -184,6 +374,9 @@ impl Theme {
         let has_photo_template = templates
             .get_template_names()
             .any(|n| n == templates::PHOTO);
+        let has_archive_template = templates
+            .get_template_names()
+            .any(|n| n == templates::ARCHIVE);
 
         // Get static/ subdirectory if it exists
         let static_source = dir
@@ -210,25 +403,95 @@ impl Theme {
         tracing::info!(
             has_album = has_album_template,
             has_photo = has_photo_template,
+            has_archive = has_archive_template,
             has_static = !matches!(static_source, StaticSource::None),
             defaults = defaults.len(),
             "theme loaded"
         );
 
+        check_static_references(&sources, &static_source)?;
+        let renders = RenderCache::new(&sources);
+
         Ok(Self {
             templates,
             static_source,
             has_album_template,
             has_photo_template,
+            has_archive_template,
             defaults,
+            sources,
+            renders,
+        })
+    }
+}
+
+/// Check that every `static(path='...')` call in the templates names an
+/// asset that exists, so a typo fails the build before rendering starts
+/// rather than partway through thousands of photo pages. Paths computed at
+/// render time can't be checked here.
+fn check_static_references(
+    sources: &BTreeMap<String, String>,
+    static_source: &StaticSource,
+) -> Result<()> {
+    let assets = static_source.asset_names();
+    let missing: Vec<String> = sources
+        .iter()
+        .flat_map(|(template, source)| {
+            static_references(source)
+                .into_iter()
+                .filter(|(_, path)| !assets.contains(path))
+                .map(move |(line, path)| format!("'{}' ({}:{})", path, template, line))
         })
+        .collect();
+
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::MissingStaticAssets { missing })
+    }
+}
+
+/// Line numbers and paths of the `static()` calls in a template that pass a
+/// string literal.
+fn static_references(source: &str) -> Vec<(usize, String)> {
+    let mut references = Vec::new();
+    for (start, _) in source.match_indices("static(") {
+        // Skip functions that merely end in "static", like `my_static(`
+        let preceding = source[..start].chars().next_back();
+        if preceding.is_some_and(|c| c.is_alphanumeric() || c == '_') {
+            continue;
+        }
+
+        let args = source[start + "static(".len()..].trim_start();
+        let Some(args) = args.strip_prefix("path") else {
+            continue;
+        };
+        let Some(args) = args.trim_start().strip_prefix('=') else {
+            continue;
+        };
+        let args = args.trim_start();
+        let Some(quote) = args
+            .chars()
+            .next()
+            .filter(|c| matches!(c, '\'' | '"' | '`'))
+        else {
+            continue;
+        };
+        let Some(end) = args[1..].find(quote) else {
+            continue;
+        };
+
+        let line = source[..start].matches('\n').count() + 1;
+        references.push((line, args[1..=end].to_string()));
     }
+    references
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::fs;
+    use test_case::test_case;
 
     fn create_temp_theme(templates: &[(&str, &str)]) -> tempfile::TempDir {
         let dir = tempfile::tempdir().unwrap();
@@ -250,6 +513,7 @@ mod tests {
 
         assert!(!theme.has_album_template);
         assert!(!theme.has_photo_template);
+        assert!(!theme.has_archive_template);
         assert!(matches!(theme.static_source, StaticSource::None));
         assert!(theme.defaults.is_empty());
     }
@@ -260,6 +524,7 @@ mod tests {
             ("index.html", "<html></html>"),
             ("album.html", "<html></html>"),
             ("photo.html", "<html></html>"),
+            ("archive.html", "<html></html>"),
         ]);
 
         // Create static directory
@@ -269,6 +534,7 @@ mod tests {
 
         assert!(theme.has_album_template);
         assert!(theme.has_photo_template);
+        assert!(theme.has_archive_template);
         assert!(matches!(theme.static_source, StaticSource::Directory(_)));
         assert!(theme.defaults.is_empty());
     }
@@ -300,6 +566,98 @@ mod tests {
         );
     }
 
+    #[test]
+    fn autoescape_toggle() {
+        let dir = create_temp_theme(&[("index.html", "{{ value }}|{{ value | safe }}")]);
+        let mut theme = Theme::load(dir.path()).unwrap();
+        let mut context = tera::Context::new();
+        context.insert("value", "<b>");
+
+        let raw = theme.templates.render("index.html", &context).unwrap();
+        assert_eq!(raw, "<b>|<b>");
+
+        theme.set_autoescape(true);
+        let escaped = theme.templates.render("index.html", &context).unwrap();
+        assert_eq!(escaped, "&lt;b&gt;|<b>");
+    }
+
+    #[test]
+    fn static_references_checked() {
+        let dir = create_temp_theme(&[(
+            "index.html",
+            "<link href=\"{{ static(path='style.css') }}\">\n\
+             <script src=\"{{ static(path = \"js/app.js\") }}\"></script>",
+        )]);
+        fs::create_dir_all(dir.path().join("static/js")).unwrap();
+        fs::write(dir.path().join("static/style.css"), "").unwrap();
+        fs::write(dir.path().join("static/js/app.js"), "").unwrap();
+
+        assert!(Theme::load(dir.path()).is_ok());
+
+        fs::remove_file(dir.path().join("static/js/app.js")).unwrap();
+        let err = Theme::load(dir.path()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "theme references missing static assets: 'js/app.js' (index.html:2)"
+        );
+    }
+
+    #[test]
+    fn static_references_parsed() {
+        let source = "{{ static(path='a.css') }}\n\
+                      {{ static(path=name) }} {{ my_static(path='b.css') }}\n\
+                      {{ static( path = \"c/d.js\" ) }}";
+
+        assert_eq!(
+            static_references(source),
+            [(1, "a.css".to_string()), (3, "c/d.js".to_string())]
+        );
+    }
+
+    #[test]
+    fn builtin_themes_reference_existing_assets() {
+        for name in crate::builtin_themes::NAMES {
+            let dir = crate::builtin_themes::get(name).unwrap();
+            if let Err(e) = Theme::from_builtin(dir) {
+                panic!("built-in theme {}: {}", name, e);
+            }
+        }
+    }
+
+    #[test]
+    fn identical_contexts_render_once() {
+        let dir = create_temp_theme(&[("index.html", "{{ album }}")]);
+        let mut theme = Theme::load(dir.path()).unwrap();
+        let render = |theme: &Theme, album: &str, photo: &str| {
+            let mut context = Context::new();
+            context.insert("album", album);
+            context.insert("photo", photo);
+            theme.render("index.html", &context, "test").unwrap()
+        };
+
+        // The template never reads `photo`, so only `album` tells pages apart
+        assert_eq!(render(&theme, "Iceland", "beach"), "Iceland");
+        assert_eq!(render(&theme, "Iceland", "glacier"), "Iceland");
+        assert_eq!(render(&theme, "Japan", "beach"), "Japan");
+        assert_eq!(theme.reset_renders(), 1);
+        assert_eq!(theme.reset_renders(), 0);
+    }
+
+    #[test_case("{{ __tera_context | length }}{{ album }}" ; "whole context")]
+    #[test_case("{{ album }} {{ get_random(end=10) }}" ; "random")]
+    #[test_case("{{ album }} {{ get_env(name=\"HOME\", default=\"\") }}" ; "environment")]
+    fn unmemoizable_templates_always_render(template: &str) {
+        let dir = create_temp_theme(&[("index.html", template)]);
+        let mut theme = Theme::load(dir.path()).unwrap();
+        let mut context = Context::new();
+        context.insert("album", "Iceland");
+
+        theme.render("index.html", &context, "test").unwrap();
+        theme.render("index.html", &context, "test").unwrap();
+
+        assert_eq!(theme.reset_renders(), 0);
+    }
+
     #[test]
     fn missing_index_fails() {
         let dir = create_temp_theme(&[("photo.html", "<html></html>")]);
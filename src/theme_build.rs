@@ -80,7 +80,7 @@ pub fn build_vite_theme(theme_dir: &Path) -> Result<PathBuf> {
 /// Find a package manager to use for the theme.
 ///
 /// Returns (name, path) tuple.
-fn find_package_manager(theme_dir: &Path) -> Result<(&'static str, PathBuf)> {
+pub(crate) fn find_package_manager(theme_dir: &Path) -> Result<(&'static str, PathBuf)> {
     // Check lockfiles first (respect user's choice)
     let preferred = if theme_dir.join("bun.lockb").exists() {
         Some("bun")
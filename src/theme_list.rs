@@ -0,0 +1,205 @@
+//! Themes a site can use.
+//!
+//! `galerie themes` lists the built-in themes and the theme directories
+//! found in the site directory, up to two levels deep so `themes/mine` is
+//! found too. Each is described by whether it's a classic or Vite theme and
+//! which optional pages it renders, since a theme without `album.html` or
+//! `photo.html` gives a site no album or photo pages.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+use crate::builtin_themes;
+use crate::theme::templates;
+use crate::theme_build::{self, ThemeType};
+
+/// Templates a theme may leave out.
+const OPTIONAL_TEMPLATES: [&str; 3] = [templates::ALBUM, templates::PHOTO, templates::ARCHIVE];
+
+/// Directories never searched for themes.
+const SKIPPED_DIRS: [&str; 2] = ["node_modules", "dist"];
+
+/// A theme and what it provides.
+#[derive(Debug, PartialEq)]
+pub struct ThemeInfo {
+    /// Name as `theme` in site.toml takes it
+    pub name: String,
+    pub kind: ThemeType,
+    /// Optional templates the theme has
+    pub templates: Vec<&'static str>,
+}
+
+/// Built-in themes.
+pub fn builtin() -> Vec<ThemeInfo> {
+    builtin_themes::list()
+        .map(|(name, dir, kind)| ThemeInfo {
+            name: name.to_string(),
+            kind,
+            templates: OPTIONAL_TEMPLATES
+                .into_iter()
+                .filter(|template| dir.get_file(format!("templates/{}", template)).is_some())
+                .collect(),
+        })
+        .collect()
+}
+
+/// Theme directories in `site_dir` and its subdirectories, by path.
+pub fn local(site_dir: &Path) -> Vec<ThemeInfo> {
+    let mut themes = Vec::new();
+    for dir in subdirectories(site_dir) {
+        if let Some(theme) = describe(site_dir, &dir) {
+            themes.push(theme);
+            continue;
+        }
+        themes.extend(
+            subdirectories(&dir)
+                .iter()
+                .filter_map(|dir| describe(site_dir, dir)),
+        );
+    }
+    themes.sort_by(|a, b| a.name.cmp(&b.name));
+    themes
+}
+
+/// The theme in `dir`, if it is one: a `templates/index.html`, whose
+/// templates a Vite theme keeps in the same place.
+fn describe(site_dir: &Path, dir: &Path) -> Option<ThemeInfo> {
+    let templates_dir = dir.join("templates");
+    if !templates_dir.join(templates::INDEX).is_file() {
+        return None;
+    }
+    let name = dir
+        .strip_prefix(site_dir)
+        .ok()?
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/");
+    Some(ThemeInfo {
+        name,
+        kind: theme_build::detect_theme_type(dir),
+        templates: OPTIONAL_TEMPLATES
+            .into_iter()
+            .filter(|template| templates_dir.join(template).is_file())
+            .collect(),
+    })
+}
+
+/// Visible directories in `dir`, except ones that never hold themes.
+fn subdirectories(dir: &Path) -> Vec<std::path::PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_ok_and(|kind| kind.is_dir()))
+        .filter(|entry| {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            !name.starts_with('.') && !SKIPPED_DIRS.contains(&name.as_ref())
+        })
+        .map(|entry| entry.path())
+        .collect()
+}
+
+/// The themes as a table, marking the one `in_use`.
+pub fn table(builtin: &[ThemeInfo], local: &[ThemeInfo], in_use: Option<&str>) -> String {
+    let width = builtin
+        .iter()
+        .chain(local)
+        .map(|theme| theme.name.len())
+        .max()
+        .unwrap_or(0);
+    let mut out = String::new();
+    let mut section = |heading: &str, themes: &[ThemeInfo]| {
+        let _ = writeln!(out, "{}", heading);
+        if themes.is_empty() {
+            let _ = writeln!(out, "  (none)");
+        }
+        for theme in themes {
+            let kind = match theme.kind {
+                ThemeType::Classic => "classic",
+                ThemeType::Vite => "vite",
+            };
+            let pages = if theme.templates.is_empty() {
+                "index only".to_string()
+            } else {
+                theme.templates.join(", ")
+            };
+            let marker = if in_use == Some(theme.name.as_str()) {
+                "  (in use)"
+            } else {
+                ""
+            };
+            let _ = writeln!(
+                out,
+                "  {:<width$}  {:<7}  {}{}",
+                theme.name,
+                kind,
+                pages,
+                marker,
+                width = width
+            );
+        }
+    };
+    section("Built-in themes", builtin);
+    section("\nLocal themes", local);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn theme(dir: &Path, templates: &[&str]) {
+        fs::create_dir_all(dir.join("templates")).unwrap();
+        for template in templates {
+            fs::write(dir.join("templates").join(template), "").unwrap();
+        }
+    }
+
+    #[test]
+    fn local_themes_found() {
+        let site = tempfile::tempdir().unwrap();
+        theme(&site.path().join("mine"), &["index.html", "photo.html"]);
+        theme(
+            &site.path().join("themes/vite"),
+            &["index.html", "album.html"],
+        );
+        fs::write(site.path().join("themes/vite/package.json"), "{}").unwrap();
+        fs::write(site.path().join("themes/vite/vite.config.ts"), "").unwrap();
+        // Not themes: no index template, or hidden
+        theme(&site.path().join("photos/trip"), &["photo.html"]);
+        theme(&site.path().join(".cache"), &["index.html"]);
+
+        let themes = local(site.path());
+
+        assert_eq!(
+            themes,
+            [
+                ThemeInfo {
+                    name: "mine".to_string(),
+                    kind: ThemeType::Classic,
+                    templates: vec!["photo.html"],
+                },
+                ThemeInfo {
+                    name: "themes/vite".to_string(),
+                    kind: ThemeType::Vite,
+                    templates: vec!["album.html"],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn builtin_themes_described() {
+        let themes = builtin();
+
+        assert_eq!(themes.len(), builtin_themes::NAMES.len());
+        let basic = themes.iter().find(|theme| theme.name == "basic").unwrap();
+        assert_eq!(basic.kind, ThemeType::Classic);
+        assert!(basic.templates.contains(&templates::PHOTO));
+        assert!(table(&themes, &[], Some("basic")).contains("(in use)"));
+    }
+}
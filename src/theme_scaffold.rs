@@ -0,0 +1,96 @@
+//! Scaffolding for new themes.
+//!
+//! `galerie new-theme` writes a small classic theme: a base layout, the
+//! index, album, and photo templates using the context the pipeline
+//! provides, a stylesheet, and a `theme.toml` with a setting the templates
+//! read. It's meant to be edited, not configured, so every page shows the
+//! variables it has to work with.
+
+use std::fs;
+use std::path::Path;
+
+use crate::error::{Error, Result};
+
+/// Files of the scaffold, by path in the theme directory.
+const FILES: &[(&str, &str)] = &[
+    ("theme.toml", include_str!("data/theme/theme.toml")),
+    (
+        "templates/base.html",
+        include_str!("data/theme/templates/base.html"),
+    ),
+    (
+        "templates/index.html",
+        include_str!("data/theme/templates/index.html"),
+    ),
+    (
+        "templates/album.html",
+        include_str!("data/theme/templates/album.html"),
+    ),
+    (
+        "templates/photo.html",
+        include_str!("data/theme/templates/photo.html"),
+    ),
+    (
+        "static/style.css",
+        include_str!("data/theme/static/style.css"),
+    ),
+];
+
+/// Create a theme at `name`, relative to `site_dir`.
+pub fn run(site_dir: &Path, name: &str) -> Result<()> {
+    let theme_dir = site_dir.join(name);
+    if theme_dir.exists() {
+        return Err(Error::Other(format!(
+            "{} already exists; choose another name",
+            theme_dir.display()
+        )));
+    }
+
+    for (path, content) in FILES {
+        let path = theme_dir.join(path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, content)?;
+    }
+    tracing::info!(path = %theme_dir.display(), files = FILES.len(), "created theme");
+
+    println!(
+        "\n  Created a theme in {}\n  Use it with theme = {} in site.toml, then run: galerie serve\n  The variables templates can use are listed in wiki/template-context.md\n",
+        theme_dir.display(),
+        toml::Value::String(name.to_string())
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scaffold_renders_every_page() {
+        let site_dir = tempfile::tempdir().unwrap();
+        let snapshot_dir = tempfile::tempdir().unwrap();
+        let mut site = crate::preview::generate(site_dir.path(), "themes/mine").unwrap();
+        run(site_dir.path(), "themes/mine").unwrap();
+        site.minify = false;
+        let report =
+            crate::snapshot::check(site_dir.path(), site, snapshot_dir.path(), false).unwrap();
+
+        assert!(report.passed());
+        let index = fs::read_to_string(snapshot_dir.path().join("index.html")).unwrap();
+        assert!(index.contains("--accent: #3a6ea5"));
+    }
+
+    #[test]
+    fn existing_directory_kept() {
+        let site_dir = tempfile::tempdir().unwrap();
+        fs::create_dir(site_dir.path().join("mine")).unwrap();
+
+        assert!(run(site_dir.path(), "mine").is_err());
+        assert_eq!(
+            fs::read_dir(site_dir.path().join("mine")).unwrap().count(),
+            0
+        );
+    }
+}
@@ -0,0 +1,316 @@
+//! Where a build's time goes.
+//!
+//! With `--timings`, each photo records how long it spent in each phase of
+//! processing, and the pipeline records how long each stage of the build
+//! took. The report lists the stages, the phases summed over all photos, and
+//! the slowest photos. Phase times are summed across threads, so on a
+//! multi-core build they add up to more than the processing stage took.
+
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::error::{Error, Result};
+
+/// Slowest photos listed in the report.
+const SLOWEST: usize = 10;
+
+/// A phase of processing one photo.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// Reading the original from disk
+    Read,
+    /// Hashing the original
+    Hash,
+    /// EXIF, XMP, and IPTC metadata, and place names
+    Metadata,
+    /// Decoding the original into pixels
+    Decode,
+    /// Resizing and encoding variants
+    Encode,
+    /// Writing variants and originals
+    Write,
+}
+
+impl Phase {
+    const ALL: [Phase; 6] = [
+        Phase::Read,
+        Phase::Hash,
+        Phase::Metadata,
+        Phase::Decode,
+        Phase::Encode,
+        Phase::Write,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Phase::Read => "read",
+            Phase::Hash => "hash",
+            Phase::Metadata => "metadata",
+            Phase::Decode => "decode",
+            Phase::Encode => "encode",
+            Phase::Write => "write",
+        }
+    }
+}
+
+/// Time one photo spent in each phase.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PhotoTimings {
+    phases: [Duration; Phase::ALL.len()],
+}
+
+impl PhotoTimings {
+    /// Run `f`, counting its time towards `phase`.
+    pub fn time<T>(&mut self, phase: Phase, f: impl FnOnce() -> T) -> T {
+        let started = Instant::now();
+        let result = f();
+        self.phases[phase as usize] += started.elapsed();
+        result
+    }
+
+    fn total(&self) -> Duration {
+        self.phases.iter().sum()
+    }
+}
+
+/// Timings collected during a build. Nothing is kept unless enabled.
+#[derive(Debug, Default)]
+pub struct Timings {
+    enabled: bool,
+    stages: Mutex<Vec<(&'static str, Duration)>>,
+    photos: Mutex<Vec<(PathBuf, PhotoTimings)>>,
+}
+
+impl Timings {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            ..Self::default()
+        }
+    }
+
+    /// Record that the stage `name` took the time since `started`.
+    pub fn stage(&self, name: &'static str, started: Instant) {
+        if self.enabled {
+            let elapsed = started.elapsed();
+            self.stages.lock().unwrap().push((name, elapsed));
+        }
+    }
+
+    /// Record the phases of processing the photo at `path`.
+    pub fn photo(&self, path: &Path, timings: PhotoTimings) {
+        if self.enabled {
+            self.photos
+                .lock()
+                .unwrap()
+                .push((path.to_path_buf(), timings));
+        }
+    }
+
+    /// What was recorded, with photos slowest first.
+    pub fn report(&self) -> Report {
+        let stages = self
+            .stages
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|&(name, duration)| StageReport {
+                name,
+                seconds: duration.as_secs_f64(),
+            })
+            .collect();
+
+        let mut photos = self.photos.lock().unwrap().clone();
+        photos.sort_by_key(|(_, timings)| std::cmp::Reverse(timings.total()));
+
+        let phases = Phase::ALL
+            .iter()
+            .map(|&phase| {
+                let times = photos
+                    .iter()
+                    .map(|(_, timings)| timings.phases[phase as usize]);
+                let total: Duration = times.clone().sum();
+                PhaseReport {
+                    name: phase.name(),
+                    total: total.as_secs_f64(),
+                    mean: if photos.is_empty() {
+                        0.0
+                    } else {
+                        total.as_secs_f64() / photos.len() as f64
+                    },
+                    max: times.max().unwrap_or_default().as_secs_f64(),
+                }
+            })
+            .collect();
+
+        let photos = photos
+            .into_iter()
+            .map(|(path, timings)| PhotoReport {
+                path,
+                seconds: timings.total().as_secs_f64(),
+                phases: Phase::ALL
+                    .iter()
+                    .map(|&phase| (phase.name(), timings.phases[phase as usize].as_secs_f64()))
+                    .collect(),
+            })
+            .collect();
+
+        Report {
+            stages,
+            phases,
+            photos,
+        }
+    }
+}
+
+/// Timings of a build, as printed or written as JSON.
+#[derive(Debug, Serialize)]
+pub struct Report {
+    pub stages: Vec<StageReport>,
+    pub phases: Vec<PhaseReport>,
+    /// Every photo processed, slowest first
+    pub photos: Vec<PhotoReport>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StageReport {
+    pub name: &'static str,
+    pub seconds: f64,
+}
+
+/// A phase summed over every photo, in seconds.
+#[derive(Debug, Serialize)]
+pub struct PhaseReport {
+    pub name: &'static str,
+    pub total: f64,
+    pub mean: f64,
+    pub max: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PhotoReport {
+    pub path: PathBuf,
+    pub seconds: f64,
+    pub phases: std::collections::BTreeMap<&'static str, f64>,
+}
+
+impl Report {
+    /// Write the report as JSON to `path`.
+    pub fn write_json(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| Error::Other(format!("failed to serialize timings: {}", e)))?;
+        std::fs::write(path, json)?;
+        Ok(())
+    }
+
+    /// The report as a plain text table.
+    pub fn table(&self) -> String {
+        let mut out = String::new();
+        let total: f64 = self.stages.iter().map(|stage| stage.seconds).sum();
+        let _ = writeln!(out, "{:<20} {:>10} {:>6}", "stage", "time", "share");
+        for stage in &self.stages {
+            let share = if total > 0.0 {
+                stage.seconds / total * 100.0
+            } else {
+                0.0
+            };
+            let _ = writeln!(
+                out,
+                "{:<20} {:>10} {:>5.1}%",
+                stage.name,
+                seconds(stage.seconds),
+                share
+            );
+        }
+        let _ = writeln!(out, "{:<20} {:>10}", "total", seconds(total));
+
+        if self.photos.is_empty() {
+            return out;
+        }
+        let _ = writeln!(
+            out,
+            "\n{:<20} {:>10} {:>10} {:>10}   ({} photos, summed across threads)",
+            "phase",
+            "total",
+            "mean",
+            "max",
+            self.photos.len()
+        );
+        for phase in &self.phases {
+            let _ = writeln!(
+                out,
+                "{:<20} {:>10} {:>10} {:>10}",
+                phase.name,
+                seconds(phase.total),
+                seconds(phase.mean),
+                seconds(phase.max)
+            );
+        }
+
+        let _ = writeln!(out, "\nslowest photos");
+        for photo in self.photos.iter().take(SLOWEST) {
+            let _ = writeln!(
+                out,
+                "{:>10}  {}",
+                seconds(photo.seconds),
+                photo.path.display()
+            );
+        }
+        out
+    }
+}
+
+/// Seconds, to the millisecond.
+fn seconds(seconds: f64) -> String {
+    format!("{:.3}s", seconds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn photo(phases: &[(Phase, u64)]) -> PhotoTimings {
+        let mut timings = PhotoTimings::default();
+        for &(phase, millis) in phases {
+            timings.phases[phase as usize] += Duration::from_millis(millis);
+        }
+        timings
+    }
+
+    #[test]
+    fn photos_reported_slowest_first() {
+        let timings = Timings::new(true);
+        timings.photo(Path::new("a.jpg"), photo(&[(Phase::Decode, 100)]));
+        timings.photo(
+            Path::new("b.jpg"),
+            photo(&[(Phase::Decode, 300), (Phase::Encode, 200)]),
+        );
+        timings.stage("photos", Instant::now());
+
+        let report = timings.report();
+
+        assert_eq!(report.photos[0].path, Path::new("b.jpg"));
+        assert_eq!(report.photos[0].seconds, 0.5);
+        assert_eq!(report.photos[0].phases["encode"], 0.2);
+        let decode = &report.phases[Phase::Decode as usize];
+        assert_eq!((decode.total, decode.mean, decode.max), (0.4, 0.2, 0.3));
+        assert_eq!(report.stages[0].name, "photos");
+        assert!(report.table().contains("slowest photos"));
+    }
+
+    #[test]
+    fn nothing_recorded_unless_enabled() {
+        let timings = Timings::new(false);
+        timings.photo(Path::new("a.jpg"), photo(&[(Phase::Read, 10)]));
+        timings.stage("photos", Instant::now());
+
+        let report = timings.report();
+
+        assert!(report.photos.is_empty());
+        assert!(report.stages.is_empty());
+    }
+}
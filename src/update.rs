@@ -0,0 +1,281 @@
+//! Notices about newer releases.
+//!
+//! With `update_check` enabled, builds look up the latest galerie release on
+//! GitHub at most once a day and log a line when it's newer than the running
+//! version, with the first few items of its release notes. The request sends
+//! nothing about the site, only galerie's version in its user agent. Results
+//! are kept in the user's cache directory, and failures are ignored. The
+//! `GALERIE_UPDATE_CHECK` environment variable overrides the config, so
+//! checks can be turned off on CI or on for a single run.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+
+/// Environment variable overriding `update_check` ("0"/"1", "false"/"true", "off"/"on").
+const ENV_VAR: &str = "GALERIE_UPDATE_CHECK";
+
+/// GitHub API endpoint describing the latest release.
+const LATEST_URL: &str = "https://api.github.com/repos/Twister915/galerie/releases/latest";
+
+/// Timeout for the release lookup, short enough not to hold up builds.
+const TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Seconds between lookups.
+const INTERVAL: u64 = 24 * 60 * 60;
+
+/// Release note items included in the notice.
+const HIGHLIGHTS: usize = 3;
+
+/// The latest release, as cached between builds.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Release {
+    /// Version without the tag's "v" prefix (e.g., "0.2.0")
+    version: String,
+    /// Release page
+    url: String,
+    /// First items of the release notes
+    highlights: Vec<String>,
+}
+
+/// On-disk representation of the last lookup.
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheFile {
+    /// Unix time of the lookup
+    checked_at: u64,
+    /// What the lookup found, or the previous result if it failed
+    latest: Option<Release>,
+}
+
+/// Fields of GitHub's release response that are used.
+#[derive(Deserialize)]
+struct GithubRelease {
+    tag_name: String,
+    html_url: String,
+    #[serde(default)]
+    body: Option<String>,
+}
+
+/// Log a notice if a newer release is out, when checks are enabled by
+/// `configured` or the environment.
+pub fn notify(configured: bool) {
+    let enabled = std::env::var(ENV_VAR)
+        .ok()
+        .and_then(|value| parse_switch(&value))
+        .unwrap_or(configured);
+    if !enabled {
+        return;
+    }
+    let Some(path) = cache_path() else {
+        tracing::debug!("no cache directory, skipping update check");
+        return;
+    };
+
+    if let Some(release) = latest(&path, now())
+        && is_newer(&release.version, env!("CARGO_PKG_VERSION"))
+    {
+        tracing::info!("{}", notice(&release));
+    }
+}
+
+/// The latest release, looked up again if the cached result at `path` is
+/// older than a day.
+fn latest(path: &Path, now: u64) -> Option<Release> {
+    let cached: Option<CacheFile> = fs::read(path)
+        .ok()
+        .and_then(|data| serde_json::from_slice(&data).ok());
+    if let Some(cached) = &cached
+        && now.saturating_sub(cached.checked_at) < INTERVAL
+    {
+        return cached.latest.clone();
+    }
+
+    let latest = match fetch() {
+        Ok(release) => Some(release),
+        Err(e) => {
+            tracing::debug!(error = %e, "update check failed");
+            cached.and_then(|cached| cached.latest)
+        }
+    };
+
+    // Failures are recorded too, so an offline machine doesn't retry every build
+    let file = CacheFile {
+        checked_at: now,
+        latest: latest.clone(),
+    };
+    if let Some(dir) = path.parent()
+        && let Err(e) = fs::create_dir_all(dir)
+            .and_then(|()| fs::write(path, serde_json::to_vec(&file).unwrap_or_default()))
+    {
+        tracing::debug!(path = %path.display(), error = %e, "failed to save update check");
+    }
+    latest
+}
+
+/// Look up the latest release on GitHub.
+fn fetch() -> Result<Release> {
+    let agent = ureq::AgentBuilder::new()
+        .timeout(TIMEOUT)
+        .user_agent(concat!("galerie/", env!("CARGO_PKG_VERSION")))
+        .build();
+    let release: GithubRelease = agent
+        .get(LATEST_URL)
+        .set("Accept", "application/vnd.github+json")
+        .call()
+        .map_err(|e| Error::Other(e.to_string()))?
+        .into_json()?;
+
+    Ok(Release {
+        version: release.tag_name.trim_start_matches('v').to_string(),
+        url: release.html_url,
+        highlights: highlights(release.body.as_deref().unwrap_or_default()),
+    })
+}
+
+/// The first list items of release notes, without their markers.
+fn highlights(body: &str) -> Vec<String> {
+    body.lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            line.strip_prefix("- ")
+                .or_else(|| line.strip_prefix("* "))
+                .map(str::trim)
+        })
+        .filter(|item| !item.is_empty())
+        .take(HIGHLIGHTS)
+        .map(str::to_string)
+        .collect()
+}
+
+/// One-line notice about `release`.
+fn notice(release: &Release) -> String {
+    let mut notice = format!(
+        "galerie {} is available (this is {})",
+        release.version,
+        env!("CARGO_PKG_VERSION")
+    );
+    if !release.highlights.is_empty() {
+        notice.push_str(": ");
+        notice.push_str(&release.highlights.join("; "));
+    }
+    notice.push_str(" — ");
+    notice.push_str(&release.url);
+    notice
+}
+
+/// Whether `latest` is a higher version than `current`.
+fn is_newer(latest: &str, current: &str) -> bool {
+    match (parse_version(latest), parse_version(current)) {
+        (Some(latest), Some(current)) => latest > current,
+        _ => false,
+    }
+}
+
+/// Major, minor, and patch numbers of a version like "0.2.0", ignoring any
+/// pre-release or build suffix.
+fn parse_version(version: &str) -> Option<(u64, u64, u64)> {
+    let version = version.trim_start_matches('v');
+    let core = version.split(['-', '+']).next()?;
+    let mut numbers = core.split('.').map(|n| n.parse::<u64>().ok());
+    let major = numbers.next()??;
+    let minor = numbers.next().unwrap_or(Some(0))?;
+    let patch = numbers.next().unwrap_or(Some(0))?;
+    Some((major, minor, patch))
+}
+
+/// Parse an on/off environment value.
+fn parse_switch(value: &str) -> Option<bool> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "1" | "true" | "on" | "yes" => Some(true),
+        "0" | "false" | "off" | "no" => Some(false),
+        _ => None,
+    }
+}
+
+/// Where the last lookup is kept.
+fn cache_path() -> Option<PathBuf> {
+    let dir = std::env::var_os("XDG_CACHE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+    Some(dir.join("galerie").join("update-check.json"))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    fn release(version: &str) -> Release {
+        Release {
+            version: version.to_string(),
+            url: format!(
+                "https://github.com/Twister915/galerie/releases/tag/v{}",
+                version
+            ),
+            highlights: vec!["Fix HEIC rotation".to_string()],
+        }
+    }
+
+    #[test_case("0.2.0", "0.1.10", true ; "minor")]
+    #[test_case("0.1.10", "0.1.9", true ; "numeric not lexical")]
+    #[test_case("0.1.10", "0.1.10", false ; "same")]
+    #[test_case("0.1.9", "0.1.10", false ; "older")]
+    #[test_case("v1.0", "0.9.3", true ; "prefix and short")]
+    #[test_case("0.2.0-rc.1", "0.1.10", true ; "pre-release suffix")]
+    #[test_case("nightly", "0.1.10", false ; "not a version")]
+    fn test_is_newer(latest: &str, current: &str, expected: bool) {
+        assert_eq!(is_newer(latest, current), expected);
+    }
+
+    #[test]
+    fn highlights_from_release_notes() {
+        let body = "## What's changed\r\n\r\n- Fix HEIC rotation\r\n* Faster AVIF encoding\n\nSome prose.\n- \n- Video support\n- Dominant colors\n";
+
+        assert_eq!(
+            highlights(body),
+            ["Fix HEIC rotation", "Faster AVIF encoding", "Video support"]
+        );
+    }
+
+    #[test]
+    fn notice_lists_highlights() {
+        let notice = notice(&release("9.0.0"));
+
+        assert!(notice.starts_with("galerie 9.0.0 is available (this is "));
+        assert!(notice.ends_with(
+            ": Fix HEIC rotation — https://github.com/Twister915/galerie/releases/tag/v9.0.0"
+        ));
+    }
+
+    #[test]
+    fn recent_lookup_reused() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("update-check.json");
+        let file = CacheFile {
+            checked_at: 1_000_000,
+            latest: Some(release("9.0.0")),
+        };
+        fs::write(&path, serde_json::to_vec(&file).unwrap()).unwrap();
+
+        assert_eq!(latest(&path, 1_000_000 + 60), Some(release("9.0.0")));
+    }
+
+    #[test_case("1", Some(true))]
+    #[test_case(" Off ", Some(false))]
+    #[test_case("maybe", None)]
+    fn test_parse_switch(value: &str, expected: Option<bool>) {
+        assert_eq!(parse_switch(value), expected);
+    }
+}
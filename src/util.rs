@@ -28,9 +28,187 @@ pub fn url_encode_path(path: &str) -> String {
         .join("/")
 }
 
+/// Standard base64 with padding.
+pub fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// An EXIF date (`2024:04:02 06:12:45`) in ISO 8601 form (`2024-04-02T06:12:45`).
+///
+/// EXIF dates carry no time zone, so neither does the result. Values that
+/// aren't EXIF dates are returned unchanged.
+pub fn iso_datetime(value: &str) -> String {
+    match value.trim().split_once(' ') {
+        Some((date, time)) if date.len() == 10 && date.matches(':').count() == 2 => {
+            format!("{}T{}", date.replace(':', "-"), time)
+        }
+        _ => value.to_string(),
+    }
+}
+
+const WEEKDAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// Format seconds since the epoch as an RFC 822 date (`Tue, 02 Apr 2024 06:12:45 +0000`).
+///
+/// EXIF times carry no time zone, so they're written as if they were UTC.
+pub fn rfc822(seconds: i64) -> String {
+    let days = seconds.div_euclid(86400);
+    let time = seconds.rem_euclid(86400);
+    let (year, month, day) = civil_from_days(days);
+    let weekday = (days + 4).rem_euclid(7) as usize;
+
+    format!(
+        "{}, {:02} {} {} {:02}:{:02}:{:02} +0000",
+        WEEKDAYS[weekday],
+        day,
+        MONTHS[month as usize - 1],
+        year,
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+/// Format seconds since the epoch as an RFC 3339 date (`2024-04-02T06:12:45Z`).
+///
+/// Like [`rfc822`], EXIF times are written as if they were UTC.
+pub fn rfc3339(seconds: i64) -> String {
+    let time = seconds.rem_euclid(86400);
+    let (year, month, day) = civil_from_days(seconds.div_euclid(86400));
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+/// Proleptic Gregorian `(year, month, day)` for days since 1970-01-01.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Parse an EXIF date (`2024:04:02 06:12:45`) as seconds since the epoch.
+pub fn parse_exif_time(value: &str) -> Option<i64> {
+    let (date, time) = value.trim().split_once(' ')?;
+    timestamp(date.split(':'), time)
+}
+
+/// Parse an ISO 8601 UTC time (`2024-04-02T06:12:45Z`, optionally with
+/// fractional seconds or a `+hh:mm` offset) as seconds since the epoch.
+pub fn parse_iso_time(value: &str) -> Option<i64> {
+    let (date, rest) = value.trim().split_once('T')?;
+
+    let (time, offset) = match rest.find(['Z', '+', '-']) {
+        Some(i) => (&rest[..i], &rest[i..]),
+        None => (rest, "Z"),
+    };
+    let time = time.split('.').next()?;
+    let offset = match offset {
+        "Z" => 0,
+        _ => parse_offset(offset)?,
+    };
+
+    Some(timestamp(date.split('-'), time)? - offset)
+}
+
+/// Parse a `+hh:mm[:ss]` / `-hh:mm[:ss]` offset as seconds.
+pub fn parse_offset(value: &str) -> Option<i64> {
+    let value = value.trim();
+    let (sign, rest) = match value.as_bytes().first()? {
+        b'+' => (1, &value[1..]),
+        b'-' => (-1, &value[1..]),
+        _ => return None,
+    };
+
+    let parts: Vec<i64> = rest
+        .split(':')
+        .map(|p| p.parse().ok())
+        .collect::<Option<_>>()?;
+    let seconds = match parts.as_slice() {
+        [h, m] if *m < 60 => h * 3600 + m * 60,
+        [h, m, s] if *m < 60 && *s < 60 => h * 3600 + m * 60 + s,
+        _ => return None,
+    };
+
+    Some(sign * seconds)
+}
+
+/// Seconds since the epoch for a `[year, month, day]` date and `hh:mm:ss` time.
+fn timestamp<'a>(date: impl Iterator<Item = &'a str>, time: &str) -> Option<i64> {
+    let number = |part: &str| part.parse::<i64>().ok();
+
+    let date: Vec<i64> = date.map(number).collect::<Option<_>>()?;
+    let time: Vec<i64> = time.split(':').map(number).collect::<Option<_>>()?;
+    let (&[year, month, day], &[hour, minute, second]) = (date.as_slice(), time.as_slice()) else {
+        return None;
+    };
+
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    Some(days_from_civil(year, month, day) * 86400 + hour * 3600 + minute * 60 + second)
+}
+
+/// Days since 1970-01-01 for a proleptic Gregorian date.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// `value` as compact JSON with every object's keys sorted, so the same
+/// content always serializes, and hashes, the same way. Going through a
+/// `serde_json::Value` does the sorting: its objects are `BTreeMap`s, while
+/// `HashMap`s serialized directly come out in whatever order they iterate.
+pub fn canonical_json<T: serde::Serialize>(value: &T) -> serde_json::Result<String> {
+    serde_json::to_string(&serde_json::to_value(value)?)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use test_case::test_case;
 
     #[test]
     fn url_encode_special_chars() {
@@ -49,4 +227,92 @@ mod tests {
         );
         assert_eq!(url_encode_path("a/b/c"), "a/b/c");
     }
+
+    #[test_case(b"", "" ; "empty")]
+    #[test_case(b"f", "Zg==" ; "one byte")]
+    #[test_case(b"fo", "Zm8=" ; "two bytes")]
+    #[test_case(b"foo", "Zm9v" ; "three bytes")]
+    #[test_case(b"foobar", "Zm9vYmFy" ; "two chunks")]
+    fn test_base64(data: &[u8], expected: &str) {
+        assert_eq!(base64(data), expected);
+    }
+
+    #[test_case("2024:04:02 06:12:45", "2024-04-02T06:12:45" ; "exif")]
+    #[test_case("2024-04-02T06:12:45", "2024-04-02T06:12:45" ; "already iso")]
+    #[test_case("sometime", "sometime" ; "not a date")]
+    fn test_iso_datetime(value: &str, expected: &str) {
+        assert_eq!(iso_datetime(value), expected);
+    }
+
+    #[test_case(0, "Thu, 01 Jan 1970 00:00:00 +0000" ; "epoch")]
+    #[test_case(1712038365, "Tue, 02 Apr 2024 06:12:45 +0000" ; "recent")]
+    #[test_case(951782400, "Tue, 29 Feb 2000 00:00:00 +0000" ; "leap day")]
+    #[test_case(-86400, "Wed, 31 Dec 1969 00:00:00 +0000" ; "before epoch")]
+    fn test_rfc822(seconds: i64, expected: &str) {
+        assert_eq!(rfc822(seconds), expected);
+    }
+
+    #[test_case(0, "1970-01-01T00:00:00Z" ; "epoch")]
+    #[test_case(1712038365, "2024-04-02T06:12:45Z" ; "recent")]
+    fn test_rfc3339(seconds: i64, expected: &str) {
+        assert_eq!(rfc3339(seconds), expected);
+    }
+
+    #[test_case("2024:04:02 06:12:45", Some(1712038365) ; "exif")]
+    #[test_case(" 2024:04:02 06:12:45\n", Some(1712038365) ; "surrounding whitespace")]
+    #[test_case("2024-04-02T06:12:45", None ; "iso")]
+    fn test_parse_exif_time(value: &str, expected: Option<i64>) {
+        assert_eq!(parse_exif_time(value), expected);
+    }
+
+    #[test_case("2024-04-02T12:00:00Z", Some(1712059200) ; "utc")]
+    #[test_case("2024-04-02T12:00:00.250Z", Some(1712059200) ; "fractional")]
+    #[test_case("2024-04-02T14:00:00+02:00", Some(1712059200) ; "offset")]
+    #[test_case("1969-12-31T23:59:59Z", Some(-1) ; "before epoch")]
+    #[test_case("2024-13-02T12:00:00Z", None ; "invalid month")]
+    fn test_parse_iso_time(value: &str, expected: Option<i64>) {
+        assert_eq!(parse_iso_time(value), expected);
+    }
+
+    #[test_case("+02:00", Some(7200) ; "positive")]
+    #[test_case("-00:30", Some(-1800) ; "negative")]
+    #[test_case("+00:00:15", Some(15) ; "seconds")]
+    #[test_case("02:00", None ; "missing sign")]
+    #[test_case("+01:75", None ; "invalid minutes")]
+    fn test_parse_offset(value: &str, expected: Option<i64>) {
+        assert_eq!(parse_offset(value), expected);
+    }
+
+    #[test]
+    fn canonical_json_sorts_keys() {
+        #[derive(serde::Serialize)]
+        struct Photo {
+            width: u32,
+            stem: &'static str,
+            labels: std::collections::HashMap<&'static str, &'static str>,
+        }
+        let labels = [
+            ("zh", "照片"),
+            ("en", "Photo"),
+            ("de", "Foto"),
+            ("fr", "Photo"),
+        ];
+        let photo = Photo {
+            width: 600,
+            stem: "sunset",
+            labels: labels.into_iter().collect(),
+        };
+        let reversed = Photo {
+            labels: labels.into_iter().rev().collect(),
+            ..photo
+        };
+
+        let json = canonical_json(&[photo]).unwrap();
+
+        assert_eq!(
+            json,
+            r#"[{"labels":{"de":"Foto","en":"Photo","fr":"Photo","zh":"照片"},"stem":"sunset","width":600}]"#
+        );
+        assert_eq!(canonical_json(&[reversed]).unwrap(), json);
+    }
 }
@@ -0,0 +1,229 @@
+//! Short video clips, probed and framed with ffmpeg.
+//!
+//! Videos are published as they are, without transcoding, so clips should
+//! already be in a format browsers play (H.264 or HEVC in MP4 or MOV). The
+//! thumbnails and full-size images of a video are made from a poster frame
+//! ffmpeg picks as representative of its first seconds. Without ffprobe and
+//! ffmpeg on the PATH, videos are skipped with a warning.
+
+use std::path::Path;
+use std::process::Command;
+
+use serde::Deserialize;
+
+use crate::error::{Error, Result};
+
+/// What ffprobe reports about a clip.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Probe {
+    /// Displayed width, after rotation
+    pub width: u32,
+    /// Displayed height, after rotation
+    pub height: u32,
+    /// Recording time in EXIF format ("YYYY:MM:DD HH:MM:SS")
+    pub date_taken: Option<String>,
+}
+
+/// Dimensions and recording time of the video at `path`.
+pub fn probe(path: &Path) -> Result<Probe> {
+    let output = run(
+        "ffprobe",
+        Command::new("ffprobe")
+            .args(["-v", "error", "-select_streams", "v:0", "-show_entries"])
+            .arg("stream=width,height:stream_tags=rotate:stream_side_data=rotation:format_tags=creation_time")
+            .args(["-of", "json"])
+            .arg(path),
+    )?;
+    parse_probe(&output)
+}
+
+/// A representative frame from the start of the video at `path`, as PNG.
+pub fn poster(path: &Path) -> Result<Vec<u8>> {
+    run(
+        "ffmpeg",
+        Command::new("ffmpeg")
+            .args(["-v", "error", "-i"])
+            .arg(path)
+            .args(["-vf", "thumbnail", "-frames:v", "1"])
+            .args(["-f", "image2pipe", "-c:v", "png", "-"]),
+    )
+}
+
+/// Publish the video at `source` to `dest`, without its metadata when
+/// `strip_metadata` is set. Phones record the location in the container.
+pub fn publish(source: &Path, dest: &Path, strip_metadata: bool) -> Result<()> {
+    if !strip_metadata {
+        std::fs::copy(source, dest)?;
+        return Ok(());
+    }
+    run(
+        "ffmpeg",
+        Command::new("ffmpeg")
+            .args(["-v", "error", "-y", "-i"])
+            .arg(source)
+            .args(["-map", "0:v", "-map", "0:a?"])
+            .args(["-map_metadata", "-1", "-map_chapters", "-1"])
+            .args(["-c", "copy", "-movflags", "+faststart"])
+            .arg(dest),
+    )?;
+    Ok(())
+}
+
+/// Run `tool`, returning its output.
+fn run(tool: &str, command: &mut Command) -> Result<Vec<u8>> {
+    if which::which(tool).is_err() {
+        return Err(Error::Other(format!(
+            "{} not found (needed for videos)",
+            tool
+        )));
+    }
+    let output = command
+        .output()
+        .map_err(|e| Error::Other(format!("failed to run {}: {}", tool, e)))?;
+    if !output.status.success() {
+        return Err(Error::Other(format!(
+            "{} failed: {}",
+            tool,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(output.stdout)
+}
+
+#[derive(Deserialize)]
+struct ProbeOutput {
+    #[serde(default)]
+    streams: Vec<Stream>,
+    #[serde(default)]
+    format: Option<Format>,
+}
+
+#[derive(Deserialize)]
+struct Stream {
+    width: u32,
+    height: u32,
+    #[serde(default)]
+    tags: Tags,
+    #[serde(default)]
+    side_data_list: Vec<SideData>,
+}
+
+#[derive(Deserialize, Default)]
+struct Tags {
+    rotate: Option<String>,
+    creation_time: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SideData {
+    rotation: Option<i32>,
+}
+
+#[derive(Deserialize)]
+struct Format {
+    #[serde(default)]
+    tags: Tags,
+}
+
+/// Parse ffprobe's JSON output.
+fn parse_probe(output: &[u8]) -> Result<Probe> {
+    let output: ProbeOutput = serde_json::from_slice(output)
+        .map_err(|e| Error::Other(format!("unexpected ffprobe output: {}", e)))?;
+    let stream = output
+        .streams
+        .into_iter()
+        .next()
+        .ok_or_else(|| Error::Other("no video stream".to_string()))?;
+
+    // Phones record portrait clips sideways with a rotation, as an old-style
+    // tag or as display matrix side data
+    let rotation = stream
+        .side_data_list
+        .iter()
+        .find_map(|side_data| side_data.rotation)
+        .or_else(|| stream.tags.rotate.as_deref()?.parse().ok())
+        .unwrap_or(0);
+    let (width, height) = if rotation.rem_euclid(180) == 90 {
+        (stream.height, stream.width)
+    } else {
+        (stream.width, stream.height)
+    };
+
+    let date_taken = output
+        .format
+        .and_then(|format| format.tags.creation_time)
+        .and_then(|time| exif_datetime(&time));
+
+    Ok(Probe {
+        width,
+        height,
+        date_taken,
+    })
+}
+
+/// Convert an ISO 8601 time like "2024-06-02T07:15:00.000000Z" to EXIF's
+/// format. Cameras without a clock write 1970 or 1904, which are dropped.
+fn exif_datetime(time: &str) -> Option<String> {
+    let date = time.get(..10)?;
+    let clock = time.get(11..19)?;
+    let year: u32 = date.get(..4)?.parse().ok()?;
+    if year <= 1970 {
+        return None;
+    }
+    Some(format!("{} {}", date.replace('-', ":"), clock))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test]
+    fn probe_portrait_phone_clip() {
+        let output = br#"{
+            "programs": [],
+            "streams": [{
+                "width": 1920,
+                "height": 1080,
+                "side_data_list": [{"rotation": -90}]
+            }],
+            "format": {"tags": {"creation_time": "2024-06-02T07:15:00.000000Z"}}
+        }"#;
+
+        assert_eq!(
+            parse_probe(output).unwrap(),
+            Probe {
+                width: 1080,
+                height: 1920,
+                date_taken: Some("2024:06:02 07:15:00".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn probe_rotate_tag() {
+        let output = br#"{
+            "streams": [{"width": 1280, "height": 720, "tags": {"rotate": "270"}}],
+            "format": {}
+        }"#;
+
+        let probe = parse_probe(output).unwrap();
+
+        assert_eq!((probe.width, probe.height), (720, 1280));
+        assert_eq!(probe.date_taken, None);
+    }
+
+    #[test]
+    fn probe_without_video_stream() {
+        assert!(parse_probe(br#"{"streams": [], "format": {}}"#).is_err());
+    }
+
+    #[test_case("2024-06-02T07:15:00.000000Z", Some("2024:06:02 07:15:00") ; "utc")]
+    #[test_case("2023-12-31T23:59:59Z", Some("2023:12:31 23:59:59") ; "no fraction")]
+    #[test_case("1970-01-01T00:00:00.000000Z", None ; "unix epoch")]
+    #[test_case("1904-01-01T00:00:00.000000Z", None ; "quicktime epoch")]
+    #[test_case("2024", None ; "truncated")]
+    fn test_exif_datetime(time: &str, expected: Option<&str>) {
+        assert_eq!(exif_datetime(time).as_deref(), expected);
+    }
+}
@@ -2,58 +2,269 @@
 //!
 //! Watches the site directory and triggers rebuilds when photos are added,
 //! modified, or deleted. Includes debouncing to handle batch uploads and
-//! partial file transfers.
+//! partial file transfers. Under systemd, readiness, build status, and
+//! watchdog pings are reported (see `systemd`).
+//!
+//! Debouncing is per path: a rebuild starts once some changed files have
+//! been left alone for the debounce time, even if others are still being
+//! written, so a steady trickle of uploads doesn't hold off rebuilds forever.
+//! Files still changing are picked up by a later rebuild once they settle.
+//! Photos changed while nothing was watching, like during the initial
+//! build, are found by comparing them with the metadata cache on startup.
+//!
+//! Builds are numbered as generations, the initial build being the first.
+//! A file a build was started for changing again while it runs supersedes
+//! it: the build stops before it writes any pages, so the last generation
+//! that finished stays live, and a new build starts once the file settles.
+//! Variants the superseded build made are kept, so the next one picks up
+//! where it left off.
+//!
+//! A rebuild can also be requested over HTTP (see `serve::Trigger`); it
+//! starts at once, with every change waiting to settle.
+//!
+//! With `--json-events`, changes and builds are also written to stdout as
+//! JSON lines (see [`Event`]), for wrappers that react to builds.
 
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::{RecvTimeoutError, channel};
-use std::time::Duration;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use notify::{Config, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::Serialize;
 
-use crate::config::{Site, ThemeConfig};
-use crate::error::Result;
+use crate::cache::{self, MetadataCache};
+use crate::config::{Overrides, Reloader, Site};
+use crate::error::{Error, Result};
+use crate::gpx;
+use crate::photos;
 use crate::pipeline::Pipeline;
+use crate::processing::ProcessingStats;
+use crate::systemd;
+
+/// How often the event loop checks whether to build or stop.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// A change or build, written as a JSON line with `--json-events`.
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event<'a> {
+    /// Files changed; a build follows once they settle
+    Changed { paths: Vec<&'a Path> },
+    /// A build started, for `changed` settled files (none for the initial build)
+    BuildStarted { generation: u64, changed: usize },
+    /// A build finished and is now live
+    BuildSucceeded {
+        generation: u64,
+        seconds: f64,
+        photos: ProcessingStats,
+    },
+    /// A build was abandoned for newer changes; `live` is still served
+    BuildSuperseded { generation: u64, live: Option<u64> },
+    /// A build failed; `live` is still served
+    BuildFailed {
+        generation: u64,
+        live: Option<u64>,
+        error: String,
+        hint: Option<String>,
+    },
+}
+
+/// Where events go: stdout with `--json-events`, otherwise nowhere.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Events {
+    enabled: bool,
+}
+
+impl Events {
+    pub fn new(enabled: bool) -> Self {
+        Self { enabled }
+    }
+
+    fn emit(&self, event: &Event) {
+        if !self.enabled {
+            return;
+        }
+        // Each event is flushed at once, so wrappers see it as it happens
+        let mut out = std::io::stdout().lock();
+        let written = serde_json::to_writer(&mut out, event)
+            .map_err(std::io::Error::other)
+            .and_then(|()| writeln!(out))
+            .and_then(|()| out.flush());
+        if let Err(e) = written {
+            tracing::warn!(error = %e, "failed to write event");
+        }
+    }
+
+    /// Emit how the build of `generation` that took `elapsed` ended, with
+    /// the generation `live` after it.
+    fn finished(
+        &self,
+        generation: u64,
+        result: &Result<ProcessingStats>,
+        live: Option<u64>,
+        elapsed: Duration,
+    ) {
+        self.emit(&match result {
+            Ok(photos) => Event::BuildSucceeded {
+                generation,
+                seconds: elapsed.as_secs_f64(),
+                photos: *photos,
+            },
+            Err(Error::Cancelled) => Event::BuildSuperseded { generation, live },
+            Err(e) => Event::BuildFailed {
+                generation,
+                live,
+                error: e.to_string(),
+                hint: e.hint(),
+            },
+        });
+    }
+}
 
 /// Watch a site directory for changes and rebuild automatically.
 ///
 /// This function blocks forever, continuously watching for file changes
-/// and triggering rebuilds after a debounce period.
+/// and triggering rebuilds after a debounce period. With `once`, it returns
+/// after the first rebuild instead, failing if that rebuild failed.
 pub fn watch(
     site_dir: PathBuf,
     config_path: PathBuf,
-    theme_override: Option<String>,
+    overrides: Overrides,
     debounce_secs: u64,
+    once: bool,
+    events: Events,
 ) -> Result<()> {
+    let mut config = Reloader::new(config_path.clone());
+
     // Initial build
     tracing::info!("performing initial build");
-    if let Err(e) = do_build(&site_dir, &config_path, theme_override.as_deref()) {
-        tracing::error!(error = %e, "initial build failed");
+    events.emit(&Event::BuildStarted {
+        generation: 1,
+        changed: 0,
+    });
+    let started = Instant::now();
+    let result = do_build(&site_dir, &mut config, &overrides, &Arc::default());
+    if let Err(e) = &result {
+        tracing::error!(error = %e, hint = e.hint(), "initial build failed");
+    }
+    let live = result.is_ok().then_some(1);
+    events.finished(1, &result, live, started.elapsed());
+    systemd::ready(&status_line(&result, live));
+
+    if !once {
+        return watch_and_rebuild(
+            site_dir,
+            config,
+            overrides,
+            Duration::from_secs(debounce_secs),
+            live,
+            events,
+            Arc::default(),
+        );
     }
 
-    watch_and_rebuild(
-        site_dir,
-        config_path,
-        theme_override,
+    // A superseded rebuild doesn't count; the one after it does
+    let stop = AtomicBool::new(false);
+    let failed = AtomicBool::new(false);
+    let build = |_generation, cancel: &Arc<AtomicBool>| {
+        let result = do_build(&site_dir, &mut config, &overrides, cancel);
+        if !matches!(result, Err(Error::Cancelled)) {
+            failed.store(result.is_err(), Ordering::Relaxed);
+            stop.store(true, Ordering::Relaxed);
+        }
+        result
+    };
+    watch_until(
+        &site_dir,
+        &config_path,
         Duration::from_secs(debounce_secs),
-    )
+        live,
+        &stop,
+        &AtomicBool::new(false),
+        events,
+        build,
+    )?;
+    systemd::stopping();
+    if failed.load(Ordering::Relaxed) {
+        return Err(Error::Other("rebuild failed".to_string()));
+    }
+    Ok(())
+}
+
+/// Status line for systemd after a build, with the generation now `live`.
+pub fn status_line<T>(result: &Result<T>, live: Option<u64>) -> String {
+    match (result, live) {
+        (Ok(_), Some(live)) => format!("Built generation {}, watching for changes", live),
+        (Ok(_), None) => "Built, watching for changes".to_string(),
+        (Err(e), Some(live)) => format!(
+            "Build failed, generation {} still live, watching for changes: {}",
+            live, e
+        ),
+        (Err(e), None) => format!("Build failed, watching for changes: {}", e),
+    }
 }
 
 /// Watch for file changes and rebuild automatically.
 ///
 /// This function blocks forever. Unlike `watch()`, it does not perform an
 /// initial build - use this when you've already built the site and just
-/// want to watch for changes.
+/// want to watch for changes. `live` is the generation of that build, if
+/// it succeeded. Setting `rebuild` starts a rebuild at once.
 pub fn watch_and_rebuild(
     site_dir: PathBuf,
-    config_path: PathBuf,
-    theme_override: Option<String>,
+    mut config: Reloader,
+    overrides: Overrides,
     debounce: Duration,
+    live: Option<u64>,
+    events: Events,
+    rebuild: Arc<AtomicBool>,
 ) -> Result<()> {
+    let config_path = config.path().to_path_buf();
+    let build = |_generation, cancel: &Arc<AtomicBool>| {
+        do_build(&site_dir, &mut config, &overrides, cancel)
+    };
+    watch_until(
+        &site_dir,
+        &config_path,
+        debounce,
+        live,
+        &AtomicBool::new(false),
+        &rebuild,
+        events,
+        build,
+    )
+}
+
+/// Watch for file changes and call `build` after each burst of changes,
+/// until `stop` is set.
+///
+/// `build` is given the generation it builds and a flag that's set when a
+/// file it was started for changes while it runs, which should make it
+/// return [`Error::Cancelled`]. `live` is the generation already built, if
+/// any. Setting `rebuild` starts a build at once, which clears it. Changes
+/// and builds are reported to `events`.
+#[allow(clippy::too_many_arguments)]
+pub fn watch_until<F>(
+    site_dir: &Path,
+    config_path: &Path,
+    debounce: Duration,
+    mut live: Option<u64>,
+    stop: &AtomicBool,
+    rebuild: &AtomicBool,
+    events: Events,
+    mut build: F,
+) -> Result<()>
+where
+    F: FnMut(u64, &Arc<AtomicBool>) -> Result<ProcessingStats>,
+{
     let debounce_secs = debounce.as_secs();
 
     // Load config to determine what paths to watch
-    let config_content = std::fs::read_to_string(&config_path)?;
-    let site: Site = toml::from_str(&config_content)?;
+    let site = Site::load(config_path)?;
 
     let photos_dir = site_dir.join(&site.photos);
     // Canonicalize output_dir so it matches absolute paths from notify events
@@ -68,13 +279,26 @@ pub fn watch_and_rebuild(
         if dir.is_dir() { Some(dir) } else { None }
     };
 
-    // Set up file watcher
+    // Set up file watcher; changes to the files being built supersede the build
     let (tx, rx) = channel();
+    let superseded = Arc::new(AtomicBool::new(false));
+    let building: Arc<Mutex<HashSet<PathBuf>>> = Arc::default();
 
     let mut watcher = RecommendedWatcher::new(
-        move |res| {
-            if let Ok(event) = res {
-                let _ = tx.send(event);
+        {
+            let superseded = Arc::clone(&superseded);
+            let building = Arc::clone(&building);
+            let output_dir = output_dir.clone();
+            move |res: notify::Result<notify::Event>| {
+                if let Ok(event) = res {
+                    let building = building.lock().unwrap();
+                    if !should_ignore_event(&event, &output_dir)
+                        && event.paths.iter().any(|path| building.contains(path))
+                    {
+                        superseded.store(true, Ordering::Relaxed);
+                    }
+                    let _ = tx.send(event);
+                }
             }
         },
         Config::default(),
@@ -86,7 +310,13 @@ pub fn watch_and_rebuild(
 
     // Watch site.toml
     tracing::info!(path = %config_path.display(), "watching config file");
-    watcher.watch(&config_path, RecursiveMode::NonRecursive)?;
+    watcher.watch(config_path, RecursiveMode::NonRecursive)?;
+
+    // Watch GPX tracks used for geotagging
+    for path in gpx::files(site_dir, &site.gpx) {
+        tracing::info!(path = %path.display(), "watching GPX track");
+        watcher.watch(&path, RecursiveMode::NonRecursive)?;
+    }
 
     // Watch local theme if present
     if let Some(ref dir) = theme_dir {
@@ -103,11 +333,32 @@ pub fn watch_and_rebuild(
         "output directory for filtering"
     );
 
-    // Event loop with debouncing
-    let mut needs_rebuild = false;
+    // Event loop with debouncing; each path's last event starts its wait over
+    let mut pending: HashMap<PathBuf, Instant> = HashMap::new();
+    // The initial build was the first generation
+    let mut generation = 1;
+
+    // Photos changed while nothing was watching, such as during the build
+    // before this, are found by comparing them with the cache it left
+    if live.is_some() {
+        let changed = changed_since_build(site_dir, &photos_dir);
+        if !changed.is_empty() {
+            tracing::info!(
+                changes = changed.len(),
+                "photos changed since the last build, waiting {}s for more changes...",
+                debounce_secs
+            );
+            events.emit(&Event::Changed {
+                paths: changed.iter().map(PathBuf::as_path).collect(),
+            });
+            let now = Instant::now();
+            pending.extend(changed.into_iter().map(|path| (path, now)));
+        }
+    }
 
-    loop {
-        match rx.recv_timeout(debounce) {
+    while !stop.load(Ordering::Relaxed) {
+        systemd::watchdog();
+        match rx.recv_timeout(POLL_INTERVAL.min(debounce)) {
             Ok(event) => {
                 let _span = tracing::debug_span!(
                     "file_event",
@@ -126,58 +377,136 @@ pub fn watch_and_rebuild(
 
                 tracing::debug!("event passed filters");
 
-                if !needs_rebuild {
+                if pending.is_empty() {
                     tracing::info!(
                         "change detected, waiting {}s for more changes...",
                         debounce_secs
                     );
                 }
-                needs_rebuild = true;
-                // Continue loop to reset timeout
-            }
-            Err(RecvTimeoutError::Timeout) => {
-                if needs_rebuild {
-                    tracing::info!("rebuilding site...");
-
-                    match do_build(&site_dir, &config_path, theme_override.as_deref()) {
-                        Ok(()) => tracing::info!("build complete"),
-                        Err(e) => tracing::error!(error = %e, "build failed"),
-                    }
-
-                    needs_rebuild = false;
+                let now = Instant::now();
+                // Events without paths (e.g. the watcher asking for a rescan) affect the whole site
+                let paths = if event.paths.is_empty() {
+                    vec![site_dir.to_path_buf()]
+                } else {
+                    event.paths
+                };
+                events.emit(&Event::Changed {
+                    paths: paths.iter().map(PathBuf::as_path).collect(),
+                });
+                for path in paths {
+                    pending.insert(path, now);
                 }
-                // Continue watching
             }
+            Err(RecvTimeoutError::Timeout) => {}
             Err(RecvTimeoutError::Disconnected) => {
                 tracing::warn!("watcher disconnected, stopping");
                 break;
             }
         }
+
+        // A requested rebuild doesn't wait for anything to settle
+        let changed = if rebuild.swap(false, Ordering::Relaxed) {
+            tracing::info!(
+                still_changing = pending.len(),
+                "rebuild requested, building now"
+            );
+            pending.insert(site_dir.to_path_buf(), Instant::now());
+            pending.drain().map(|(path, _)| path).collect()
+        } else {
+            settled(&mut pending, debounce, Instant::now())
+        };
+        if !changed.is_empty() {
+            generation += 1;
+            tracing::info!(
+                generation,
+                changed = changed.len(),
+                still_changing = pending.len(),
+                "rebuilding site..."
+            );
+
+            // Changes up to now are part of this build
+            events.emit(&Event::BuildStarted {
+                generation,
+                changed: changed.len(),
+            });
+            *building.lock().unwrap() = changed;
+            superseded.store(false, Ordering::Relaxed);
+            systemd::status(&format!("Rebuilding generation {}", generation));
+            let started = Instant::now();
+            // Builds block the loop, so the watchdog is pinged from elsewhere meanwhile
+            let result = systemd::keep_alive(|| build(generation, &superseded));
+            building.lock().unwrap().clear();
+            match &result {
+                Ok(_) => {
+                    live = Some(generation);
+                    tracing::info!(generation, "build complete, now live");
+                }
+                Err(Error::Cancelled) => {
+                    tracing::info!(generation, live, "build superseded by new changes");
+                }
+                Err(e) => {
+                    tracing::error!(generation, live, error = %e, hint = e.hint(), "build failed")
+                }
+            }
+            events.finished(generation, &result, live, started.elapsed());
+            if !matches!(result, Err(Error::Cancelled)) {
+                systemd::status(&status_line(&result, live));
+            }
+        }
     }
 
     Ok(())
 }
 
-/// Perform a single build of the site.
-pub fn do_build(site_dir: &Path, config_path: &Path, theme_override: Option<&str>) -> Result<()> {
-    do_build_with_options(site_dir, config_path, theme_override, false)
+/// Photos in `photos_dir` added, changed, or removed since the last build.
+fn changed_since_build(site_dir: &Path, photos_dir: &Path) -> Vec<PathBuf> {
+    let photos: Vec<PathBuf> = match photos::discover(photos_dir) {
+        Ok(root) => root
+            .all_photos()
+            .into_iter()
+            .map(|photo| photo.source.clone())
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    MetadataCache::load(cache::path(site_dir)).changed_sources(&photos)
+}
+
+/// Remove and return the paths in `pending` that haven't changed for `debounce`.
+fn settled(
+    pending: &mut HashMap<PathBuf, Instant>,
+    debounce: Duration,
+    now: Instant,
+) -> HashSet<PathBuf> {
+    let settled: HashSet<PathBuf> = pending
+        .iter()
+        .filter(|(_, changed)| now.duration_since(**changed) >= debounce)
+        .map(|(path, _)| path.clone())
+        .collect();
+    pending.retain(|path, _| !settled.contains(path));
+    settled
+}
+
+/// Perform a single build of the site, abandoned if `cancel` is set.
+pub fn do_build(
+    site_dir: &Path,
+    config: &mut Reloader,
+    overrides: &Overrides,
+    cancel: &Arc<AtomicBool>,
+) -> Result<ProcessingStats> {
+    do_build_with_options(site_dir, config, overrides, false, cancel)
 }
 
 /// Perform a single build of the site with options.
 pub fn do_build_with_options(
     site_dir: &Path,
-    config_path: &Path,
-    theme_override: Option<&str>,
+    config: &mut Reloader,
+    overrides: &Overrides,
     source_maps: bool,
-) -> Result<()> {
+    cancel: &Arc<AtomicBool>,
+) -> Result<ProcessingStats> {
     // Reload config each time in case it changed
-    let config_content = std::fs::read_to_string(config_path)?;
-    let mut site: Site = toml::from_str(&config_content)?;
-
-    // Apply theme override if specified
-    if let Some(theme_name) = theme_override {
-        site.theme = ThemeConfig::Name(theme_name.to_string());
-    }
+    let mut site = config.load()?;
+    overrides.apply(&mut site);
 
     // Disable minification when source maps are enabled (easier debugging)
     if source_maps && site.minify {
@@ -185,9 +514,12 @@ pub fn do_build_with_options(
     }
 
     let mut pipeline = Pipeline::load(site_dir.to_path_buf(), site, source_maps)?;
+    pipeline.cancel = Arc::clone(cancel);
+    // Photos are edited while the site is watched
+    pipeline.map_originals = false;
     pipeline.build()?;
 
-    Ok(())
+    Ok(pipeline.stats)
 }
 
 /// Check if an event should be ignored.
@@ -234,6 +566,81 @@ pub fn should_ignore_event(event: &notify::Event, output_dir: &Path) -> bool {
 mod tests {
     use super::*;
     use std::path::PathBuf;
+    use test_case::test_case;
+
+    #[test]
+    fn events_serialized_as_tagged_lines() {
+        let photos = ProcessingStats {
+            total: 3,
+            generated: 1,
+            cached: 2,
+            ..Default::default()
+        };
+        let succeeded = Event::BuildSucceeded {
+            generation: 2,
+            seconds: 1.5,
+            photos,
+        };
+        let failed = Event::BuildFailed {
+            generation: 3,
+            live: Some(2),
+            error: "no photos".to_string(),
+            hint: None,
+        };
+
+        assert_eq!(
+            serde_json::to_string(&succeeded).unwrap(),
+            r#"{"event":"build_succeeded","generation":2,"seconds":1.5,"photos":{"total":3,"cached":2,"generated":1,"copied":0,"skipped":0}}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&failed).unwrap(),
+            r#"{"event":"build_failed","generation":3,"live":2,"error":"no photos","hint":null}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&Event::Changed {
+                paths: vec![Path::new("photos/a.jpg")]
+            })
+            .unwrap(),
+            r#"{"event":"changed","paths":["photos/a.jpg"]}"#
+        );
+    }
+
+    #[test]
+    fn settled_paths_built_while_others_change() {
+        let start = Instant::now();
+        let debounce = Duration::from_secs(5);
+        let mut pending = HashMap::from([
+            (PathBuf::from("/site/photos/a.jpg"), start),
+            (
+                PathBuf::from("/site/photos/b.jpg"),
+                start + Duration::from_secs(4),
+            ),
+        ]);
+
+        assert!(settled(&mut pending, debounce, start + Duration::from_secs(3)).is_empty());
+        assert_eq!(
+            settled(&mut pending, debounce, start + Duration::from_secs(6)),
+            HashSet::from([PathBuf::from("/site/photos/a.jpg")])
+        );
+        assert_eq!(pending.len(), 1);
+        assert_eq!(
+            settled(&mut pending, debounce, start + Duration::from_secs(9)),
+            HashSet::from([PathBuf::from("/site/photos/b.jpg")])
+        );
+        assert!(pending.is_empty());
+    }
+
+    #[test_case(true, Some(3), "Built generation 3, watching for changes" ; "built")]
+    #[test_case(false, Some(3), "Build failed, generation 3 still live, watching for changes: no photos" ; "failed")]
+    #[test_case(false, None, "Build failed, watching for changes: no photos" ; "nothing live")]
+    fn test_status_line(ok: bool, live: Option<u64>, expected: &str) {
+        let result = match ok {
+            true => Ok(()),
+            false => Err(Error::Other("no photos".to_string())),
+        };
+
+        assert_eq!(status_line(&result, live), expected);
+    }
 
     #[test]
     fn test_should_ignore_hidden_files() {
@@ -0,0 +1,393 @@
+//! Watermarks composited onto published images.
+//!
+//! `[watermark]` marks the thumbnails, full-size images, responsive widths,
+//! and cover crops generated for each photo, while originals are published
+//! untouched. The mark is an image from the site directory or a line of
+//! text. Text is drawn in a built-in 5×7 pixel font, scaled by whole pixels
+//! so it stays crisp, in white with a dark shadow to show on any photo. The
+//! micro thumbnail and placeholder stay unmarked, since a mark that small
+//! would only be a smudge.
+
+use std::path::Path;
+
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, GenericImageView, Rgba, RgbaImage};
+
+use crate::config::{WatermarkConfig, WatermarkPosition};
+use crate::error::{Error, Result};
+
+/// Glyph width and height in font pixels.
+const GLYPH_WIDTH: u32 = 5;
+const GLYPH_HEIGHT: u32 = 7;
+
+/// Printable ASCII from space to tilde, one byte per column with the top row
+/// in the lowest bit.
+const FONT: [[u8; 5]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x00, 0x00, 0x5F, 0x00, 0x00], // '!'
+    [0x00, 0x07, 0x00, 0x07, 0x00], // '"'
+    [0x14, 0x7F, 0x14, 0x7F, 0x14], // '#'
+    [0x24, 0x2A, 0x7F, 0x2A, 0x12], // '$'
+    [0x23, 0x13, 0x08, 0x64, 0x62], // '%'
+    [0x36, 0x49, 0x55, 0x22, 0x50], // '&'
+    [0x00, 0x05, 0x03, 0x00, 0x00], // '\''
+    [0x00, 0x1C, 0x22, 0x41, 0x00], // '('
+    [0x00, 0x41, 0x22, 0x1C, 0x00], // ')'
+    [0x14, 0x08, 0x3E, 0x08, 0x14], // '*'
+    [0x08, 0x08, 0x3E, 0x08, 0x08], // '+'
+    [0x00, 0x50, 0x30, 0x00, 0x00], // ','
+    [0x08, 0x08, 0x08, 0x08, 0x08], // '-'
+    [0x00, 0x60, 0x60, 0x00, 0x00], // '.'
+    [0x20, 0x10, 0x08, 0x04, 0x02], // '/'
+    [0x3E, 0x51, 0x49, 0x45, 0x3E], // '0'
+    [0x00, 0x42, 0x7F, 0x40, 0x00], // '1'
+    [0x42, 0x61, 0x51, 0x49, 0x46], // '2'
+    [0x21, 0x41, 0x45, 0x4B, 0x31], // '3'
+    [0x18, 0x14, 0x12, 0x7F, 0x10], // '4'
+    [0x27, 0x45, 0x45, 0x45, 0x39], // '5'
+    [0x3C, 0x4A, 0x49, 0x49, 0x30], // '6'
+    [0x01, 0x71, 0x09, 0x05, 0x03], // '7'
+    [0x36, 0x49, 0x49, 0x49, 0x36], // '8'
+    [0x06, 0x49, 0x49, 0x29, 0x1E], // '9'
+    [0x00, 0x36, 0x36, 0x00, 0x00], // ':'
+    [0x00, 0x56, 0x36, 0x00, 0x00], // ';'
+    [0x08, 0x14, 0x22, 0x41, 0x00], // '<'
+    [0x14, 0x14, 0x14, 0x14, 0x14], // '='
+    [0x00, 0x41, 0x22, 0x14, 0x08], // '>'
+    [0x02, 0x01, 0x51, 0x09, 0x06], // '?'
+    [0x32, 0x49, 0x79, 0x41, 0x3E], // '@'
+    [0x7E, 0x11, 0x11, 0x11, 0x7E], // 'A'
+    [0x7F, 0x49, 0x49, 0x49, 0x36], // 'B'
+    [0x3E, 0x41, 0x41, 0x41, 0x22], // 'C'
+    [0x7F, 0x41, 0x41, 0x22, 0x1C], // 'D'
+    [0x7F, 0x49, 0x49, 0x49, 0x41], // 'E'
+    [0x7F, 0x09, 0x09, 0x09, 0x01], // 'F'
+    [0x3E, 0x41, 0x49, 0x49, 0x7A], // 'G'
+    [0x7F, 0x08, 0x08, 0x08, 0x7F], // 'H'
+    [0x00, 0x41, 0x7F, 0x41, 0x00], // 'I'
+    [0x20, 0x40, 0x41, 0x3F, 0x01], // 'J'
+    [0x7F, 0x08, 0x14, 0x22, 0x41], // 'K'
+    [0x7F, 0x40, 0x40, 0x40, 0x40], // 'L'
+    [0x7F, 0x02, 0x0C, 0x02, 0x7F], // 'M'
+    [0x7F, 0x04, 0x08, 0x10, 0x7F], // 'N'
+    [0x3E, 0x41, 0x41, 0x41, 0x3E], // 'O'
+    [0x7F, 0x09, 0x09, 0x09, 0x06], // 'P'
+    [0x3E, 0x41, 0x51, 0x21, 0x5E], // 'Q'
+    [0x7F, 0x09, 0x19, 0x29, 0x46], // 'R'
+    [0x46, 0x49, 0x49, 0x49, 0x31], // 'S'
+    [0x01, 0x01, 0x7F, 0x01, 0x01], // 'T'
+    [0x3F, 0x40, 0x40, 0x40, 0x3F], // 'U'
+    [0x1F, 0x20, 0x40, 0x20, 0x1F], // 'V'
+    [0x3F, 0x40, 0x38, 0x40, 0x3F], // 'W'
+    [0x63, 0x14, 0x08, 0x14, 0x63], // 'X'
+    [0x07, 0x08, 0x70, 0x08, 0x07], // 'Y'
+    [0x61, 0x51, 0x49, 0x45, 0x43], // 'Z'
+    [0x00, 0x7F, 0x41, 0x41, 0x00], // '['
+    [0x02, 0x04, 0x08, 0x10, 0x20], // '\\'
+    [0x00, 0x41, 0x41, 0x7F, 0x00], // ']'
+    [0x04, 0x02, 0x01, 0x02, 0x04], // '^'
+    [0x40, 0x40, 0x40, 0x40, 0x40], // '_'
+    [0x00, 0x01, 0x02, 0x04, 0x00], // '`'
+    [0x20, 0x54, 0x54, 0x54, 0x78], // 'a'
+    [0x7F, 0x48, 0x44, 0x44, 0x38], // 'b'
+    [0x38, 0x44, 0x44, 0x44, 0x20], // 'c'
+    [0x38, 0x44, 0x44, 0x48, 0x7F], // 'd'
+    [0x38, 0x54, 0x54, 0x54, 0x18], // 'e'
+    [0x08, 0x7E, 0x09, 0x01, 0x02], // 'f'
+    [0x0C, 0x52, 0x52, 0x52, 0x3E], // 'g'
+    [0x7F, 0x08, 0x04, 0x04, 0x78], // 'h'
+    [0x00, 0x44, 0x7D, 0x40, 0x00], // 'i'
+    [0x20, 0x40, 0x44, 0x3D, 0x00], // 'j'
+    [0x7F, 0x10, 0x28, 0x44, 0x00], // 'k'
+    [0x00, 0x41, 0x7F, 0x40, 0x00], // 'l'
+    [0x7C, 0x04, 0x18, 0x04, 0x78], // 'm'
+    [0x7C, 0x08, 0x04, 0x04, 0x78], // 'n'
+    [0x38, 0x44, 0x44, 0x44, 0x38], // 'o'
+    [0x7C, 0x14, 0x14, 0x14, 0x08], // 'p'
+    [0x08, 0x14, 0x14, 0x18, 0x7C], // 'q'
+    [0x7C, 0x08, 0x04, 0x04, 0x08], // 'r'
+    [0x48, 0x54, 0x54, 0x54, 0x20], // 's'
+    [0x04, 0x3F, 0x44, 0x40, 0x20], // 't'
+    [0x3C, 0x40, 0x40, 0x20, 0x7C], // 'u'
+    [0x1C, 0x20, 0x40, 0x20, 0x1C], // 'v'
+    [0x3C, 0x40, 0x30, 0x40, 0x3C], // 'w'
+    [0x44, 0x28, 0x10, 0x28, 0x44], // 'x'
+    [0x0C, 0x50, 0x50, 0x50, 0x3C], // 'y'
+    [0x44, 0x64, 0x54, 0x4C, 0x44], // 'z'
+    [0x00, 0x08, 0x36, 0x41, 0x00], // '{'
+    [0x00, 0x00, 0x7F, 0x00, 0x00], // '|'
+    [0x00, 0x41, 0x36, 0x08, 0x00], // '}'
+    [0x02, 0x01, 0x02, 0x04, 0x02], // '~'
+];
+
+/// '©', the one character beyond ASCII that watermarks tend to need.
+const COPYRIGHT: [u8; 5] = [0x3E, 0x5D, 0x55, 0x55, 0x3E];
+
+/// A loaded watermark, ready to apply.
+#[derive(Debug)]
+pub struct Watermark {
+    mark: Mark,
+    position: WatermarkPosition,
+    opacity: f32,
+    scale: f32,
+    id: String,
+}
+
+#[derive(Debug)]
+enum Mark {
+    Image(RgbaImage),
+    Text(String),
+}
+
+impl Watermark {
+    /// Check the config and load its image, relative to `site_dir`.
+    pub fn load(config: &WatermarkConfig, site_dir: &Path) -> Result<Self> {
+        if !(config.opacity > 0.0 && config.opacity <= 1.0) {
+            return Err(Error::Other(format!(
+                "[watermark] opacity must be above 0 and at most 1, got {}",
+                config.opacity
+            )));
+        }
+        if !(config.scale > 0.0 && config.scale <= 1.0) {
+            return Err(Error::Other(format!(
+                "[watermark] scale must be above 0 and at most 1, got {}",
+                config.scale
+            )));
+        }
+
+        let mut hasher = blake3::Hasher::new();
+        let mark = match (&config.text, &config.image) {
+            (Some(text), None) if !text.trim().is_empty() => {
+                hasher.update(b"text\0");
+                hasher.update(text.as_bytes());
+                Mark::Text(text.clone())
+            }
+            (None, Some(image)) => {
+                let path = site_dir.join(image);
+                let data = std::fs::read(&path).map_err(|e| {
+                    Error::Other(format!(
+                        "failed to read [watermark] image {}: {}",
+                        path.display(),
+                        e
+                    ))
+                })?;
+                hasher.update(b"image\0");
+                hasher.update(&data);
+                Mark::Image(image::load_from_memory(&data)?.to_rgba8())
+            }
+            _ => {
+                return Err(Error::Other(
+                    "[watermark] needs either text or image".to_string(),
+                ));
+            }
+        };
+        hasher.update(format!("{:?}", config.position).as_bytes());
+        hasher.update(&config.opacity.to_le_bytes());
+        hasher.update(&config.scale.to_le_bytes());
+
+        Ok(Self {
+            mark,
+            position: config.position,
+            opacity: config.opacity,
+            scale: config.scale,
+            id: hasher.finalize().to_hex()[..8].to_string(),
+        })
+    }
+
+    /// Short hash of the mark and its settings, put in the names of marked
+    /// images so changing the watermark generates new ones.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// A copy of `img` with the watermark composited on.
+    pub fn apply(&self, img: &DynamicImage) -> DynamicImage {
+        let (width, height) = img.dimensions();
+        let target_width = ((width as f32 * self.scale).round() as u32).max(1);
+        let mut mark = match &self.mark {
+            Mark::Image(mark) => {
+                let target_height = (u64::from(mark.height()) * u64::from(target_width)
+                    / u64::from(mark.width().max(1))) as u32;
+                imageops::resize(
+                    mark,
+                    target_width,
+                    target_height.max(1),
+                    FilterType::Lanczos3,
+                )
+            }
+            Mark::Text(text) => {
+                let units = text_width(text);
+                render_text(text, (target_width / units).max(1))
+            }
+        };
+        for pixel in mark.pixels_mut() {
+            pixel.0[3] = (f32::from(pixel.0[3]) * self.opacity).round() as u8;
+        }
+
+        let margin = i64::from(width.min(height) / 32);
+        let (x, y) = placement(
+            self.position,
+            (width, height),
+            (mark.width(), mark.height()),
+            margin,
+        );
+        let mut marked = img.to_rgba8();
+        imageops::overlay(&mut marked, &mark, x, y);
+        DynamicImage::ImageRgba8(marked)
+    }
+}
+
+/// Top left corner of a `mark`-sized watermark in an `image`-sized photo.
+fn placement(
+    position: WatermarkPosition,
+    image: (u32, u32),
+    mark: (u32, u32),
+    margin: i64,
+) -> (i64, i64) {
+    let right = i64::from(image.0) - i64::from(mark.0) - margin;
+    let bottom = i64::from(image.1) - i64::from(mark.1) - margin;
+    match position {
+        WatermarkPosition::TopLeft => (margin, margin),
+        WatermarkPosition::TopRight => (right, margin),
+        WatermarkPosition::BottomLeft => (margin, bottom),
+        WatermarkPosition::BottomRight => (right, bottom),
+        WatermarkPosition::Center => (
+            (i64::from(image.0) - i64::from(mark.0)) / 2,
+            (i64::from(image.1) - i64::from(mark.1)) / 2,
+        ),
+    }
+}
+
+/// Glyph columns of a character, with '?' for ones the font lacks.
+fn glyph(c: char) -> [u8; 5] {
+    match c {
+        '©' => COPYRIGHT,
+        ' '..='~' => FONT[c as usize - ' ' as usize],
+        _ => FONT['?' as usize - ' ' as usize],
+    }
+}
+
+/// Width of rendered text in font pixels. Glyphs are a pixel apart, and the
+/// last gap leaves room for the shadow.
+fn text_width(text: &str) -> u32 {
+    let glyphs = text.chars().count() as u32;
+    glyphs * (GLYPH_WIDTH + 1)
+}
+
+/// Draw `text` in white with a dark shadow, each font pixel `pixel` pixels wide.
+fn render_text(text: &str, pixel: u32) -> RgbaImage {
+    let mut img = RgbaImage::new(text_width(text) * pixel, (GLYPH_HEIGHT + 1) * pixel);
+    // Shadow first, one font pixel down and right, then the text over it
+    for (offset, color) in [(1, Rgba([0, 0, 0, 160])), (0, Rgba([255, 255, 255, 255]))] {
+        for (i, c) in text.chars().enumerate() {
+            for (column, bits) in glyph(c).into_iter().enumerate() {
+                for row in 0..GLYPH_HEIGHT {
+                    if bits & (1 << row) == 0 {
+                        continue;
+                    }
+                    let x = (i as u32 * (GLYPH_WIDTH + 1) + column as u32 + offset) * pixel;
+                    let y = (row + offset) * pixel;
+                    for dy in 0..pixel {
+                        for dx in 0..pixel {
+                            img.put_pixel(x + dx, y + dy, color);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    img
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    fn config(text: Option<&str>, image: Option<&str>) -> WatermarkConfig {
+        WatermarkConfig {
+            text: text.map(str::to_string),
+            image: image.map(Into::into),
+            position: WatermarkPosition::BottomRight,
+            opacity: 1.0,
+            scale: 0.5,
+        }
+    }
+
+    #[test]
+    fn text_marks_corner() {
+        let watermark = Watermark::load(&config(Some("© AB"), None), Path::new(".")).unwrap();
+        let img = DynamicImage::new_rgb8(400, 200);
+
+        let marked = watermark.apply(&img).to_rgba8();
+
+        // 4 glyphs of 6 font pixels fit 200px at 8px per font pixel
+        let white = |x0: u32, y0: u32, x1: u32, y1: u32| {
+            (y0..y1).any(|y| (x0..x1).any(|x| marked.get_pixel(x, y).0[..3] == [255, 255, 255]))
+        };
+        assert!(white(200, 100, 400, 200));
+        assert!(!white(0, 0, 200, 100));
+        assert!(!white(0, 0, 400, 130));
+    }
+
+    #[test]
+    fn image_mark_scaled_with_opacity() {
+        let dir = tempfile::tempdir().unwrap();
+        RgbaImage::from_pixel(10, 5, Rgba([255, 0, 0, 255]))
+            .save(dir.path().join("mark.png"))
+            .unwrap();
+        let watermark = Watermark::load(
+            &WatermarkConfig {
+                opacity: 0.5,
+                position: WatermarkPosition::TopLeft,
+                ..config(None, Some("mark.png"))
+            },
+            dir.path(),
+        )
+        .unwrap();
+
+        let marked = watermark
+            .apply(&DynamicImage::new_rgb8(320, 320))
+            .to_rgba8();
+
+        // 160x80 mark, 10px in from the corner, half red over black
+        let pixel = marked.get_pixel(20, 20).0;
+        assert!((120..=135).contains(&pixel[0]) && pixel[1] == 0);
+        assert_eq!(marked.get_pixel(5, 5).0, [0, 0, 0, 255]);
+        assert_eq!(marked.get_pixel(175, 20).0, [0, 0, 0, 255]);
+    }
+
+    #[test_case(None, None ; "neither")]
+    #[test_case(Some("A"), Some("mark.png") ; "both")]
+    #[test_case(Some(" "), None ; "blank text")]
+    fn needs_text_or_image(text: Option<&str>, image: Option<&str>) {
+        assert!(Watermark::load(&config(text, image), Path::new(".")).is_err());
+    }
+
+    #[test]
+    fn opacity_checked() {
+        let config = WatermarkConfig {
+            opacity: 1.5,
+            ..config(Some("A"), None)
+        };
+
+        assert!(Watermark::load(&config, Path::new(".")).is_err());
+    }
+
+    #[test]
+    fn id_changes_with_settings() {
+        let a = Watermark::load(&config(Some("A"), None), Path::new(".")).unwrap();
+        let b = Watermark::load(&config(Some("B"), None), Path::new(".")).unwrap();
+        let a_again = Watermark::load(&config(Some("A"), None), Path::new(".")).unwrap();
+
+        assert_ne!(a.id(), b.id());
+        assert_eq!(a.id(), a_again.id());
+    }
+
+    #[test_case(WatermarkPosition::TopLeft, (10, 10))]
+    #[test_case(WatermarkPosition::BottomRight, (290, 140))]
+    #[test_case(WatermarkPosition::Center, (150, 75))]
+    fn test_placement(position: WatermarkPosition, expected: (i64, i64)) {
+        assert_eq!(placement(position, (400, 200), (100, 50), 10), expected);
+    }
+}
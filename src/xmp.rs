@@ -0,0 +1,616 @@
+//! XMP metadata from embedded packets and sidecar files.
+//!
+//! RAW workflows (Lightroom, darktable, Capture One) keep ratings, titles,
+//! keywords, and often GPS in `.xmp` sidecars next to the image instead of
+//! embedding them. Both sources are parsed the same way; sidecar values win
+//! since they reflect the latest edits.
+
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::name::ResolveResult;
+use quick_xml::reader::NsReader;
+use serde::{Deserialize, Serialize};
+
+const NS_RDF: &[u8] = b"http://www.w3.org/1999/02/22-rdf-syntax-ns#";
+const NS_XMP: &[u8] = b"http://ns.adobe.com/xap/1.0/";
+const NS_DC: &[u8] = b"http://purl.org/dc/elements/1.1/";
+const NS_EXIF: &[u8] = b"http://ns.adobe.com/exif/1.0/";
+const NS_PHOTOSHOP: &[u8] = b"http://ns.adobe.com/photoshop/1.0/";
+const NS_DIGIKAM: &[u8] = b"http://www.digikam.org/ns/1.0/";
+
+/// A photo's pick flag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Pick {
+    Picked,
+    Rejected,
+}
+
+/// Metadata read from an XMP packet.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct XmpData {
+    /// xmp:Rating (0-5; rejected photos (-1) are a pick flag instead)
+    pub rating: Option<u8>,
+    /// digiKam:PickLabel, or rejected from an xmp:Rating of -1
+    pub pick: Option<Pick>,
+    /// xmp:Label, the color label (e.g., "Red")
+    pub label: Option<String>,
+    /// dc:title
+    pub title: Option<String>,
+    /// dc:description
+    pub description: Option<String>,
+    /// dc:creator (first entry)
+    pub creator: Option<String>,
+    /// dc:subject
+    pub keywords: Vec<String>,
+    /// dc:rights
+    pub copyright: Option<String>,
+    /// exif:DateTimeOriginal or photoshop:DateCreated, in EXIF format
+    pub date_taken: Option<String>,
+    /// exif:GPSLatitude/GPSLongitude as signed decimal degrees
+    pub gps: Option<(f64, f64)>,
+    /// exif:GPSAltitude in meters, negative below sea level
+    pub altitude: Option<f64>,
+    /// exif:GPSImgDirection in degrees
+    pub direction: Option<f64>,
+}
+
+impl XmpData {
+    /// Fill fields missing from `self` with values from `other`.
+    pub fn or(self, other: XmpData) -> XmpData {
+        XmpData {
+            rating: self.rating.or(other.rating),
+            pick: self.pick.or(other.pick),
+            label: self.label.or(other.label),
+            title: self.title.or(other.title),
+            description: self.description.or(other.description),
+            creator: self.creator.or(other.creator),
+            keywords: if self.keywords.is_empty() {
+                other.keywords
+            } else {
+                self.keywords
+            },
+            copyright: self.copyright.or(other.copyright),
+            date_taken: self.date_taken.or(other.date_taken),
+            gps: self.gps.or(other.gps),
+            altitude: self.altitude.or(other.altitude),
+            direction: self.direction.or(other.direction),
+        }
+    }
+}
+
+/// Find the sidecar for an image: `photo.jpg.xmp` or `photo.xmp`.
+pub fn sidecar_path(source: &Path) -> Option<PathBuf> {
+    let mut with_ext = source.as_os_str().to_owned();
+    with_ext.push(".xmp");
+
+    [
+        PathBuf::from(with_ext),
+        source.with_extension("xmp"),
+        source.with_extension("XMP"),
+    ]
+    .into_iter()
+    .find(|p| p.is_file())
+}
+
+/// Read and parse the sidecar for an image, if one exists.
+pub fn read_sidecar(source: &Path) -> Option<XmpData> {
+    let path = sidecar_path(source)?;
+    let data = fs::read(&path)
+        .map_err(
+            |e| tracing::warn!(path = %path.display(), error = %e, "failed to read XMP sidecar"),
+        )
+        .ok()?;
+
+    let xmp = parse(&data);
+    if xmp.is_none() {
+        tracing::warn!(path = %path.display(), "failed to parse XMP sidecar");
+    }
+    xmp
+}
+
+/// Parse the XMP packet embedded in image data, if any.
+pub fn read_embedded(data: &[u8]) -> Option<XmpData> {
+    parse(embedded_packet(data)?)
+}
+
+/// Locate the `<?xpacket begin=...?> ... <?xpacket end=...?>` block.
+pub fn embedded_packet(data: &[u8]) -> Option<&[u8]> {
+    embedded_packet_range(data).map(|range| &data[range])
+}
+
+/// Byte range of the embedded XMP packet, if any.
+pub fn embedded_packet_range(data: &[u8]) -> Option<Range<usize>> {
+    let begin = b"<?xpacket begin=";
+    let end = b"<?xpacket end=";
+
+    let start = find(data, begin)?;
+    let end_marker = start + find(&data[start..], end)?;
+    let close = end_marker + find(&data[end_marker..], b"?>")? + 2;
+
+    Some(start..close)
+}
+
+/// Blank out every GPS property in an XMP packet, keeping its length.
+///
+/// `exif:GPSLatitude` and friends are overwritten with spaces, whether
+/// written as elements or as attributes of `rdf:Description`. The XML stays
+/// valid and the packet keeps its size, so nothing around it in the file
+/// moves. Returns whether anything was blanked.
+pub fn strip_gps(packet: &mut [u8]) -> bool {
+    let is_name_byte = |b: u8| b.is_ascii_alphanumeric() || matches!(b, b'_' | b'-' | b'.');
+
+    let mut stripped = false;
+    let mut pos = 0;
+    while let Some(found) = find(&packet[pos..], b":GPS") {
+        let colon = pos + found;
+        let mut name_start = colon;
+        while name_start > 0 && is_name_byte(packet[name_start - 1]) {
+            name_start -= 1;
+        }
+        let mut name_end = colon + 1;
+        while name_end < packet.len() && is_name_byte(packet[name_end]) {
+            name_end += 1;
+        }
+
+        let range = match name_start.checked_sub(1).map(|i| packet[i]) {
+            Some(b'<') => element_range(packet, name_start - 1, name_end),
+            Some(b) if b.is_ascii_whitespace() => attribute_range(packet, name_start, name_end),
+            _ => None,
+        };
+        match range {
+            Some(range) => {
+                pos = range.end;
+                packet[range].fill(b' ');
+                stripped = true;
+            }
+            None => pos = name_end,
+        }
+    }
+    stripped
+}
+
+/// Range of the element opened at `open`, whose name ends at `name_end`.
+fn element_range(packet: &[u8], open: usize, name_end: usize) -> Option<Range<usize>> {
+    let tag_end = name_end + find(&packet[name_end..], b">")? + 1;
+    if packet[tag_end - 2] == b'/' {
+        return Some(open..tag_end);
+    }
+
+    let mut closing = b"</".to_vec();
+    closing.extend_from_slice(&packet[open + 1..name_end]);
+    let close = tag_end + find(&packet[tag_end..], &closing)?;
+    let close_end = close + find(&packet[close..], b">")? + 1;
+    Some(open..close_end)
+}
+
+/// Range of the `name="value"` attribute starting at `start`.
+fn attribute_range(packet: &[u8], start: usize, name_end: usize) -> Option<Range<usize>> {
+    let mut pos = name_end;
+    let skip_space = |pos: &mut usize| {
+        while packet.get(*pos).is_some_and(|b| b.is_ascii_whitespace()) {
+            *pos += 1;
+        }
+    };
+    skip_space(&mut pos);
+    if packet.get(pos) != Some(&b'=') {
+        return None;
+    }
+    pos += 1;
+    skip_space(&mut pos);
+    let quote = *packet.get(pos).filter(|&&b| b == b'"' || b == b'\'')?;
+    let value_end = pos + 1 + packet[pos + 1..].iter().position(|&b| b == quote)?;
+    Some(start..value_end + 1)
+}
+
+/// Position of the first occurrence of `needle` in `haystack`.
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+/// Parse state: GPS halves and the altitude reference are combined once
+/// the whole packet is read.
+#[derive(Debug, Default)]
+struct Parsed {
+    xmp: XmpData,
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+    below_sea_level: bool,
+}
+
+/// A property being read inside `rdf:Description`.
+#[derive(Debug, Default)]
+struct Property {
+    namespace: Vec<u8>,
+    name: Vec<u8>,
+    /// Element depth of the property element
+    depth: usize,
+    values: Vec<String>,
+}
+
+/// Parse an XMP document.
+///
+/// Handles properties written as attributes on `rdf:Description` and as
+/// child elements, including `rdf:Alt`/`rdf:Bag`/`rdf:Seq` lists.
+pub fn parse(xml: &[u8]) -> Option<XmpData> {
+    let mut reader = NsReader::from_reader(xml);
+    reader.config_mut().trim_text(true);
+
+    let mut parsed = Parsed::default();
+    let mut depth = 0;
+    let mut description_depth = None;
+    let mut property: Option<Property> = None;
+    let mut buf = Vec::new();
+
+    loop {
+        let (namespace, event) = reader.read_resolved_event_into(&mut buf).ok()?;
+        let namespace = match namespace {
+            ResolveResult::Bound(ns) => ns.as_ref().to_vec(),
+            _ => Vec::new(),
+        };
+
+        match event {
+            Event::Start(ref e) | Event::Empty(ref e) => {
+                let is_empty = matches!(event, Event::Empty(_));
+                depth += 1;
+                let local = e.local_name().as_ref().to_vec();
+
+                if namespace == NS_RDF && local == b"Description" {
+                    for (ns, name, value) in attributes(&reader, e) {
+                        apply(&mut parsed, &ns, &name, vec![value]);
+                    }
+                    description_depth = Some(depth);
+                } else if property.is_none() && description_depth == Some(depth - 1) {
+                    property = Some(Property {
+                        namespace,
+                        name: local,
+                        depth,
+                        values: Vec::new(),
+                    });
+                }
+
+                if is_empty {
+                    if description_depth == Some(depth) {
+                        description_depth = None;
+                    }
+                    depth -= 1;
+                    close(&mut parsed, &mut property, depth);
+                }
+            }
+            Event::Text(ref t) => {
+                if let Some(property) = property.as_mut() {
+                    property.values.push(t.unescape().ok()?.into_owned());
+                }
+            }
+            Event::End(_) => {
+                if description_depth == Some(depth) {
+                    description_depth = None;
+                }
+                depth -= 1;
+                close(&mut parsed, &mut property, depth);
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    let mut xmp = parsed.xmp;
+    xmp.gps = parsed.latitude.zip(parsed.longitude);
+    if parsed.below_sea_level {
+        xmp.altitude = xmp.altitude.map(|a| -a);
+    }
+    Some(xmp)
+}
+
+/// Collect namespaced attributes of an element as (namespace, name, value).
+fn attributes(reader: &NsReader<&[u8]>, e: &BytesStart) -> Vec<(Vec<u8>, Vec<u8>, String)> {
+    e.attributes()
+        .filter_map(|a| a.ok())
+        .filter_map(|a| {
+            let (ns, name) = reader.resolve_attribute(a.key);
+            let ResolveResult::Bound(ns) = ns else {
+                return None;
+            };
+            let value = a.unescape_value().ok()?.into_owned();
+            Some((ns.as_ref().to_vec(), name.as_ref().to_vec(), value))
+        })
+        .collect()
+}
+
+/// Apply a property once its element closes.
+fn close(parsed: &mut Parsed, property: &mut Option<Property>, depth: usize) {
+    if property.as_ref().is_some_and(|p| depth < p.depth)
+        && let Some(p) = property.take()
+    {
+        apply(parsed, &p.namespace, &p.name, p.values);
+    }
+}
+
+/// Store a property value if it's one galerie uses.
+fn apply(parsed: &mut Parsed, namespace: &[u8], name: &[u8], values: Vec<String>) {
+    let first = || values.first().cloned().filter(|v| !v.is_empty());
+    let xmp = &mut parsed.xmp;
+
+    match (namespace, name) {
+        (NS_XMP, b"Rating") if first().as_deref() == Some("-1") => {
+            xmp.pick.get_or_insert(Pick::Rejected);
+        }
+        (NS_XMP, b"Rating") => xmp.rating = first().and_then(|v| v.parse().ok()),
+        (NS_XMP, b"Label") => xmp.label = first(),
+        (NS_DIGIKAM, b"PickLabel") => {
+            xmp.pick = match first().as_deref() {
+                Some("1") => Some(Pick::Rejected),
+                Some("3") => Some(Pick::Picked),
+                _ => None,
+            }
+        }
+        (NS_DC, b"title") => xmp.title = first(),
+        (NS_DC, b"description") => xmp.description = first(),
+        (NS_DC, b"creator") => xmp.creator = first(),
+        (NS_DC, b"rights") => xmp.copyright = first(),
+        (NS_DC, b"subject") => xmp.keywords = values,
+        (NS_EXIF, b"DateTimeOriginal") => {
+            xmp.date_taken = first().map(|v| to_exif_date(&v));
+        }
+        (NS_PHOTOSHOP, b"DateCreated") if xmp.date_taken.is_none() => {
+            xmp.date_taken = first().map(|v| to_exif_date(&v));
+        }
+        (NS_EXIF, b"GPSLatitude") => parsed.latitude = first().and_then(|v| parse_coordinate(&v)),
+        (NS_EXIF, b"GPSLongitude") => parsed.longitude = first().and_then(|v| parse_coordinate(&v)),
+        (NS_EXIF, b"GPSAltitude") => xmp.altitude = first().and_then(|v| parse_rational(&v)),
+        (NS_EXIF, b"GPSAltitudeRef") => parsed.below_sea_level = first().as_deref() == Some("1"),
+        (NS_EXIF, b"GPSImgDirection") => xmp.direction = first().and_then(|v| parse_rational(&v)),
+        _ => {}
+    }
+}
+
+/// Convert an XMP date (`2024-04-02T06:12:45+09:00`) to EXIF format
+/// (`2024:04:02 06:12:45`) to match dates read from EXIF.
+fn to_exif_date(value: &str) -> String {
+    let (date, time) = value.split_once('T').unwrap_or((value, ""));
+    let date = date.replace('-', ":");
+    let time: String = time.chars().take(8).collect();
+    if time.is_empty() {
+        date
+    } else {
+        format!("{} {}", date, time)
+    }
+}
+
+/// Parse an XMP rational (`4215/10`) or plain number.
+fn parse_rational(value: &str) -> Option<f64> {
+    match value.trim().split_once('/') {
+        Some((num, denom)) => {
+            let denom: f64 = denom.trim().parse().ok()?;
+            (denom != 0.0).then_some(num.trim().parse::<f64>().ok()? / denom)
+        }
+        None => value.trim().parse().ok(),
+    }
+}
+
+/// Parse an XMP GPS coordinate: `DDD,MM.mmk` or `DDD,MM,SSk` where k is N/S/E/W.
+fn parse_coordinate(value: &str) -> Option<f64> {
+    let value = value.trim();
+    let direction = value.chars().last()?;
+    let sign = match direction.to_ascii_uppercase() {
+        'N' | 'E' => 1.0,
+        'S' | 'W' => -1.0,
+        _ => return None,
+    };
+
+    let parts: Vec<f64> = value[..value.len() - 1]
+        .split(',')
+        .map(|p| p.trim().parse().ok())
+        .collect::<Option<_>>()?;
+
+    let decimal = match parts.as_slice() {
+        [degrees, minutes] => degrees + minutes / 60.0,
+        [degrees, minutes, seconds] => degrees + minutes / 60.0 + seconds / 3600.0,
+        _ => return None,
+    };
+
+    Some(sign * decimal)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    const SIDECAR: &str = r#"<x:xmpmeta xmlns:x="adobe:ns:meta/">
+ <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+  <rdf:Description rdf:about=""
+    xmlns:xmp="http://ns.adobe.com/xap/1.0/"
+    xmlns:dc="http://purl.org/dc/elements/1.1/"
+    xmlns:exif="http://ns.adobe.com/exif/1.0/"
+    xmp:Rating="4"
+    exif:DateTimeOriginal="2024-04-02T06:12:45.00+09:00"
+    exif:GPSLatitude="35,40.572N"
+    exif:GPSLongitude="139,39.018E"
+    exif:GPSAltitude="4215/10"
+    exif:GPSAltitudeRef="1"
+    exif:GPSImgDirection="27050/100">
+   <dc:title>
+    <rdf:Alt>
+     <rdf:li xml:lang="x-default">Tokyo &amp; Tower</rdf:li>
+    </rdf:Alt>
+   </dc:title>
+   <dc:creator>
+    <rdf:Seq>
+     <rdf:li>Aiko Tanaka</rdf:li>
+    </rdf:Seq>
+   </dc:creator>
+   <dc:subject>
+    <rdf:Bag>
+     <rdf:li>japan</rdf:li>
+     <rdf:li>night</rdf:li>
+    </rdf:Bag>
+   </dc:subject>
+  </rdf:Description>
+ </rdf:RDF>
+</x:xmpmeta>"#;
+
+    #[test]
+    fn parses_attributes_and_lists() {
+        let xmp = parse(SIDECAR.as_bytes()).unwrap();
+
+        assert_eq!(xmp.rating, Some(4));
+        assert_eq!(xmp.title.as_deref(), Some("Tokyo & Tower"));
+        assert_eq!(xmp.keywords, vec!["japan", "night"]);
+        assert_eq!(xmp.creator.as_deref(), Some("Aiko Tanaka"));
+        assert_eq!(xmp.date_taken.as_deref(), Some("2024:04:02 06:12:45"));
+
+        let (lat, lon) = xmp.gps.unwrap();
+        assert!((lat - 35.6762).abs() < 0.0001);
+        assert!((lon - 139.6503).abs() < 0.0001);
+        assert_eq!(xmp.altitude, Some(-421.5));
+        assert_eq!(xmp.direction, Some(270.5));
+    }
+
+    #[test]
+    fn parses_element_properties() {
+        let xml = r#"<rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+            <rdf:Description xmlns:xmp="http://ns.adobe.com/xap/1.0/">
+                <xmp:Rating>2</xmp:Rating>
+            </rdf:Description></rdf:RDF>"#;
+
+        assert_eq!(parse(xml.as_bytes()).unwrap().rating, Some(2));
+    }
+
+    #[test_case(r#"xmp:Rating="-1""#, None, Some(Pick::Rejected) ; "rejected rating")]
+    #[test_case(r#"digiKam:PickLabel="3" xmp:Rating="5""#, Some(5), Some(Pick::Picked) ; "digikam pick")]
+    #[test_case(r#"digiKam:PickLabel="1""#, None, Some(Pick::Rejected) ; "digikam reject")]
+    #[test_case(r#"digiKam:PickLabel="2""#, None, None ; "digikam pending")]
+    fn parses_picks(attributes: &str, rating: Option<u8>, pick: Option<Pick>) {
+        let xml = format!(
+            r#"<rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+            <rdf:Description xmlns:xmp="http://ns.adobe.com/xap/1.0/"
+                xmlns:digiKam="http://www.digikam.org/ns/1.0/"
+                xmp:Label="Green" {}/></rdf:RDF>"#,
+            attributes
+        );
+        let xmp = parse(xml.as_bytes()).unwrap();
+
+        assert_eq!(xmp.rating, rating);
+        assert_eq!(xmp.pick, pick);
+        assert_eq!(xmp.label.as_deref(), Some("Green"));
+    }
+
+    #[test]
+    fn half_gps_is_dropped() {
+        let xml = r#"<rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+            <rdf:Description xmlns:exif="http://ns.adobe.com/exif/1.0/"
+                xmlns:xmp="http://ns.adobe.com/xap/1.0/"
+                exif:GPSLatitude="35,40.5N" xmp:Rating="1"/></rdf:RDF>"#;
+
+        assert_eq!(parse(xml.as_bytes()).unwrap().gps, None);
+    }
+
+    #[test]
+    fn finds_embedded_packet() {
+        let mut data = b"\xff\xd8 junk ".to_vec();
+        data.extend_from_slice(b"<?xpacket begin=\"\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>");
+        data.extend_from_slice(SIDECAR.as_bytes());
+        data.extend_from_slice(b"<?xpacket end=\"w\"?> trailing");
+
+        let xmp = read_embedded(&data).unwrap();
+
+        assert_eq!(xmp.rating, Some(4));
+    }
+
+    #[test]
+    fn sidecar_takes_priority() {
+        let sidecar = XmpData {
+            rating: Some(5),
+            ..Default::default()
+        };
+        let embedded = XmpData {
+            rating: Some(1),
+            title: Some("embedded".to_string()),
+            ..Default::default()
+        };
+
+        let merged = sidecar.or(embedded);
+
+        assert_eq!(merged.rating, Some(5));
+        assert_eq!(merged.title.as_deref(), Some("embedded"));
+    }
+
+    #[test]
+    fn finds_sidecar_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let photo = dir.path().join("IMG_0001.jpg");
+        fs::write(&photo, b"").unwrap();
+        assert_eq!(sidecar_path(&photo), None);
+
+        fs::write(dir.path().join("IMG_0001.xmp"), SIDECAR).unwrap();
+        assert_eq!(sidecar_path(&photo), Some(dir.path().join("IMG_0001.xmp")));
+
+        fs::write(dir.path().join("IMG_0001.jpg.xmp"), SIDECAR).unwrap();
+        assert_eq!(
+            sidecar_path(&photo),
+            Some(dir.path().join("IMG_0001.jpg.xmp"))
+        );
+
+        assert_eq!(read_sidecar(&photo).unwrap().rating, Some(4));
+    }
+
+    #[test_case("2024-04-02T06:12:45+09:00", "2024:04:02 06:12:45" ; "with zone")]
+    #[test_case("2024-04-02", "2024:04:02" ; "date only")]
+    fn test_to_exif_date(input: &str, expected: &str) {
+        assert_eq!(to_exif_date(input), expected);
+    }
+
+    #[test_case("4215/10", Some(421.5) ; "rational")]
+    #[test_case("90", Some(90.0) ; "plain number")]
+    #[test_case("1/0", None ; "zero denominator")]
+    fn test_parse_rational(input: &str, expected: Option<f64>) {
+        assert_eq!(parse_rational(input), expected);
+    }
+
+    #[test_case("35,40.5N", Some(35.675) ; "degrees minutes")]
+    #[test_case("21,56,33W", Some(-(21.0 + 56.0 / 60.0 + 33.0 / 3600.0)) ; "degrees minutes seconds")]
+    #[test_case("35.5", None ; "no direction")]
+    #[test_case("x,yN", None ; "garbage")]
+    fn test_parse_coordinate(input: &str, expected: Option<f64>) {
+        match (parse_coordinate(input), expected) {
+            (Some(actual), Some(expected)) => assert!((actual - expected).abs() < 1e-9),
+            (actual, expected) => assert_eq!(actual, expected),
+        }
+    }
+
+    #[test]
+    fn strip_gps_blanks_attributes_and_elements() {
+        let mut bytes = SIDECAR
+            .replace(
+                "   <dc:title>",
+                "   <exif:GPSDestLatitude>35,40.5N</exif:GPSDestLatitude>\n   <exif:GPSVersionID/>\n   <dc:title>",
+            )
+            .into_bytes();
+        let len = bytes.len();
+
+        assert!(strip_gps(&mut bytes));
+
+        let text = String::from_utf8(bytes).unwrap();
+        assert_eq!(text.len(), len);
+        assert!(!text.contains("GPS"));
+        assert!(!text.contains("35,40"));
+
+        let xmp = parse(text.as_bytes()).unwrap();
+        assert_eq!(xmp.gps, None);
+        assert_eq!(xmp.rating, Some(4));
+        assert_eq!(xmp.title.as_deref(), Some("Tokyo & Tower"));
+    }
+
+    #[test]
+    fn strip_gps_without_gps_changes_nothing() {
+        let mut bytes = b"<rdf:Description xmp:Rating=\"4\"/>".to_vec();
+
+        assert!(!strip_gps(&mut bytes));
+        assert_eq!(bytes, b"<rdf:Description xmp:Rating=\"4\"/>");
+    }
+}
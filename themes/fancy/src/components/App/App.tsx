@@ -9,7 +9,7 @@ import { Header } from '../Header';
 import { Grid } from '../Grid';
 import { Footer } from '../Footer';
 import { Viewer } from '../Viewer';
-import type { GalleryData } from '../../types';
+import type { GalleryData, Photo, VariantPaths } from '../../types';
 
 // Data loading
 async function loadGalleryData(): Promise<GalleryData> {
@@ -39,6 +39,57 @@ async function loadGalleryData(): Promise<GalleryData> {
   return data;
 }
 
+// Whether the browser asks for reduced data usage (Save-Data)
+function prefersSaveData(): boolean {
+  const connection = (navigator as Navigator & { connection?: { saveData?: boolean } })
+    .connection;
+  return connection?.saveData === true;
+}
+
+// 1x1 AVIF, decoded to check browser support
+const AVIF_PROBE =
+  'data:image/avif;base64,AAAAGGZ0eXBhdmlmAAAAAG1pZjFtaWFmAAAA0m1ldGEAAAAAAAAAIWhkbHIAAAAAAAAAAHBpY3QAAAAAAAAAAAAAAAAAAAAADnBpdG0AAAAAAAEAAAAeaWxvYwAAAABEAAABAAEAAAABAAAA8gAAACwAAAAjaWluZgAAAAAAAQAAABVpbmZlAgAAAAABAABhdjAxAAAAAFZpcHJwAAAAOGlwY28AAAAUaXNwZQAAAAAAAAABAAAAAQAAAAxhdjFDgT8AAAAAABBwaXhpAAAAAAMICAgAAAAWaXBtYQAAAAAAAAABAAEDAYIDAAAANG1kYXQSAAoHP8ACEBDQbTIfZfI/QR8V+MAAIAAQAAAAAAAAAAAAI+Ktb7Qf5+XGiA==';
+
+// Whether the browser can display AVIF
+function supportsAvif(): Promise<boolean> {
+  return new Promise((resolve) => {
+    const img = new Image();
+    img.onload = () => resolve(img.width > 0);
+    img.onerror = () => resolve(false);
+    img.src = AVIF_PROBE;
+  });
+}
+
+// Swap in alternate variants where the build generated them
+function applyVariants(
+  photos: Photo[],
+  pick: (photo: Photo) => VariantPaths | null | undefined
+): Photo[] {
+  return photos.map((photo) => {
+    const variants = pick(photo);
+    return variants
+      ? {
+          ...photo,
+          imagePath: variants.imagePath,
+          thumbPath: variants.thumbPath,
+          // The double-size thumbnail would be picked over the alternate one
+          thumb2xPath: null,
+        }
+      : photo;
+  });
+}
+
+// Data saver variants when the visitor asks for them, otherwise AVIF where supported
+async function choosePhotoVariants(photos: Photo[]): Promise<Photo[]> {
+  if (prefersSaveData()) {
+    return applyVariants(photos, (photo) => photo.dataSaver);
+  }
+  if (photos.some((photo) => photo.avif) && (await supportsAvif())) {
+    return applyVariants(photos, (photo) => photo.avif);
+  }
+  return photos;
+}
+
 function AppContent() {
   // Initialize hooks
   useHashRouter();
@@ -63,8 +114,9 @@ export function App() {
     document.body.classList.add('loading');
 
     loadGalleryData()
-      .then((data) => {
-        setGalleryData(data.photos, data.albums, data.site);
+      .then(async (data) => {
+        const photos = await choosePhotoVariants(data.photos);
+        setGalleryData(photos, data.albums, data.site);
         document.body.classList.remove('loading');
         setLoading(false);
       })
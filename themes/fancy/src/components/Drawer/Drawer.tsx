@@ -126,12 +126,14 @@ export function Drawer() {
           </MetaSection>
         )}
 
-        {/* Download link */}
-        <DownloadLink
-          href={photo.originalPath}
-          size={photo.originalSize}
-          label={t('action.download')}
-        />
+        {/* Download link (absent when originals aren't published) */}
+        {photo.originalPath && (
+          <DownloadLink
+            href={photo.originalPath}
+            size={photo.originalSize}
+            label={t('action.download')}
+          />
+        )}
       </div>
     </aside>
   );
@@ -30,14 +30,21 @@ function PhotoTileInner({ photo, onClick, sortKey }: PhotoTileProps) {
     >
       <img
         src={photo.thumbPath}
+        srcSet={photo.thumb2xPath ? `${photo.thumbPath} 1x, ${photo.thumb2xPath} 2x` : undefined}
         alt={photo.stem}
         loading="lazy"
         decoding="async"
-        style={
-          photo.width && photo.height
+        style={{
+          ...(photo.width && photo.height
             ? { aspectRatio: `${photo.width} / ${photo.height}` }
-            : undefined
-        }
+            : {}),
+          // Blurry preview, or the dominant color, shown until the thumbnail loads
+          ...(photo.placeholder
+            ? { background: `url(${photo.placeholder}) center / cover` }
+            : photo.color
+              ? { background: photo.color }
+              : {}),
+        }}
       />
     </div>
   );
@@ -28,6 +28,9 @@ const translationCache: Record<string, Translations> = {};
 const RTL_LANGUAGES = new Set(['ar', 'he']);
 
 function getStoredLang(): string {
+  // Language alternate links point at ?lang=
+  const requested = new URLSearchParams(window.location.search).get('lang');
+  if (requested && I18N_URLS[requested]) return requested;
   try {
     return localStorage.getItem('lang') || detectLangFromBrowser();
   } catch {
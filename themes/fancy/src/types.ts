@@ -12,20 +12,48 @@ export interface ThemeConfig {
   [key: string]: unknown; // Allow arbitrary theme-specific keys
 }
 
+export type MediaType = 'photo' | 'video';
+
 export interface Photo {
   stem: string;
   hash: string;
+  /** 'video' clips have a poster frame as their images */
+  mediaType: MediaType;
+  /** Animated GIF or WebP; imagePath and thumbPath are animated WebP */
+  animated: boolean;
   width: number;
   height: number;
   originalSize: number;
   imagePath: string;
   thumbPath: string;
+  /** Thumbnail at twice the size (with [processing] thumb_2x) */
+  thumb2xPath?: string | null;
   microThumbPath: string;
-  originalPath: string;
+  originalPath: string | null;
+  videoPath: string | null;
+  dataSaver?: VariantPaths | null;
+  avif?: VariantPaths | null;
+  srcset?: string | null;
+  widths?: WidthPath[];
+  placeholder?: string | null;
+  color?: string | null;
   htmlPath: string;
   metadata: PhotoMetadata;
 }
 
+// Alternate variants: smaller, more compressed ones (with data_saver enabled)
+// or AVIF encodes (with [avif] configured)
+export interface VariantPaths {
+  imagePath: string;
+  thumbPath: string;
+}
+
+// Responsive image (with [processing] widths configured)
+export interface WidthPath {
+  width: number;
+  path: string;
+}
+
 export interface PhotoMetadata {
   dateTaken?: string;
   camera?: string;
@@ -34,6 +62,8 @@ export interface PhotoMetadata {
   gps?: GpsData;
   exposure?: ExposureData;
   rating?: number;
+  pick?: 'picked' | 'rejected';
+  label?: string;
 }
 
 export interface GpsData {
@@ -60,6 +90,23 @@ export interface Album {
   slug: string;
   path: string;
   photoCount: number;
+  stats: AlbumStats;
+}
+
+// Summary of an album's photos, including nested albums
+export interface AlbumStats {
+  firstDate: string | null;
+  lastDate: string | null;
+  cameras: string[];
+  totalSize: number;
+  countries: AlbumCountry[];
+}
+
+export interface AlbumCountry {
+  code: string;
+  name: string | null;
+  flag: string | null;
+  photoCount: number;
 }
 
 export interface SiteInfo {